@@ -13,7 +13,7 @@ stacks_address=$(source $dir/get_credentials.sh | jq -r '.credentials["1"].stack
 
 amount=$((RANDOM%9000+1000))
 
-json=$($dir/../sbtc/bin/sbtc deposit \
+json=$($dir/../sbtc/bin/sbtc deposit --json \
     -w $btc_wif \
     -n regtest \
     -r $stacks_address \
@@ -32,4 +32,4 @@ tx=$(echo -n $json | jq -r .hex)
 
 echo $tx
 
-$dir/../sbtc/bin/sbtc broadcast electrs:60401 $tx | jq -r .
+$dir/../sbtc/bin/sbtc broadcast --json electrs:60401 $tx | jq -r .txid
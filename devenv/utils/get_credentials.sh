@@ -6,4 +6,4 @@ dir="$(dirname "$0")"
 
 mnemonic="twice kind fence tip hidden tilt action fragile skin nothing glory cousin green tomorrow spring wrist shed math olympic multiply hip blue scout claw"
 
-$dir/../sbtc/bin/sbtc generate-from -s testnet -b regtest --accounts 2 mnemonic "$mnemonic"
+$dir/../sbtc/bin/sbtc generate-from --json -s testnet -b regtest --accounts 2 mnemonic "$mnemonic"
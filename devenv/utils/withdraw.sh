@@ -18,7 +18,7 @@ stacks_wif=$(source $dir/get_credentials.sh | jq -r '.credentials["1"].stacks.wi
 amount=$((RANDOM%1000+1000))
 fulfillment_fee=$((RANDOM%1000+1000))
 
-json=$($dir/../sbtc/bin/sbtc withdraw \
+json=$($dir/../sbtc/bin/sbtc withdraw --json \
     -w $btc_wif \
     -n regtest \
     -d $stacks_wif \
@@ -36,4 +36,4 @@ fi
 
 tx=$(echo -n $json | jq -r .hex)
 
-$dir/../sbtc/bin/sbtc broadcast electrs:60401 $tx | jq -r .
+$dir/../sbtc/bin/sbtc broadcast --json electrs:60401 $tx | jq -r .txid
@@ -0,0 +1,297 @@
+//! Operator overrides of the deposits and withdrawals of a running daemon.
+//! They are requested over a Unix socket of the state directory, so that only
+//! the users who can write to the state can intervene, and every request is
+//! recorded in an audit log next to it.
+
+use std::{path::Path, time::SystemTime};
+
+use anyhow::anyhow;
+use bdk::bitcoin::Txid as BitcoinTxId;
+use serde::{Deserialize, Serialize};
+use tokio::{
+	fs::OpenOptions,
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::{UnixListener, UnixStream},
+	sync::{mpsc, oneshot},
+};
+use tracing::{info, warn};
+
+use crate::{config::Config, event::OperationOverride};
+
+/// File name of the admin socket within the state directory
+pub const ADMIN_SOCKET_FILE: &str = "admin.sock";
+
+/// File name of the audit log within the state directory
+pub const AUDIT_LOG_FILE: &str = "audit.ndjson";
+
+/// Override of the operation of a Bitcoin transaction, sent as a JSON line
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OverrideRequest {
+	/// Txid of the Bitcoin request of the deposit or withdrawal
+	pub txid: BitcoinTxId,
+	/// What to do with the operation
+	pub action: OperationOverride,
+}
+
+/// Answer to an override request, sent as a JSON line
+#[derive(Debug, Serialize, Deserialize)]
+struct OverrideResponse {
+	/// Why the override was refused, if it was
+	error: Option<String>,
+}
+
+/// Entry of the audit log
+#[derive(Debug, Serialize)]
+struct AuditEntry {
+	time: String,
+	#[serde(flatten)]
+	request: OverrideRequest,
+	applied: bool,
+	error: Option<String>,
+}
+
+/// Override request waiting for the run loop, which answers it once the
+/// override is applied or refused
+#[derive(Debug)]
+pub struct PendingOverride {
+	/// The override to apply
+	pub request: OverrideRequest,
+	reply: oneshot::Sender<anyhow::Result<()>>,
+}
+
+impl PendingOverride {
+	/// Record the applied override in the audit log of the state directory
+	/// and answer the operator
+	pub async fn accept(self, state_directory: &Path) {
+		info!(
+			"Applied the {:?} override of operation {}",
+			self.request.action, self.request.txid
+		);
+
+		self.answer(state_directory, Ok(())).await
+	}
+
+	/// Record the refused override in the audit log of the state directory
+	/// and answer the operator with the reason
+	pub async fn refuse(self, state_directory: &Path, err: anyhow::Error) {
+		warn!(
+			"Refused the {:?} override of operation {}: {}",
+			self.request.action, self.request.txid, err
+		);
+
+		self.answer(state_directory, Err(err)).await
+	}
+
+	async fn answer(self, state_directory: &Path, result: anyhow::Result<()>) {
+		let entry = AuditEntry {
+			time: humantime::format_rfc3339_seconds(SystemTime::now())
+				.to_string(),
+			request: self.request,
+			applied: result.is_ok(),
+			error: result.as_ref().err().map(ToString::to_string),
+		};
+
+		if let Err(err) = audit(state_directory, &entry).await {
+			warn!("Unable to write to the audit log: {}", err);
+		}
+
+		// The operator may have stopped waiting
+		let _ = self.reply.send(result);
+	}
+}
+
+/// Listen on the admin socket of the state directory in the background,
+/// passing the override requests to the run loop. Must be called within a
+/// Tokio runtime.
+pub fn serve(
+	state_directory: &Path,
+	overrides: mpsc::Sender<PendingOverride>,
+) -> anyhow::Result<()> {
+	let path = state_directory.join(ADMIN_SOCKET_FILE);
+
+	// The socket of a previous run would make the bind fail
+	if path.exists() {
+		std::fs::remove_file(&path)?;
+	}
+
+	let listener = UnixListener::bind(&path)?;
+
+	tokio::spawn(async move {
+		info!("Listening to operator overrides at {:?}", path);
+
+		loop {
+			match listener.accept().await {
+				Ok((stream, _)) => {
+					tokio::spawn(handle(stream, overrides.clone()));
+				}
+				Err(err) => warn!("Admin socket failed: {}", err),
+			}
+		}
+	});
+
+	Ok(())
+}
+
+async fn handle(stream: UnixStream, overrides: mpsc::Sender<PendingOverride>) {
+	let (reader, mut writer) = stream.into_split();
+	let mut line = String::new();
+
+	let result = match BufReader::new(reader).read_line(&mut line).await {
+		Ok(_) => match serde_json::from_str(&line) {
+			Ok(request) => request_override(&overrides, request).await,
+			Err(err) => Err(anyhow!("Invalid override request: {}", err)),
+		},
+		Err(err) => Err(err.into()),
+	};
+
+	let response = OverrideResponse {
+		error: result.err().map(|err| err.to_string()),
+	};
+	let mut bytes =
+		serde_json::to_vec(&response).expect("Cannot serialize response");
+	bytes.push(b'\n');
+
+	if let Err(err) = writer.write_all(&bytes).await {
+		warn!("Unable to answer an override request: {}", err);
+	}
+}
+
+async fn request_override(
+	overrides: &mpsc::Sender<PendingOverride>,
+	request: OverrideRequest,
+) -> anyhow::Result<()> {
+	let (reply, result) = oneshot::channel();
+
+	overrides
+		.send(PendingOverride { request, reply })
+		.await
+		.map_err(|_| anyhow!("The run loop has stopped"))?;
+
+	result
+		.await
+		.map_err(|_| anyhow!("The run loop has stopped"))?
+}
+
+async fn audit(
+	state_directory: &Path,
+	entry: &AuditEntry,
+) -> anyhow::Result<()> {
+	let mut bytes = serde_json::to_vec(entry)?;
+	bytes.push(b'\n');
+
+	let mut file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(state_directory.join(AUDIT_LOG_FILE))
+		.await?;
+
+	file.write_all(&bytes).await?;
+	file.sync_data().await?;
+
+	Ok(())
+}
+
+/// Run a `romeo op` command against the running daemon of the state
+/// directory of the contract, the main one by default
+pub async fn run_command(
+	config: Config,
+	action: OperationOverride,
+	txid: BitcoinTxId,
+	contract: Option<String>,
+) -> anyhow::Result<()> {
+	let state_directory = match contract {
+		Some(contract) => config.state_directory.join(contract),
+		None => config.state_directory,
+	};
+
+	request(&state_directory, OverrideRequest { txid, action }).await?;
+
+	println!("Applied the {:?} override of operation {}", action, txid);
+
+	Ok(())
+}
+
+/// Send the override request to the daemon of the state directory and wait
+/// for it to be applied
+pub async fn request(
+	state_directory: &Path,
+	request: OverrideRequest,
+) -> anyhow::Result<()> {
+	let path = state_directory.join(ADMIN_SOCKET_FILE);
+
+	let stream = UnixStream::connect(&path).await.map_err(|err| {
+		anyhow!("Unable to reach the daemon at {:?}: {}", path, err)
+	})?;
+	let (reader, mut writer) = stream.into_split();
+
+	let mut bytes = serde_json::to_vec(&request)?;
+	bytes.push(b'\n');
+	writer.write_all(&bytes).await?;
+
+	let mut line = String::new();
+	BufReader::new(reader).read_line(&mut line).await?;
+
+	let response: OverrideResponse = serde_json::from_str(&line)?;
+
+	match response.error {
+		Some(err) => Err(anyhow!("Override refused: {}", err)),
+		None => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::hashes::Hash;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_overrides_are_answered_and_audited() {
+		let dir = std::env::temp_dir()
+			.join(format!("romeo-admin-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let (tx, mut rx) = mpsc::channel(1);
+		serve(&dir, tx).unwrap();
+
+		// Stands in for the run loop, which only knows of the null txid
+		let run_loop_dir = dir.clone();
+		tokio::spawn(async move {
+			while let Some(pending) = rx.recv().await {
+				if pending.request.txid == BitcoinTxId::all_zeros() {
+					pending.accept(&run_loop_dir).await;
+				} else {
+					pending
+						.refuse(&run_loop_dir, anyhow!("Unknown operation"))
+						.await;
+				}
+			}
+		});
+
+		let known = OverrideRequest {
+			txid: BitcoinTxId::all_zeros(),
+			action: OperationOverride::Requeue,
+		};
+		let unknown = OverrideRequest {
+			txid: BitcoinTxId::from_inner([1; 32]),
+			action: OperationOverride::Abandon,
+		};
+
+		request(&dir, known).await.unwrap();
+		assert!(request(&dir, unknown).await.is_err());
+
+		let audit_log =
+			std::fs::read_to_string(dir.join(AUDIT_LOG_FILE)).unwrap();
+		let entries: Vec<serde_json::Value> = audit_log
+			.lines()
+			.map(|line| serde_json::from_str(line).unwrap())
+			.collect();
+
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0]["applied"], true);
+		assert_eq!(entries[1]["applied"], false);
+		assert_eq!(entries[1]["action"], "Abandon");
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,317 @@
+//! HTTP health and status API of the daemon, for load balancers and
+//! alerting, and registration of deposit addresses
+
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+
+use bdk::bitcoin::Address as BitcoinAddress;
+use blockstack_lib::vm::types::PrincipalData;
+use hyper::{
+	header::CONTENT_TYPE,
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::timeout};
+use tracing::{info, warn};
+
+use crate::{
+	bitcoin_client::BitcoinBackend,
+	config::Config,
+	deposit_registry::{DepositAddress, DepositRegistry},
+	stacks_client::{StacksBackend, StacksClient},
+	state::{PendingOperations, State},
+};
+
+/// Time after which an unresponsive node is reported as unreachable
+const NODE_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Progress of the state, published by the run loop after every update
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProcessingStatus {
+	/// Whether deposits and withdrawals are being processed
+	pub initialized: bool,
+	/// Height of the last processed Stacks block
+	pub stacks_block_height: Option<u32>,
+	/// Height of the last processed Bitcoin block
+	pub bitcoin_block_height: Option<u32>,
+	/// Deposits and withdrawals whose processing is not complete
+	pub pending_operations: PendingOperations,
+}
+
+impl From<&State> for ProcessingStatus {
+	fn from(state: &State) -> Self {
+		let block_heights = state.block_heights();
+
+		Self {
+			initialized: state.is_initialized(),
+			stacks_block_height: block_heights.map(|(stacks, _)| stacks),
+			bitcoin_block_height: block_heights.map(|(_, bitcoin)| bitcoin),
+			pending_operations: state.pending_operations(),
+		}
+	}
+}
+
+/// Connectivity of a node
+#[derive(Debug, Clone, Serialize)]
+struct NodeStatus {
+	reachable: bool,
+	/// Height of the chain tip of the node
+	tip_height: Option<u32>,
+	error: Option<String>,
+}
+
+impl From<anyhow::Result<u32>> for NodeStatus {
+	fn from(res: anyhow::Result<u32>) -> Self {
+		match res {
+			Ok(height) => Self {
+				reachable: true,
+				tip_height: Some(height),
+				error: None,
+			},
+			Err(err) => Self {
+				reachable: false,
+				tip_height: None,
+				error: Some(err.to_string()),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct StatusReport {
+	version: &'static str,
+	ready: bool,
+	bitcoin: NodeStatus,
+	stacks: NodeStatus,
+	#[serde(flatten)]
+	processing: ProcessingStatus,
+}
+
+/// Request body of a deposit address registration
+#[derive(Debug, Deserialize)]
+struct RegistrationRequest {
+	recipient: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DepositAddressResponse {
+	index: u32,
+	address: String,
+	recipient: String,
+}
+
+impl From<DepositAddress> for DepositAddressResponse {
+	fn from(deposit_address: DepositAddress) -> Self {
+		Self {
+			index: deposit_address.index,
+			address: deposit_address.address.to_string(),
+			recipient: deposit_address.recipient.to_string(),
+		}
+	}
+}
+
+struct Context {
+	bitcoin_client: Arc<dyn BitcoinBackend>,
+	stacks_client: StacksClient,
+	processing: watch::Receiver<ProcessingStatus>,
+	deposit_registry: Arc<DepositRegistry>,
+}
+
+impl Context {
+	async fn report(&self) -> StatusReport {
+		let (bitcoin, stacks) = futures::join!(
+			probe(self.bitcoin_client.get_height()),
+			probe(self.stacks_client.get_tip_height()),
+		);
+		let processing = self.processing.borrow().clone();
+
+		StatusReport {
+			version: env!("CARGO_PKG_VERSION"),
+			ready: processing.initialized
+				&& bitcoin.reachable
+				&& stacks.reachable,
+			bitcoin,
+			stacks,
+			processing,
+		}
+	}
+}
+
+/// Serve `/health`, `/ready`, `/status` and `/deposit-addresses` at the
+/// address in the background. Must be called within a Tokio runtime.
+pub fn serve(
+	address: SocketAddr,
+	config: &Config,
+	bitcoin_client: Arc<dyn BitcoinBackend>,
+	processing: watch::Receiver<ProcessingStatus>,
+	deposit_registry: Arc<DepositRegistry>,
+) {
+	// The API has its own Stacks client, so that probes never wait for the
+	// lock held by the tasks
+	let context = Arc::new(Context {
+		bitcoin_client,
+		stacks_client: StacksClient::new(
+			config.clone(),
+			config
+				.outbound
+				.http_client()
+				.expect("Invalid outbound connection settings"),
+		),
+		processing,
+		deposit_registry,
+	});
+
+	let make_service = make_service_fn(move |_| {
+		let context = context.clone();
+
+		async move {
+			Ok::<_, Infallible>(service_fn(move |req| {
+				let context = context.clone();
+
+				async move { Ok::<_, Infallible>(handle(&context, req).await) }
+			}))
+		}
+	});
+
+	tokio::spawn(async move {
+		info!("Serving the status API at {}", address);
+
+		if let Err(err) = Server::bind(&address).serve(make_service).await {
+			warn!("Status API server failed: {}", err);
+		}
+	});
+}
+
+async fn handle(context: &Context, req: Request<Body>) -> Response<Body> {
+	let method = req.method().clone();
+	let path = req.uri().path().to_string();
+
+	match (method, path.as_str()) {
+		(Method::POST, "/deposit-addresses") => {
+			register_deposit_address(context, req).await
+		}
+		(Method::GET, path) if path.starts_with("/deposit-addresses/") => {
+			lookup_deposit_address(
+				context,
+				&path["/deposit-addresses/".len()..],
+			)
+		}
+		(Method::GET, path) => get(context, path).await,
+		(_, "/health" | "/ready" | "/status" | "/deposit-addresses") => {
+			empty(StatusCode::METHOD_NOT_ALLOWED)
+		}
+		(_, path) if path.starts_with("/deposit-addresses/") => {
+			empty(StatusCode::METHOD_NOT_ALLOWED)
+		}
+		_ => empty(StatusCode::NOT_FOUND),
+	}
+}
+
+async fn get(context: &Context, path: &str) -> Response<Body> {
+	match path {
+		// The daemon is alive as long as it answers
+		"/health" => json(
+			StatusCode::OK,
+			&serde_json::json!({
+				"status": "ok",
+				"version": env!("CARGO_PKG_VERSION"),
+			}),
+		),
+		"/ready" => {
+			let report = context.report().await;
+			let status = if report.ready {
+				StatusCode::OK
+			} else {
+				StatusCode::SERVICE_UNAVAILABLE
+			};
+
+			json(status, &serde_json::json!({ "ready": report.ready }))
+		}
+		"/status" => json(StatusCode::OK, &context.report().await),
+		_ => empty(StatusCode::NOT_FOUND),
+	}
+}
+
+/// Register the recipient of the request body, answering with its deposit
+/// address
+async fn register_deposit_address(
+	context: &Context,
+	req: Request<Body>,
+) -> Response<Body> {
+	let recipient = match parse_registration(req).await {
+		Ok(recipient) => recipient,
+		Err(err) => return error(StatusCode::BAD_REQUEST, err),
+	};
+
+	match context.deposit_registry.register(recipient) {
+		Ok(deposit_address) => json(
+			StatusCode::OK,
+			&DepositAddressResponse::from(deposit_address),
+		),
+		Err(err) => {
+			warn!("Could not register deposit address: {}", err);
+			error(StatusCode::INTERNAL_SERVER_ERROR, err)
+		}
+	}
+}
+
+async fn parse_registration(
+	req: Request<Body>,
+) -> anyhow::Result<PrincipalData> {
+	let body = hyper::body::to_bytes(req.into_body()).await?;
+	let request: RegistrationRequest = serde_json::from_slice(&body)?;
+
+	PrincipalData::parse(&request.recipient)
+		.map_err(|err| anyhow::anyhow!("Invalid recipient: {:?}", err))
+}
+
+/// Look up a deposit address by the address itself or by its recipient
+fn lookup_deposit_address(context: &Context, key: &str) -> Response<Body> {
+	let deposit_address = if let Ok(address) = key.parse::<BitcoinAddress>() {
+		context.deposit_registry.by_script(&address.script_pubkey())
+	} else if let Ok(recipient) = PrincipalData::parse(key) {
+		context.deposit_registry.by_recipient(&recipient)
+	} else {
+		return error(
+			StatusCode::BAD_REQUEST,
+			anyhow::anyhow!("Neither an address nor a principal: {}", key),
+		);
+	};
+
+	match deposit_address {
+		Some(deposit_address) => json(
+			StatusCode::OK,
+			&DepositAddressResponse::from(deposit_address),
+		),
+		None => empty(StatusCode::NOT_FOUND),
+	}
+}
+
+async fn probe(
+	tip_height: impl std::future::Future<Output = anyhow::Result<u32>>,
+) -> NodeStatus {
+	match timeout(NODE_PROBE_TIMEOUT, tip_height).await {
+		Ok(res) => res.into(),
+		Err(_) => Err(anyhow::anyhow!("Node did not respond in time")).into(),
+	}
+}
+
+fn json(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+	let body = serde_json::to_vec(body).expect("Cannot serialize response");
+
+	Response::builder()
+		.status(status)
+		.header(CONTENT_TYPE, "application/json")
+		.body(Body::from(body))
+		.unwrap()
+}
+
+fn error(status: StatusCode, err: anyhow::Error) -> Response<Body> {
+	json(status, &serde_json::json!({ "error": err.to_string() }))
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+	let mut res = Response::new(Body::empty());
+	*res.status_mut() = status;
+	res
+}
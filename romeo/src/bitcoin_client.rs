@@ -1,103 +1,472 @@
 //! RPC Bitcoin client
 
 use std::{
-	sync::{Arc, Mutex},
+	collections::HashSet,
+	fmt::Debug,
+	sync::{Arc, Mutex, RwLock, RwLockReadGuard},
 	time::Duration,
 };
 
 use anyhow::anyhow;
+use async_trait::async_trait;
 use bdk::{
-	bitcoin::{Block, PrivateKey, Script, Transaction, Txid},
-	bitcoincore_rpc::{self, Auth, Client as RPCClient, RpcApi},
-	blockchain::{
-		ConfigurableBlockchain, ElectrumBlockchain, ElectrumBlockchainConfig,
+	bitcoin::{
+		psbt::PartiallySignedTransaction, util::bip158::BlockFilter, Address,
+		Block, BlockHash, OutPoint, PrivateKey, Script, Transaction, Txid,
+	},
+	bitcoincore_rpc::{self, Client as RPCClient, RpcApi},
+	blockchain::{ElectrumBlockchain, GetHeight, WalletSync},
+	database::{
+		any::SledDbConfiguration, AnyDatabase, AnyDatabaseConfig,
+		BatchDatabase, ConfigurableDatabase,
 	},
-	database::MemoryDatabase,
 	template::P2TR,
-	SignOptions, SyncOptions, Wallet,
+	wallet::{tx_builder::TxOrdering, AddressIndex},
+	FeeRate, SignOptions, SyncOptions, Wallet,
+};
+use sbtc_core::{
+	operations::op_return::utils::reorder_outputs,
+	signer::frost::{
+		self, sign_taproot_key_spends, RemoteParticipant, ThresholdCoordinator,
+	},
 };
-use sbtc_core::operations::op_return::utils::reorder_outputs;
-use tokio::{task::spawn_blocking, time::sleep};
-use tracing::trace;
+use tokio::{
+	sync::watch,
+	task::{spawn_blocking, JoinHandle},
+	time::{sleep, timeout},
+};
+use tracing::{trace, warn, Span};
 
-use crate::{config::Config, event::TransactionStatus};
+use self::{
+	coin_selection::UtxoControl,
+	electrum_pool::ElectrumPool,
+	rebroadcast::Rebroadcaster,
+	retry::{is_transient_error, is_transient_rpc_error},
+	rpc_pool::RpcPool,
+	zmq::ZmqListener,
+};
+use crate::{
+	config::Config,
+	event::TransactionStatus,
+	metrics::{self, RequestClient},
+};
+
+pub mod coin_selection;
+pub mod electrum_pool;
+pub mod esplora;
+pub mod fee;
+pub mod rebroadcast;
+pub mod retry;
+pub mod rpc_pool;
+pub mod zmq;
 
 const BLOCK_POLLING_INTERVAL: Duration = Duration::from_secs(5);
 
+/// Coordinator of the threshold signing rounds of the peg wallet transactions
+pub(crate) type ThresholdSigner =
+	Arc<Mutex<ThresholdCoordinator<RemoteParticipant>>>;
+
+/// Directory of the peg wallet database within the state directory
+const WALLET_DATABASE_DIRECTORY: &str = "wallet";
+
+/// Name of the peg wallet tree in the database
+const WALLET_DATABASE_TREE: &str = "peg-wallet";
+
+/// Number of idle RPC connections kept open for reuse
+const MAX_IDLE_RPC_CLIENTS: usize = 4;
+
+/// Bitcoin Core RPC error code for miscellaneous errors
+const RPC_MISC_ERROR: i32 = -1;
+
+/// Bitcoin Core RPC error code for invalid, missing or duplicate parameters
+const RPC_INVALID_PARAMETER: i32 = -8;
+
+/// Bitcoin client error type
+#[derive(Debug, thiserror::Error)]
+pub enum BitcoinClientError {
+	/// The node has pruned the requested block
+	#[error("Bitcoin block at height {0} has been pruned by the node")]
+	BlockPruned(u32),
+}
+
+/// Access to the Bitcoin network and the peg wallet. The daemon only talks to
+/// Bitcoin through this trait, so other node APIs, test doubles or remote
+/// signing services can be plugged in.
+#[async_trait]
+pub trait BitcoinBackend: Send + Sync {
+	/// Broadcast a transaction
+	async fn broadcast(&self, tx: Transaction) -> anyhow::Result<()>;
+
+	/// Get transaction status
+	async fn get_tx_status(
+		&self,
+		txid: Txid,
+	) -> anyhow::Result<TransactionStatus>;
+
+	/// Get the block at the height, waiting for it if it is not mined yet
+	async fn get_block(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<(u32, Block)>;
+
+	/// Get the block at the height like `get_block`, but with only its header
+	/// if its compact block filter matches none of the scripts. Backends
+	/// without compact block filters return the full block.
+	async fn get_filtered_block(
+		&self,
+		block_height: u32,
+		_scripts: Vec<Script>,
+	) -> anyhow::Result<(u32, Block)> {
+		self.get_block(block_height).await
+	}
+
+	/// Get the hash of the block at the height in the best chain
+	async fn get_block_hash(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<BlockHash>;
+
+	/// Get current block height
+	async fn get_height(&self) -> anyhow::Result<u32>;
+
+	/// Estimate the fee rate needed to confirm within the target number of
+	/// blocks
+	async fn estimate_fee(&self, target_blocks: u16)
+		-> anyhow::Result<FeeRate>;
+
+	/// Sign and broadcast a transaction paying the outputs from the peg wallet
+	/// at the fee rate
+	async fn sign_and_broadcast(
+		&self,
+		outputs: Vec<(Script, u64)>,
+		fee_rate: FeeRate,
+	) -> anyhow::Result<Txid>;
+
+	/// Replace an unconfirmed peg wallet transaction with one paying the
+	/// higher fee rate, and broadcast it
+	async fn bump_fee(
+		&self,
+		txid: Txid,
+		fee_rate: FeeRate,
+	) -> anyhow::Result<Txid>;
+
+	/// Use the values of a reloaded config
+	fn reload_config(&self, config: &Config) -> anyhow::Result<()>;
+}
+
 /// Bitcoin RPC client
 #[derive(Clone)]
 pub struct Client {
-	config: Config,
-	blockchain: Arc<ElectrumBlockchain>,
+	config: Arc<RwLock<Config>>,
+	rpc: Arc<RpcPool>,
+	electrum: Arc<ElectrumPool>,
 	// required for fulfillment txs
-	wallet: Arc<Mutex<Wallet<MemoryDatabase>>>,
+	wallet: Arc<Mutex<Wallet<AnyDatabase>>>,
+	signer: Option<ThresholdSigner>,
+	utxos: Arc<UtxoControl>,
+	rebroadcaster: Arc<Rebroadcaster>,
+	zmq: Option<Arc<ZmqListener>>,
 }
 
 impl Client {
-	/// Create a new RPC client
+	/// Create a new RPC client. Listening to ZMQ notifications and checking
+	/// the health of fallback Electrum servers, if configured, require a Tokio
+	/// runtime.
 	pub fn new(config: Config) -> anyhow::Result<Self> {
-		let url = config.electrum_node_url.as_str().to_string();
-		let network = config.bitcoin_network;
-		let p2tr_private_key = PrivateKey::from_wif(
-			&config.bitcoin_credentials.wif_p2tr().to_string(),
-		)?;
-
-		let blockchain =
-			ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
-				url,
-				socks5: None,
-				retry: 3,
-				timeout: Some(10),
-				stop_gap: 10,
-				validate_domain: false,
-			})?;
+		let proxy = config.outbound.socks5_proxy()?;
+
+		let electrum = Arc::new(ElectrumPool::new(
+			std::iter::once(config.electrum_node_url.clone())
+				.chain(config.electrum_fallback_node_urls.iter().cloned()),
+			proxy.clone(),
+			!config.outbound.accept_invalid_certs,
+		));
 
-		let wallet = Wallet::new(
-			P2TR(p2tr_private_key),
-			Some(P2TR(p2tr_private_key)),
-			network,
-			MemoryDatabase::default(),
-		)?;
+		if !config.electrum_fallback_node_urls.is_empty() {
+			electrum.spawn_health_checks();
+		}
+
+		let wallet = peg_wallet(&config)?;
+		let signer = threshold_signer(&config)?;
+		let zmq = config
+			.bitcoin_zmq_url
+			.as_ref()
+			.map(|url| Arc::new(ZmqListener::spawn(url)));
 
 		Ok(Self {
-			config,
-			blockchain: Arc::new(blockchain),
+			rpc: Arc::new(RpcPool::new(
+				config.bitcoin_node_url.clone(),
+				config.bitcoin_rpc_auth.clone(),
+				proxy,
+				MAX_IDLE_RPC_CLIENTS,
+			)),
+			utxos: Arc::new(UtxoControl::new(&config.coin_selection)),
+			rebroadcaster: Default::default(),
+			config: Arc::new(RwLock::new(config)),
+			electrum,
 			wallet: Arc::new(Mutex::new(wallet)),
+			signer,
+			zmq,
 		})
 	}
 
+	/// Pins, exclusions and reservations of the peg wallet UTXOs
+	pub fn utxos(&self) -> &UtxoControl {
+		&self.utxos
+	}
+
+	fn config(&self) -> anyhow::Result<RwLockReadGuard<'_, Config>> {
+		self.config
+			.read()
+			.map_err(|_| anyhow!("Cannot get config read lock"))
+	}
+
 	async fn execute<F, T>(
 		&self,
 		f: F,
 	) -> anyhow::Result<bitcoincore_rpc::Result<T>>
 	where
-		F: FnOnce(RPCClient) -> bitcoincore_rpc::Result<T> + Send + 'static,
-		T: Send + 'static,
+		F: Fn(&RPCClient) -> bitcoincore_rpc::Result<T> + Send + Sync + 'static,
+		T: Debug + Send + 'static,
+	{
+		let f = Arc::new(f);
+		let retry_policy = self.config()?.bitcoin_retry.clone();
+
+		let res = retry_policy
+			.retry(
+				|| {
+					let rpc = self.rpc.clone();
+					let f = f.clone();
+
+					async move {
+						spawn_blocking_in_span(move || {
+							rpc.execute(|client| f(client))
+						})
+						.await?
+					}
+				},
+				|res| matches!(res, Ok(Err(err)) if is_transient_rpc_error(err)),
+			)
+			.await;
+
+		metrics::request_finished(
+			RequestClient::BitcoinRpc,
+			matches!(res, Ok(Ok(_))),
+		);
+
+		res
+	}
+
+	/// Runs the blocking operation on the peg wallet, failing over to another
+	/// Electrum server and retrying on transient errors
+	async fn with_wallet<F, T>(&self, f: F) -> anyhow::Result<T>
+	where
+		F: Fn(&Wallet<AnyDatabase>, &ElectrumBlockchain) -> anyhow::Result<T>
+			+ Send
+			+ Sync
+			+ 'static,
+		T: Debug + Send + 'static,
 	{
-		let mut url = self.config.bitcoin_node_url.clone();
+		let f = Arc::new(f);
+		let retry_policy = self.config()?.bitcoin_retry.clone();
+
+		let res = retry_policy
+			.retry(
+				|| {
+					let electrum = self.electrum.clone();
+					let wallet = self.wallet.clone();
+					let f = f.clone();
+
+					async move {
+						spawn_blocking_in_span(move || {
+							let wallet = wallet.lock().map_err(|_| {
+								anyhow!("Cannot get wallet read lock")
+							})?;
+
+							let (url, blockchain) = electrum.blockchain()?;
+							let res = f(&wallet, &blockchain);
+
+							if matches!(&res, Err(err) if is_transient_error(err))
+							{
+								electrum.report_failure(&url);
+							}
+
+							res
+						})
+						.await?
+					}
+				},
+				|res| matches!(res, Err(err) if is_transient_error(err)),
+			)
+			.await;
+
+		metrics::request_finished(RequestClient::Electrum, res.is_ok());
+
+		res
+	}
+
+	/// Broadcast a transaction whose inputs are reserved, releasing them if
+	/// the broadcast fails
+	async fn broadcast_reserved(
+		&self,
+		tx: Transaction,
+	) -> anyhow::Result<Txid> {
+		let res = {
+			let tx = tx.clone();
+			self.execute(move |client| client.send_raw_transaction(&tx))
+				.await
+		};
+
+		match res {
+			Ok(Ok(txid)) => {
+				self.rebroadcaster.track(&tx)?;
+				Ok(txid)
+			}
+			Ok(Err(err)) => {
+				self.utxos.release(&tx)?;
+				Err(err.into())
+			}
+			Err(err) => {
+				self.utxos.release(&tx)?;
+				Err(err)
+			}
+		}
+	}
+
+	/// Resubmits a transaction that dropped out of the mempool, unless a
+	/// conflicting transaction spending one of its inputs is confirmed
+	async fn recover_dropped(
+		&self,
+		txid: Txid,
+	) -> anyhow::Result<TransactionStatus> {
+		let rebroadcaster = self.rebroadcaster.clone();
+
+		let Some(tx) = self
+			.with_wallet(move |wallet, _| rebroadcaster.get(&txid, wallet))
+			.await?
+		else {
+			warn!(
+				"Transaction {} is unknown and cannot be rebroadcasted",
+				txid
+			);
+			return Ok(TransactionStatus::Rejected);
+		};
+
+		for input in &tx.input {
+			if self.is_spent_in_chain(input.previous_output).await? {
+				warn!(
+					"Input {} of transaction {} is spent by a confirmed transaction",
+					input.previous_output, txid
+				);
+				return Ok(TransactionStatus::Rejected);
+			}
+		}
 
-		let username = url.username().to_string();
-		let password = url.password().unwrap_or_default().to_string();
+		warn!(
+			"Transaction {} dropped out of the mempool, rebroadcasting",
+			txid
+		);
+
+		if let Err(err) = self
+			.execute(move |client| client.send_raw_transaction(&tx))
+			.await?
+		{
+			warn!("Unable to rebroadcast transaction {}: {}", txid, err);
+		}
+
+		Ok(TransactionStatus::Broadcasted)
+	}
+
+	async fn is_spent_in_chain(
+		&self,
+		outpoint: OutPoint,
+	) -> anyhow::Result<bool> {
+		let unspent = self
+			.execute(move |client| {
+				client.get_tx_out(&outpoint.txid, outpoint.vout, Some(false))
+			})
+			.await??
+			.is_some();
 
-		if username.is_empty() {
-			return Err(anyhow::anyhow!("Username is empty"));
+		if unspent {
+			return Ok(false);
 		}
 
-		if password.is_empty() {
-			return Err(anyhow::anyhow!("Password is empty"));
+		// Outputs of unconfirmed transactions are missing from the UTXO set too
+		let parent_confirmations = self
+			.execute(move |client| {
+				client.get_raw_transaction_info(&outpoint.txid, None)
+			})
+			.await?
+			.ok()
+			.and_then(|tx| tx.confirmations)
+			.unwrap_or_default();
+
+		Ok(parent_confirmations > 0)
+	}
+
+	/// Get the last unused change address of the peg wallet
+	pub async fn next_change_address(&self) -> anyhow::Result<Address> {
+		self.with_wallet(|wallet, blockchain| {
+			sync_wallet(wallet, blockchain)?;
+
+			change_address(wallet)
+		})
+		.await
+	}
+
+	/// Hash of the block at the height, waiting for it if it is not mined yet
+	async fn wait_for_block_hash(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<BlockHash> {
+		// Subscribe before the first request so no notification is missed
+		let mut blocks = self.zmq.as_ref().map(|zmq| zmq.blocks());
+
+		loop {
+			let res = self
+				.execute(move |client| {
+					client.get_block_hash(block_height as u64)
+				})
+				.await?;
+
+			if let Some(hash) = check_block_response(block_height, res)? {
+				trace!(
+					"Got Bitcoin block hash at height {}: {}",
+					block_height,
+					hash
+				);
+				return Ok(hash);
+			}
+
+			wait_for_block(blocks.as_mut()).await;
 		}
+	}
 
-		url.set_username("").unwrap();
-		url.set_password(None).unwrap();
+	async fn fetch_block(
+		&self,
+		block_height: u32,
+		block_hash: BlockHash,
+	) -> anyhow::Result<(u32, Block)> {
+		let res = self
+			.execute(move |client| client.get_block(&block_hash))
+			.await?;
 
-		let client =
-			RPCClient::new(url.as_ref(), Auth::UserPass(username, password))?;
+		let block =
+			check_block_response(block_height, res)?.ok_or_else(|| {
+				anyhow!("Bitcoin block {} is not available", block_hash)
+			})?;
 
-		Ok(spawn_blocking(move || f(client)).await?)
+		Ok((block_height, block))
 	}
+}
 
+#[async_trait]
+impl BitcoinBackend for Client {
 	/// Broadcast a transaction
-	pub async fn broadcast(&self, tx: Transaction) -> anyhow::Result<()> {
+	async fn broadcast(&self, tx: Transaction) -> anyhow::Result<()> {
+		self.rebroadcaster.track(&tx)?;
+
 		self.execute(move |client| client.send_raw_transaction(&tx))
 			.await??;
 
@@ -105,16 +474,15 @@ impl Client {
 	}
 
 	/// Get transaction status
-	pub async fn get_tx_status(
+	async fn get_tx_status(
 		&self,
 		txid: Txid,
 	) -> anyhow::Result<TransactionStatus> {
-		let is_confirmed = self
+		let confirmations = self
 			.execute(move |client| client.get_raw_transaction_info(&txid, None))
 			.await?
 			.ok()
 			.and_then(|tx| tx.confirmations)
-			.map(|confirmations| confirmations > 0)
 			.unwrap_or_default();
 
 		let in_mempool = self
@@ -122,75 +490,93 @@ impl Client {
 			.await?
 			.is_ok();
 
-		let res = match (is_confirmed, in_mempool) {
-			(true, false) => TransactionStatus::Confirmed,
-			(false, true) => TransactionStatus::Broadcasted,
-			(false, false) => TransactionStatus::Rejected,
-			(true, true) => {
+		let min_confirmations = self.config()?.min_confirmations;
+
+		let res = match (confirmations, in_mempool) {
+			(0, true) => TransactionStatus::Broadcasted,
+			(0, false) => self.recover_dropped(txid).await?,
+			(confirmations, false) => {
+				confirmation_status(confirmations, min_confirmations)
+			}
+			(_, true) => {
 				panic!("Transaction cannot be both confirmed and pending")
 			}
 		};
 
+		if matches!(
+			res,
+			TransactionStatus::Confirmed | TransactionStatus::Rejected
+		) {
+			self.rebroadcaster.forget(&txid)?;
+		}
+
 		tracing::debug!("BTC TX {} IS {:?}", txid, res);
 
 		Ok(res)
 	}
 
 	/// Get block
-	pub async fn get_block(
+	async fn get_block(
 		&self,
 		block_height: u32,
 	) -> anyhow::Result<(u32, Block)> {
-		let block_hash = loop {
-			let res = self
-				.execute(move |client| {
-					client.get_block_hash(block_height as u64)
-				})
-				.await?;
+		let block_hash = self.wait_for_block_hash(block_height).await?;
 
-			match res {
-				Ok(hash) => {
-					trace!(
-						"Got Bitcoin block hash at height {}: {}",
-						block_height,
-						hash
-					);
-					break hash;
-				}
-				Err(bitcoincore_rpc::Error::JsonRpc(
-					bitcoincore_rpc::jsonrpc::Error::Rpc(err),
-				)) => {
-					if err.code == -8 {
-						trace!("Bitcoin block not found, retrying...");
-					} else {
-						Err(anyhow!(
-							"Error fetching Bitcoin block: {:?}",
-							err
-						))?;
-					}
-				}
-				Err(bitcoincore_rpc::Error::JsonRpc(
-					bitcoincore_rpc::jsonrpc::Error::Transport(_),
-				)) => {
-					trace!("Bitcoin client connection error, retrying...");
-				}
-				Err(err) => {
-					Err(anyhow!("Error fetching Bitcoin block: {:?}", err))?
-				}
-			};
-
-			sleep(BLOCK_POLLING_INTERVAL).await;
-		};
+		self.fetch_block(block_height, block_hash).await
+	}
 
-		let block = self
-			.execute(move |client| client.get_block(&block_hash))
+	/// Get block, or only its header if its compact block filter matches none
+	/// of the scripts
+	async fn get_filtered_block(
+		&self,
+		block_height: u32,
+		scripts: Vec<Script>,
+	) -> anyhow::Result<(u32, Block)> {
+		if !self.config()?.bitcoin_block_filters {
+			return self.get_block(block_height).await;
+		}
+
+		let block_hash = self.wait_for_block_hash(block_height).await?;
+
+		let filter = self
+			.execute(move |client| client.get_block_filter(&block_hash))
+			.await??
+			.into_filter();
+
+		if filter_matches(&filter, &block_hash, &scripts)? {
+			return self.fetch_block(block_height, block_hash).await;
+		}
+
+		trace!(
+			"Bitcoin block {} does not match the compact block filter",
+			block_hash
+		);
+
+		let header = self
+			.execute(move |client| client.get_block_header(&block_hash))
 			.await??;
 
-		Ok((block_height, block))
+		Ok((
+			block_height,
+			Block {
+				header,
+				txdata: vec![],
+			},
+		))
+	}
+
+	/// Get block hash
+	async fn get_block_hash(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<BlockHash> {
+		Ok(self
+			.execute(move |client| client.get_block_hash(block_height as u64))
+			.await??)
 	}
 
 	/// Get current block height
-	pub async fn get_height(&self) -> anyhow::Result<u32> {
+	async fn get_height(&self) -> anyhow::Result<u32> {
 		let info = self
 			.execute(|client| client.get_blockchain_info())
 			.await??;
@@ -198,65 +584,553 @@ impl Client {
 		Ok(info.blocks as u32)
 	}
 
+	/// Estimate the fee rate with the node's smart fee estimation
+	async fn estimate_fee(
+		&self,
+		target_blocks: u16,
+	) -> anyhow::Result<FeeRate> {
+		let estimate = self
+			.execute(move |client| {
+				client.estimate_smart_fee(target_blocks, None)
+			})
+			.await??;
+
+		let fee_rate = estimate.fee_rate.ok_or_else(|| {
+			anyhow!(
+				"No fee estimate for {} blocks: {:?}",
+				target_blocks,
+				estimate.errors
+			)
+		})?;
+
+		Ok(FeeRate::from_btc_per_kvb(fee_rate.to_btc() as f32))
+	}
+
 	/// Sign and broadcast a transaction
-	pub async fn sign_and_broadcast(
+	async fn sign_and_broadcast(
 		&self,
 		outputs: Vec<(Script, u64)>,
+		fee_rate: FeeRate,
 	) -> anyhow::Result<Txid> {
 		sleep(Duration::from_secs(3)).await;
 
-		let blockchain = self.blockchain.clone();
-		let wallet = self.wallet.clone();
+		let utxos = self.utxos.clone();
+		let signer = self.signer.clone();
 
-		let tx: Transaction =
-			spawn_blocking::<_, anyhow::Result<Transaction>>(move || {
-				let wallet = wallet
-					.lock()
-					.map_err(|_| anyhow!("Cannot get wallet read lock"))?;
+		let tx = self
+			.with_wallet(move |wallet, blockchain| {
+				build_peg_transaction(
+					wallet,
+					signer.as_ref(),
+					blockchain,
+					&utxos,
+					outputs.clone(),
+					fee_rate,
+				)
+			})
+			.await?;
 
-				wallet.sync(&blockchain, SyncOptions::default())?;
+		self.broadcast_reserved(tx).await
+	}
 
-				let mut tx_builder = wallet.build_tx();
+	/// Bump the fee of a transaction
+	async fn bump_fee(
+		&self,
+		txid: Txid,
+		fee_rate: FeeRate,
+	) -> anyhow::Result<Txid> {
+		let utxos = self.utxos.clone();
+		let signer = self.signer.clone();
 
-				for (script, amount) in outputs.clone() {
-					tx_builder.add_recipient(script, amount);
-				}
+		let tx = self
+			.with_wallet(move |wallet, blockchain| {
+				build_fee_bump_transaction(
+					wallet,
+					signer.as_ref(),
+					blockchain,
+					&utxos,
+					txid,
+					fee_rate,
+				)
+			})
+			.await?;
 
-				let (mut partial_tx, _) = tx_builder.finish()?;
+		let replacement_txid = self.broadcast_reserved(tx).await?;
 
-				partial_tx.unsigned_tx.output =
-					reorder_outputs(partial_tx.unsigned_tx.output, outputs);
+		self.rebroadcaster.forget(&txid)?;
 
-				wallet.sign(&mut partial_tx, SignOptions::default())?;
+		Ok(replacement_txid)
+	}
 
-				Ok(partial_tx.extract_tx())
-			})
-			.await??;
+	/// Use the values of a reloaded config
+	fn reload_config(&self, config: &Config) -> anyhow::Result<()> {
+		*self
+			.config
+			.write()
+			.map_err(|_| anyhow!("Cannot get config write lock"))? = config.clone();
 
-		let txid: Txid = self
-			.execute(move |client| client.send_raw_transaction(&tx))
-			.await??;
+		Ok(())
+	}
+}
+
+/// Status of a mined transaction with the number of confirmations
+pub(crate) fn confirmation_status(
+	confirmations: u32,
+	min_confirmations: u32,
+) -> TransactionStatus {
+	if confirmations >= min_confirmations {
+		TransactionStatus::Confirmed
+	} else {
+		TransactionStatus::Confirming { confirmations }
+	}
+}
+
+/// Runs the blocking operation within the current span, so that its logs are
+/// correlated with the operation it is part of
+pub(crate) fn spawn_blocking_in_span<F, T>(f: F) -> JoinHandle<T>
+where
+	F: FnOnce() -> T + Send + 'static,
+	T: Send + 'static,
+{
+	let span = Span::current();
+
+	spawn_blocking(move || span.in_scope(f))
+}
+
+/// Waits for the next ZMQ block notification, or for the polling interval if
+/// ZMQ is not configured or stays silent
+async fn wait_for_block(blocks: Option<&mut watch::Receiver<u64>>) {
+	match blocks {
+		Some(blocks) => {
+			let _ = timeout(BLOCK_POLLING_INTERVAL, blocks.changed()).await;
+		}
+		None => sleep(BLOCK_POLLING_INTERVAL).await,
+	}
+}
+
+/// Creates the P2TR peg wallet of the configured credentials, or a watch-only
+/// one of the group key if threshold signing is configured. The wallet is
+/// persisted in the state directory unless configured to be kept in memory.
+pub(crate) fn peg_wallet(
+	config: &Config,
+) -> anyhow::Result<Wallet<AnyDatabase>> {
+	let database_config = if config.in_memory_wallet {
+		AnyDatabaseConfig::Memory(())
+	} else {
+		AnyDatabaseConfig::Sled(SledDbConfiguration {
+			path: config
+				.state_directory
+				.join(WALLET_DATABASE_DIRECTORY)
+				.to_string_lossy()
+				.into_owned(),
+			tree_name: WALLET_DATABASE_TREE.to_string(),
+		})
+	};
+
+	let database = AnyDatabase::from_config(&database_config)?;
+
+	if let Some(threshold_signing) = &config.threshold_signing {
+		let internal_key =
+			threshold_signing.group_public_key.x_only_public_key().0;
+
+		return Ok(Wallet::new(
+			&format!("tr({})", internal_key),
+			None,
+			config.bitcoin_network,
+			database,
+		)?);
+	}
+
+	let p2tr_private_key = PrivateKey::from_wif(
+		&config.bitcoin_credentials.wif_p2tr()?.to_string(),
+	)?;
+
+	Ok(Wallet::new(
+		P2TR(p2tr_private_key),
+		Some(P2TR(p2tr_private_key)),
+		config.bitcoin_network,
+		database,
+	)?)
+}
+
+/// Creates the coordinator of the configured threshold signers, if any
+pub(crate) fn threshold_signer(
+	config: &Config,
+) -> anyhow::Result<Option<ThresholdSigner>> {
+	config
+		.threshold_signing
+		.as_ref()
+		.map(|threshold_signing| {
+			let http_client = config.outbound.blocking_http_client()?;
+			let participants = threshold_signing
+				.participant_urls
+				.iter()
+				.cloned()
+				.map(|url| {
+					RemoteParticipant::with_client(url, http_client.clone())
+				})
+				.collect();
+
+			Ok(Arc::new(Mutex::new(ThresholdCoordinator::new(
+				frost::group_key(&threshold_signing.group_public_key)?,
+				threshold_signing.threshold,
+				participants,
+			))))
+		})
+		.transpose()
+}
+
+/// Syncs the peg wallet and builds a signed transaction paying the outputs at
+/// the fee rate, sending the change back to the wallet
+pub(crate) fn build_peg_transaction<B>(
+	wallet: &Wallet<AnyDatabase>,
+	signer: Option<&ThresholdSigner>,
+	blockchain: &B,
+	utxos: &UtxoControl,
+	outputs: Vec<(Script, u64)>,
+	fee_rate: FeeRate,
+) -> anyhow::Result<Transaction>
+where
+	B: WalletSync + GetHeight,
+{
+	sync_wallet(wallet, blockchain)?;
+
+	let selection = utxos.select(&unspent_outpoints(wallet)?)?;
+
+	let change_address = change_address(wallet)?;
+
+	let mut tx_builder = wallet.build_tx();
+
+	tx_builder
+		.drain_to(change_address.script_pubkey())
+		.fee_rate(fee_rate)
+		.enable_rbf();
+
+	for (script, amount) in outputs.clone() {
+		tx_builder.add_recipient(script, amount);
+	}
+
+	let mut partial_tx = utxos.finish(tx_builder, selection)?;
+
+	partial_tx.unsigned_tx.output =
+		reorder_outputs(partial_tx.unsigned_tx.output, outputs);
+
+	let tx = sign_peg_transaction(wallet, signer, partial_tx)?;
 
-		Ok(txid)
+	utxos.reserve(&tx)?;
+
+	Ok(tx)
+}
+
+/// Last unused internal address of the wallet, which moves on to the next
+/// index once a transaction of the wallet pays to it
+fn change_address<D>(wallet: &Wallet<D>) -> anyhow::Result<Address>
+where
+	D: BatchDatabase,
+{
+	Ok(wallet
+		.get_internal_address(AddressIndex::LastUnused)?
+		.address)
+}
+
+/// Syncs the peg wallet and builds a signed replacement of the unconfirmed
+/// transaction paying the fee rate, taking the extra fee from the change
+pub(crate) fn build_fee_bump_transaction<B>(
+	wallet: &Wallet<AnyDatabase>,
+	signer: Option<&ThresholdSigner>,
+	blockchain: &B,
+	utxos: &UtxoControl,
+	txid: Txid,
+	fee_rate: FeeRate,
+) -> anyhow::Result<Transaction>
+where
+	B: WalletSync + GetHeight,
+{
+	sync_wallet(wallet, blockchain)?;
+
+	let selection = utxos.select(&unspent_outpoints(wallet)?)?;
+
+	let mut tx_builder = wallet.build_fee_bump(txid)?;
+
+	// The recipients keep their order, so the OP_RETURN output stays first and
+	// the change stays last
+	tx_builder
+		.fee_rate(fee_rate)
+		.enable_rbf()
+		.ordering(TxOrdering::Untouched)
+		.unspendable(selection.unspendable);
+
+	let (partial_tx, _) = tx_builder.finish()?;
+
+	let tx = sign_peg_transaction(wallet, signer, partial_tx)?;
+
+	utxos.reserve(&tx)?;
+
+	Ok(tx)
+}
+
+/// Signs the peg wallet transaction with the wallet key, or in a signing round
+/// of the threshold signers if configured
+fn sign_peg_transaction(
+	wallet: &Wallet<AnyDatabase>,
+	signer: Option<&ThresholdSigner>,
+	mut partial_tx: PartiallySignedTransaction,
+) -> anyhow::Result<Transaction> {
+	let Some(signer) = signer else {
+		wallet.sign(&mut partial_tx, SignOptions::default())?;
+
+		return Ok(partial_tx.extract_tx());
+	};
+
+	let prevouts = partial_tx
+		.inputs
+		.iter()
+		.map(|input| {
+			input
+				.witness_utxo
+				.clone()
+				.ok_or_else(|| anyhow!("Missing output spent by an input"))
+		})
+		.collect::<anyhow::Result<Vec<_>>>()?;
+
+	let mut tx = partial_tx.extract_tx();
+
+	let mut coordinator = signer
+		.lock()
+		.map_err(|_| anyhow!("Cannot get threshold signer lock"))?;
+
+	sign_taproot_key_spends(&mut *coordinator, &mut tx, &prevouts)?;
+
+	Ok(tx)
+}
+
+/// Syncs the peg wallet and records its balance
+fn sync_wallet<B>(
+	wallet: &Wallet<AnyDatabase>,
+	blockchain: &B,
+) -> anyhow::Result<()>
+where
+	B: WalletSync + GetHeight,
+{
+	wallet.sync(blockchain, SyncOptions::default())?;
+
+	metrics::wallet_balance(wallet.get_balance()?.confirmed);
+
+	Ok(())
+}
+
+fn unspent_outpoints(
+	wallet: &Wallet<AnyDatabase>,
+) -> anyhow::Result<HashSet<OutPoint>> {
+	Ok(wallet
+		.list_unspent()?
+		.into_iter()
+		.map(|utxo| utxo.outpoint)
+		.collect())
+}
+
+/// Checks the response of a block RPC call. Returns `None` if the block is not
+/// available yet and the call should be retried.
+fn check_block_response<T>(
+	block_height: u32,
+	res: bitcoincore_rpc::Result<T>,
+) -> anyhow::Result<Option<T>> {
+	match res {
+		Ok(value) => Ok(Some(value)),
+		Err(bitcoincore_rpc::Error::JsonRpc(
+			bitcoincore_rpc::jsonrpc::Error::Rpc(err),
+		)) => match err.code {
+			RPC_INVALID_PARAMETER => {
+				trace!("Bitcoin block not found, retrying...");
+				Ok(None)
+			}
+			// Waiting for a pruned block to appear is pointless
+			RPC_MISC_ERROR if err.message.contains("pruned") => {
+				Err(BitcoinClientError::BlockPruned(block_height).into())
+			}
+			_ => Err(anyhow!("Error fetching Bitcoin block: {:?}", err)),
+		},
+		Err(bitcoincore_rpc::Error::JsonRpc(
+			bitcoincore_rpc::jsonrpc::Error::Transport(_),
+		)) => {
+			trace!("Bitcoin client connection error, retrying...");
+			Ok(None)
+		}
+		Err(err) => Err(anyhow!("Error fetching Bitcoin block: {:?}", err)),
 	}
 }
 
+/// Checks whether the compact block filter of a block matches any of the
+/// scripts
+fn filter_matches(
+	filter: &BlockFilter,
+	block_hash: &BlockHash,
+	scripts: &[Script],
+) -> anyhow::Result<bool> {
+	filter
+		.match_any(
+			block_hash,
+			&mut scripts.iter().map(|script| script.as_bytes()),
+		)
+		.map_err(|err| anyhow!("Invalid compact block filter: {:?}", err))
+}
+
 #[cfg(test)]
 // test that wallet returns correct address
 mod tests {
 
-	use std::path::Path;
+	use std::{path::Path, str::FromStr};
 
-	use bdk::bitcoin::Network as BitcoinNetwork;
-	use blockstack_lib::vm::ContractName;
+	use bdk::{
+		bitcoin::{
+			util::bip32::DerivationPath, Network as BitcoinNetwork, TxOut,
+		},
+		bitcoincore_rpc::{
+			self,
+			jsonrpc::{self, error::RpcError},
+		},
+		database::{BatchOperations, MemoryDatabase},
+		TransactionDetails,
+	};
+	use blockstack_lib::{core::CHAIN_ID_TESTNET, vm::ContractName};
 	use stacks_core::{wallet::Wallet, Network};
 
-	use super::Client;
-	use crate::config::Config;
+	use super::*;
+	use crate::config::{Config, LogFormat};
+
+	fn rpc_error(code: i32, message: &str) -> bitcoincore_rpc::Error {
+		bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(RpcError {
+			code,
+			message: message.to_string(),
+			data: None,
+		}))
+	}
+
+	#[test]
+	fn test_block_filter_matches_output_scripts() {
+		let block = bdk::bitcoin::blockdata::constants::genesis_block(
+			BitcoinNetwork::Testnet,
+		);
+		let filter =
+			BlockFilter::new_script_filter(&block, |_| unreachable!()).unwrap();
+
+		assert!(filter_matches(
+			&filter,
+			&block.block_hash(),
+			&[
+				Script::new_op_return(&[]),
+				block.txdata[0].output[0].script_pubkey.clone()
+			]
+		)
+		.unwrap());
+		assert!(!filter_matches(
+			&filter,
+			&block.block_hash(),
+			&[Script::new_op_return(&[])]
+		)
+		.unwrap());
+	}
+
+	#[test]
+	fn test_pruned_block_is_not_retried() {
+		let res: bitcoincore_rpc::Result<()> = Err(rpc_error(
+			RPC_MISC_ERROR,
+			"Block not available (pruned data)",
+		));
+
+		let err = check_block_response(100, res).unwrap_err();
+
+		assert!(matches!(
+			err.downcast_ref::<BitcoinClientError>(),
+			Some(BitcoinClientError::BlockPruned(100))
+		));
+	}
+
+	#[test]
+	fn test_missing_block_is_retried() {
+		let res: bitcoincore_rpc::Result<()> = Err(rpc_error(
+			RPC_INVALID_PARAMETER,
+			"Block height out of range",
+		));
+
+		assert!(check_block_response(100, res).unwrap().is_none());
+	}
+
+	#[test]
+	fn test_confirmation_status() {
+		assert_eq!(
+			confirmation_status(2, 6),
+			TransactionStatus::Confirming { confirmations: 2 }
+		);
+		assert_eq!(confirmation_status(6, 6), TransactionStatus::Confirmed);
+		assert_eq!(confirmation_status(7, 6), TransactionStatus::Confirmed);
+	}
+
+	fn test_wallet() -> Wallet {
+		Wallet::new("twice kind fence tip hidden tilt action fragile skin nothing glory cousin green tomorrow spring wrist shed math olympic multiply hip blue scout claw").unwrap()
+	}
+
+	fn derivable_wallet(
+		database: MemoryDatabase,
+	) -> bdk::Wallet<MemoryDatabase> {
+		let xpub = test_wallet()
+			.xpub(&DerivationPath::from_str("m/86'/1'/0'").unwrap())
+			.unwrap();
+
+		bdk::Wallet::new(
+			&format!("tr({}/0/*)", xpub),
+			Some(&format!("tr({}/1/*)", xpub)),
+			xpub.network,
+			database,
+		)
+		.unwrap()
+	}
+
+	#[test]
+	fn test_change_address_rotates_after_it_is_used() {
+		let unused =
+			change_address(&derivable_wallet(MemoryDatabase::new())).unwrap();
+
+		// A transaction of the wallet paying change to the address
+		let tx = Transaction {
+			version: 2,
+			lock_time: bdk::bitcoin::PackedLockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut {
+				value: 10_000,
+				script_pubkey: unused.script_pubkey(),
+			}],
+		};
+		let mut database = MemoryDatabase::new();
+		database
+			.set_tx(&TransactionDetails {
+				txid: tx.txid(),
+				transaction: Some(tx),
+				received: 10_000,
+				sent: 0,
+				fee: None,
+				confirmation_time: None,
+			})
+			.unwrap();
+
+		let wallet = derivable_wallet(database);
+		let next = change_address(&wallet).unwrap();
+
+		assert_ne!(next, unused);
+		assert_eq!(
+			next,
+			wallet
+				.get_internal_address(AddressIndex::Peek(1))
+				.unwrap()
+				.address
+		);
+		// The rotated address stays cached until it is used as well
+		assert_eq!(change_address(&wallet).unwrap(), next);
+	}
 
 	#[test]
 	fn test_wallet_address() {
-		let wallet = Wallet::new("twice kind fence tip hidden tilt action fragile skin nothing glory cousin green tomorrow spring wrist shed math olympic multiply hip blue scout claw").unwrap();
+		let wallet = test_wallet();
 
 		let stacks_network = Network::Testnet;
 		let stacks_credentials = wallet.credentials(stacks_network, 0).unwrap();
@@ -265,15 +1139,50 @@ mod tests {
 			.unwrap();
 
 		let conf = Config {
+			config_file: None,
 			state_directory: Path::new("/tmp/romeo").to_path_buf(),
 			bitcoin_credentials,
+			threshold_signing: None,
+			deposit_xpub: wallet
+				.xpub(&DerivationPath::from_str("m/86'/1'/0'/0").unwrap())
+				.unwrap(),
 			bitcoin_node_url: "http://localhost:18443".parse().unwrap(),
+			bitcoin_rpc_auth: None,
 			electrum_node_url: "ssl://blockstream.info:993".parse().unwrap(),
+			electrum_fallback_node_urls: vec![],
+			bitcoin_backend: Default::default(),
+			esplora_url: None,
+			outbound: Default::default(),
+			bitcoin_zmq_url: None,
+			min_confirmations: 1,
+			in_memory_wallet: true,
+			auto_deploy_contract: false,
+			bitcoin_retry: Default::default(),
+			bitcoin_fee: Default::default(),
+			stacks_fee: Default::default(),
+			stacks_rate_limit: Default::default(),
+			mempool_space_url: None,
+			fulfillment_batch: Default::default(),
+			bitcoin_catch_up_batch_size: 1,
+			bitcoin_block_filters: false,
+			start_height: None,
+			resume: false,
+			coin_selection: Default::default(),
+			metrics_address: None,
+			api_address: None,
+			event_observer_address: None,
+			log_level: None,
+			log_format: LogFormat::Text,
 			bitcoin_network: "testnet".parse().unwrap(),
 			contract_name: ContractName::from("asset"),
+			additional_contracts: vec![],
 			stacks_node_url: "http://localhost:20443".parse().unwrap(),
+			stacks_fallback_node_urls: vec![],
+			stacks_max_tip_lag: 6,
 			stacks_credentials,
+			stacks_sponsor_credentials: None,
 			stacks_network,
+			stacks_chain_id: CHAIN_ID_TESTNET,
 			hiro_api_key: None,
 			strict: true,
 		};
@@ -0,0 +1,280 @@
+//! Coin selection of peg wallet transactions
+
+use std::{
+	collections::HashSet,
+	sync::{Mutex, MutexGuard},
+};
+
+use anyhow::anyhow;
+use bdk::{
+	bitcoin::{psbt::PartiallySignedTransaction, OutPoint, Transaction},
+	database::AnyDatabase,
+	wallet::{
+		coin_selection::{
+			BranchAndBoundCoinSelection, DefaultCoinSelectionAlgorithm,
+			LargestFirstCoinSelection, OldestFirstCoinSelection,
+		},
+		tx_builder::{CreateTx, TxBuilder},
+	},
+};
+use tracing::debug;
+
+/// Algorithm picking the peg wallet UTXOs spent by a transaction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoinSelectionStrategy {
+	/// Search for an input set that needs no change, falling back to
+	/// largest-first
+	#[default]
+	BranchAndBound,
+	/// Spend the oldest UTXOs first
+	OldestFirst,
+	/// Spend the largest UTXOs first
+	LargestFirst,
+}
+
+/// Coin selection settings of peg wallet transactions
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct CoinSelectionPolicy {
+	/// Algorithm picking the UTXOs
+	pub strategy: CoinSelectionStrategy,
+	/// UTXOs spent by the next transaction while they are unspent
+	pub pinned: Vec<OutPoint>,
+	/// UTXOs never spent
+	pub excluded: Vec<OutPoint>,
+}
+
+/// UTXOs that a transaction build must or must not spend
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UtxoSelection {
+	/// UTXOs the transaction must spend
+	pub must_spend: Vec<OutPoint>,
+	/// UTXOs the transaction must not spend
+	pub unspendable: Vec<OutPoint>,
+}
+
+/// Pins, exclusions and reservations of the peg wallet UTXOs, shared by all
+/// transaction builds. The inputs of a built transaction stay reserved until
+/// the wallet sees them spent, so that concurrent builds cannot pick the same
+/// UTXOs and double-spend each other.
+#[derive(Debug, Default)]
+pub struct UtxoControl {
+	strategy: CoinSelectionStrategy,
+	state: Mutex<UtxoState>,
+}
+
+#[derive(Debug, Default)]
+struct UtxoState {
+	pinned: HashSet<OutPoint>,
+	excluded: HashSet<OutPoint>,
+	reserved: HashSet<OutPoint>,
+}
+
+impl UtxoControl {
+	/// Create the UTXO control of the policy
+	pub fn new(policy: &CoinSelectionPolicy) -> Self {
+		Self {
+			strategy: policy.strategy,
+			state: Mutex::new(UtxoState {
+				pinned: policy.pinned.iter().copied().collect(),
+				excluded: policy.excluded.iter().copied().collect(),
+				reserved: HashSet::new(),
+			}),
+		}
+	}
+
+	/// Spend the UTXO in the next transaction
+	pub fn pin(&self, outpoint: OutPoint) -> anyhow::Result<()> {
+		let mut state = self.lock()?;
+
+		state.excluded.remove(&outpoint);
+		state.pinned.insert(outpoint);
+
+		Ok(())
+	}
+
+	/// Never spend the UTXO
+	pub fn exclude(&self, outpoint: OutPoint) -> anyhow::Result<()> {
+		let mut state = self.lock()?;
+
+		state.pinned.remove(&outpoint);
+		state.excluded.insert(outpoint);
+
+		Ok(())
+	}
+
+	/// Let the coin selection decide whether to spend the UTXO
+	pub fn reset(&self, outpoint: &OutPoint) -> anyhow::Result<()> {
+		let mut state = self.lock()?;
+
+		state.pinned.remove(outpoint);
+		state.excluded.remove(outpoint);
+
+		Ok(())
+	}
+
+	/// Select the UTXOs of a transaction among the unspent ones of the wallet,
+	/// dropping the reservations of UTXOs the wallet sees spent
+	pub fn select(
+		&self,
+		unspent: &HashSet<OutPoint>,
+	) -> anyhow::Result<UtxoSelection> {
+		let mut state = self.lock()?;
+
+		state.reserved.retain(|outpoint| unspent.contains(outpoint));
+
+		let must_spend = state
+			.pinned
+			.iter()
+			.filter(|outpoint| {
+				unspent.contains(outpoint) && !state.reserved.contains(outpoint)
+			})
+			.copied()
+			.collect();
+
+		let unspendable =
+			state.excluded.union(&state.reserved).copied().collect();
+
+		Ok(UtxoSelection {
+			must_spend,
+			unspendable,
+		})
+	}
+
+	/// Reserve the inputs of the transaction
+	pub fn reserve(&self, tx: &Transaction) -> anyhow::Result<()> {
+		let mut state = self.lock()?;
+
+		for input in &tx.input {
+			state.pinned.remove(&input.previous_output);
+			state.reserved.insert(input.previous_output);
+		}
+
+		debug!("Reserved {} UTXOs for {}", tx.input.len(), tx.txid());
+
+		Ok(())
+	}
+
+	/// Release the inputs of a transaction that has not been broadcasted
+	pub fn release(&self, tx: &Transaction) -> anyhow::Result<()> {
+		let mut state = self.lock()?;
+
+		for input in &tx.input {
+			state.reserved.remove(&input.previous_output);
+		}
+
+		Ok(())
+	}
+
+	/// Apply the selection to the builder and build the transaction with the
+	/// configured strategy
+	pub(crate) fn finish(
+		&self,
+		mut tx_builder: TxBuilder<
+			'_,
+			AnyDatabase,
+			DefaultCoinSelectionAlgorithm,
+			CreateTx,
+		>,
+		selection: UtxoSelection,
+	) -> anyhow::Result<PartiallySignedTransaction> {
+		tx_builder
+			.add_utxos(&selection.must_spend)?
+			.unspendable(selection.unspendable);
+
+		let (partial_tx, _) = match self.strategy {
+			CoinSelectionStrategy::BranchAndBound => tx_builder
+				.coin_selection(BranchAndBoundCoinSelection::default())
+				.finish()?,
+			CoinSelectionStrategy::OldestFirst => tx_builder
+				.coin_selection(OldestFirstCoinSelection)
+				.finish()?,
+			CoinSelectionStrategy::LargestFirst => tx_builder
+				.coin_selection(LargestFirstCoinSelection)
+				.finish()?,
+		};
+
+		Ok(partial_tx)
+	}
+
+	fn lock(&self) -> anyhow::Result<MutexGuard<'_, UtxoState>> {
+		self.state
+			.lock()
+			.map_err(|_| anyhow!("Cannot get UTXO control lock"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{hashes::Hash, PackedLockTime, TxIn, Txid};
+
+	use super::*;
+
+	fn outpoint(vout: u32) -> OutPoint {
+		OutPoint::new(Txid::all_zeros(), vout)
+	}
+
+	fn spending(outpoints: &[OutPoint]) -> Transaction {
+		Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: outpoints
+				.iter()
+				.map(|outpoint| TxIn {
+					previous_output: *outpoint,
+					..Default::default()
+				})
+				.collect(),
+			output: vec![],
+		}
+	}
+
+	#[test]
+	fn test_reserved_utxos_are_not_selected_again() {
+		let control = UtxoControl::default();
+		let unspent = (0..3).map(outpoint).collect();
+
+		control.reserve(&spending(&[outpoint(0)])).unwrap();
+
+		let selection = control.select(&unspent).unwrap();
+		assert_eq!(selection.unspendable, vec![outpoint(0)]);
+
+		// Released when the broadcast fails
+		control.release(&spending(&[outpoint(0)])).unwrap();
+		assert!(control.select(&unspent).unwrap().unspendable.is_empty());
+	}
+
+	#[test]
+	fn test_reservations_of_spent_utxos_are_dropped() {
+		let control = UtxoControl::default();
+
+		control.reserve(&spending(&[outpoint(0)])).unwrap();
+
+		let unspent = [outpoint(1)].into_iter().collect();
+		control.select(&unspent).unwrap();
+
+		assert!(control.lock().unwrap().reserved.is_empty());
+	}
+
+	#[test]
+	fn test_pinned_and_excluded_utxos() {
+		let control = UtxoControl::new(&CoinSelectionPolicy {
+			pinned: vec![outpoint(0), outpoint(5)],
+			excluded: vec![outpoint(1)],
+			..Default::default()
+		});
+		let unspent = (0..3).map(outpoint).collect();
+
+		let selection = control.select(&unspent).unwrap();
+		assert_eq!(selection.must_spend, vec![outpoint(0)]);
+		assert_eq!(selection.unspendable, vec![outpoint(1)]);
+
+		control.exclude(outpoint(0)).unwrap();
+		control.reset(&outpoint(1)).unwrap();
+
+		let selection = control.select(&unspent).unwrap();
+		assert!(selection.must_spend.is_empty());
+		assert_eq!(selection.unspendable, vec![outpoint(0)]);
+	}
+}
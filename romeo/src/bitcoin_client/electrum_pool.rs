@@ -0,0 +1,333 @@
+//! Failover between Electrum servers
+
+use std::{
+	sync::{Arc, Mutex, MutexGuard},
+	time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use bdk::{
+	blockchain::{ElectrumBlockchain, GetHeight},
+	electrum_client::{self, Client, ConfigBuilder, Socks5Config},
+};
+use tokio::{task::spawn_blocking, time::sleep};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::outbound::Socks5Proxy;
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Electrum servers of the peg wallet, used in order of health and latency.
+/// Connections are opened on first use, and a server whose call fails with a
+/// transient error is skipped until a health check finds it responsive again.
+pub struct ElectrumPool {
+	servers: Mutex<Vec<ElectrumServer>>,
+	options: ConnectionOptions,
+}
+
+/// Proxy and TLS verification of the connections to the servers
+struct ConnectionOptions {
+	proxy: Option<Socks5Proxy>,
+	validate_domain: bool,
+}
+
+struct ElectrumServer {
+	url: Url,
+	connection: Option<Arc<ElectrumBlockchain>>,
+	healthy: bool,
+	latency: Option<Duration>,
+}
+
+impl ElectrumServer {
+	fn connect(
+		&mut self,
+		options: &ConnectionOptions,
+	) -> anyhow::Result<Arc<ElectrumBlockchain>> {
+		if let Some(connection) = &self.connection {
+			return Ok(connection.clone());
+		}
+
+		let connection = Arc::new(connect(&self.url, options)?);
+		self.connection = Some(connection.clone());
+
+		Ok(connection)
+	}
+}
+
+impl ElectrumPool {
+	/// Create a pool of the servers, preferring them in the given order until
+	/// their latency is known. Connections go through the proxy, if any, and
+	/// accept invalid certificates if `validate_domain` is false.
+	pub fn new(
+		urls: impl IntoIterator<Item = Url>,
+		proxy: Option<Socks5Proxy>,
+		validate_domain: bool,
+	) -> Self {
+		let servers = urls
+			.into_iter()
+			.map(|url| ElectrumServer {
+				url,
+				connection: None,
+				healthy: true,
+				latency: None,
+			})
+			.collect();
+
+		Self {
+			servers: Mutex::new(servers),
+			options: ConnectionOptions {
+				proxy,
+				validate_domain,
+			},
+		}
+	}
+
+	/// Checks the health and latency of the servers in a background task.
+	/// Must be called within a Tokio runtime.
+	pub fn spawn_health_checks(self: &Arc<Self>) {
+		let pool = self.clone();
+
+		tokio::spawn(async move {
+			loop {
+				sleep(HEALTH_CHECK_INTERVAL).await;
+
+				let pool = pool.clone();
+
+				if let Err(err) =
+					spawn_blocking(move || pool.check_health()).await
+				{
+					warn!("Electrum health check failed: {}", err);
+				}
+			}
+		});
+	}
+
+	/// Get a connection to the best available server, blocking while
+	/// connecting
+	pub fn blockchain(&self) -> anyhow::Result<(Url, Arc<ElectrumBlockchain>)> {
+		let mut servers = self.lock()?;
+		let all_unhealthy = servers.iter().all(|server| !server.healthy);
+		let mut last_err = None;
+
+		for server in servers.iter_mut() {
+			// Unhealthy servers are only tried as a last resort
+			if !server.healthy && !all_unhealthy {
+				continue;
+			}
+
+			match server.connect(&self.options) {
+				Ok(connection) => return Ok((server.url.clone(), connection)),
+				Err(err) => {
+					warn!(
+						"Unable to connect to Electrum server {}: {}",
+						server.url, err
+					);
+					server.healthy = false;
+					last_err = Some(err);
+				}
+			}
+		}
+
+		Err(last_err
+			.unwrap_or_else(|| anyhow!("No Electrum server configured")))
+	}
+
+	/// Mark the server as unhealthy after a failed call, so that the next
+	/// calls fail over to another server
+	pub fn report_failure(&self, url: &Url) {
+		let Ok(mut servers) = self.lock() else {
+			return;
+		};
+
+		if let Some(server) =
+			servers.iter_mut().find(|server| server.url == *url)
+		{
+			warn!("Electrum server {} failed, failing over", url);
+
+			server.healthy = false;
+			server.connection = None;
+		}
+
+		rank(&mut servers);
+	}
+
+	/// Measure the latency of every server and reorder them, blocking until
+	/// all of them responded or timed out
+	pub fn check_health(&self) -> anyhow::Result<()> {
+		let urls: Vec<Url> = self
+			.lock()?
+			.iter()
+			.map(|server| server.url.clone())
+			.collect();
+
+		// The servers are not locked while waiting for their responses
+		let results: Vec<_> = urls
+			.into_iter()
+			.map(|url| {
+				let start = Instant::now();
+				let res = connect(&url, &self.options).and_then(|connection| {
+					connection.get_height()?;
+					Ok(connection)
+				});
+
+				(url, res, start.elapsed())
+			})
+			.collect();
+
+		let mut servers = self.lock()?;
+
+		for (url, res, latency) in results {
+			let Some(server) =
+				servers.iter_mut().find(|server| server.url == url)
+			else {
+				continue;
+			};
+
+			match res {
+				Ok(connection) => {
+					debug!(
+						"Electrum server {} responded in {:?}",
+						url, latency
+					);
+
+					server.healthy = true;
+					server.latency = Some(latency);
+					server.connection.get_or_insert(Arc::new(connection));
+				}
+				Err(err) => {
+					warn!("Electrum server {} is unhealthy: {}", url, err);
+
+					server.healthy = false;
+					server.latency = None;
+					server.connection = None;
+				}
+			}
+		}
+
+		rank(&mut servers);
+
+		Ok(())
+	}
+
+	fn lock(&self) -> anyhow::Result<MutexGuard<'_, Vec<ElectrumServer>>> {
+		self.servers
+			.lock()
+			.map_err(|_| anyhow!("Cannot get Electrum pool lock"))
+	}
+}
+
+/// Orders the servers healthy first, then by latency. Servers of unknown
+/// latency keep their configured order after the measured ones.
+fn rank(servers: &mut [ElectrumServer]) {
+	servers.sort_by_key(|server| {
+		(!server.healthy, server.latency.unwrap_or(Duration::MAX))
+	});
+}
+
+fn connect(
+	url: &Url,
+	options: &ConnectionOptions,
+) -> anyhow::Result<ElectrumBlockchain> {
+	let client = Client::from_config(url.as_str(), client_config(options)?)?;
+
+	Ok(ElectrumBlockchain::from(client))
+}
+
+/// Configuration of the Electrum client. The client is configured directly
+/// rather than through bdk, which only passes the address of the proxy on.
+fn client_config(
+	options: &ConnectionOptions,
+) -> anyhow::Result<electrum_client::Config> {
+	let socks5 = options
+		.proxy
+		.as_ref()
+		.map(|proxy| match &proxy.credentials {
+			Some((username, password)) => Socks5Config::with_credentials(
+				&proxy.address,
+				username.clone(),
+				password.clone(),
+			),
+			None => Socks5Config::new(&proxy.address),
+		});
+
+	Ok(ConfigBuilder::new()
+		.retry(3)
+		// The Electrum client supports no timeout through a proxy
+		.timeout(socks5.is_none().then_some(10))?
+		.socks5(socks5)?
+		.validate_domain(options.validate_domain)
+		.build())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pool() -> ElectrumPool {
+		ElectrumPool::new(
+			[
+				"ssl://a.example:50002".parse().unwrap(),
+				"ssl://b.example:50002".parse().unwrap(),
+				"ssl://c.example:50002".parse().unwrap(),
+			],
+			None,
+			true,
+		)
+	}
+
+	fn hosts(pool: &ElectrumPool) -> Vec<String> {
+		pool.lock()
+			.unwrap()
+			.iter()
+			.map(|server| server.url.host_str().unwrap().to_string())
+			.collect()
+	}
+
+	#[test]
+	fn test_servers_are_ranked_by_health_and_latency() {
+		let pool = pool();
+
+		{
+			let mut servers = pool.lock().unwrap();
+			servers[0].latency = Some(Duration::from_millis(300));
+			servers[1].latency = Some(Duration::from_millis(100));
+			rank(&mut servers);
+		}
+
+		assert_eq!(hosts(&pool), ["b.example", "a.example", "c.example"]);
+
+		pool.report_failure(&"ssl://b.example:50002".parse().unwrap());
+
+		assert_eq!(hosts(&pool), ["a.example", "c.example", "b.example"]);
+	}
+
+	#[test]
+	fn test_connections_go_through_the_proxy_with_its_credentials() {
+		let options = ConnectionOptions {
+			proxy: Some(Socks5Proxy {
+				address: "127.0.0.1:9050".to_string(),
+				credentials: Some(("user".to_string(), "pass".to_string())),
+			}),
+			validate_domain: true,
+		};
+
+		let config = client_config(&options).unwrap();
+		let socks5 = config.socks5().as_ref().unwrap();
+		let credentials = socks5.credentials.as_ref().unwrap();
+
+		assert_eq!(socks5.addr, "127.0.0.1:9050");
+		assert_eq!(credentials.username, "user");
+		assert_eq!(credentials.password, "pass");
+		assert_eq!(config.timeout(), None);
+
+		let config = client_config(&ConnectionOptions {
+			proxy: None,
+			validate_domain: true,
+		})
+		.unwrap();
+
+		assert!(config.socks5().is_none());
+		assert!(config.timeout().is_some());
+	}
+}
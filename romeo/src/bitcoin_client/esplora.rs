@@ -0,0 +1,322 @@
+//! Esplora Bitcoin client
+
+use std::sync::{
+	atomic::{AtomicU32, Ordering},
+	Arc, Mutex,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bdk::{
+	bitcoin::{Block, BlockHash, Script, Transaction, Txid},
+	blockchain::EsploraBlockchain,
+	database::AnyDatabase,
+	esplora_client::{convert_fee_rate, AsyncClient, OutputStatus, TxStatus},
+	FeeRate, Wallet,
+};
+use tokio::time::sleep;
+use tracing::{trace, warn};
+
+use super::{
+	build_fee_bump_transaction, build_peg_transaction,
+	coin_selection::UtxoControl, confirmation_status, peg_wallet,
+	rebroadcast::Rebroadcaster, spawn_blocking_in_span, threshold_signer,
+	BitcoinBackend, ThresholdSigner, BLOCK_POLLING_INTERVAL,
+};
+use crate::{config::Config, event::TransactionStatus};
+
+/// Gap of unused addresses after which the wallet sync stops
+const STOP_GAP: usize = 10;
+
+/// Bitcoin client talking to an Esplora REST API, such as the ones of
+/// blockstream.info or mempool.space
+#[derive(Clone)]
+pub struct EsploraClient {
+	blockchain: Arc<EsploraBlockchain>,
+	// required for fulfillment txs
+	wallet: Arc<Mutex<Wallet<AnyDatabase>>>,
+	signer: Option<ThresholdSigner>,
+	utxos: Arc<UtxoControl>,
+	rebroadcaster: Arc<Rebroadcaster>,
+	min_confirmations: Arc<AtomicU32>,
+}
+
+impl EsploraClient {
+	/// Create a new Esplora client
+	pub fn new(config: Config) -> anyhow::Result<Self> {
+		let url = config
+			.esplora_url
+			.as_ref()
+			.ok_or_else(|| anyhow!("Esplora URL is not configured"))?;
+
+		let blockchain = EsploraBlockchain::from_client(
+			AsyncClient::from_client(
+				url.as_str().trim_end_matches('/').to_string(),
+				config.outbound.http_client()?,
+			),
+			STOP_GAP,
+		);
+
+		let wallet = peg_wallet(&config)?;
+
+		Ok(Self {
+			blockchain: Arc::new(blockchain),
+			wallet: Arc::new(Mutex::new(wallet)),
+			signer: threshold_signer(&config)?,
+			utxos: Arc::new(UtxoControl::new(&config.coin_selection)),
+			rebroadcaster: Default::default(),
+			min_confirmations: Arc::new(AtomicU32::new(
+				config.min_confirmations,
+			)),
+		})
+	}
+
+	/// Pins, exclusions and reservations of the peg wallet UTXOs
+	pub fn utxos(&self) -> &UtxoControl {
+		&self.utxos
+	}
+
+	/// Broadcast a transaction whose inputs are reserved, releasing them if
+	/// the broadcast fails
+	async fn broadcast_reserved(
+		&self,
+		tx: Transaction,
+	) -> anyhow::Result<Txid> {
+		if let Err(err) = self.blockchain.broadcast(&tx).await {
+			self.utxos.release(&tx)?;
+			return Err(err.into());
+		}
+
+		self.rebroadcaster.track(&tx)?;
+
+		Ok(tx.txid())
+	}
+
+	/// Resubmits a transaction that dropped out of the mempool, unless a
+	/// conflicting transaction spending one of its inputs is confirmed
+	async fn recover_dropped(
+		&self,
+		txid: Txid,
+	) -> anyhow::Result<TransactionStatus> {
+		let rebroadcaster = self.rebroadcaster.clone();
+		let wallet = self.wallet.clone();
+
+		let tx = spawn_blocking_in_span(move || {
+			let wallet = wallet
+				.lock()
+				.map_err(|_| anyhow!("Cannot get wallet read lock"))?;
+
+			rebroadcaster.get(&txid, &wallet)
+		})
+		.await??;
+
+		let Some(tx) = tx else {
+			warn!(
+				"Transaction {} is unknown and cannot be rebroadcasted",
+				txid
+			);
+			return Ok(TransactionStatus::Rejected);
+		};
+
+		for input in &tx.input {
+			let outpoint = input.previous_output;
+			let spend = self
+				.blockchain
+				.get_output_status(&outpoint.txid, outpoint.vout as u64)
+				.await?;
+
+			if let Some(OutputStatus {
+				spent: true,
+				status: Some(TxStatus {
+					confirmed: true, ..
+				}),
+				..
+			}) = spend
+			{
+				warn!(
+					"Input {} of transaction {} is spent by a confirmed transaction",
+					outpoint, txid
+				);
+				return Ok(TransactionStatus::Rejected);
+			}
+		}
+
+		warn!(
+			"Transaction {} dropped out of the mempool, rebroadcasting",
+			txid
+		);
+
+		if let Err(err) = self.blockchain.broadcast(&tx).await {
+			warn!("Unable to rebroadcast transaction {}: {}", txid, err);
+		}
+
+		Ok(TransactionStatus::Broadcasted)
+	}
+}
+
+#[async_trait]
+impl BitcoinBackend for EsploraClient {
+	/// Broadcast a transaction
+	async fn broadcast(&self, tx: Transaction) -> anyhow::Result<()> {
+		self.rebroadcaster.track(&tx)?;
+
+		Ok(self.blockchain.broadcast(&tx).await?)
+	}
+
+	/// Get transaction status
+	async fn get_tx_status(
+		&self,
+		txid: Txid,
+	) -> anyhow::Result<TransactionStatus> {
+		let res = match self.blockchain.get_tx_status(&txid).await? {
+			Some(status) if status.confirmed => {
+				let block_height = status.block_height.ok_or_else(|| {
+					anyhow!(
+						"Confirmed transaction {} has no block height",
+						txid
+					)
+				})?;
+				let tip_height = self.blockchain.get_height().await?;
+
+				confirmation_status(
+					(tip_height + 1).saturating_sub(block_height),
+					self.min_confirmations.load(Ordering::Relaxed),
+				)
+			}
+			Some(_) => TransactionStatus::Broadcasted,
+			None => self.recover_dropped(txid).await?,
+		};
+
+		if matches!(
+			res,
+			TransactionStatus::Confirmed | TransactionStatus::Rejected
+		) {
+			self.rebroadcaster.forget(&txid)?;
+		}
+
+		tracing::debug!("BTC TX {} IS {:?}", txid, res);
+
+		Ok(res)
+	}
+
+	/// Get block
+	async fn get_block(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<(u32, Block)> {
+		while self.blockchain.get_height().await? < block_height {
+			trace!("Bitcoin block not found, retrying...");
+			sleep(BLOCK_POLLING_INTERVAL).await;
+		}
+
+		let block_hash = self.blockchain.get_block_hash(block_height).await?;
+
+		let block = self
+			.blockchain
+			.get_block_by_hash(&block_hash)
+			.await?
+			.ok_or_else(|| {
+				anyhow!("Bitcoin block {} is not available", block_hash)
+			})?;
+
+		Ok((block_height, block))
+	}
+
+	/// Get block hash
+	async fn get_block_hash(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<BlockHash> {
+		Ok(self.blockchain.get_block_hash(block_height).await?)
+	}
+
+	/// Get current block height
+	async fn get_height(&self) -> anyhow::Result<u32> {
+		Ok(self.blockchain.get_height().await?)
+	}
+
+	/// Estimate the fee rate with the Esplora fee estimates
+	async fn estimate_fee(
+		&self,
+		target_blocks: u16,
+	) -> anyhow::Result<FeeRate> {
+		let estimates = self.blockchain.get_fee_estimates().await?;
+		let sat_per_vb = convert_fee_rate(target_blocks as usize, estimates)?;
+
+		Ok(FeeRate::from_sat_per_vb(sat_per_vb))
+	}
+
+	/// Sign and broadcast a transaction
+	async fn sign_and_broadcast(
+		&self,
+		outputs: Vec<(Script, u64)>,
+		fee_rate: FeeRate,
+	) -> anyhow::Result<Txid> {
+		let blockchain = self.blockchain.clone();
+		let wallet = self.wallet.clone();
+		let signer = self.signer.clone();
+		let utxos = self.utxos.clone();
+
+		// The wallet sync blocks on its own runtime
+		let tx = spawn_blocking_in_span(move || {
+			let wallet = wallet
+				.lock()
+				.map_err(|_| anyhow!("Cannot get wallet read lock"))?;
+
+			build_peg_transaction(
+				&wallet,
+				signer.as_ref(),
+				blockchain.as_ref(),
+				&utxos,
+				outputs,
+				fee_rate,
+			)
+		})
+		.await??;
+
+		self.broadcast_reserved(tx).await
+	}
+
+	/// Bump the fee of a transaction
+	async fn bump_fee(
+		&self,
+		txid: Txid,
+		fee_rate: FeeRate,
+	) -> anyhow::Result<Txid> {
+		let blockchain = self.blockchain.clone();
+		let wallet = self.wallet.clone();
+		let signer = self.signer.clone();
+		let utxos = self.utxos.clone();
+
+		// The wallet sync blocks on its own runtime
+		let tx = spawn_blocking_in_span(move || {
+			let wallet = wallet
+				.lock()
+				.map_err(|_| anyhow!("Cannot get wallet read lock"))?;
+
+			build_fee_bump_transaction(
+				&wallet,
+				signer.as_ref(),
+				blockchain.as_ref(),
+				&utxos,
+				txid,
+				fee_rate,
+			)
+		})
+		.await??;
+
+		let replacement_txid = self.broadcast_reserved(tx).await?;
+
+		self.rebroadcaster.forget(&txid)?;
+
+		Ok(replacement_txid)
+	}
+
+	/// Use the values of a reloaded config
+	fn reload_config(&self, config: &Config) -> anyhow::Result<()> {
+		self.min_confirmations
+			.store(config.min_confirmations, Ordering::Relaxed);
+
+		Ok(())
+	}
+}
@@ -0,0 +1,243 @@
+//! Fee estimation of fulfillment transactions
+
+use bdk::FeeRate;
+use tracing::{debug, warn};
+use url::Url;
+
+use super::BitcoinBackend;
+use crate::config::Config;
+
+/// Bounds and confirmation target of the fee rate paid by fulfillment
+/// transactions
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct FeePolicy {
+	/// Number of blocks the transactions should confirm within
+	pub target_blocks: u16,
+	/// Lowest fee rate in sat/vB, also used when no estimate is available
+	pub min_sat_per_vb: f32,
+	/// Highest fee rate in sat/vB
+	pub max_sat_per_vb: f32,
+	/// Number of blocks a fulfillment may stay unconfirmed before its fee is
+	/// bumped, 0 to never bump
+	pub bump_after_blocks: u32,
+	/// Lowest factor the fee rate of a replacement grows by
+	pub bump_multiplier: f32,
+}
+
+impl Default for FeePolicy {
+	fn default() -> Self {
+		Self {
+			target_blocks: 6,
+			min_sat_per_vb: 1.0,
+			max_sat_per_vb: 200.0,
+			bump_after_blocks: 6,
+			bump_multiplier: 1.5,
+		}
+	}
+}
+
+impl FeePolicy {
+	/// Clamps the fee rate between the floor and the ceiling
+	pub fn clamp(&self, fee_rate: FeeRate) -> FeeRate {
+		FeeRate::from_sat_per_vb(
+			fee_rate
+				.as_sat_per_vb()
+				.clamp(self.min_sat_per_vb, self.max_sat_per_vb),
+		)
+	}
+
+	/// Fee rate of a replacement of a transaction paying the previous fee
+	/// rate, or `None` if the ceiling does not leave room for a higher one
+	pub fn bumped(
+		&self,
+		estimate: FeeRate,
+		previous: Option<FeeRate>,
+	) -> Option<FeeRate> {
+		let Some(previous) = previous else {
+			return Some(self.clamp(estimate));
+		};
+
+		let fee_rate = self.clamp(FeeRate::from_sat_per_vb(
+			estimate
+				.as_sat_per_vb()
+				.max(previous.as_sat_per_vb() * self.bump_multiplier),
+		));
+
+		(fee_rate > previous).then_some(fee_rate)
+	}
+}
+
+/// Fee estimates of the mempool.space API, in sat/vB
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecommendedFees {
+	fastest_fee: f32,
+	half_hour_fee: f32,
+	hour_fee: f32,
+	economy_fee: f32,
+}
+
+impl RecommendedFees {
+	/// Picks the estimate matching the number of blocks to confirm within
+	fn for_target(&self, target_blocks: u16) -> f32 {
+		match target_blocks {
+			0..=1 => self.fastest_fee,
+			2..=3 => self.half_hour_fee,
+			4..=6 => self.hour_fee,
+			_ => self.economy_fee,
+		}
+	}
+}
+
+/// Picks the fee rate of fulfillment transactions. The Bitcoin backend is
+/// asked first, then the mempool.space API if configured, and the result is
+/// kept within the bounds of the fee policy.
+#[derive(Debug, Clone)]
+pub struct FeeEstimator {
+	policy: FeePolicy,
+	mempool_space_url: Option<Url>,
+	http_client: reqwest::Client,
+}
+
+impl FeeEstimator {
+	/// Create a fee estimator from the config
+	pub fn new(config: &Config) -> anyhow::Result<Self> {
+		Ok(Self {
+			policy: config.bitcoin_fee.clone(),
+			mempool_space_url: config.mempool_space_url.clone(),
+			http_client: config.outbound.http_client()?,
+		})
+	}
+
+	/// Estimate the fee rate, falling back to the policy floor if no source
+	/// has an estimate
+	pub async fn estimate(&self, backend: &dyn BitcoinBackend) -> FeeRate {
+		let target_blocks = self.policy.target_blocks;
+
+		let fee_rate = match backend.estimate_fee(target_blocks).await {
+			Ok(fee_rate) => Some(fee_rate),
+			Err(err) => {
+				warn!("Bitcoin backend has no fee estimate: {:?}", err);
+				self.estimate_with_mempool_space().await
+			}
+		};
+
+		let fee_rate = self.policy.clamp(fee_rate.unwrap_or_else(|| {
+			warn!("No fee estimate available, using the fee rate floor");
+			FeeRate::from_sat_per_vb(self.policy.min_sat_per_vb)
+		}));
+
+		debug!(
+			"Estimated fee rate of {} sat/vB for {} blocks",
+			fee_rate.as_sat_per_vb(),
+			target_blocks
+		);
+
+		fee_rate
+	}
+
+	/// Estimate the fee rate of a replacement of a transaction paying the
+	/// previous fee rate
+	pub async fn estimate_bump(
+		&self,
+		backend: &dyn BitcoinBackend,
+		previous: Option<FeeRate>,
+	) -> Option<FeeRate> {
+		let estimate = self.estimate(backend).await;
+
+		self.policy.bumped(estimate, previous)
+	}
+
+	async fn estimate_with_mempool_space(&self) -> Option<FeeRate> {
+		let url = format!(
+			"{}/api/v1/fees/recommended",
+			self.mempool_space_url
+				.as_ref()?
+				.as_str()
+				.trim_end_matches('/')
+		);
+
+		let res = async {
+			self.http_client
+				.get(url)
+				.send()
+				.await?
+				.error_for_status()?
+				.json::<RecommendedFees>()
+				.await
+		}
+		.await;
+
+		match res {
+			Ok(fees) => Some(FeeRate::from_sat_per_vb(
+				fees.for_target(self.policy.target_blocks),
+			)),
+			Err(err) => {
+				warn!("mempool.space has no fee estimate: {:?}", err);
+				None
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_fee_rate_is_clamped() {
+		let policy = FeePolicy {
+			min_sat_per_vb: 2.0,
+			max_sat_per_vb: 50.0,
+			..Default::default()
+		};
+
+		let clamp =
+			|sat_per_vb| policy.clamp(FeeRate::from_sat_per_vb(sat_per_vb));
+
+		assert_eq!(clamp(1.0), FeeRate::from_sat_per_vb(2.0));
+		assert_eq!(clamp(12.5), FeeRate::from_sat_per_vb(12.5));
+		assert_eq!(clamp(120.0), FeeRate::from_sat_per_vb(50.0));
+	}
+
+	#[test]
+	fn test_bumped_fee_rate() {
+		let policy = FeePolicy {
+			max_sat_per_vb: 50.0,
+			bump_multiplier: 1.5,
+			..Default::default()
+		};
+
+		let rate = FeeRate::from_sat_per_vb;
+
+		// The estimate is used if it grew more than the multiplier
+		assert_eq!(
+			policy.bumped(rate(20.0), Some(rate(10.0))),
+			Some(rate(20.0))
+		);
+		assert_eq!(
+			policy.bumped(rate(10.0), Some(rate(10.0))),
+			Some(rate(15.0))
+		);
+		assert_eq!(policy.bumped(rate(10.0), None), Some(rate(10.0)));
+		assert_eq!(
+			policy.bumped(rate(10.0), Some(rate(40.0))),
+			Some(rate(50.0))
+		);
+		assert_eq!(policy.bumped(rate(10.0), Some(rate(50.0))), None);
+	}
+
+	#[test]
+	fn test_mempool_space_estimate_matches_target() {
+		let fees: RecommendedFees = serde_json::from_str(
+			r#"{"fastestFee":40,"halfHourFee":30,"hourFee":20,"economyFee":10,"minimumFee":5}"#,
+		)
+		.unwrap();
+
+		assert_eq!(fees.for_target(1), 40.0);
+		assert_eq!(fees.for_target(3), 30.0);
+		assert_eq!(fees.for_target(6), 20.0);
+		assert_eq!(fees.for_target(144), 10.0);
+	}
+}
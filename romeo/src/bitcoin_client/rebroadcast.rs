@@ -0,0 +1,116 @@
+//! Rebroadcasts of transactions dropped out of the mempool
+
+use std::{
+	collections::HashMap,
+	sync::{Mutex, MutexGuard},
+};
+
+use anyhow::anyhow;
+use bdk::{
+	bitcoin::{Transaction, Txid},
+	database::AnyDatabase,
+	Wallet,
+};
+
+/// Keeps the raw transactions of in-flight broadcasts, so that they can be
+/// resubmitted when they drop out of the mempool. Transactions broadcasted
+/// before a restart are looked up in the peg wallet instead.
+#[derive(Debug, Default)]
+pub struct Rebroadcaster {
+	txs: Mutex<HashMap<Txid, Transaction>>,
+}
+
+impl Rebroadcaster {
+	/// Keep the transaction until it is confirmed or rejected
+	pub fn track(&self, tx: &Transaction) -> anyhow::Result<()> {
+		self.lock()?.insert(tx.txid(), tx.clone());
+
+		Ok(())
+	}
+
+	/// Stop keeping the transaction
+	pub fn forget(&self, txid: &Txid) -> anyhow::Result<()> {
+		self.lock()?.remove(txid);
+
+		Ok(())
+	}
+
+	/// Get the kept transaction, or the one stored in the wallet
+	pub fn get(
+		&self,
+		txid: &Txid,
+		wallet: &Wallet<AnyDatabase>,
+	) -> anyhow::Result<Option<Transaction>> {
+		if let Some(tx) = self.lock()?.get(txid) {
+			return Ok(Some(tx.clone()));
+		}
+
+		Ok(wallet
+			.get_tx(txid, true)?
+			.and_then(|details| details.transaction))
+	}
+
+	fn lock(
+		&self,
+	) -> anyhow::Result<MutexGuard<'_, HashMap<Txid, Transaction>>> {
+		self.txs
+			.lock()
+			.map_err(|_| anyhow!("Cannot get rebroadcaster lock"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::{
+		bitcoin::{
+			secp256k1::SecretKey, Network, PackedLockTime, PrivateKey, TxOut,
+		},
+		database::MemoryDatabase,
+		template::P2TR,
+	};
+
+	use super::*;
+
+	fn wallet() -> Wallet<AnyDatabase> {
+		let key = PrivateKey::new(
+			SecretKey::from_slice(&[1; 32]).unwrap(),
+			Network::Testnet,
+		);
+
+		Wallet::new(
+			P2TR(key),
+			None,
+			Network::Testnet,
+			AnyDatabase::Memory(MemoryDatabase::new()),
+		)
+		.unwrap()
+	}
+
+	fn tx(value: u64) -> Transaction {
+		Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut {
+				value,
+				..Default::default()
+			}],
+		}
+	}
+
+	#[test]
+	fn test_tracked_transactions_are_kept_until_forgotten() {
+		let rebroadcaster = Rebroadcaster::default();
+		let wallet = wallet();
+		let tx = tx(1000);
+
+		rebroadcaster.track(&tx).unwrap();
+		assert_eq!(
+			rebroadcaster.get(&tx.txid(), &wallet).unwrap(),
+			Some(tx.clone())
+		);
+
+		rebroadcaster.forget(&tx.txid()).unwrap();
+		assert_eq!(rebroadcaster.get(&tx.txid(), &wallet).unwrap(), None);
+	}
+}
@@ -0,0 +1,204 @@
+//! Retries of transient Bitcoin node errors
+
+use std::{fmt::Debug, future::Future, time::Duration};
+
+use backoff::{
+	backoff::Backoff, ExponentialBackoff, ExponentialBackoffBuilder,
+};
+use bdk::{
+	bitcoincore_rpc::{self, jsonrpc},
+	electrum_client,
+};
+use tokio::time::sleep;
+use tracing::warn;
+
+/// Bitcoin Core RPC error code returned while the node is starting up
+const RPC_IN_WARMUP: i32 = -28;
+
+/// Policy for retrying calls to the Bitcoin node that fail with a transient
+/// error, waiting with exponential backoff and jitter between attempts
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+	/// Maximum number of attempts, including the first one
+	pub max_attempts: u32,
+	/// Delay before the first retry in milliseconds
+	pub initial_interval_ms: u64,
+	/// Upper bound of the delay between attempts in milliseconds
+	pub max_interval_ms: u64,
+	/// Factor the delay grows by after each attempt
+	pub multiplier: f64,
+	/// Relative amount of randomness applied to each delay, from 0 to 1
+	pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: 5,
+			initial_interval_ms: 500,
+			max_interval_ms: 30_000,
+			multiplier: 2.0,
+			jitter: 0.5,
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// Runs the operation until its output is not classified as transient, or
+	/// the attempts run out, and returns the last output
+	pub async fn retry<T, F, Fut>(
+		&self,
+		mut operation: F,
+		is_transient: impl Fn(&T) -> bool,
+	) -> T
+	where
+		T: Debug,
+		F: FnMut() -> Fut,
+		Fut: Future<Output = T>,
+	{
+		let mut backoff = self.backoff();
+		let mut attempt = 1;
+
+		loop {
+			let output = operation().await;
+
+			if attempt >= self.max_attempts || !is_transient(&output) {
+				return output;
+			}
+
+			let delay = backoff
+				.next_backoff()
+				.unwrap_or(Duration::from_millis(self.max_interval_ms));
+
+			warn!(
+				"Retrying in {:?} after attempt {}/{} failed: {:?}",
+				delay, attempt, self.max_attempts, output
+			);
+
+			sleep(delay).await;
+			attempt += 1;
+		}
+	}
+
+	fn backoff(&self) -> ExponentialBackoff {
+		ExponentialBackoffBuilder::new()
+			.with_initial_interval(Duration::from_millis(
+				self.initial_interval_ms,
+			))
+			.with_max_interval(Duration::from_millis(self.max_interval_ms))
+			.with_multiplier(self.multiplier)
+			.with_randomization_factor(self.jitter)
+			.with_max_elapsed_time(None)
+			.build()
+	}
+}
+
+/// Whether the RPC error is caused by the connection or the node warming up,
+/// so that the call may succeed later
+pub fn is_transient_rpc_error(err: &bitcoincore_rpc::Error) -> bool {
+	match err {
+		bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_))
+		| bitcoincore_rpc::Error::Io(_) => true,
+		bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(err)) => {
+			err.code == RPC_IN_WARMUP
+		}
+		_ => false,
+	}
+}
+
+/// Whether the error is a transient RPC or Electrum error
+pub fn is_transient_error(err: &anyhow::Error) -> bool {
+	if let Some(err) = err.downcast_ref::<bitcoincore_rpc::Error>() {
+		return is_transient_rpc_error(err);
+	}
+
+	match err.downcast_ref::<bdk::Error>() {
+		Some(bdk::Error::Electrum(err)) => is_transient_electrum_error(err),
+		_ => false,
+	}
+}
+
+fn is_transient_electrum_error(err: &electrum_client::Error) -> bool {
+	match err {
+		electrum_client::Error::IOError(_)
+		| electrum_client::Error::SharedIOError(_)
+		| electrum_client::Error::CouldntLockReader
+		| electrum_client::Error::Mpsc => true,
+		electrum_client::Error::AllAttemptsErrored(errs) => {
+			errs.iter().all(is_transient_electrum_error)
+		}
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+
+	use super::*;
+
+	fn policy(max_attempts: u32) -> RetryPolicy {
+		RetryPolicy {
+			max_attempts,
+			initial_interval_ms: 1,
+			max_interval_ms: 1,
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	async fn test_transient_errors_are_retried() {
+		let mut attempts = 0;
+
+		let res: Result<u32, u32> = policy(5)
+			.retry(
+				|| {
+					attempts += 1;
+					let res = if attempts < 3 {
+						Err(attempts)
+					} else {
+						Ok(attempts)
+					};
+					async move { res }
+				},
+				Result::is_err,
+			)
+			.await;
+
+		assert_eq!(res, Ok(3));
+	}
+
+	#[tokio::test]
+	async fn test_attempts_are_limited() {
+		let mut attempts = 0;
+
+		let res: Result<(), u32> = policy(2)
+			.retry(
+				|| {
+					attempts += 1;
+					let res = Err(attempts);
+					async move { res }
+				},
+				Result::is_err,
+			)
+			.await;
+
+		assert_eq!(res, Err(2));
+	}
+
+	#[test]
+	fn test_error_classification() {
+		let transport =
+			bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(
+				Box::new(io::Error::from(io::ErrorKind::ConnectionRefused)),
+			));
+		let electrum = bdk::Error::Electrum(electrum_client::Error::IOError(
+			io::Error::from(io::ErrorKind::TimedOut),
+		));
+
+		assert!(is_transient_error(&transport.into()));
+		assert!(is_transient_error(&electrum.into()));
+		assert!(!is_transient_error(&anyhow::anyhow!("Invalid transaction")));
+	}
+}
@@ -0,0 +1,252 @@
+//! Pool of Bitcoin Core RPC clients
+
+use std::{
+	path::PathBuf,
+	sync::{Mutex, MutexGuard},
+};
+
+use anyhow::anyhow;
+use bdk::bitcoincore_rpc::{
+	self,
+	jsonrpc::{self, simple_http::SimpleHttpTransport},
+	Client as RPCClient,
+};
+use url::Url;
+
+use crate::{outbound::Socks5Proxy, redact};
+
+/// Authentication of the Bitcoin Core RPC calls
+#[derive(Clone, PartialEq, Eq, derivative::Derivative)]
+#[derivative(Debug)]
+pub enum RpcAuth {
+	/// Username and password, from the environment or the node URL
+	UserPass {
+		/// RPC username
+		username: String,
+		/// RPC password
+		#[derivative(Debug(format_with = "redact::secret"))]
+		password: String,
+	},
+	/// Cookie file of bitcoind. It is read on every connection, since
+	/// bitcoind writes a new one whenever it starts.
+	CookieFile(PathBuf),
+}
+
+impl RpcAuth {
+	/// Username and password to authenticate with
+	fn credentials(&self) -> anyhow::Result<(String, String)> {
+		match self {
+			Self::UserPass { username, password } => {
+				Ok((username.clone(), password.clone()))
+			}
+			Self::CookieFile(path) => {
+				let cookie = std::fs::read_to_string(path).map_err(|err| {
+					anyhow!(
+						"Unable to read the cookie file {:?}: {}",
+						path,
+						err
+					)
+				})?;
+
+				cookie
+					.trim()
+					.split_once(':')
+					.map(|(username, password)| {
+						(username.to_string(), password.to_string())
+					})
+					.ok_or_else(|| anyhow!("Invalid cookie file {:?}", path))
+			}
+		}
+	}
+}
+
+/// Pool of Bitcoin Core RPC clients. Clients keep their HTTP connection alive
+/// between calls, so reusing them saves a connection setup per call. A client
+/// whose call fails at the transport level is dropped, and the next call
+/// reconnects with a new one.
+#[derive(Debug)]
+pub struct RpcPool {
+	url: Url,
+	auth: Option<RpcAuth>,
+	proxy: Option<Socks5Proxy>,
+	max_idle: usize,
+	idle: Mutex<Vec<RPCClient>>,
+}
+
+impl RpcPool {
+	/// Create an empty pool keeping at most `max_idle` clients for reuse,
+	/// authenticating with `auth` and connecting through the proxy if any
+	pub fn new(
+		url: Url,
+		auth: Option<RpcAuth>,
+		proxy: Option<Socks5Proxy>,
+		max_idle: usize,
+	) -> Self {
+		Self {
+			url: redact::without_credentials(&url),
+			auth,
+			proxy,
+			max_idle,
+			idle: Mutex::new(vec![]),
+		}
+	}
+
+	/// Run the blocking call with an idle client, or a new one if all of them
+	/// are busy
+	pub fn execute<F, T>(
+		&self,
+		f: F,
+	) -> anyhow::Result<bitcoincore_rpc::Result<T>>
+	where
+		F: FnOnce(&RPCClient) -> bitcoincore_rpc::Result<T>,
+	{
+		let client = match self.lock()?.pop() {
+			Some(client) => client,
+			None => self.connect()?,
+		};
+
+		let res = f(&client);
+
+		if !is_connection_error(&res) {
+			let mut idle = self.lock()?;
+
+			if idle.len() < self.max_idle {
+				idle.push(client);
+			}
+		}
+
+		Ok(res)
+	}
+
+	fn connect(&self) -> anyhow::Result<RPCClient> {
+		let (username, password) = self
+			.auth
+			.as_ref()
+			.ok_or_else(|| anyhow!("Bitcoin Core RPC credentials are missing"))?
+			.credentials()?;
+
+		if username.is_empty() {
+			return Err(anyhow!("Username is empty"));
+		}
+
+		if password.is_empty() {
+			return Err(anyhow!("Password is empty"));
+		}
+
+		let mut transport = SimpleHttpTransport::builder()
+			.url(self.url.as_str())?
+			.auth(username, Some(password));
+
+		if let Some(proxy) = &self.proxy {
+			transport = transport.proxy_addr(&proxy.address)?;
+
+			if let Some((username, password)) = &proxy.credentials {
+				transport = transport.proxy_auth(username, password);
+			}
+		}
+
+		Ok(RPCClient::from_jsonrpc(jsonrpc::Client::with_transport(
+			transport.build(),
+		)))
+	}
+
+	fn lock(&self) -> anyhow::Result<MutexGuard<'_, Vec<RPCClient>>> {
+		self.idle
+			.lock()
+			.map_err(|_| anyhow!("Cannot get RPC pool lock"))
+	}
+}
+
+fn is_connection_error<T>(res: &bitcoincore_rpc::Result<T>) -> bool {
+	matches!(
+		res,
+		Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(
+			_
+		))) | Err(bitcoincore_rpc::Error::Io(_))
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io;
+
+	use super::*;
+
+	fn pool() -> RpcPool {
+		RpcPool::new(
+			"http://localhost:18443".parse().unwrap(),
+			Some(RpcAuth::UserPass {
+				username: "user".to_string(),
+				password: "pass".to_string(),
+			}),
+			None,
+			1,
+		)
+	}
+
+	#[test]
+	fn test_clients_are_reused() {
+		let pool = pool();
+
+		pool.execute(|_| Ok(())).unwrap().unwrap();
+		pool.execute(|_| Ok(())).unwrap().unwrap();
+
+		assert_eq!(pool.lock().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn test_broken_clients_are_dropped() {
+		let pool = pool();
+
+		pool.execute(|_| Ok(())).unwrap().unwrap();
+
+		let res = pool
+			.execute::<_, ()>(|_| {
+				Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(
+					Box::new(io::Error::from(io::ErrorKind::ConnectionReset)),
+				)))
+			})
+			.unwrap();
+
+		assert!(res.is_err());
+		assert!(pool.lock().unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_credentials_are_required() {
+		let pool = RpcPool::new(
+			"http://localhost:18443".parse().unwrap(),
+			None,
+			None,
+			1,
+		);
+
+		assert!(pool.execute(|_| Ok(())).is_err());
+	}
+
+	#[test]
+	fn test_credentials_are_read_from_the_cookie_file() {
+		let path = std::env::temp_dir()
+			.join(format!("romeo-rpc-cookie-{}", std::process::id()));
+		std::fs::write(&path, "__cookie__:secret\n").unwrap();
+
+		let auth = RpcAuth::CookieFile(path.clone());
+
+		assert_eq!(
+			auth.credentials().unwrap(),
+			("__cookie__".to_string(), "secret".to_string())
+		);
+
+		std::fs::write(&path, "secret").unwrap();
+		assert!(auth.credentials().is_err());
+
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn test_password_is_redacted() {
+		let pool = pool();
+
+		assert!(!format!("{:?}", pool).contains("pass\""));
+	}
+}
@@ -0,0 +1,119 @@
+//! ZMQ notifications of a bitcoind node
+
+use std::time::Duration;
+
+use tokio::{sync::watch, task::AbortHandle, time::sleep};
+use tracing::{trace, warn};
+use url::Url;
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+/// Topic of the block hash notifications
+const HASH_BLOCK_TOPIC: &str = "hashblock";
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Listens to the `hashblock` notifications of a bitcoind node. The channel
+/// counts the notifications received, so waiters only have to watch for
+/// changes. The background task stops when the listener is dropped.
+#[derive(Debug)]
+pub struct ZmqListener {
+	blocks: watch::Receiver<u64>,
+	task: AbortHandle,
+}
+
+impl ZmqListener {
+	/// Starts listening to the endpoint in a background task, reconnecting
+	/// whenever the connection is lost. Must be called within a Tokio runtime.
+	pub fn spawn(endpoint: &Url) -> Self {
+		let (blocks_tx, blocks) = watch::channel(0);
+		let task = tokio::spawn(run(blocks_tx, endpoint.to_string()));
+
+		Self {
+			blocks,
+			task: task.abort_handle(),
+		}
+	}
+
+	/// Receiver that changes whenever a new block is announced
+	pub fn blocks(&self) -> watch::Receiver<u64> {
+		self.blocks.clone()
+	}
+}
+
+impl Drop for ZmqListener {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
+
+async fn run(blocks: watch::Sender<u64>, endpoint: String) {
+	loop {
+		if let Err(err) = listen(&blocks, &endpoint).await {
+			warn!(
+				"ZMQ notifications from {} unavailable, polling instead: {}",
+				endpoint, err
+			);
+		}
+
+		sleep(RECONNECT_INTERVAL).await;
+	}
+}
+
+async fn listen(
+	blocks: &watch::Sender<u64>,
+	endpoint: &str,
+) -> anyhow::Result<()> {
+	let mut socket = SubSocket::new();
+
+	socket.connect(endpoint).await?;
+	socket.subscribe(HASH_BLOCK_TOPIC).await?;
+
+	loop {
+		let message = socket.recv().await?;
+
+		if let Some(topic) = message.get(0) {
+			notify(blocks, topic);
+		}
+	}
+}
+
+fn notify(blocks: &watch::Sender<u64>, topic: &[u8]) {
+	if topic != HASH_BLOCK_TOPIC.as_bytes() {
+		return;
+	}
+
+	trace!("Got ZMQ {} notification", HASH_BLOCK_TOPIC);
+
+	blocks.send_modify(|count| *count += 1);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_only_block_notifications_are_counted() {
+		let (blocks_tx, blocks) = watch::channel(0);
+
+		notify(&blocks_tx, b"hashblock");
+		notify(&blocks_tx, b"hashblock");
+		notify(&blocks_tx, b"hashtx");
+		notify(&blocks_tx, b"rawblock");
+
+		assert!(blocks.has_changed().unwrap());
+		assert_eq!(*blocks.borrow(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_dropped_listener_stops_its_task() {
+		// Nothing listens on the endpoint, so the task keeps reconnecting
+		let listener =
+			ZmqListener::spawn(&"tcp://127.0.0.1:1".parse().unwrap());
+		let mut blocks = listener.blocks();
+
+		drop(listener);
+
+		// The sender is dropped with the aborted task
+		assert!(blocks.changed().await.is_err());
+	}
+}
@@ -0,0 +1,126 @@
+//! Checkpoint of the scanning progress, so that a restart can resume from the
+//! last processed blocks instead of the contract deployment
+
+use std::{
+	fs::{self, File},
+	io::Write,
+	path::Path,
+};
+
+use bdk::bitcoin::BlockHash as BitcoinBlockHash;
+use serde_json::Value;
+
+use crate::state_file::{self, STATE_VERSION};
+
+/// File name of the checkpoint within the state directory
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// Last processed Stacks and Bitcoin blocks
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Checkpoint {
+	/// Height of the last processed Stacks block
+	pub stacks_block_height: u32,
+	/// Height of the last processed Bitcoin block
+	pub bitcoin_block_height: u32,
+	/// Heights and hashes of the last processed Bitcoin blocks, to detect the
+	/// reorgs that happened while the system was stopped
+	pub bitcoin_block_hashes: Vec<(u32, BitcoinBlockHash)>,
+}
+
+/// Checkpoint as written to the state directory, with the state version
+#[derive(serde::Serialize)]
+struct VersionedCheckpoint<'a> {
+	version: u32,
+	#[serde(flatten)]
+	checkpoint: &'a Checkpoint,
+}
+
+impl Checkpoint {
+	/// Read the checkpoint of the state directory, if it has been written
+	pub fn load(state_directory: &Path) -> anyhow::Result<Option<Self>> {
+		Ok(Self::read(state_directory)?.map(|(checkpoint, _)| checkpoint))
+	}
+
+	/// Migrate the checkpoint of the state directory to the current version,
+	/// returning the version it had, or `None` if there is no checkpoint
+	pub fn migrate(state_directory: &Path) -> anyhow::Result<Option<u32>> {
+		let Some((checkpoint, version)) = Self::read(state_directory)? else {
+			return Ok(None);
+		};
+
+		if version < STATE_VERSION {
+			checkpoint.save(state_directory)?;
+		}
+
+		Ok(Some(version))
+	}
+
+	/// Read and migrate the checkpoint, returning it with its version
+	fn read(state_directory: &Path) -> anyhow::Result<Option<(Self, u32)>> {
+		let path = state_directory.join(CHECKPOINT_FILE);
+
+		if !path.exists() {
+			return Ok(None);
+		}
+
+		let mut value: Value = serde_json::from_reader(File::open(path)?)?;
+
+		// Checkpoints written before the versioning are at version 0
+		let version = match value
+			.as_object_mut()
+			.and_then(|fields| fields.remove("version"))
+		{
+			Some(version) => serde_json::from_value(version)?,
+			None => 0,
+		};
+
+		state_file::migrate_checkpoint(&mut value, version)?;
+
+		Ok(Some((serde_json::from_value(value)?, version)))
+	}
+
+	/// Write the checkpoint to the state directory. It replaces the previous
+	/// one atomically, so a crash never leaves a partial checkpoint.
+	pub fn save(&self, state_directory: &Path) -> anyhow::Result<()> {
+		let path = state_directory.join(CHECKPOINT_FILE);
+		let tmp_path = path.with_extension("json.tmp");
+
+		let mut file = File::create(&tmp_path)?;
+		file.write_all(&serde_json::to_vec(&VersionedCheckpoint {
+			version: STATE_VERSION,
+			checkpoint: self,
+		})?)?;
+		file.sync_all()?;
+
+		fs::rename(tmp_path, path)?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::hashes::Hash;
+
+	use super::*;
+
+	#[test]
+	fn test_saved_checkpoint_is_loaded() {
+		let dir = std::env::temp_dir()
+			.join(format!("romeo-checkpoint-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+
+		assert_eq!(Checkpoint::load(&dir).unwrap(), None);
+
+		let checkpoint = Checkpoint {
+			stacks_block_height: 10,
+			bitcoin_block_height: 20,
+			bitcoin_block_hashes: vec![(20, BitcoinBlockHash::all_zeros())],
+		};
+		checkpoint.save(&dir).unwrap();
+
+		assert_eq!(Checkpoint::load(&dir).unwrap(), Some(checkpoint));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
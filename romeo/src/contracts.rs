@@ -0,0 +1,407 @@
+//! Deployment and upgrades of the asset contract. Clarity contracts are
+//! immutable, so upgrades are deployed as new contracts with versioned names,
+//! which the config is then pointed at.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use blockstack_lib::{
+	burnchains::Txid as StacksTxId,
+	chainstate::stacks::{
+		StacksTransaction, TransactionPayload, TransactionSmartContract,
+		TransactionVersion,
+	},
+	util_lib::strings::StacksString,
+	vm::{types::QualifiedContractIdentifier, ClarityVersion, ContractName},
+};
+use stacks_core::Network as StacksNetwork;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+	config::{Config, ContractAction},
+	event::TransactionStatus,
+	stacks_client::{asset, LockedClient, StacksBackend, StacksClient},
+	system::contract_call_auth,
+};
+
+/// Clarity source of the asset contract shipped with the daemon
+pub const ASSET_CONTRACT_SOURCE: &str =
+	include_str!("../asset-contract/contracts/asset.clar");
+
+/// Name of the contract verifying Bitcoin transactions, which the asset
+/// contract calls
+pub const BITCOIN_CONTRACT_NAME: &str = "clarity-bitcoin-mini";
+
+/// Clarity source of the Bitcoin contract with the test network shortcuts
+const BITCOIN_CONTRACT_SOURCE: &str =
+	include_str!("../asset-contract/contracts/clarity-bitcoin-mini.clar");
+
+/// Clarity source of the Bitcoin contract for mainnet
+const BITCOIN_CONTRACT_MAINNET_SOURCE: &str = include_str!(
+	"../asset-contract/contracts/clarity-bitcoin-mini-deploy.clar"
+);
+
+/// Functions of the asset contract called by the daemon
+const REQUIRED_FUNCTIONS: &[&str] = &[
+	"(define-public (set-bitcoin-wallet-public-key",
+	"(define-public (mint",
+	"(define-public (burn",
+	"(define-read-only (get-amount-by-btc-txid",
+];
+
+const CONFIRMATION_POLLING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Deployment of the asset contract compared to the bundled source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractStatus {
+	/// No contract is deployed under the name
+	Missing,
+	/// The bundled source is deployed
+	UpToDate,
+	/// Another source the daemon can operate against is deployed
+	Drifted {
+		/// Version declared by the deployed source
+		version: Option<String>,
+	},
+	/// The deployed source lacks functions the daemon calls
+	Incompatible {
+		/// Definitions missing from the deployed source
+		missing_functions: Vec<String>,
+	},
+}
+
+/// Version declared in the header of a Clarity source
+pub fn version(source: &str) -> Option<&str> {
+	source.lines().find_map(|line| {
+		line.trim_start_matches(';')
+			.trim()
+			.strip_prefix("version:")
+			.map(str::trim)
+	})
+}
+
+/// Compare a deployed source to the bundled one
+pub fn check(deployed_source: Option<&str>) -> ContractStatus {
+	let Some(source) = deployed_source else {
+		return ContractStatus::Missing;
+	};
+
+	if normalize(source) == normalize(ASSET_CONTRACT_SOURCE) {
+		return ContractStatus::UpToDate;
+	}
+
+	let missing_functions: Vec<String> = REQUIRED_FUNCTIONS
+		.iter()
+		.filter(|function| !source.contains(*function))
+		.map(|function| function.to_string())
+		.collect();
+
+	if missing_functions.is_empty() {
+		ContractStatus::Drifted {
+			version: version(source).map(str::to_string),
+		}
+	} else {
+		ContractStatus::Incompatible { missing_functions }
+	}
+}
+
+fn normalize(source: &str) -> String {
+	source.replace("\r\n", "\n").trim().to_string()
+}
+
+/// Name the bundled source is deployed under when migrating the contract of
+/// the config
+pub fn versioned_name(config: &Config) -> anyhow::Result<ContractName> {
+	let name = config.contract_name.as_str();
+	let base = match name.rsplit_once("-v") {
+		Some((base, suffix))
+			if suffix.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+		{
+			base
+		}
+		_ => name,
+	};
+	let version = version(ASSET_CONTRACT_SOURCE)
+		.ok_or_else(|| anyhow!("The bundled contract has no version"))?;
+
+	ContractName::try_from(format!("{}-v{}", base, version.replace('.', "-")))
+		.map_err(|err| anyhow!("Invalid contract name: {:?}", err))
+}
+
+/// Compare the contract deployed under the name to the bundled source
+pub async fn verify(
+	config: &Config,
+	backend: &dyn StacksBackend,
+	name: ContractName,
+) -> anyhow::Result<ContractStatus> {
+	let contract = QualifiedContractIdentifier {
+		name,
+		..asset::contract_id(config)
+	};
+	let source = backend.get_contract_source(&contract).await?;
+
+	Ok(check(source.as_deref()))
+}
+
+/// Deploy the bundled source under the name, and the Bitcoin contract it
+/// calls if it is missing. Returns the txids of the deployments, without
+/// waiting for them to be confirmed.
+pub async fn deploy(
+	config: &Config,
+	backend: &mut dyn StacksBackend,
+	name: ContractName,
+) -> anyhow::Result<Vec<StacksTxId>> {
+	let mut txids = vec![];
+
+	let bitcoin_contract = QualifiedContractIdentifier {
+		name: ContractName::from(BITCOIN_CONTRACT_NAME),
+		..asset::contract_id(config)
+	};
+
+	if backend
+		.get_contract_source(&bitcoin_contract)
+		.await?
+		.is_none()
+	{
+		let source = match config.stacks_network {
+			StacksNetwork::Mainnet => BITCOIN_CONTRACT_MAINNET_SOURCE,
+			StacksNetwork::Testnet => BITCOIN_CONTRACT_SOURCE,
+		};
+
+		info!("Deploying contract {}", bitcoin_contract);
+		txids.push(
+			backend
+				.submit_tx(deployment(config, bitcoin_contract.name, source)?)
+				.await?,
+		);
+	}
+
+	match verify(config, backend, name.clone()).await? {
+		ContractStatus::Missing => {
+			info!("Deploying contract {}", name);
+			txids.push(
+				backend
+					.submit_tx(deployment(config, name, ASSET_CONTRACT_SOURCE)?)
+					.await?,
+			);
+		}
+		status => info!("Contract {} is already deployed: {:?}", name, status),
+	}
+
+	Ok(txids)
+}
+
+/// Deploy the bundled source under its versioned name if the contract of the
+/// config is not up to date. Returns the name the config should be pointed
+/// at, if it changed.
+pub async fn migrate(
+	config: &Config,
+	backend: &mut dyn StacksBackend,
+) -> anyhow::Result<(Option<ContractName>, Vec<StacksTxId>)> {
+	match verify(config, backend, config.contract_name.clone()).await? {
+		ContractStatus::UpToDate => Ok((None, vec![])),
+		ContractStatus::Missing => Err(anyhow!(
+			"Contract {} is not deployed, deploy it instead",
+			config.contract_name
+		)),
+		status => {
+			let name = versioned_name(config)?;
+			info!(
+				"Contract {} is not up to date ({:?}), migrating to {}",
+				config.contract_name, status, name
+			);
+
+			let txids = deploy(config, backend, name.clone()).await?;
+
+			Ok((Some(name), txids))
+		}
+	}
+}
+
+/// Refuse to operate against an incompatible contract, and deploy a missing
+/// one if the config enables it
+pub async fn ensure_compatible(
+	config: &Config,
+	stacks_client: &LockedClient,
+) -> anyhow::Result<()> {
+	let name = config.contract_name.clone();
+	let status =
+		verify(config, &*stacks_client.lock().await, name.clone()).await?;
+
+	match status {
+		ContractStatus::UpToDate => Ok(()),
+		ContractStatus::Drifted { version } => {
+			warn!(
+				"Contract {} differs from the bundled source, deployed version is {:?} and bundled version is {:?}",
+				name,
+				version,
+				version_of_bundled()
+			);
+			Ok(())
+		}
+		ContractStatus::Incompatible { missing_functions } => Err(anyhow!(
+			"Contract {} is incompatible, it lacks {:?}. Migrate it with `romeo contract migrate`.",
+			name,
+			missing_functions
+		)),
+		ContractStatus::Missing if config.auto_deploy_contract => {
+			let txids =
+				deploy(config, &mut *stacks_client.lock().await, name).await?;
+
+			for txid in txids {
+				wait_for_confirmation(stacks_client, txid).await?;
+			}
+
+			Ok(())
+		}
+		ContractStatus::Missing => Ok(()),
+	}
+}
+
+/// Run a `romeo contract` command
+pub async fn run_command(
+	config: Config,
+	action: ContractAction,
+) -> anyhow::Result<()> {
+	let stacks_client: LockedClient =
+		StacksClient::new(config.clone(), config.outbound.http_client()?)
+			.into();
+
+	match action {
+		ContractAction::Deploy => {
+			let txids = deploy(
+				&config,
+				&mut *stacks_client.lock().await,
+				config.contract_name.clone(),
+			)
+			.await?;
+
+			for txid in txids {
+				wait_for_confirmation(&stacks_client, txid).await?;
+			}
+
+			println!("Contract {} is deployed", config.contract_name);
+		}
+		ContractAction::Verify => {
+			let status = verify(
+				&config,
+				&*stacks_client.lock().await,
+				config.contract_name.clone(),
+			)
+			.await?;
+
+			println!("Contract {}: {:?}", config.contract_name, status);
+
+			if matches!(
+				status,
+				ContractStatus::Missing | ContractStatus::Incompatible { .. }
+			) {
+				return Err(anyhow!(
+					"Contract {} cannot be operated against",
+					config.contract_name
+				));
+			}
+		}
+		ContractAction::Migrate => {
+			let (name, txids) =
+				migrate(&config, &mut *stacks_client.lock().await).await?;
+
+			for txid in txids {
+				wait_for_confirmation(&stacks_client, txid).await?;
+			}
+
+			match name {
+				Some(name) => println!(
+					"Contract migrated to {}, set `contract_name` to it in the config",
+					name
+				),
+				None => {
+					println!("Contract {} is up to date", config.contract_name)
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn version_of_bundled() -> Option<&'static str> {
+	version(ASSET_CONTRACT_SOURCE)
+}
+
+fn deployment(
+	config: &Config,
+	name: ContractName,
+	source: &str,
+) -> anyhow::Result<StacksTransaction> {
+	let tx_version = match config.stacks_network {
+		StacksNetwork::Mainnet => TransactionVersion::Mainnet,
+		StacksNetwork::Testnet => TransactionVersion::Testnet,
+	};
+	let code_body = StacksString::from_str(source)
+		.ok_or_else(|| anyhow!("Invalid characters in contract {}", name))?;
+
+	Ok(StacksTransaction::new(
+		tx_version,
+		contract_call_auth(config),
+		TransactionPayload::SmartContract(
+			TransactionSmartContract { name, code_body },
+			Some(ClarityVersion::Clarity2),
+		),
+	))
+}
+
+async fn wait_for_confirmation(
+	stacks_client: &LockedClient,
+	txid: StacksTxId,
+) -> anyhow::Result<()> {
+	loop {
+		match stacks_client.lock().await.get_tx_status(txid).await? {
+			TransactionStatus::Confirmed => return Ok(()),
+			TransactionStatus::Rejected => {
+				return Err(anyhow!("Deployment {} was rejected", txid))
+			}
+			TransactionStatus::Broadcasted => {
+				sleep(CONFIRMATION_POLLING_INTERVAL).await
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_deployed_sources_are_checked() {
+		assert_eq!(check(None), ContractStatus::Missing);
+		assert_eq!(
+			check(Some(ASSET_CONTRACT_SOURCE)),
+			ContractStatus::UpToDate
+		);
+
+		let drifted = ASSET_CONTRACT_SOURCE
+			.replace(";; version: 0.1.0", ";; version: 0.0.9");
+		assert_eq!(
+			check(Some(&drifted)),
+			ContractStatus::Drifted {
+				version: Some("0.0.9".to_string())
+			}
+		);
+
+		let incompatible = ASSET_CONTRACT_SOURCE
+			.replace("(define-public (burn", "(define-public (destroy");
+		assert_eq!(
+			check(Some(&incompatible)),
+			ContractStatus::Incompatible {
+				missing_functions: vec!["(define-public (burn".to_string()]
+			}
+		);
+	}
+
+	#[test]
+	fn test_bundled_version_is_declared() {
+		assert_eq!(version_of_bundled(), Some("0.1.0"));
+	}
+}
@@ -0,0 +1,269 @@
+//! Registry of unique deposit addresses, so that deposits without an
+//! OP_RETURN output are attributed to the right principal
+
+use std::{
+	collections::HashMap,
+	fs::{self, File},
+	io::Write,
+	path::{Path, PathBuf},
+	sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use anyhow::anyhow;
+use bdk::bitcoin::{
+	secp256k1::Secp256k1,
+	util::bip32::{ChildNumber, ExtendedPubKey},
+	Address as BitcoinAddress, Network as BitcoinNetwork, Script,
+};
+use blockstack_lib::vm::types::PrincipalData;
+
+/// File name of the registry within the state directory
+pub const DEPOSIT_REGISTRY_FILE: &str = "deposit_addresses.json";
+
+/// Deposit address of a depositor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositAddress {
+	/// Derivation index of the key of the address
+	pub index: u32,
+	/// Taproot address the depositor sends deposits to
+	pub address: BitcoinAddress,
+	/// Principal the deposits are minted to
+	pub recipient: PrincipalData,
+}
+
+/// Persisted entry of the registry. Addresses are derived again when the
+/// registry is loaded.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Registration {
+	index: u32,
+	recipient: PrincipalData,
+}
+
+#[derive(Debug, Default)]
+struct Addresses {
+	registered: Vec<DepositAddress>,
+	by_script: HashMap<Script, usize>,
+	by_recipient: HashMap<PrincipalData, usize>,
+}
+
+impl Addresses {
+	fn insert(&mut self, deposit_address: DepositAddress) -> DepositAddress {
+		let position = self.registered.len();
+
+		self.by_script
+			.insert(deposit_address.address.script_pubkey(), position);
+		self.by_recipient
+			.insert(deposit_address.recipient.clone(), position);
+		self.registered.push(deposit_address.clone());
+
+		deposit_address
+	}
+}
+
+/// Derives a unique taproot address per depositor from the external chain of
+/// the peg wallet account, and persists the recipient of each of them in the
+/// state directory. The key at index 0 is the one of the peg wallet, so
+/// deposit addresses start at index 1.
+#[derive(Debug)]
+pub struct DepositRegistry {
+	path: PathBuf,
+	xpub: ExtendedPubKey,
+	network: BitcoinNetwork,
+	addresses: RwLock<Addresses>,
+}
+
+impl DepositRegistry {
+	/// Load the registry of the state directory, which is empty if it has
+	/// never been written. The addresses are derived from the extended public
+	/// key of the external chain of the peg wallet account.
+	pub fn open(
+		state_directory: impl AsRef<Path>,
+		xpub: ExtendedPubKey,
+		network: BitcoinNetwork,
+	) -> anyhow::Result<Self> {
+		let registry = Self {
+			path: state_directory.as_ref().join(DEPOSIT_REGISTRY_FILE),
+			xpub,
+			network,
+			addresses: Default::default(),
+		};
+
+		let registrations: Vec<Registration> = match File::open(&registry.path)
+		{
+			Ok(file) => serde_json::from_reader(file)?,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+			Err(err) => return Err(err.into()),
+		};
+
+		{
+			let mut addresses = registry.write()?;
+
+			for Registration { index, recipient } in registrations {
+				let address = registry.derive_address(index)?;
+
+				addresses.insert(DepositAddress {
+					index,
+					address,
+					recipient,
+				});
+			}
+		}
+
+		Ok(registry)
+	}
+
+	/// Get the deposit address of the recipient, registering a new one if it
+	/// has none
+	pub fn register(
+		&self,
+		recipient: PrincipalData,
+	) -> anyhow::Result<DepositAddress> {
+		let mut addresses = self.write()?;
+
+		if let Some(position) = addresses.by_recipient.get(&recipient) {
+			return Ok(addresses.registered[*position].clone());
+		}
+
+		let index = addresses.registered.len() as u32 + 1;
+		let deposit_address = DepositAddress {
+			index,
+			address: self.derive_address(index)?,
+			recipient,
+		};
+
+		let registrations: Vec<_> = addresses
+			.registered
+			.iter()
+			.chain([&deposit_address])
+			.map(|deposit_address| Registration {
+				index: deposit_address.index,
+				recipient: deposit_address.recipient.clone(),
+			})
+			.collect();
+
+		// Persisted before it is handed out, so that deposits to the address
+		// are attributed after a restart
+		self.persist(&registrations)?;
+
+		Ok(addresses.insert(deposit_address))
+	}
+
+	/// The registered deposit address paid by the output script, if any
+	pub fn by_script(&self, script: &Script) -> Option<DepositAddress> {
+		let addresses = self.read().ok()?;
+
+		addresses
+			.by_script
+			.get(script)
+			.map(|position| addresses.registered[*position].clone())
+	}
+
+	/// Output scripts of all the registered deposit addresses
+	pub fn scripts(&self) -> Vec<Script> {
+		self.read()
+			.map(|addresses| addresses.by_script.keys().cloned().collect())
+			.unwrap_or_default()
+	}
+
+	/// The deposit address registered for the recipient, if any
+	pub fn by_recipient(
+		&self,
+		recipient: &PrincipalData,
+	) -> Option<DepositAddress> {
+		let addresses = self.read().ok()?;
+
+		addresses
+			.by_recipient
+			.get(recipient)
+			.map(|position| addresses.registered[*position].clone())
+	}
+
+	fn derive_address(&self, index: u32) -> anyhow::Result<BitcoinAddress> {
+		let secp = Secp256k1::new();
+		let key = self
+			.xpub
+			.derive_pub(&secp, &[ChildNumber::from_normal_idx(index)?])?;
+
+		Ok(BitcoinAddress::p2tr(
+			&secp,
+			key.public_key.x_only_public_key().0,
+			None,
+			self.network,
+		))
+	}
+
+	fn persist(&self, registrations: &[Registration]) -> anyhow::Result<()> {
+		let tmp_path = self.path.with_extension("json.tmp");
+
+		let mut file = File::create(&tmp_path)?;
+		file.write_all(&serde_json::to_vec(registrations)?)?;
+		file.sync_all()?;
+
+		fs::rename(tmp_path, &self.path)?;
+
+		Ok(())
+	}
+
+	fn read(&self) -> anyhow::Result<RwLockReadGuard<'_, Addresses>> {
+		self.addresses
+			.read()
+			.map_err(|_| anyhow!("Cannot get deposit registry read lock"))
+	}
+
+	fn write(&self) -> anyhow::Result<RwLockWriteGuard<'_, Addresses>> {
+		self.addresses
+			.write()
+			.map_err(|_| anyhow!("Cannot get deposit registry write lock"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use bdk::bitcoin::util::bip32::DerivationPath;
+	use stacks_core::wallet::Wallet;
+
+	use super::*;
+
+	#[test]
+	fn test_registered_addresses_are_restored() {
+		let dir = std::env::temp_dir()
+			.join(format!("romeo-deposit-registry-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+
+		let wallet = Wallet::new("twice kind fence tip hidden tilt action fragile skin nothing glory cousin green tomorrow spring wrist shed math olympic multiply hip blue scout claw").unwrap();
+		let xpub = wallet
+			.xpub(&DerivationPath::from_str("m/86'/1'/0'/0").unwrap())
+			.unwrap();
+		let recipient =
+			PrincipalData::parse("ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM")
+				.unwrap();
+
+		let deposit_address = {
+			let registry =
+				DepositRegistry::open(&dir, xpub, BitcoinNetwork::Regtest)
+					.unwrap();
+			let deposit_address = registry.register(recipient.clone()).unwrap();
+
+			assert_eq!(deposit_address.index, 1);
+			assert_eq!(
+				registry.register(recipient.clone()).unwrap(),
+				deposit_address
+			);
+
+			deposit_address
+		};
+
+		let registry =
+			DepositRegistry::open(&dir, xpub, BitcoinNetwork::Regtest).unwrap();
+
+		assert_eq!(
+			registry.by_script(&deposit_address.address.script_pubkey()),
+			Some(deposit_address.clone())
+		);
+		assert_eq!(registry.by_recipient(&recipient), Some(deposit_address));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,492 @@
+//! Orchestration of a local regtest environment, made of a bitcoind regtest
+//! node and a stacks-node anchored to it. Funds the configured accounts,
+//! mines blocks on demand and drives a deposit and a withdrawal through the
+//! system, so that a deployment can be validated without testnet faucets.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use bdk::{
+	bitcoin::{
+		Address as BitcoinAddress, Network as BitcoinNetwork, OutPoint,
+		PrivateKey, Transaction, TxOut, Txid,
+	},
+	bitcoincore_rpc::{
+		json::{ScanTxOutRequest, Utxo},
+		RpcApi,
+	},
+	FeeRate, KeychainKind, LocalUtxo,
+};
+use blockstack_lib::burnchains::Txid as StacksTxId;
+use sbtc_core::operations::{
+	op_return::{deposit, withdrawal_request},
+	transaction_signer::TransactionSigner,
+	utils::{build_offline_psbt, finalize_psbt, OfflineFunding},
+};
+use stacks_core::{
+	address::StacksAddress,
+	utils::PrincipalData,
+	wallet::{BitcoinCredentials, Credentials, Wallet},
+};
+use tokio::{task::spawn_blocking, time::sleep};
+use tracing::info;
+
+use crate::{
+	bitcoin_client::rpc_pool::RpcPool,
+	config::{Config, DevenvAction},
+	contracts,
+	event::TransactionStatus,
+	stacks_client::{asset, LockedClient, StacksBackend, StacksClient},
+	system,
+};
+
+/// Blocks after which a coinbase output can be spent
+const COINBASE_MATURITY: u64 = 100;
+
+/// Blocks mined to each Bitcoin address being funded
+const FUNDING_BLOCKS: u64 = 5;
+
+/// Fee rate of the transactions of the scenario
+const SCENARIO_FEE_RATE: f32 = 10.0;
+
+/// Amount paid to the peg wallet for the fulfillment of the withdrawal
+const FULFILLMENT_FEE: u64 = 10_000;
+
+/// Interval between the blocks mined while waiting for the system
+const MINING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Blocks mined while waiting for the system before giving up
+const MAX_WAITING_BLOCKS: u32 = 120;
+
+/// Bitcoin regtest node and Stacks node of the environment
+pub struct Devenv {
+	config: Config,
+	rpc: Arc<RpcPool>,
+	stacks_client: LockedClient,
+	http_client: reqwest::Client,
+}
+
+impl Devenv {
+	/// Connect to the nodes of the config, which must be on regtest
+	pub fn new(config: Config) -> anyhow::Result<Self> {
+		if config.bitcoin_network != BitcoinNetwork::Regtest {
+			anyhow::bail!(
+				"The devenv requires bitcoin_network to be regtest, not {}",
+				config.bitcoin_network
+			);
+		}
+
+		let http_client = config.outbound.http_client()?;
+
+		Ok(Self {
+			rpc: Arc::new(RpcPool::new(
+				config.bitcoin_node_url.clone(),
+				config.bitcoin_rpc_auth.clone(),
+				config.outbound.socks5_proxy()?,
+				1,
+			)),
+			stacks_client: StacksClient::new(
+				config.clone(),
+				http_client.clone(),
+			)
+			.into(),
+			http_client,
+			config,
+		})
+	}
+
+	/// Mine blocks to the peg wallet. Returns the new height.
+	pub async fn mine(&self, blocks: u64) -> anyhow::Result<u64> {
+		self.mine_to(blocks, self.config.sbtc_wallet_address())
+			.await
+	}
+
+	/// Mine blocks to the address. Returns the new height.
+	pub async fn mine_to(
+		&self,
+		blocks: u64,
+		address: BitcoinAddress,
+	) -> anyhow::Result<u64> {
+		self.execute(move |client| {
+			client.generate_to_address(blocks, &address)?;
+			client.get_block_count()
+		})
+		.await
+	}
+
+	/// Fund the peg wallet with mature coinbase outputs, and the Stacks
+	/// accounts of the config with the STX faucet of the Stacks API
+	pub async fn fund(&self) -> anyhow::Result<()> {
+		self.mine(FUNDING_BLOCKS).await?;
+
+		let mut addresses = vec![self.config.stacks_credentials.address()];
+		addresses.extend(
+			self.config
+				.stacks_sponsor_credentials
+				.as_ref()
+				.map(Credentials::address),
+		);
+
+		for address in addresses {
+			let txid = self.request_stx(&address).await?;
+			info!("Requested STX for {} in {}", address, txid);
+		}
+
+		// Mature the coinbase outputs and confirm the faucet transactions
+		self.mine_to(COINBASE_MATURITY, self.burn_address()).await?;
+
+		Ok(())
+	}
+
+	/// Fund the environment, deploy the asset contract and run a deposit of
+	/// the amount followed by a withdrawal of half of it against the system.
+	/// The system is run alongside the scenario unless it runs elsewhere.
+	pub async fn run_scenario(
+		&self,
+		amount: u64,
+		external_system: bool,
+	) -> anyhow::Result<()> {
+		self.fund().await?;
+		self.deploy_contract().await?;
+
+		if external_system {
+			return self.deposit_and_withdraw(amount).await;
+		}
+
+		tokio::select! {
+			res = self.deposit_and_withdraw(amount) => res,
+			_ = system::run(self.config.clone()) => {
+				Err(anyhow!("The system stopped before the scenario completed"))
+			}
+		}
+	}
+
+	async fn deploy_contract(&self) -> anyhow::Result<()> {
+		let txids = contracts::deploy(
+			&self.config,
+			&mut *self.stacks_client.lock().await,
+			self.config.contract_name.clone(),
+		)
+		.await?;
+
+		for txid in txids {
+			self.wait_for(&format!("deployment {}", txid), || {
+				self.is_confirmed(txid)
+			})
+			.await?;
+		}
+
+		Ok(())
+	}
+
+	async fn deposit_and_withdraw(&self, amount: u64) -> anyhow::Result<()> {
+		let user = User::random()?;
+		self.mine_to(FUNDING_BLOCKS, user.funding_address()).await?;
+		self.mine_to(COINBASE_MATURITY, self.burn_address()).await?;
+
+		let contract = asset::contract_id(&self.config);
+		let sbtc_wallet_address = self.config.sbtc_wallet_address();
+
+		let psbt = deposit::create_offline_psbt(
+			&self.funding(&user).await?,
+			user.stacks.address().into(),
+			&sbtc_wallet_address,
+			amount,
+			BitcoinNetwork::Regtest,
+		)?;
+		let deposit_txid = self.sign_and_broadcast(&user, psbt).await?;
+		info!("Broadcasted deposit {}", deposit_txid);
+
+		self.wait_for(&format!("mint of deposit {}", deposit_txid), || {
+			self.is_processed(deposit_txid)
+		})
+		.await?;
+
+		let balance = asset::get_balance(
+			&*self.stacks_client.lock().await,
+			&contract,
+			principal(&user.stacks.address()),
+		)
+		.await?;
+		info!("Minted {} sBTC to {}", balance, user.stacks.address());
+
+		let withdrawal_amount = amount / 2;
+		let payee_address = user.bitcoin.address_p2tr();
+		let outputs = withdrawal_request::create_outputs(
+			&user.stacks.private_key()?,
+			&payee_address,
+			&sbtc_wallet_address,
+			withdrawal_amount,
+			FULFILLMENT_FEE,
+			BitcoinNetwork::Regtest,
+		)?;
+		let psbt = build_offline_psbt(&self.funding(&user).await?, &outputs)?;
+		let withdrawal_txid = self.sign_and_broadcast(&user, psbt).await?;
+		info!("Broadcasted withdrawal request {}", withdrawal_txid);
+
+		self.wait_for(
+			&format!("burn of withdrawal request {}", withdrawal_txid),
+			|| self.is_processed(withdrawal_txid),
+		)
+		.await?;
+
+		self.wait_for(
+			&format!("fulfillment of withdrawal request {}", withdrawal_txid),
+			|| async {
+				Ok(self
+					.scan(payee_address.clone())
+					.await?
+					.iter()
+					.any(|utxo| utxo.amount.to_sat() == withdrawal_amount))
+			},
+		)
+		.await?;
+
+		info!(
+			"Deposited {} and withdrew {} sats through {}",
+			amount, withdrawal_amount, contract
+		);
+
+		Ok(())
+	}
+
+	/// Mine a block at every interval until the condition holds
+	async fn wait_for<F, Fut>(
+		&self,
+		description: &str,
+		condition: F,
+	) -> anyhow::Result<()>
+	where
+		F: Fn() -> Fut,
+		Fut: std::future::Future<Output = anyhow::Result<bool>>,
+	{
+		for _ in 0..MAX_WAITING_BLOCKS {
+			if condition().await? {
+				return Ok(());
+			}
+
+			self.mine_to(1, self.burn_address()).await?;
+			sleep(MINING_INTERVAL).await;
+		}
+
+		Err(anyhow!(
+			"Gave up waiting for the {} after {} blocks",
+			description,
+			MAX_WAITING_BLOCKS
+		))
+	}
+
+	async fn is_confirmed(&self, txid: StacksTxId) -> anyhow::Result<bool> {
+		match self.stacks_client.lock().await.get_tx_status(txid).await? {
+			TransactionStatus::Confirmed => Ok(true),
+			TransactionStatus::Broadcasted => Ok(false),
+			TransactionStatus::Rejected => {
+				Err(anyhow!("Transaction {} was rejected", txid))
+			}
+		}
+	}
+
+	async fn is_processed(&self, txid: Txid) -> anyhow::Result<bool> {
+		let amount = asset::get_amount_by_btc_txid(
+			&*self.stacks_client.lock().await,
+			&asset::contract_id(&self.config),
+			txid,
+		)
+		.await?;
+
+		Ok(amount.is_some())
+	}
+
+	async fn funding(&self, user: &User) -> anyhow::Result<OfflineFunding> {
+		let height = self.execute(|client| client.get_block_count()).await?;
+		let utxos = self.scan(user.funding_address()).await?;
+
+		Ok(OfflineFunding {
+			utxos: spendable_utxos(utxos, height),
+			change_address: user.funding_address(),
+			fee_rate: FeeRate::from_sat_per_vb(SCENARIO_FEE_RATE),
+		})
+	}
+
+	async fn sign_and_broadcast(
+		&self,
+		user: &User,
+		mut psbt: bdk::bitcoin::psbt::PartiallySignedTransaction,
+	) -> anyhow::Result<Txid> {
+		user.bitcoin_signer()?.sign_psbt(&mut psbt)?;
+		let tx: Transaction = finalize_psbt(psbt)?;
+
+		self.execute(move |client| client.send_raw_transaction(&tx))
+			.await
+	}
+
+	async fn scan(&self, address: BitcoinAddress) -> anyhow::Result<Vec<Utxo>> {
+		let descriptor = format!("addr({})", address);
+
+		self.execute(move |client| {
+			client.scan_tx_out_set_blocking(&[ScanTxOutRequest::Single(
+				descriptor,
+			)])
+		})
+		.await
+		.map(|result| result.unspents)
+	}
+
+	async fn request_stx(
+		&self,
+		address: &StacksAddress,
+	) -> anyhow::Result<String> {
+		let mut url = self
+			.config
+			.stacks_node_url
+			.join("/extended/v1/faucets/stx")?;
+		url.query_pairs_mut()
+			.append_pair("address", &address.to_string());
+
+		let res: serde_json::Value = self
+			.http_client
+			.post(url)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		res["txId"]
+			.as_str()
+			.map(str::to_string)
+			.ok_or_else(|| anyhow!("Unexpected faucet response: {}", res))
+	}
+
+	/// Address the blocks which only need to be mined are paid to
+	fn burn_address(&self) -> BitcoinAddress {
+		self.config.bitcoin_credentials.address_p2wpkh()
+	}
+
+	async fn execute<F, T>(&self, f: F) -> anyhow::Result<T>
+	where
+		F: FnOnce(
+				&bdk::bitcoincore_rpc::Client,
+			) -> bdk::bitcoincore_rpc::Result<T>
+			+ Send
+			+ 'static,
+		T: Send + 'static,
+	{
+		let rpc = self.rpc.clone();
+
+		Ok(spawn_blocking(move || rpc.execute(f)).await???)
+	}
+}
+
+/// Depositor and withdrawer of the scenario, with fresh keys on every run
+struct User {
+	stacks: Credentials,
+	bitcoin: BitcoinCredentials,
+}
+
+impl User {
+	fn random() -> anyhow::Result<Self> {
+		let wallet = Wallet::random()?;
+
+		Ok(Self {
+			stacks: wallet.credentials(stacks_core::Network::Testnet, 0)?,
+			bitcoin: wallet.bitcoin_credentials(BitcoinNetwork::Regtest, 0)?,
+		})
+	}
+
+	/// Address of the outputs funding the transactions of the user
+	fn funding_address(&self) -> BitcoinAddress {
+		self.bitcoin.address_p2wpkh()
+	}
+
+	fn bitcoin_signer(&self) -> anyhow::Result<PrivateKey> {
+		Ok(PrivateKey::new(
+			self.bitcoin.private_key_p2wpkh()?,
+			BitcoinNetwork::Regtest,
+		))
+	}
+}
+
+/// Outputs of the scan which are mature at the height
+fn spendable_utxos(utxos: Vec<Utxo>, height: u64) -> Vec<LocalUtxo> {
+	utxos
+		.into_iter()
+		.filter(|utxo| utxo.height + COINBASE_MATURITY <= height)
+		.map(|utxo| LocalUtxo {
+			outpoint: OutPoint::new(utxo.txid, utxo.vout),
+			txout: TxOut {
+				value: utxo.amount.to_sat(),
+				script_pubkey: utxo.script_pub_key,
+			},
+			keychain: KeychainKind::External,
+			is_spent: false,
+		})
+		.collect()
+}
+
+fn principal(address: &StacksAddress) -> PrincipalData {
+	PrincipalData::Standard(address.clone().into())
+}
+
+/// Run a `romeo devenv` command
+pub async fn run_command(
+	config: Config,
+	action: DevenvAction,
+) -> anyhow::Result<()> {
+	let devenv = Devenv::new(config)?;
+
+	match action {
+		DevenvAction::Fund => {
+			devenv.fund().await?;
+
+			println!("Funded the configured accounts");
+		}
+		DevenvAction::Mine { blocks } => {
+			let height = devenv.mine(blocks).await?;
+
+			println!("Mined {} blocks, the height is {}", blocks, height);
+		}
+		DevenvAction::Scenario {
+			amount,
+			external_system,
+		} => {
+			devenv.run_scenario(amount, external_system).await?;
+
+			println!("The deposit and withdrawal scenario completed");
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use bdk::bitcoin::{Amount, Script};
+
+	use super::*;
+
+	#[test]
+	fn test_immature_outputs_are_not_spent() {
+		let utxo = |vout, height| {
+			Utxo {
+			txid: Txid::from_str(
+				"a1f2bd2db66d0df1f2d8c9f0e7a74db9ce2c0b8d8f4a4bde1b5f6a8d3c2b1a00",
+			)
+			.unwrap(),
+			vout,
+			script_pub_key: Script::new(),
+			descriptor: String::new(),
+			amount: Amount::from_sat(5_000_000_000),
+			height,
+		}
+		};
+
+		let spendable = spendable_utxos(vec![utxo(0, 100), utxo(1, 101)], 200);
+
+		assert_eq!(spendable.len(), 1);
+		assert_eq!(spendable[0].outpoint.vout, 0);
+		assert_eq!(spendable[0].txout.value, 5_000_000_000);
+	}
+}
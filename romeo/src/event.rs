@@ -23,11 +23,28 @@ pub enum Event {
 	/// A mint transaction has been created and broadcasted
 	MintBroadcasted(DepositInfo, StacksTxId),
 
+	/// The contract has already minted the deposit, so no mint transaction
+	/// was broadcasted
+	DepositAlreadyMinted(DepositInfo),
+
 	/// A burn transaction has been created and broadcasted
 	BurnBroadcasted(WithdrawalInfo, StacksTxId),
 
-	/// A fulfill transaction has been created and broadcasted
-	FulfillBroadcasted(WithdrawalInfo, BitcoinTxId),
+	/// The contract has already burned the withdrawal, so no burn transaction
+	/// was broadcasted
+	WithdrawalAlreadyBurned(WithdrawalInfo),
+
+	/// A fulfill transaction has been created and broadcasted at the fee rate
+	/// in sat/vB
+	FulfillBroadcasted(WithdrawalInfo, BitcoinTxId, Option<f32>),
+
+	/// A fulfill transaction of several withdrawals has been created and
+	/// broadcasted at the fee rate in sat/vB
+	FulfillmentBatchBroadcasted(Vec<WithdrawalInfo>, BitcoinTxId, f32),
+
+	/// A fulfill transaction has been replaced by one paying a higher fee rate
+	/// in sat/vB
+	FulfillmentFeeBumped(Vec<WithdrawalInfo>, BitcoinTxId, f32),
 
 	/// A stacks node has responded with an updated status regarding this txid
 	StacksTransactionUpdate(StacksTxId, TransactionStatus),
@@ -40,6 +57,41 @@ pub enum Event {
 
 	/// A wild bitcoin block has appeared
 	BitcoinBlock(u32, #[derivative(Debug = "ignore")] Block),
+
+	/// Consecutive bitcoin blocks fetched together while catching up with the
+	/// chain tip
+	BitcoinBlocks(#[derivative(Debug = "ignore")] Vec<(u32, Block)>),
+
+	/// The Bitcoin chain has been reorganized below the last processed block
+	Reorg {
+		/// Number of processed blocks that are no longer in the best chain
+		depth: u32,
+	},
+
+	/// An operator has overridden the processing of the deposit or withdrawal
+	/// of the Bitcoin transaction
+	OperationOverridden(BitcoinTxId, OperationOverride),
+}
+
+/// Manual intervention of an operator on a deposit or withdrawal
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	serde::Serialize,
+	serde::Deserialize,
+	clap::ValueEnum,
+)]
+pub enum OperationOverride {
+	/// Create the pending transaction of the operation again
+	Requeue,
+	/// Stop processing the operation
+	Abandon,
+	/// Consider the operation processed, without creating its pending
+	/// transactions
+	Complete,
 }
 
 /// Status of a broadcasted transaction, useful for implementing retry logic
@@ -47,8 +99,13 @@ pub enum Event {
 pub enum TransactionStatus {
 	/// Broadcasted to a node
 	Broadcasted,
-	/// This transaction has received
-	/// `Config::number_of_required_confirmations` confirmations
+	/// Mined, but with fewer than `Config::min_confirmations` confirmations
+	Confirming {
+		/// Number of confirmations so far
+		confirmations: u32,
+	},
+	/// This transaction has received `Config::min_confirmations`
+	/// confirmations
 	Confirmed,
 	/// There are indications that this transaction will never be mined
 	Rejected,
@@ -0,0 +1,283 @@
+//! Write-ahead log of the events, replayed on startup to restore the state
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+	fs::{self, File, OpenOptions},
+	io::{AsyncReadExt, AsyncWriteExt},
+};
+use tracing::{info, warn};
+
+use crate::{
+	event::Event,
+	state_file::{self, Header, STATE_VERSION},
+};
+
+/// File name of the event log within the state directory
+pub const EVENT_LOG_FILE: &str = "log.ndjson";
+
+/// Append-only log of newline delimited JSON events, after a [`Header`] line
+/// with the version of the events. Every event is durably written before it
+/// is applied to the state, so that replaying the log restores the state the
+/// daemon had when it stopped.
+pub struct EventLog(File);
+
+impl EventLog {
+	/// Open the log in the state directory and read its events. A partially
+	/// written last event, left by a crash during its write, is discarded
+	/// since it was never applied. A log of an older version is migrated.
+	pub async fn open(
+		state_directory: impl AsRef<Path>,
+	) -> anyhow::Result<(Self, Vec<Event>)> {
+		let (event_log, events, _) = Self::load(state_directory).await?;
+
+		Ok((event_log, events))
+	}
+
+	/// Migrate the log of the state directory to the current version,
+	/// returning the version it had, or `None` if there is no log
+	pub async fn migrate(
+		state_directory: impl AsRef<Path>,
+	) -> anyhow::Result<Option<u32>> {
+		if !state_directory.as_ref().join(EVENT_LOG_FILE).exists() {
+			return Ok(None);
+		}
+
+		let (_, _, version) = Self::load(state_directory).await?;
+
+		Ok(version)
+	}
+
+	/// Read the events of the log of the state directory without modifying
+	/// it. The events of a log of an older version are only migrated in
+	/// memory.
+	pub async fn read(
+		state_directory: impl AsRef<Path>,
+	) -> anyhow::Result<Vec<Event>> {
+		let bytes =
+			match fs::read(state_directory.as_ref().join(EVENT_LOG_FILE)).await
+			{
+				Ok(bytes) => bytes,
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+					return Ok(vec![])
+				}
+				Err(err) => return Err(err.into()),
+			};
+
+		Ok(parse_log(&bytes)?.events)
+	}
+
+	async fn load(
+		state_directory: impl AsRef<Path>,
+	) -> anyhow::Result<(Self, Vec<Event>, Option<u32>)> {
+		let path = state_directory.as_ref().join(EVENT_LOG_FILE);
+
+		let mut file = OpenOptions::new()
+			.create(true)
+			.read(true)
+			.append(true)
+			.open(&path)
+			.await?;
+
+		let mut bytes = vec![];
+		file.read_to_end(&mut bytes).await?;
+
+		let log = parse_log(&bytes)?;
+
+		if log.len < bytes.len() {
+			warn!(
+				"Discarding {} bytes of a partially written event at the end of the event log",
+				bytes.len() - log.len
+			);
+		}
+
+		if log.version != Some(STATE_VERSION) {
+			if let Some(version) = log.version {
+				info!(
+					"Migrating the event log from version {} to {}",
+					version, STATE_VERSION
+				);
+			}
+
+			file = rewrite(&path, &log.events).await?;
+		} else if log.len < bytes.len() {
+			file.set_len(log.len as u64).await?;
+			file.sync_all().await?;
+		}
+
+		Ok((Self(file), log.events, log.version))
+	}
+
+	/// Durably append the event to the log
+	pub async fn append(&mut self, event: &Event) -> anyhow::Result<()> {
+		let mut bytes = serde_json::to_vec(event)?;
+		bytes.push(b'\n');
+
+		self.0.write_all(&bytes).await?;
+		self.0.sync_data().await?;
+
+		Ok(())
+	}
+}
+
+/// Events of a log
+struct ParsedLog {
+	/// Version of the events, or `None` for an empty log
+	version: Option<u32>,
+	events: Vec<Event>,
+	/// Length of the log the events span
+	len: usize,
+}
+
+/// Parses the events of the log, migrating them if they were written at an
+/// older version. Only the last event may be incomplete.
+fn parse_log(bytes: &[u8]) -> anyhow::Result<ParsedLog> {
+	let mut version = None;
+	let mut events = vec![];
+	let mut offset = 0;
+
+	while offset < bytes.len() {
+		let rest = &bytes[offset..];
+
+		let Some(line_len) = rest.iter().position(|byte| *byte == b'\n') else {
+			// No newline, the write of the last event did not complete
+			break;
+		};
+
+		let line = &rest[..line_len];
+		let line_offset = offset;
+		offset += line_len + 1;
+
+		if line.iter().all(u8::is_ascii_whitespace) {
+			continue;
+		}
+
+		let corrupted = |err: serde_json::Error| {
+			anyhow!(
+				"Corrupted event at byte {} of the log: {}",
+				line_offset,
+				err
+			)
+		};
+
+		let event = if version == Some(STATE_VERSION) {
+			serde_json::from_slice(line).map_err(corrupted)?
+		} else {
+			let mut value: Value =
+				serde_json::from_slice(line).map_err(corrupted)?;
+
+			if version.is_none() {
+				if let Ok(header) = Header::deserialize(&value) {
+					state_file::check_version(header.version)?;
+					version = Some(header.version);
+					continue;
+				}
+			}
+
+			// Logs written before the header are at version 0
+			let version = *version.get_or_insert(0);
+
+			state_file::migrate_event(&mut value, version)?;
+			serde_json::from_value(value).map_err(corrupted)?
+		};
+
+		events.push(event);
+	}
+
+	Ok(ParsedLog {
+		version,
+		events,
+		len: offset,
+	})
+}
+
+/// Atomically replace the log with the events at the current version,
+/// returning the new log opened for appending
+async fn rewrite(path: &Path, events: &[Event]) -> anyhow::Result<File> {
+	let tmp_path = path.with_extension("ndjson.tmp");
+
+	let mut bytes = serde_json::to_vec(&Header {
+		version: STATE_VERSION,
+	})?;
+	bytes.push(b'\n');
+
+	for event in events {
+		bytes.extend(serde_json::to_vec(event)?);
+		bytes.push(b'\n');
+	}
+
+	let mut file = File::create(&tmp_path).await?;
+	file.write_all(&bytes).await?;
+	file.sync_all().await?;
+
+	fs::rename(tmp_path, path).await?;
+
+	Ok(OpenOptions::new()
+		.read(true)
+		.append(true)
+		.open(path)
+		.await?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn log(events: &[Event]) -> Vec<u8> {
+		events
+			.iter()
+			.flat_map(|event| {
+				let mut bytes = serde_json::to_vec(event).unwrap();
+				bytes.push(b'\n');
+				bytes
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_partially_written_event_is_discarded() {
+		let mut bytes =
+			log(&[Event::ContractBlockHeight(1, 2), Event::Reorg { depth: 1 }]);
+		let valid_len = bytes.len();
+
+		bytes.extend_from_slice(br#"{"Reorg":{"dep"#);
+
+		let log = parse_log(&bytes).unwrap();
+
+		assert_eq!(log.events.len(), 2);
+		assert_eq!(log.len, valid_len);
+	}
+
+	#[test]
+	fn test_corrupted_event_is_an_error() {
+		let mut bytes = b"not an event\n".to_vec();
+		bytes.extend(log(&[Event::Reorg { depth: 1 }]));
+
+		assert!(parse_log(&bytes).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_appended_events_are_replayed() {
+		let dir = std::env::temp_dir()
+			.join(format!("romeo-event-log-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		{
+			let (mut event_log, events) = EventLog::open(&dir).await.unwrap();
+			assert!(events.is_empty());
+
+			event_log
+				.append(&Event::ContractBlockHeight(1, 2))
+				.await
+				.unwrap();
+		}
+
+		let (_, events) = EventLog::open(&dir).await.unwrap();
+		assert!(matches!(events[..], [Event::ContractBlockHeight(1, 2)]));
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,370 @@
+//! Listener for the event observer of a stacks-node, so that Stacks blocks
+//! and the outcome of contract calls are pushed to the daemon instead of being
+//! polled for. The node is pointed at the listener with an
+//! `[[events_observer]]` entry in its config.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	convert::Infallible,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use blockstack_lib::{
+	burnchains::Txid as StacksTxId,
+	chainstate::stacks::StacksTransaction,
+	codec::StacksMessageCodec,
+	vm::{types::QualifiedContractIdentifier, ContractName},
+};
+use hyper::{
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use stacks_core::{
+	address::StacksAddress, clarity::Value as ClarityValue, uint::Uint256,
+};
+use tokio::{sync::watch, time::timeout};
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+	config::Config,
+	event::TransactionStatus,
+	stacks_client::{StacksAccount, StacksBackend},
+};
+
+/// Number of pushed blocks kept until they are fetched
+const MAX_OBSERVED_BLOCKS: usize = 256;
+
+/// Time after which the node is asked for a block which has not been pushed
+const BLOCK_PUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Stacks blocks pushed by the node
+#[derive(Debug)]
+pub struct ObservedBlocks {
+	blocks: Mutex<BTreeMap<u32, ObservedBlock>>,
+	tip_height: watch::Sender<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct ObservedBlock {
+	txs: Vec<StacksTransaction>,
+	statuses: HashMap<StacksTxId, TransactionStatus>,
+}
+
+impl Default for ObservedBlocks {
+	fn default() -> Self {
+		Self {
+			blocks: Default::default(),
+			tip_height: watch::channel(0).0,
+		}
+	}
+}
+
+impl ObservedBlocks {
+	fn insert(&self, block_height: u32, block: ObservedBlock) {
+		let mut blocks = self.blocks.lock().unwrap();
+
+		blocks.insert(block_height, block);
+
+		while blocks.len() > MAX_OBSERVED_BLOCKS {
+			blocks.pop_first();
+		}
+
+		self.tip_height.send_if_modified(|tip_height| {
+			let modified = block_height > *tip_height;
+			*tip_height = (*tip_height).max(block_height);
+
+			modified
+		});
+	}
+
+	fn transactions(
+		&self,
+		block_height: u32,
+	) -> Option<Vec<StacksTransaction>> {
+		self.blocks
+			.lock()
+			.unwrap()
+			.get(&block_height)
+			.map(|block| block.txs.clone())
+	}
+
+	fn status(&self, txid: StacksTxId) -> Option<TransactionStatus> {
+		self.blocks
+			.lock()
+			.unwrap()
+			.values()
+			.find_map(|block| block.statuses.get(&txid).cloned())
+	}
+}
+
+/// Serve the event observer endpoints of the node at the address in the
+/// background. Must be called within a Tokio runtime.
+pub fn serve(address: SocketAddr, blocks: Arc<ObservedBlocks>) {
+	let make_service = make_service_fn(move |_| {
+		let blocks = blocks.clone();
+
+		async move {
+			Ok::<_, Infallible>(service_fn(move |req| {
+				let blocks = blocks.clone();
+
+				async move { Ok::<_, Infallible>(handle(&blocks, req).await) }
+			}))
+		}
+	});
+
+	tokio::spawn(async move {
+		info!("Listening for Stacks node events at {}", address);
+
+		if let Err(err) = Server::bind(&address).serve(make_service).await {
+			warn!("Event observer server failed: {}", err);
+		}
+	});
+}
+
+async fn handle(blocks: &ObservedBlocks, req: Request<Body>) -> Response<Body> {
+	if req.method() != Method::POST {
+		return empty(StatusCode::METHOD_NOT_ALLOWED);
+	}
+
+	if req.uri().path() != "/new_block" {
+		// The node retries every event until it is acknowledged, even the
+		// ones of no interest
+		return empty(StatusCode::OK);
+	}
+
+	let block = match hyper::body::to_bytes(req.into_body()).await {
+		Ok(body) => serde_json::from_slice::<NewBlock>(&body),
+		Err(err) => return empty_with_warning(err),
+	};
+
+	match block {
+		Ok(block) => {
+			trace!("Stacks block {} pushed by the node", block.block_height);
+			blocks.insert(block.block_height, block.into());
+
+			empty(StatusCode::OK)
+		}
+		Err(err) => empty_with_warning(err),
+	}
+}
+
+fn empty_with_warning(err: impl std::fmt::Display) -> Response<Body> {
+	warn!("Could not parse the block pushed by the node: {}", err);
+
+	empty(StatusCode::BAD_REQUEST)
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+	Response::builder()
+		.status(status)
+		.body(Body::empty())
+		.expect("Cannot build response")
+}
+
+/// Payload of the `/new_block` event
+#[derive(serde::Deserialize)]
+struct NewBlock {
+	block_height: u32,
+	transactions: Vec<NewBlockTransaction>,
+}
+
+#[derive(serde::Deserialize)]
+struct NewBlockTransaction {
+	txid: String,
+	raw_tx: String,
+	status: String,
+}
+
+impl From<NewBlock> for ObservedBlock {
+	fn from(block: NewBlock) -> Self {
+		let mut txs = Vec::with_capacity(block.transactions.len());
+		let mut statuses = HashMap::new();
+
+		for tx in block.transactions {
+			let status = match tx.status.as_str() {
+				"success" => TransactionStatus::Confirmed,
+				"abort_by_response" | "abort_by_post_condition" => {
+					TransactionStatus::Rejected
+				}
+				_ => TransactionStatus::Broadcasted,
+			};
+
+			if let Ok(txid) =
+				StacksTxId::from_hex(tx.txid.trim_start_matches("0x"))
+			{
+				statuses.insert(txid, status);
+			}
+
+			// Burnchain operations have no raw transaction
+			let decoded = hex::decode(tx.raw_tx.trim_start_matches("0x"))
+				.ok()
+				.and_then(|bytes| {
+					StacksTransaction::consensus_deserialize(&mut &bytes[..])
+						.ok()
+				});
+
+			match decoded {
+				Some(decoded) => txs.push(decoded),
+				None => debug!("Skipping Stacks transaction {}", tx.txid),
+			}
+		}
+
+		Self { txs, statuses }
+	}
+}
+
+/// Stacks backend reading the blocks pushed by the node, falling back to the
+/// inner backend for the blocks it missed and for everything else
+#[derive(Debug)]
+pub struct ObservedStacksBackend<B> {
+	inner: B,
+	blocks: Arc<ObservedBlocks>,
+}
+
+impl<B> ObservedStacksBackend<B> {
+	/// Create a backend reading the blocks pushed by the node
+	pub fn new(inner: B, blocks: Arc<ObservedBlocks>) -> Self {
+		Self { inner, blocks }
+	}
+}
+
+#[async_trait]
+impl<B: StacksBackend> StacksBackend for ObservedStacksBackend<B> {
+	async fn submit_tx(
+		&mut self,
+		tx: StacksTransaction,
+	) -> anyhow::Result<StacksTxId> {
+		self.inner.submit_tx(tx).await
+	}
+
+	async fn get_tx_status(
+		&mut self,
+		txid: StacksTxId,
+	) -> anyhow::Result<TransactionStatus> {
+		match self.blocks.status(txid) {
+			Some(status) => Ok(status),
+			None => self.inner.get_tx_status(txid).await,
+		}
+	}
+
+	async fn get_account(
+		&self,
+		address: &StacksAddress,
+	) -> anyhow::Result<StacksAccount> {
+		self.inner.get_account(address).await
+	}
+
+	async fn call_read_only(
+		&self,
+		contract: &QualifiedContractIdentifier,
+		function_name: &str,
+		args: &[ClarityValue],
+	) -> anyhow::Result<ClarityValue> {
+		self.inner
+			.call_read_only(contract, function_name, args)
+			.await
+	}
+
+	async fn get_contract_source(
+		&self,
+		contract: &QualifiedContractIdentifier,
+	) -> anyhow::Result<Option<String>> {
+		self.inner.get_contract_source(contract).await
+	}
+
+	async fn get_block(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<Vec<StacksTransaction>> {
+		let mut tip_height = self.blocks.tip_height.subscribe();
+
+		loop {
+			if let Some(txs) = self.blocks.transactions(block_height) {
+				return Ok(txs);
+			}
+
+			let pushed_tip_height = *tip_height.borrow();
+
+			// Blocks pushed before the daemon started listening, or evicted
+			// before they were fetched
+			if pushed_tip_height > block_height
+				|| self.inner.get_tip_height().await? >= block_height
+			{
+				return self.inner.get_block(block_height).await;
+			}
+
+			let _ = timeout(BLOCK_PUSH_TIMEOUT, tip_height.changed()).await;
+		}
+	}
+
+	async fn get_tip_height(&self) -> anyhow::Result<u32> {
+		self.inner.get_tip_height().await
+	}
+
+	async fn get_contract_block_height(
+		&self,
+		name: ContractName,
+	) -> anyhow::Result<u32> {
+		self.inner.get_contract_block_height(name).await
+	}
+
+	async fn get_bitcoin_block_height(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<u32> {
+		self.inner.get_bitcoin_block_height(block_height).await
+	}
+
+	async fn get_block_hash_from_bitcoin_height(
+		&self,
+		height: u32,
+	) -> anyhow::Result<Uint256> {
+		self.inner.get_block_hash_from_bitcoin_height(height).await
+	}
+
+	fn reload_config(&mut self, config: Config) {
+		self.inner.reload_config(config)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::stacks_client::mock::MockStacksClient;
+
+	#[tokio::test]
+	async fn test_pushed_blocks_are_served() {
+		let blocks = Arc::new(ObservedBlocks::default());
+		let mock = MockStacksClient::default();
+		let mut backend =
+			ObservedStacksBackend::new(mock.clone(), blocks.clone());
+
+		let txid = StacksTxId([1; 32]);
+		let payload = serde_json::json!({
+			"block_height": 5,
+			"transactions": [{
+				"txid": format!("0x{}", txid),
+				"raw_tx": "0x00",
+				"status": "success",
+			}],
+		});
+		let req = Request::post("/new_block")
+			.body(Body::from(payload.to_string()))
+			.unwrap();
+
+		assert_eq!(handle(&blocks, req).await.status(), StatusCode::OK);
+		assert_eq!(backend.get_block(5).await.unwrap().len(), 0);
+		assert_eq!(
+			backend.get_tx_status(txid).await.unwrap(),
+			TransactionStatus::Confirmed
+		);
+
+		// Missed blocks are read from the node
+		mock.state().tip_height = 5;
+		mock.state().blocks.insert(4, vec![]);
+		assert!(backend.get_block(4).await.is_ok());
+	}
+}
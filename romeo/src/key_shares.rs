@@ -0,0 +1,79 @@
+//! Key shares of the threshold signers computed by a distributed key
+//! generation, so that the group key of the peg wallet survives restarts
+
+use std::{
+	fs::{self, File},
+	io::Write,
+	path::Path,
+};
+
+use sbtc_core::signer::frost::KeyShares;
+
+/// File name of the key shares within the state directory
+pub const KEY_SHARES_FILE: &str = "key-shares.json";
+
+/// Read the key shares of the state directory, if they have been written
+pub fn load(state_directory: &Path) -> anyhow::Result<Option<KeyShares>> {
+	let path = state_directory.join(KEY_SHARES_FILE);
+
+	if !path.exists() {
+		return Ok(None);
+	}
+
+	Ok(Some(serde_json::from_reader(File::open(path)?)?))
+}
+
+/// Write the key shares to the state directory, readable by the owner only.
+/// They replace the previous ones atomically, so a crash never leaves partial
+/// key shares.
+pub fn save(
+	key_shares: &KeyShares,
+	state_directory: &Path,
+) -> anyhow::Result<()> {
+	let path = state_directory.join(KEY_SHARES_FILE);
+	let tmp_path = path.with_extension("json.tmp");
+
+	let mut file = File::create(&tmp_path)?;
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+
+		file.set_permissions(fs::Permissions::from_mode(0o600))?;
+	}
+
+	file.write_all(&serde_json::to_vec(key_shares)?)?;
+	file.sync_all()?;
+
+	fs::rename(tmp_path, path)?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use p256k1::{point::Point, scalar::Scalar};
+
+	use super::*;
+
+	#[test]
+	fn test_saved_key_shares_are_loaded() {
+		let dir = std::env::temp_dir()
+			.join(format!("romeo-key-shares-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+
+		assert_eq!(load(&dir).unwrap(), None);
+
+		let private_key = Scalar::from(7);
+		let key_shares = KeyShares {
+			signer_id: 1,
+			group_key: Point::from(private_key),
+			private_keys: vec![(2, private_key)],
+		};
+		save(&key_shares, &dir).unwrap();
+
+		assert_eq!(load(&dir).unwrap(), Some(key_shares));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -6,11 +6,26 @@
 //! and respond the same way the final sBTC system is intended to.
 #![forbid(missing_docs)]
 
+pub mod admin;
+pub mod api;
 pub mod bitcoin_client;
+pub mod checkpoint;
 pub mod config;
+pub mod contracts;
+pub mod deposit_registry;
+#[cfg(feature = "devenv")]
+pub mod devenv;
 pub mod event;
+pub mod event_log;
+pub mod event_observer;
+pub mod key_shares;
+pub mod logging;
+pub mod metrics;
+pub mod outbound;
 pub mod proof_data;
+pub mod redact;
 pub mod stacks_client;
 pub mod state;
+pub mod state_file;
 pub mod system;
 pub mod task;
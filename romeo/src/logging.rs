@@ -0,0 +1,85 @@
+//! Logging
+
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use bdk::bitcoin::Txid as BitcoinTxId;
+use tracing_subscriber::{
+	filter::LevelFilter, fmt, layer::SubscriberExt, reload,
+	util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+use crate::config::LogFormat;
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Install the global subscriber, filtering with the directives if any, or
+/// else with the `RUST_LOG` environment variable
+pub fn init(
+	log_level: Option<&str>,
+	log_format: LogFormat,
+) -> anyhow::Result<()> {
+	let (filter, handle) = reload::Layer::new(env_filter(log_level)?);
+
+	let (text, json) = match log_format {
+		LogFormat::Text => {
+			(Some(fmt::layer().compact().with_ansi(false)), None)
+		}
+		LogFormat::Json => (
+			None,
+			Some(
+				fmt::layer()
+					.json()
+					.with_current_span(true)
+					.with_span_list(true),
+			),
+		),
+	};
+
+	tracing_subscriber::registry()
+		.with(filter)
+		.with(text)
+		.with(json)
+		.init();
+
+	FILTER
+		.set(handle)
+		.map_err(|_| anyhow!("Logging is already initialized"))
+}
+
+/// Replace the filter of the global subscriber
+pub fn set_log_level(log_level: Option<&str>) -> anyhow::Result<()> {
+	FILTER
+		.get()
+		.ok_or_else(|| anyhow!("Logging is not initialized"))?
+		.reload(env_filter(log_level)?)?;
+
+	Ok(())
+}
+
+/// Correlation ID of the deposits and withdrawals, the txids of their Bitcoin
+/// request transactions, recorded as the `operation` field of their spans
+pub fn correlation_id(operation_ids: &[BitcoinTxId]) -> Option<String> {
+	if operation_ids.is_empty() {
+		return None;
+	}
+
+	Some(
+		operation_ids
+			.iter()
+			.map(ToString::to_string)
+			.collect::<Vec<_>>()
+			.join(","),
+	)
+}
+
+/// Filter of the directives if any, or else of the `RUST_LOG` environment
+/// variable, logging at the info level by default
+pub fn env_filter(log_level: Option<&str>) -> anyhow::Result<EnvFilter> {
+	Ok(match log_level {
+		Some(directives) => EnvFilter::try_new(directives)?,
+		None => EnvFilter::builder()
+			.with_default_directive(LevelFilter::INFO.into())
+			.from_env_lossy(),
+	})
+}
@@ -1,23 +1,34 @@
 use clap::Parser;
-use tracing_subscriber::{
-	filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt,
-};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	tracing_subscriber::registry()
-		.with(tracing_subscriber::fmt::layer().compact().with_ansi(false))
-		.with(
-			tracing_subscriber::EnvFilter::builder()
-				.with_default_directive(LevelFilter::INFO.into())
-				.from_env_lossy(),
-		)
-		.init();
-
 	let args = romeo::config::Cli::parse();
-	let config = romeo::config::Config::from_path(args.config_file)?;
+	let mut config = romeo::config::Config::from_path(args.config_file)?;
+	config.start_height = args.start_height;
+	config.resume = args.resume;
+
+	romeo::logging::init(config.log_level.as_deref(), config.log_format)?;
 
-	romeo::system::run(config).await;
+	match args.command {
+		Some(romeo::config::Command::Contract { action }) => {
+			romeo::contracts::run_command(config, action).await
+		}
+		Some(romeo::config::Command::Op {
+			action,
+			txid,
+			contract,
+		}) => romeo::admin::run_command(config, action, txid, contract).await,
+		Some(romeo::config::Command::State { action }) => {
+			romeo::state_file::run_command(config, action).await
+		}
+		#[cfg(feature = "devenv")]
+		Some(romeo::config::Command::Devenv { action }) => {
+			romeo::devenv::run_command(config, action).await
+		}
+		None => {
+			romeo::system::run(config).await;
 
-	Ok(())
+			Ok(())
+		}
+	}
 }
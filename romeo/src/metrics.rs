@@ -0,0 +1,316 @@
+//! Prometheus metrics, served over HTTP when the `metrics` feature is enabled
+//! and a metrics address is configured. Without the feature, recording a
+//! metric does nothing.
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+
+use std::{net::SocketAddr, time::Duration};
+
+use crate::{event::Event, state::State, task::Task};
+
+/// Client of a remote service whose requests are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestClient {
+	/// Bitcoin node RPC
+	BitcoinRpc,
+	/// Electrum server
+	Electrum,
+	/// Stacks node API
+	Stacks,
+}
+
+#[cfg(feature = "metrics")]
+impl RequestClient {
+	fn as_str(&self) -> &'static str {
+		match self {
+			Self::BitcoinRpc => "bitcoin_rpc",
+			Self::Electrum => "electrum",
+			Self::Stacks => "stacks",
+		}
+	}
+}
+
+/// Name of the task, used as a metric label
+pub fn task_name(task: &Task) -> &'static str {
+	match task {
+		Task::GetContractBlockHeight => "get_contract_block_height",
+		Task::UpdateContractPublicKey => "update_contract_public_key",
+		Task::CreateMint(_) => "create_mint",
+		Task::CreateBurn(_) => "create_burn",
+		Task::CreateFulfillment(_) => "create_fulfillment",
+		Task::BumpFulfillmentFee(..) => "bump_fulfillment_fee",
+		Task::CheckBitcoinTransactionStatus(_) => {
+			"check_bitcoin_transaction_status"
+		}
+		Task::CheckStacksTransactionStatus(_) => {
+			"check_stacks_transaction_status"
+		}
+		Task::FetchStacksBlock(_) => "fetch_stacks_block",
+		Task::FetchBitcoinBlock(_) => "fetch_bitcoin_block",
+		Task::FindBitcoinForkPoint(_) => "find_bitcoin_fork_point",
+	}
+}
+
+/// Record a task run and its duration
+pub fn task_finished(task: &'static str, duration: Duration) {
+	#[cfg(feature = "metrics")]
+	registry::TASK_DURATION
+		.with_label_values(&[task])
+		.observe(duration.as_secs_f64());
+}
+
+/// Record the event returned by a task. Replayed events are not recorded.
+pub fn event_emitted(event: &Event) {
+	#[cfg(feature = "metrics")]
+	registry::record_event(event);
+}
+
+/// Record the chain tips and deposits of the state, after the replay and
+/// after every update
+pub fn state_updated(state: &State) {
+	#[cfg(feature = "metrics")]
+	registry::record_state(state);
+}
+
+/// Record a request to a remote service, after its retries
+pub fn request_finished(client: RequestClient, success: bool) {
+	#[cfg(feature = "metrics")]
+	{
+		registry::REQUESTS
+			.with_label_values(&[client.as_str()])
+			.inc();
+
+		if !success {
+			registry::REQUEST_ERRORS
+				.with_label_values(&[client.as_str()])
+				.inc();
+		}
+	}
+}
+
+/// Record the confirmed balance of the peg wallet in sats
+pub fn wallet_balance(sats: u64) {
+	#[cfg(feature = "metrics")]
+	registry::WALLET_BALANCE.set(sats as i64);
+}
+
+/// Serve the metrics at the address in the background. Must be called within
+/// a Tokio runtime.
+pub fn serve(address: SocketAddr) {
+	#[cfg(feature = "metrics")]
+	registry::serve(address);
+
+	#[cfg(not(feature = "metrics"))]
+	tracing::warn!(
+		"Metrics are not served at {}, romeo is built without the metrics feature",
+		address
+	);
+}
+
+#[cfg(feature = "metrics")]
+mod registry {
+	use std::{convert::Infallible, net::SocketAddr};
+
+	use hyper::{
+		header::CONTENT_TYPE,
+		service::{make_service_fn, service_fn},
+		Body, Method, Request, Response, Server, StatusCode,
+	};
+	use once_cell::sync::Lazy;
+	use prometheus::{
+		register_histogram, register_histogram_vec, register_int_counter,
+		register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+		Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+		IntGaugeVec, TextEncoder,
+	};
+	use tracing::{info, warn};
+
+	use crate::{event::Event, state::State};
+
+	pub static BLOCKS_PROCESSED: Lazy<IntCounterVec> = Lazy::new(|| {
+		register_int_counter_vec!(
+			"romeo_blocks_processed_total",
+			"Blocks processed",
+			&["chain"]
+		)
+		.unwrap()
+	});
+
+	pub static CHAIN_TIP: Lazy<IntGaugeVec> = Lazy::new(|| {
+		register_int_gauge_vec!(
+			"romeo_chain_tip_height",
+			"Height of the last processed block",
+			&["chain"]
+		)
+		.unwrap()
+	});
+
+	pub static DEPOSITS_DETECTED: Lazy<IntGauge> = Lazy::new(|| {
+		register_int_gauge!(
+			"romeo_deposits_detected",
+			"Deposits detected in Bitcoin blocks"
+		)
+		.unwrap()
+	});
+
+	pub static MINTS_BROADCASTED: Lazy<IntCounter> = Lazy::new(|| {
+		register_int_counter!(
+			"romeo_mints_broadcasted_total",
+			"Mint transactions broadcasted"
+		)
+		.unwrap()
+	});
+
+	pub static FULFILLMENTS_BROADCASTED: Lazy<IntCounter> = Lazy::new(|| {
+		register_int_counter!(
+			"romeo_fulfillments_broadcasted_total",
+			"Withdrawals whose fulfillment transaction has been broadcasted"
+		)
+		.unwrap()
+	});
+
+	pub static FULFILLMENT_LATENCY: Lazy<Histogram> = Lazy::new(|| {
+		register_histogram!(
+			"romeo_fulfillment_latency_blocks",
+			"Bitcoin blocks between a withdrawal request and the broadcast of its fulfillment",
+			vec![1.0, 2.0, 3.0, 6.0, 12.0, 24.0, 72.0, 144.0]
+		)
+		.unwrap()
+	});
+
+	pub static REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+		register_int_counter_vec!(
+			"romeo_requests_total",
+			"Requests to remote services",
+			&["client"]
+		)
+		.unwrap()
+	});
+
+	pub static REQUEST_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+		register_int_counter_vec!(
+			"romeo_request_errors_total",
+			"Requests to remote services failing after their retries",
+			&["client"]
+		)
+		.unwrap()
+	});
+
+	pub static WALLET_BALANCE: Lazy<IntGauge> = Lazy::new(|| {
+		register_int_gauge!(
+			"romeo_wallet_balance_sats",
+			"Confirmed balance of the peg wallet"
+		)
+		.unwrap()
+	});
+
+	pub static TASK_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+		register_histogram_vec!(
+			"romeo_task_duration_seconds",
+			"Duration of task runs",
+			&["task"]
+		)
+		.unwrap()
+	});
+
+	pub fn record_event(event: &Event) {
+		match event {
+			Event::BitcoinBlock(..) => {
+				BLOCKS_PROCESSED.with_label_values(&["bitcoin"]).inc()
+			}
+			Event::BitcoinBlocks(blocks) => BLOCKS_PROCESSED
+				.with_label_values(&["bitcoin"])
+				.inc_by(blocks.len() as u64),
+			Event::StacksBlock(..) => {
+				BLOCKS_PROCESSED.with_label_values(&["stacks"]).inc()
+			}
+			Event::MintBroadcasted(..) => MINTS_BROADCASTED.inc(),
+			Event::FulfillBroadcasted(withdrawal_info, ..) => {
+				fulfillment_broadcasted(withdrawal_info.block_height)
+			}
+			Event::FulfillmentBatchBroadcasted(withdrawal_infos, ..) => {
+				for withdrawal_info in withdrawal_infos {
+					fulfillment_broadcasted(withdrawal_info.block_height)
+				}
+			}
+			_ => {}
+		}
+	}
+
+	pub fn record_state(state: &State) {
+		if let Some((stacks_height, bitcoin_height)) = state.block_heights() {
+			CHAIN_TIP
+				.with_label_values(&["stacks"])
+				.set(stacks_height as i64);
+			CHAIN_TIP
+				.with_label_values(&["bitcoin"])
+				.set(bitcoin_height as i64);
+		}
+
+		DEPOSITS_DETECTED.set(state.deposit_count() as i64);
+	}
+
+	fn fulfillment_broadcasted(block_height: u32) {
+		let tip = CHAIN_TIP.with_label_values(&["bitcoin"]).get();
+
+		FULFILLMENTS_BROADCASTED.inc();
+		FULFILLMENT_LATENCY
+			.observe(tip.saturating_sub(block_height as i64).max(0) as f64);
+	}
+
+	pub fn serve(address: SocketAddr) {
+		let make_service = make_service_fn(|_| async {
+			Ok::<_, Infallible>(service_fn(|req| async { handle(req) }))
+		});
+
+		tokio::spawn(async move {
+			info!("Serving metrics at {}", address);
+
+			if let Err(err) = Server::bind(&address).serve(make_service).await {
+				warn!("Metrics server failed: {}", err);
+			}
+		});
+	}
+
+	fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+		if req.method() != Method::GET || req.uri().path() != "/metrics" {
+			let mut res = Response::new(Body::empty());
+			*res.status_mut() = StatusCode::NOT_FOUND;
+			return Ok(res);
+		}
+
+		let encoder = TextEncoder::new();
+		let mut body = vec![];
+
+		if let Err(err) = encoder.encode(&prometheus::gather(), &mut body) {
+			warn!("Unable to encode metrics: {}", err);
+		}
+
+		Ok(Response::builder()
+			.header(CONTENT_TYPE, encoder.format_type())
+			.body(Body::from(body))
+			.unwrap())
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+		use crate::metrics::{request_finished, RequestClient};
+
+		#[test]
+		fn test_metrics_are_gathered() {
+			request_finished(RequestClient::Stacks, false);
+			MINTS_BROADCASTED.inc();
+
+			let mut body = vec![];
+			TextEncoder::new()
+				.encode(&prometheus::gather(), &mut body)
+				.unwrap();
+			let body = String::from_utf8(body).unwrap();
+
+			assert!(
+				body.contains("romeo_request_errors_total{client=\"stacks\"}")
+			);
+			assert!(body.contains("romeo_mints_broadcasted_total"));
+		}
+	}
+}
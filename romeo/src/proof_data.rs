@@ -1,24 +1,10 @@
 //! Proof Data used in Clarity Contracts
-use bdk::bitcoin::{Block, BlockHeader, Transaction, Txid as BitcoinTxId};
+use bdk::bitcoin::{Block, BlockHeader, Txid as BitcoinTxId};
 use blockstack_lib::vm::types::{
 	ListData, ListTypeData, SequenceData, Value, BUFF_32,
 };
-use rs_merkle::Hasher;
-use stacks_core::crypto::{sha256::DoubleSha256Hasher, Hashing};
-/// The double sha256 algorithm used for bitcoin
-#[derive(Clone)]
-pub struct DoubleSha256Algorithm {}
-
-impl Hasher for DoubleSha256Algorithm {
-	type Hash = [u8; 32];
-
-	fn hash(data: &[u8]) -> [u8; 32] {
-		DoubleSha256Hasher::hash(data)
-			.as_bytes()
-			.try_into()
-			.unwrap()
-	}
-}
+pub use sbtc_core::operations::proof::BitcoinMerkleTree;
+use sbtc_core::operations::proof::{serialize_header, TransactionProof};
 
 /// Data needed to prove that a bitcoin transaction was mined on the bitcoin
 /// network. This data is used by clarity contracts.
@@ -55,163 +41,34 @@ pub struct ProofDataClarityValues {
 	pub merkle_path: Value,
 }
 
-/// Merkle tree for Bitcoin block transactions
-pub struct BitcoinMerkleTree {
-	data: Vec<Vec<[u8; 32]>>,
-}
-
-impl BitcoinMerkleTree {
-	/// Make a new Merkle tree out of the given Bitcoin txids
-	pub fn new(txs: &[BitcoinTxId]) -> Self {
-		if txs.is_empty() {
-			return Self { data: vec![] };
-		}
-
-		let mut tree = vec![];
-
-		// fill in leaf hashes
-		let mut leaf_hashes = vec![];
-		for tx in txs {
-			let mut hash_slice = [0u8; 32];
-			hash_slice.copy_from_slice(tx);
-			leaf_hashes.push(hash_slice);
-		}
-		// must have an even number of hashes
-		if txs.len() % 2 == 1 {
-			let last_hash_slice = leaf_hashes
-				.last()
-				.expect(
-					"FATAL: unreachable: non-empty vec does not have `last()`",
-				)
-				.to_owned();
-			leaf_hashes.push(last_hash_slice);
-		}
-
-		tree.push(leaf_hashes);
-
-		// calculate parent hashes until we reach the root
-		let mut last_row_len =
-			tree.last().expect("FATAL: unreachable: empty tree").len();
-		loop {
-			let mut next_row = vec![];
-			let last_row = tree.last().expect("FATAL: unreachable: empty tree");
-			for i in 0..(last_row_len / 2) {
-				let mut intermediate_preimage = [0u8; 64];
-				intermediate_preimage[0..32].copy_from_slice(&last_row[2 * i]);
-				intermediate_preimage[32..64]
-					.copy_from_slice(&last_row[2 * i + 1]);
-
-				let intermediate_hash =
-					DoubleSha256Algorithm::hash(&intermediate_preimage);
-				next_row.push(intermediate_hash);
-			}
-
-			// reached the root
-			if next_row.len() == 1 {
-				tree.push(next_row);
-				break;
-			}
-
-			// have more to go -- this row must have an even number of nodes
-			if next_row.len() % 2 == 1 {
-				let last_hash_slice = next_row
-					.last()
-					.expect("FATAL: unreachable: next_row is empty")
-					.to_owned();
-				next_row.push(last_hash_slice);
-			}
-
-			last_row_len = next_row.len();
-			tree.push(next_row);
-		}
-
-		Self { data: tree }
-	}
-
-	/// Get the Merkle root.
-	/// It will be None if the tree is empty
-	pub fn root(&self) -> Option<[u8; 32]> {
-		self.data.last().map(|root_row| root_row[0])
-	}
-
-	/// Calculate a merkle proof for a transaction, given its index.
-	/// This algorithm uses the following insight: the ith bit in the index
-	/// tells us which sibling to use (left or right) at the ith level of the
-	/// Merkle tree.
-	/// * if ith bit of `index` is 0, then use the _right_ sibling at height i
-	/// * if ith bit of `index` is 1, then use the _left_ sibling at height i
-	pub fn proof(&self, mut index: usize) -> Option<Vec<[u8; 32]>> {
-		if self.data.is_empty() {
-			// empty tree
-			return None;
-		}
-		if index >= self.data[0].len() {
-			// off the end of the leaf row
-			return None;
-		}
-
-		let mut proof = vec![];
-		for i in 0..(self.data.len() - 1) {
-			let sibling = if index % 2 == 0 {
-				assert!(
-					index + 1 < self.data[i].len(),
-					"BUG: {} + 1 >= data[{}].len() ({})",
-					index,
-					i,
-					self.data[i].len()
-				);
-				self.data[i][index + 1]
-			} else {
-				assert!(index > 0, "BUG: index == 0");
-				self.data[i][index - 1]
-			};
-			proof.push(sibling);
-			index >>= 1;
-		}
-
-		Some(proof)
-	}
-
-	/// Calculate the tree depth, including leaves.
-	/// This value is always greater than 0, unless the tree is empty.
-	pub fn depth(&self) -> usize {
-		self.data.len()
-	}
-}
-
 impl ProofData {
 	/// Create a new proof from a bitcoin transaction and a block
 	pub fn from_block_and_index(block: &Block, index: usize) -> Self {
-		let tx: &Transaction =
-			block.txdata.get(index).expect("Invalid tx index");
-
-		let txids: Vec<BitcoinTxId> =
-			block.txdata.iter().map(|tx| tx.txid()).collect();
-		let merkle_tree = BitcoinMerkleTree::new(&txids);
-		let merkle_path = merkle_tree
-			.proof(index)
-			.expect("FATAL: index is out-of-bounds");
+		let proof =
+			TransactionProof::new(block, index).expect("Invalid tx index");
+		let merkle_root = BitcoinMerkleTree::new(
+			&block.txdata.iter().map(|tx| tx.txid()).collect::<Vec<_>>(),
+		)
+		.root()
+		.expect("FATAL: unreachable: non-empty block has no merkle root");
 
 		Self {
-			reversed_txid: tx.txid(),
-			tx_index: index as u32,
-			block_height: block
-				.bip34_block_height()
-				.expect("Failed to get block height"),
-			block_header: block.header,
-			merkle_path: merkle_path.into_iter().map(|h| h.to_vec()).collect(),
-			merkle_root: hex::encode(merkle_tree.root().unwrap()),
+			reversed_txid: proof.txid,
+			tx_index: proof.tx_index,
+			block_height: proof.block_height,
+			block_header: proof.block_header,
+			merkle_path: proof
+				.merkle_path
+				.into_iter()
+				.map(|hash| hash.to_vec())
+				.collect(),
+			merkle_root: hex::encode(merkle_root),
 		}
 	}
 
 	/// converts the proof data to a tuple of clarity values
 	pub fn to_values(&self) -> ProofDataClarityValues {
-		let mut header = self.block_header.version.to_le_bytes().to_vec();
-		header.append(&mut self.block_header.prev_blockhash.to_vec());
-		header.append(&mut self.block_header.merkle_root.to_vec());
-		header.append(&mut self.block_header.time.to_le_bytes().to_vec());
-		header.append(&mut self.block_header.bits.to_le_bytes().to_vec());
-		header.append(&mut self.block_header.nonce.to_le_bytes().to_vec());
+		let header = serialize_header(&self.block_header);
 
 		// use txid in big endian for clarity call
 		let mut txid = self.reversed_txid.to_vec();
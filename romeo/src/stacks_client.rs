@@ -1,71 +1,207 @@
 //! Stacks client
 
-use std::{io::Cursor, sync::Arc, time::Duration};
+use std::{
+	collections::HashMap, fmt::Debug, io::Cursor, sync::Arc, time::Duration,
+};
 
 use anyhow::{anyhow, Error};
+use async_trait::async_trait;
 use blockstack_lib::{
 	burnchains::Txid as StacksTxId,
 	chainstate::stacks::{
 		StacksTransaction, StacksTransactionSigner, TransactionAnchorMode,
-		TransactionPostConditionMode,
+		TransactionAuth, TransactionPostConditionMode,
 	},
 	codec::StacksMessageCodec,
-	core::CHAIN_ID_TESTNET,
 	types::chainstate::StacksPrivateKey,
-	vm::{
-		types::{QualifiedContractIdentifier, StandardPrincipalData},
-		ContractName,
-	},
+	vm::{types::QualifiedContractIdentifier, ContractName},
 };
 use futures::Future;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use reqwest::{Request, RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use stacks_core::{codec::Codec, uint::Uint256};
+use stacks_core::{
+	address::StacksAddress, clarity::Value as ClarityValue, codec::Codec,
+	crypto::secret::SecretBytes, uint::Uint256, wallet::Credentials,
+};
 use tokio::{
 	sync::{Mutex, MutexGuard},
 	time::sleep,
 };
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
+
+use self::{
+	endpoint_pool::{endpoint_url, EndpointPool},
+	fee::FeeEstimations,
+	nonce::NonceManager,
+	rate_limit::RateLimiter,
+};
+use crate::{
+	config::Config,
+	event::TransactionStatus,
+	metrics::{self, RequestClient},
+};
 
-use crate::{config::Config, event::TransactionStatus};
+pub mod asset;
+pub mod endpoint_pool;
+pub mod fee;
+pub mod mock;
+pub mod nonce;
+pub mod rate_limit;
 
 const BLOCK_POLLING_INTERVAL: Duration = Duration::from_secs(5);
 
-/// Wrapped Stacks Client which can be shared safely between threads.
+/// Access to the Stacks network and the accounts of the system. The daemon
+/// only talks to Stacks through this trait, so other node APIs or test doubles
+/// can be plugged in.
+#[async_trait]
+pub trait StacksBackend: Send + Sync + Debug {
+	/// Sign and broadcast an unsigned stacks transaction
+	async fn submit_tx(
+		&mut self,
+		tx: StacksTransaction,
+	) -> anyhow::Result<StacksTxId>;
+
+	/// Get transaction status for a given txid
+	async fn get_tx_status(
+		&mut self,
+		txid: StacksTxId,
+	) -> anyhow::Result<TransactionStatus>;
+
+	/// Get the balance and the nonce of the account
+	async fn get_account(
+		&self,
+		address: &StacksAddress,
+	) -> anyhow::Result<StacksAccount>;
+
+	/// Call a read-only function of the contract
+	async fn call_read_only(
+		&self,
+		contract: &QualifiedContractIdentifier,
+		function_name: &str,
+		args: &[ClarityValue],
+	) -> anyhow::Result<ClarityValue>;
+
+	/// Get the Clarity source of the contract, if it is deployed
+	async fn get_contract_source(
+		&self,
+		contract: &QualifiedContractIdentifier,
+	) -> anyhow::Result<Option<String>>;
+
+	/// Get the transactions of the block at height, waiting for it if it is
+	/// not mined yet
+	async fn get_block(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<Vec<StacksTransaction>>;
+
+	/// Get the height of the Stacks chain tip, without retrying
+	async fn get_tip_height(&self) -> anyhow::Result<u32>;
+
+	/// Get the block height of the contract
+	async fn get_contract_block_height(
+		&self,
+		name: ContractName,
+	) -> anyhow::Result<u32>;
+
+	/// Get the Bitcoin block height for a Stacks block height
+	async fn get_bitcoin_block_height(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<u32>;
+
+	/// Get the block hash for a given Bitcoin height
+	async fn get_block_hash_from_bitcoin_height(
+		&self,
+		height: u32,
+	) -> anyhow::Result<Uint256>;
+
+	/// Use the values of a reloaded config
+	fn reload_config(&mut self, _config: Config) {}
+}
+
+/// Balance and nonce of a Stacks account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StacksAccount {
+	/// Unlocked balance in microSTX
+	pub balance: u128,
+	/// Nonce following the confirmed transactions of the account
+	pub nonce: u64,
+}
+
+/// Wrapped Stacks backend which can be shared safely between threads.
 #[derive(Clone, Debug)]
-pub struct LockedClient(Arc<Mutex<StacksClient>>);
+pub struct LockedClient(Arc<Mutex<dyn StacksBackend>>);
 
 impl LockedClient {
-	/// Lock and obtain a handle to the inner stacks client
-	pub async fn lock(&self) -> MutexGuard<StacksClient> {
+	/// Lock and obtain a handle to the inner stacks backend
+	pub async fn lock(&self) -> MutexGuard<dyn StacksBackend> {
 		self.0.lock().await
 	}
 }
 
-impl From<StacksClient> for LockedClient {
-	fn from(client: StacksClient) -> Self {
-		Self(Arc::new(Mutex::new(client)))
+impl<B: StacksBackend + 'static> From<B> for LockedClient {
+	fn from(backend: B) -> Self {
+		Self(Arc::new(Mutex::new(backend)))
 	}
 }
 
 /// Stateful client for creating and broadcasting Stacks transactions
 ///
-/// This client keeps track of the last executed nonce for the given
-/// key.
+/// This client keeps track of the nonces of its pending transactions, so
+/// broadcasts through the same client never reuse a nonce.
 #[derive(Debug)]
 pub struct StacksClient {
 	config: Config,
 	http_client: reqwest::Client,
+	endpoints: Arc<EndpointPool>,
+	rate_limiter: RateLimiter,
+	origin_nonces: NonceManager,
+	sponsor_nonces: NonceManager,
+	/// Broadcasted contract calls which may need to be resubmitted
+	unconfirmed: HashMap<StacksTxId, UnconfirmedCall>,
+	/// Latest resubmissions of the contract calls, by original txid
+	replacements: HashMap<StacksTxId, StacksTxId>,
+}
+
+/// Contract call before signing, with the fee it pays and the Stacks block
+/// height it was broadcasted at
+#[derive(Debug, Clone)]
+struct UnconfirmedCall {
+	tx: StacksTransaction,
+	nonce: u64,
+	sponsor_nonce: Option<u64>,
+	fee: u64,
+	block_height: u32,
 }
 
 impl StacksClient {
-	/// Create a new StacksClient
+	/// Create a new StacksClient. Checking the health of fallback Stacks
+	/// nodes, if configured, requires a Tokio runtime.
 	pub fn new(config: Config, http_client: reqwest::Client) -> Self {
+		let endpoints = Arc::new(EndpointPool::new(
+			std::iter::once(config.stacks_node_url.clone())
+				.chain(config.stacks_fallback_node_urls.iter().cloned()),
+			config.stacks_max_tip_lag,
+		));
+
+		if !config.stacks_fallback_node_urls.is_empty() {
+			endpoints.spawn_health_checks(
+				http_client.clone(),
+				config.hiro_api_key.clone(),
+			);
+		}
+
 		Self {
+			endpoints,
+			rate_limiter: RateLimiter::new(config.stacks_rate_limit.clone()),
 			config,
 			http_client,
+			origin_nonces: NonceManager::default(),
+			sponsor_nonces: NonceManager::default(),
+			unconfirmed: HashMap::new(),
+			replacements: HashMap::new(),
 		}
 	}
 
@@ -76,11 +212,11 @@ impl StacksClient {
 	{
 		let request_url = request_builder().url().to_string();
 
-		let res = retry(|| {
-			self.http_client
-				.execute(self.add_stacks_api_key(request_builder()))
-		})
-		.await?;
+		let res = retry(|| self.execute(request_builder())).await;
+
+		metrics::request_finished(RequestClient::Stacks, res.is_ok());
+
+		let res = res?;
 
 		let status = res.status();
 		let body = res.text().await?;
@@ -111,6 +247,64 @@ impl StacksClient {
         })
 	}
 
+	/// Send the request to the best Stacks node once the rate limits allow
+	/// it. Throttled requests are sent again after the pause the API asks
+	/// for, so that a busy API slows the daemon down instead of failing its
+	/// requests, and failed requests are sent again to the next node.
+	async fn execute(&self, request: Request) -> reqwest::Result<Response> {
+		let request = self.add_stacks_api_key(request);
+		let mut tried = Vec::new();
+		let mut last_res = None;
+
+		while let Some(endpoint) = self.endpoints.select(&tried) {
+			let url = endpoint_url(&endpoint, request.url());
+
+			// Requests with a streamed body cannot be sent again
+			let Some(mut attempt) = request.try_clone() else {
+				let mut request = request;
+				*request.url_mut() = url;
+
+				return self.http_client.execute(request).await;
+			};
+
+			*attempt.url_mut() = url.clone();
+			self.rate_limiter.acquire(&url).await;
+
+			let res = self.http_client.execute(attempt).await;
+
+			let failed = match &res {
+				Ok(res) => {
+					if let Some(pause) = self.rate_limiter.throttled(&url, res)
+					{
+						warn!(
+							"Throttled by Stacks node {}, retrying {} in {:?}",
+							endpoint,
+							url.path(),
+							pause
+						);
+						continue;
+					}
+
+					res.status().is_server_error()
+				}
+				Err(_) => true,
+			};
+
+			if !failed {
+				debug!("Stacks node {} served {}", endpoint, url.path());
+				self.endpoints.report_success(&endpoint);
+
+				return res;
+			}
+
+			self.endpoints.report_failure(&endpoint);
+			tried.push(endpoint);
+			last_res = Some(res);
+		}
+
+		last_res.expect("The Stacks endpoint pool is empty")
+	}
+
 	/// if hiro_api_key is set, add it to the request
 	fn add_stacks_api_key(&self, request: Request) -> Request {
 		match &self.config.hiro_api_key {
@@ -124,205 +318,246 @@ impl StacksClient {
 		}
 	}
 
-	/// Sign and broadcast an unsigned stacks transaction
-	pub async fn sign_and_broadcast(
-		&mut self,
+	async fn sign_and_send(
+		&self,
 		mut tx: StacksTransaction,
+		fee: u64,
 	) -> anyhow::Result<StacksTxId> {
-		#[cfg(debug_assertions)]
-		{
-			sleep(Duration::from_secs(3)).await;
-		}
-
-		tx.set_origin_nonce(self.get_nonce_info().await?.possible_next_nonce);
-		tx.set_tx_fee(self.calculate_fee(tx.tx_len()).await?);
-
-		tx.anchor_mode = TransactionAnchorMode::Any;
-		tx.post_condition_mode = TransactionPostConditionMode::Allow;
-		tx.chain_id = CHAIN_ID_TESTNET;
+		tx.set_tx_fee(fee);
 
-		let mut signer = StacksTransactionSigner::new(&tx);
-
-		signer
-			.sign_origin(
-				&StacksPrivateKey::from_slice(
-					&self
-						.config
-						.stacks_credentials
-						.private_key()
-						.secret_bytes(),
-				)
-				.unwrap(),
-			)
-			.unwrap();
+		let origin_key = private_key(self.credentials(Account::Origin)?)?;
+		let sponsor_key = match tx.auth {
+			TransactionAuth::Sponsored(..) => {
+				Some(private_key(self.credentials(Account::Sponsor)?)?)
+			}
+			TransactionAuth::Standard(_) => None,
+		};
 
-		tx = signer.get_tx().unwrap();
+		let tx = sign_transaction(&tx, &origin_key, sponsor_key.as_ref())?;
 
 		let mut tx_bytes = vec![];
-		tx.consensus_serialize(&mut tx_bytes).unwrap();
-
-		let res = self
-			.send_request(|| {
-				let tx_bytes = tx_bytes.clone();
+		tx.consensus_serialize(&mut tx_bytes).map_err(|err| {
+			anyhow!("Unable to serialize the transaction: {}", err)
+		})?;
 
-				self.http_client
-					.post(self.transaction_url())
-					.header("Content-type", "application/octet-stream")
-					.body(tx_bytes)
-					.build()
-					.unwrap()
-			})
-			.await?;
+		self.send_request(|| {
+			let tx_bytes = tx_bytes.clone();
 
-		Ok(res)
+			self.http_client
+				.post(self.transaction_url())
+				.header("Content-type", "application/octet-stream")
+				.body(tx_bytes)
+				.build()
+				.unwrap()
+		})
+		.await
 	}
 
-	/// Get transaction status for a given txid
-	pub async fn get_transation_status(
+	/// Resubmit the contract call at a higher fee if it has been unconfirmed
+	/// for too long, so that the replacement is tracked under the txid of the
+	/// original call
+	async fn resubmit_if_stalled(
 		&mut self,
+		original_txid: StacksTxId,
 		txid: StacksTxId,
-	) -> anyhow::Result<TransactionStatus> {
-		let res: anyhow::Result<Value> = self
-			.send_request(|| {
-				self.http_client
-					.get(self.cachebust(self.get_transation_details_url(txid)))
-					.header("Accept", "application/json")
-					.build()
-					.unwrap()
-			})
-			.await;
+	) -> anyhow::Result<()> {
+		let policy = self.config.stacks_fee.clone();
 
-		let tx_status_str = match res {
-			Ok(json) => json["tx_status"]
-				.as_str()
-				.map(|s| s.to_string())
-				.expect("Could not get raw transaction from response"),
-			// Stacks node sometimes returns 404 for pending transactions
-			// :shrug:
-			Err(err) if err.to_string().contains("404 Not Found") => {
-				"pending".to_string()
-			}
-			err => panic!("Unknown transation status: {:?}", err),
+		if policy.resubmit_after_blocks == 0 {
+			return Ok(());
+		}
+
+		let Some(call) = self.unconfirmed.get(&txid).cloned() else {
+			return Ok(());
 		};
 
-		Ok(match tx_status_str.as_str() {
-			"pending" => TransactionStatus::Broadcasted,
-			"success" => TransactionStatus::Confirmed,
-			"abort_by_response" => TransactionStatus::Rejected,
-			status => panic!("Unknown transation status: {}", status),
-		})
-	}
+		let block_height = self.get_tip_height().await?;
 
-	async fn get_nonce_info(&mut self) -> anyhow::Result<NonceInfo> {
-		self.send_request(|| {
-			self.http_client
-				.get(self.cachebust(self.nonce_url()))
-				.build()
-				.unwrap()
-		})
-		.await
+		if block_height < call.block_height + policy.resubmit_after_blocks {
+			return Ok(());
+		}
+
+		let estimate = self.estimate_fee(&call.tx).await?;
+		let Some(fee) = policy.bumped(estimate, call.fee) else {
+			debug!("Stacks transaction {} already pays the maximum fee", txid);
+			return Ok(());
+		};
+
+		info!(
+			"Stacks transaction {} unconfirmed since block {}, resubmitting it with a fee of {} microSTX",
+			txid, call.block_height, fee
+		);
+
+		let replacement_txid = self.sign_and_send(call.tx.clone(), fee).await?;
+
+		self.record_resubmission(
+			original_txid,
+			txid,
+			replacement_txid,
+			UnconfirmedCall {
+				fee,
+				block_height,
+				..call
+			},
+		);
+
+		Ok(())
 	}
 
-	/// Get the block height of the contract
-	pub async fn get_contract_block_height(
+	/// Resubmit the contract call of a transaction dropped out of the mempool
+	/// with the nonces it held, so that the call is not lost. Returns whether
+	/// the transaction was a known contract call.
+	async fn resubmit_dropped(
 		&mut self,
-		name: ContractName,
-	) -> anyhow::Result<u32> {
-		let addr = self.config.stacks_credentials.address();
-		let id = QualifiedContractIdentifier::new(
-			StandardPrincipalData(
-				addr.version() as u8,
-				addr.hash().as_ref().try_into().unwrap(),
-			),
-			name,
+		txid: StacksTxId,
+	) -> anyhow::Result<bool> {
+		let Some(call) = self.unconfirmed.get(&txid).cloned() else {
+			return Ok(false);
+		};
+
+		// A higher fee also replaces the transaction in the mempools of the
+		// nodes which did not drop it
+		let policy = self.config.stacks_fee.clone();
+		let estimate = self.estimate_fee(&call.tx).await?;
+		let fee = policy.bumped(estimate, call.fee).unwrap_or(call.fee);
+		let block_height = self.get_tip_height().await?;
+
+		info!(
+			"Stacks transaction {} was dropped, resubmitting it with nonce {} and a fee of {} microSTX",
+			txid, call.nonce, fee
 		);
 
-		let res: Value = self
-			.send_request(|| {
-				self.http_client
-					.get(self.contract_info_url(id.to_string()))
-					.build()
-					.unwrap()
-			})
-			.await?;
+		let replacement_txid = self.sign_and_send(call.tx.clone(), fee).await?;
 
-		if let Some(err) = res["error"].as_str() {
-			Err(Error::msg(err.to_string()))
-		} else {
-			Ok(res["block_height"].as_u64().unwrap() as u32)
-		}
+		let original_txid = self
+			.replacements
+			.iter()
+			.find(|(_, latest_txid)| **latest_txid == txid)
+			.map(|(original_txid, _)| *original_txid)
+			.unwrap_or(txid);
+
+		self.record_resubmission(
+			original_txid,
+			txid,
+			replacement_txid,
+			UnconfirmedCall {
+				fee,
+				block_height,
+				..call
+			},
+		);
+
+		Ok(true)
 	}
 
-	/// Get the Bitcoin block height for a Stacks block height
-	pub async fn get_bitcoin_block_height(
+	/// Track the resubmission of the contract call under the txid of the
+	/// original call, holding the nonces of the replaced transaction
+	fn record_resubmission(
 		&mut self,
-		block_height: u32,
-	) -> anyhow::Result<u32> {
-		let res: Value = self
-			.send_request(|| {
-				self.http_client
-					.get(self.block_by_height_url(block_height))
-					.build()
-					.unwrap()
-			})
-			.await?;
-
-		Ok(res["burn_block_height"].as_u64().unwrap() as u32)
+		original_txid: StacksTxId,
+		txid: StacksTxId,
+		replacement_txid: StacksTxId,
+		call: UnconfirmedCall,
+	) {
+		self.record_broadcast(call.nonce, call.sponsor_nonce, replacement_txid);
+		self.unconfirmed.remove(&txid);
+		self.unconfirmed.insert(replacement_txid, call);
+		self.replacements.insert(original_txid, replacement_txid);
 	}
 
-	/// Get the block at height
-	pub async fn get_block(
+	fn record_broadcast(
 		&mut self,
-		block_height: u32,
-	) -> anyhow::Result<Vec<StacksTransaction>> {
-		let res: Value = loop {
-			let maybe_response: Result<Value, Error> = self
-				.send_request(|| {
-					self.http_client
-						.get(self.block_by_height_url(block_height))
-						.build()
-						.unwrap()
-				})
-				.await;
+		nonce: u64,
+		sponsor_nonce: Option<u64>,
+		txid: StacksTxId,
+	) {
+		self.origin_nonces.broadcasted(nonce, txid);
 
-			if let Ok(inner_response) = maybe_response {
-				if inner_response["txs"].is_array() {
-					trace!("Found Stacks block of height {}", block_height);
-					break inner_response;
+		if let Some(sponsor_nonce) = sponsor_nonce {
+			self.sponsor_nonces.broadcasted(sponsor_nonce, txid);
+		}
+	}
+
+	/// Get the nonce of the next transaction of the account, once the node
+	/// accepts more unconfirmed transactions of it
+	async fn reserve_nonce(&mut self, account: Account) -> anyhow::Result<u64> {
+		loop {
+			let account_nonce = self.get_account_nonce(account).await?;
+			self.nonces(account).reconcile(account_nonce);
+
+			if let Some(txid) = self.nonces(account).stalled(account_nonce) {
+				if !self.is_transaction_known(txid).await? {
+					match self.resubmit_dropped(txid).await {
+						Ok(true) => {}
+						res => {
+							warn!(
+								"Stacks transaction {} with {:?} nonce {} was dropped and not resubmitted, reusing its nonce: {:?}",
+								txid, account, account_nonce, res
+							);
+							self.nonces(account).dropped(account_nonce);
+							self.unconfirmed.remove(&txid);
+						}
+					}
 				}
 			}
 
-			trace!("Stacks block not found, retrying...");
+			if !self.nonces(account).is_full() {
+				return Ok(self.nonces(account).next_nonce(account_nonce));
+			}
+
+			debug!("Too many unconfirmed Stacks transactions, waiting for confirmations");
 			sleep(BLOCK_POLLING_INTERVAL).await;
-		};
+		}
+	}
 
-		let tx_ids: Vec<StacksTxId> = res["txs"]
-			.as_array()
-			.unwrap_or_else(|| {
-				panic!("Could not get txs from response: {:?}", res)
-			})
-			.iter()
-			.map(|id| {
-				let mut id = id.as_str().unwrap().to_string();
-				id = id.replace("0x", "");
+	/// Whether the node knows the transaction, in the mempool or in a block
+	async fn is_transaction_known(
+		&self,
+		txid: StacksTxId,
+	) -> anyhow::Result<bool> {
+		let request = self
+			.http_client
+			.get(self.cachebust(self.get_transation_details_url(txid)))
+			.build()?;
+		let res = self.execute(request).await?;
 
-				StacksTxId::from_hex(&id).unwrap()
-			})
-			.collect();
+		if res.status() == StatusCode::NOT_FOUND {
+			return Ok(false);
+		}
 
-		let mut txs = Vec::with_capacity(tx_ids.len());
+		res.error_for_status()?;
 
-		for id in tx_ids {
-			let tx = self.get_transaction(id).await?;
-			txs.push(tx);
+		Ok(true)
+	}
+
+	/// Get the nonce following the confirmed transactions of the account
+	async fn get_account_nonce(&self, account: Account) -> anyhow::Result<u64> {
+		let address = self.credentials(account)?.address();
+
+		Ok(self.get_account(&address).await?.nonce)
+	}
+
+	fn nonces(&mut self, account: Account) -> &mut NonceManager {
+		match account {
+			Account::Origin => &mut self.origin_nonces,
+			Account::Sponsor => &mut self.sponsor_nonces,
 		}
+	}
 
-		Ok(txs)
+	fn credentials(&self, account: Account) -> anyhow::Result<&Credentials> {
+		match account {
+			Account::Origin => Ok(&self.config.stacks_credentials),
+			Account::Sponsor => self
+				.config
+				.stacks_sponsor_credentials
+				.as_ref()
+				.ok_or_else(|| anyhow!("No sponsor account is configured")),
+		}
 	}
 
-	/// Get the block at height
+	/// Get the transaction with the id
 	pub async fn get_transaction(
-		&mut self,
+		&self,
 		id: StacksTxId,
 	) -> anyhow::Result<StacksTransaction> {
 		let res: Value = self
@@ -345,34 +580,46 @@ impl StacksClient {
 		Ok(tx)
 	}
 
-	/// Get the block hash for a given Bitcoin height
-	pub async fn get_block_hash_from_bitcoin_height(
-		&mut self,
-		height: u32,
-	) -> anyhow::Result<Uint256> {
-		let res: Value = self
+	/// Estimate the fee of the transaction with the node, falling back to the
+	/// fee rate of transfers if the node has no estimation
+	async fn estimate_fee(
+		&self,
+		tx: &StacksTransaction,
+	) -> anyhow::Result<u64> {
+		let mut payload = vec![];
+		tx.payload.consensus_serialize(&mut payload)?;
+
+		let body = serde_json::json!({
+			"transaction_payload": hex::encode(payload),
+			"estimated_len": tx.tx_len(),
+		});
+
+		let res: anyhow::Result<FeeEstimations> = self
 			.send_request(|| {
 				self.http_client
-					.get(self.block_by_bitcoin_height_url(height))
-					.header("Accept", "application/json")
+					.post(self.fee_estimation_url())
+					.json(&body)
 					.build()
 					.unwrap()
 			})
-			.await?;
-
-		let hash_str = res["hash"]
-			.as_str()
-			.unwrap_or_else(|| panic!("Could not get block hash: {:?}", res));
-		let hash_bytes = hex::decode(hash_str.replace("0x", ""))?;
+			.await;
 
-		Ok(Uint256::deserialize(&mut Cursor::new(hash_bytes))?)
+		match res.map(|estimations| estimations.middle()) {
+			Ok(Some(fee)) => Ok(fee),
+			Ok(None) => self.calculate_fee(tx.tx_len()).await,
+			Err(err) => {
+				debug!(
+					"No fee estimation from the Stacks node, using the transfer fee rate: {:?}",
+					err
+				);
+				self.calculate_fee(tx.tx_len()).await
+			}
+		}
 	}
 
 	async fn calculate_fee(&self, tx_len: u64) -> anyhow::Result<u64> {
 		let fee_rate: u64 = self
-			.http_client
-			.get(self.fee_url())
-			.send()
+			.execute(self.http_client.get(self.fee_url()).build()?)
 			.await?
 			.json()
 			.await?;
@@ -446,39 +693,494 @@ impl StacksClient {
 		url
 	}
 
-	fn nonce_url(&self) -> reqwest::Url {
-		let path = format!(
-			"/extended/v1/address/{}/nonces",
-			self.config.stacks_credentials.address(),
-		);
+	fn account_url(&self, address: &StacksAddress) -> reqwest::Url {
+		let path = format!("/v2/accounts/{}?proof=0", address);
 
 		self.config.stacks_node_url.join(&path).unwrap()
 	}
 
+	fn contract_source_url(
+		&self,
+		contract: &QualifiedContractIdentifier,
+	) -> reqwest::Url {
+		self.config
+			.stacks_node_url
+			.join(&format!(
+				"/v2/contracts/source/{}/{}?proof=0",
+				contract.issuer, contract.name
+			))
+			.unwrap()
+	}
+
+	fn call_read_only_url(
+		&self,
+		contract: &QualifiedContractIdentifier,
+		function_name: &str,
+	) -> reqwest::Url {
+		self.config
+			.stacks_node_url
+			.join(&format!(
+				"/v2/contracts/call-read/{}/{}/{}",
+				contract.issuer, contract.name, function_name
+			))
+			.unwrap()
+	}
+
 	fn fee_url(&self) -> reqwest::Url {
 		self.config
 			.stacks_node_url
 			.join("/v2/fees/transfer")
 			.unwrap()
 	}
-}
 
-#[derive(serde::Deserialize)]
-struct NonceInfo {
-	possible_next_nonce: u64,
+	fn fee_estimation_url(&self) -> reqwest::Url {
+		self.config
+			.stacks_node_url
+			.join("/v2/fees/transaction")
+			.unwrap()
+	}
+
+	fn info_url(&self) -> reqwest::Url {
+		self.config.stacks_node_url.join("/v2/info").unwrap()
+	}
 }
 
-async fn retry<O, Fut>(operation: O) -> anyhow::Result<Response>
-where
-	O: Clone + Fn() -> Fut,
-	Fut: Future<Output = Result<Response, reqwest::Error>>,
-{
-	let operation = || async {
-		operation.clone()()
-			.await
-			.and_then(Response::error_for_status)
-			.map_err(|err| {
-				if err.is_request() {
+#[async_trait]
+impl StacksBackend for StacksClient {
+	async fn submit_tx(
+		&mut self,
+		mut tx: StacksTransaction,
+	) -> anyhow::Result<StacksTxId> {
+		#[cfg(debug_assertions)]
+		{
+			sleep(Duration::from_secs(3)).await;
+		}
+
+		let nonce = self.reserve_nonce(Account::Origin).await?;
+
+		tx.set_origin_nonce(nonce);
+
+		let sponsor_nonce = match tx.auth {
+			TransactionAuth::Sponsored(..) => {
+				let sponsor_nonce =
+					self.reserve_nonce(Account::Sponsor).await?;
+
+				tx.set_sponsor_nonce(sponsor_nonce).map_err(|err| {
+					anyhow!("Cannot set the sponsor nonce: {:?}", err)
+				})?;
+
+				Some(sponsor_nonce)
+			}
+			TransactionAuth::Standard(_) => None,
+		};
+
+		tx.anchor_mode = TransactionAnchorMode::Any;
+		tx.post_condition_mode = TransactionPostConditionMode::Allow;
+		tx.chain_id = self.config.stacks_chain_id;
+
+		let fee = self.config.stacks_fee.fee(self.estimate_fee(&tx).await?);
+		let block_height = self.get_tip_height().await?;
+
+		let txid = self.sign_and_send(tx.clone(), fee).await?;
+
+		self.record_broadcast(nonce, sponsor_nonce, txid);
+		self.unconfirmed.insert(
+			txid,
+			UnconfirmedCall {
+				tx,
+				nonce,
+				sponsor_nonce,
+				fee,
+				block_height,
+			},
+		);
+
+		Ok(txid)
+	}
+
+	/// The status of a resubmitted contract call is the one of its latest
+	/// resubmission.
+	async fn get_tx_status(
+		&mut self,
+		txid: StacksTxId,
+	) -> anyhow::Result<TransactionStatus> {
+		let mut latest_txid =
+			self.replacements.get(&txid).copied().unwrap_or(txid);
+
+		let tx_status_str = loop {
+			let res: anyhow::Result<Value> = self
+				.send_request(|| {
+					self.http_client
+						.get(self.cachebust(
+							self.get_transation_details_url(latest_txid),
+						))
+						.header("Accept", "application/json")
+						.build()
+						.unwrap()
+				})
+				.await;
+
+			let json = match res {
+				Ok(json) => json,
+				// Stacks node sometimes returns 404 for pending transactions
+				// :shrug:
+				Err(err) if err.to_string().contains("404 Not Found") => {
+					break "pending".to_string();
+				}
+				err => panic!("Unknown transation status: {:?}", err),
+			};
+
+			let tx_status = json["tx_status"]
+				.as_str()
+				.map(|s| s.to_string())
+				.expect("Could not get raw transaction from response");
+
+			// Resubmissions from before a restart are only known to the node
+			if tx_status == "dropped_replace_by_fee" {
+				if let Some(replacement_txid) =
+					json["replaced_by_tx_id"].as_str().and_then(|id| {
+						StacksTxId::from_hex(&id.replace("0x", "")).ok()
+					}) {
+					latest_txid = replacement_txid;
+					continue;
+				}
+
+				break "pending".to_string();
+			}
+
+			break tx_status;
+		};
+
+		let status = match tx_status_str.as_str() {
+			"pending" => TransactionStatus::Broadcasted,
+			"success" => TransactionStatus::Confirmed,
+			"abort_by_response" => TransactionStatus::Rejected,
+			status => panic!("Unknown transation status: {}", status),
+		};
+
+		if status == TransactionStatus::Broadcasted {
+			if let Err(err) = self.resubmit_if_stalled(txid, latest_txid).await
+			{
+				warn!(
+					"Unable to resubmit Stacks transaction {}: {:?}",
+					latest_txid, err
+				);
+			}
+		} else {
+			self.unconfirmed.remove(&latest_txid);
+			self.replacements.remove(&txid);
+		}
+
+		Ok(status)
+	}
+
+	async fn get_account(
+		&self,
+		address: &StacksAddress,
+	) -> anyhow::Result<StacksAccount> {
+		let url = self.account_url(address);
+		let info: AccountInfo = self
+			.send_request(|| {
+				self.http_client
+					.get(self.cachebust(url.clone()))
+					.build()
+					.unwrap()
+			})
+			.await?;
+
+		let balance =
+			u128::from_str_radix(info.balance.trim_start_matches("0x"), 16)?;
+
+		Ok(StacksAccount {
+			balance,
+			nonce: info.nonce,
+		})
+	}
+
+	async fn call_read_only(
+		&self,
+		contract: &QualifiedContractIdentifier,
+		function_name: &str,
+		args: &[ClarityValue],
+	) -> anyhow::Result<ClarityValue> {
+		let arguments: Vec<String> = args
+			.iter()
+			.map(|arg| format!("0x{}", arg.to_hex()))
+			.collect();
+
+		let body = serde_json::json!({
+			"sender": self.config.stacks_credentials.address().to_string(),
+			"arguments": arguments,
+		});
+
+		let res: ReadOnlyResponse = self
+			.send_request(|| {
+				self.http_client
+					.post(self.call_read_only_url(contract, function_name))
+					.json(&body)
+					.build()
+					.unwrap()
+			})
+			.await?;
+
+		match (res.okay, res.result) {
+			(true, Some(result)) => Ok(ClarityValue::from_hex(result)?),
+			_ => Err(anyhow!(
+				"Read-only call {}.{} failed: {}",
+				contract,
+				function_name,
+				res.cause.unwrap_or_default()
+			)),
+		}
+	}
+
+	async fn get_contract_source(
+		&self,
+		contract: &QualifiedContractIdentifier,
+	) -> anyhow::Result<Option<String>> {
+		let request = self
+			.http_client
+			.get(self.contract_source_url(contract))
+			.build()?;
+		let res = self.execute(request).await?;
+
+		if res.status() == StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+
+		let source: ContractSource = res.error_for_status()?.json().await?;
+
+		Ok(Some(source.source))
+	}
+
+	async fn get_contract_block_height(
+		&self,
+		name: ContractName,
+	) -> anyhow::Result<u32> {
+		let id = QualifiedContractIdentifier {
+			name,
+			..asset::contract_id(&self.config)
+		};
+
+		let res: Value = self
+			.send_request(|| {
+				self.http_client
+					.get(self.contract_info_url(id.to_string()))
+					.build()
+					.unwrap()
+			})
+			.await?;
+
+		if let Some(err) = res["error"].as_str() {
+			Err(Error::msg(err.to_string()))
+		} else {
+			Ok(res["block_height"].as_u64().unwrap() as u32)
+		}
+	}
+
+	async fn get_bitcoin_block_height(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<u32> {
+		let res: Value = self
+			.send_request(|| {
+				self.http_client
+					.get(self.block_by_height_url(block_height))
+					.build()
+					.unwrap()
+			})
+			.await?;
+
+		Ok(res["burn_block_height"].as_u64().unwrap() as u32)
+	}
+
+	async fn get_block(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<Vec<StacksTransaction>> {
+		// Each block takes a request per transaction, so no block is fetched
+		// while too many requests wait for their turn
+		self.rate_limiter.wait_for_capacity().await;
+
+		let res: Value = loop {
+			let maybe_response: Result<Value, Error> = self
+				.send_request(|| {
+					self.http_client
+						.get(self.block_by_height_url(block_height))
+						.build()
+						.unwrap()
+				})
+				.await;
+
+			if let Ok(inner_response) = maybe_response {
+				if inner_response["txs"].is_array() {
+					trace!("Found Stacks block of height {}", block_height);
+					break inner_response;
+				}
+			}
+
+			trace!("Stacks block not found, retrying...");
+			sleep(BLOCK_POLLING_INTERVAL).await;
+		};
+
+		let tx_ids: Vec<StacksTxId> = res["txs"]
+			.as_array()
+			.unwrap_or_else(|| {
+				panic!("Could not get txs from response: {:?}", res)
+			})
+			.iter()
+			.map(|id| {
+				let mut id = id.as_str().unwrap().to_string();
+				id = id.replace("0x", "");
+
+				StacksTxId::from_hex(&id).unwrap()
+			})
+			.collect();
+
+		let mut txs = Vec::with_capacity(tx_ids.len());
+
+		for id in tx_ids {
+			let tx = self.get_transaction(id).await?;
+			txs.push(tx);
+		}
+
+		Ok(txs)
+	}
+
+	async fn get_tip_height(&self) -> anyhow::Result<u32> {
+		let info: NodeInfo = self
+			.execute(self.http_client.get(self.info_url()).build()?)
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		Ok(info.stacks_tip_height)
+	}
+
+	async fn get_block_hash_from_bitcoin_height(
+		&self,
+		height: u32,
+	) -> anyhow::Result<Uint256> {
+		let res: Value = self
+			.send_request(|| {
+				self.http_client
+					.get(self.block_by_bitcoin_height_url(height))
+					.header("Accept", "application/json")
+					.build()
+					.unwrap()
+			})
+			.await?;
+
+		let hash_str = res["hash"]
+			.as_str()
+			.unwrap_or_else(|| panic!("Could not get block hash: {:?}", res));
+		let hash_bytes = hex::decode(hash_str.replace("0x", ""))?;
+
+		Ok(Uint256::deserialize(&mut Cursor::new(hash_bytes))?)
+	}
+
+	fn reload_config(&mut self, config: Config) {
+		if self.rate_limiter.policy() != &config.stacks_rate_limit {
+			self.rate_limiter =
+				RateLimiter::new(config.stacks_rate_limit.clone());
+		}
+
+		self.config = config;
+	}
+}
+
+/// Account whose nonces are tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Account {
+	/// Account calling the contract
+	Origin,
+	/// Account paying the fees of sponsored contract calls
+	Sponsor,
+}
+
+fn private_key(credentials: &Credentials) -> anyhow::Result<StacksPrivateKey> {
+	let secret = SecretBytes::from(credentials.private_key()?);
+
+	StacksPrivateKey::from_slice(secret.as_bytes()).map_err(|err| anyhow!(err))
+}
+
+/// Sign the transaction by its origin, and then by its sponsor if it is
+/// sponsored
+fn sign_transaction(
+	tx: &StacksTransaction,
+	origin_key: &StacksPrivateKey,
+	sponsor_key: Option<&StacksPrivateKey>,
+) -> anyhow::Result<StacksTransaction> {
+	let mut signer = StacksTransactionSigner::new(tx);
+
+	signer
+		.sign_origin(origin_key)
+		.map_err(|err| anyhow!("Unable to sign the transaction: {}", err))?;
+
+	let signer = match (&tx.auth, sponsor_key) {
+		(TransactionAuth::Standard(_), _) => signer,
+		(TransactionAuth::Sponsored(_, sponsor), Some(sponsor_key)) => {
+			let mut signer = StacksTransactionSigner::new_sponsor(
+				&signer.get_tx_incomplete(),
+				sponsor.clone(),
+			)
+			.map_err(|err| {
+				anyhow!("Unable to sponsor the transaction: {}", err)
+			})?;
+
+			signer.sign_sponsor(sponsor_key).map_err(|err| {
+				anyhow!("Unable to sign the sponsored transaction: {}", err)
+			})?;
+
+			signer
+		}
+		(TransactionAuth::Sponsored(..), None) => {
+			return Err(anyhow!(
+				"Unable to sign a sponsored transaction without the sponsor key"
+			))
+		}
+	};
+
+	signer
+		.get_tx()
+		.ok_or_else(|| anyhow!("The transaction is not completely signed"))
+}
+
+#[derive(serde::Deserialize)]
+struct AccountInfo {
+	balance: String,
+	nonce: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct ContractSource {
+	source: String,
+}
+
+#[derive(serde::Deserialize)]
+struct ReadOnlyResponse {
+	okay: bool,
+	result: Option<String>,
+	cause: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct NodeInfo {
+	stacks_tip_height: u32,
+}
+
+async fn retry<O, Fut>(operation: O) -> anyhow::Result<Response>
+where
+	O: Clone + Fn() -> Fut,
+	Fut: Future<Output = Result<Response, reqwest::Error>>,
+{
+	let operation = || async {
+		operation.clone()()
+			.await
+			.and_then(Response::error_for_status)
+			.map_err(|err| {
+				if err.is_request() {
 					backoff::Error::transient(anyhow::anyhow!(err))
 				} else if err.is_status() {
 					// Impossible not to have a status code at this section. May
@@ -513,22 +1215,242 @@ where
 
 #[cfg(test)]
 mod tests {
+	use std::convert::Infallible;
+
+	use blockstack_lib::{
+		chainstate::stacks::{
+			TokenTransferMemo, TransactionPayload,
+			TransactionSpendingCondition, TransactionVersion,
+		},
+		core::CHAIN_ID_TESTNET,
+		types::chainstate::StacksPublicKey,
+		vm::types::{PrincipalData, StandardPrincipalData},
+	};
+	use hyper::{
+		service::{make_service_fn, service_fn},
+		Body, Server,
+	};
+
 	use super::*;
 	use crate::config::Config;
 
+	type Submitted = Arc<Mutex<Vec<StacksTransaction>>>;
+
+	fn config(name: &str, stacks_node_url: &reqwest::Url) -> Config {
+		let dir = std::env::temp_dir().join(format!(
+			"romeo-stacks-client-{}-{}",
+			name,
+			std::process::id()
+		));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("config.json");
+
+		let config = serde_json::json!({
+			"state_directory": "./state",
+			"mnemonic": "twice kind fence tip hidden tilt action fragile skin nothing glory cousin green tomorrow spring wrist shed math olympic multiply hip blue scout claw",
+			"network": "devnet",
+			"contract_name": "asset",
+			"stacks_node_url": stacks_node_url.to_string(),
+		});
+		std::fs::write(&path, config.to_string()).unwrap();
+
+		Config::from_path(&path).unwrap()
+	}
+
+	/// Serve a Stacks node which knows no transaction and keeps the submitted
+	/// ones
+	fn serve_stacks_node(account_nonce: u64) -> (reqwest::Url, Submitted) {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap())
+			.parse()
+			.unwrap();
+		let submitted = Submitted::default();
+
+		let state = submitted.clone();
+		let make_service = make_service_fn(move |_| {
+			let submitted = state.clone();
+
+			async move {
+				Ok::<_, Infallible>(service_fn(move |req| {
+					stacks_node(req, account_nonce, submitted.clone())
+				}))
+			}
+		});
+
+		tokio::spawn(Server::from_tcp(listener).unwrap().serve(make_service));
+
+		(url, submitted)
+	}
+
+	async fn stacks_node(
+		req: hyper::Request<Body>,
+		account_nonce: u64,
+		submitted: Submitted,
+	) -> Result<hyper::Response<Body>, Infallible> {
+		let path = req.uri().path().to_string();
+		let json =
+			|value: Value| hyper::Response::new(Body::from(value.to_string()));
+
+		let res = if path.starts_with("/v2/accounts/") {
+			json(
+				serde_json::json!({ "balance": "0x0", "nonce": account_nonce }),
+			)
+		} else if path == "/v2/info" {
+			json(serde_json::json!({ "stacks_tip_height": 10 }))
+		} else if path == "/v2/fees/transaction" {
+			json(serde_json::json!({ "estimations": [{ "fee": 100 }] }))
+		} else if path == "/v2/transactions" {
+			let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+			let tx = StacksTransaction::consensus_deserialize(&mut &body[..])
+				.unwrap();
+			let txid = tx.txid();
+
+			submitted.lock().await.push(tx);
+			json(Value::String(txid.to_string()))
+		} else {
+			hyper::Response::builder()
+				.status(hyper::StatusCode::NOT_FOUND)
+				.body(Body::empty())
+				.unwrap()
+		};
+
+		Ok(res)
+	}
+
+	fn spending_condition(
+		key: &StacksPrivateKey,
+	) -> TransactionSpendingCondition {
+		TransactionSpendingCondition::new_singlesig_p2pkh(
+			StacksPublicKey::from_private(key),
+		)
+		.unwrap()
+	}
+
+	fn transaction(auth: TransactionAuth) -> StacksTransaction {
+		let mut tx = StacksTransaction::new(
+			TransactionVersion::Testnet,
+			auth,
+			TransactionPayload::TokenTransfer(
+				PrincipalData::Standard(StandardPrincipalData(26, [1; 20])),
+				100,
+				TokenTransferMemo([0; 34]),
+			),
+		);
+		tx.chain_id = CHAIN_ID_TESTNET;
+		tx.set_tx_fee(1000);
+
+		tx
+	}
+
+	#[test]
+	fn test_sponsored_transaction_is_signed_by_origin_and_sponsor() {
+		let origin_key = StacksPrivateKey::new();
+		let sponsor_key = StacksPrivateKey::new();
+		let tx = transaction(TransactionAuth::Sponsored(
+			spending_condition(&origin_key),
+			spending_condition(&sponsor_key),
+		));
+
+		let signed_tx =
+			sign_transaction(&tx, &origin_key, Some(&sponsor_key)).unwrap();
+
+		// Verifies the signature of the origin, and then of the sponsor
+		signed_tx.verify().unwrap();
+
+		// Signed by the wrong sponsor
+		let wrong_tx =
+			sign_transaction(&tx, &origin_key, Some(&origin_key)).unwrap();
+
+		assert!(wrong_tx.verify().is_err());
+	}
+
+	#[test]
+	fn test_standard_transaction_is_signed_by_origin() {
+		let origin_key = StacksPrivateKey::new();
+		let tx = transaction(TransactionAuth::Standard(spending_condition(
+			&origin_key,
+		)));
+
+		sign_transaction(&tx, &origin_key, None)
+			.unwrap()
+			.verify()
+			.unwrap();
+	}
+
+	#[test]
+	fn test_sponsored_transaction_requires_the_sponsor_key() {
+		let origin_key = StacksPrivateKey::new();
+		let tx = transaction(TransactionAuth::Sponsored(
+			spending_condition(&origin_key),
+			spending_condition(&StacksPrivateKey::new()),
+		));
+
+		assert!(sign_transaction(&tx, &origin_key, None).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_dropped_transaction_is_resubmitted_with_its_nonce() {
+		let (url, submitted) = serve_stacks_node(3);
+		let config = config("dropped", &url);
+		let origin_key = private_key(&config.stacks_credentials).unwrap();
+		let mut client = StacksClient::new(config, reqwest::Client::new());
+
+		let mut tx = transaction(TransactionAuth::Standard(
+			spending_condition(&origin_key),
+		));
+		tx.set_origin_nonce(3);
+
+		let dropped_txid = StacksTxId([1; 32]);
+		client.record_broadcast(3, None, dropped_txid);
+		client.unconfirmed.insert(
+			dropped_txid,
+			UnconfirmedCall {
+				tx,
+				nonce: 3,
+				sponsor_nonce: None,
+				fee: 1000,
+				block_height: 1,
+			},
+		);
+		client.origin_nonces.stall(3);
+
+		// The resubmission holds the nonce of the dropped transaction
+		assert_eq!(client.reserve_nonce(Account::Origin).await.unwrap(), 4);
+
+		let submitted = submitted.lock().await;
+		assert_eq!(submitted.len(), 1);
+
+		let resubmission = &submitted[0];
+		resubmission.verify().unwrap();
+		assert_eq!(resubmission.get_origin_nonce(), 3);
+		assert_eq!(resubmission.get_tx_fee(), 1500);
+
+		let replacement_txid = resubmission.txid();
+		assert_eq!(
+			client.replacements.get(&dropped_txid),
+			Some(&replacement_txid)
+		);
+		assert!(client.unconfirmed.contains_key(&replacement_txid));
+		assert!(!client.unconfirmed.contains_key(&dropped_txid));
+		assert_eq!(client.origin_nonces.stalled(3), None);
+	}
+
 	// These integration tests are for exploration/experimentation but should be
 	// removed once we have more decent tests
 	#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 	#[ignore]
-	async fn get_nonce_info() {
+	async fn get_account_nonce() {
 		let config = Config::from_path("./testing/config.json")
 			.expect("Failed to find config file");
 		let http_client = reqwest::Client::new();
 
-		let mut stacks_client = StacksClient::new(config, http_client);
+		let stacks_client = StacksClient::new(config, http_client);
 
-		let nonce_info = stacks_client.get_nonce_info().await.unwrap();
-		assert_eq!(nonce_info.possible_next_nonce, 122);
+		let nonce = stacks_client
+			.get_account_nonce(Account::Origin)
+			.await
+			.unwrap();
+		assert_eq!(nonce, 122);
 	}
 
 	#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
@@ -0,0 +1,126 @@
+//! Read-only queries of the asset contract
+
+use anyhow::anyhow;
+use bdk::bitcoin::Txid as BitcoinTxId;
+use blockstack_lib::vm::types::{
+	QualifiedContractIdentifier, StandardPrincipalData,
+};
+use stacks_core::{clarity::Value as ClarityValue, utils::PrincipalData};
+
+use super::StacksBackend;
+use crate::config::Config;
+
+/// Identifier of the asset contract of the config
+pub fn contract_id(config: &Config) -> QualifiedContractIdentifier {
+	let addr = config.stacks_credentials.address();
+
+	QualifiedContractIdentifier::new(
+		StandardPrincipalData(
+			addr.version() as u8,
+			addr.hash().as_ref().try_into().unwrap(),
+		),
+		config.contract_name.clone(),
+	)
+}
+
+/// Get the amount of sBTC in circulation
+pub async fn get_total_supply(
+	backend: &dyn StacksBackend,
+	contract: &QualifiedContractIdentifier,
+) -> anyhow::Result<u128> {
+	let value = backend
+		.call_read_only(contract, "get-total-supply", &[])
+		.await?;
+
+	ok_uint(value)
+}
+
+/// Get the sBTC balance of the principal
+pub async fn get_balance(
+	backend: &dyn StacksBackend,
+	contract: &QualifiedContractIdentifier,
+	owner: PrincipalData,
+) -> anyhow::Result<u128> {
+	let value = backend
+		.call_read_only(contract, "get-balance", &[ClarityValue::from(owner)])
+		.await?;
+
+	ok_uint(value)
+}
+
+/// Get the amount minted, or burned if negative, by the contract for the
+/// Bitcoin transaction, if it has been processed already
+pub async fn get_amount_by_btc_txid(
+	backend: &dyn StacksBackend,
+	contract: &QualifiedContractIdentifier,
+	txid: BitcoinTxId,
+) -> anyhow::Result<Option<i128>> {
+	// The contract stores the txids in big endian
+	let mut txid = txid.to_vec();
+	txid.reverse();
+
+	let value = backend
+		.call_read_only(
+			contract,
+			"get-amount-by-btc-txid",
+			&[ClarityValue::Buffer(txid)],
+		)
+		.await?;
+
+	match value {
+		ClarityValue::Optional(None) => Ok(None),
+		ClarityValue::Optional(Some(data)) => match *data {
+			ClarityValue::Int(amount) => Ok(Some(amount)),
+			value => Err(anyhow!("Expected an int, got {:?}", value)),
+		},
+		value => Err(anyhow!("Expected an optional, got {:?}", value)),
+	}
+}
+
+fn ok_uint(value: ClarityValue) -> anyhow::Result<u128> {
+	match value {
+		ClarityValue::ResponseOk(data) => match *data {
+			ClarityValue::UInt(amount) => Ok(amount),
+			value => Err(anyhow!("Expected a uint, got {:?}", value)),
+		},
+		value => Err(anyhow!("Expected an ok response, got {:?}", value)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use super::*;
+	use crate::stacks_client::mock::MockStacksClient;
+
+	#[tokio::test]
+	async fn test_amounts_of_processed_txids_are_read() {
+		let mock = MockStacksClient::default();
+		let contract = QualifiedContractIdentifier::new(
+			StandardPrincipalData(26, [0; 20]),
+			"asset".into(),
+		);
+		let txid = BitcoinTxId::from_str(
+			"a1f2bd2db66d0df1f2d8c9f0e7a74db9ce2c0b8d8f4a4bde1b5f6a8d3c2b1a00",
+		)
+		.unwrap();
+
+		mock.state().read_only_results.insert(
+			(contract.to_string(), "get-amount-by-btc-txid".to_string()),
+			ClarityValue::some(ClarityValue::Int(1_000)),
+		);
+		mock.state().read_only_results.insert(
+			(contract.to_string(), "get-total-supply".to_string()),
+			ClarityValue::ok(ClarityValue::UInt(21)),
+		);
+
+		assert_eq!(
+			get_amount_by_btc_txid(&mock, &contract, txid)
+				.await
+				.unwrap(),
+			Some(1_000)
+		);
+		assert_eq!(get_total_supply(&mock, &contract).await.unwrap(), 21);
+	}
+}
@@ -0,0 +1,299 @@
+//! Failover between Stacks nodes
+
+use std::{
+	sync::{Arc, Mutex, MutexGuard},
+	time::Duration,
+};
+
+use anyhow::anyhow;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use url::{Position, Url};
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Stacks nodes or APIs, used in their configured order of priority. A node
+/// whose request fails is skipped until a health check finds it responsive
+/// again, and so is a node whose chain tip lags behind the others.
+#[derive(Debug)]
+pub struct EndpointPool {
+	endpoints: Mutex<Vec<Endpoint>>,
+	/// Number of blocks a chain tip can lag behind the highest one before
+	/// its node is considered stale
+	max_tip_lag: u32,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+	url: Url,
+	healthy: bool,
+	tip_height: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+struct NodeInfo {
+	stacks_tip_height: u32,
+}
+
+impl EndpointPool {
+	/// Create a pool of the endpoints, in order of priority
+	pub fn new(urls: impl IntoIterator<Item = Url>, max_tip_lag: u32) -> Self {
+		let endpoints = urls
+			.into_iter()
+			.map(|url| Endpoint {
+				url,
+				healthy: true,
+				tip_height: None,
+			})
+			.collect();
+
+		Self {
+			endpoints: Mutex::new(endpoints),
+			max_tip_lag,
+		}
+	}
+
+	/// Checks the health and chain tips of the endpoints in a background
+	/// task. Must be called within a Tokio runtime.
+	pub fn spawn_health_checks(
+		self: &Arc<Self>,
+		http_client: reqwest::Client,
+		hiro_api_key: Option<String>,
+	) {
+		let pool = self.clone();
+
+		tokio::spawn(async move {
+			loop {
+				sleep(HEALTH_CHECK_INTERVAL).await;
+
+				if let Err(err) = pool
+					.check_health(&http_client, hiro_api_key.as_deref())
+					.await
+				{
+					warn!("Stacks node health check failed: {}", err);
+				}
+			}
+		});
+	}
+
+	/// Best endpoint not tried yet: the first healthy one with a fresh chain
+	/// tip, else the first healthy one, else the first one
+	pub fn select(&self, tried: &[Url]) -> Option<Url> {
+		let endpoints = self.lock().ok()?;
+		let best_tip = best_tip(&endpoints);
+		let untried = || {
+			endpoints
+				.iter()
+				.filter(|endpoint| !tried.contains(&endpoint.url))
+		};
+
+		untried()
+			.find(|endpoint| {
+				endpoint.healthy && !self.is_stale(endpoint, best_tip)
+			})
+			.or_else(|| untried().find(|endpoint| endpoint.healthy))
+			.or_else(|| untried().next())
+			.map(|endpoint| endpoint.url.clone())
+	}
+
+	/// Mark the endpoint as unhealthy after a failed request, so that the
+	/// next requests fail over to another one
+	pub fn report_failure(&self, url: &Url) {
+		let Ok(mut endpoints) = self.lock() else {
+			return;
+		};
+
+		if let Some(endpoint) =
+			endpoints.iter_mut().find(|endpoint| endpoint.url == *url)
+		{
+			if endpoint.healthy {
+				warn!("Stacks node {} failed, failing over", url);
+			}
+
+			endpoint.healthy = false;
+		}
+	}
+
+	/// Mark the endpoint as healthy after a successful request, which
+	/// recovers an endpoint used as a last resort before its health check
+	pub fn report_success(&self, url: &Url) {
+		let Ok(mut endpoints) = self.lock() else {
+			return;
+		};
+
+		if let Some(endpoint) =
+			endpoints.iter_mut().find(|endpoint| endpoint.url == *url)
+		{
+			endpoint.healthy = true;
+		}
+	}
+
+	/// Fetch the chain tip of every endpoint
+	pub async fn check_health(
+		&self,
+		http_client: &reqwest::Client,
+		hiro_api_key: Option<&str>,
+	) -> anyhow::Result<()> {
+		let urls: Vec<Url> = self
+			.lock()?
+			.iter()
+			.map(|endpoint| endpoint.url.clone())
+			.collect();
+
+		// The endpoints are not locked while waiting for their responses
+		let mut results = Vec::with_capacity(urls.len());
+
+		for url in urls {
+			let res = tip_height(http_client, &url, hiro_api_key).await;
+			results.push((url, res));
+		}
+
+		let mut endpoints = self.lock()?;
+
+		for (url, res) in results {
+			let Some(endpoint) =
+				endpoints.iter_mut().find(|endpoint| endpoint.url == url)
+			else {
+				continue;
+			};
+
+			match res {
+				Ok(height) => {
+					debug!("Stacks node {} is at height {}", url, height);
+
+					endpoint.healthy = true;
+					endpoint.tip_height = Some(height);
+				}
+				Err(err) => {
+					warn!("Stacks node {} is unhealthy: {}", url, err);
+
+					endpoint.healthy = false;
+				}
+			}
+		}
+
+		let best_tip = best_tip(&endpoints);
+
+		for endpoint in endpoints.iter() {
+			if endpoint.healthy && self.is_stale(endpoint, best_tip) {
+				warn!(
+					"Stacks node {} is stale at height {:?}, the highest tip \
+					 is {:?}",
+					endpoint.url, endpoint.tip_height, best_tip
+				);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Whether the endpoint lags too far behind the highest chain tip. The
+	/// endpoints of unknown tip are not stale.
+	fn is_stale(&self, endpoint: &Endpoint, best_tip: Option<u32>) -> bool {
+		match (endpoint.tip_height, best_tip) {
+			(Some(height), Some(best_tip)) => {
+				best_tip.saturating_sub(height) > self.max_tip_lag
+			}
+			_ => false,
+		}
+	}
+
+	fn lock(&self) -> anyhow::Result<MutexGuard<'_, Vec<Endpoint>>> {
+		self.endpoints
+			.lock()
+			.map_err(|_| anyhow!("Cannot get Stacks endpoint pool lock"))
+	}
+}
+
+/// URL of the request on the endpoint, keeping its path and query
+pub fn endpoint_url(endpoint: &Url, url: &Url) -> Url {
+	endpoint
+		.join(&url[Position::BeforePath..])
+		.unwrap_or_else(|_| url.clone())
+}
+
+fn best_tip(endpoints: &[Endpoint]) -> Option<u32> {
+	endpoints
+		.iter()
+		.filter(|endpoint| endpoint.healthy)
+		.filter_map(|endpoint| endpoint.tip_height)
+		.max()
+}
+
+async fn tip_height(
+	http_client: &reqwest::Client,
+	url: &Url,
+	hiro_api_key: Option<&str>,
+) -> anyhow::Result<u32> {
+	let mut request = http_client
+		.get(url.join("/v2/info")?)
+		.timeout(Duration::from_secs(10));
+
+	if let Some(api_key) = hiro_api_key {
+		request = request.header("x-hiro-api-key", api_key);
+	}
+
+	let info: NodeInfo =
+		request.send().await?.error_for_status()?.json().await?;
+
+	Ok(info.stacks_tip_height)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn url(host: &str) -> Url {
+		format!("https://{}", host).parse().unwrap()
+	}
+
+	fn pool() -> EndpointPool {
+		EndpointPool::new([url("a.example"), url("b.example")], 2)
+	}
+
+	#[test]
+	fn test_failed_endpoint_is_skipped() {
+		let pool = pool();
+
+		assert_eq!(pool.select(&[]), Some(url("a.example")));
+
+		pool.report_failure(&url("a.example"));
+
+		assert_eq!(pool.select(&[]), Some(url("b.example")));
+		assert_eq!(pool.select(&[url("b.example")]), Some(url("a.example")));
+		assert_eq!(pool.select(&[url("a.example"), url("b.example")]), None);
+
+		pool.report_success(&url("a.example"));
+
+		assert_eq!(pool.select(&[]), Some(url("a.example")));
+	}
+
+	#[test]
+	fn test_stale_endpoint_is_skipped() {
+		let pool = pool();
+
+		{
+			let mut endpoints = pool.lock().unwrap();
+			endpoints[0].tip_height = Some(100);
+			endpoints[1].tip_height = Some(102);
+		}
+
+		assert_eq!(pool.select(&[]), Some(url("a.example")));
+
+		pool.lock().unwrap()[1].tip_height = Some(103);
+
+		assert_eq!(pool.select(&[]), Some(url("b.example")));
+	}
+
+	#[test]
+	fn test_requests_keep_their_path_on_the_endpoint() {
+		let request = "http://localhost:3999/extended/v1/tx/0x00?cachebuster=a"
+			.parse()
+			.unwrap();
+
+		assert_eq!(
+			endpoint_url(&url("b.example"), &request).as_str(),
+			"https://b.example/extended/v1/tx/0x00?cachebuster=a"
+		);
+	}
+}
@@ -0,0 +1,92 @@
+//! Fees of Stacks contract calls
+
+/// Fees paid by contract calls, and their increase when the calls stay
+/// unconfirmed
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct StacksFeePolicy {
+	/// Factor applied to the fee estimated by the node
+	pub multiplier: f64,
+	/// Highest fee in microSTX
+	pub max_fee: u64,
+	/// Number of Stacks blocks a contract call may stay unconfirmed before
+	/// it is resubmitted at a higher fee, 0 to never resubmit
+	pub resubmit_after_blocks: u32,
+	/// Lowest factor the fee of a resubmission grows by
+	pub bump_multiplier: f64,
+}
+
+impl Default for StacksFeePolicy {
+	fn default() -> Self {
+		Self {
+			multiplier: 1.0,
+			max_fee: 1_000_000,
+			resubmit_after_blocks: 6,
+			bump_multiplier: 1.5,
+		}
+	}
+}
+
+impl StacksFeePolicy {
+	/// Fee of a contract call given the estimate of the node
+	pub fn fee(&self, estimate: u64) -> u64 {
+		((estimate as f64 * self.multiplier).ceil() as u64).min(self.max_fee)
+	}
+
+	/// Fee of a resubmission of a contract call paying the previous fee, or
+	/// `None` if the ceiling does not leave room for a higher one
+	pub fn bumped(&self, estimate: u64, previous: u64) -> Option<u64> {
+		let fee = self
+			.fee(estimate)
+			.max((previous as f64 * self.bump_multiplier).ceil() as u64)
+			// A replacement must pay strictly more
+			.max(previous + 1)
+			.min(self.max_fee);
+
+		(fee > previous).then_some(fee)
+	}
+}
+
+/// Fee estimations of the `/v2/fees/transaction` endpoint of a Stacks node
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FeeEstimations {
+	/// Low, middle and high estimations
+	pub estimations: Vec<FeeEstimation>,
+}
+
+/// Fee estimation of a Stacks node
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FeeEstimation {
+	/// Fee in microSTX
+	pub fee: u64,
+}
+
+impl FeeEstimations {
+	/// The middle estimation, if any
+	pub fn middle(&self) -> Option<u64> {
+		self.estimations
+			.get(self.estimations.len() / 2)
+			.map(|estimation| estimation.fee)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_resubmissions_pay_more_up_to_the_ceiling() {
+		let policy = StacksFeePolicy {
+			max_fee: 1_000,
+			..Default::default()
+		};
+
+		assert_eq!(policy.fee(400), 400);
+		assert_eq!(policy.fee(2_000), 1_000);
+
+		assert_eq!(policy.bumped(100, 400), Some(600));
+		assert_eq!(policy.bumped(800, 400), Some(800));
+		assert_eq!(policy.bumped(100, 800), Some(1_000));
+		assert_eq!(policy.bumped(100, 1_000), None);
+	}
+}
@@ -0,0 +1,259 @@
+//! In-memory Stacks backend, so that the system can be exercised without a
+//! live stacks-node
+
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use blockstack_lib::{
+	burnchains::Txid as StacksTxId,
+	chainstate::stacks::StacksTransaction,
+	vm::{types::QualifiedContractIdentifier, ContractName},
+};
+use stacks_core::{
+	address::StacksAddress, clarity::Value as ClarityValue, uint::Uint256,
+};
+
+use super::{StacksAccount, StacksBackend};
+use crate::event::TransactionStatus;
+
+/// Chain state served by a [`MockStacksClient`]
+#[derive(Debug, Default)]
+pub struct MockStacksState {
+	/// Height of the Stacks chain tip
+	pub tip_height: u32,
+	/// Transactions of the Stacks blocks, by height
+	pub blocks: HashMap<u32, Vec<StacksTransaction>>,
+	/// Bitcoin block heights of the Stacks blocks, by Stacks block height
+	pub bitcoin_block_heights: HashMap<u32, u32>,
+	/// Stacks block hashes, by Bitcoin block height
+	pub block_hashes: HashMap<u32, Uint256>,
+	/// Deployment heights of the contracts, by contract name
+	pub contract_block_heights: HashMap<String, u32>,
+	/// Sources of the deployed contracts, by contract identifier
+	pub contract_sources: HashMap<String, String>,
+	/// Accounts, by address
+	pub accounts: HashMap<String, StacksAccount>,
+	/// Results of read-only calls, by contract and function name
+	pub read_only_results: HashMap<(String, String), ClarityValue>,
+	/// Statuses of the known transactions
+	pub tx_statuses: HashMap<StacksTxId, TransactionStatus>,
+	/// Submitted transactions, in submission order
+	pub submitted: Vec<StacksTransaction>,
+}
+
+/// Stacks backend serving a shared in-memory chain state. Clones share the
+/// state, so it can be inspected and changed while the system uses the
+/// backend.
+#[derive(Debug, Clone, Default)]
+pub struct MockStacksClient(Arc<Mutex<MockStacksState>>);
+
+impl MockStacksClient {
+	/// Lock and obtain a handle to the chain state
+	pub fn state(&self) -> MutexGuard<MockStacksState> {
+		self.0.lock().unwrap()
+	}
+}
+
+#[async_trait]
+impl StacksBackend for MockStacksClient {
+	async fn submit_tx(
+		&mut self,
+		tx: StacksTransaction,
+	) -> anyhow::Result<StacksTxId> {
+		let txid = tx.txid();
+		let mut state = self.state();
+
+		state
+			.tx_statuses
+			.insert(txid, TransactionStatus::Broadcasted);
+		state.submitted.push(tx);
+
+		Ok(txid)
+	}
+
+	async fn get_tx_status(
+		&mut self,
+		txid: StacksTxId,
+	) -> anyhow::Result<TransactionStatus> {
+		self.state()
+			.tx_statuses
+			.get(&txid)
+			.cloned()
+			.ok_or_else(|| anyhow!("Unknown Stacks transaction {}", txid))
+	}
+
+	async fn get_account(
+		&self,
+		address: &StacksAddress,
+	) -> anyhow::Result<StacksAccount> {
+		Ok(self
+			.state()
+			.accounts
+			.get(&address.to_string())
+			.copied()
+			.unwrap_or_default())
+	}
+
+	async fn call_read_only(
+		&self,
+		contract: &QualifiedContractIdentifier,
+		function_name: &str,
+		_args: &[ClarityValue],
+	) -> anyhow::Result<ClarityValue> {
+		self.state()
+			.read_only_results
+			.get(&(contract.to_string(), function_name.to_string()))
+			.cloned()
+			.ok_or_else(|| {
+				anyhow!(
+					"No read-only result for {}.{}",
+					contract,
+					function_name
+				)
+			})
+	}
+
+	async fn get_contract_source(
+		&self,
+		contract: &QualifiedContractIdentifier,
+	) -> anyhow::Result<Option<String>> {
+		Ok(self
+			.state()
+			.contract_sources
+			.get(&contract.to_string())
+			.cloned())
+	}
+
+	async fn get_block(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<Vec<StacksTransaction>> {
+		self.state()
+			.blocks
+			.get(&block_height)
+			.cloned()
+			.ok_or_else(|| anyhow!("Stacks block {} not found", block_height))
+	}
+
+	async fn get_tip_height(&self) -> anyhow::Result<u32> {
+		Ok(self.state().tip_height)
+	}
+
+	async fn get_contract_block_height(
+		&self,
+		name: ContractName,
+	) -> anyhow::Result<u32> {
+		self.state()
+			.contract_block_heights
+			.get(name.as_str())
+			.copied()
+			.ok_or_else(|| anyhow!("Contract {} not found", name))
+	}
+
+	async fn get_bitcoin_block_height(
+		&self,
+		block_height: u32,
+	) -> anyhow::Result<u32> {
+		self.state()
+			.bitcoin_block_heights
+			.get(&block_height)
+			.copied()
+			.ok_or_else(|| anyhow!("Stacks block {} not found", block_height))
+	}
+
+	async fn get_block_hash_from_bitcoin_height(
+		&self,
+		height: u32,
+	) -> anyhow::Result<Uint256> {
+		self.state()
+			.block_hashes
+			.get(&height)
+			.cloned()
+			.ok_or_else(|| {
+				anyhow!("No Stacks block at Bitcoin height {}", height)
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use blockstack_lib::{
+		chainstate::stacks::{
+			TokenTransferMemo, TransactionAuth, TransactionPayload,
+			TransactionSpendingCondition, TransactionVersion,
+		},
+		types::chainstate::{StacksPrivateKey, StacksPublicKey},
+		vm::types::{PrincipalData, StandardPrincipalData},
+	};
+
+	use super::*;
+	use crate::stacks_client::LockedClient;
+
+	fn transaction() -> StacksTransaction {
+		let public_key =
+			StacksPublicKey::from_private(&StacksPrivateKey::new());
+		let auth = TransactionAuth::Standard(
+			TransactionSpendingCondition::new_singlesig_p2pkh(public_key)
+				.unwrap(),
+		);
+
+		StacksTransaction::new(
+			TransactionVersion::Testnet,
+			auth,
+			TransactionPayload::TokenTransfer(
+				PrincipalData::Standard(StandardPrincipalData(26, [0; 20])),
+				1,
+				TokenTransferMemo([0; 34]),
+			),
+		)
+	}
+
+	#[tokio::test]
+	async fn test_submitted_transactions_are_tracked() {
+		let mock = MockStacksClient::default();
+		let client = LockedClient::from(mock.clone());
+
+		let txid = client.lock().await.submit_tx(transaction()).await.unwrap();
+
+		assert_eq!(mock.state().submitted.len(), 1);
+		assert_eq!(
+			client.lock().await.get_tx_status(txid).await.unwrap(),
+			TransactionStatus::Broadcasted
+		);
+
+		mock.state()
+			.tx_statuses
+			.insert(txid, TransactionStatus::Confirmed);
+
+		assert_eq!(
+			client.lock().await.get_tx_status(txid).await.unwrap(),
+			TransactionStatus::Confirmed
+		);
+		assert!(client
+			.lock()
+			.await
+			.get_tx_status(StacksTxId([1; 32]))
+			.await
+			.is_err());
+	}
+
+	#[tokio::test]
+	async fn test_chain_state_is_served() {
+		let mock = MockStacksClient::default();
+		{
+			let mut state = mock.state();
+			state.tip_height = 10;
+			state.blocks.insert(10, vec![transaction()]);
+			state.bitcoin_block_heights.insert(10, 200);
+		}
+
+		assert_eq!(mock.get_tip_height().await.unwrap(), 10);
+		assert_eq!(mock.get_block(10).await.unwrap().len(), 1);
+		assert_eq!(mock.get_bitcoin_block_height(10).await.unwrap(), 200);
+		assert!(mock.get_block(11).await.is_err());
+	}
+}
@@ -0,0 +1,149 @@
+//! Nonces of the transactions of the Stacks account
+
+use std::{
+	collections::BTreeMap,
+	time::{Duration, Instant},
+};
+
+use blockstack_lib::burnchains::Txid as StacksTxId;
+use tracing::warn;
+
+/// Maximum number of unconfirmed transactions of an account accepted in the
+/// mempool of a Stacks node
+pub const MAX_PENDING_TRANSACTIONS: usize = 25;
+
+/// Time after which a transaction still holding up the account nonce is
+/// checked for having been dropped out of the mempool
+pub const STALLED_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Tracks the nonces of the broadcasted transactions which are not confirmed
+/// yet, so that concurrent contract calls get distinct nonces. The node only
+/// knows the nonce following the confirmed transactions, and the local view
+/// is reconciled with it before every broadcast.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+	pending: BTreeMap<u64, PendingTransaction>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingTransaction {
+	txid: StacksTxId,
+	broadcasted_at: Instant,
+}
+
+impl NonceManager {
+	/// Forget the transactions confirmed according to the account nonce of the
+	/// node
+	pub fn reconcile(&mut self, account_nonce: u64) {
+		self.pending = self.pending.split_off(&account_nonce);
+	}
+
+	/// The next usable nonce: the lowest one from the account nonce which no
+	/// pending transaction has. A gap below the last pending nonce blocks the
+	/// later transactions, so it is filled first.
+	pub fn next_nonce(&self, account_nonce: u64) -> u64 {
+		let nonce = (account_nonce..)
+			.find(|nonce| !self.pending.contains_key(nonce))
+			.expect("Nonces are exhausted");
+
+		if let Some(last_nonce) = self.pending.keys().next_back() {
+			if nonce < *last_nonce {
+				warn!(
+					"Detected a gap at nonce {} before pending nonce {}, filling it",
+					nonce, last_nonce
+				);
+			}
+		}
+
+		nonce
+	}
+
+	/// Record the broadcast of the transaction with the nonce
+	pub fn broadcasted(&mut self, nonce: u64, txid: StacksTxId) {
+		self.pending.insert(
+			nonce,
+			PendingTransaction {
+				txid,
+				broadcasted_at: Instant::now(),
+			},
+		);
+	}
+
+	/// The transaction with the account nonce, if it has been pending for so
+	/// long that it may have been dropped
+	pub fn stalled(&self, account_nonce: u64) -> Option<StacksTxId> {
+		self.pending_for(account_nonce, STALLED_TRANSACTION_TIMEOUT)
+	}
+
+	/// Release the nonce of a dropped transaction, so that it is used again
+	pub fn dropped(&mut self, nonce: u64) {
+		self.pending.remove(&nonce);
+	}
+
+	/// Make the transaction with the nonce look as if it was broadcasted long
+	/// enough ago to be stalled
+	#[cfg(test)]
+	pub fn stall(&mut self, nonce: u64) {
+		if let Some(pending) = self.pending.get_mut(&nonce) {
+			pending.broadcasted_at -= STALLED_TRANSACTION_TIMEOUT;
+		}
+	}
+
+	/// Whether the node would not accept more unconfirmed transactions, in
+	/// which case broadcasts must wait for confirmations
+	pub fn is_full(&self) -> bool {
+		self.pending.len() >= MAX_PENDING_TRANSACTIONS
+	}
+
+	fn pending_for(
+		&self,
+		nonce: u64,
+		duration: Duration,
+	) -> Option<StacksTxId> {
+		self.pending
+			.get(&nonce)
+			.filter(|pending| pending.broadcasted_at.elapsed() >= duration)
+			.map(|pending| pending.txid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn txid(byte: u8) -> StacksTxId {
+		StacksTxId([byte; 32])
+	}
+
+	#[test]
+	fn test_nonces_are_reconciled_with_the_account_nonce() {
+		let mut nonces = NonceManager::default();
+
+		assert_eq!(nonces.next_nonce(5), 5);
+		nonces.broadcasted(5, txid(5));
+		nonces.broadcasted(6, txid(6));
+		assert_eq!(nonces.next_nonce(5), 7);
+
+		nonces.reconcile(6);
+		assert_eq!(nonces.next_nonce(6), 7);
+
+		// Transactions confirmed from elsewhere
+		nonces.reconcile(9);
+		assert_eq!(nonces.next_nonce(9), 9);
+	}
+
+	#[test]
+	fn test_gaps_of_dropped_transactions_are_filled() {
+		let mut nonces = NonceManager::default();
+
+		for nonce in 3..6 {
+			nonces.broadcasted(nonce, txid(nonce as u8));
+		}
+
+		assert_eq!(nonces.stalled(3), None);
+		assert_eq!(nonces.pending_for(3, Duration::ZERO), Some(txid(3)));
+
+		nonces.dropped(3);
+		assert_eq!(nonces.next_nonce(3), 3);
+	}
+}
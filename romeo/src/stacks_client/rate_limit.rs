@@ -0,0 +1,298 @@
+//! Rate limiting of the requests to the Stacks API, so that the daemon stays
+//! within the limits of hosted APIs instead of being throttled by them
+
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Mutex,
+	},
+	time::Duration,
+};
+
+use reqwest::{header::RETRY_AFTER, Response, StatusCode, Url};
+use tokio::{
+	sync::Notify,
+	time::{sleep, Instant},
+};
+
+/// Rate limits of the requests to the Stacks API, as token buckets refilled
+/// at a number of requests per second
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct StacksRateLimitPolicy {
+	/// Requests per second to the endpoints without a limit of their own, 0
+	/// for no limit
+	pub requests_per_second: f64,
+	/// Requests sent at once after a quiet period
+	pub burst: u32,
+	/// Requests per second to the endpoints whose path starts with the key,
+	/// such as `/extended/v1/tx`. The longest matching path applies.
+	pub endpoints: HashMap<String, f64>,
+	/// Seconds to pause the requests to an endpoint that throttled one
+	/// without a `Retry-After` header
+	pub default_retry_after_secs: u64,
+	/// Number of requests waiting for their turn above which no more blocks
+	/// are fetched until the queue drains
+	pub max_queue_depth: usize,
+}
+
+impl Default for StacksRateLimitPolicy {
+	fn default() -> Self {
+		Self {
+			requests_per_second: 10.0,
+			burst: 10,
+			endpoints: HashMap::new(),
+			default_retry_after_secs: 5,
+			max_queue_depth: 16,
+		}
+	}
+}
+
+/// Token buckets of the endpoints of the policy, shared by the requests of a
+/// client
+#[derive(Debug)]
+pub struct RateLimiter {
+	policy: StacksRateLimitPolicy,
+	/// Buckets by endpoint path, `None` for the endpoints without a limit of
+	/// their own
+	buckets: Mutex<HashMap<Option<String>, Bucket>>,
+	/// Number of requests waiting for their turn
+	waiting: AtomicUsize,
+	drained: Notify,
+}
+
+#[derive(Debug)]
+struct Bucket {
+	tokens: f64,
+	refilled_at: Instant,
+	/// End of the pause requested by the API
+	paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+	/// Create the buckets of the policy, full
+	pub fn new(policy: StacksRateLimitPolicy) -> Self {
+		Self {
+			policy,
+			buckets: Default::default(),
+			waiting: AtomicUsize::new(0),
+			drained: Notify::new(),
+		}
+	}
+
+	/// Policy the limiter enforces
+	pub fn policy(&self) -> &StacksRateLimitPolicy {
+		&self.policy
+	}
+
+	/// Wait for the turn of a request to the URL
+	pub async fn acquire(&self, url: &Url) {
+		let (endpoint, rate) = self.endpoint(url);
+
+		if rate <= 0.0 {
+			return;
+		}
+
+		self.waiting.fetch_add(1, Ordering::SeqCst);
+
+		while let Some(delay) =
+			self.try_acquire(&endpoint, rate, Instant::now())
+		{
+			sleep(delay).await;
+		}
+
+		let waiting = self.waiting.fetch_sub(1, Ordering::SeqCst) - 1;
+
+		if waiting <= self.policy.max_queue_depth {
+			self.drained.notify_waiters();
+		}
+	}
+
+	/// Pause the requests to the endpoint of the URL if the response throttled
+	/// the request, for as long as its `Retry-After` header asks. Returns the
+	/// pause, after which the request can be sent again.
+	pub fn throttled(&self, url: &Url, res: &Response) -> Option<Duration> {
+		if res.status() != StatusCode::TOO_MANY_REQUESTS {
+			return None;
+		}
+
+		let pause = res
+			.headers()
+			.get(RETRY_AFTER)
+			.and_then(|value| value.to_str().ok())
+			.and_then(parse_retry_after)
+			.unwrap_or(Duration::from_secs(
+				self.policy.default_retry_after_secs,
+			));
+
+		let (endpoint, _) = self.endpoint(url);
+		self.pause(endpoint, pause, Instant::now());
+
+		Some(pause)
+	}
+
+	/// Wait until the number of requests waiting for their turn is within the
+	/// maximum queue depth, so that block processing slows down instead of
+	/// piling up requests
+	pub async fn wait_for_capacity(&self) {
+		loop {
+			// Created before the check, so that no wakeup is missed
+			let drained = self.drained.notified();
+
+			if self.waiting.load(Ordering::SeqCst)
+				<= self.policy.max_queue_depth
+			{
+				return;
+			}
+
+			drained.await;
+		}
+	}
+
+	/// Endpoint of the URL with its rate, the one of the longest matching
+	/// path of the policy
+	fn endpoint(&self, url: &Url) -> (Option<String>, f64) {
+		self.policy
+			.endpoints
+			.iter()
+			.filter(|(path, _)| url.path().starts_with(path.as_str()))
+			.max_by_key(|(path, _)| path.len())
+			.map(|(path, rate)| (Some(path.clone()), *rate))
+			.unwrap_or((None, self.policy.requests_per_second))
+	}
+
+	/// Take a token of the bucket of the endpoint, or return how long to wait
+	/// for one
+	fn try_acquire(
+		&self,
+		endpoint: &Option<String>,
+		rate: f64,
+		now: Instant,
+	) -> Option<Duration> {
+		let burst = self.policy.burst.max(1) as f64;
+		let mut buckets = self.buckets.lock().unwrap();
+		let bucket = buckets.entry(endpoint.clone()).or_insert(Bucket {
+			tokens: burst,
+			refilled_at: now,
+			paused_until: None,
+		});
+
+		if let Some(paused_until) = bucket.paused_until {
+			if paused_until > now {
+				return Some(paused_until - now);
+			}
+
+			bucket.paused_until = None;
+		}
+
+		let elapsed = now.saturating_duration_since(bucket.refilled_at);
+		bucket.tokens =
+			(bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+		bucket.refilled_at = now;
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			None
+		} else {
+			Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+		}
+	}
+
+	fn pause(&self, endpoint: Option<String>, pause: Duration, now: Instant) {
+		let mut buckets = self.buckets.lock().unwrap();
+		let bucket = buckets.entry(endpoint).or_insert(Bucket {
+			tokens: 0.0,
+			refilled_at: now,
+			paused_until: None,
+		});
+
+		bucket.paused_until = bucket.paused_until.max(Some(now + pause));
+		bucket.tokens = 0.0;
+	}
+}
+
+/// Parse a `Retry-After` header given in seconds. HTTP dates are not used by
+/// the Stacks APIs, and fall back to the default pause.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+	value.trim().parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn limiter() -> RateLimiter {
+		RateLimiter::new(StacksRateLimitPolicy {
+			requests_per_second: 2.0,
+			burst: 2,
+			endpoints: HashMap::from([
+				("/extended".to_string(), 1.0),
+				("/extended/v1/tx".to_string(), 4.0),
+			]),
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn test_longest_matching_endpoint_applies() {
+		let limiter = limiter();
+		let url =
+			|path| Url::parse("http://localhost").unwrap().join(path).unwrap();
+
+		assert_eq!(
+			limiter.endpoint(&url("/extended/v1/tx/0x00")),
+			(Some("/extended/v1/tx".to_string()), 4.0)
+		);
+		assert_eq!(
+			limiter.endpoint(&url("/extended/v1/block/by_height/1")),
+			(Some("/extended".to_string()), 1.0)
+		);
+		assert_eq!(limiter.endpoint(&url("/v2/info")), (None, 2.0));
+	}
+
+	#[test]
+	fn test_burst_is_followed_by_the_rate() {
+		let limiter = limiter();
+		let now = Instant::now();
+
+		assert_eq!(limiter.try_acquire(&None, 2.0, now), None);
+		assert_eq!(limiter.try_acquire(&None, 2.0, now), None);
+		assert_eq!(
+			limiter.try_acquire(&None, 2.0, now),
+			Some(Duration::from_millis(500))
+		);
+		assert_eq!(
+			limiter.try_acquire(&None, 2.0, now + Duration::from_millis(500)),
+			None
+		);
+	}
+
+	#[test]
+	fn test_paused_endpoint_waits_for_the_pause() {
+		let limiter = limiter();
+		let now = Instant::now();
+
+		limiter.pause(None, Duration::from_secs(3), now);
+
+		assert_eq!(
+			limiter.try_acquire(&None, 2.0, now + Duration::from_secs(1)),
+			Some(Duration::from_secs(2))
+		);
+		// Other endpoints are not paused
+		assert_eq!(
+			limiter.try_acquire(
+				&Some("/extended".to_string()),
+				1.0,
+				now + Duration::from_secs(1)
+			),
+			None
+		);
+	}
+
+	#[test]
+	fn test_retry_after_is_parsed_in_seconds() {
+		assert_eq!(parse_retry_after(" 7"), Some(Duration::from_secs(7)));
+		assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+	}
+}
@@ -1,22 +1,36 @@
 //! State
 
-use std::{io::Cursor, iter};
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	io::Cursor,
+	iter,
+};
 
-use bdk::bitcoin::{Address as BitcoinAddress, Block, Txid as BitcoinTxId};
+use anyhow::anyhow;
+use bdk::bitcoin::{
+	hashes::Hash, Address as BitcoinAddress, Block,
+	BlockHash as BitcoinBlockHash, BlockHeader, Transaction,
+	Txid as BitcoinTxId,
+};
 use blockstack_lib::{
 	burnchains::Txid as StacksTxId, chainstate::stacks::StacksTransaction,
 	codec::StacksMessageCodec, types::chainstate::StacksAddress,
 	vm::types::PrincipalData,
 };
+use rayon::prelude::*;
 use sbtc_core::operations::{
-	op_return, op_return::withdrawal_request::WithdrawalRequestData,
+	op_return::withdrawal_request::WithdrawalRequestData,
+	scan::{scan_block, Operation},
 };
 use stacks_core::codec::Codec;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
+	checkpoint::Checkpoint,
 	config::Config,
-	event::{Event, TransactionStatus},
+	deposit_registry::DepositRegistry,
+	event::{Event, OperationOverride, TransactionStatus},
+	logging,
 	task::Task,
 };
 
@@ -24,6 +38,10 @@ use crate::{
 /// the deposit transaction.
 const STX_TRANSACTION_DELAY_BLOCKS: u32 = 1;
 
+/// The number of most recent Bitcoin blocks whose hashes are kept to detect
+/// reorgs. Deeper reorgs cannot be recovered from.
+const BITCOIN_REORG_TRACKING_DEPTH: usize = 100;
+
 /// Romeo internal state
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum State {
@@ -54,6 +72,9 @@ pub enum State {
 		stacks_block_height: u32,
 		/// Bitcoin block height
 		bitcoin_block_height: u32,
+		/// Heights and hashes of the most recently processed Bitcoin blocks
+		#[serde(default)]
+		bitcoin_block_hashes: VecDeque<(u32, BitcoinBlockHash)>,
 		/// Deposits
 		deposits: Vec<Deposit>,
 		/// Withdrawals
@@ -67,7 +88,9 @@ impl State {
 		Default::default()
 	}
 
-	/// Spawn initial tasks given a recovered state
+	/// Spawn initial tasks given a recovered state. Requests whose task was
+	/// interrupted before returning its event are scheduled again, so they are
+	/// created anew once the next block is processed.
 	pub fn bootstrap(&mut self) -> Vec<Task> {
 		match self {
 			State::Uninitialized => vec![Task::GetContractBlockHeight],
@@ -85,6 +108,7 @@ impl State {
 				bitcoin_block_height,
 				deposits,
 				withdrawals,
+				..
 			} => {
 				iter::empty()
 					.chain(
@@ -120,6 +144,27 @@ impl State {
 						}
 					});
 
+				for deposit in deposits.iter_mut() {
+					reschedule_interrupted(
+						&mut deposit.mint,
+						*stacks_block_height,
+						deposit.info.txid,
+					);
+				}
+
+				for withdrawal in withdrawals.iter_mut() {
+					reschedule_interrupted(
+						&mut withdrawal.burn,
+						*stacks_block_height,
+						withdrawal.info.txid,
+					);
+					reschedule_interrupted(
+						&mut withdrawal.fulfillment,
+						*bitcoin_block_height,
+						withdrawal.info.txid,
+					);
+				}
+
 				vec![
 					Task::FetchStacksBlock(*stacks_block_height + 1),
 					Task::FetchBitcoinBlock(*bitcoin_block_height + 1),
@@ -128,9 +173,335 @@ impl State {
 		}
 	}
 
-	/// Updates the state and return new tasks to be schedules
-	#[tracing::instrument(skip(self, config))]
-	pub fn update(&mut self, event: Event, config: &Config) -> Vec<Task> {
+	/// Stacks and Bitcoin block heights processed so far, once the contract is
+	/// detected
+	pub fn block_heights(&self) -> Option<(u32, u32)> {
+		match self {
+			State::Uninitialized => None,
+			State::ContractDetected {
+				stacks_block_height,
+				bitcoin_block_height,
+			}
+			| State::ContractPublicKeySetup {
+				stacks_block_height,
+				bitcoin_block_height,
+				..
+			}
+			| State::Initialized {
+				stacks_block_height,
+				bitcoin_block_height,
+				..
+			} => Some((*stacks_block_height, *bitcoin_block_height)),
+		}
+	}
+
+	/// Progress of the scanning, once the contract is detected
+	pub fn checkpoint(&self) -> Option<Checkpoint> {
+		let (stacks_block_height, bitcoin_block_height) =
+			self.block_heights()?;
+
+		let bitcoin_block_hashes = match self {
+			State::Initialized {
+				bitcoin_block_hashes,
+				..
+			} => bitcoin_block_hashes.iter().copied().collect(),
+			_ => vec![],
+		};
+
+		Some(Checkpoint {
+			stacks_block_height,
+			bitcoin_block_height,
+			bitcoin_block_hashes,
+		})
+	}
+
+	/// Skips the blocks up to the checkpoint, for each chain whose processed
+	/// height is behind it. Requests mined in the skipped blocks are not
+	/// detected. Returns false if the contract is not detected yet, in which
+	/// case there are no heights to skip from.
+	pub fn resume_from(&mut self, checkpoint: &Checkpoint) -> bool {
+		let (stacks_block_height, bitcoin_block_height, bitcoin_block_hashes) =
+			match self {
+				State::Uninitialized => return false,
+				State::ContractDetected {
+					stacks_block_height,
+					bitcoin_block_height,
+				}
+				| State::ContractPublicKeySetup {
+					stacks_block_height,
+					bitcoin_block_height,
+					..
+				} => (stacks_block_height, bitcoin_block_height, None),
+				State::Initialized {
+					stacks_block_height,
+					bitcoin_block_height,
+					bitcoin_block_hashes,
+					..
+				} => (
+					stacks_block_height,
+					bitcoin_block_height,
+					Some(bitcoin_block_hashes),
+				),
+			};
+
+		if checkpoint.stacks_block_height > *stacks_block_height {
+			info!(
+				"Resuming Stacks blocks from height {}",
+				checkpoint.stacks_block_height
+			);
+
+			*stacks_block_height = checkpoint.stacks_block_height;
+		}
+
+		if checkpoint.bitcoin_block_height > *bitcoin_block_height {
+			info!(
+				"Resuming Bitcoin blocks from height {}",
+				checkpoint.bitcoin_block_height
+			);
+
+			*bitcoin_block_height = checkpoint.bitcoin_block_height;
+
+			if let Some(bitcoin_block_hashes) = bitcoin_block_hashes {
+				*bitcoin_block_hashes =
+					checkpoint.bitcoin_block_hashes.iter().copied().collect();
+			}
+		}
+
+		true
+	}
+
+	/// Number of deposits detected
+	pub fn deposit_count(&self) -> usize {
+		match self {
+			State::Initialized { deposits, .. } => deposits.len(),
+			_ => 0,
+		}
+	}
+
+	/// Whether the state is ready to process deposits and withdrawals
+	pub fn is_initialized(&self) -> bool {
+		matches!(self, State::Initialized { .. })
+	}
+
+	/// Deposits and withdrawals whose processing is not complete
+	pub fn pending_operations(&self) -> PendingOperations {
+		let State::Initialized {
+			deposits,
+			withdrawals,
+			..
+		} = self
+		else {
+			return Default::default();
+		};
+
+		PendingOperations {
+			deposits: deposits
+				.iter()
+				.filter(|deposit| deposit.is_pending())
+				.count(),
+			withdrawals: withdrawals
+				.iter()
+				.filter(|withdrawal| withdrawal.is_pending())
+				.count(),
+		}
+	}
+
+	/// Deposits detected so far, once initialized
+	pub fn deposits(&self) -> &[Deposit] {
+		match self {
+			State::Initialized { deposits, .. } => deposits,
+			_ => &[],
+		}
+	}
+
+	/// Withdrawals detected so far, once initialized
+	pub fn withdrawals(&self) -> &[Withdrawal] {
+		match self {
+			State::Initialized { withdrawals, .. } => withdrawals,
+			_ => &[],
+		}
+	}
+
+	/// Checks that the override can be applied to the deposit or withdrawal
+	/// of the Bitcoin transaction. Operations with a task in flight are
+	/// refused, since the event of the task would no longer match them.
+	pub fn check_override(
+		&self,
+		txid: BitcoinTxId,
+		action: OperationOverride,
+	) -> anyhow::Result<()> {
+		let deposit = self
+			.deposits()
+			.iter()
+			.find(|deposit| deposit.info.txid == txid);
+		let withdrawal = self
+			.withdrawals()
+			.iter()
+			.find(|withdrawal| withdrawal.info.txid == txid);
+
+		// Only the scheduled transaction of the current step of an operation
+		// can be requeued
+		let (is_pending, in_flight, requeueable) = match (deposit, withdrawal) {
+			(Some(deposit), _) => (
+				deposit.is_pending(),
+				is_in_flight(&deposit.mint),
+				deposit.mint.as_ref().map(|_| Ok(())),
+			),
+			(None, Some(withdrawal)) => (
+				withdrawal.is_pending(),
+				is_in_flight(&withdrawal.burn)
+					|| is_in_flight(&withdrawal.fulfillment),
+				if is_confirmed(&withdrawal.burn) {
+					withdrawal.fulfillment.as_ref().map(|fulfillment| {
+						match fulfillment {
+							// A fulfillment that may still confirm would pay
+							// the withdrawal twice, stuck ones are bumped
+							// instead
+							TransactionRequest::Acknowledged {
+								status, ..
+							} if *status != TransactionStatus::Rejected => {
+								Err(anyhow!(
+									"Fulfillment of withdrawal {} is not rejected, only rejected fulfillments are requeued",
+									txid
+								))
+							}
+							_ => Ok(()),
+						}
+					})
+				} else {
+					withdrawal.burn.as_ref().map(|_| Ok(()))
+				},
+			),
+			(None, None) => {
+				return Err(anyhow!("No deposit or withdrawal {}", txid))
+			}
+		};
+
+		if !is_pending {
+			return Err(anyhow!("Operation {} is already complete", txid));
+		}
+
+		if in_flight {
+			return Err(anyhow!(
+				"Operation {} has a task in flight, retry once it returns",
+				txid
+			));
+		}
+
+		if action == OperationOverride::Requeue {
+			requeueable.ok_or_else(|| {
+				anyhow!("Operation {} has no transaction to requeue yet", txid)
+			})??;
+		}
+
+		Ok(())
+	}
+
+	/// Correlation IDs of the deposits and withdrawals the task operates on,
+	/// which are the txids of their Bitcoin request transactions
+	pub fn operation_ids_of_task(&self, task: &Task) -> Vec<BitcoinTxId> {
+		match task {
+			Task::CreateMint(deposit_info) => vec![deposit_info.txid],
+			Task::CreateBurn(withdrawal_info) => vec![withdrawal_info.txid],
+			Task::CreateFulfillment(withdrawal_infos)
+			| Task::BumpFulfillmentFee(withdrawal_infos, ..) => {
+				withdrawal_infos.iter().map(|info| info.txid).collect()
+			}
+			Task::CheckStacksTransactionStatus(txid) => {
+				self.operation_ids_of_stacks_transaction(txid)
+			}
+			Task::CheckBitcoinTransactionStatus(txid) => {
+				self.operation_ids_of_bitcoin_transaction(txid)
+			}
+			_ => vec![],
+		}
+	}
+
+	/// Correlation IDs of the deposits and withdrawals the event is about
+	pub fn operation_ids_of_event(&self, event: &Event) -> Vec<BitcoinTxId> {
+		match event {
+			Event::MintBroadcasted(deposit_info, _)
+			| Event::DepositAlreadyMinted(deposit_info) => {
+				vec![deposit_info.txid]
+			}
+			Event::BurnBroadcasted(withdrawal_info, _)
+			| Event::WithdrawalAlreadyBurned(withdrawal_info)
+			| Event::FulfillBroadcasted(withdrawal_info, ..) => {
+				vec![withdrawal_info.txid]
+			}
+			Event::FulfillmentBatchBroadcasted(withdrawal_infos, ..)
+			| Event::FulfillmentFeeBumped(withdrawal_infos, ..) => {
+				withdrawal_infos.iter().map(|info| info.txid).collect()
+			}
+			Event::OperationOverridden(txid, _) => vec![*txid],
+			Event::StacksTransactionUpdate(txid, _) => {
+				self.operation_ids_of_stacks_transaction(txid)
+			}
+			Event::BitcoinTransactionUpdate(txid, _) => {
+				self.operation_ids_of_bitcoin_transaction(txid)
+			}
+			_ => vec![],
+		}
+	}
+
+	fn operation_ids_of_stacks_transaction(
+		&self,
+		txid: &StacksTxId,
+	) -> Vec<BitcoinTxId> {
+		let State::Initialized {
+			deposits,
+			withdrawals,
+			..
+		} = self
+		else {
+			return vec![];
+		};
+
+		let mints = deposits
+			.iter()
+			.filter(|deposit| acknowledged_txid(&deposit.mint) == Some(txid))
+			.map(|deposit| deposit.info.txid);
+		let burns = withdrawals
+			.iter()
+			.filter(|withdrawal| {
+				acknowledged_txid(&withdrawal.burn) == Some(txid)
+			})
+			.map(|withdrawal| withdrawal.info.txid);
+
+		mints.chain(burns).collect()
+	}
+
+	fn operation_ids_of_bitcoin_transaction(
+		&self,
+		txid: &BitcoinTxId,
+	) -> Vec<BitcoinTxId> {
+		let State::Initialized { withdrawals, .. } = self else {
+			return vec![];
+		};
+
+		withdrawals
+			.iter()
+			.filter(|withdrawal| {
+				acknowledged_txid(&withdrawal.fulfillment) == Some(txid)
+			})
+			.map(|withdrawal| withdrawal.info.txid)
+			.collect()
+	}
+
+	/// Updates the state and return new tasks to be schedules. Deposits
+	/// without an OP_RETURN are attributed with the deposit registry.
+	#[tracing::instrument(
+		skip(self, config, deposit_registry),
+		fields(operation = logging::correlation_id(
+			&self.operation_ids_of_event(&event)
+		))
+	)]
+	pub fn update(
+		&mut self,
+		event: Event,
+		config: &Config,
+		deposit_registry: &DepositRegistry,
+	) -> Vec<Task> {
 		info!("Processing");
 
 		match event {
@@ -149,29 +520,69 @@ impl State {
 				.process_bitcoin_transaction_update(txid, status, config)
 				.into_iter()
 				.collect(),
-			Event::StacksBlock(height, txs) => {
-				self.process_stacks_block(height, txs).into_iter().collect()
-			}
+			Event::StacksBlock(height, txs) => self
+				.process_stacks_block(height, txs, config)
+				.into_iter()
+				.collect(),
 			Event::BitcoinBlock(height, block) => self
-				.process_bitcoin_block(config, height, block)
+				.process_bitcoin_block(config, deposit_registry, height, block)
 				.into_iter()
 				.collect(),
+			Event::BitcoinBlocks(blocks) => {
+				self.process_bitcoin_blocks(config, deposit_registry, blocks)
+			}
+			Event::Reorg { depth } => self.process_bitcoin_reorg(depth),
 			Event::MintBroadcasted(deposit_info, txid) => {
 				self.process_mint_broadcasted(deposit_info, txid, config);
 				vec![]
 			}
+			Event::DepositAlreadyMinted(deposit_info) => {
+				self.process_deposit_already_minted(deposit_info);
+				vec![]
+			}
 			Event::BurnBroadcasted(withdrawal_info, txid) => {
 				self.process_burn_broadcasted(withdrawal_info, txid, config);
 				vec![]
 			}
-			Event::FulfillBroadcasted(withdrawal_info, txid) => {
+			Event::WithdrawalAlreadyBurned(withdrawal_info) => {
+				self.process_withdrawal_already_burned(withdrawal_info);
+				vec![]
+			}
+			Event::FulfillBroadcasted(withdrawal_info, txid, sat_per_vb) => {
+				self.process_fulfillment_broadcasted(
+					vec![withdrawal_info],
+					txid,
+					sat_per_vb,
+					config,
+				);
+				vec![]
+			}
+			Event::FulfillmentBatchBroadcasted(
+				withdrawal_infos,
+				txid,
+				sat_per_vb,
+			) => {
 				self.process_fulfillment_broadcasted(
-					withdrawal_info,
+					withdrawal_infos,
+					txid,
+					Some(sat_per_vb),
+					config,
+				);
+				vec![]
+			}
+			Event::FulfillmentFeeBumped(withdrawal_infos, txid, sat_per_vb) => {
+				self.process_fulfillment_fee_bumped(
+					withdrawal_infos,
 					txid,
+					sat_per_vb,
 					config,
 				);
 				vec![]
 			}
+			Event::OperationOverridden(txid, action) => {
+				self.process_operation_override(txid, action);
+				vec![]
+			}
 		}
 	}
 
@@ -227,7 +638,7 @@ impl State {
 		status: TransactionStatus,
 		config: &Config,
 	) -> Vec<Task> {
-		let mut tasks = self.get_bitcoin_transactions();
+		let mut tasks = self.get_bitcoin_transactions(config);
 
 		let statuses_updated = match self {
 			State::Uninitialized => None,
@@ -280,6 +691,7 @@ impl State {
 					*self = Self::Initialized {
 						stacks_block_height: *stacks_block_height,
 						bitcoin_block_height,
+						bitcoin_block_hashes: VecDeque::new(),
 						deposits: vec![],
 						withdrawals: vec![],
 					};
@@ -418,20 +830,22 @@ impl State {
 			    true
 			}).map(|updated| updated as usize).sum();
 
-		if statuses_updated != 1 {
+		// A batched fulfillment transaction is shared by several withdrawals
+		if statuses_updated == 0 {
 			panic!(
 				"Unexpected number of statuses updated: {}",
 				statuses_updated
 			);
 		}
 
-		self.get_stacks_transactions()
+		self.get_stacks_transactions(config)
 	}
 
 	fn process_stacks_block(
 		&mut self,
 		stacks_height: u32,
 		_txs: Vec<StacksTransaction>,
+		config: &Config,
 	) -> Vec<Task> {
 		let stacks_block_height = match self {
 			State::Uninitialized | State::ContractDetected { .. } => panic!("Cannot process Stacks block if uninitialized or contract detected"),
@@ -450,7 +864,7 @@ impl State {
 		let mut tasks = vec![Task::FetchStacksBlock(stacks_height + 1)];
 
 		tasks.extend(self.get_stacks_status_checks());
-		tasks.extend(self.get_bitcoin_transactions());
+		tasks.extend(self.get_bitcoin_transactions(config));
 
 		tasks
 	}
@@ -458,11 +872,62 @@ impl State {
 	fn process_bitcoin_block(
 		&mut self,
 		config: &Config,
+		deposit_registry: &DepositRegistry,
 		bitcoin_height: u32,
 		block: Block,
+	) -> Vec<Task> {
+		let parsed_block = parse_bitcoin_block(
+			config,
+			deposit_registry,
+			bitcoin_height,
+			&block,
+		);
+
+		self.process_parsed_bitcoin_block(config, parsed_block)
+	}
+
+	fn process_bitcoin_blocks(
+		&mut self,
+		config: &Config,
+		deposit_registry: &DepositRegistry,
+		blocks: Vec<(u32, Block)>,
+	) -> Vec<Task> {
+		let parsed_blocks =
+			parse_bitcoin_blocks(config, deposit_registry, &blocks);
+
+		let mut tasks = vec![];
+
+		for parsed_block in parsed_blocks {
+			let block_tasks =
+				self.process_parsed_bitcoin_block(config, parsed_block);
+			let extends_chain =
+				matches!(block_tasks.first(), Some(Task::FetchBitcoinBlock(_)));
+
+			// Only the block following the last processed one is fetched
+			tasks.retain(|task| !matches!(task, Task::FetchBitcoinBlock(_)));
+			tasks.extend(block_tasks);
+
+			if !extends_chain {
+				break;
+			}
+		}
+
+		tasks
+	}
+
+	fn process_parsed_bitcoin_block(
+		&mut self,
+		config: &Config,
+		ParsedBitcoinBlock {
+			height: bitcoin_height,
+			header,
+			deposits: parsed_deposits,
+			withdrawals: parsed_withdrawals,
+		}: ParsedBitcoinBlock,
 	) -> Vec<Task> {
 		let State::Initialized {
 			bitcoin_block_height,
+			bitcoin_block_hashes,
 			deposits,
 			withdrawals,
 			..
@@ -471,44 +936,208 @@ impl State {
 			panic!("Cannot process Stacks block if not initialized")
 		};
 
+		if let Some((_, parent_hash)) = bitcoin_block_hashes.back() {
+			if *parent_hash != header.prev_blockhash {
+				warn!(
+					"Bitcoin block {} does not extend the processed chain, looking for the fork point",
+					bitcoin_height
+				);
+
+				return vec![Task::FindBitcoinForkPoint(
+					bitcoin_block_hashes.iter().copied().collect(),
+				)];
+			}
+		}
+
 		*bitcoin_block_height = bitcoin_height;
 
-		deposits.extend(parse_deposits(config, bitcoin_height, &block));
-		withdrawals.extend(parse_withdrawals(config, &block));
+		bitcoin_block_hashes.push_back((bitcoin_height, header.block_hash()));
+
+		if bitcoin_block_hashes.len() > BITCOIN_REORG_TRACKING_DEPTH {
+			bitcoin_block_hashes.pop_front();
+		}
+
+		// Requests that survived a reorg are mined again in the new chain
+		let new_deposits: Vec<_> = parsed_deposits
+			.into_iter()
+			.filter(|deposit| {
+				deposits
+					.iter()
+					.all(|known| known.info.txid != deposit.info.txid)
+			})
+			.collect();
+		let new_withdrawals: Vec<_> = parsed_withdrawals
+			.into_iter()
+			.filter(|withdrawal| {
+				withdrawals
+					.iter()
+					.all(|known| known.info.txid != withdrawal.info.txid)
+			})
+			.collect();
+
+		deposits.extend(new_deposits);
+		withdrawals.extend(new_withdrawals);
 
 		let mut tasks = vec![Task::FetchBitcoinBlock(bitcoin_height + 1)];
 
+		// Bumps take the place of the status checks of stuck fulfillments
+		tasks.extend(self.get_fulfillment_fee_bumps(config));
 		tasks.extend(self.get_bitcoin_status_checks());
-		tasks.extend(self.get_stacks_transactions());
+		tasks.extend(self.get_bitcoin_transactions(config));
+		tasks.extend(self.get_stacks_transactions(config));
 
 		tasks
 	}
 
-	fn get_bitcoin_transactions(&mut self) -> Vec<Task> {
-		let State::Initialized { withdrawals, .. } = self else {
+	fn process_bitcoin_reorg(&mut self, depth: u32) -> Vec<Task> {
+		let State::Initialized {
+			bitcoin_block_height,
+			bitcoin_block_hashes,
+			deposits,
+			withdrawals,
+			..
+		} = self
+		else {
+			panic!("Cannot process Bitcoin reorg if not initialized")
+		};
+
+		// A reorg deeper than the processed chain leaves no common block,
+		// so everything is rolled back and the chain is scanned again
+		let fork_height =
+			bitcoin_block_height.checked_sub(depth).unwrap_or_else(|| {
+				error!(
+					"Bitcoin reorg of depth {} is deeper than block {}, rescanning the chain",
+					depth, bitcoin_block_height
+				);
+				0
+			});
+
+		warn!(
+			"Bitcoin reorg of depth {}, rolling back to block {}",
+			depth, fork_height
+		);
+
+		*bitcoin_block_height = fork_height;
+		bitcoin_block_hashes.retain(|(height, _)| *height <= fork_height);
+
+		// Requests whose Stacks transaction is already created cannot be
+		// rolled back, they are kept and matched again in the new chain
+		deposits.retain(|deposit| {
+			let orphaned = deposit.info.block_height > fork_height;
+			let in_flight = matches!(
+				deposit.mint,
+				None | Some(TransactionRequest::Scheduled { .. })
+			);
+
+			if orphaned && !in_flight {
+				warn!(
+					"Deposit {} was orphaned after its mint was created",
+					deposit.info.txid
+				);
+			}
+
+			!(orphaned && in_flight)
+		});
+		withdrawals.retain(|withdrawal| {
+			let orphaned = withdrawal.info.block_height > fork_height;
+			let in_flight = matches!(
+				withdrawal.burn,
+				None | Some(TransactionRequest::Scheduled { .. })
+			);
+
+			if orphaned && !in_flight {
+				warn!(
+					"Withdrawal {} was orphaned after its burn was created",
+					withdrawal.info.txid
+				);
+			}
+
+			!(orphaned && in_flight)
+		});
+
+		vec![Task::FetchBitcoinBlock(fork_height + 1)]
+	}
+
+	fn get_bitcoin_transactions(&mut self, config: &Config) -> Vec<Task> {
+		let State::Initialized {
+			bitcoin_block_height,
+			withdrawals,
+			..
+		} = self
+		else {
 			return vec![];
 		};
 
-		withdrawals
-			.iter_mut()
-			.filter_map(|withdrawal| match withdrawal.burn {
+		let bitcoin_block_height = *bitcoin_block_height;
+		let batch = &config.fulfillment_batch;
+
+		// Withdrawals with a confirmed burn wait for their batch to fill
+		for withdrawal in withdrawals.iter_mut() {
+			if let (
 				Some(TransactionRequest::Acknowledged {
 					status: TransactionStatus::Confirmed,
 					..
-				}) => match withdrawal.fulfillment.as_mut() {
-					None => {
-						withdrawal.fulfillment =
-							Some(TransactionRequest::Created);
-						Some(Task::CreateFulfillment(withdrawal.info.clone()))
-					}
-					_ => None,
-				},
-				_ => None,
+				}),
+				None,
+			) = (&withdrawal.burn, &withdrawal.fulfillment)
+			{
+				withdrawal.fulfillment = Some(TransactionRequest::Scheduled {
+					block_height: bitcoin_block_height + batch.max_wait_blocks,
+				});
+			}
+		}
+
+		let mut pending: Vec<&mut Withdrawal> = withdrawals
+			.iter_mut()
+			.filter(|withdrawal| {
+				matches!(
+					withdrawal.fulfillment,
+					Some(TransactionRequest::Scheduled { .. })
+				)
 			})
-			.collect()
+			.collect();
+
+		let is_due = |withdrawal: &&mut Withdrawal| {
+			matches!(
+				withdrawal.fulfillment,
+				Some(TransactionRequest::Scheduled { block_height })
+					if block_height <= bitcoin_block_height
+			)
+		};
+
+		let mut tasks = vec![];
+
+		while pending.len() >= batch.max_size || pending.iter().any(is_due) {
+			let mut batch_infos: Vec<WithdrawalInfo> = vec![];
+
+			// Outputs paying the same amount to the same recipient cannot be
+			// told apart when ordering them, so they go in separate batches
+			pending.retain_mut(|withdrawal| {
+				let duplicate = batch_infos.iter().any(|info| {
+					info.amount == withdrawal.info.amount
+						&& info.recipient.script_pubkey()
+							== withdrawal.info.recipient.script_pubkey()
+				});
+
+				if batch_infos.len() == batch.max_size || duplicate {
+					return true;
+				}
+
+				withdrawal.fulfillment = Some(TransactionRequest::Created);
+				batch_infos.push(withdrawal.info.clone());
+
+				false
+			});
+
+			debug!("Created fulfillment for {} withdrawals", batch_infos.len());
+
+			tasks.push(Task::CreateFulfillment(batch_infos));
+		}
+
+		tasks
 	}
 
-	fn get_stacks_transactions(&mut self) -> Vec<Task> {
+	fn get_stacks_transactions(&mut self, config: &Config) -> Vec<Task> {
 		match self {
 			State::Uninitialized | State::ContractPublicKeySetup { .. } => {
 				vec![]
@@ -521,10 +1150,18 @@ impl State {
 				deposits,
 				withdrawals,
 				stacks_block_height,
+				bitcoin_block_height,
 				..
 			} => {
 				let deposit_tasks = deposits.iter_mut().filter_map(|deposit| {
+					let confirmations = (*bitcoin_block_height + 1)
+						.saturating_sub(deposit.info.block_height);
+
 					match deposit.mint.as_mut() {
+						// Wait for the deposit to be buried deep enough
+						None if confirmations < config.min_confirmations => {
+							None
+						}
 						None => {
 							// We often receive the deposit before the
 							// transaction is actually mined. By scheduling the
@@ -637,6 +1274,8 @@ impl State {
 	}
 
 	fn get_bitcoin_status_checks(&mut self) -> Vec<Task> {
+		let mut checked_txids = HashSet::new();
+
 		match self {
 			State::Initialized { withdrawals, .. } => withdrawals
 				.iter_mut()
@@ -644,11 +1283,16 @@ impl State {
 				.filter_map(|req| match req {
 					TransactionRequest::Acknowledged {
 						txid,
-						status: TransactionStatus::Broadcasted,
+						status:
+							TransactionStatus::Broadcasted
+							| TransactionStatus::Confirming { .. },
 						has_pending_task,
 					} if !*has_pending_task => {
 						*has_pending_task = true;
-						Some(Task::CheckBitcoinTransactionStatus(*txid))
+						// Batched withdrawals share a single check
+						checked_txids.insert(*txid).then_some(
+							Task::CheckBitcoinTransactionStatus(*txid),
+						)
 					}
 					_ => None,
 				})
@@ -657,6 +1301,78 @@ impl State {
 		}
 	}
 
+	fn get_fulfillment_fee_bumps(&mut self, config: &Config) -> Vec<Task> {
+		let bump_after_blocks = config.bitcoin_fee.bump_after_blocks;
+
+		let State::Initialized {
+			bitcoin_block_height,
+			withdrawals,
+			..
+		} = self
+		else {
+			return vec![];
+		};
+
+		if bump_after_blocks == 0 {
+			return vec![];
+		}
+
+		// Batched withdrawals are bumped together
+		let mut bumps: Vec<(BitcoinTxId, Vec<WithdrawalInfo>, Option<f32>)> =
+			vec![];
+
+		for withdrawal in withdrawals.iter_mut() {
+			let Some(broadcast) = withdrawal.fulfillment_broadcast.as_ref()
+			else {
+				continue;
+			};
+
+			if *bitcoin_block_height
+				< broadcast.block_height + bump_after_blocks
+			{
+				continue;
+			}
+
+			let Some(TransactionRequest::Acknowledged {
+				txid,
+				status: TransactionStatus::Broadcasted,
+				has_pending_task,
+			}) = withdrawal.fulfillment.as_mut()
+			else {
+				continue;
+			};
+
+			if *has_pending_task {
+				continue;
+			}
+
+			*has_pending_task = true;
+
+			match bumps.iter_mut().find(|(bumped, ..)| bumped == txid) {
+				Some((_, infos, _)) => infos.push(withdrawal.info.clone()),
+				None => {
+					debug!(
+						"Fulfillment {} unconfirmed since block {}, bumping its fee",
+						txid, broadcast.block_height
+					);
+
+					bumps.push((
+						*txid,
+						vec![withdrawal.info.clone()],
+						broadcast.sat_per_vb,
+					));
+				}
+			}
+		}
+
+		bumps
+			.into_iter()
+			.map(|(txid, infos, sat_per_vb)| {
+				Task::BumpFulfillmentFee(infos, txid, sat_per_vb)
+			})
+			.collect()
+	}
+
 	fn process_mint_broadcasted(
 		&mut self,
 		deposit_info: DepositInfo,
@@ -687,6 +1403,21 @@ impl State {
 		});
 	}
 
+	/// The deposit is considered minted, with the null txid since the mint
+	/// transaction is not known
+	fn process_deposit_already_minted(&mut self, deposit_info: DepositInfo) {
+		let State::Initialized { deposits, .. } = self else {
+			panic!("Cannot process minted deposit if uninitialized")
+		};
+
+		let deposit = deposits
+			.iter_mut()
+			.find(|deposit| deposit.info == deposit_info)
+			.expect("Could not find a deposit for the mint");
+
+		deposit.mint = Some(already_processed());
+	}
+
 	fn process_burn_broadcasted(
 		&mut self,
 		withdrawal_info: WithdrawalInfo,
@@ -716,33 +1447,196 @@ impl State {
 		});
 	}
 
-	fn process_fulfillment_broadcasted(
+	/// The withdrawal is considered burned, with the null txid since the burn
+	/// transaction is not known
+	fn process_withdrawal_already_burned(
 		&mut self,
 		withdrawal_info: WithdrawalInfo,
-		txid: BitcoinTxId,
-		config: &Config,
 	) {
 		let State::Initialized { withdrawals, .. } = self else {
-			panic!("Cannot process broadcasted fulfillment if uninitialized")
+			panic!("Cannot process burned withdrawal if uninitialized")
 		};
 
 		let withdrawal = withdrawals
 			.iter_mut()
 			.find(|withdrawal| withdrawal.info == withdrawal_info)
-			.expect("Could not find a withdrawal for the fulfillment");
+			.expect("Could not find a withdrawal for the burn");
 
-		if config.strict {
-			assert!(
-			matches!(withdrawal.fulfillment, Some(TransactionRequest::Created)),
-			"Newly fulfilled withdrawal already has fulfillment acknowledged"
-		);
+		withdrawal.burn = Some(already_processed());
+	}
+
+	fn process_fulfillment_broadcasted(
+		&mut self,
+		withdrawal_infos: Vec<WithdrawalInfo>,
+		txid: BitcoinTxId,
+		sat_per_vb: Option<f32>,
+		config: &Config,
+	) {
+		let State::Initialized {
+			bitcoin_block_height,
+			withdrawals,
+			..
+		} = self
+		else {
+			panic!("Cannot process broadcasted fulfillment if uninitialized")
+		};
+
+		for withdrawal_info in withdrawal_infos {
+			let withdrawal = withdrawals
+				.iter_mut()
+				.find(|withdrawal| withdrawal.info == withdrawal_info)
+				.expect("Could not find a withdrawal for the fulfillment");
+
+			if config.strict {
+				assert!(
+					matches!(
+						withdrawal.fulfillment,
+						Some(TransactionRequest::Created)
+					),
+					"Newly fulfilled withdrawal already has fulfillment acknowledged"
+				);
+			}
+
+			withdrawal.fulfillment = Some(TransactionRequest::Acknowledged {
+				txid,
+				status: TransactionStatus::Broadcasted,
+				has_pending_task: false,
+			});
+			withdrawal.fulfillment_broadcast = Some(FulfillmentBroadcast {
+				block_height: *bitcoin_block_height,
+				sat_per_vb,
+			});
 		}
+	}
 
-		withdrawal.fulfillment = Some(TransactionRequest::Acknowledged {
+	fn process_fulfillment_fee_bumped(
+		&mut self,
+		withdrawal_infos: Vec<WithdrawalInfo>,
+		txid: BitcoinTxId,
+		sat_per_vb: f32,
+		config: &Config,
+	) {
+		let State::Initialized {
+			bitcoin_block_height,
+			withdrawals,
+			..
+		} = self
+		else {
+			panic!("Cannot process bumped fulfillment if uninitialized")
+		};
+
+		info!(
+			"Fulfillment of {} withdrawals replaced by {} at {} sat/vB",
+			withdrawal_infos.len(),
 			txid,
-			status: TransactionStatus::Broadcasted,
-			has_pending_task: false,
-		});
+			sat_per_vb
+		);
+
+		for withdrawal_info in withdrawal_infos {
+			let withdrawal = withdrawals
+				.iter_mut()
+				.find(|withdrawal| withdrawal.info == withdrawal_info)
+				.expect(
+					"Could not find a withdrawal for the bumped fulfillment",
+				);
+
+			if config.strict {
+				assert!(
+					matches!(
+						withdrawal.fulfillment,
+						Some(TransactionRequest::Acknowledged {
+							has_pending_task: true,
+							..
+						})
+					),
+					"Bumped fulfillment is not pending a fee bump"
+				);
+			}
+
+			withdrawal.fulfillment = Some(TransactionRequest::Acknowledged {
+				txid,
+				status: TransactionStatus::Broadcasted,
+				has_pending_task: false,
+			});
+			withdrawal.fulfillment_broadcast = Some(FulfillmentBroadcast {
+				block_height: *bitcoin_block_height,
+				sat_per_vb: Some(sat_per_vb),
+			});
+		}
+	}
+
+	/// Applies an override checked by [`State::check_override`]. Requeued
+	/// transactions are created again once the next block is processed, and
+	/// abandoned operations are forgotten.
+	fn process_operation_override(
+		&mut self,
+		txid: BitcoinTxId,
+		action: OperationOverride,
+	) {
+		let State::Initialized {
+			stacks_block_height,
+			bitcoin_block_height,
+			deposits,
+			withdrawals,
+			..
+		} = self
+		else {
+			panic!("Cannot override an operation if uninitialized")
+		};
+
+		warn!("Operator override {:?} of operation {}", action, txid);
+
+		if action == OperationOverride::Abandon {
+			deposits.retain(|deposit| deposit.info.txid != txid);
+			withdrawals.retain(|withdrawal| withdrawal.info.txid != txid);
+
+			return;
+		}
+
+		if let Some(deposit) = deposits
+			.iter_mut()
+			.find(|deposit| deposit.info.txid == txid)
+		{
+			deposit.mint = Some(match action {
+				OperationOverride::Requeue => TransactionRequest::Scheduled {
+					block_height: *stacks_block_height,
+				},
+				_ => already_processed(),
+			});
+
+			return;
+		}
+
+		let withdrawal = withdrawals
+			.iter_mut()
+			.find(|withdrawal| withdrawal.info.txid == txid)
+			.expect("Could not find the overridden operation");
+
+		match action {
+			OperationOverride::Requeue if !is_confirmed(&withdrawal.burn) => {
+				withdrawal.burn = Some(TransactionRequest::Scheduled {
+					block_height: *stacks_block_height,
+				});
+			}
+			OperationOverride::Requeue => {
+				withdrawal.fulfillment = Some(TransactionRequest::Scheduled {
+					block_height: *bitcoin_block_height,
+				});
+				withdrawal.fulfillment_broadcast = None;
+			}
+			_ => {
+				if !is_confirmed(&withdrawal.burn) {
+					withdrawal.burn = Some(already_processed());
+				}
+
+				withdrawal.fulfillment =
+					Some(TransactionRequest::Acknowledged {
+						txid: BitcoinTxId::all_zeros(),
+						status: TransactionStatus::Confirmed,
+						has_pending_task: false,
+					});
+			}
+		}
 	}
 }
 
@@ -752,106 +1646,254 @@ impl Default for State {
 	}
 }
 
+/// Requests of a Bitcoin block, parsed before the block is processed
+struct ParsedBitcoinBlock {
+	height: u32,
+	header: BlockHeader,
+	deposits: Vec<Deposit>,
+	withdrawals: Vec<Withdrawal>,
+}
+
+fn parse_bitcoin_block(
+	config: &Config,
+	deposit_registry: &DepositRegistry,
+	height: u32,
+	block: &Block,
+) -> ParsedBitcoinBlock {
+	ParsedBitcoinBlock {
+		height,
+		header: block.header,
+		deposits: parse_deposits(config, deposit_registry, height, block),
+		withdrawals: parse_withdrawals(config, height, block),
+	}
+}
+
+/// Parses the blocks in parallel, since scanning the transactions is the
+/// costly part of processing blocks. The parsed blocks keep the order of the
+/// blocks.
+fn parse_bitcoin_blocks(
+	config: &Config,
+	deposit_registry: &DepositRegistry,
+	blocks: &[(u32, Block)],
+) -> Vec<ParsedBitcoinBlock> {
+	blocks
+		.par_iter()
+		.map(|(height, block)| {
+			parse_bitcoin_block(config, deposit_registry, *height, block)
+		})
+		.collect()
+}
+
 fn parse_deposits(
 	config: &Config,
+	deposit_registry: &DepositRegistry,
 	bitcoin_height: u32,
 	block: &Block,
 ) -> Vec<Deposit> {
 	let sbtc_wallet_address = config.sbtc_wallet_address();
+	let mut scanned_deposits: HashMap<_, _> =
+		scan_block(block, config.bitcoin_credentials.network())
+			.into_iter()
+			.filter_map(|parsed_operation| match parsed_operation.operation {
+				Operation::Deposit(deposit) => {
+					Some((parsed_operation.txid, deposit))
+				}
+				Operation::WithdrawalRequest(_)
+				| Operation::WalletHandoff(_) => None,
+			})
+			.collect();
+
 	block
 		.txdata
 		.iter()
-		.cloned()
 		.filter_map(|tx| {
 			let txid = tx.txid();
 
-			op_return::deposit::Deposit::parse(
-				config.bitcoin_credentials.network(),
-				tx,
-			)
-			.ok()
-			.filter(|parsed_deposit| {
-				parsed_deposit.sbtc_wallet_address == sbtc_wallet_address
-			})
-			.map(|parsed_deposit| {
-				let bytes = parsed_deposit.recipient.serialize_to_vec();
-				let recipient = PrincipalData::consensus_deserialize(
-					&mut Cursor::new(bytes),
-				)
-				.unwrap();
+			let (amount, recipient) = match scanned_deposits.remove(&txid) {
+				Some(parsed_deposit) => {
+					if parsed_deposit.sbtc_wallet_address != sbtc_wallet_address
+					{
+						return None;
+					}
 
-				Deposit {
-					info: DepositInfo {
-						txid,
-						amount: parsed_deposit.amount,
-						recipient,
-						block_height: bitcoin_height,
-					},
-					mint: None,
+					let bytes = parsed_deposit.recipient.serialize_to_vec();
+					let recipient = PrincipalData::consensus_deserialize(
+						&mut Cursor::new(bytes),
+					)
+					.unwrap();
+
+					(parsed_deposit.amount, recipient)
 				}
+				None => parse_registered_deposit(deposit_registry, tx)?,
+			};
+
+			Some(Deposit {
+				info: DepositInfo {
+					txid,
+					amount,
+					recipient,
+					block_height: bitcoin_height,
+				},
+				mint: None,
 			})
 		})
 		.collect()
 }
 
-fn parse_withdrawals(config: &Config, block: &Block) -> Vec<Withdrawal> {
-	let sbtc_wallet_address = config.sbtc_wallet_address();
-	let block_height = block
-		.bip34_block_height()
-		.expect("Failed to get block height") as u32;
+/// Amount and recipient of a deposit without an OP_RETURN, paying the first
+/// registered deposit address among the outputs of the transaction
+fn parse_registered_deposit(
+	deposit_registry: &DepositRegistry,
+	tx: &Transaction,
+) -> Option<(u64, PrincipalData)> {
+	let deposit_address = tx
+		.output
+		.iter()
+		.find_map(|output| deposit_registry.by_script(&output.script_pubkey))?;
+	let script_pubkey = deposit_address.address.script_pubkey();
 
-	block
-		.txdata
+	let amount = tx
+		.output
 		.iter()
-		.cloned()
-		.filter_map(|tx| {
-			let txid = tx.txid();
+		.filter(|output| output.script_pubkey == script_pubkey)
+		.map(|output| output.value)
+		.sum();
 
-			op_return::withdrawal_request::try_parse_withdrawal_request(
-				config.bitcoin_network,
-				tx,
-			)
-			.ok()
-			.filter(|parsed_withdrawal| {
-				parsed_withdrawal.sbtc_wallet == sbtc_wallet_address
-			})
-			.map(
-				|WithdrawalRequestData {
-				     payee_bitcoin_address,
-				     drawee_stacks_address,
-				     amount,
-				     ..
-				 }| {
-					let blockstack_lib_address =
-						StacksAddress::consensus_deserialize(&mut Cursor::new(
-							drawee_stacks_address.serialize_to_vec(),
-						))
-						.unwrap();
-					let source = PrincipalData::from(blockstack_lib_address);
-
-					Withdrawal {
-						info: WithdrawalInfo {
-							txid,
-							amount,
-							source,
-							recipient: payee_bitcoin_address,
-							block_height,
-						},
-						burn: None,
-						fulfillment: None,
-					}
-				},
-			)
+	Some((amount, deposit_address.recipient))
+}
+
+fn parse_withdrawals(
+	config: &Config,
+	block_height: u32,
+	block: &Block,
+) -> Vec<Withdrawal> {
+	let sbtc_wallet_address = config.sbtc_wallet_address();
+
+	scan_block(block, config.bitcoin_network)
+		.into_iter()
+		.filter_map(|parsed_operation| {
+			let txid = parsed_operation.txid;
+
+			let Operation::WithdrawalRequest(parsed_withdrawal) =
+				parsed_operation.operation
+			else {
+				return None;
+			};
+
+			Some(parsed_withdrawal)
+				.filter(|parsed_withdrawal| {
+					parsed_withdrawal.sbtc_wallet == sbtc_wallet_address
+				})
+				.map(
+					|WithdrawalRequestData {
+					     payee_bitcoin_address,
+					     drawee_stacks_address,
+					     amount,
+					     ..
+					 }| {
+						let blockstack_lib_address =
+							StacksAddress::consensus_deserialize(
+								&mut Cursor::new(
+									drawee_stacks_address.serialize_to_vec(),
+								),
+							)
+							.unwrap();
+						let source =
+							PrincipalData::from(blockstack_lib_address);
+
+						Withdrawal {
+							info: WithdrawalInfo {
+								txid,
+								amount,
+								source,
+								recipient: payee_bitcoin_address,
+								block_height,
+							},
+							burn: None,
+							fulfillment: None,
+							fulfillment_broadcast: None,
+						}
+					},
+				)
 		})
 		.collect()
 }
 
+/// Schedules the request at the block height again if its task was
+/// interrupted
+fn reschedule_interrupted<T>(
+	req: &mut Option<TransactionRequest<T>>,
+	block_height: u32,
+	txid: BitcoinTxId,
+) {
+	if let Some(TransactionRequest::Created) = req {
+		warn!(
+			"Transaction request of {} was interrupted, scheduling it again",
+			txid
+		);
+
+		*req = Some(TransactionRequest::Scheduled { block_height });
+	}
+}
+
+/// Confirmed request of a Stacks transaction the contract had already
+/// processed before it was created
+fn already_processed() -> TransactionRequest<StacksTxId> {
+	TransactionRequest::Acknowledged {
+		txid: StacksTxId([0; 32]),
+		status: TransactionStatus::Confirmed,
+		has_pending_task: false,
+	}
+}
+
+fn acknowledged_txid<T>(req: &Option<TransactionRequest<T>>) -> Option<&T> {
+	match req {
+		Some(TransactionRequest::Acknowledged { txid, .. }) => Some(txid),
+		_ => None,
+	}
+}
+
+/// Whether a task is creating the transaction or checking its status
+fn is_in_flight<T>(req: &Option<TransactionRequest<T>>) -> bool {
+	matches!(
+		req,
+		Some(
+			TransactionRequest::Created
+				| TransactionRequest::Acknowledged {
+					has_pending_task: true,
+					..
+				}
+		)
+	)
+}
+
+fn is_confirmed<T>(req: &Option<TransactionRequest<T>>) -> bool {
+	matches!(
+		req,
+		Some(TransactionRequest::Acknowledged {
+			status: TransactionStatus::Confirmed,
+			..
+		})
+	)
+}
+
+/// Numbers of deposits not minted yet and of withdrawals not fulfilled yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct PendingOperations {
+	/// Deposits whose mint is not confirmed
+	pub deposits: usize,
+	/// Withdrawals whose fulfillment is not confirmed
+	pub withdrawals: usize,
+}
+
 /// A transaction request
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum TransactionRequest<T> {
-	/// Scheduled to be created at a given stacks block height.
+	/// Scheduled to be created at a given block height. Stacks transactions
+	/// are scheduled at Stacks block heights, and fulfillments at Bitcoin
+	/// block heights.
 	Scheduled {
-		/// The stacks block height at which the transaction should be created.
+		/// The block height at which the transaction should be created.
 		block_height: u32,
 	},
 	/// Created and passed on to a task
@@ -874,6 +1916,23 @@ pub struct Deposit {
 	mint: Option<TransactionRequest<StacksTxId>>,
 }
 
+impl Deposit {
+	/// Information of the deposit request
+	pub fn info(&self) -> &DepositInfo {
+		&self.info
+	}
+
+	/// Mint transaction request, once scheduled
+	pub fn mint(&self) -> Option<&TransactionRequest<StacksTxId>> {
+		self.mint.as_ref()
+	}
+
+	/// Whether the mint is not confirmed yet
+	pub fn is_pending(&self) -> bool {
+		!is_confirmed(&self.mint)
+	}
+}
+
 /// Relevant information for processing deposits
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub struct DepositInfo {
@@ -896,6 +1955,47 @@ pub struct Withdrawal {
 	info: WithdrawalInfo,
 	burn: Option<TransactionRequest<StacksTxId>>,
 	fulfillment: Option<TransactionRequest<BitcoinTxId>>,
+	#[serde(default)]
+	fulfillment_broadcast: Option<FulfillmentBroadcast>,
+}
+
+impl Withdrawal {
+	/// Information of the withdrawal request
+	pub fn info(&self) -> &WithdrawalInfo {
+		&self.info
+	}
+
+	/// Burn transaction request, once scheduled
+	pub fn burn(&self) -> Option<&TransactionRequest<StacksTxId>> {
+		self.burn.as_ref()
+	}
+
+	/// Fulfillment transaction request, once scheduled
+	pub fn fulfillment(&self) -> Option<&TransactionRequest<BitcoinTxId>> {
+		self.fulfillment.as_ref()
+	}
+
+	/// Bitcoin block height of the last broadcast of the fulfillment
+	pub fn fulfillment_broadcast_height(&self) -> Option<u32> {
+		self.fulfillment_broadcast
+			.as_ref()
+			.map(|broadcast| broadcast.block_height)
+	}
+
+	/// Whether the fulfillment is not confirmed yet
+	pub fn is_pending(&self) -> bool {
+		!is_confirmed(&self.fulfillment)
+	}
+}
+
+/// Bitcoin block height and fee rate of the last broadcast fulfillment, used
+/// to bump the fee of fulfillments that stay unconfirmed
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct FulfillmentBroadcast {
+	block_height: u32,
+	/// Fee rate in sat/vB, unknown for fulfillments broadcasted without an
+	/// explicit fee rate
+	sat_per_vb: Option<f32>,
 }
 
 /// Relevant information for processing withdrawals
@@ -917,3 +2017,612 @@ pub struct WithdrawalInfo {
 	/// transaction exists
 	pub block_height: u32,
 }
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{
+		hashes::Hash, Network as BitcoinNetwork, Script, TxMerkleNode,
+	};
+
+	use super::*;
+
+	const PRINCIPAL: &str = "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM";
+
+	fn config(name: &str) -> Config {
+		let dir = std::env::temp_dir().join(format!(
+			"romeo-state-{}-{}",
+			name,
+			std::process::id()
+		));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("config.json");
+
+		let config = serde_json::json!({
+			"state_directory": "./state",
+			"mnemonic": "twice kind fence tip hidden tilt action fragile skin nothing glory cousin green tomorrow spring wrist shed math olympic multiply hip blue scout claw",
+			"network": "devnet",
+			"contract_name": "asset",
+		});
+		std::fs::write(&path, config.to_string()).unwrap();
+
+		Config::from_path(&path).unwrap()
+	}
+
+	fn txid(n: u8) -> BitcoinTxId {
+		BitcoinTxId::from_inner([n; 32])
+	}
+
+	fn block_hash(height: u32) -> BitcoinBlockHash {
+		BitcoinBlockHash::hash(&height.to_le_bytes())
+	}
+
+	fn header(prev_blockhash: BitcoinBlockHash) -> BlockHeader {
+		BlockHeader {
+			version: 2,
+			prev_blockhash,
+			merkle_root: TxMerkleNode::all_zeros(),
+			time: 0,
+			bits: 0,
+			nonce: 0,
+		}
+	}
+
+	fn acknowledged<T>(
+		txid: T,
+		status: TransactionStatus,
+	) -> Option<TransactionRequest<T>> {
+		Some(TransactionRequest::Acknowledged {
+			txid,
+			status,
+			has_pending_task: false,
+		})
+	}
+
+	fn deposit(
+		n: u8,
+		block_height: u32,
+		mint: Option<TransactionRequest<StacksTxId>>,
+	) -> Deposit {
+		Deposit {
+			info: DepositInfo {
+				txid: txid(n),
+				amount: 1000,
+				recipient: PrincipalData::parse(PRINCIPAL).unwrap(),
+				block_height,
+			},
+			mint,
+		}
+	}
+
+	fn withdrawal(
+		n: u8,
+		block_height: u32,
+		burn: Option<TransactionRequest<StacksTxId>>,
+	) -> Withdrawal {
+		Withdrawal {
+			info: WithdrawalInfo {
+				txid: txid(n),
+				amount: 1000 + n as u64,
+				source: PrincipalData::parse(PRINCIPAL).unwrap(),
+				recipient: BitcoinAddress::p2wsh(
+					&Script::new(),
+					BitcoinNetwork::Regtest,
+				),
+				block_height,
+			},
+			burn,
+			fulfillment: None,
+			fulfillment_broadcast: None,
+		}
+	}
+
+	/// State which processed the Bitcoin blocks 101 to 105
+	fn initialized(
+		deposits: Vec<Deposit>,
+		withdrawals: Vec<Withdrawal>,
+	) -> State {
+		State::Initialized {
+			stacks_block_height: 10,
+			bitcoin_block_height: 105,
+			bitcoin_block_hashes: (101..=105)
+				.map(|height| (height, block_hash(height)))
+				.collect(),
+			deposits,
+			withdrawals,
+		}
+	}
+
+	fn parsed_block(
+		height: u32,
+		prev_blockhash: BitcoinBlockHash,
+		deposits: Vec<Deposit>,
+	) -> ParsedBitcoinBlock {
+		ParsedBitcoinBlock {
+			height,
+			header: header(prev_blockhash),
+			deposits,
+			withdrawals: vec![],
+		}
+	}
+
+	/// Empty block extending the processed chain
+	fn next_block(state: &State) -> ParsedBitcoinBlock {
+		let State::Initialized {
+			bitcoin_block_hashes,
+			..
+		} = state
+		else {
+			panic!("State is not initialized")
+		};
+
+		let (height, hash) = *bitcoin_block_hashes.back().unwrap();
+
+		parsed_block(height + 1, hash, vec![])
+	}
+
+	/// Withdrawal whose burn is confirmed, waiting for its fulfillment
+	fn burned_withdrawal(n: u8) -> Withdrawal {
+		withdrawal(
+			n,
+			104,
+			acknowledged(StacksTxId([n; 32]), TransactionStatus::Confirmed),
+		)
+	}
+
+	fn fulfillments(tasks: &[Task]) -> Vec<Vec<BitcoinTxId>> {
+		tasks
+			.iter()
+			.filter_map(|task| match task {
+				Task::CreateFulfillment(infos) => {
+					Some(infos.iter().map(|info| info.txid).collect())
+				}
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn bitcoin_block_heights(state: &State) -> (u32, Vec<u32>) {
+		let State::Initialized {
+			bitcoin_block_height,
+			bitcoin_block_hashes,
+			..
+		} = state
+		else {
+			panic!("State is not initialized")
+		};
+
+		(
+			*bitcoin_block_height,
+			bitcoin_block_hashes
+				.iter()
+				.map(|(height, _)| *height)
+				.collect(),
+		)
+	}
+
+	fn deposit_txids(state: &State) -> Vec<BitcoinTxId> {
+		state
+			.deposits()
+			.iter()
+			.map(|deposit| deposit.info.txid)
+			.collect()
+	}
+
+	fn withdrawal_txids(state: &State) -> Vec<BitcoinTxId> {
+		state
+			.withdrawals()
+			.iter()
+			.map(|withdrawal| withdrawal.info.txid)
+			.collect()
+	}
+
+	#[test]
+	fn test_reorg_drops_orphaned_requests_not_created_yet() {
+		let mut state = initialized(
+			vec![
+				deposit(
+					1,
+					102,
+					Some(TransactionRequest::Scheduled { block_height: 11 }),
+				),
+				deposit(2, 104, None),
+				deposit(
+					3,
+					105,
+					Some(TransactionRequest::Scheduled { block_height: 11 }),
+				),
+				deposit(4, 104, Some(TransactionRequest::Created)),
+			],
+			vec![
+				withdrawal(5, 104, None),
+				withdrawal(
+					6,
+					105,
+					acknowledged(
+						StacksTxId([6; 32]),
+						TransactionStatus::Broadcasted,
+					),
+				),
+				withdrawal(7, 103, None),
+			],
+		);
+
+		let tasks = state.process_bitcoin_reorg(2);
+
+		assert!(matches!(tasks[..], [Task::FetchBitcoinBlock(104)]));
+		assert_eq!(bitcoin_block_heights(&state), (103, vec![101, 102, 103]));
+		assert_eq!(deposit_txids(&state), vec![txid(1), txid(4)]);
+		assert_eq!(withdrawal_txids(&state), vec![txid(6), txid(7)]);
+	}
+
+	#[test]
+	fn test_block_not_extending_the_chain_finds_the_fork_point() {
+		let config = config("fork-point");
+		let mut state = initialized(vec![], vec![]);
+
+		let tasks = state.process_parsed_bitcoin_block(
+			&config,
+			parsed_block(106, block_hash(1105), vec![deposit(1, 106, None)]),
+		);
+
+		let [Task::FindBitcoinForkPoint(blocks)] = &tasks[..] else {
+			panic!("Unexpected tasks {:?}", tasks)
+		};
+		assert_eq!(
+			*blocks,
+			(101..=105)
+				.map(|height| (height, block_hash(height)))
+				.collect::<Vec<_>>()
+		);
+		assert_eq!(bitcoin_block_heights(&state).0, 105);
+		assert!(state.deposits().is_empty());
+	}
+
+	#[test]
+	fn test_block_after_reorg_extends_the_fork_point() {
+		let config = config("after-reorg");
+		let mut state = initialized(
+			vec![deposit(4, 104, Some(TransactionRequest::Created))],
+			vec![],
+		);
+
+		state.process_bitcoin_reorg(2);
+
+		// The deposit whose mint survived the reorg is mined again
+		let block =
+			parsed_block(104, block_hash(103), vec![deposit(4, 104, None)]);
+		let hash = block.header.block_hash();
+		let tasks = state.process_parsed_bitcoin_block(&config, block);
+
+		assert!(matches!(tasks.first(), Some(Task::FetchBitcoinBlock(105))));
+		assert_eq!(
+			bitcoin_block_heights(&state),
+			(104, vec![101, 102, 103, 104])
+		);
+		assert!(matches!(
+			state.deposits(),
+			[Deposit {
+				mint: Some(TransactionRequest::Created),
+				..
+			}]
+		));
+
+		let State::Initialized {
+			bitcoin_block_hashes,
+			..
+		} = &state
+		else {
+			panic!("State is not initialized")
+		};
+		assert_eq!(bitcoin_block_hashes.back(), Some(&(104, hash)));
+	}
+
+	#[test]
+	fn test_reorg_deeper_than_the_tracked_blocks_rolls_all_back() {
+		let config = config("deep-reorg");
+		let mut state = initialized(vec![deposit(1, 101, None)], vec![]);
+
+		// Depth of a reorg which forked off before the oldest tracked block
+		let tasks = state.process_bitcoin_reorg(5);
+
+		assert!(matches!(tasks[..], [Task::FetchBitcoinBlock(101)]));
+		assert_eq!(bitcoin_block_heights(&state), (100, vec![]));
+		assert!(state.deposits().is_empty());
+
+		// Without tracked blocks, any block is accepted as the next one
+		let tasks = state.process_parsed_bitcoin_block(
+			&config,
+			parsed_block(101, block_hash(2100), vec![]),
+		);
+
+		assert!(matches!(tasks.first(), Some(Task::FetchBitcoinBlock(102))));
+		assert_eq!(bitcoin_block_heights(&state), (101, vec![101]));
+	}
+
+	#[test]
+	fn test_reorg_deeper_than_the_chain_rescans_it() {
+		let mut state = initialized(vec![deposit(1, 101, None)], vec![]);
+
+		let tasks = state.process_bitcoin_reorg(200);
+
+		assert!(matches!(tasks[..], [Task::FetchBitcoinBlock(1)]));
+		assert_eq!(bitcoin_block_heights(&state), (0, vec![]));
+		assert!(state.deposits().is_empty());
+	}
+
+	#[test]
+	fn test_fulfillment_batches_are_limited_to_the_max_size() {
+		let mut config = config("batch-max-size");
+		config.fulfillment_batch.max_size = 2;
+		config.fulfillment_batch.max_wait_blocks = 3;
+
+		let mut state =
+			initialized(vec![], (1..=5).map(burned_withdrawal).collect());
+
+		let tasks =
+			state.process_parsed_bitcoin_block(&config, next_block(&state));
+
+		assert_eq!(
+			fulfillments(&tasks),
+			vec![vec![txid(1), txid(2)], vec![txid(3), txid(4)]]
+		);
+		assert!(state.withdrawals()[..4].iter().all(|withdrawal| matches!(
+			withdrawal.fulfillment,
+			Some(TransactionRequest::Created)
+		)));
+		assert!(matches!(
+			state.withdrawals()[4].fulfillment,
+			Some(TransactionRequest::Scheduled { block_height: 109 })
+		));
+	}
+
+	#[test]
+	fn test_partial_fulfillment_batch_is_created_after_the_max_wait() {
+		let mut config = config("batch-max-wait");
+		config.fulfillment_batch.max_size = 3;
+		config.fulfillment_batch.max_wait_blocks = 2;
+
+		let mut state =
+			initialized(vec![], (1..=2).map(burned_withdrawal).collect());
+
+		// Blocks 106 and 107 wait for the batch to fill
+		for _ in 0..2 {
+			let tasks =
+				state.process_parsed_bitcoin_block(&config, next_block(&state));
+
+			assert!(fulfillments(&tasks).is_empty());
+		}
+
+		let tasks =
+			state.process_parsed_bitcoin_block(&config, next_block(&state));
+
+		assert_eq!(fulfillments(&tasks), vec![vec![txid(1), txid(2)]]);
+	}
+
+	#[test]
+	fn test_batched_withdrawals_are_acknowledged_by_the_broadcast() {
+		let mut config = config("batch-status");
+		config.fulfillment_batch.max_size = 2;
+		config.fulfillment_batch.max_wait_blocks = 3;
+
+		// The first two withdrawals are duplicates, which cannot share a
+		// batch
+		let mut duplicate = burned_withdrawal(2);
+		duplicate.info.amount = 1001;
+
+		let mut state = initialized(
+			vec![],
+			vec![burned_withdrawal(1), duplicate, burned_withdrawal(3)],
+		);
+
+		let tasks =
+			state.process_parsed_bitcoin_block(&config, next_block(&state));
+		let [Task::CreateFulfillment(infos)] = &tasks[..] else {
+			panic!("Unexpected tasks {:?}", tasks)
+		};
+
+		assert_eq!(
+			infos.iter().map(|info| info.txid).collect::<Vec<_>>(),
+			vec![txid(1), txid(3)]
+		);
+		assert!(matches!(
+			state.withdrawals()[1].fulfillment,
+			Some(TransactionRequest::Scheduled { .. })
+		));
+
+		state.process_fulfillment_broadcasted(
+			infos.clone(),
+			txid(10),
+			Some(2.0),
+			&config,
+		);
+
+		for withdrawal in [&state.withdrawals()[0], &state.withdrawals()[2]] {
+			assert!(matches!(
+				withdrawal.fulfillment,
+				Some(TransactionRequest::Acknowledged {
+					txid: fulfillment_txid,
+					status: TransactionStatus::Broadcasted,
+					..
+				}) if fulfillment_txid == txid(10)
+			));
+			assert_eq!(withdrawal.fulfillment_broadcast_height(), Some(106));
+		}
+		assert!(matches!(
+			state.withdrawals()[1].fulfillment,
+			Some(TransactionRequest::Scheduled { .. })
+		));
+	}
+
+	/// Empty blocks chained from the last processed block 105
+	fn chained_blocks(count: u32) -> Vec<(u32, Block)> {
+		let mut prev_blockhash = block_hash(105);
+
+		(106..106 + count)
+			.map(|height| {
+				let block = Block {
+					header: header(prev_blockhash),
+					txdata: vec![],
+				};
+				prev_blockhash = block.block_hash();
+
+				(height, block)
+			})
+			.collect()
+	}
+
+	fn deposit_registry(config: &Config) -> DepositRegistry {
+		DepositRegistry::open(
+			&config.state_directory,
+			config.deposit_xpub,
+			config.bitcoin_network,
+		)
+		.unwrap()
+	}
+
+	#[test]
+	fn test_parallel_parsing_keeps_the_order_of_the_blocks() {
+		let config = config("parse-order");
+		let blocks = chained_blocks(64);
+
+		let parsed_blocks =
+			parse_bitcoin_blocks(&config, &deposit_registry(&config), &blocks);
+
+		assert_eq!(
+			parsed_blocks
+				.iter()
+				.map(|block| (block.height, block.header.block_hash()))
+				.collect::<Vec<_>>(),
+			blocks
+				.iter()
+				.map(|(height, block)| (*height, block.block_hash()))
+				.collect::<Vec<_>>()
+		);
+	}
+
+	#[test]
+	fn test_batch_of_blocks_is_processed_in_order() {
+		let config = config("batch-order");
+		let mut state = initialized(vec![], vec![]);
+		let blocks = chained_blocks(16);
+		let last_hash = blocks.last().unwrap().1.block_hash();
+
+		let tasks = state.update(
+			Event::BitcoinBlocks(blocks),
+			&config,
+			&deposit_registry(&config),
+		);
+
+		let fetches: Vec<_> = tasks
+			.iter()
+			.filter_map(|task| match task {
+				Task::FetchBitcoinBlock(height) => Some(*height),
+				_ => None,
+			})
+			.collect();
+
+		assert_eq!(fetches, vec![122]);
+		assert_eq!(bitcoin_block_heights(&state), (121, (101..=121).collect()));
+
+		let State::Initialized {
+			bitcoin_block_hashes,
+			..
+		} = &state
+		else {
+			panic!("State is not initialized")
+		};
+		assert_eq!(bitcoin_block_hashes.back(), Some(&(121, last_hash)));
+	}
+
+	/// Numbers of mint and burn tasks
+	fn contract_calls(tasks: &[Task]) -> (usize, usize) {
+		let mints = tasks
+			.iter()
+			.filter(|task| matches!(task, Task::CreateMint(_)))
+			.count();
+		let burns = tasks
+			.iter()
+			.filter(|task| matches!(task, Task::CreateBurn(_)))
+			.count();
+
+		(mints, burns)
+	}
+
+	#[test]
+	fn test_replayed_events_create_one_task_per_request() {
+		let config = config("replay");
+		let deposit_registry = deposit_registry(&config);
+		let new_state = || {
+			initialized(
+				vec![deposit(1, 104, None)],
+				vec![withdrawal(2, 104, None)],
+			)
+		};
+		let mut blocks = chained_blocks(4).into_iter();
+		let mut bitcoin_block = || {
+			let (height, block) = blocks.next().unwrap();
+
+			Event::BitcoinBlock(height, block)
+		};
+
+		// The mint and burn are created, and the system stops before their
+		// tasks return
+		let mut log = vec![
+			bitcoin_block(),
+			Event::StacksBlock(11, vec![]),
+			bitcoin_block(),
+		];
+
+		let mut state = new_state();
+		let mut tasks = vec![];
+
+		for event in log.clone() {
+			tasks.extend(state.update(event, &config, &deposit_registry));
+		}
+
+		assert_eq!(contract_calls(&tasks), (1, 1));
+
+		// Replaying the log schedules the interrupted requests again, which
+		// are created once more
+		let mut state = new_state();
+
+		for event in log.clone() {
+			state.update(event, &config, &deposit_registry);
+		}
+
+		let mut tasks = state.bootstrap();
+		let event = bitcoin_block();
+		tasks.extend(state.update(event.clone(), &config, &deposit_registry));
+		log.push(event);
+
+		assert_eq!(contract_calls(&tasks), (1, 1));
+
+		// The contract has already processed them
+		log.push(Event::DepositAlreadyMinted(
+			state.deposits()[0].info.clone(),
+		));
+		log.push(Event::WithdrawalAlreadyBurned(
+			state.withdrawals()[0].info.clone(),
+		));
+		log.push(bitcoin_block());
+		log.push(Event::StacksBlock(12, vec![]));
+
+		// Replaying the whole log creates them no more
+		let mut state = new_state();
+		let mut tasks = vec![];
+
+		for event in log.clone() {
+			state.update(event, &config, &deposit_registry);
+		}
+
+		tasks.extend(state.bootstrap());
+		tasks.extend(state.update(
+			Event::StacksBlock(13, vec![]),
+			&config,
+			&deposit_registry,
+		));
+
+		assert_eq!(contract_calls(&tasks), (0, 0));
+		assert!(state.deposits().iter().all(|deposit| !deposit.is_pending()));
+	}
+}
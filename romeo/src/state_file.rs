@@ -0,0 +1,410 @@
+//! Versions of the files of the state directory and the migrations between
+//! them. Every file records the version it was written at, and files written
+//! by an older romeo are migrated one version at a time when they are loaded.
+
+use std::{
+	collections::HashMap,
+	fmt::Display,
+	time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use bdk::bitcoin::Txid as BitcoinTxId;
+use serde_json::Value;
+use tracing::subscriber::NoSubscriber;
+
+use crate::{
+	checkpoint::Checkpoint,
+	config::{Config, StateAction},
+	deposit_registry::DepositRegistry,
+	event::{Event, TransactionStatus},
+	event_log::EventLog,
+	state::{Deposit, State, TransactionRequest, Withdrawal},
+};
+
+/// Version of the state files written by this build
+pub const STATE_VERSION: u32 = 1;
+
+/// Migration of a JSON value from a version to the next one
+type Migration = fn(&mut Value) -> anyhow::Result<()>;
+
+/// Migrations of the events of the log. The migration at index `n` migrates
+/// an event from version `n` to version `n + 1`.
+const EVENT_MIGRATIONS: [Migration; STATE_VERSION as usize] = [event_v0_to_v1];
+
+/// Migrations of the checkpoint, indexed like the event migrations
+const CHECKPOINT_MIGRATIONS: [Migration; STATE_VERSION as usize] =
+	[checkpoint_v0_to_v1];
+
+/// Header written as the first line of the event log
+#[derive(
+	Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(deny_unknown_fields)]
+pub struct Header {
+	/// Version the events of the log are written at
+	pub version: u32,
+}
+
+/// Migrate an event written at the version to the current one
+pub fn migrate_event(event: &mut Value, version: u32) -> anyhow::Result<()> {
+	migrate(event, version, &EVENT_MIGRATIONS)
+}
+
+/// Migrate a checkpoint written at the version to the current one
+pub fn migrate_checkpoint(
+	checkpoint: &mut Value,
+	version: u32,
+) -> anyhow::Result<()> {
+	migrate(checkpoint, version, &CHECKPOINT_MIGRATIONS)
+}
+
+/// Fail if the version was written by a newer romeo, which this one cannot
+/// migrate from
+pub fn check_version(version: u32) -> anyhow::Result<()> {
+	if version > STATE_VERSION {
+		return Err(anyhow!(
+			"State version {} is newer than the version {} of this romeo",
+			version,
+			STATE_VERSION
+		));
+	}
+
+	Ok(())
+}
+
+fn migrate(
+	value: &mut Value,
+	version: u32,
+	migrations: &[Migration],
+) -> anyhow::Result<()> {
+	check_version(version)?;
+
+	migrations[version as usize..]
+		.iter()
+		.try_for_each(|migration| migration(value))
+}
+
+/// Version 0 logs have no header, and their fulfillments were broadcasted
+/// without recording the fee rate
+fn event_v0_to_v1(event: &mut Value) -> anyhow::Result<()> {
+	if let Some(Value::Array(fields)) = event.get_mut("FulfillBroadcasted") {
+		if fields.len() == 2 {
+			fields.push(Value::Null);
+		}
+	}
+
+	Ok(())
+}
+
+/// Version 1 only added the version to the checkpoint
+fn checkpoint_v0_to_v1(_: &mut Value) -> anyhow::Result<()> {
+	Ok(())
+}
+
+/// Run a `romeo state` command
+pub async fn run_command(
+	config: Config,
+	action: StateAction,
+) -> anyhow::Result<()> {
+	match action {
+		StateAction::Migrate => {
+			print_migration(
+				"Event log",
+				EventLog::migrate(&config.state_directory).await?,
+			);
+			print_migration(
+				"Checkpoint",
+				Checkpoint::migrate(&config.state_directory)?,
+			);
+		}
+		StateAction::Show { deposit, pending } => {
+			show(&config, deposit, pending).await?
+		}
+	}
+
+	Ok(())
+}
+
+/// Replay the event log without modifying the state directory and print the
+/// deposits and withdrawals of the resulting state
+async fn show(
+	config: &Config,
+	deposit_txid: Option<BitcoinTxId>,
+	pending: bool,
+) -> anyhow::Result<()> {
+	let deposit_registry = DepositRegistry::open(
+		&config.state_directory,
+		config.deposit_xpub,
+		config.bitcoin_network,
+	)?;
+	let events = EventLog::read(&config.state_directory).await?;
+
+	let mut state = State::new();
+	let mut block_times = BlockTimes::default();
+
+	// Every replayed event is logged, which would drown the report
+	tracing::subscriber::with_default(NoSubscriber::default(), || {
+		for event in events {
+			block_times.record(&event);
+			state.update(event, config, &deposit_registry);
+		}
+	});
+
+	match state.block_heights() {
+		Some((stacks_block_height, bitcoin_block_height)) => println!(
+			"Processed Stacks block {} and Bitcoin block {}",
+			stacks_block_height, bitcoin_block_height
+		),
+		None => println!("Contract not detected yet"),
+	}
+
+	if let Some(txid) = deposit_txid {
+		let deposit = state
+			.deposits()
+			.iter()
+			.find(|deposit| deposit.info().txid == txid)
+			.ok_or_else(|| anyhow!("No deposit {} in the state", txid))?;
+
+		print_deposit(deposit, &block_times);
+
+		return Ok(());
+	}
+
+	state
+		.deposits()
+		.iter()
+		.filter(|deposit| !pending || deposit.is_pending())
+		.for_each(|deposit| print_deposit(deposit, &block_times));
+
+	state
+		.withdrawals()
+		.iter()
+		.filter(|withdrawal| !pending || withdrawal.is_pending())
+		.for_each(|withdrawal| print_withdrawal(withdrawal, &block_times));
+
+	Ok(())
+}
+
+/// Times of the Bitcoin blocks of the log, by height
+#[derive(Default)]
+struct BlockTimes(HashMap<u32, u32>);
+
+impl BlockTimes {
+	fn record(&mut self, event: &Event) {
+		match event {
+			Event::BitcoinBlock(height, block) => {
+				self.0.insert(*height, block.header.time);
+			}
+			Event::BitcoinBlocks(blocks) => {
+				for (height, block) in blocks {
+					self.0.insert(*height, block.header.time);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Height of the block with its time, if the block is in the log
+	fn describe(&self, height: u32) -> String {
+		match self.0.get(&height) {
+			Some(time) => format!(
+				"{} ({})",
+				height,
+				humantime::format_rfc3339_seconds(
+					UNIX_EPOCH + Duration::from_secs((*time).into())
+				)
+			),
+			None => height.to_string(),
+		}
+	}
+}
+
+fn print_deposit(deposit: &Deposit, block_times: &BlockTimes) {
+	let info = deposit.info();
+
+	println!("Deposit {}", info.txid);
+	println!("  status: {}", pending_status(deposit.is_pending()));
+	println!("  amount: {} sats", info.amount);
+	println!("  recipient: {}", info.recipient);
+	println!("  block: {}", block_times.describe(info.block_height));
+	println!("  mint: {}", request_status(deposit.mint()));
+}
+
+fn print_withdrawal(withdrawal: &Withdrawal, block_times: &BlockTimes) {
+	let info = withdrawal.info();
+
+	println!("Withdrawal {}", info.txid);
+	println!("  status: {}", pending_status(withdrawal.is_pending()));
+	println!("  amount: {} sats", info.amount);
+	println!("  source: {}", info.source);
+	println!("  recipient: {}", info.recipient);
+	println!("  block: {}", block_times.describe(info.block_height));
+	println!("  burn: {}", request_status(withdrawal.burn()));
+	println!(
+		"  fulfillment: {}",
+		request_status(withdrawal.fulfillment())
+	);
+
+	if let Some(height) = withdrawal.fulfillment_broadcast_height() {
+		println!(
+			"  fulfillment broadcasted at block: {}",
+			block_times.describe(height)
+		);
+	}
+}
+
+fn pending_status(is_pending: bool) -> &'static str {
+	if is_pending {
+		"pending"
+	} else {
+		"complete"
+	}
+}
+
+fn request_status<T: Display>(
+	request: Option<&TransactionRequest<T>>,
+) -> String {
+	match request {
+		None => "not scheduled".to_string(),
+		Some(TransactionRequest::Scheduled { block_height }) => {
+			format!("scheduled at block {}", block_height)
+		}
+		Some(TransactionRequest::Created) => "being created".to_string(),
+		Some(TransactionRequest::Acknowledged { txid, status, .. }) => {
+			let status = match status {
+				TransactionStatus::Broadcasted => "broadcasted".to_string(),
+				TransactionStatus::Confirming { confirmations } => {
+					format!("confirming ({} confirmations)", confirmations)
+				}
+				TransactionStatus::Confirmed => "confirmed".to_string(),
+				TransactionStatus::Rejected => "rejected".to_string(),
+			};
+
+			format!("{} {}", txid, status)
+		}
+	}
+}
+
+fn print_migration(file: &str, version: Option<u32>) {
+	match version {
+		None => println!("{}: not written yet", file),
+		Some(STATE_VERSION) => {
+			println!("{}: already at version {}", file, STATE_VERSION)
+		}
+		Some(version) => println!(
+			"{}: migrated from version {} to {}",
+			file, version, STATE_VERSION
+		),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, path::PathBuf};
+
+	use super::*;
+	use crate::{
+		checkpoint::CHECKPOINT_FILE, event::Event, event_log::EVENT_LOG_FILE,
+	};
+
+	/// Event log and checkpoint written at every version, indexed by version
+	const FIXTURES: [(&str, &str); STATE_VERSION as usize + 1] = [
+		(
+			include_str!("../tests/fixtures/state/v0/log.ndjson"),
+			include_str!("../tests/fixtures/state/v0/checkpoint.json"),
+		),
+		(
+			include_str!("../tests/fixtures/state/v1/log.ndjson"),
+			include_str!("../tests/fixtures/state/v1/checkpoint.json"),
+		),
+	];
+
+	/// Directory of the test with the fixture files of the version
+	fn fixture_directory(test: &str, version: usize) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!(
+			"romeo-state-file-{}-{}-v{}",
+			std::process::id(),
+			test,
+			version
+		));
+		fs::create_dir_all(&dir).unwrap();
+
+		let (log, checkpoint) = FIXTURES[version];
+		fs::write(dir.join(EVENT_LOG_FILE), log).unwrap();
+		fs::write(dir.join(CHECKPOINT_FILE), checkpoint).unwrap();
+
+		dir
+	}
+
+	#[tokio::test]
+	async fn test_fixtures_of_every_version_are_loaded() {
+		for version in 0..FIXTURES.len() {
+			let dir = fixture_directory("load", version);
+
+			let (_, events) = EventLog::open(&dir).await.unwrap();
+
+			assert!(
+				matches!(
+					events[..],
+					[
+						Event::ContractBlockHeight(10, 200),
+						Event::BitcoinTransactionUpdate(..),
+						Event::FulfillBroadcasted(..),
+					]
+				),
+				"events of version {}",
+				version
+			);
+
+			let checkpoint = Checkpoint::load(&dir).unwrap().unwrap();
+			assert_eq!(checkpoint.bitcoin_block_height, 200);
+
+			fs::remove_dir_all(dir).unwrap();
+		}
+	}
+
+	#[tokio::test]
+	async fn test_fee_rate_of_version_0_fulfillments_is_unknown() {
+		let dir = fixture_directory("fee-rate", 0);
+
+		let (_, events) = EventLog::open(&dir).await.unwrap();
+		assert!(matches!(events[2], Event::FulfillBroadcasted(_, _, None)));
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_migrated_files_are_at_the_current_version() {
+		for version in 0..FIXTURES.len() {
+			let dir = fixture_directory("migrate", version);
+
+			assert_eq!(
+				EventLog::migrate(&dir).await.unwrap(),
+				Some(version as u32)
+			);
+			assert_eq!(
+				Checkpoint::migrate(&dir).unwrap(),
+				Some(version as u32)
+			);
+
+			assert_eq!(
+				EventLog::migrate(&dir).await.unwrap(),
+				Some(STATE_VERSION)
+			);
+			assert_eq!(Checkpoint::migrate(&dir).unwrap(), Some(STATE_VERSION));
+
+			let (_, events) = EventLog::open(&dir).await.unwrap();
+			assert_eq!(events.len(), 3);
+
+			fs::remove_dir_all(dir).unwrap();
+		}
+	}
+
+	#[test]
+	fn test_newer_version_is_an_error() {
+		let mut event = serde_json::json!({ "Reorg": { "depth": 1 } });
+
+		assert!(migrate_event(&mut event, STATE_VERSION + 1).is_err());
+	}
+}
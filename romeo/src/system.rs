@@ -1,8 +1,21 @@
 //! System
 
-use std::{fs::create_dir_all, io::Cursor};
+use std::{
+	fs::create_dir_all,
+	future::Future,
+	io::Cursor,
+	iter,
+	sync::Arc,
+	time::{Duration, Instant},
+};
 
-use bdk::bitcoin::Txid as BitcoinTxId;
+use anyhow::anyhow;
+use bdk::{
+	bitcoin::{
+		Block, BlockHash as BitcoinBlockHash, Script, Txid as BitcoinTxId,
+	},
+	FeeRate,
+};
 use blockstack_lib::{
 	burnchains::Txid as StacksTxId,
 	chainstate::stacks::{
@@ -14,50 +27,207 @@ use blockstack_lib::{
 	vm::{types::Value, ClarityName},
 };
 use sbtc_core::operations::op_return::withdrawal_fulfillment::create_outputs;
-use stacks_core::{codec::Codec, BlockId, Network as StacksNetwork};
+use stacks_core::{
+	codec::Codec, wallet::Credentials, BlockId, Network as StacksNetwork,
+};
 use tokio::{
-	fs::{File, OpenOptions},
-	io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
-	sync::mpsc,
+	signal::unix::{signal, SignalKind},
+	sync::{mpsc, watch},
 	task::JoinHandle,
+	time::sleep,
 };
-use tracing::{debug, info, trace};
+use tracing::{debug, error, info, trace, warn, Instrument};
 
 use crate::{
-	bitcoin_client::Client as BitcoinClient,
-	config::Config,
+	admin::{self, OverrideRequest, PendingOverride},
+	api::{self, ProcessingStatus},
+	bitcoin_client::{
+		esplora::EsploraClient, fee::FeeEstimator, spawn_blocking_in_span,
+		BitcoinBackend, Client,
+	},
+	checkpoint::Checkpoint,
+	config::{BitcoinBackendKind, Config},
+	contracts,
+	deposit_registry::DepositRegistry,
 	event::Event,
+	event_log::EventLog,
+	event_observer::{self, ObservedBlocks, ObservedStacksBackend},
+	logging, metrics,
 	proof_data::{ProofData, ProofDataClarityValues},
-	stacks_client::{LockedClient, StacksClient},
+	stacks_client::{asset, LockedClient, StacksBackend, StacksClient},
 	state,
 	state::{DepositInfo, WithdrawalInfo},
 	task::Task,
 };
 
+/// Shared handle to the Bitcoin backend used by the tasks
+type BitcoinClient = Arc<dyn BitcoinBackend>;
+
 const DUMMY_STACKS_ID: StacksTxId = StacksTxId([
 	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 	0, 0, 0, 0, 0, 0, 0,
 ]);
 
+/// Interval between the attempts to find the fork point of a Bitcoin reorg,
+/// once the retries of the Bitcoin backend are exhausted
+const FORK_POINT_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
 /// The main run loop of this system.
 /// This function feeds all events to the `state::update` function and spawns
 /// all tasks returned from this function.
 ///
 /// The system is bootstrapped by emitting the CreateAssetContract task.
+///
+/// Every asset contract has its own run loop. They share the Stacks client,
+/// so that their contract calls get distinct nonces.
 pub async fn run(config: Config) {
+	let stacks_client = stacks_backend(&config);
+
+	let configs: Vec<Config> = iter::once(config.clone())
+		.chain(config.additional_contract_configs())
+		.collect();
+
+	for config in &configs {
+		ensure_compatible_contract(config, &stacks_client).await;
+	}
+
+	let contracts = configs.into_iter().map(|config| {
+		let bitcoin_client = bitcoin_backend(&config);
+
+		run_contract(config, bitcoin_client, stacks_client.clone())
+	});
+
+	futures::future::join_all(contracts).await;
+}
+
+/// Runs the system against the given Bitcoin backend instead of the default
+/// RPC and Electrum client. Only the main contract is processed.
+pub async fn run_with_bitcoin_backend(
+	config: Config,
+	bitcoin_client: BitcoinClient,
+) {
+	let stacks_client = stacks_backend(&config);
+	ensure_compatible_contract(&config, &stacks_client).await;
+
+	run_contract(config, bitcoin_client, stacks_client).await
+}
+
+/// Runs the system against the given Bitcoin and Stacks backends, such as
+/// in-memory test doubles. Only the main contract is processed.
+pub async fn run_with_backends(
+	config: Config,
+	bitcoin_client: BitcoinClient,
+	stacks_backend: impl StacksBackend + 'static,
+) {
+	let stacks_client: LockedClient = stacks_backend.into();
+	ensure_compatible_contract(&config, &stacks_client).await;
+
+	run_contract(config, bitcoin_client, stacks_client).await
+}
+
+async fn ensure_compatible_contract(
+	config: &Config,
+	stacks_client: &LockedClient,
+) {
+	contracts::ensure_compatible(config, stacks_client)
+		.await
+		.expect("Cannot operate against the deployed contract");
+}
+
+/// The Stacks client, reading the blocks pushed by the node if the event
+/// observer is enabled
+fn stacks_backend(config: &Config) -> LockedClient {
+	let http_client = config
+		.outbound
+		.http_client()
+		.expect("Invalid outbound connection settings");
+	let client = StacksClient::new(config.clone(), http_client);
+
+	match config.event_observer_address {
+		Some(address) => {
+			let blocks = Arc::new(ObservedBlocks::default());
+			event_observer::serve(address, blocks.clone());
+
+			ObservedStacksBackend::new(client, blocks).into()
+		}
+		None => client.into(),
+	}
+}
+
+fn bitcoin_backend(config: &Config) -> BitcoinClient {
+	match config.bitcoin_backend {
+		BitcoinBackendKind::Rpc => Arc::new(
+			Client::new(config.clone())
+				.expect("Failed to instantiate bitcoin client"),
+		),
+		BitcoinBackendKind::Esplora => Arc::new(
+			EsploraClient::new(config.clone())
+				.expect("Failed to instantiate Esplora client"),
+		),
+	}
+}
+
+/// The run loop of the asset contract of the config
+#[tracing::instrument(
+	skip(config, bitcoin_client, stacks_client),
+	fields(contract = %config.contract_name)
+)]
+async fn run_contract(
+	mut config: Config,
+	bitcoin_client: BitcoinClient,
+	stacks_client: LockedClient,
+) {
 	let (tx, mut rx) = mpsc::channel::<Event>(128); // TODO: Make capacity configurable
-	let bitcoin_client = BitcoinClient::new(config.clone())
-		.expect("Failed to instantiate bitcoin client");
-	let stacks_client: LockedClient =
-		StacksClient::new(config.clone(), reqwest::Client::new()).into();
+
+	create_dir_all(&config.state_directory).unwrap();
+
+	let deposit_registry = Arc::new(
+		DepositRegistry::open(
+			&config.state_directory,
+			config.deposit_xpub,
+			config.bitcoin_network,
+		)
+		.expect("Unable to read the deposit registry"),
+	);
 
 	info!("Starting replay of persisted events");
 
-	let (mut storage, mut state) =
-		Storage::load_and_replay(&config, state::State::new()).await;
+	let (mut event_log, mut state) =
+		load_and_replay(&config, &deposit_registry, state::State::new()).await;
 
 	info!("Replay finished with state: {:?}", state);
 
+	// Resuming waits for the contract to be detected if the replayed state
+	// has not detected it yet
+	let mut resume_checkpoint = resume_checkpoint(&config)
+		.expect("Unable to read the checkpoint")
+		.filter(|checkpoint| !state.resume_from(checkpoint));
+	let mut checkpoint = state.checkpoint();
+
+	metrics::state_updated(&state);
+
+	if let Some(address) = config.metrics_address {
+		metrics::serve(address);
+	}
+
+	let (processing_status, processing_status_rx) =
+		watch::channel(ProcessingStatus::from(&state));
+
+	if let Some(address) = config.api_address {
+		api::serve(
+			address,
+			&config,
+			bitcoin_client.clone(),
+			processing_status_rx,
+			deposit_registry.clone(),
+		);
+	}
+
+	let (override_tx, mut override_rx) = mpsc::channel::<PendingOverride>(16);
+
+	admin::serve(&config.state_directory, override_tx)
+		.expect("Unable to listen to operator overrides");
+
 	let bootstrap_tasks = state.bootstrap();
 
 	// Bootstrap
@@ -66,22 +236,85 @@ pub async fn run(config: Config) {
 			config.clone(),
 			bitcoin_client.clone(),
 			stacks_client.clone(),
+			deposit_registry.clone(),
+			state.operation_ids_of_task(&task),
 			task,
 			tx.clone(),
 		);
 	}
 
-	while let Some(event) = rx.recv().await {
-		storage.record(&event).await;
+	let mut hangups =
+		signal(SignalKind::hangup()).expect("Unable to listen to SIGHUP");
+
+	loop {
+		let (event, pending_override) = tokio::select! {
+			Some(event) = rx.recv() => (event, None),
+			Some(pending_override) = override_rx.recv() => {
+				let OverrideRequest { txid, action } = pending_override.request;
+
+				match state.check_override(txid, action) {
+					Ok(()) => (
+						Event::OperationOverridden(txid, action),
+						Some(pending_override),
+					),
+					Err(err) => {
+						pending_override
+							.refuse(&config.state_directory, err)
+							.await;
+						continue;
+					}
+				}
+			}
+			Some(()) = hangups.recv() => {
+				config =
+					reload_config(config, &bitcoin_client, &stacks_client);
+				continue;
+			}
+			else => break,
+		};
+
+		event_log
+			.append(&event)
+			.await
+			.expect("Unable to write to the event log");
+		metrics::event_emitted(&event);
+
+		let tasks =
+			update_state(&mut state, event, &config, &deposit_registry).await;
+
+		if let Some(resume) = &resume_checkpoint {
+			if state.resume_from(resume) {
+				resume_checkpoint = None;
+			}
+		}
+
+		let new_checkpoint = state.checkpoint();
+
+		if new_checkpoint != checkpoint {
+			if let Some(new_checkpoint) = &new_checkpoint {
+				new_checkpoint
+					.save(&config.state_directory)
+					.expect("Unable to write the checkpoint");
+			}
+
+			checkpoint = new_checkpoint;
+		}
 
-		let tasks = state.update(event, &config);
+		metrics::state_updated(&state);
+		processing_status.send_replace(ProcessingStatus::from(&state));
 		trace!("State: {}", serde_json::to_string(&state).unwrap());
 
+		if let Some(pending_override) = pending_override {
+			pending_override.accept(&config.state_directory).await;
+		}
+
 		for task in tasks {
 			spawn(
 				config.clone(),
 				bitcoin_client.clone(),
 				stacks_client.clone(),
+				deposit_registry.clone(),
+				state.operation_ids_of_task(&task),
 				task,
 				tx.clone(),
 			);
@@ -89,64 +322,157 @@ pub async fn run(config: Config) {
 	}
 }
 
-struct Storage(BufWriter<File>);
+/// Updates the state with the event. Parsing a batch of Bitcoin blocks is CPU
+/// bound, so its update runs on a blocking thread rather than on a thread of
+/// the runtime.
+async fn update_state(
+	state: &mut state::State,
+	event: Event,
+	config: &Config,
+	deposit_registry: &Arc<DepositRegistry>,
+) -> Vec<Task> {
+	if !matches!(event, Event::BitcoinBlocks(_)) {
+		return state.update(event, config, deposit_registry);
+	}
 
-impl Storage {
-	async fn load_and_replay(
-		config: &Config,
-		mut state: state::State,
-	) -> (Self, state::State) {
-		create_dir_all(&config.state_directory).unwrap();
+	let mut blocking_state = std::mem::take(state);
+	let config = config.clone();
+	let deposit_registry = deposit_registry.clone();
 
-		let mut file = OpenOptions::new()
-			.create(true)
-			.read(true)
-			.write(true)
-			.append(true)
-			.open(config.state_directory.join("log.ndjson"))
-			.await
-			.unwrap();
+	let (blocking_state, tasks) = spawn_blocking_in_span(move || {
+		let tasks = blocking_state.update(event, &config, &deposit_registry);
+
+		(blocking_state, tasks)
+	})
+	.await
+	.expect("Failed to update the state");
+
+	*state = blocking_state;
 
-		let mut r = BufReader::new(&mut file).lines();
+	tasks
+}
 
-		while let Some(line) = r.next_line().await.unwrap() {
-			let event: Event = serde_json::from_str(&line).unwrap();
+/// Reloads the config file, keeping the current config if the new one is
+/// invalid or changes values that cannot change while the system runs
+fn reload_config(
+	config: Config,
+	bitcoin_client: &BitcoinClient,
+	stacks_client: &LockedClient,
+) -> Config {
+	info!("Reloading the config file");
 
-			state.update(event, config);
+	let new_config = match config.reload() {
+		Ok(new_config) => new_config,
+		Err(err) => {
+			warn!("Rejected the reloaded config: {:?}", err);
+			return config;
 		}
+	};
+
+	if let Err(err) = logging::set_log_level(new_config.log_level.as_deref()) {
+		warn!("Unable to change the log level: {:?}", err);
+	}
 
-		(Self(BufWriter::new(file)), state)
+	if let Err(err) = bitcoin_client.reload_config(&new_config) {
+		warn!("Unable to reload the Bitcoin client config: {:?}", err);
 	}
 
-	async fn record(&mut self, event: &Event) {
-		let bytes = serde_json::to_vec(event).unwrap();
-		self.0.write_all(&bytes).await.unwrap();
-		self.0.write_all(b"\n").await.unwrap();
-		self.0.flush().await.unwrap();
+	// Tasks may hold the Stacks client for a while, the run loop does not wait
+	// for them
+	let stacks_client = stacks_client.clone();
+	let stacks_config = new_config.clone();
+	tokio::spawn(async move {
+		stacks_client.lock().await.reload_config(stacks_config);
+	});
+
+	info!("Config reloaded");
+
+	new_config
+}
+
+/// Checkpoint to skip to from the command line controls: the checkpoint of
+/// the state directory when resuming, or else the start height
+fn resume_checkpoint(config: &Config) -> anyhow::Result<Option<Checkpoint>> {
+	if config.resume {
+		if let Some(checkpoint) = Checkpoint::load(&config.state_directory)? {
+			return Ok(Some(checkpoint));
+		}
+
+		warn!("No checkpoint to resume from");
 	}
+
+	Ok(config.start_height.map(|start_height| Checkpoint {
+		stacks_block_height: 0,
+		bitcoin_block_height: start_height.saturating_sub(1),
+		bitcoin_block_hashes: vec![],
+	}))
 }
 
-#[tracing::instrument(skip(config, bitcoin_client, stacks_client, result))]
+async fn load_and_replay(
+	config: &Config,
+	deposit_registry: &DepositRegistry,
+	mut state: state::State,
+) -> (EventLog, state::State) {
+	let (event_log, events) = EventLog::open(&config.state_directory)
+		.await
+		.expect("Unable to read the event log");
+
+	for event in events {
+		state.update(event, config, deposit_registry);
+	}
+
+	(event_log, state)
+}
+
+#[tracing::instrument(
+	skip(
+		config,
+		bitcoin_client,
+		stacks_client,
+		deposit_registry,
+		operation_ids,
+		result
+	),
+	fields(operation = logging::correlation_id(&operation_ids))
+)]
 fn spawn(
 	config: Config,
 	bitcoin_client: BitcoinClient,
 	stacks_client: LockedClient,
+	deposit_registry: Arc<DepositRegistry>,
+	operation_ids: Vec<BitcoinTxId>,
 	task: Task,
 	result: mpsc::Sender<Event>,
 ) -> JoinHandle<()> {
 	info!("Spawning");
 
-	tokio::task::spawn(async move {
-		let event =
-			run_task(&config, bitcoin_client, stacks_client, task).await;
-		result.send(event).await.expect("Failed to return event");
-	})
+	tokio::task::spawn(
+		async move {
+			let task_name = metrics::task_name(&task);
+			let start = Instant::now();
+
+			let event = run_task(
+				&config,
+				bitcoin_client,
+				stacks_client,
+				&deposit_registry,
+				task,
+			)
+			.await;
+
+			metrics::task_finished(task_name, start.elapsed());
+
+			result.send(event).await.expect("Failed to return event");
+		}
+		.in_current_span(),
+	)
 }
 
 async fn run_task(
 	config: &Config,
 	bitcoin_client: BitcoinClient,
 	stacks_client: LockedClient,
+	deposit_registry: &DepositRegistry,
 	task: Task,
 ) -> Event {
 	match task {
@@ -164,12 +490,22 @@ async fn run_task(
 			burn_asset(config, bitcoin_client, stacks_client, withdrawal_info)
 				.await
 		}
-		Task::CreateFulfillment(fulfillment_info) => {
-			fulfill_asset(
+		Task::CreateFulfillment(withdrawal_infos) => {
+			fulfill_assets(
 				config,
 				bitcoin_client,
 				stacks_client,
-				fulfillment_info,
+				withdrawal_infos,
+			)
+			.await
+		}
+		Task::BumpFulfillmentFee(withdrawal_infos, txid, sat_per_vb) => {
+			bump_fulfillment_fee(
+				config,
+				bitcoin_client,
+				withdrawal_infos,
+				txid,
+				sat_per_vb,
 			)
 			.await
 		}
@@ -183,7 +519,16 @@ async fn run_task(
 			fetch_stacks_block(stacks_client, block_height).await
 		}
 		Task::FetchBitcoinBlock(block_height) => {
-			fetch_bitcoin_block(bitcoin_client, block_height).await
+			fetch_bitcoin_block(
+				config,
+				bitcoin_client,
+				deposit_registry,
+				block_height,
+			)
+			.await
+		}
+		Task::FindBitcoinForkPoint(blocks) => {
+			find_bitcoin_fork_point(bitcoin_client, blocks).await
 		}
 	}
 }
@@ -213,14 +558,7 @@ async fn update_contract_public_key(
 	config: &Config,
 	stacks_client: LockedClient,
 ) -> Event {
-	let public_key = StacksPublicKey::from_slice(
-		&config.stacks_credentials.public_key().serialize(),
-	)
-	.unwrap();
-
-	let tx_auth = TransactionAuth::Standard(
-		TransactionSpendingCondition::new_singlesig_p2pkh(public_key).unwrap(),
-	);
+	let tx_auth = contract_call_auth(config);
 
 	let function_args = vec![Value::buff_from(
 		config
@@ -252,22 +590,48 @@ async fn update_contract_public_key(
 
 	let tx = StacksTransaction::new(tx_version, tx_auth, tx_payload);
 
-	let txid = stacks_client
-		.lock()
-		.await
-		.sign_and_broadcast(tx)
-		.await
-		.expect("Unable to sign and broadcast the set public key transaction");
+	let txid =
+		stacks_client.lock().await.submit_tx(tx).await.expect(
+			"Unable to sign and broadcast the set public key transaction",
+		);
 
 	Event::ContractPublicKeySetBroadcasted(txid)
 }
 
+/// Authorization of a contract call of the Stacks account, sponsored by the
+/// sponsor account if there is one
+pub(crate) fn contract_call_auth(config: &Config) -> TransactionAuth {
+	let origin = spending_condition(&config.stacks_credentials);
+
+	match &config.stacks_sponsor_credentials {
+		Some(sponsor) => {
+			TransactionAuth::Sponsored(origin, spending_condition(sponsor))
+		}
+		None => TransactionAuth::Standard(origin),
+	}
+}
+
+fn spending_condition(
+	credentials: &Credentials,
+) -> TransactionSpendingCondition {
+	let public_key =
+		StacksPublicKey::from_slice(&credentials.public_key().serialize())
+			.unwrap();
+
+	TransactionSpendingCondition::new_singlesig_p2pkh(public_key).unwrap()
+}
+
 async fn mint_asset(
 	config: &Config,
 	bitcoin_client: BitcoinClient,
 	stacks_client: LockedClient,
 	deposit_info: DepositInfo,
 ) -> Event {
+	if is_processed_by_contract(config, &stacks_client, deposit_info.txid).await
+	{
+		return Event::DepositAlreadyMinted(deposit_info);
+	}
+
 	let proof_data = get_tx_proof(
 		&bitcoin_client,
 		deposit_info.block_height,
@@ -275,14 +639,7 @@ async fn mint_asset(
 	)
 	.await;
 
-	let public_key = StacksPublicKey::from_slice(
-		&config.stacks_credentials.public_key().serialize(),
-	)
-	.unwrap();
-
-	let tx_auth = TransactionAuth::Standard(
-		TransactionSpendingCondition::new_singlesig_p2pkh(public_key).unwrap(),
-	);
+	let tx_auth = contract_call_auth(config);
 
 	let function_args = vec![
 		Value::UInt(deposit_info.amount as u128),
@@ -314,7 +671,7 @@ async fn mint_asset(
 
 	let tx = StacksTransaction::new(tx_version, tx_auth, tx_payload);
 
-	match stacks_client.lock().await.sign_and_broadcast(tx).await {
+	match stacks_client.lock().await.submit_tx(tx).await {
 		Ok(txid) => Event::MintBroadcasted(deposit_info, txid),
 		Err(err) => {
 			if config.strict {
@@ -336,6 +693,12 @@ async fn burn_asset(
 	stacks_client: LockedClient,
 	withdrawal_info: WithdrawalInfo,
 ) -> Event {
+	if is_processed_by_contract(config, &stacks_client, withdrawal_info.txid)
+		.await
+	{
+		return Event::WithdrawalAlreadyBurned(withdrawal_info);
+	}
+
 	let proof_data = get_tx_proof(
 		&bitcoin_client,
 		withdrawal_info.block_height,
@@ -343,14 +706,7 @@ async fn burn_asset(
 	)
 	.await;
 
-	let public_key = StacksPublicKey::from_slice(
-		&config.stacks_credentials.public_key().serialize(),
-	)
-	.unwrap();
-
-	let tx_auth = TransactionAuth::Standard(
-		TransactionSpendingCondition::new_singlesig_p2pkh(public_key).unwrap(),
-	);
+	let tx_auth = contract_call_auth(config);
 
 	let function_args = vec![
 		Value::UInt(withdrawal_info.amount as u128),
@@ -382,7 +738,7 @@ async fn burn_asset(
 
 	let tx = StacksTransaction::new(tx_version, tx_auth, tx_payload);
 
-	match stacks_client.lock().await.sign_and_broadcast(tx).await {
+	match stacks_client.lock().await.submit_tx(tx).await {
 		Ok(txid) => Event::BurnBroadcasted(withdrawal_info, txid),
 		Err(err) => {
 			if config.strict {
@@ -398,35 +754,167 @@ async fn burn_asset(
 	}
 }
 
-async fn fulfill_asset(
+/// Whether the contract has already minted or burned for the Bitcoin
+/// transaction. Requests replayed after a crash may have been processed
+/// already, and their contract calls would only fail on-chain. The request is
+/// considered unprocessed if the contract cannot be queried.
+async fn is_processed_by_contract(
+	config: &Config,
+	stacks_client: &LockedClient,
+	txid: BitcoinTxId,
+) -> bool {
+	let amount = asset::get_amount_by_btc_txid(
+		&*stacks_client.lock().await,
+		&asset::contract_id(config),
+		txid,
+	)
+	.await;
+
+	match amount {
+		Ok(Some(amount)) => {
+			info!(
+				"Bitcoin transaction {} already processed by the contract for {} sats, skipping its contract call",
+				txid, amount
+			);
+			true
+		}
+		Ok(None) => false,
+		Err(err) => {
+			warn!(
+				"Unable to check whether Bitcoin transaction {} is processed by the contract: {:?}",
+				txid, err
+			);
+			false
+		}
+	}
+}
+
+async fn fulfill_assets(
 	config: &Config,
 	bitcoin_client: BitcoinClient,
 	stacks_client: LockedClient,
-	withdrawal_info: WithdrawalInfo,
+	mut withdrawal_infos: Vec<WithdrawalInfo>,
 ) -> Event {
+	// The chain tip of the most recent withdrawal covers all of the batch
+	let block_height = withdrawal_infos
+		.iter()
+		.map(|withdrawal_info| withdrawal_info.block_height)
+		.max()
+		.expect("Cannot fulfill an empty batch of withdrawals");
+
 	let stacks_chain_tip = stacks_client
 		.lock()
 		.await
-		.get_block_hash_from_bitcoin_height(withdrawal_info.block_height)
+		.get_block_hash_from_bitcoin_height(block_height)
 		.await
 		.expect("Unable to get stacks block hash");
 
-	let outputs = create_outputs(
+	let (first, rest) = withdrawal_infos.split_first().unwrap();
+
+	let mut outputs = create_outputs(
 		BlockId::new(stacks_chain_tip),
 		config.bitcoin_network,
-		&withdrawal_info.recipient,
-		withdrawal_info.amount,
+		&first.recipient,
+		first.amount,
 	)
-	.expect("Could not create withdrawal fulfillment outputs");
+	.expect("Could not create withdrawal fulfillment outputs")
+	.to_vec();
+
+	outputs.extend(rest.iter().map(|withdrawal_info| {
+		(
+			withdrawal_info.recipient.script_pubkey(),
+			withdrawal_info.amount,
+		)
+	}));
+
+	let fee_rate = FeeEstimator::new(config)
+		.expect("Invalid outbound connection settings")
+		.estimate(bitcoin_client.as_ref())
+		.await;
 
 	let txid = bitcoin_client
-		.sign_and_broadcast(outputs.to_vec())
+		.sign_and_broadcast(outputs, fee_rate)
 		.await
 		.expect(
 		"Unable to sign and broadcast the withdrawal fulfillment transaction",
 	);
 
-	Event::FulfillBroadcasted(withdrawal_info, txid)
+	if withdrawal_infos.len() == 1 {
+		Event::FulfillBroadcasted(
+			withdrawal_infos.remove(0),
+			txid,
+			Some(fee_rate.as_sat_per_vb()),
+		)
+	} else {
+		Event::FulfillmentBatchBroadcasted(
+			withdrawal_infos,
+			txid,
+			fee_rate.as_sat_per_vb(),
+		)
+	}
+}
+
+async fn bump_fulfillment_fee(
+	config: &Config,
+	bitcoin_client: BitcoinClient,
+	withdrawal_infos: Vec<WithdrawalInfo>,
+	txid: BitcoinTxId,
+	sat_per_vb: Option<f32>,
+) -> Event {
+	let fee_rate = FeeEstimator::new(config)
+		.expect("Invalid outbound connection settings")
+		.estimate_bump(
+			bitcoin_client.as_ref(),
+			sat_per_vb.map(FeeRate::from_sat_per_vb),
+		)
+		.await;
+
+	let res = match fee_rate {
+		Some(fee_rate) => {
+			replace_transaction(config, &bitcoin_client, txid, fee_rate).await
+		}
+		None => Err(anyhow!("Fee rate is already at the configured ceiling")),
+	};
+
+	match res {
+		Ok((replacement_txid, fee_rate)) => Event::FulfillmentFeeBumped(
+			withdrawal_infos,
+			replacement_txid,
+			fee_rate.as_sat_per_vb(),
+		),
+		Err(err) => {
+			// The fulfillment keeps being tracked and is bumped again later
+			warn!("Unable to bump the fee of fulfillment {}: {:?}", txid, err);
+			check_bitcoin_transaction_status(config, bitcoin_client, txid).await
+		}
+	}
+}
+
+/// Replaces the transaction at the fee rate, or at the lowest fee rate the
+/// replacement rules allow if it is higher and within the fee ceiling
+async fn replace_transaction(
+	config: &Config,
+	bitcoin_client: &BitcoinClient,
+	txid: BitcoinTxId,
+	fee_rate: FeeRate,
+) -> anyhow::Result<(BitcoinTxId, FeeRate)> {
+	let err = match bitcoin_client.bump_fee(txid, fee_rate).await {
+		Ok(replacement_txid) => return Ok((replacement_txid, fee_rate)),
+		Err(err) => err,
+	};
+
+	match err.downcast_ref::<bdk::Error>() {
+		Some(bdk::Error::FeeRateTooLow { required })
+			if required.as_sat_per_vb()
+				<= config.bitcoin_fee.max_sat_per_vb =>
+		{
+			let replacement_txid =
+				bitcoin_client.bump_fee(txid, *required).await?;
+
+			Ok((replacement_txid, *required))
+		}
+		_ => Err(err),
+	}
 }
 
 async fn get_tx_proof(
@@ -468,7 +956,7 @@ async fn check_stacks_transaction_status(
 	let status = client
 		.lock()
 		.await
-		.get_transation_status(txid)
+		.get_tx_status(txid)
 		.await
 		.expect("Could not get Stacks transaction status");
 
@@ -487,13 +975,198 @@ async fn fetch_stacks_block(client: LockedClient, block_height: u32) -> Event {
 }
 
 async fn fetch_bitcoin_block(
+	config: &Config,
 	client: BitcoinClient,
+	deposit_registry: &DepositRegistry,
 	block_height: u32,
 ) -> Event {
+	// Deposits and withdrawal requests pay the sBTC wallet or a registered
+	// deposit address, blocks paying none of them are only needed for their
+	// header
+	let scripts: Vec<_> =
+		iter::once(config.sbtc_wallet_address().script_pubkey())
+			.chain(deposit_registry.scripts())
+			.collect();
+
+	if config.bitcoin_catch_up_batch_size > 1 {
+		match fetch_bitcoin_catch_up_blocks(
+			config,
+			&client,
+			&scripts,
+			block_height,
+		)
+		.await
+		{
+			Ok(Some(blocks)) => return Event::BitcoinBlocks(blocks),
+			Ok(None) => {}
+			Err(err) => warn!(
+				"Failed to catch up from Bitcoin block {}, fetching it alone: \
+				 {}",
+				block_height, err
+			),
+		}
+	}
+
 	let (height, block) = client
-		.get_block(block_height)
+		.get_filtered_block(block_height, scripts)
 		.await
 		.expect("Failed to fetch bitcoin block");
 
 	Event::BitcoinBlock(height, block)
 }
+
+/// The blocks from the height, up to the catch up batch size, if the height
+/// is behind the chain tip
+async fn fetch_bitcoin_catch_up_blocks(
+	config: &Config,
+	client: &BitcoinClient,
+	scripts: &[Script],
+	block_height: u32,
+) -> anyhow::Result<Option<Vec<(u32, Block)>>> {
+	let tip_height = client.get_height().await?;
+
+	if tip_height <= block_height {
+		return Ok(None);
+	}
+
+	let last_height =
+		tip_height.min(block_height + config.bitcoin_catch_up_batch_size - 1);
+
+	debug!(
+		"Catching up with Bitcoin blocks {} to {}",
+		block_height, last_height
+	);
+
+	let blocks = futures::future::try_join_all(
+		(block_height..=last_height)
+			.map(|height| client.get_filtered_block(height, scripts.to_vec())),
+	)
+	.await?;
+
+	Ok(Some(blocks))
+}
+
+async fn find_bitcoin_fork_point(
+	client: BitcoinClient,
+	blocks: Vec<(u32, BitcoinBlockHash)>,
+) -> Event {
+	loop {
+		match bitcoin_reorg_depth(&blocks, |height| {
+			client.get_block_hash(height)
+		})
+		.await
+		{
+			Ok(depth) => return Event::Reorg { depth },
+			Err(err) => {
+				warn!("Failed to find the Bitcoin fork point: {}", err);
+
+				sleep(FORK_POINT_RETRY_INTERVAL).await;
+			}
+		}
+	}
+}
+
+/// Number of the processed blocks, most recent last, that are no longer in
+/// the best chain. A reorg deeper than the processed blocks rolls all of them
+/// back.
+async fn bitcoin_reorg_depth<F, Fut>(
+	blocks: &[(u32, BitcoinBlockHash)],
+	get_block_hash: F,
+) -> anyhow::Result<u32>
+where
+	F: Fn(u32) -> Fut,
+	Fut: Future<Output = anyhow::Result<BitcoinBlockHash>>,
+{
+	let (Some((oldest_height, _)), Some((tip_height, _))) =
+		(blocks.first(), blocks.last())
+	else {
+		return Ok(0);
+	};
+
+	for (height, hash) in blocks.iter().rev() {
+		if get_block_hash(*height).await? == *hash {
+			return Ok(tip_height - height);
+		}
+	}
+
+	error!(
+		"Bitcoin reorg is deeper than the {} processed blocks that are \
+		 tracked, rolling all of them back",
+		blocks.len()
+	);
+
+	Ok(tip_height - oldest_height + 1)
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::hashes::Hash;
+
+	use super::*;
+
+	fn hash(n: u8) -> BitcoinBlockHash {
+		BitcoinBlockHash::from_inner([n; 32])
+	}
+
+	fn processed_blocks() -> Vec<(u32, BitcoinBlockHash)> {
+		(100..=104)
+			.map(|height| (height, hash(height as u8)))
+			.collect()
+	}
+
+	/// Best chain which forked off the processed blocks after the height
+	fn best_chain(
+		fork_height: u32,
+	) -> impl Fn(u32) -> futures::future::Ready<anyhow::Result<BitcoinBlockHash>>
+	{
+		move |height| {
+			let n = if height <= fork_height {
+				height
+			} else {
+				height + 100
+			};
+
+			futures::future::ready(Ok(hash(n as u8)))
+		}
+	}
+
+	#[tokio::test]
+	async fn test_reorg_depth_is_the_number_of_orphaned_blocks() {
+		let blocks = processed_blocks();
+
+		assert_eq!(
+			bitcoin_reorg_depth(&blocks, best_chain(104)).await.unwrap(),
+			0
+		);
+		assert_eq!(
+			bitcoin_reorg_depth(&blocks, best_chain(102)).await.unwrap(),
+			2
+		);
+		assert_eq!(
+			bitcoin_reorg_depth(&blocks, best_chain(100)).await.unwrap(),
+			4
+		);
+	}
+
+	#[tokio::test]
+	async fn test_reorg_deeper_than_the_processed_blocks_rolls_all_back() {
+		let blocks = processed_blocks();
+
+		assert_eq!(
+			bitcoin_reorg_depth(&blocks, best_chain(50)).await.unwrap(),
+			5
+		);
+	}
+
+	#[tokio::test]
+	async fn test_reorg_depth_propagates_backend_errors() {
+		let blocks = processed_blocks();
+
+		let res = bitcoin_reorg_depth(&blocks, |_| {
+			futures::future::ready(Err(anyhow!("Connection refused")))
+		})
+		.await;
+
+		assert!(res.is_err());
+	}
+}
@@ -1,6 +1,6 @@
 //! Task
 
-use bdk::bitcoin::Txid as BitcoinTxId;
+use bdk::bitcoin::{BlockHash as BitcoinBlockHash, Txid as BitcoinTxId};
 use blockstack_lib::burnchains::Txid as StacksTxId;
 
 use crate::state;
@@ -20,8 +20,12 @@ pub enum Task {
 	/// Create and broadcast a burn stacks transaction
 	CreateBurn(state::WithdrawalInfo),
 
-	/// Create and broadcast a fulfill bitcoin transaction
-	CreateFulfillment(state::WithdrawalInfo),
+	/// Create and broadcast a fulfill bitcoin transaction of the withdrawals
+	CreateFulfillment(Vec<state::WithdrawalInfo>),
+
+	/// Replace a fulfill bitcoin transaction paying the fee rate in sat/vB, if
+	/// known, with one paying a higher fee rate
+	BumpFulfillmentFee(Vec<state::WithdrawalInfo>, BitcoinTxId, Option<f32>),
 
 	/// Poll a bitcoin node for the status of a transaction
 	CheckBitcoinTransactionStatus(BitcoinTxId),
@@ -34,4 +38,8 @@ pub enum Task {
 
 	/// Fetch a Bitcoin block for the given block height
 	FetchBitcoinBlock(u32),
+
+	/// Find the most recent of the processed Bitcoin blocks that is still in
+	/// the best chain
+	FindBitcoinForkPoint(Vec<(u32, BitcoinBlockHash)>),
 }
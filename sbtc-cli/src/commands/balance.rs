@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+use bdk::{bitcoin::Address as BitcoinAddress, electrum_client::ElectrumApi};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::commands::output::Output;
+
+#[derive(Parser, Debug, Clone)]
+pub struct AddressArgs {
+	/// Bitcoin address to query
+	address: String,
+
+	/// Electrum node to query
+	#[clap(short('u'), long, required_unless_present = "esplora_url")]
+	node_url: Option<Url>,
+
+	/// Esplora API to query instead of an Electrum node
+	#[clap(short, long, conflicts_with = "node_url")]
+	esplora_url: Option<Url>,
+}
+
+/// Balance of an address in sats
+#[derive(Serialize)]
+pub struct Balance {
+	address: String,
+	confirmed: u64,
+	unconfirmed: i64,
+}
+
+/// Unspent outputs of an address, largest first
+#[derive(Serialize)]
+pub struct Utxos {
+	address: String,
+	utxos: Vec<Utxo>,
+	/// Sum of the values of the outputs in sats
+	total: u64,
+}
+
+/// Unspent output of an address
+#[derive(Serialize)]
+struct Utxo {
+	txid: String,
+	vout: u32,
+	value: u64,
+	/// Height of the block confirming the output, if confirmed
+	height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct EsploraAddressStats {
+	chain_stats: EsploraTxoStats,
+	mempool_stats: EsploraTxoStats,
+}
+
+#[derive(Deserialize)]
+struct EsploraTxoStats {
+	funded_txo_sum: u64,
+	spent_txo_sum: u64,
+}
+
+#[derive(Deserialize)]
+struct EsploraUtxo {
+	txid: String,
+	vout: u32,
+	value: u64,
+	status: EsploraStatus,
+}
+
+#[derive(Deserialize)]
+struct EsploraStatus {
+	block_height: Option<u32>,
+}
+
+pub fn balance(args: &AddressArgs) -> anyhow::Result<Balance> {
+	let address = BitcoinAddress::from_str(&args.address)?;
+
+	let balance = match &args.esplora_url {
+		Some(esplora_url) => {
+			let stats: EsploraAddressStats = reqwest::blocking::get(
+				esplora_endpoint(esplora_url, &format!("address/{}", address))?,
+			)?
+			.error_for_status()?
+			.json()?;
+
+			Balance {
+				address: address.to_string(),
+				confirmed: stats.chain_stats.funded_txo_sum
+					- stats.chain_stats.spent_txo_sum,
+				unconfirmed: stats.mempool_stats.funded_txo_sum as i64
+					- stats.mempool_stats.spent_txo_sum as i64,
+			}
+		}
+		None => {
+			let balance = electrum_client(args)?
+				.script_get_balance(&address.script_pubkey())?;
+
+			Balance {
+				address: address.to_string(),
+				confirmed: balance.confirmed,
+				unconfirmed: balance.unconfirmed,
+			}
+		}
+	};
+
+	Ok(balance)
+}
+
+pub fn list_utxos(args: &AddressArgs) -> anyhow::Result<Utxos> {
+	let address = BitcoinAddress::from_str(&args.address)?;
+
+	let mut utxos: Vec<Utxo> = match &args.esplora_url {
+		Some(esplora_url) => {
+			let utxos: Vec<EsploraUtxo> =
+				reqwest::blocking::get(esplora_endpoint(
+					esplora_url,
+					&format!("address/{}/utxo", address),
+				)?)?
+				.error_for_status()?
+				.json()?;
+
+			utxos
+				.into_iter()
+				.map(|utxo| Utxo {
+					txid: utxo.txid,
+					vout: utxo.vout,
+					value: utxo.value,
+					height: utxo.status.block_height,
+				})
+				.collect()
+		}
+		None => electrum_client(args)?
+			.script_list_unspent(&address.script_pubkey())?
+			.into_iter()
+			.map(|utxo| Utxo {
+				txid: utxo.tx_hash.to_string(),
+				vout: utxo.tx_pos as u32,
+				value: utxo.value,
+				// Electrum reports unconfirmed outputs at height 0
+				height: (utxo.height > 0).then_some(utxo.height as u32),
+			})
+			.collect(),
+	};
+
+	utxos.sort_by(|a, b| b.value.cmp(&a.value));
+
+	Ok(Utxos {
+		address: address.to_string(),
+		total: utxos.iter().map(|utxo| utxo.value).sum(),
+		utxos,
+	})
+}
+
+impl Output for Balance {
+	fn print_text(&self) {
+		println!("Address {}", self.address);
+		println!("  confirmed:   {} sats", self.confirmed);
+		println!("  unconfirmed: {} sats", self.unconfirmed);
+	}
+}
+
+impl Output for Utxos {
+	fn print_text(&self) {
+		println!("Address {}", self.address);
+
+		for utxo in &self.utxos {
+			println!(
+				"  {}:{} {} sats {}",
+				utxo.txid,
+				utxo.vout,
+				utxo.value,
+				match utxo.height {
+					Some(height) => format!("(confirmed at {})", height),
+					None => "(unconfirmed)".to_string(),
+				}
+			);
+		}
+
+		println!("  {} outputs, {} sats", self.utxos.len(), self.total);
+	}
+}
+
+fn electrum_client(
+	args: &AddressArgs,
+) -> anyhow::Result<bdk::electrum_client::Client> {
+	let node_url = args.node_url.as_ref().ok_or_else(|| {
+		anyhow::anyhow!("An Electrum or Esplora URL is required")
+	})?;
+
+	Ok(bdk::electrum_client::Client::new(node_url.as_str())?)
+}
+
+fn esplora_endpoint(esplora_url: &Url, path: &str) -> anyhow::Result<Url> {
+	Ok(Url::parse(&format!(
+		"{}/{}",
+		esplora_url.as_str().trim_end_matches('/'),
+		path
+	))?)
+}
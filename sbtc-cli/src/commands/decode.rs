@@ -0,0 +1,224 @@
+use std::str::FromStr;
+
+use bdk::{
+	bitcoin::{
+		blockdata::{opcodes::all::OP_RETURN, script::Instruction},
+		psbt::serialize::Deserialize,
+		Address as BitcoinAddress, Network as BitcoinNetwork, Transaction,
+		TxOut, Txid,
+	},
+	electrum_client::ElectrumApi,
+};
+use clap::Parser;
+use sbtc_core::operations::{
+	op_drop,
+	op_return::withdrawal_request,
+	payload::{NetworkPayload, Payload},
+};
+use stacks_core::{codec::Codec, utils::PrincipalData};
+use url::Url;
+
+use crate::commands::output::Output;
+
+#[derive(Parser, Debug, Clone)]
+pub struct DecodeArgs {
+	/// Raw transaction hex, or the txid of a transaction to fetch from the
+	/// Electrum node
+	tx: String,
+
+	/// Electrum node to fetch the transaction from, required with a txid
+	#[clap(short('u'), long)]
+	node_url: Option<Url>,
+}
+
+/// sBTC operation decoded from a transaction. Only the fields of the
+/// operation type are set.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct DecodedOperation {
+	txid: String,
+	operation: &'static str,
+	network: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	recipient: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	amount: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	sbtc_wallet: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	drawee: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	fulfillment_fee: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	chain_tip: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	signature_error: Option<String>,
+}
+
+impl DecodedOperation {
+	/// Kind of the operation, such as `deposit` or `withdrawal-request`
+	pub fn operation(&self) -> &'static str {
+		self.operation
+	}
+}
+
+pub fn decode_tx(decode: &DecodeArgs) -> anyhow::Result<DecodedOperation> {
+	let tx = match Txid::from_str(&decode.tx) {
+		Ok(txid) => {
+			let node_url = decode.node_url.as_ref().ok_or_else(|| {
+				anyhow::anyhow!("A node URL is required to fetch a txid")
+			})?;
+
+			bdk::electrum_client::Client::new(node_url.as_str())?
+				.transaction_get(&txid)?
+		}
+		Err(_) => Transaction::deserialize(&hex::decode(decode.tx.trim())?)?,
+	};
+
+	let decoded = decode_operation(&tx).ok_or_else(|| {
+		anyhow::anyhow!("Transaction {} is not an sBTC operation", tx.txid())
+	})?;
+
+	Ok(decoded)
+}
+
+/// Decode the payload of the first output, or the OP_DROP deposit data of
+/// the inputs, of an sBTC transaction of any network
+pub fn decode_operation(tx: &Transaction) -> Option<DecodedOperation> {
+	let Some(NetworkPayload { network, payload }) =
+		tx.output.first().and_then(op_return_payload)
+	else {
+		return decode_op_drop_deposit(tx);
+	};
+
+	let mut decoded = DecodedOperation {
+		txid: tx.txid().to_string(),
+		network: network.to_string(),
+		..Default::default()
+	};
+	let second_output = tx.output.get(1);
+
+	match payload {
+		Payload::Deposit { recipient } => {
+			decoded.operation = "deposit";
+			decoded.recipient = Some(principal_to_string(&recipient));
+			decoded.amount = second_output.map(|output| output.value);
+			decoded.sbtc_wallet = second_output
+				.and_then(|output| output_address(output, network));
+		}
+		Payload::WithdrawalRequest { amount, .. } => {
+			decoded.operation = "withdrawal-request";
+			decoded.amount = Some(amount);
+			decoded.recipient = second_output
+				.and_then(|output| output_address(output, network));
+
+			match withdrawal_request::parse(tx, network) {
+				Ok(withdrawal) => {
+					decoded.drawee =
+						Some(withdrawal.drawee_stacks_address.to_string());
+					decoded.fulfillment_fee =
+						Some(withdrawal.fulfillment_amount);
+					decoded.sbtc_wallet =
+						Some(withdrawal.sbtc_wallet.to_string());
+				}
+				Err(err) => decoded.signature_error = Some(err.to_string()),
+			}
+		}
+		Payload::WithdrawalFulfillment { chain_tip } => {
+			decoded.operation = "withdrawal-fulfillment";
+			decoded.recipient = second_output
+				.and_then(|output| output_address(output, network));
+			decoded.amount = second_output.map(|output| output.value);
+			decoded.chain_tip = Some(hex::encode(chain_tip.serialize_to_vec()));
+		}
+		Payload::WalletHandoff => {
+			decoded.operation = "wallet-handoff";
+			decoded.sbtc_wallet = second_output
+				.and_then(|output| output_address(output, network));
+			decoded.amount = second_output.map(|output| output.value);
+		}
+	}
+
+	Some(decoded)
+}
+
+fn decode_op_drop_deposit(tx: &Transaction) -> Option<DecodedOperation> {
+	[
+		BitcoinNetwork::Bitcoin,
+		BitcoinNetwork::Testnet,
+		BitcoinNetwork::Regtest,
+	]
+	.into_iter()
+	.find_map(|network| {
+		let deposit =
+			op_drop::deposit::parse_deposit(network, tx.clone()).ok()?;
+
+		Some(DecodedOperation {
+			txid: tx.txid().to_string(),
+			operation: "op-drop-deposit",
+			network: network.to_string(),
+			recipient: Some(principal_to_string(&deposit.recipient)),
+			amount: Some(deposit.amount),
+			sbtc_wallet: Some(deposit.sbtc_wallet_address.to_string()),
+			..Default::default()
+		})
+	})
+}
+
+fn op_return_payload(output: &TxOut) -> Option<NetworkPayload> {
+	let mut instructions = output.script_pubkey.instructions();
+
+	let Some(Ok(Instruction::Op(OP_RETURN))) = instructions.next() else {
+		return None;
+	};
+
+	let Some(Ok(Instruction::PushBytes(mut data))) = instructions.next() else {
+		return None;
+	};
+
+	NetworkPayload::codec_deserialize(&mut data).ok()
+}
+
+fn output_address(output: &TxOut, network: BitcoinNetwork) -> Option<String> {
+	BitcoinAddress::from_script(&output.script_pubkey, network)
+		.ok()
+		.map(|address| address.to_string())
+}
+
+fn principal_to_string(principal: &PrincipalData) -> String {
+	match principal {
+		PrincipalData::Standard(data) => data.1.to_string(),
+		PrincipalData::Contract(data, contract_name) => {
+			format!("{}.{}", data.1, contract_name)
+		}
+	}
+}
+
+impl Output for DecodedOperation {
+	fn print_text(&self) {
+		println!("Transaction {}", self.txid);
+
+		let fields = [
+			("operation", Some(self.operation.to_string())),
+			("network", Some(self.network.clone())),
+			("recipient", self.recipient.clone()),
+			(
+				"amount",
+				self.amount.map(|amount| format!("{} sats", amount)),
+			),
+			("sbtc wallet", self.sbtc_wallet.clone()),
+			("drawee", self.drawee.clone()),
+			(
+				"fulfillment fee",
+				self.fulfillment_fee.map(|fee| format!("{} sats", fee)),
+			),
+			("chain tip", self.chain_tip.clone()),
+			("invalid signature", self.signature_error.clone()),
+		];
+
+		for (name, value) in fields {
+			if let Some(value) = value {
+				println!("  {:<17}{}", format!("{}:", name), value);
+			}
+		}
+	}
+}
@@ -1,33 +1,38 @@
-use std::{io::stdout, str::FromStr};
+use std::str::FromStr;
 
 use bdk::{
 	bitcoin::{
 		psbt::serialize::Serialize, Address as BitcoinAddress,
-		Network as BitcoinNetwork, PrivateKey,
+		Network as BitcoinNetwork,
 	},
 	blockchain::{
 		ConfigurableBlockchain, ElectrumBlockchain, ElectrumBlockchainConfig,
 	},
 	database::MemoryDatabase,
 	template::P2Wpkh,
-	SyncOptions, Wallet,
+	SignOptions, SyncOptions, Wallet,
 };
 use clap::Parser;
-use sbtc_core::operations::op_return::deposit::build_deposit_transaction;
+use sbtc_core::operations::{
+	op_return::deposit::{create_psbt_with_options, DepositOptions},
+	utils::SatPerVb,
+};
 use stacks_core::utils::PrincipalData;
 use url::Url;
 
-use crate::commands::utils;
+use crate::commands::{
+	output::Output,
+	utils::{self, BroadcastOptions, KeyArgs},
+};
 
 #[derive(Parser, Debug, Clone)]
 pub struct DepositArgs {
-	/// Where to broadcast the transaction
+	/// Electrum node used to fund the transaction and broadcast it
 	#[clap(short('u'), long)]
 	node_url: Url,
 
-	/// Bitcoin WIF of the P2wPKH address
-	#[clap(short, long)]
-	wif: String,
+	#[command(flatten)]
+	key: KeyArgs,
 
 	/// Bitcoin network where the deposit will be broadcasted to
 	#[clap(short, long)]
@@ -44,10 +49,42 @@ pub struct DepositArgs {
 	/// Bitcoin address of the sbtc wallet
 	#[clap(short, long)]
 	sbtc_wallet: String,
+
+	/// Fee rate of the transaction, such as `5` or `5 sat/vB`
+	#[clap(long, value_parser = utils::parse_fee_rate)]
+	fee_rate: Option<SatPerVb>,
+
+	#[command(flatten)]
+	broadcast: BroadcastOptions,
+}
+
+/// Deposit transaction built by the command
+#[derive(serde::Serialize)]
+pub struct DepositOutput {
+	/// Txid of the deposit
+	id: String,
+	/// Signed transaction
+	hex: String,
+	/// Stacks principal receiving the sBTC
+	recipient: String,
+	/// Bitcoin address of the sbtc wallet
+	sbtc_wallet: String,
+	/// Amount of sats deposited
+	amount: u64,
+	/// Fee of the transaction in sats
+	fee: u64,
+	/// Change returned to the depositor in sats
+	change: u64,
+	/// Estimated virtual size of the transaction
+	vsize: usize,
+	/// Whether the transaction was broadcasted
+	broadcasted: bool,
 }
 
-pub fn build_deposit_tx(deposit: &DepositArgs) -> anyhow::Result<()> {
-	let private_key = PrivateKey::from_wif(&deposit.wif)?;
+pub fn build_deposit_tx(
+	deposit: &DepositArgs,
+) -> anyhow::Result<DepositOutput> {
+	let private_key = deposit.key.private_key(deposit.network)?;
 
 	let blockchain =
 		ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
@@ -71,21 +108,50 @@ pub fn build_deposit_tx(deposit: &DepositArgs) -> anyhow::Result<()> {
 	let stx_recipient = PrincipalData::try_from(deposit.recipient.to_string())?;
 	let sbtc_wallet_address = BitcoinAddress::from_str(&deposit.sbtc_wallet)?;
 
-	let tx = build_deposit_transaction(
-		wallet,
+	let (mut psbt, summary) = create_psbt_with_options(
+		&wallet,
 		stx_recipient,
-		sbtc_wallet_address,
+		&sbtc_wallet_address,
 		deposit.amount,
 		deposit.network,
-	)?;
-
-	serde_json::to_writer_pretty(
-		stdout(),
-		&utils::TransactionData {
-			id: tx.txid().to_string(),
-			hex: hex::encode(tx.serialize()),
+		&DepositOptions {
+			fee_rate: deposit.fee_rate,
+			..Default::default()
 		},
 	)?;
 
-	Ok(())
+	wallet.sign(&mut psbt, SignOptions::default())?;
+
+	let tx = psbt.extract_tx();
+
+	deposit.broadcast.broadcast(&tx, &deposit.node_url)?;
+
+	Ok(DepositOutput {
+		id: tx.txid().to_string(),
+		hex: hex::encode(tx.serialize()),
+		recipient: deposit.recipient.clone(),
+		sbtc_wallet: sbtc_wallet_address.to_string(),
+		amount: summary.amount,
+		fee: summary.fee,
+		change: summary.change,
+		vsize: summary.vsize,
+		broadcasted: deposit.broadcast.enabled(),
+	})
+}
+
+impl Output for DepositOutput {
+	fn print_text(&self) {
+		println!("Deposit {}", self.id);
+		println!("  recipient:   {}", self.recipient);
+		println!("  sbtc wallet: {}", self.sbtc_wallet);
+		println!("  amount:      {} sats", self.amount);
+		println!("  fee:         {} sats", self.fee);
+		println!("  change:      {} sats", self.change);
+		println!("  vsize:       {} vB", self.vsize);
+		println!(
+			"  broadcast:   {}",
+			if self.broadcasted { "yes" } else { "no" }
+		);
+		println!("{}", self.hex);
+	}
 }
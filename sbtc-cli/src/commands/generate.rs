@@ -1,5 +1,3 @@
-use std::io::stdout;
-
 use bdk::bitcoin::Network as BitcoinNetwork;
 use clap::Parser;
 use serde_json::{Map, Value};
@@ -33,40 +31,29 @@ enum GenerateSubcommand {
 	Mnemonic { mnemonic: String },
 }
 
-pub fn generate(generate_args: &GenerateArgs) -> anyhow::Result<()> {
-	match &generate_args.subcommand {
-		GenerateSubcommand::New => {
-			let wallet = Wallet::random()?;
-
-			serde_json::to_writer_pretty(
-				stdout(),
-				&value_from_wallet(&wallet, generate_args),
-			)?;
-		}
-		GenerateSubcommand::Mnemonic { mnemonic } => {
-			let wallet = Wallet::new(mnemonic)?;
-
-			serde_json::to_writer_pretty(
-				stdout(),
-				&value_from_wallet(&wallet, generate_args),
-			)?;
-		}
+pub fn generate(generate_args: &GenerateArgs) -> anyhow::Result<Value> {
+	let wallet = match &generate_args.subcommand {
+		GenerateSubcommand::New => Wallet::random()?,
+		GenerateSubcommand::Mnemonic { mnemonic } => Wallet::new(mnemonic)?,
 	};
 
-	Ok(())
+	value_from_wallet(&wallet, generate_args)
 }
 
-fn value_from_wallet(wallet: &Wallet, generate_args: &GenerateArgs) -> Value {
+fn value_from_wallet(
+	wallet: &Wallet,
+	generate_args: &GenerateArgs,
+) -> anyhow::Result<Value> {
 	let mut map = Map::new();
 
 	map.insert("mnemonic".into(), wallet.mnemonic().to_string().into());
 	map.insert(
 		"private_key".into(),
-		hex::encode(wallet.master_key().secret_bytes()).into(),
+		hex::encode(wallet.master_key()?.secret_bytes()).into(),
 	);
 	map.insert(
 		"wif".into(),
-		wallet.wif(generate_args.stacks_network).to_string().into(),
+		wallet.wif(generate_args.stacks_network)?.to_string().into(),
 	);
 
 	let mut credentials: Vec<Value> = Default::default();
@@ -76,21 +63,15 @@ fn value_from_wallet(wallet: &Wallet, generate_args: &GenerateArgs) -> Value {
 		creds.insert(
 			"stacks".into(),
 			value_from_credentials(
-				wallet
-					.credentials(generate_args.stacks_network, i as u32)
-					.unwrap(),
-			),
+				wallet.credentials(generate_args.stacks_network, i as u32)?,
+			)?,
 		);
 		creds.insert(
 			"bitcoin".into(),
-			value_from_bitcoin_credentials(
-				wallet
-					.bitcoin_credentials(
-						generate_args.bitcoin_network,
-						i as u32,
-					)
-					.unwrap(),
-			),
+			value_from_bitcoin_credentials(wallet.bitcoin_credentials(
+				generate_args.bitcoin_network,
+				i as u32,
+			)?)?,
 		);
 
 		credentials.push(creds.into());
@@ -123,31 +104,33 @@ fn value_from_wallet(wallet: &Wallet, generate_args: &GenerateArgs) -> Value {
 			.into(),
 	);
 
-	map.into()
+	Ok(map.into())
 }
 
-fn value_from_credentials(creds: Credentials) -> Value {
+pub fn value_from_credentials(creds: Credentials) -> anyhow::Result<Value> {
 	let mut stacks_creds = Map::new();
 
 	stacks_creds.insert(
 		"private_key".into(),
-		hex::encode(creds.private_key().secret_bytes()).into(),
+		hex::encode(creds.private_key()?.secret_bytes()).into(),
 	);
 	stacks_creds
 		.insert("public_key".into(), creds.public_key().to_string().into());
 	stacks_creds.insert("address".into(), creds.address().to_string().into());
-	stacks_creds.insert("wif".into(), creds.wif().to_string().into());
+	stacks_creds.insert("wif".into(), creds.wif()?.to_string().into());
 
-	stacks_creds.into()
+	Ok(stacks_creds.into())
 }
 
-fn value_from_bitcoin_credentials(creds: BitcoinCredentials) -> Value {
+pub fn value_from_bitcoin_credentials(
+	creds: BitcoinCredentials,
+) -> anyhow::Result<Value> {
 	let mut btc_creds = Map::new();
 
 	let mut btc_p2pkh_creds = Map::new();
 	btc_p2pkh_creds.insert(
 		"private_key".into(),
-		hex::encode(creds.private_key_p2pkh().secret_bytes()).into(),
+		hex::encode(creds.private_key_p2pkh()?.secret_bytes()).into(),
 	);
 	btc_p2pkh_creds.insert(
 		"public_key".into(),
@@ -155,13 +138,13 @@ fn value_from_bitcoin_credentials(creds: BitcoinCredentials) -> Value {
 	);
 	btc_p2pkh_creds
 		.insert("address".into(), creds.address_p2pkh().to_string().into());
-	btc_p2pkh_creds.insert("wif".into(), creds.wif_p2pkh().to_string().into());
+	btc_p2pkh_creds.insert("wif".into(), creds.wif_p2pkh()?.to_string().into());
 	btc_creds.insert("p2pkh".into(), btc_p2pkh_creds.into());
 
 	let mut btc_p2wpkh_creds = Map::new();
 	btc_p2wpkh_creds.insert(
 		"private_key".into(),
-		hex::encode(creds.private_key_p2wpkh().secret_bytes()).into(),
+		hex::encode(creds.private_key_p2wpkh()?.secret_bytes()).into(),
 	);
 	btc_p2wpkh_creds.insert(
 		"public_key".into(),
@@ -170,13 +153,13 @@ fn value_from_bitcoin_credentials(creds: BitcoinCredentials) -> Value {
 	btc_p2wpkh_creds
 		.insert("address".into(), creds.address_p2wpkh().to_string().into());
 	btc_p2wpkh_creds
-		.insert("wif".into(), creds.wif_p2wpkh().to_string().into());
+		.insert("wif".into(), creds.wif_p2wpkh()?.to_string().into());
 	btc_creds.insert("p2wpkh".into(), btc_p2wpkh_creds.into());
 
 	let mut btc_p2tr_creds = Map::new();
 	btc_p2tr_creds.insert(
 		"private_key".into(),
-		hex::encode(creds.private_key_p2tr().secret_bytes()).into(),
+		hex::encode(creds.private_key_p2tr()?.secret_bytes()).into(),
 	);
 	btc_p2tr_creds.insert(
 		"public_key".into(),
@@ -184,8 +167,8 @@ fn value_from_bitcoin_credentials(creds: BitcoinCredentials) -> Value {
 	);
 	btc_p2tr_creds
 		.insert("address".into(), creds.address_p2tr().to_string().into());
-	btc_p2tr_creds.insert("wif".into(), creds.wif_p2tr().to_string().into());
+	btc_p2tr_creds.insert("wif".into(), creds.wif_p2tr()?.to_string().into());
 	btc_creds.insert("p2tr".into(), btc_p2tr_creds.into());
 
-	btc_creds.into()
+	Ok(btc_creds.into())
 }
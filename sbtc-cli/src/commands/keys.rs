@@ -0,0 +1,196 @@
+use std::str::FromStr;
+
+use bdk::bitcoin::{
+	secp256k1::{Secp256k1, SecretKey},
+	Address as BitcoinAddress, Network as BitcoinNetwork,
+};
+use clap::Parser;
+use serde_json::{json, Map, Value};
+use stacks_core::{
+	address::{AddressVersion, StacksAddress},
+	crypto::wif::WIF,
+	wallet::{Language, Wallet},
+	Network as StacksNetwork,
+};
+
+use crate::commands::generate::{
+	value_from_bitcoin_credentials, value_from_credentials,
+};
+
+#[derive(Parser, Debug, Clone)]
+pub struct KeysArgs {
+	#[command(subcommand)]
+	subcommand: KeysSubcommand,
+
+	/// Stacks network of the keys and addresses
+	#[clap(short, long, default_value_t = StacksNetwork::Mainnet)]
+	stacks_network: StacksNetwork,
+
+	/// Bitcoin network of the keys and addresses
+	#[clap(short, long, default_value_t = BitcoinNetwork::Bitcoin)]
+	bitcoin_network: BitcoinNetwork,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+enum KeysSubcommand {
+	/// Generate a random mnemonic and the credentials of its first account
+	Generate {
+		/// Number of words of the mnemonic: 12, 15, 18, 21 or 24
+		#[clap(short, long, default_value_t = 24)]
+		words: usize,
+	},
+	/// Print the credentials derived from a mnemonic at the indices
+	Inspect {
+		/// Mnemonic to derive the credentials from
+		mnemonic: String,
+
+		/// Derivation indices of the accounts, such as `0,1,7`
+		#[clap(short, long, value_delimiter = ',', default_value = "0")]
+		indices: Vec<u32>,
+
+		/// BIP39 passphrase of the mnemonic
+		#[clap(long, default_value = "")]
+		passphrase: String,
+	},
+	/// Convert a WIF, hex private key, Stacks address or Bitcoin address into
+	/// the other representations
+	Convert {
+		/// The key or address to convert
+		input: String,
+	},
+}
+
+pub fn keys(keys_args: &KeysArgs) -> anyhow::Result<Value> {
+	match &keys_args.subcommand {
+		KeysSubcommand::Generate { words } => {
+			let wallet = Wallet::generate(*words, Language::English)?;
+
+			value_from_accounts(&wallet, &[0], keys_args)
+		}
+		KeysSubcommand::Inspect {
+			mnemonic,
+			indices,
+			passphrase,
+		} => {
+			let wallet = Wallet::new_with_passphrase(mnemonic, passphrase)?;
+
+			value_from_accounts(&wallet, indices, keys_args)
+		}
+		KeysSubcommand::Convert { input } => convert(input.trim(), keys_args),
+	}
+}
+
+fn value_from_accounts(
+	wallet: &Wallet,
+	indices: &[u32],
+	keys_args: &KeysArgs,
+) -> anyhow::Result<Value> {
+	let mut credentials = Map::new();
+
+	for index in indices {
+		credentials.insert(
+			index.to_string(),
+			json!({
+				"stacks": value_from_credentials(
+					wallet.credentials(keys_args.stacks_network, *index)?
+				)?,
+				"bitcoin": value_from_bitcoin_credentials(
+					wallet.bitcoin_credentials(
+						keys_args.bitcoin_network,
+						*index
+					)?
+				)?,
+			}),
+		);
+	}
+
+	Ok(json!({
+		"mnemonic": wallet.mnemonic().to_string(),
+		"credentials": credentials,
+		"network_stacks": network_name(keys_args.stacks_network),
+		"network_bitcoin": keys_args.bitcoin_network.to_string(),
+	}))
+}
+
+fn convert(input: &str, keys_args: &KeysArgs) -> anyhow::Result<Value> {
+	if let Ok(wif) = WIF::try_from(input.to_string()) {
+		return Ok(value_from_private_key(wif.private_key()?, keys_args));
+	}
+
+	if let Ok(private_key) = hex::decode(input)
+		.map_err(anyhow::Error::from)
+		.and_then(|bytes| Ok(SecretKey::from_slice(&bytes)?))
+	{
+		return Ok(value_from_private_key(private_key, keys_args));
+	}
+
+	if let Ok(address) = StacksAddress::try_from_lenient(input) {
+		let bitcoin_network = match address.version().network() {
+			StacksNetwork::Mainnet => BitcoinNetwork::Bitcoin,
+			StacksNetwork::Testnet => keys_args.bitcoin_network,
+		};
+
+		return Ok(json!({
+			"stacks_address": address.to_string(),
+			"version": u8::from(address.version()),
+			"hash160": hex::encode(address.hash().as_ref()),
+			"bitcoin_address": address
+				.to_bitcoin_address(bitcoin_network)
+				.ok()
+				.map(|address| address.to_string()),
+		}));
+	}
+
+	if let Ok(address) = BitcoinAddress::from_str(input) {
+		let stacks_address = StacksAddress::from_bitcoin_address(&address)?;
+
+		return Ok(json!({
+			"bitcoin_address": address.to_string(),
+			"stacks_address": stacks_address.to_string(),
+			"version": u8::from(stacks_address.version()),
+			"hash160": hex::encode(stacks_address.hash().as_ref()),
+		}));
+	}
+
+	Err(anyhow::anyhow!(
+		"Expected a WIF, a hex private key, a Stacks address or a Bitcoin \
+		 address"
+	))
+}
+
+fn value_from_private_key(
+	private_key: SecretKey,
+	keys_args: &KeysArgs,
+) -> Value {
+	let public_key = private_key.public_key(&Secp256k1::new());
+	let bitcoin_public_key = bdk::bitcoin::PublicKey::new(public_key);
+	let stacks_version = match keys_args.stacks_network {
+		StacksNetwork::Mainnet => AddressVersion::MainnetSingleSig,
+		StacksNetwork::Testnet => AddressVersion::TestnetSingleSig,
+	};
+
+	json!({
+		"private_key": hex::encode(private_key.secret_bytes()),
+		"public_key": public_key.to_string(),
+		"wif": WIF::new(keys_args.stacks_network, private_key).to_string(),
+		"stacks_address":
+			StacksAddress::p2pkh(stacks_version, &public_key).to_string(),
+		"bitcoin_address_p2pkh": BitcoinAddress::p2pkh(
+			&bitcoin_public_key,
+			keys_args.bitcoin_network,
+		)
+		.to_string(),
+		"bitcoin_address_p2wpkh": BitcoinAddress::p2wpkh(
+			&bitcoin_public_key,
+			keys_args.bitcoin_network,
+		)
+		.map(|address| address.to_string())
+		.ok(),
+		"network_stacks": network_name(keys_args.stacks_network),
+		"network_bitcoin": keys_args.bitcoin_network.to_string(),
+	})
+}
+
+fn network_name(network: StacksNetwork) -> String {
+	network.to_string().to_ascii_lowercase()
+}
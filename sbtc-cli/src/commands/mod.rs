@@ -1,5 +1,10 @@
+pub mod balance;
 pub mod broadcast;
+pub mod decode;
 pub mod deposit;
 pub mod generate;
+pub mod keys;
+pub mod output;
+pub mod track;
 pub mod utils;
 pub mod withdraw;
@@ -0,0 +1,134 @@
+//! Output of the commands, printed for humans or as versioned JSON for
+//! scripts. The JSON objects carry the `schema_version` of their fields, which
+//! is bumped whenever a field is removed or changes meaning.
+
+use std::io::{stdout, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Version of the JSON schemas of the command outputs
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How the outputs of the commands are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+	/// Human readable lines
+	Text,
+	/// One JSON object
+	Json,
+}
+
+/// Output of a command
+pub trait Output: Serialize {
+	/// Print the output for humans. Defaults to one `path: value` line per
+	/// field of the JSON object.
+	fn print_text(&self) {
+		if let Ok(value) = serde_json::to_value(self) {
+			print_value("", &value);
+		}
+	}
+}
+
+impl Output for Value {}
+
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+	schema_version: u32,
+	#[serde(flatten)]
+	output: &'a T,
+}
+
+#[derive(Serialize)]
+struct ErrorOutput {
+	error: ErrorObject,
+}
+
+#[derive(Serialize)]
+struct ErrorObject {
+	message: String,
+	causes: Vec<String>,
+}
+
+/// Print the output of a command in the format
+pub fn print(output: &impl Output, format: OutputFormat) -> anyhow::Result<()> {
+	match format {
+		OutputFormat::Text => output.print_text(),
+		OutputFormat::Json => {
+			serde_json::to_writer_pretty(
+				stdout(),
+				&Versioned {
+					schema_version: SCHEMA_VERSION,
+					output,
+				},
+			)?;
+			println!();
+		}
+	}
+
+	Ok(())
+}
+
+/// Print one event of a command streaming its progress. JSON events are
+/// printed one per line.
+pub fn print_event(
+	event: &impl Output,
+	format: OutputFormat,
+) -> anyhow::Result<()> {
+	match format {
+		OutputFormat::Text => event.print_text(),
+		OutputFormat::Json => {
+			let mut stdout = stdout().lock();
+
+			serde_json::to_writer(
+				&mut stdout,
+				&Versioned {
+					schema_version: SCHEMA_VERSION,
+					output: event,
+				},
+			)?;
+			writeln!(stdout)?;
+			stdout.flush()?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Print the error of a failed command as a JSON object
+pub fn print_error(err: &anyhow::Error) -> anyhow::Result<()> {
+	let output = ErrorOutput {
+		error: ErrorObject {
+			message: err.to_string(),
+			causes: err.chain().skip(1).map(ToString::to_string).collect(),
+		},
+	};
+
+	print(&serde_json::to_value(output)?, OutputFormat::Json)
+}
+
+fn print_value(path: &str, value: &Value) {
+	let child_path = |key: &str| {
+		if path.is_empty() {
+			key.to_string()
+		} else {
+			format!("{}.{}", path, key)
+		}
+	};
+
+	match value {
+		Value::Object(map) => {
+			for (key, value) in map {
+				print_value(&child_path(key), value);
+			}
+		}
+		Value::Array(values) => {
+			for (index, value) in values.iter().enumerate() {
+				print_value(&child_path(&index.to_string()), value);
+			}
+		}
+		Value::Null => {}
+		Value::String(string) => println!("{}: {}", path, string),
+		value => println!("{}: {}", path, value),
+	}
+}
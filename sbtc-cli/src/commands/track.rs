@@ -0,0 +1,287 @@
+use std::{thread::sleep, time::Duration};
+
+use bdk::{
+	bitcoin::{Network as BitcoinNetwork, Txid},
+	electrum_client::{Client as ElectrumClient, ElectrumApi},
+};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use stacks_core::{
+	address::StacksAddress, clarity::Value as ClarityValue,
+	contract_name::ContractName,
+};
+use url::Url;
+
+use crate::commands::{
+	decode::decode_operation,
+	output::{self, Output, OutputFormat},
+};
+
+#[derive(Parser, Debug, Clone)]
+pub struct TrackArgs {
+	/// Txid of the Bitcoin transaction of the operation
+	txid: Txid,
+
+	/// Electrum node to poll the Bitcoin transaction from
+	#[clap(short('u'), long)]
+	node_url: Url,
+
+	/// Stacks node to poll the contract from
+	#[clap(short('s'), long)]
+	stacks_node_url: Url,
+
+	/// Contract processing the operation, such as `SP000...000.asset`
+	#[clap(short, long)]
+	contract: String,
+
+	/// Bitcoin network of the transaction, which sets the default number of
+	/// confirmations
+	#[clap(short, long, default_value_t = BitcoinNetwork::Bitcoin)]
+	network: BitcoinNetwork,
+
+	/// Confirmations before the operation is processed by the contract
+	#[clap(long)]
+	min_confirmations: Option<u32>,
+
+	/// Seconds between two polls
+	#[clap(short, long, default_value_t = 10)]
+	interval: u64,
+}
+
+/// Progress of an operation, printed whenever it changes
+#[derive(Serialize)]
+pub struct TrackEvent {
+	txid: String,
+	operation: &'static str,
+	#[serde(flatten)]
+	progress: Progress,
+}
+
+/// Status of an operation. Bitcoin transactions go through the same statuses
+/// as the ones of romeo before being processed by the contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+enum Progress {
+	/// In the mempool
+	Broadcasted,
+	/// Mined, but with fewer than the minimum confirmations
+	Confirming { confirmations: u32, required: u32 },
+	/// Buried deep enough, waiting for the contract to process it
+	Confirmed { block_height: u32 },
+	/// Deposit minted by the contract
+	Minted { amount: u64 },
+	/// Withdrawal burned by the contract
+	Burned { amount: u64 },
+}
+
+impl Progress {
+	fn is_terminal(&self, operation: &str) -> bool {
+		match self {
+			Progress::Minted { .. } | Progress::Burned { .. } => true,
+			// Only deposits and withdrawal requests have contract calls
+			Progress::Confirmed { .. } => !is_processed_by_contract(operation),
+			_ => false,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct ReadOnlyResponse {
+	okay: bool,
+	result: Option<String>,
+	cause: Option<String>,
+}
+
+pub fn track(args: &TrackArgs, format: OutputFormat) -> anyhow::Result<()> {
+	let (issuer, contract_name) = parse_contract(&args.contract)?;
+	let min_confirmations = args
+		.min_confirmations
+		.unwrap_or_else(|| default_min_confirmations(args.network));
+
+	let client = ElectrumClient::new(args.node_url.as_str())?;
+	let http_client = reqwest::blocking::Client::new();
+
+	let tx = client.transaction_get(&args.txid)?;
+	let operation = decode_operation(&tx)
+		.ok_or_else(|| {
+			anyhow::anyhow!(
+				"Transaction {} is not an sBTC operation",
+				args.txid
+			)
+		})?
+		.operation();
+
+	// OP_RETURN outputs are not indexed by every Electrum server
+	let script = tx
+		.output
+		.iter()
+		.find(|output| !output.script_pubkey.is_op_return())
+		.map(|output| output.script_pubkey.clone())
+		.ok_or_else(|| {
+			anyhow::anyhow!("Transaction has no spendable output")
+		})?;
+
+	let mut last_progress = None;
+
+	loop {
+		let block_height = client
+			.script_get_history(&script)?
+			.into_iter()
+			.find(|entry| entry.tx_hash == args.txid)
+			// Electrum reports mempool transactions at heights 0 and -1
+			.and_then(|entry| {
+				(entry.height > 0).then_some(entry.height as u32)
+			});
+
+		let progress = match block_height {
+			None => Progress::Broadcasted,
+			Some(block_height) => {
+				let tip_height =
+					client.block_headers_subscribe()?.height as u32;
+				let confirmations =
+					(tip_height + 1).saturating_sub(block_height);
+
+				if confirmations < min_confirmations {
+					Progress::Confirming {
+						confirmations,
+						required: min_confirmations,
+					}
+				} else {
+					match get_amount_by_btc_txid(
+						&http_client,
+						&args.stacks_node_url,
+						&issuer,
+						&contract_name,
+						args.txid,
+					)? {
+						Some(amount) if amount >= 0 => Progress::Minted {
+							amount: u64::try_from(amount)?,
+						},
+						Some(amount) => Progress::Burned {
+							amount: u64::try_from(amount.unsigned_abs())?,
+						},
+						None => Progress::Confirmed { block_height },
+					}
+				}
+			}
+		};
+
+		if last_progress.as_ref() != Some(&progress) {
+			output::print_event(
+				&TrackEvent {
+					txid: args.txid.to_string(),
+					operation,
+					progress: progress.clone(),
+				},
+				format,
+			)?;
+		}
+
+		if progress.is_terminal(operation) {
+			return Ok(());
+		}
+
+		last_progress = Some(progress);
+		sleep(Duration::from_secs(args.interval));
+	}
+}
+
+impl Output for TrackEvent {
+	fn print_text(&self) {
+		let status = match &self.progress {
+			Progress::Broadcasted => "broadcasted".to_string(),
+			Progress::Confirming {
+				confirmations,
+				required,
+			} => format!("confirming ({}/{})", confirmations, required),
+			Progress::Confirmed { block_height } => {
+				if is_processed_by_contract(self.operation) {
+					format!(
+						"confirmed at {}, waiting for the contract",
+						block_height
+					)
+				} else {
+					format!("confirmed at {}", block_height)
+				}
+			}
+			Progress::Minted { amount } => format!("minted {} sats", amount),
+			Progress::Burned { amount } => format!("burned {} sats", amount),
+		};
+
+		println!("{} {}: {}", self.operation, self.txid, status);
+	}
+}
+
+fn is_processed_by_contract(operation: &str) -> bool {
+	matches!(
+		operation,
+		"deposit" | "op-drop-deposit" | "withdrawal-request"
+	)
+}
+
+/// Same defaults as the network profiles of romeo
+fn default_min_confirmations(network: BitcoinNetwork) -> u32 {
+	match network {
+		BitcoinNetwork::Bitcoin => 6,
+		BitcoinNetwork::Testnet | BitcoinNetwork::Signet => 3,
+		BitcoinNetwork::Regtest => 1,
+	}
+}
+
+fn parse_contract(contract: &str) -> anyhow::Result<(String, String)> {
+	let (issuer, name) = contract.split_once('.').ok_or_else(|| {
+		anyhow::anyhow!("Expected a contract such as `SP000...000.asset`")
+	})?;
+
+	StacksAddress::try_from_lenient(issuer)?;
+	ContractName::new(name)
+		.map_err(|err| anyhow::anyhow!("Invalid contract name: {:?}", err))?;
+
+	Ok((issuer.to_string(), name.to_string()))
+}
+
+/// Amount recorded by the contract for the Bitcoin transaction: positive for
+/// minted deposits and negative for burned withdrawals
+fn get_amount_by_btc_txid(
+	http_client: &reqwest::blocking::Client,
+	stacks_node_url: &Url,
+	issuer: &str,
+	contract_name: &str,
+	txid: Txid,
+) -> anyhow::Result<Option<i128>> {
+	// The contract stores the txids in big endian
+	let mut txid = txid.to_vec();
+	txid.reverse();
+
+	let res: ReadOnlyResponse = http_client
+		.post(stacks_node_url.join(&format!(
+			"/v2/contracts/call-read/{}/{}/get-amount-by-btc-txid",
+			issuer, contract_name
+		))?)
+		.json(&serde_json::json!({
+			"sender": issuer,
+			"arguments": [format!("0x{}", ClarityValue::from(txid).to_hex())],
+		}))
+		.send()?
+		.error_for_status()?
+		.json()?;
+
+	let result = match (res.okay, res.result) {
+		(true, Some(result)) => ClarityValue::from_hex(result)?,
+		_ => {
+			return Err(anyhow::anyhow!(
+				"Read-only call failed: {}",
+				res.cause.unwrap_or_default()
+			))
+		}
+	};
+
+	match result {
+		ClarityValue::Optional(None) => Ok(None),
+		ClarityValue::Optional(Some(value)) => match *value {
+			ClarityValue::Int(amount) => Ok(Some(amount)),
+			value => Err(anyhow::anyhow!("Expected an int, got {:?}", value)),
+		},
+		value => Err(anyhow::anyhow!("Expected an optional, got {:?}", value)),
+	}
+}
@@ -1,7 +1,111 @@
-use serde::Serialize;
+use bdk::{
+	bitcoin::{Network as BitcoinNetwork, PrivateKey, Transaction},
+	bitcoincore_rpc::{Auth, Client as RpcClient, RpcApi},
+	electrum_client::ElectrumApi,
+};
+use clap::Args;
+use sbtc_core::operations::utils::SatPerVb;
+use stacks_core::wallet::Wallet;
+use url::Url;
 
-#[derive(Serialize)]
-pub struct TransactionData {
-	pub id: String,
-	pub hex: String,
+/// Key paying for a transaction, given as a WIF or derived from a mnemonic
+#[derive(Args, Debug, Clone)]
+pub struct KeyArgs {
+	/// Bitcoin WIF of the P2WPKH address
+	#[clap(short, long, required_unless_present = "mnemonic")]
+	wif: Option<String>,
+
+	/// Mnemonic the P2WPKH key is derived from instead of a WIF
+	#[clap(long, conflicts_with = "wif")]
+	mnemonic: Option<String>,
+
+	/// Account index of the key derived from the mnemonic
+	#[clap(long, default_value_t = 0, requires = "mnemonic")]
+	account: u32,
+}
+
+impl KeyArgs {
+	/// The P2WPKH private key on the network
+	pub fn private_key(
+		&self,
+		network: BitcoinNetwork,
+	) -> anyhow::Result<PrivateKey> {
+		match (&self.wif, &self.mnemonic) {
+			(Some(wif), _) => Ok(PrivateKey::from_wif(wif)?),
+			(None, Some(mnemonic)) => {
+				let credentials = Wallet::new(mnemonic)?
+					.bitcoin_credentials(network, self.account)?;
+
+				Ok(PrivateKey::new(credentials.private_key_p2wpkh()?, network))
+			}
+			(None, None) => {
+				Err(anyhow::anyhow!("A WIF or mnemonic is required"))
+			}
+		}
+	}
+}
+
+/// Where to broadcast a transaction, if anywhere
+#[derive(Args, Debug, Clone)]
+pub struct BroadcastOptions {
+	/// Broadcast the transaction instead of only printing it
+	#[clap(long)]
+	broadcast: bool,
+
+	/// Bitcoin Core RPC endpoint to broadcast through instead of the Electrum
+	/// node, with the credentials in the URL
+	#[clap(long, requires = "broadcast")]
+	rpc_url: Option<Url>,
+}
+
+impl BroadcastOptions {
+	/// Whether the transaction is to be broadcasted
+	pub fn enabled(&self) -> bool {
+		self.broadcast
+	}
+
+	/// Broadcast the transaction through the RPC endpoint if set, through the
+	/// Electrum node otherwise. Does nothing unless broadcasting is enabled.
+	pub fn broadcast(
+		&self,
+		tx: &Transaction,
+		electrum_url: &Url,
+	) -> anyhow::Result<()> {
+		if !self.broadcast {
+			return Ok(());
+		}
+
+		match &self.rpc_url {
+			Some(rpc_url) => {
+				rpc_client(rpc_url)?.send_raw_transaction(tx)?;
+			}
+			None => {
+				bdk::electrum_client::Client::new(electrum_url.as_str())?
+					.transaction_broadcast(tx)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Parses a fee rate argument such as `5` or `5 sat/vB`
+pub fn parse_fee_rate(s: &str) -> Result<SatPerVb, String> {
+	s.parse::<SatPerVb>().map_err(|err| err.to_string())
+}
+
+fn rpc_client(url: &Url) -> anyhow::Result<RpcClient> {
+	let mut url = url.clone();
+	let auth = match url.username() {
+		"" => Auth::None,
+		username => Auth::UserPass(
+			username.to_string(),
+			url.password().unwrap_or_default().to_string(),
+		),
+	};
+
+	url.set_username("").unwrap();
+	url.set_password(None).unwrap();
+
+	Ok(RpcClient::new(url.as_str(), auth)?)
 }
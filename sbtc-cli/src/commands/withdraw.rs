@@ -1,25 +1,29 @@
-use std::{io::stdout, str::FromStr};
+use std::str::FromStr;
 
 use bdk::{
 	bitcoin::{
-		psbt::serialize::Serialize, Address as BitcoinAddress,
-		Network as BitcoinNetwork, PrivateKey,
+		consensus::encode, psbt::serialize::Serialize,
+		Address as BitcoinAddress, Network as BitcoinNetwork, PrivateKey,
 	},
 	blockchain::{
 		ConfigurableBlockchain, ElectrumBlockchain, ElectrumBlockchainConfig,
 	},
 	database::MemoryDatabase,
 	template::P2Wpkh,
-	SyncOptions, Wallet,
+	SignOptions, SyncOptions, Wallet,
 };
 use clap::Parser;
+use sbtc_core::operations::op_return::withdrawal_request::create_psbt;
 use url::Url;
 
-use crate::commands::utils::TransactionData;
+use crate::commands::{
+	output::Output,
+	utils::{BroadcastOptions, KeyArgs},
+};
 
 #[derive(Parser, Debug, Clone)]
 pub struct WithdrawalArgs {
-	/// Where to broadcast the transaction
+	/// Electrum node used to fund the transaction and broadcast it
 	#[clap(short('u'), long)]
 	node_url: Url,
 
@@ -27,10 +31,10 @@ pub struct WithdrawalArgs {
 	#[clap(short, long)]
 	network: BitcoinNetwork,
 
-	/// WIF of the Bitcoin P2WPKH address that will broadcast and pay for the
+	/// Key of the Bitcoin P2WPKH address that will broadcast and pay for the
 	/// withdrawal request
-	#[clap(short, long)]
-	wif: String,
+	#[command(flatten)]
+	key: KeyArgs,
 
 	/// WIF of the Stacks address that owns sBTC to be withdrawn
 	#[clap(short, long)]
@@ -51,10 +55,39 @@ pub struct WithdrawalArgs {
 	/// Bitcoin address of the sbtc wallet
 	#[clap(short, long)]
 	sbtc_wallet: String,
+
+	/// Print the unsigned PSBT, hex encoded, instead of the signed
+	/// transaction
+	#[clap(long, conflicts_with = "broadcast")]
+	psbt: bool,
+
+	#[command(flatten)]
+	broadcast: BroadcastOptions,
+}
+
+/// Withdrawal request printed by the command
+#[derive(serde::Serialize)]
+pub struct WithdrawalData {
+	/// Txid of the withdrawal request
+	id: String,
+	/// Signed transaction, unless only the PSBT was requested
+	hex: Option<String>,
+	/// Unsigned PSBT, if requested
+	psbt: Option<String>,
+	/// Amount of sats to withdraw
+	amount: u64,
+	/// Fulfillment fee paid to the sbtc wallet
+	fulfillment_fee: u64,
+	/// Bitcoin address receiving the BTC
+	payee_address: String,
+	/// Whether the transaction was broadcasted
+	broadcasted: bool,
 }
 
-pub fn build_withdrawal_tx(withdrawal: &WithdrawalArgs) -> anyhow::Result<()> {
-	let private_key = PrivateKey::from_wif(&withdrawal.wif)?;
+pub fn build_withdrawal_tx(
+	withdrawal: &WithdrawalArgs,
+) -> anyhow::Result<WithdrawalData> {
+	let private_key = withdrawal.key.private_key(withdrawal.network)?;
 
 	let blockchain =
 		ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
@@ -82,23 +115,60 @@ pub fn build_withdrawal_tx(withdrawal: &WithdrawalArgs) -> anyhow::Result<()> {
 	let sbtc_wallet_bitcoin_address =
 		BitcoinAddress::from_str(&withdrawal.sbtc_wallet)?;
 
-	let tx = sbtc_core::operations::op_return::withdrawal_request::build_withdrawal_tx(
-        &wallet,
-        withdrawal.network,
-        drawee_stacks_private_key,
-        payee_bitcoin_address,
-        sbtc_wallet_bitcoin_address,
-        withdrawal.amount,
-        withdrawal.fulfillment_fee,
-    )?;
-
-	serde_json::to_writer_pretty(
-		stdout(),
-		&TransactionData {
-			id: tx.txid().to_string(),
-			hex: hex::encode(tx.serialize()),
-		},
+	let mut psbt = create_psbt(
+		&wallet,
+		&drawee_stacks_private_key,
+		&payee_bitcoin_address,
+		&sbtc_wallet_bitcoin_address,
+		withdrawal.amount,
+		withdrawal.fulfillment_fee,
+		withdrawal.network,
 	)?;
 
-	Ok(())
+	let mut data = WithdrawalData {
+		id: psbt.unsigned_tx.txid().to_string(),
+		hex: None,
+		psbt: None,
+		amount: withdrawal.amount,
+		fulfillment_fee: withdrawal.fulfillment_fee,
+		payee_address: payee_bitcoin_address.to_string(),
+		broadcasted: false,
+	};
+
+	if withdrawal.psbt {
+		data.psbt = Some(hex::encode(encode::serialize(&psbt)));
+	} else {
+		wallet.sign(&mut psbt, SignOptions::default())?;
+
+		let tx = psbt.extract_tx();
+
+		withdrawal.broadcast.broadcast(&tx, &withdrawal.node_url)?;
+
+		data.id = tx.txid().to_string();
+		data.hex = Some(hex::encode(tx.serialize()));
+		data.broadcasted = withdrawal.broadcast.enabled();
+	}
+
+	Ok(data)
+}
+
+impl Output for WithdrawalData {
+	fn print_text(&self) {
+		println!("Withdrawal request {}", self.id);
+		println!("  payee:           {}", self.payee_address);
+		println!("  amount:          {} sats", self.amount);
+		println!("  fulfillment fee: {} sats", self.fulfillment_fee);
+		println!(
+			"  broadcast:       {}",
+			if self.broadcasted { "yes" } else { "no" }
+		);
+
+		if let Some(psbt) = &self.psbt {
+			println!("{}", psbt);
+		}
+
+		if let Some(hex) = &self.hex {
+			println!("{}", hex);
+		}
+	}
 }
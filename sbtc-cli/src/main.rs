@@ -9,9 +9,14 @@
 use clap::{Parser, Subcommand};
 
 use crate::commands::{
+	balance::{balance, list_utxos, AddressArgs},
 	broadcast::{broadcast_tx, BroadcastArgs},
+	decode::{decode_tx, DecodeArgs},
 	deposit::{build_deposit_tx, DepositArgs},
 	generate::{generate, GenerateArgs},
+	keys::{keys, KeysArgs},
+	output::{self, OutputFormat},
+	track::{track, TrackArgs},
 	withdraw::{build_withdrawal_tx, WithdrawalArgs},
 };
 
@@ -21,6 +26,10 @@ mod commands;
 struct Cli {
 	#[command(subcommand)]
 	command: Command,
+
+	/// Print the output, or the error, as a versioned JSON object
+	#[clap(long, global = true)]
+	json: bool,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -28,18 +37,58 @@ enum Command {
 	Deposit(DepositArgs),
 	Withdraw(WithdrawalArgs),
 	Broadcast(BroadcastArgs),
+	Balance(AddressArgs),
+	Utxos(AddressArgs),
+	Decode(DecodeArgs),
+	Track(TrackArgs),
 	GenerateFrom(GenerateArgs),
+	Keys(KeysArgs),
 }
 
 fn main() -> Result<(), anyhow::Error> {
 	let args = Cli::parse();
+	let format = if args.json {
+		OutputFormat::Json
+	} else {
+		OutputFormat::Text
+	};
+
+	let result = run(args.command, format);
+
+	if let Err(err) = &result {
+		if format == OutputFormat::Json {
+			output::print_error(err)?;
+			std::process::exit(1);
+		}
+	}
+
+	result
+}
 
-	match args.command {
-		Command::Deposit(deposit_args) => build_deposit_tx(&deposit_args),
+fn run(command: Command, format: OutputFormat) -> anyhow::Result<()> {
+	match command {
+		Command::Deposit(deposit_args) => {
+			output::print(&build_deposit_tx(&deposit_args)?, format)
+		}
 		Command::Withdraw(withdrawal_args) => {
-			build_withdrawal_tx(&withdrawal_args)
+			output::print(&build_withdrawal_tx(&withdrawal_args)?, format)
+		}
+		Command::Broadcast(broadcast_args) => {
+			output::print(&broadcast_tx(&broadcast_args)?, format)
+		}
+		Command::Balance(address_args) => {
+			output::print(&balance(&address_args)?, format)
+		}
+		Command::Utxos(address_args) => {
+			output::print(&list_utxos(&address_args)?, format)
+		}
+		Command::Decode(decode_args) => {
+			output::print(&decode_tx(&decode_args)?, format)
+		}
+		Command::Track(track_args) => track(&track_args, format),
+		Command::GenerateFrom(generate_args) => {
+			output::print(&generate(&generate_args)?, format)
 		}
-		Command::Broadcast(broadcast_args) => broadcast_tx(&broadcast_args),
-		Command::GenerateFrom(generate_args) => generate(&generate_args),
+		Command::Keys(keys_args) => output::print(&keys(&keys_args)?, format),
 	}
 }
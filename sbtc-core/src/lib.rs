@@ -2,7 +2,9 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
 //! # sbtc-core library: a library for interacting with the sBTC protocol
 
+#[cfg(feature = "electrum")]
 use bdk::electrum_client::Error as ElectrumError;
+use operations::validation::ValidationError;
 use stacks_core::{contract_name::ContractNameError, StacksError};
 use thiserror::Error;
 
@@ -12,6 +14,18 @@ pub mod operations;
 /// Module for an sBTC signer
 pub mod signer;
 
+/// Module for the Kotlin and Swift bindings of the sBTC operations
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+
+/// Module for the Python bindings of block scanning and payload parsing
+#[cfg(feature = "python")]
+pub mod python;
+
+/// Module for the JavaScript bindings of the sBTC operations
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 #[derive(Error, Debug)]
 /// sBTC error type
 pub enum SBTCError {
@@ -21,6 +35,7 @@ pub enum SBTCError {
 	#[error("Data is malformed: {0}")]
 	/// Malformed data
 	MalformedData(&'static str),
+	#[cfg(feature = "electrum")]
 	#[error("Electrum error: {0}: {1}")]
 	/// Electrum error
 	ElectrumError(&'static str, ElectrumError),
@@ -42,7 +57,25 @@ pub enum SBTCError {
 	/// Not an sBTC operation
 	#[error("Not an sBTC operation")]
 	NotSBTCOperation,
+	/// Transaction outputs are not in the order defined by the protocol
+	#[error("Invalid output order: {0}")]
+	InvalidOutputOrder(&'static str),
+	/// Partially signed transaction could not be finalized
+	#[error("Could not finalize PSBT: {0}")]
+	PSBTFinalizeError(String),
+	/// Transaction signer error
+	#[error("Signer error: {0}")]
+	SignerError(String),
+	/// Operation failed validation before construction
+	#[error("Validation error: {0}")]
+	ValidationError(#[from] ValidationError),
+	/// UTXOs do not cover the outputs and fee of a transaction
+	#[error("Insufficient funds: {0} available, {1} needed")]
+	InsufficientFunds(u64, u64),
 }
 
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
 /// A helper type for sBTC results
 pub type SBTCResult<T> = Result<T, SBTCError>;
@@ -0,0 +1,369 @@
+//! UniFFI bindings of the sBTC operations, from which the Kotlin and Swift
+//! bindings are generated with the `uniffi-bindgen` binary.
+
+use std::str::FromStr;
+
+use bdk::{
+	bitcoin::{
+		consensus::encode, psbt::serialize::Deserialize,
+		Address as BitcoinAddress, Network, OutPoint, Script, Transaction,
+		TxOut, Txid,
+	},
+	FeeRate, KeychainKind, LocalUtxo,
+};
+use stacks_core::{
+	c32::decode_address_checked, codec::Codec, utils::PrincipalData,
+};
+
+use crate::operations::{
+	op_return::{
+		deposit,
+		utils::{parse_op_return, ParsedOpReturn},
+		withdrawal_request,
+	},
+	utils::{principal_to_string, OfflineFunding},
+};
+
+/// Error of the bindings, carrying the message of the underlying error
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum OperationError {
+	/// An argument could not be parsed
+	#[error("Invalid argument: {0}")]
+	InvalidArgument(String),
+	/// The operation could not be built or parsed
+	#[error("{0}")]
+	Operation(String),
+}
+
+/// UTXO of the wallet funding a transaction
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Utxo {
+	/// Txid of the transaction creating the output
+	pub txid: String,
+	/// Index of the output
+	pub vout: u32,
+	/// Value in sats
+	pub value: u64,
+	/// Hex encoded pubkey script of the output
+	pub script_pubkey: String,
+}
+
+/// sBTC operation parsed from a transaction
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum Operation {
+	/// Deposit of BTC minting sBTC to the recipient
+	Deposit {
+		/// Stacks principal receiving the sBTC
+		recipient: String,
+		/// Amount of sats deposited
+		amount: u64,
+		/// Address of the sBTC wallet
+		sbtc_wallet_address: String,
+	},
+	/// Request to burn sBTC of the drawee and pay BTC to the payee
+	WithdrawalRequest {
+		/// Stacks address burning the sBTC
+		drawee_address: String,
+		/// Bitcoin address receiving the BTC
+		payee_address: String,
+		/// Amount of sats withdrawn
+		amount: u64,
+		/// Fee paid to the sBTC wallet for the fulfillment
+		fulfillment_fee: u64,
+	},
+	/// Payment of the BTC of a withdrawal by the sBTC wallet
+	WithdrawalFulfillment {
+		/// Hex encoded Stacks block ID of the chain tip
+		chain_tip: String,
+	},
+	/// Transfer of the funds to the next sBTC wallet
+	WalletHandoff,
+}
+
+/// Kind of an address
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum AddressKind {
+	/// Stacks address
+	Stacks,
+	/// Bitcoin address
+	Bitcoin,
+}
+
+/// Valid address and the network it belongs to
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct AddressInfo {
+	/// Kind of the address
+	pub kind: AddressKind,
+	/// Network of the address, such as `mainnet` for Stacks or `testnet` for
+	/// Bitcoin
+	pub network: String,
+}
+
+/// Builds the unsigned PSBT of a deposit, hex encoded, spending the UTXOs
+#[uniffi::export]
+pub fn build_deposit(
+	network: String,
+	utxos: Vec<Utxo>,
+	change_address: String,
+	fee_rate: f32,
+	recipient: String,
+	sbtc_wallet_address: String,
+	amount: u64,
+) -> Result<String, OperationError> {
+	let funding = OfflineFunding {
+		utxos: utxos
+			.into_iter()
+			.map(local_utxo)
+			.collect::<Result<_, _>>()?,
+		change_address: parse_address(&change_address)?,
+		fee_rate: FeeRate::from_sat_per_vb(fee_rate),
+	};
+
+	let psbt = deposit::create_offline_psbt(
+		&funding,
+		PrincipalData::try_from(recipient).map_err(invalid_argument)?,
+		&parse_address(&sbtc_wallet_address)?,
+		amount,
+		parse_network(&network)?,
+	)
+	.map_err(operation_error)?;
+
+	Ok(hex::encode(encode::serialize(&psbt)))
+}
+
+/// Parses the sBTC operation of a hex encoded transaction of the network
+#[uniffi::export]
+pub fn parse_operation(
+	network: String,
+	tx: String,
+) -> Result<Operation, OperationError> {
+	let network = parse_network(&network)?;
+	let tx =
+		Transaction::deserialize(&hex::decode(tx).map_err(invalid_argument)?)
+			.map_err(invalid_argument)?;
+
+	let data_output = tx.output.first().ok_or_else(not_an_operation)?;
+	let second_output = tx.output.get(1).ok_or_else(not_an_operation)?;
+
+	let parsed = parse_op_return(&data_output.script_pubkey, network)
+		.map_err(operation_error)?
+		.ok_or_else(not_an_operation)?;
+
+	Ok(match parsed {
+		ParsedOpReturn::Deposit(data) => Operation::Deposit {
+			recipient: principal_to_string(&data.recipient),
+			amount: second_output.value,
+			sbtc_wallet_address: output_address(second_output, network)?,
+		},
+		ParsedOpReturn::WithdrawalRequest(_) => {
+			let data = withdrawal_request::parse(&tx, network)
+				.map_err(operation_error)?;
+
+			Operation::WithdrawalRequest {
+				drawee_address: data.drawee_stacks_address.to_string(),
+				payee_address: data.payee_bitcoin_address.to_string(),
+				amount: data.amount,
+				fulfillment_fee: data.fulfillment_amount,
+			}
+		}
+		ParsedOpReturn::WithdrawalFulfillment(data) => {
+			Operation::WithdrawalFulfillment {
+				chain_tip: hex::encode(data.chain_tip.serialize_to_vec()),
+			}
+		}
+		ParsedOpReturn::WalletHandoff(_) => Operation::WalletHandoff,
+		ParsedOpReturn::Unknown { op_type, .. } => {
+			return Err(OperationError::Operation(format!(
+				"Unsupported sBTC operation: {}",
+				op_type
+			)))
+		}
+	})
+}
+
+/// Validates a Stacks or Bitcoin address, returning its kind and network
+#[uniffi::export]
+pub fn validate_address(
+	address: String,
+) -> Result<AddressInfo, OperationError> {
+	if let Ok((network, _, _)) = decode_address_checked(&address, None) {
+		return Ok(AddressInfo {
+			kind: AddressKind::Stacks,
+			network: network.to_string(),
+		});
+	}
+
+	let address = parse_address(&address)?;
+
+	Ok(AddressInfo {
+		kind: AddressKind::Bitcoin,
+		network: address.network.to_string(),
+	})
+}
+
+fn local_utxo(utxo: Utxo) -> Result<LocalUtxo, OperationError> {
+	Ok(LocalUtxo {
+		outpoint: OutPoint::new(
+			Txid::from_str(&utxo.txid).map_err(invalid_argument)?,
+			utxo.vout,
+		),
+		txout: TxOut {
+			value: utxo.value,
+			script_pubkey: Script::from(
+				hex::decode(utxo.script_pubkey).map_err(invalid_argument)?,
+			),
+		},
+		keychain: KeychainKind::External,
+		is_spent: false,
+	})
+}
+
+fn output_address(
+	output: &TxOut,
+	network: Network,
+) -> Result<String, OperationError> {
+	Ok(BitcoinAddress::from_script(&output.script_pubkey, network)
+		.map_err(operation_error)?
+		.to_string())
+}
+
+fn parse_network(network: &str) -> Result<Network, OperationError> {
+	Network::from_str(network).map_err(invalid_argument)
+}
+
+fn parse_address(address: &str) -> Result<BitcoinAddress, OperationError> {
+	BitcoinAddress::from_str(address).map_err(invalid_argument)
+}
+
+fn invalid_argument(err: impl ToString) -> OperationError {
+	OperationError::InvalidArgument(err.to_string())
+}
+
+fn operation_error(err: impl ToString) -> OperationError {
+	OperationError::Operation(err.to_string())
+}
+
+fn not_an_operation() -> OperationError {
+	OperationError::Operation("Not an sBTC operation".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::psbt::PartiallySignedTransaction;
+
+	use super::*;
+
+	const RECIPIENT: &str = "ST000000000000000000002AMW42H";
+	const CHANGE_ADDRESS: &str = "tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms";
+	const SBTC_WALLET_ADDRESS: &str =
+		"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4";
+
+	fn utxos(value: u64) -> Vec<Utxo> {
+		let script_pubkey = BitcoinAddress::from_str(CHANGE_ADDRESS)
+			.unwrap()
+			.script_pubkey();
+
+		vec![Utxo {
+			txid: "01".repeat(32),
+			vout: 0,
+			value,
+			script_pubkey: hex::encode(script_pubkey.as_bytes()),
+		}]
+	}
+
+	fn deposit(network: &str, value: u64) -> Result<String, OperationError> {
+		build_deposit(
+			network.to_string(),
+			utxos(value),
+			CHANGE_ADDRESS.to_string(),
+			1.0,
+			RECIPIENT.to_string(),
+			SBTC_WALLET_ADDRESS.to_string(),
+			10_000,
+		)
+	}
+
+	fn unsigned_tx(psbt: &str) -> String {
+		let psbt: PartiallySignedTransaction =
+			encode::deserialize(&hex::decode(psbt).unwrap()).unwrap();
+
+		hex::encode(encode::serialize(&psbt.unsigned_tx))
+	}
+
+	#[test]
+	fn should_parse_the_deposit_it_builds() {
+		let tx = unsigned_tx(&deposit("testnet", 100_000).unwrap());
+
+		let Operation::Deposit {
+			recipient,
+			amount,
+			sbtc_wallet_address,
+		} = parse_operation("testnet".to_string(), tx).unwrap()
+		else {
+			panic!("Not a deposit");
+		};
+
+		assert_eq!(recipient, RECIPIENT);
+		assert_eq!(amount, 10_000);
+		assert_eq!(sbtc_wallet_address, SBTC_WALLET_ADDRESS);
+	}
+
+	#[test]
+	fn should_map_unparsable_arguments_to_invalid_argument_errors() {
+		assert!(matches!(
+			deposit("dogecoin", 100_000),
+			Err(OperationError::InvalidArgument(_))
+		));
+		assert!(matches!(
+			parse_operation("testnet".to_string(), "zz".to_string()),
+			Err(OperationError::InvalidArgument(_))
+		));
+		assert!(matches!(
+			validate_address("not an address".to_string()),
+			Err(OperationError::InvalidArgument(_))
+		));
+	}
+
+	#[test]
+	fn should_map_failed_operations_to_operation_errors() {
+		assert!(matches!(
+			deposit("testnet", 1_000),
+			Err(OperationError::Operation(_))
+		));
+
+		let tx = Transaction {
+			version: 2,
+			lock_time: bdk::bitcoin::PackedLockTime::ZERO,
+			input: vec![Default::default()],
+			output: vec![
+				TxOut {
+					value: 0,
+					script_pubkey: Script::new(),
+				};
+				2
+			],
+		};
+
+		assert!(matches!(
+			parse_operation(
+				"testnet".to_string(),
+				hex::encode(encode::serialize(&tx))
+			),
+			Err(OperationError::Operation(message))
+				if message == "Not an sBTC operation"
+		));
+	}
+
+	#[test]
+	fn should_validate_addresses_of_both_kinds() {
+		let stacks = validate_address(RECIPIENT.to_string()).unwrap();
+		let bitcoin =
+			validate_address(SBTC_WALLET_ADDRESS.to_string()).unwrap();
+
+		assert_eq!(stacks.kind, AddressKind::Stacks);
+		assert_eq!(stacks.network, "testnet");
+		assert_eq!(bitcoin.kind, AddressKind::Bitcoin);
+		assert_eq!(bitcoin.network, "testnet");
+	}
+}
@@ -2,16 +2,21 @@
 use std::io;
 
 use bdk::bitcoin::{
-	Address as BitcoinAddress, Amount, Transaction, TxOut, XOnlyPublicKey,
+	Address as BitcoinAddress, Amount, Network, OutPoint, Transaction, TxOut,
+	XOnlyPublicKey,
 };
 use stacks_core::{codec::Codec, utils::PrincipalData};
 
 use crate::operations::{
-	commit_reveal::utils::{commit, reveal, CommitRevealResult, RevealInputs},
-	Opcode,
+	commit_reveal::utils::{
+		commit, parse_commit, parse_reveal, reveal, CommitOutput,
+		CommitRevealError, CommitRevealResult, RevealInputs,
+	},
+	payload::Payload,
 };
 
 /// Data to construct a commit reveal deposit transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DepositData {
 	/// Address or contract to deposit to
 	pub principal: PrincipalData,
@@ -21,32 +26,29 @@ pub struct DepositData {
 
 impl Codec for DepositData {
 	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
-		Codec::codec_serialize(&Opcode::Deposit, dest)?;
-		self.principal.codec_serialize(dest)?;
-		self.reveal_fee.codec_serialize(dest)?;
-
-		todo!()
+		Payload::Deposit {
+			recipient: self.principal.clone(),
+		}
+		.codec_serialize(dest)?;
+		self.reveal_fee.codec_serialize(dest)
 	}
 
 	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
 	where
 		Self: Sized,
 	{
-		let opcode = Opcode::codec_deserialize(data)
-			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-
-		if !matches!(opcode, Opcode::Deposit) {
+		let Payload::Deposit { recipient } = Payload::codec_deserialize(data)?
+		else {
 			return Err(io::Error::new(
 				io::ErrorKind::InvalidData,
 				"Invalid opcode, expected deposit",
 			));
-		}
+		};
 
-		let principal = PrincipalData::codec_deserialize(data)?;
 		let reveal_fee = Amount::codec_deserialize(data)?;
 
 		Ok(Self {
-			principal,
+			principal: recipient,
 			reveal_fee,
 		})
 	}
@@ -57,8 +59,33 @@ pub fn deposit_commit_address(
 	deposit_data: DepositData,
 	revealer_key: &XOnlyPublicKey,
 	reclaim_key: &XOnlyPublicKey,
+	network: Network,
 ) -> CommitRevealResult<BitcoinAddress> {
-	commit(&deposit_data.serialize_to_vec(), revealer_key, reclaim_key)
+	commit(
+		&deposit_data.serialize_to_vec(),
+		revealer_key,
+		reclaim_key,
+		network,
+	)
+}
+
+/// Finds the output of the transaction paying to the deposit payment address
+/// of the deposit data, if any
+pub fn parse_deposit_commit(
+	tx: &Transaction,
+	deposit_data: &DepositData,
+	revealer_key: &XOnlyPublicKey,
+	reclaim_key: &XOnlyPublicKey,
+	network: Network,
+) -> CommitRevealResult<Option<CommitOutput>> {
+	let commit_address = commit(
+		&deposit_data.serialize_to_vec(),
+		revealer_key,
+		reclaim_key,
+		network,
+	)?;
+
+	Ok(parse_commit(tx, &commit_address))
 }
 
 /// Constructs a transaction that reveals the deposit payment address
@@ -77,3 +104,147 @@ pub fn deposit_reveal_unsigned_tx(
 
 	Ok(tx)
 }
+
+/// Deposit revealed by a reveal transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositReveal {
+	/// Revealed deposit data
+	pub deposit_data: DepositData,
+	/// Commit output spent by the reveal transaction
+	pub commit_output: OutPoint,
+	/// Amount paid to the sBTC wallet
+	pub amount: Amount,
+	/// The address where the BTC was deposited
+	pub sbtc_wallet_address: BitcoinAddress,
+}
+
+/// Parses a deposit reveal transaction
+pub fn parse_deposit_reveal(
+	tx: &Transaction,
+	stacks_magic_bytes: &[u8; 2],
+	network: Network,
+) -> CommitRevealResult<DepositReveal> {
+	let revealed = parse_reveal(tx, stacks_magic_bytes)?;
+	let deposit_data =
+		DepositData::codec_deserialize(&mut revealed.data.as_slice())
+			.map_err(CommitRevealError::MalformedData)?;
+
+	let payment_output =
+		tx.output
+			.get(1)
+			.ok_or(CommitRevealError::NotRevealTransaction(
+				"Missing payment output",
+			))?;
+	let sbtc_wallet_address =
+		BitcoinAddress::from_script(&payment_output.script_pubkey, network)
+			.map_err(CommitRevealError::AddressError)?;
+
+	Ok(DepositReveal {
+		deposit_data,
+		commit_output: revealed.commit_output,
+		amount: Amount::from_sat(payment_output.value),
+		sbtc_wallet_address,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{
+		secp256k1::{KeyPair, Secp256k1},
+		PackedLockTime,
+	};
+	use stacks_core::address::StacksAddress;
+
+	use super::*;
+
+	fn test_key(secret: u8) -> XOnlyPublicKey {
+		let secp = Secp256k1::new();
+
+		KeyPair::from_seckey_slice(&secp, &[secret; 32])
+			.unwrap()
+			.x_only_public_key()
+			.0
+	}
+
+	fn test_deposit_data() -> DepositData {
+		let recipient: StacksAddress =
+			"ST3RBZ4TZ3EK22SZRKGFZYBCKD7WQ5B8FFRS57TT6"
+				.try_into()
+				.unwrap();
+
+		DepositData {
+			principal: recipient.into(),
+			reveal_fee: Amount::from_sat(2_000),
+		}
+	}
+
+	#[test]
+	fn should_serialize_and_deserialize_deposit_data() {
+		let deposit_data = test_deposit_data();
+
+		let serialized = deposit_data.serialize_to_vec();
+
+		assert_eq!(
+			DepositData::deserialize(&mut serialized.as_slice()).unwrap(),
+			deposit_data
+		);
+	}
+
+	#[test]
+	fn should_parse_deposit_commit_and_reveal() {
+		let revealer_key = test_key(1);
+		let reclaim_key = test_key(2);
+		let sbtc_wallet_address: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+
+		let commit_address = deposit_commit_address(
+			test_deposit_data(),
+			&revealer_key,
+			&reclaim_key,
+			Network::Testnet,
+		)
+		.unwrap();
+		let commit_tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut {
+				value: 100_000,
+				script_pubkey: commit_address.script_pubkey(),
+			}],
+		};
+
+		let commit_output = parse_deposit_commit(
+			&commit_tx,
+			&test_deposit_data(),
+			&revealer_key,
+			&reclaim_key,
+			Network::Testnet,
+		)
+		.unwrap()
+		.unwrap();
+
+		let reveal_tx = deposit_reveal_unsigned_tx(
+			test_deposit_data(),
+			RevealInputs {
+				commit_output: commit_output.outpoint,
+				stacks_magic_bytes: b"T2",
+				revealer_key: &revealer_key,
+				reclaim_key: &reclaim_key,
+			},
+			commit_output.amount,
+			sbtc_wallet_address.clone(),
+		)
+		.unwrap();
+
+		let deposit =
+			parse_deposit_reveal(&reveal_tx, b"T2", Network::Testnet).unwrap();
+
+		assert_eq!(deposit.deposit_data, test_deposit_data());
+		assert_eq!(deposit.commit_output, commit_output.outpoint);
+		assert_eq!(deposit.amount, Amount::from_sat(98_000));
+		assert_eq!(deposit.sbtc_wallet_address, sbtc_wallet_address);
+	}
+}
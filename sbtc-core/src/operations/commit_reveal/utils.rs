@@ -1,18 +1,19 @@
 //! Utils for operation construction
-use std::{iter::once, num::TryFromIntError};
+use std::{io, iter::once, num::TryFromIntError};
 
 use bdk::bitcoin::{
 	blockdata::{
 		opcodes::all::{OP_CHECKSIG, OP_DROP, OP_RETURN},
-		script::Builder,
+		script::{Builder, Instruction},
 	},
 	schnorr::UntweakedPublicKey,
 	secp256k1::Secp256k1,
 	util::taproot::{
-		LeafVersion, TaprootBuilder, TaprootBuilderError, TaprootSpendInfo,
+		ControlBlock, LeafVersion, TaprootBuilder, TaprootBuilderError,
+		TaprootSpendInfo,
 	},
-	Address as BitcoinAddress, Network, OutPoint, PackedLockTime, Script,
-	Sequence, Transaction, TxIn, TxOut, Witness, XOnlyPublicKey,
+	Address as BitcoinAddress, Amount, Network, OutPoint, PackedLockTime,
+	Script, Sequence, Transaction, TxIn, TxOut, Witness, XOnlyPublicKey,
 };
 use thiserror::Error;
 
@@ -28,12 +29,21 @@ pub enum CommitRevealError {
 	#[error("Could not build taproot spend info: {0}: {1}")]
 	/// Taproot error
 	InvalidTaproot(&'static str, TaprootBuilderError),
+	#[error("Not a reveal transaction: {0}")]
+	/// The transaction does not have the structure of a reveal transaction
+	NotRevealTransaction(&'static str),
+	#[error("Revealed data is malformed: {0}")]
+	/// The revealed data could not be deserialized
+	MalformedData(io::Error),
+	#[error("Could not build address from script pubkey: {0}")]
+	/// Address error
+	AddressError(bdk::bitcoin::util::address::Error),
 }
 
 /// Commit reveal result
 pub type CommitRevealResult<T> = Result<T, CommitRevealError>;
 
-fn internal_key() -> UntweakedPublicKey {
+pub(crate) fn internal_key() -> UntweakedPublicKey {
 	// Copied from BIP-0341 at https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs
 	// The BIP recommends a point
 	// lift_x(0x0250929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0).
@@ -68,7 +78,10 @@ fn reclaim_script(reclaim_key: &XOnlyPublicKey) -> Script {
 		.into_script()
 }
 
-fn op_drop_script(data: &[u8], revealer_key: &XOnlyPublicKey) -> Script {
+pub(crate) fn op_drop_script(
+	data: &[u8],
+	revealer_key: &XOnlyPublicKey,
+) -> Script {
 	Builder::new()
 		.push_slice(data)
 		.push_opcode(OP_DROP)
@@ -79,6 +92,7 @@ fn op_drop_script(data: &[u8], revealer_key: &XOnlyPublicKey) -> Script {
 
 fn address_from_taproot_spend_info(
 	spend_info: TaprootSpendInfo,
+	network: Network,
 ) -> BitcoinAddress {
 	let secp = Secp256k1::new(); // Impure call
 
@@ -86,7 +100,7 @@ fn address_from_taproot_spend_info(
 		&secp,
 		spend_info.internal_key(),
 		spend_info.merkle_root(),
-		Network::Testnet, // TODO: Make sure to make this configurable
+		network,
 	)
 }
 
@@ -116,9 +130,37 @@ pub fn commit(
 	data: &[u8],
 	revealer_key: &XOnlyPublicKey,
 	reclaim_key: &XOnlyPublicKey,
+	network: Network,
 ) -> CommitRevealResult<BitcoinAddress> {
 	let spend_info = taproot_spend_info(data, revealer_key, reclaim_key)?;
-	Ok(address_from_taproot_spend_info(spend_info))
+	Ok(address_from_taproot_spend_info(spend_info, network))
+}
+
+/// Output of a commit transaction paying to a commit address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitOutput {
+	/// Outpoint to be spent by the reveal transaction
+	pub outpoint: OutPoint,
+	/// Amount committed
+	pub amount: Amount,
+}
+
+/// Finds the output of the commit transaction paying to the commit address,
+/// if any
+pub fn parse_commit(
+	tx: &Transaction,
+	commit_address: &BitcoinAddress,
+) -> Option<CommitOutput> {
+	let script_pubkey = commit_address.script_pubkey();
+
+	tx.output
+		.iter()
+		.enumerate()
+		.find(|(_, output)| output.script_pubkey == script_pubkey)
+		.map(|(vout, output)| CommitOutput {
+			outpoint: OutPoint::new(tx.txid(), vout as u32),
+			amount: Amount::from_sat(output.value),
+		})
 }
 
 /// Data for the construction of the reveal transaction
@@ -171,3 +213,196 @@ pub fn reveal(
 
 	Ok(tx)
 }
+
+/// Data exposed by the first input of a reveal transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revealed {
+	/// Commit output spent by the reveal transaction
+	pub commit_output: OutPoint,
+	/// Committed data
+	pub data: Vec<u8>,
+	/// Key that revealed the data
+	pub revealer_key: XOnlyPublicKey,
+	/// Control block proving that the script is a leaf of the commit output
+	pub control_block: ControlBlock,
+}
+
+/// Parses the data revealed by a reveal transaction. The reveal script and its
+/// control block are the last two items of the witness of the first input, so
+/// that transactions are parsed with and without a signature.
+pub fn parse_reveal(
+	tx: &Transaction,
+	stacks_magic_bytes: &[u8; 2],
+) -> CommitRevealResult<Revealed> {
+	let data_output =
+		tx.output
+			.first()
+			.ok_or(CommitRevealError::NotRevealTransaction(
+				"Missing data output",
+			))?;
+
+	if data_output.script_pubkey != reveal_op_return_script(stacks_magic_bytes)
+	{
+		return Err(CommitRevealError::NotRevealTransaction(
+			"Invalid data output",
+		));
+	}
+
+	let input =
+		tx.input
+			.first()
+			.ok_or(CommitRevealError::NotRevealTransaction(
+				"Missing commit input",
+			))?;
+
+	let (Some(script), Some(control_block)) =
+		(input.witness.second_to_last(), input.witness.last())
+	else {
+		return Err(CommitRevealError::NotRevealTransaction(
+			"Missing reveal script",
+		));
+	};
+
+	let control_block =
+		ControlBlock::from_slice(control_block).map_err(|_| {
+			CommitRevealError::NotRevealTransaction("Invalid control block")
+		})?;
+	let (data, revealer_key) =
+		parse_op_drop_script(&Script::from(script.to_vec())).ok_or(
+			CommitRevealError::NotRevealTransaction("Invalid reveal script"),
+		)?;
+
+	Ok(Revealed {
+		commit_output: input.previous_output,
+		data,
+		revealer_key,
+		control_block,
+	})
+}
+
+pub(crate) fn parse_op_drop_script(
+	script: &Script,
+) -> Option<(Vec<u8>, XOnlyPublicKey)> {
+	let mut instructions = script.instructions();
+
+	let Some(Ok(Instruction::PushBytes(data))) = instructions.next() else {
+		return None;
+	};
+	let Some(Ok(Instruction::Op(OP_DROP))) = instructions.next() else {
+		return None;
+	};
+	let Some(Ok(Instruction::PushBytes(revealer_key))) = instructions.next()
+	else {
+		return None;
+	};
+	let Some(Ok(Instruction::Op(OP_CHECKSIG))) = instructions.next() else {
+		return None;
+	};
+
+	if instructions.next().is_some() {
+		return None;
+	}
+
+	Some((
+		data.to_vec(),
+		XOnlyPublicKey::from_slice(revealer_key).ok()?,
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{hashes::Hash, secp256k1::KeyPair, Txid};
+
+	use super::*;
+
+	fn test_key(secret: u8) -> XOnlyPublicKey {
+		let secp = Secp256k1::new();
+
+		KeyPair::from_seckey_slice(&secp, &[secret; 32])
+			.unwrap()
+			.x_only_public_key()
+			.0
+	}
+
+	#[test]
+	fn should_reveal_committed_data() {
+		let data = b"sbtc commit reveal".to_vec();
+		let revealer_key = test_key(1);
+		let reclaim_key = test_key(2);
+
+		let commit_address =
+			commit(&data, &revealer_key, &reclaim_key, Network::Regtest)
+				.unwrap();
+		let commit_tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut {
+				value: 10_000,
+				script_pubkey: commit_address.script_pubkey(),
+			}],
+		};
+
+		let commit_output = parse_commit(&commit_tx, &commit_address).unwrap();
+
+		assert_eq!(commit_output.outpoint, OutPoint::new(commit_tx.txid(), 0));
+		assert_eq!(commit_output.amount, Amount::from_sat(10_000));
+
+		let reveal_tx = reveal(
+			&data,
+			RevealInputs {
+				commit_output: commit_output.outpoint,
+				stacks_magic_bytes: b"id",
+				revealer_key: &revealer_key,
+				reclaim_key: &reclaim_key,
+			},
+		)
+		.unwrap();
+
+		let revealed = parse_reveal(&reveal_tx, b"id").unwrap();
+
+		assert_eq!(revealed.commit_output, commit_output.outpoint);
+		assert_eq!(revealed.data, data);
+		assert_eq!(revealed.revealer_key, revealer_key);
+
+		// The revealed script is a leaf of the output key of the commit
+		let output_key = match commit_address.payload {
+			bdk::bitcoin::util::address::Payload::WitnessProgram {
+				program,
+				..
+			} => XOnlyPublicKey::from_slice(&program).unwrap(),
+			_ => panic!("Commit address should be a taproot address"),
+		};
+
+		assert!(revealed.control_block.verify_taproot_commitment(
+			&Secp256k1::verification_only(),
+			output_key,
+			&op_drop_script(&revealed.data, &revealed.revealer_key),
+		));
+	}
+
+	#[test]
+	fn should_not_parse_other_transactions_as_reveals() {
+		let tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: OutPoint::new(Txid::all_zeros(), 0),
+				..Default::default()
+			}],
+			output: vec![TxOut {
+				value: 0,
+				script_pubkey: reveal_op_return_script(b"id"),
+			}],
+		};
+
+		assert!(matches!(
+			parse_reveal(&tx, b"id"),
+			Err(CommitRevealError::NotRevealTransaction(_))
+		));
+		assert!(matches!(
+			parse_reveal(&tx, b"T2"),
+			Err(CommitRevealError::NotRevealTransaction(_))
+		));
+	}
+}
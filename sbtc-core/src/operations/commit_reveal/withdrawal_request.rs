@@ -3,16 +3,20 @@ use std::io;
 
 use bdk::bitcoin::{
 	secp256k1::ecdsa::RecoverableSignature, Address as BitcoinAddress, Amount,
-	Transaction, TxOut, XOnlyPublicKey,
+	Network, OutPoint, Transaction, TxOut, XOnlyPublicKey,
 };
 use stacks_core::codec::Codec;
 
 use crate::operations::{
-	commit_reveal::utils::{commit, reveal, CommitRevealResult, RevealInputs},
-	Opcode,
+	commit_reveal::utils::{
+		commit, parse_commit, parse_reveal, reveal, CommitOutput,
+		CommitRevealError, CommitRevealResult, RevealInputs,
+	},
+	payload::Payload,
 };
 
 /// Data to construct a commit reveal withdrawal transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WithdrawalData {
 	/// Amount to withdraw
 	pub amount: Amount,
@@ -24,9 +28,11 @@ pub struct WithdrawalData {
 
 impl Codec for WithdrawalData {
 	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
-		Codec::codec_serialize(&Opcode::WithdrawalRequest, dest)?;
-		self.amount.codec_serialize(dest)?;
-		self.signature.codec_serialize(dest)?;
+		Payload::WithdrawalRequest {
+			amount: self.amount.to_sat(),
+			signature: self.signature,
+		}
+		.codec_serialize(dest)?;
 		self.reveal_fee.codec_serialize(dest)
 	}
 
@@ -34,22 +40,19 @@ impl Codec for WithdrawalData {
 	where
 		Self: Sized,
 	{
-		let opcode = Opcode::codec_deserialize(data)
-			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-
-		if !matches!(opcode, Opcode::WithdrawalRequest) {
+		let Payload::WithdrawalRequest { amount, signature } =
+			Payload::codec_deserialize(data)?
+		else {
 			return Err(io::Error::new(
 				io::ErrorKind::InvalidData,
 				"Invalid opcode, expected withdrawal request",
 			));
-		}
+		};
 
-		let amount = Amount::codec_deserialize(data)?;
-		let signature = RecoverableSignature::codec_deserialize(data)?;
 		let reveal_fee = Amount::codec_deserialize(data)?;
 
 		Ok(Self {
-			amount,
+			amount: Amount::from_sat(amount),
 			signature,
 			reveal_fee,
 		})
@@ -61,14 +64,35 @@ pub fn withdrawal_request_commit_address(
 	withdrawal_data: WithdrawalData,
 	revealer_key: &XOnlyPublicKey,
 	reclaim_key: &XOnlyPublicKey,
+	network: Network,
 ) -> CommitRevealResult<BitcoinAddress> {
 	commit(
 		&withdrawal_data.serialize_to_vec(),
 		revealer_key,
 		reclaim_key,
+		network,
 	)
 }
 
+/// Finds the output of the transaction paying to the withdrawal payment
+/// address of the withdrawal data, if any
+pub fn parse_withdrawal_request_commit(
+	tx: &Transaction,
+	withdrawal_data: &WithdrawalData,
+	revealer_key: &XOnlyPublicKey,
+	reclaim_key: &XOnlyPublicKey,
+	network: Network,
+) -> CommitRevealResult<Option<CommitOutput>> {
+	let commit_address = commit(
+		&withdrawal_data.serialize_to_vec(),
+		revealer_key,
+		reclaim_key,
+		network,
+	)?;
+
+	Ok(parse_commit(tx, &commit_address))
+}
+
 /// Constructs a transaction that reveals the withdrawal payment address
 pub fn withdrawal_request_reveal_unsigned_tx(
 	withdrawal_data: WithdrawalData,
@@ -92,3 +116,156 @@ pub fn withdrawal_request_reveal_unsigned_tx(
 
 	Ok(tx)
 }
+
+/// Withdrawal request revealed by a reveal transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalRequestReveal {
+	/// Revealed withdrawal data
+	pub withdrawal_data: WithdrawalData,
+	/// Commit output spent by the reveal transaction
+	pub commit_output: OutPoint,
+	/// Address the withdrawn BTC is sent to
+	pub recipient_wallet_address: BitcoinAddress,
+	/// Fee paid to the sBTC wallet for the fulfillment
+	pub fulfillment_fee: Amount,
+	/// The address of the sBTC wallet
+	pub sbtc_wallet_address: BitcoinAddress,
+}
+
+/// Parses a withdrawal request reveal transaction
+pub fn parse_withdrawal_request_reveal(
+	tx: &Transaction,
+	stacks_magic_bytes: &[u8; 2],
+	network: Network,
+) -> CommitRevealResult<WithdrawalRequestReveal> {
+	let revealed = parse_reveal(tx, stacks_magic_bytes)?;
+	let withdrawal_data =
+		WithdrawalData::codec_deserialize(&mut revealed.data.as_slice())
+			.map_err(CommitRevealError::MalformedData)?;
+
+	let (Some(recipient_output), Some(fulfillment_output)) =
+		(tx.output.get(1), tx.output.get(2))
+	else {
+		return Err(CommitRevealError::NotRevealTransaction(
+			"Missing withdrawal outputs",
+		));
+	};
+
+	let address = |output: &TxOut| {
+		BitcoinAddress::from_script(&output.script_pubkey, network)
+			.map_err(CommitRevealError::AddressError)
+	};
+
+	Ok(WithdrawalRequestReveal {
+		withdrawal_data,
+		commit_output: revealed.commit_output,
+		recipient_wallet_address: address(recipient_output)?,
+		fulfillment_fee: Amount::from_sat(fulfillment_output.value),
+		sbtc_wallet_address: address(fulfillment_output)?,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{
+		secp256k1::{KeyPair, Message, Secp256k1, SecretKey},
+		PackedLockTime,
+	};
+
+	use super::*;
+
+	fn test_key(secret: u8) -> XOnlyPublicKey {
+		let secp = Secp256k1::new();
+
+		KeyPair::from_seckey_slice(&secp, &[secret; 32])
+			.unwrap()
+			.x_only_public_key()
+			.0
+	}
+
+	fn test_withdrawal_data() -> WithdrawalData {
+		let secp = Secp256k1::new();
+		let signature = secp.sign_ecdsa_recoverable(
+			&Message::from_slice(&[7; 32]).unwrap(),
+			&SecretKey::from_slice(&[3; 32]).unwrap(),
+		);
+
+		WithdrawalData {
+			amount: Amount::from_sat(50_000),
+			signature,
+			reveal_fee: Amount::from_sat(2_000),
+		}
+	}
+
+	#[test]
+	fn should_parse_withdrawal_request_commit_and_reveal() {
+		let revealer_key = test_key(1);
+		let reclaim_key = test_key(2);
+		let sbtc_wallet_address: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+		let recipient_wallet_address: BitcoinAddress =
+			"tb1qlj64u6fqutr0xue85kl55fx0gt4m4urun25p7q"
+				.parse()
+				.unwrap();
+
+		let commit_address = withdrawal_request_commit_address(
+			test_withdrawal_data(),
+			&revealer_key,
+			&reclaim_key,
+			Network::Testnet,
+		)
+		.unwrap();
+		let commit_tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut {
+				value: 10_000,
+				script_pubkey: commit_address.script_pubkey(),
+			}],
+		};
+
+		let commit_output = parse_withdrawal_request_commit(
+			&commit_tx,
+			&test_withdrawal_data(),
+			&revealer_key,
+			&reclaim_key,
+			Network::Testnet,
+		)
+		.unwrap()
+		.unwrap();
+
+		let reveal_tx = withdrawal_request_reveal_unsigned_tx(
+			test_withdrawal_data(),
+			RevealInputs {
+				commit_output: commit_output.outpoint,
+				stacks_magic_bytes: b"T2",
+				revealer_key: &revealer_key,
+				reclaim_key: &reclaim_key,
+			},
+			Amount::from_sat(1_000),
+			commit_output.amount,
+			sbtc_wallet_address.clone(),
+			recipient_wallet_address.clone(),
+		)
+		.unwrap();
+
+		let withdrawal = parse_withdrawal_request_reveal(
+			&reveal_tx,
+			b"T2",
+			Network::Testnet,
+		)
+		.unwrap();
+
+		assert_eq!(withdrawal.withdrawal_data, test_withdrawal_data());
+		assert_eq!(withdrawal.commit_output, commit_output.outpoint);
+		assert_eq!(
+			withdrawal.recipient_wallet_address,
+			recipient_wallet_address
+		);
+		assert_eq!(withdrawal.fulfillment_fee, Amount::from_sat(1_000));
+		assert_eq!(withdrawal.sbtc_wallet_address, sbtc_wallet_address);
+	}
+}
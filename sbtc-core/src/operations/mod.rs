@@ -5,8 +5,14 @@ use stacks_core::codec::Codec;
 use strum::FromRepr;
 
 pub mod commit_reveal;
+pub mod op_drop;
 pub mod op_return;
+pub mod payload;
+pub mod proof;
+pub mod scan;
+pub mod transaction_signer;
 pub mod utils;
+pub mod validation;
 
 /// Opcodes of sBTC transactions
 #[derive(FromRepr, Debug, Clone, Copy)]
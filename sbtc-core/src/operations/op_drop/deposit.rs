@@ -0,0 +1,328 @@
+//! Tools for the construction and parsing of the sBTC OP_DROP deposit
+//! transactions.
+//!
+//! Instead of an OP_RETURN output, the deposit data is pushed in a tapscript
+//! of the form:
+//!
+//! ```text
+//! <deposit data> OP_DROP <depositor key> OP_CHECKSIG
+//! ```
+//!
+//! The depositor first funds the taproot address whose only leaf is this
+//! script. The deposit transaction spends it through the script path, so the
+//! data is revealed in the witness of its first input, and pays the sBTC
+//! wallet address in its first output. The deposit data has the same format
+//! as the one of OP_RETURN deposits, but is not limited to 80 bytes.
+use bdk::bitcoin::{
+	schnorr::SchnorrSig,
+	secp256k1::{KeyPair, Message, Secp256k1},
+	util::{
+		sighash::{Prevouts, SighashCache},
+		taproot::{
+			ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder,
+			TaprootSpendInfo,
+		},
+	},
+	Address as BitcoinAddress, Network, OutPoint, PackedLockTime,
+	SchnorrSighashType, Script, Sequence, Transaction, TxIn, TxOut, Witness,
+	XOnlyPublicKey,
+};
+use stacks_core::{codec::Codec, utils::PrincipalData};
+
+use crate::{
+	operations::{
+		commit_reveal::utils::{
+			internal_key, op_drop_script, parse_op_drop_script,
+		},
+		magic_bytes,
+		op_return::deposit::{Deposit, DepositOutputData, DepositParseError},
+		validation::validate_peg_amount,
+	},
+	SBTCError, SBTCResult,
+};
+
+fn deposit_script(
+	recipient: PrincipalData,
+	depositor_key: &XOnlyPublicKey,
+	network: Network,
+) -> Script {
+	let deposit_data =
+		DepositOutputData { network, recipient }.serialize_to_vec();
+
+	op_drop_script(&deposit_data, depositor_key)
+}
+
+fn taproot_spend_info(script: Script) -> SBTCResult<TaprootSpendInfo> {
+	let secp = Secp256k1::new();
+
+	TaprootBuilder::new()
+		.add_leaf(0, script)
+		.map_err(|_| SBTCError::MalformedData("Invalid deposit script"))?
+		.finalize(&secp, internal_key())
+		.map_err(|_| SBTCError::MalformedData("Could not build taproot tree"))
+}
+
+/// Constructs the address the depositor funds before sending the deposit
+/// transaction. Its only spending path reveals the deposit data.
+pub fn deposit_data_address(
+	recipient: PrincipalData,
+	depositor_key: &XOnlyPublicKey,
+	network: Network,
+) -> SBTCResult<BitcoinAddress> {
+	let spend_info =
+		taproot_spend_info(deposit_script(recipient, depositor_key, network))?;
+
+	Ok(BitcoinAddress::p2tr(
+		&Secp256k1::new(),
+		spend_info.internal_key(),
+		spend_info.merkle_root(),
+		network,
+	))
+}
+
+/// Builds a complete deposit transaction spending the output paying to the
+/// deposit data address. Whatever the funding amount exceeds the deposit
+/// amount by is paid as fee.
+pub fn build_deposit_transaction(
+	depositor_key: &KeyPair,
+	funding_output: OutPoint,
+	funding_amount: u64,
+	recipient: PrincipalData,
+	sbtc_address: &BitcoinAddress,
+	amount: u64,
+	network: Network,
+) -> SBTCResult<Transaction> {
+	let secp = Secp256k1::new();
+	let (depositor_public_key, _) = depositor_key.x_only_public_key();
+
+	let sbtc_wallet_script = sbtc_address.script_pubkey();
+
+	validate_peg_amount(amount, &sbtc_wallet_script, network)?;
+
+	if amount > funding_amount {
+		return Err(SBTCError::MalformedData(
+			"Deposit amount exceeds the funding amount",
+		));
+	}
+
+	let script = deposit_script(recipient, &depositor_public_key, network);
+	let spend_info = taproot_spend_info(script.clone())?;
+	let control_block = spend_info
+		.control_block(&(script.clone(), LeafVersion::TapScript))
+		.ok_or(SBTCError::MalformedData("Missing control block"))?;
+
+	let funding_script = BitcoinAddress::p2tr(
+		&secp,
+		spend_info.internal_key(),
+		spend_info.merkle_root(),
+		network,
+	)
+	.script_pubkey();
+
+	let mut tx = Transaction {
+		version: 2,
+		lock_time: PackedLockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: funding_output,
+			script_sig: Script::new(),
+			sequence: Sequence::MAX,
+			witness: Witness::new(),
+		}],
+		output: vec![TxOut {
+			value: amount,
+			script_pubkey: sbtc_wallet_script,
+		}],
+	};
+
+	let sighash = SighashCache::new(&tx)
+		.taproot_script_spend_signature_hash(
+			0,
+			&Prevouts::All(&[TxOut {
+				value: funding_amount,
+				script_pubkey: funding_script,
+			}]),
+			TapLeafHash::from_script(&script, LeafVersion::TapScript),
+			SchnorrSighashType::Default,
+		)
+		.map_err(|_| {
+			SBTCError::MalformedData("Could not compute the signature hash")
+		})?;
+	let message = Message::from_slice(&sighash[..])
+		.map_err(|err| SBTCError::SECPError("Invalid signature hash", err))?;
+
+	let signature = SchnorrSig {
+		sig: secp.sign_schnorr_no_aux_rand(&message, depositor_key),
+		hash_ty: SchnorrSighashType::Default,
+	};
+
+	let mut witness = Witness::new();
+	witness.push(signature.to_vec());
+	witness.push(script);
+	witness.push(control_block.serialize());
+
+	tx.input[0].witness = witness;
+
+	Ok(tx)
+}
+
+/// Parse an OP_DROP deposit from a transaction. The deposit data is taken from
+/// the first input revealing it.
+pub fn parse_deposit(
+	network: Network,
+	tx: Transaction,
+) -> Result<Deposit, DepositParseError> {
+	let deposit_data = tx
+		.input
+		.iter()
+		.find_map(|input| parse_deposit_data(&input.witness))
+		.ok_or(DepositParseError::NotSbtcOp)?;
+
+	if magic_bytes(deposit_data.network) != magic_bytes(network) {
+		return Err(DepositParseError::NotSbtcOp);
+	}
+
+	let amount_output = tx
+		.output
+		.into_iter()
+		.next()
+		.ok_or(DepositParseError::InvalidOutputs)?;
+
+	let sbtc_wallet_address =
+		BitcoinAddress::from_script(&amount_output.script_pubkey, network)?;
+
+	Ok(Deposit {
+		amount: amount_output.value,
+		recipient: deposit_data.recipient,
+		sbtc_wallet_address,
+		network,
+	})
+}
+
+/// The deposit data of a script path spend, which ends with the script and
+/// its control block
+fn parse_deposit_data(witness: &Witness) -> Option<DepositOutputData> {
+	let script = Script::from(witness.second_to_last()?.to_vec());
+	ControlBlock::from_slice(witness.last()?).ok()?;
+
+	let (data, _) = parse_op_drop_script(&script)?;
+
+	DepositOutputData::codec_deserialize(&mut data.as_slice()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{hashes::Hash, Txid};
+	use stacks_core::{
+		address::StacksAddress, contract_name::ContractName,
+		utils::StandardPrincipalData,
+	};
+
+	use super::*;
+
+	fn test_key_pair() -> KeyPair {
+		KeyPair::from_seckey_slice(&Secp256k1::new(), &[1; 32]).unwrap()
+	}
+
+	fn test_recipient() -> PrincipalData {
+		let address: StacksAddress =
+			"ST3RBZ4TZ3EK22SZRKGFZYBCKD7WQ5B8FFRS57TT6"
+				.try_into()
+				.unwrap();
+
+		PrincipalData::Contract(
+			StandardPrincipalData::new(address.version(), address),
+			ContractName::new("a-contract-name-of-forty-characters-long")
+				.unwrap(),
+		)
+	}
+
+	fn test_sbtc_address() -> BitcoinAddress {
+		"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+			.parse()
+			.unwrap()
+	}
+
+	#[test]
+	fn should_build_and_parse_deposit_transaction() {
+		let key_pair = test_key_pair();
+		let funding_output = OutPoint::new(Txid::all_zeros(), 1);
+
+		let tx = build_deposit_transaction(
+			&key_pair,
+			funding_output,
+			135_000,
+			test_recipient(),
+			&test_sbtc_address(),
+			133_742,
+			Network::Testnet,
+		)
+		.unwrap();
+
+		assert_eq!(tx.input[0].previous_output, funding_output);
+
+		let deposit = parse_deposit(Network::Testnet, tx).unwrap();
+
+		assert_eq!(deposit.amount, 133_742);
+		assert_eq!(deposit.recipient, test_recipient());
+		assert_eq!(deposit.sbtc_wallet_address, test_sbtc_address());
+	}
+
+	#[test]
+	fn should_spend_the_deposit_data_address() {
+		let key_pair = test_key_pair();
+		let (public_key, _) = key_pair.x_only_public_key();
+
+		let address = deposit_data_address(
+			test_recipient(),
+			&public_key,
+			Network::Testnet,
+		)
+		.unwrap();
+		let tx = build_deposit_transaction(
+			&key_pair,
+			OutPoint::new(Txid::all_zeros(), 0),
+			135_000,
+			test_recipient(),
+			&test_sbtc_address(),
+			133_742,
+			Network::Testnet,
+		)
+		.unwrap();
+
+		let witness = &tx.input[0].witness;
+		let control_block =
+			ControlBlock::from_slice(witness.last().unwrap()).unwrap();
+		let output_key = match address.payload {
+			bdk::bitcoin::util::address::Payload::WitnessProgram {
+				program,
+				..
+			} => XOnlyPublicKey::from_slice(&program).unwrap(),
+			_ => panic!("Deposit data address should be a taproot address"),
+		};
+
+		assert!(control_block.verify_taproot_commitment(
+			&Secp256k1::verification_only(),
+			output_key,
+			&Script::from(witness.second_to_last().unwrap().to_vec()),
+		));
+	}
+
+	#[test]
+	fn should_not_parse_deposit_of_another_network() {
+		let tx = build_deposit_transaction(
+			&test_key_pair(),
+			OutPoint::new(Txid::all_zeros(), 0),
+			135_000,
+			test_recipient(),
+			&test_sbtc_address(),
+			133_742,
+			Network::Testnet,
+		)
+		.unwrap();
+
+		assert_eq!(
+			parse_deposit(Network::Bitcoin, tx).unwrap_err(),
+			DepositParseError::NotSbtcOp
+		);
+	}
+}
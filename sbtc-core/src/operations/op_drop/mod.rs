@@ -0,0 +1,2 @@
+//! Primitives for sBTC transactions embedding their data with OP_DROP
+pub mod deposit;
@@ -6,18 +6,19 @@
 //! 1. data output
 //! 2. payment to sbtc wallet address
 //!
-//! The data output should contain data in the following byte format:
+//! The data output should contain a versioned
+//! [`payload`](crate::operations::payload) in the following byte format:
 //!
 //! ```text
-//! 0     2  3                                                                    80
-//! |-----|--|---------------------------------------------------------------------|
-//! magic op                           deposit data
+//! 0     2  3       4                                                            80
+//! |-----|--|-------|-------------------------------------------------------------|
+//! magic op version                                    deposit data
 //! ```
 //!
 //! Where deposit data should be in the following format:
 //!
 //! ```text
-//! 3                                                      25 >= N <= 66          80
+//! 4                                                      26 >= N <= 67          80
 //! |------------------------------------------------------------------|-----------|
 //! principal data                              extra
 //! bytes
@@ -34,31 +35,41 @@
 //! Principal data should be in the following format:
 //!
 //! ```text
-//! 3         4         5                25       26                         N <= 66
+//! 4         5         6                26       27                         N <= 67
 //! |---------|---------|-----------------|--------|-------------------------------|
 //! principal  address       address      contract            contract
 //! type     version        hash          name                name
 //! length (N)
 //! ```
-use std::{collections::HashMap, io};
+use std::io;
 
 use bdk::{
 	bitcoin::{
 		blockdata::{opcodes::all::OP_RETURN, script::Instruction},
 		psbt::PartiallySignedTransaction,
-		Address as BitcoinAddress, Network, PrivateKey, Transaction,
+		Address as BitcoinAddress, Network, Script, Transaction,
 	},
-	database::{BatchDatabase, MemoryDatabase},
+	blockchain::{GetHeight, WalletSync},
+	database::BatchDatabase,
 	SignOptions, Wallet,
 };
-use stacks_core::{codec::Codec, utils::PrincipalData};
+use stacks_core::{
+	address::StacksAddress,
+	codec::Codec,
+	crypto::{sha256::Sha256Hasher, Hashing},
+	utils::PrincipalData,
+};
 
 use crate::{
 	operations::{
-		magic_bytes,
 		op_return::utils::{build_op_return_script, reorder_outputs},
-		utils::setup_wallet,
-		Opcode,
+		payload::{NetworkPayload, Payload},
+		transaction_signer::TransactionSigner,
+		utils::{
+			build_offline_psbt, estimate_vsize, finalize_psbt, setup_wallet,
+			OfflineFunding, SatPerVb,
+		},
+		validation::{validate_op_return_data, validate_peg_amount},
 	},
 	SBTCError, SBTCResult,
 };
@@ -71,31 +82,8 @@ pub fn build_deposit_transaction<T: BatchDatabase>(
 	amount: u64,
 	network: Network,
 ) -> SBTCResult<Transaction> {
-	let mut tx_builder = wallet.build_tx();
-
-	let deposit_data =
-		DepositOutputData { network, recipient }.serialize_to_vec();
-	let op_return_script = build_op_return_script(&deposit_data);
-
-	let sbtc_wallet_script = sbtc_address.script_pubkey();
-	let dust_amount = sbtc_wallet_script.dust_value().to_sat();
-
-	if amount < dust_amount {
-		return Err(SBTCError::AmountInsufficient(amount, dust_amount));
-	}
-
-	let outputs = [(op_return_script, 0), (sbtc_wallet_script, amount)];
-
-	for (script, amount) in outputs.clone() {
-		tx_builder.add_recipient(script, amount);
-	}
-
-	let (mut partial_tx, _) = tx_builder.finish().map_err(|err| {
-		SBTCError::BDKError("Could not finish the transaction", err)
-	})?;
-
-	partial_tx.unsigned_tx.output =
-		reorder_outputs(partial_tx.unsigned_tx.output, outputs);
+	let mut partial_tx =
+		create_psbt(&wallet, recipient, &sbtc_address, amount, network)?;
 
 	wallet
 		.sign(&mut partial_tx, SignOptions::default())
@@ -184,121 +172,318 @@ pub enum DepositParseError {
 /// Data for the sBTC OP_RETURN deposit transaction output
 pub struct DepositOutputData {
 	/// Network to be used for the transaction
-	network: Network,
+	pub(crate) network: Network,
 	/// Recipient of the deposit
-	recipient: PrincipalData,
+	pub(crate) recipient: PrincipalData,
 }
 
 impl Codec for DepositOutputData {
 	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
-		dest.write_all(&magic_bytes(self.network))?;
-		dest.write_all(&[Opcode::Deposit as u8])?;
-		self.recipient.codec_serialize(dest)
+		NetworkPayload {
+			network: self.network,
+			payload: Payload::Deposit {
+				recipient: self.recipient.clone(),
+			},
+		}
+		.codec_serialize(dest)
 	}
 
 	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
 	where
 		Self: Sized,
 	{
-		let mut magic_bytes_buffer = [0; 2];
-		data.read_exact(&mut magic_bytes_buffer)?;
+		let NetworkPayload { network, payload } =
+			NetworkPayload::codec_deserialize(data)?;
 
-		let network_magic_bytes = [
-			Network::Bitcoin,
-			Network::Testnet,
-			Network::Signet,
-			Network::Regtest,
-		]
-		.into_iter()
-		.map(|network| (magic_bytes(network), network))
-		.collect::<HashMap<[u8; 2], Network>>();
-
-		let network = network_magic_bytes
-			.get(&magic_bytes_buffer)
-			.cloned()
-			.ok_or(io::Error::new(
-				io::ErrorKind::InvalidData,
-				format!("Unknown magic bytes: {:?}", magic_bytes_buffer),
-			))?;
-
-		let opcode = Opcode::codec_deserialize(data)
-			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-
-		if !matches!(opcode, Opcode::Deposit) {
-			return Err(io::Error::new(
+		match payload {
+			Payload::Deposit { recipient } => Ok(Self { network, recipient }),
+			_ => Err(io::Error::new(
 				io::ErrorKind::InvalidData,
-				format!("Invalid opcode, expected deposit: {:?}", opcode),
-			));
+				"Invalid opcode, expected deposit",
+			)),
 		}
-
-		let recipient = PrincipalData::codec_deserialize(data)?;
-
-		Ok(Self { network, recipient })
 	}
 }
 
-fn create_partially_signed_deposit_transaction(
-	wallet: &Wallet<MemoryDatabase>,
+/// Construct a deposit partially signed transaction with the sBTC outputs
+/// populated, to be signed by the holder of the keys of the wallet. The wallet
+/// may be watch-only.
+pub fn create_psbt<D: BatchDatabase>(
+	wallet: &Wallet<D>,
 	recipient: PrincipalData,
 	sbtc_address: &BitcoinAddress,
 	amount: u64,
 	network: Network,
 ) -> SBTCResult<PartiallySignedTransaction> {
-	let mut tx_builder = wallet.build_tx();
+	let (psbt, _) = create_psbt_with_options(
+		wallet,
+		recipient,
+		sbtc_address,
+		amount,
+		network,
+		&DepositOptions::default(),
+	)?;
 
-	let deposit_data =
-		DepositOutputData { network, recipient }.serialize_to_vec();
-	let op_return_script = build_op_return_script(&deposit_data);
+	Ok(psbt)
+}
+
+/// Fee and change policy of a deposit transaction. The wallet defaults are
+/// used for the unset options.
+#[derive(Debug, Clone, Default)]
+pub struct DepositOptions {
+	/// Fee rate of the transaction
+	pub fee_rate: Option<SatPerVb>,
+	/// Absolute fee of the transaction, exclusive with the fee rate
+	pub fee_absolute: Option<u64>,
+	/// Address receiving the change instead of the wallet
+	pub change_address: Option<BitcoinAddress>,
+	/// Minimum deposit amount, raised to the dust value of the sBTC wallet
+	/// output if lower
+	pub dust_threshold: Option<u64>,
+	/// Sweep the whole wallet into the sBTC wallet address. The deposit amount
+	/// is then whatever remains after the fee, and there is no change.
+	pub drain: bool,
+}
+
+/// Summary of a constructed deposit transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepositSummary {
+	/// Amount paid to the sBTC wallet address
+	pub amount: u64,
+	/// Fee paid by the transaction
+	pub fee: u64,
+	/// Amount returned as change
+	pub change: u64,
+	/// Estimated virtual size of the signed transaction, assuming P2WPKH
+	/// inputs
+	pub vsize: usize,
+}
+
+/// Construct a deposit partially signed transaction with the given fee and
+/// change policy. The amount is ignored in drain mode.
+pub fn create_psbt_with_options<D: BatchDatabase>(
+	wallet: &Wallet<D>,
+	recipient: PrincipalData,
+	sbtc_address: &BitcoinAddress,
+	amount: u64,
+	network: Network,
+	options: &DepositOptions,
+) -> SBTCResult<(PartiallySignedTransaction, DepositSummary)> {
 	let sbtc_wallet_script = sbtc_address.script_pubkey();
-	let dust_amount = sbtc_wallet_script.dust_value().to_sat();
+	let dust_amount = options
+		.dust_threshold
+		.unwrap_or_default()
+		.max(sbtc_wallet_script.dust_value().to_sat());
+
+	let mut tx_builder = wallet.build_tx();
 
-	if amount < dust_amount {
-		return Err(SBTCError::AmountInsufficient(amount, dust_amount));
+	match (options.fee_rate, options.fee_absolute) {
+		(Some(_), Some(_)) => {
+			return Err(SBTCError::MalformedData(
+				"Fee rate and absolute fee are mutually exclusive",
+			))
+		}
+		(Some(fee_rate), None) => {
+			tx_builder.fee_rate(fee_rate.into());
+		}
+		(None, Some(fee)) => {
+			tx_builder.fee_absolute(fee);
+		}
+		(None, None) => {}
 	}
 
-	let outputs = [(op_return_script, 0), (sbtc_wallet_script, amount)];
+	let mut outputs = if options.drain {
+		let op_return = create_data_script(recipient, network)?;
 
-	for (script, amount) in outputs.clone() {
-		tx_builder.add_recipient(script, amount);
-	}
+		tx_builder
+			.add_recipient(op_return.clone(), 0)
+			.drain_wallet()
+			.drain_to(sbtc_wallet_script.clone());
 
-	let (mut partial_tx, _) = tx_builder.finish().map_err(|err| {
+		vec![(op_return, 0)]
+	} else {
+		if amount < dust_amount {
+			return Err(SBTCError::AmountInsufficient(amount, dust_amount));
+		}
+
+		let outputs = create_outputs(recipient, sbtc_address, amount, network)?;
+
+		for (script, amount) in outputs.clone() {
+			tx_builder.add_recipient(script, amount);
+		}
+
+		if let Some(change_address) = &options.change_address {
+			tx_builder.drain_to(change_address.script_pubkey());
+		}
+
+		outputs.to_vec()
+	};
+
+	let (mut partial_tx, details) = tx_builder.finish().map_err(|err| {
 		SBTCError::BDKError(
 			"Could not finish the partially signed transaction",
 			err,
 		)
 	})?;
 
+	if options.drain {
+		let amount = partial_tx
+			.unsigned_tx
+			.output
+			.iter()
+			.find(|output| output.script_pubkey == sbtc_wallet_script)
+			.map(|output| output.value)
+			.unwrap_or_default();
+
+		if amount < dust_amount {
+			return Err(SBTCError::AmountInsufficient(amount, dust_amount));
+		}
+
+		outputs.push((sbtc_wallet_script.clone(), amount));
+	}
+
 	partial_tx.unsigned_tx.output =
-		reorder_outputs(partial_tx.unsigned_tx.output, outputs);
+		reorder_outputs(partial_tx.unsigned_tx.output, outputs.clone());
+
+	let amount = outputs[1].1;
+	let change = partial_tx.unsigned_tx.output[outputs.len()..]
+		.iter()
+		.map(|output| output.value)
+		.sum();
 
-	Ok(partial_tx)
+	let summary = DepositSummary {
+		amount,
+		fee: details.fee.unwrap_or_default(),
+		change,
+		vsize: estimate_vsize(&partial_tx.unsigned_tx),
+	};
+
+	Ok((partial_tx, summary))
 }
 
-/// Construct a BTC transaction containing the provided sBTC deposit data
-pub fn deposit(
-	depositor_private_key: PrivateKey,
+/// Construct an unsigned deposit transaction funded by the UTXOs provided by
+/// the caller, without access to a wallet or blockchain backend
+pub fn create_offline_psbt(
+	funding: &OfflineFunding,
 	recipient: PrincipalData,
+	sbtc_address: &BitcoinAddress,
 	amount: u64,
+	network: Network,
+) -> SBTCResult<PartiallySignedTransaction> {
+	let outputs = create_outputs(recipient, sbtc_address, amount, network)?;
+
+	build_offline_psbt(funding, &outputs)
+}
+
+/// Create the data and sBTC wallet outputs of a deposit transaction
+fn create_outputs(
+	recipient: PrincipalData,
 	sbtc_address: &BitcoinAddress,
-) -> SBTCResult<Transaction> {
-	let wallet = setup_wallet(depositor_private_key)?;
+	amount: u64,
+	network: Network,
+) -> SBTCResult<[(Script, u64); 2]> {
+	let sbtc_wallet_script = sbtc_address.script_pubkey();
 
-	let mut psbt = create_partially_signed_deposit_transaction(
-		&wallet,
-		recipient,
-		sbtc_address,
-		amount,
-		depositor_private_key.network,
-	)?;
+	validate_peg_amount(amount, &sbtc_wallet_script, network)?;
 
-	wallet
-		.sign(&mut psbt, SignOptions::default())
-		.map_err(|err| {
-			SBTCError::BDKError("Could not sign transaction", err)
-		})?;
+	Ok([
+		(create_data_script(recipient, network)?, 0),
+		(sbtc_wallet_script, amount),
+	])
+}
+
+/// Create the OP_RETURN script carrying the deposit data
+fn create_data_script(
+	recipient: PrincipalData,
+	network: Network,
+) -> SBTCResult<Script> {
+	let deposit_data =
+		DepositOutputData { network, recipient }.serialize_to_vec();
+
+	validate_op_return_data(&deposit_data)?;
+
+	Ok(build_op_return_script(&deposit_data))
+}
 
-	Ok(psbt.extract_tx())
+/// Builds a well-formed deposit transaction spending a dummy input, to be used
+/// as a fixture in tests
+#[cfg(any(test, feature = "test-utils"))]
+pub fn make_test_deposit_tx(
+	recipient: &StacksAddress,
+	amount: u64,
+	peg_wallet: &bdk::bitcoin::Script,
+	network: Network,
+) -> Transaction {
+	use bdk::bitcoin::{OutPoint, PackedLockTime, Sequence, TxIn, TxOut};
+
+	let deposit_data = DepositOutputData {
+		network,
+		recipient: recipient.clone().into(),
+	}
+	.serialize_to_vec();
+
+	Transaction {
+		version: 2,
+		lock_time: PackedLockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: OutPoint::null(),
+			sequence: Sequence::MAX,
+			..Default::default()
+		}],
+		output: vec![
+			TxOut {
+				value: 0,
+				script_pubkey: build_op_return_script(&deposit_data),
+			},
+			TxOut {
+				value: amount,
+				script_pubkey: peg_wallet.clone(),
+			},
+		],
+	}
+}
+
+/// Derives a deterministic identifier for an off-chain deposit request. The id
+/// is the SHA256 of the 21 byte recipient address followed by the big-endian
+/// amount and nonce, so it does not depend on the eventual txid.
+pub fn deposit_request_id(
+	recipient: &StacksAddress,
+	amount: u64,
+	nonce: u64,
+) -> [u8; 32] {
+	let mut data = recipient.serialize_to_vec();
+	data.extend_from_slice(&amount.to_be_bytes());
+	data.extend_from_slice(&nonce.to_be_bytes());
+
+	Sha256Hasher::new(data)
+		.as_bytes()
+		.try_into()
+		.expect("SHA256 hash should be 32 bytes")
+}
+
+/// Construct a BTC transaction containing the provided sBTC deposit data,
+/// funded by the P2WPKH outputs of the key of the signer found on the
+/// blockchain. Callers without access to a blockchain backend can build the
+/// PSBT of their own wallet with [`create_psbt`] instead.
+pub fn deposit<B>(
+	signer: &impl TransactionSigner,
+	blockchain: &B,
+	recipient: PrincipalData,
+	amount: u64,
+	sbtc_address: &BitcoinAddress,
+	network: Network,
+) -> SBTCResult<Transaction>
+where
+	B: WalletSync + GetHeight,
+{
+	let wallet = setup_wallet(signer.public_key(), network, blockchain)?;
+
+	let mut psbt =
+		create_psbt(&wallet, recipient, sbtc_address, amount, network)?;
+
+	signer.sign_psbt(&mut psbt)?;
+
+	finalize_psbt(psbt)
 }
 
 #[cfg(test)]
@@ -408,7 +593,7 @@ mod tests {
 
 		let assertions = [
             DepositParseScenario {
-                given_tx_hex: "010000000001019131d69f4616c2a17f3d2519a3dc697136a56846794e677982f565f79295e0370100000000feffffff0300000000000000001b6a1954323c051af0bf935f1ba62167f89c1fff2d9369f972ad0f7e6e0a020000000000225120b85fdda4ae0f69883280360a9b91555a2f23c5b9e34173fabec5d903416c2aaf7b850800000000001600147c969cfcab0d2ad171aa3f201c94b51b0e8eca6602473044022036663b723c79333f9c8b7d5d9db3b6cd301fc6bf82515e62303713eb69b4d18d0220548939af6e1d86fcf8a54da1f6942f25f36ed0488a0d3616c47daa49f59bc7b601210215bd6d522931e602fde924571eb472bc1db953484b29ba6542774ebbf083412329c62500",
+                given_tx_hex: "010000000001019131d69f4616c2a17f3d2519a3dc697136a56846794e677982f565f79295e0370100000000feffffff0300000000000000001c6a1a54323c01051af0bf935f1ba62167f89c1fff2d9369f972ad0f7e6e0a020000000000225120b85fdda4ae0f69883280360a9b91555a2f23c5b9e34173fabec5d903416c2aaf7b850800000000001600147c969cfcab0d2ad171aa3f201c94b51b0e8eca6602473044022036663b723c79333f9c8b7d5d9db3b6cd301fc6bf82515e62303713eb69b4d18d0220548939af6e1d86fcf8a54da1f6942f25f36ed0488a0d3616c47daa49f59bc7b601210215bd6d522931e602fde924571eb472bc1db953484b29ba6542774ebbf083412329c62500",
                 expected_amount: 133742,
                 expected_recipient: recipient.clone(),
             }
@@ -419,6 +604,321 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn deposit_parse_should_succeed_given_a_test_deposit_transaction() {
+		let mut rng = test_rng();
+		let recipient = generate_address(&mut rng);
+		let peg_wallet: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+
+		let tx = make_test_deposit_tx(
+			&recipient,
+			133742,
+			&peg_wallet.script_pubkey(),
+			Network::Testnet,
+		);
+		let deposit = Deposit::parse(Network::Testnet, tx).unwrap();
+
+		assert_eq!(deposit.amount, 133742);
+		assert_eq!(deposit.recipient, recipient.into());
+		assert_eq!(deposit.sbtc_wallet_address, peg_wallet);
+	}
+
+	#[test]
+	fn should_sign_deposit_psbt_of_watch_only_wallet_externally() {
+		use bdk::{
+			bitcoin::{
+				OutPoint, PackedLockTime, PrivateKey, PublicKey, TxIn, TxOut,
+			},
+			database::{BatchOperations, MemoryDatabase},
+			KeychainKind, LocalUtxo, TransactionDetails,
+		};
+
+		use crate::operations::utils::finalize_psbt;
+
+		let secp = Secp256k1::new();
+		let private_key = PrivateKey::new(
+			bdk::bitcoin::secp256k1::SecretKey::from_slice(&[5; 32]).unwrap(),
+			Network::Testnet,
+		);
+		let public_key = PublicKey::from_private_key(&secp, &private_key);
+		let funding_script =
+			BitcoinAddress::p2wpkh(&public_key, Network::Testnet)
+				.unwrap()
+				.script_pubkey();
+
+		let funding_tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![TxIn::default()],
+			output: vec![TxOut {
+				value: 100_000,
+				script_pubkey: funding_script.clone(),
+			}],
+		};
+
+		let mut database = MemoryDatabase::new();
+		database
+			.set_script_pubkey(&funding_script, KeychainKind::External, 0)
+			.unwrap();
+		database.set_last_index(KeychainKind::External, 0).unwrap();
+		database
+			.set_utxo(&LocalUtxo {
+				outpoint: OutPoint::new(funding_tx.txid(), 0),
+				txout: funding_tx.output[0].clone(),
+				keychain: KeychainKind::External,
+				is_spent: false,
+			})
+			.unwrap();
+		database.set_raw_tx(&funding_tx).unwrap();
+		database
+			.set_tx(&TransactionDetails {
+				transaction: Some(funding_tx.clone()),
+				txid: funding_tx.txid(),
+				received: 100_000,
+				sent: 0,
+				fee: None,
+				confirmation_time: None,
+			})
+			.unwrap();
+
+		// Only knows the public key of the depositor
+		let watch_only_wallet = Wallet::new(
+			&format!("wpkh({})", public_key),
+			None,
+			Network::Testnet,
+			database,
+		)
+		.unwrap();
+		let signer_wallet = Wallet::new(
+			&format!("wpkh({})", private_key),
+			None,
+			Network::Testnet,
+			MemoryDatabase::new(),
+		)
+		.unwrap();
+
+		let recipient = generate_address(&mut test_rng());
+		let peg_wallet: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+
+		let mut psbt = create_psbt(
+			&watch_only_wallet,
+			recipient.clone().into(),
+			&peg_wallet,
+			10_000,
+			Network::Testnet,
+		)
+		.unwrap();
+
+		assert!(finalize_psbt(psbt.clone()).is_err());
+
+		signer_wallet
+			.sign(
+				&mut psbt,
+				SignOptions {
+					try_finalize: false,
+					..Default::default()
+				},
+			)
+			.unwrap();
+
+		let tx = finalize_psbt(psbt).unwrap();
+
+		assert!(!tx.input[0].witness.is_empty());
+
+		let deposit = Deposit::parse(Network::Testnet, tx).unwrap();
+
+		assert_eq!(deposit.amount, 10_000);
+		assert_eq!(deposit.recipient, recipient.into());
+		assert_eq!(deposit.sbtc_wallet_address, peg_wallet);
+	}
+
+	#[test]
+	fn should_sign_offline_deposit_psbt() {
+		use bdk::{
+			bitcoin::{hashes::Hash, OutPoint, PrivateKey, TxOut, Txid},
+			FeeRate, KeychainKind, LocalUtxo,
+		};
+
+		use crate::operations::transaction_signer::TransactionSigner;
+
+		let private_key = PrivateKey::new(
+			bdk::bitcoin::secp256k1::SecretKey::from_slice(&[5; 32]).unwrap(),
+			Network::Testnet,
+		);
+		let change_address = BitcoinAddress::p2wpkh(
+			&TransactionSigner::public_key(&private_key),
+			Network::Testnet,
+		)
+		.unwrap();
+		let funding = OfflineFunding {
+			utxos: vec![LocalUtxo {
+				outpoint: OutPoint::new(Txid::all_zeros(), 0),
+				txout: TxOut {
+					value: 100_000,
+					script_pubkey: change_address.script_pubkey(),
+				},
+				keychain: KeychainKind::External,
+				is_spent: false,
+			}],
+			change_address,
+			fee_rate: FeeRate::from_sat_per_vb(2.0),
+		};
+
+		let recipient = generate_address(&mut test_rng());
+		let peg_wallet: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+
+		let mut psbt = create_offline_psbt(
+			&funding,
+			recipient.clone().into(),
+			&peg_wallet,
+			10_000,
+			Network::Testnet,
+		)
+		.unwrap();
+
+		private_key.sign_psbt(&mut psbt).unwrap();
+
+		let deposit =
+			Deposit::parse(Network::Testnet, finalize_psbt(psbt).unwrap())
+				.unwrap();
+
+		assert_eq!(deposit.amount, 10_000);
+		assert_eq!(deposit.recipient, recipient.into());
+		assert_eq!(deposit.sbtc_wallet_address, peg_wallet);
+	}
+
+	fn funded_wallet() -> Wallet<bdk::database::AnyDatabase> {
+		let (wallet, _, _) = bdk::wallet::get_funded_wallet(
+			"wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+		);
+
+		wallet
+	}
+
+	fn test_peg_wallet() -> BitcoinAddress {
+		"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+			.parse()
+			.unwrap()
+	}
+
+	#[test]
+	fn should_create_psbt_with_absolute_fee_and_change_address() {
+		let change_address: BitcoinAddress =
+			"tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms"
+				.parse()
+				.unwrap();
+
+		let (psbt, summary) = create_psbt_with_options(
+			&funded_wallet(),
+			generate_address(&mut test_rng()).into(),
+			&test_peg_wallet(),
+			10_000,
+			Network::Testnet,
+			&DepositOptions {
+				fee_absolute: Some(1_000),
+				change_address: Some(change_address.clone()),
+				..Default::default()
+			},
+		)
+		.unwrap();
+
+		let outputs = &psbt.unsigned_tx.output;
+
+		assert_eq!(summary.amount, 10_000);
+		assert_eq!(summary.fee, 1_000);
+		assert_eq!(summary.change, 50_000 - 10_000 - 1_000);
+		assert!(summary.vsize > psbt.unsigned_tx.vsize());
+		assert_eq!(outputs[1].script_pubkey, test_peg_wallet().script_pubkey());
+		assert_eq!(outputs[2].script_pubkey, change_address.script_pubkey());
+	}
+
+	#[test]
+	fn should_drain_wallet_into_peg_address() {
+		let recipient = generate_address(&mut test_rng());
+
+		let (psbt, summary) = create_psbt_with_options(
+			&funded_wallet(),
+			recipient.clone().into(),
+			&test_peg_wallet(),
+			0,
+			Network::Testnet,
+			&DepositOptions {
+				fee_rate: Some("2 sat/vB".parse().unwrap()),
+				drain: true,
+				..Default::default()
+			},
+		)
+		.unwrap();
+
+		assert_eq!(summary.change, 0);
+		assert_eq!(summary.amount + summary.fee, 50_000);
+		assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+		let deposit =
+			Deposit::parse(Network::Testnet, psbt.unsigned_tx).unwrap();
+
+		assert_eq!(deposit.amount, summary.amount);
+		assert_eq!(deposit.recipient, recipient.into());
+	}
+
+	#[test]
+	fn should_not_create_psbt_below_dust_threshold() {
+		let result = create_psbt_with_options(
+			&funded_wallet(),
+			generate_address(&mut test_rng()).into(),
+			&test_peg_wallet(),
+			10_000,
+			Network::Testnet,
+			&DepositOptions {
+				dust_threshold: Some(20_000),
+				..Default::default()
+			},
+		);
+
+		assert!(matches!(
+			result,
+			Err(SBTCError::AmountInsufficient(10_000, 20_000))
+		));
+	}
+
+	#[test]
+	fn should_not_create_psbt_with_fee_rate_and_absolute_fee() {
+		let result = create_psbt_with_options(
+			&funded_wallet(),
+			generate_address(&mut test_rng()).into(),
+			&test_peg_wallet(),
+			10_000,
+			Network::Testnet,
+			&DepositOptions {
+				fee_rate: Some("2 sat/vB".parse().unwrap()),
+				fee_absolute: Some(1_000),
+				..Default::default()
+			},
+		);
+
+		assert!(matches!(result, Err(SBTCError::MalformedData(_))));
+	}
+
+	#[test]
+	fn deposit_request_id_should_be_stable_and_bound_to_amount() {
+		let mut rng = test_rng();
+		let recipient = generate_address(&mut rng);
+
+		let id = deposit_request_id(&recipient, 1000, 7);
+
+		assert_eq!(id, deposit_request_id(&recipient, 1000, 7));
+		assert_ne!(id, deposit_request_id(&recipient, 1001, 7));
+	}
+
 	struct DepositParseScenario {
 		given_tx_hex: &'static str,
 		expected_amount: u64,
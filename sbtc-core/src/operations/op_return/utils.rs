@@ -3,9 +3,46 @@
 use std::collections::{BTreeMap, HashMap};
 
 use bdk::bitcoin::{
-	blockdata::{opcodes::all::OP_RETURN, script::Builder},
-	Script, TxOut,
+	blockdata::{
+		opcodes::all::OP_RETURN,
+		script::{Builder, Instruction},
+	},
+	Network, Script, Transaction, TxOut,
 };
+use stacks_core::codec::Codec;
+
+use crate::{
+	operations::{
+		magic_bytes,
+		op_return::{
+			deposit::DepositOutputData,
+			wallet_handoff::WalletHandoffOutputData,
+			withdrawal_fulfillment::ParsedWithdrawalFulfillmentData,
+			withdrawal_request::WithdrawalRequestDataOutputData,
+		},
+		Opcode,
+	},
+	SBTCError, SBTCResult,
+};
+
+/// sBTC OP_RETURN data recognized by the decoder
+pub enum ParsedOpReturn {
+	/// Deposit data
+	Deposit(DepositOutputData),
+	/// Withdrawal request data
+	WithdrawalRequest(WithdrawalRequestDataOutputData),
+	/// Withdrawal fulfillment data
+	WithdrawalFulfillment(ParsedWithdrawalFulfillmentData),
+	/// Wallet handoff data
+	WalletHandoff(WalletHandoffOutputData),
+	/// sBTC data with an operation the decoder does not support
+	Unknown {
+		/// The operation byte
+		op_type: u8,
+		/// The data following the operation byte
+		payload: Vec<u8>,
+	},
+}
 
 /// Builds an OP_RETURN script from the provided data
 pub(crate) fn build_op_return_script(data: &[u8]) -> Script {
@@ -40,3 +77,153 @@ pub fn reorder_outputs(
 
 	outputs_ordered.into_values().collect()
 }
+
+/// Checks whether any output of the transaction is an OP_RETURN starting with
+/// the sBTC magic bytes of the network. This is a cheap pre-filter and does not
+/// validate the rest of the data.
+pub fn is_sbtc_transaction(tx: &Transaction, network: Network) -> bool {
+	let magic_bytes = magic_bytes(network);
+
+	tx.output.iter().any(|output| {
+		let mut instructions = output.script_pubkey.instructions();
+
+		matches!(
+			(instructions.next(), instructions.next()),
+			(
+				Some(Ok(Instruction::Op(OP_RETURN))),
+				Some(Ok(Instruction::PushBytes(data)))
+			) if data.starts_with(&magic_bytes)
+		)
+	})
+}
+
+/// Parses an sBTC OP_RETURN script. Returns `None` if the script is not an
+/// OP_RETURN carrying the sBTC magic bytes of the network.
+pub fn parse_op_return(
+	script: &Script,
+	network: Network,
+) -> SBTCResult<Option<ParsedOpReturn>> {
+	let mut instructions = script.instructions();
+
+	let (
+		Some(Ok(Instruction::Op(OP_RETURN))),
+		Some(Ok(Instruction::PushBytes(data))),
+	) = (instructions.next(), instructions.next())
+	else {
+		return Ok(None);
+	};
+
+	let Some(op_data) = data.strip_prefix(&magic_bytes(network)) else {
+		return Ok(None);
+	};
+
+	let (&op_type, payload) = op_data
+		.split_first()
+		.ok_or(SBTCError::MalformedData("Missing sBTC operation byte"))?;
+
+	let parsed = match Opcode::from_repr(op_type) {
+		Some(Opcode::Deposit) => ParsedOpReturn::Deposit(
+			DepositOutputData::deserialize(&mut &data[..])?,
+		),
+		Some(Opcode::WithdrawalRequest) => ParsedOpReturn::WithdrawalRequest(
+			WithdrawalRequestDataOutputData::deserialize(&mut &data[..])?,
+		),
+		Some(Opcode::WithdrawalFulfillment) => {
+			ParsedOpReturn::WithdrawalFulfillment(
+				ParsedWithdrawalFulfillmentData::deserialize(&mut &data[..])?,
+			)
+		}
+		Some(Opcode::WalletHandoff) => ParsedOpReturn::WalletHandoff(
+			WalletHandoffOutputData::deserialize(&mut &data[..])?,
+		),
+		None => ParsedOpReturn::Unknown {
+			op_type,
+			payload: payload.to_vec(),
+		},
+	};
+
+	Ok(Some(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{Address as BitcoinAddress, PackedLockTime};
+	use stacks_core::address::{AddressVersion, StacksAddress};
+
+	use super::*;
+	use crate::operations::op_return::deposit::make_test_deposit_tx;
+
+	fn deposit_tx(network: Network) -> Transaction {
+		let peg_wallet: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+		let recipient = StacksAddress::new(
+			AddressVersion::TestnetSingleSig,
+			Default::default(),
+		);
+
+		make_test_deposit_tx(
+			&recipient,
+			1000,
+			&peg_wallet.script_pubkey(),
+			network,
+		)
+	}
+
+	#[test]
+	fn should_detect_sbtc_transaction() {
+		let tx = deposit_tx(Network::Testnet);
+
+		assert!(is_sbtc_transaction(&tx, Network::Testnet));
+		assert!(!is_sbtc_transaction(&tx, Network::Bitcoin));
+	}
+
+	#[test]
+	fn should_not_detect_unrelated_op_return() {
+		let tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut {
+				value: 0,
+				script_pubkey: build_op_return_script(b"hello world"),
+			}],
+		};
+
+		assert!(!is_sbtc_transaction(&tx, Network::Testnet));
+	}
+
+	#[test]
+	fn should_parse_deposit_op_return() {
+		let tx = deposit_tx(Network::Testnet);
+
+		assert!(matches!(
+			parse_op_return(&tx.output[0].script_pubkey, Network::Testnet),
+			Ok(Some(ParsedOpReturn::Deposit(_)))
+		));
+	}
+
+	#[test]
+	fn should_parse_unknown_sbtc_op_return() {
+		let data = [b'T', b'2', b'?', 1, 2, 3];
+		let script = build_op_return_script(&data);
+
+		let parsed = parse_op_return(&script, Network::Testnet).unwrap();
+
+		assert!(matches!(
+			parsed,
+			Some(ParsedOpReturn::Unknown { op_type: b'?', payload })
+				if payload == [1, 2, 3]
+		));
+	}
+
+	#[test]
+	fn should_ignore_non_sbtc_op_return() {
+		let script = build_op_return_script(b"hello world");
+
+		assert!(parse_op_return(&script, Network::Testnet)
+			.unwrap()
+			.is_none());
+	}
+}
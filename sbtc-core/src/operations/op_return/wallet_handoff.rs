@@ -0,0 +1,297 @@
+//! Tools for the construction and parsing of the sBTC OP_RETURN wallet handoff
+//! transactions, which move the UTXO set of the sBTC wallet to the taproot
+//! address of a new signer set.
+//!
+//! Its output structure is as below:
+//!
+//! 1. data output
+//! 2. taproot address of the new sBTC wallet, receiving all the funds
+//!
+//! The data output should contain a versioned
+//! [`payload`](crate::operations::payload) in the following byte format:
+//!
+//! ```text
+//! 0     2  3       4
+//! |-----|--|-------|
+//! magic op version
+//! ```
+//!
+//! The wallet handoff payload carries no data: the new sBTC wallet is the
+//! address of the second output.
+use std::io;
+
+use bdk::{
+	bitcoin::{
+		blockdata::{opcodes::all::OP_RETURN, script::Instruction},
+		psbt::PartiallySignedTransaction,
+		util::address::AddressType,
+		Address as BitcoinAddress, Network as BitcoinNetwork, Script,
+		Transaction,
+	},
+	database::BatchDatabase,
+	FeeRate, SignOptions, Wallet,
+};
+use stacks_core::codec::Codec;
+
+use crate::{
+	operations::{
+		magic_bytes,
+		op_return::utils::{build_op_return_script, reorder_outputs},
+		payload::{NetworkPayload, Payload},
+	},
+	SBTCError, SBTCResult,
+};
+
+/// Wallet handoff transaction data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletHandoffData {
+	/// The taproot address of the new sBTC wallet
+	pub sbtc_wallet: BitcoinAddress,
+	/// How much is moved to the new sBTC wallet
+	pub amount: u64,
+}
+
+/// Parses a Bitcoin transaction into a wallet handoff
+pub fn parse(
+	tx: &Transaction,
+	network: BitcoinNetwork,
+) -> SBTCResult<WalletHandoffData> {
+	let mut output_iter = tx.output.iter();
+
+	let data_output = output_iter.next().ok_or(SBTCError::NotSBTCOperation)?;
+
+	let mut instructions_iter = data_output.script_pubkey.instructions();
+
+	let Some(Ok(Instruction::Op(OP_RETURN))) = instructions_iter.next() else {
+		return Err(SBTCError::NotSBTCOperation);
+	};
+
+	let Some(Ok(Instruction::PushBytes(mut data))) = instructions_iter.next()
+	else {
+		return Err(SBTCError::NotSBTCOperation);
+	};
+
+	let handoff_data = WalletHandoffOutputData::codec_deserialize(&mut data)
+		.map_err(|_| SBTCError::NotSBTCOperation)?;
+
+	if magic_bytes(handoff_data.network) != magic_bytes(network) {
+		return Err(SBTCError::NotSBTCOperation);
+	}
+
+	let sbtc_wallet_output =
+		output_iter.next().ok_or(SBTCError::NotSBTCOperation)?;
+
+	let sbtc_wallet =
+		BitcoinAddress::from_script(&sbtc_wallet_output.script_pubkey, network)
+			.map_err(|_| SBTCError::NotSBTCOperation)?;
+
+	if sbtc_wallet.address_type() != Some(AddressType::P2tr) {
+		return Err(SBTCError::NotSBTCOperation);
+	}
+
+	Ok(WalletHandoffData {
+		sbtc_wallet,
+		amount: sbtc_wallet_output.value,
+	})
+}
+
+/// Construct a wallet handoff transaction, signed by the wallet of the current
+/// signer set
+pub fn build_wallet_handoff_tx(
+	wallet: &Wallet<impl BatchDatabase>,
+	bitcoin_network: BitcoinNetwork,
+	new_sbtc_wallet_address: &BitcoinAddress,
+	fee_rate: FeeRate,
+) -> SBTCResult<Transaction> {
+	let mut psbt = create_psbt(
+		wallet,
+		bitcoin_network,
+		new_sbtc_wallet_address,
+		fee_rate,
+	)?;
+
+	wallet
+		.sign(&mut psbt, SignOptions::default())
+		.map_err(|err| {
+			SBTCError::BDKError(
+				"Could not sign wallet handoff transaction",
+				err,
+			)
+		})?;
+
+	Ok(psbt.extract_tx())
+}
+
+/// Construct a wallet handoff partially signed transaction, spending every
+/// UTXO of the wallet to the new sBTC wallet
+pub fn create_psbt<D: BatchDatabase>(
+	wallet: &Wallet<D>,
+	bitcoin_network: BitcoinNetwork,
+	new_sbtc_wallet_address: &BitcoinAddress,
+	fee_rate: FeeRate,
+) -> SBTCResult<PartiallySignedTransaction> {
+	let data_output = create_data_output(bitcoin_network);
+	let new_sbtc_wallet_script =
+		create_sbtc_wallet_script(new_sbtc_wallet_address)?;
+
+	let mut tx_builder = wallet.build_tx();
+
+	tx_builder
+		.add_recipient(data_output.0.clone(), data_output.1)
+		.drain_wallet()
+		.drain_to(new_sbtc_wallet_script)
+		.fee_rate(fee_rate);
+
+	let (mut partial_tx, _) = tx_builder.finish().map_err(|err| {
+		SBTCError::BDKError(
+			"Could not build partially signed wallet handoff transaction",
+			err,
+		)
+	})?;
+
+	// The drained output is not in the order, so it follows the data output
+	partial_tx.unsigned_tx.output =
+		reorder_outputs(partial_tx.unsigned_tx.output, [data_output]);
+
+	Ok(partial_tx)
+}
+
+/// Create the data output of a wallet handoff transaction
+pub fn create_data_output(bitcoin_network: BitcoinNetwork) -> (Script, u64) {
+	let data = WalletHandoffOutputData {
+		network: bitcoin_network,
+	};
+
+	(build_op_return_script(&data.serialize_to_vec()), 0)
+}
+
+fn create_sbtc_wallet_script(
+	new_sbtc_wallet_address: &BitcoinAddress,
+) -> SBTCResult<Script> {
+	if new_sbtc_wallet_address.address_type() != Some(AddressType::P2tr) {
+		return Err(SBTCError::MalformedData(
+			"New sBTC wallet address should be a taproot address",
+		));
+	}
+
+	Ok(new_sbtc_wallet_address.script_pubkey())
+}
+
+/// Data output for a wallet handoff transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletHandoffOutputData {
+	/// The Bitcoin network
+	pub network: BitcoinNetwork,
+}
+
+impl Codec for WalletHandoffOutputData {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		NetworkPayload {
+			network: self.network,
+			payload: Payload::WalletHandoff,
+		}
+		.codec_serialize(dest)
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let NetworkPayload { network, payload } =
+			NetworkPayload::codec_deserialize(data)?;
+
+		match payload {
+			Payload::WalletHandoff => Ok(Self { network }),
+			_ => Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"Invalid opcode, expected wallet handoff",
+			)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::{
+		bitcoin::secp256k1::{Secp256k1, SecretKey},
+		psbt::PsbtUtils,
+		wallet::{get_funded_wallet, AddressIndex},
+	};
+
+	use super::*;
+
+	fn new_sbtc_wallet_address() -> BitcoinAddress {
+		let secp = Secp256k1::new();
+		let key = SecretKey::from_slice(&[1; 32]).unwrap();
+
+		BitcoinAddress::p2tr(
+			&secp,
+			key.x_only_public_key(&secp).0,
+			None,
+			BitcoinNetwork::Regtest,
+		)
+	}
+
+	#[test]
+	fn should_move_all_funds_to_the_new_wallet() {
+		let (wallet, _, _) = get_funded_wallet(
+			"wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+		);
+		let new_sbtc_wallet = new_sbtc_wallet_address();
+
+		let psbt = create_psbt(
+			&wallet,
+			BitcoinNetwork::Regtest,
+			&new_sbtc_wallet,
+			FeeRate::from_sat_per_vb(1.0),
+		)
+		.unwrap();
+
+		assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+		let handoff =
+			parse(&psbt.unsigned_tx, BitcoinNetwork::Regtest).unwrap();
+		let fee = psbt.fee_amount().unwrap();
+
+		assert_eq!(handoff.sbtc_wallet, new_sbtc_wallet);
+		assert_eq!(handoff.amount + fee, 50_000);
+	}
+
+	#[test]
+	fn should_reject_non_taproot_wallet() {
+		let (wallet, _, _) = get_funded_wallet(
+			"wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+		);
+		let address = wallet.get_address(AddressIndex::New).unwrap().address;
+
+		assert!(matches!(
+			create_psbt(
+				&wallet,
+				BitcoinNetwork::Regtest,
+				&address,
+				FeeRate::from_sat_per_vb(1.0),
+			),
+			Err(SBTCError::MalformedData(_))
+		));
+	}
+
+	#[test]
+	fn should_not_parse_handoff_of_another_network() {
+		let (wallet, _, _) = get_funded_wallet(
+			"wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+		);
+
+		let psbt = create_psbt(
+			&wallet,
+			BitcoinNetwork::Regtest,
+			&new_sbtc_wallet_address(),
+			FeeRate::from_sat_per_vb(1.0),
+		)
+		.unwrap();
+
+		assert!(matches!(
+			parse(&psbt.unsigned_tx, BitcoinNetwork::Testnet),
+			Err(SBTCError::NotSBTCOperation)
+		));
+	}
+}
@@ -4,12 +4,13 @@
 //! 1. data output
 //! 2. Bitcoin address to send the BTC to
 //!
-//! The data output should contain data in the following byte format:
+//! The data output should contain a versioned
+//! [`payload`](crate::operations::payload) in the following byte format:
 //!
 //! ```text
-//! 0     2  3                                                                    80
-//! |-----|--|---------------------------------------------------------------------|
-//! magic op                      withdrawal fulfillment data
+//! 0     2  3       4                                                            80
+//! |-----|--|-------|-------------------------------------------------------------|
+//! magic op version                             withdrawal fulfillment data
 //! ```
 //!
 //! Where withdrawal fulfillment data should be in the following format:
@@ -19,12 +20,14 @@
 //! |------------------------------------------------------------------------------|
 //! chain tip
 
-use std::{collections::HashMap, io};
+use std::io;
 
 use bdk::{
 	bitcoin::{
-		psbt::PartiallySignedTransaction, Address as BitcoinAddress,
-		Network as BitcoinNetwork, Script, Transaction,
+		blockdata::{opcodes::all::OP_RETURN, script::Instruction},
+		psbt::PartiallySignedTransaction,
+		Address as BitcoinAddress, Network as BitcoinNetwork, OutPoint, Script,
+		Transaction,
 	},
 	database::BatchDatabase,
 	SignOptions, Wallet,
@@ -34,11 +37,22 @@ use stacks_core::{codec::Codec, BlockId};
 use super::utils::reorder_outputs;
 use crate::{
 	operations::{
-		magic_bytes, op_return::utils::build_op_return_script, Opcode,
+		magic_bytes,
+		op_return::utils::build_op_return_script,
+		payload::{NetworkPayload, Payload},
 	},
 	SBTCError, SBTCResult,
 };
 
+/// Explicit input selection for a withdrawal fulfillment transaction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputSelection {
+	/// UTXOs that must be spent by the transaction
+	pub utxos: Vec<OutPoint>,
+	/// Whether the transaction may only spend the given UTXOs
+	pub manually_selected_only: bool,
+}
+
 /// Construct a withdrawal fulfillment transaction
 pub fn build_withdrawal_fulfillment_tx(
 	wallet: &Wallet<impl BatchDatabase>,
@@ -46,6 +60,7 @@ pub fn build_withdrawal_fulfillment_tx(
 	bitcoin_network: BitcoinNetwork,
 	recipient_bitcoin_address: &BitcoinAddress,
 	amount: u64,
+	input_selection: &InputSelection,
 ) -> SBTCResult<Transaction> {
 	let mut psbt = create_psbt(
 		wallet,
@@ -53,6 +68,7 @@ pub fn build_withdrawal_fulfillment_tx(
 		bitcoin_network,
 		recipient_bitcoin_address,
 		amount,
+		input_selection,
 	)?;
 
 	wallet
@@ -71,6 +87,7 @@ pub fn create_psbt<D: BatchDatabase>(
 	bitcoin_network: BitcoinNetwork,
 	recipient_bitcoin_address: &BitcoinAddress,
 	amount: u64,
+	input_selection: &InputSelection,
 ) -> SBTCResult<PartiallySignedTransaction> {
 	let outputs = create_outputs(
 		stacks_chain_tip,
@@ -81,6 +98,14 @@ pub fn create_psbt<D: BatchDatabase>(
 
 	let mut tx_builder = wallet.build_tx();
 
+	tx_builder
+		.add_utxos(&input_selection.utxos)
+		.map_err(|err| SBTCError::BDKError("Could not add UTXOs", err))?;
+
+	if input_selection.manually_selected_only {
+		tx_builder.manually_selected_only();
+	}
+
 	for (script, amount) in outputs.clone() {
 		tx_builder.add_recipient(script, amount);
 	}
@@ -116,6 +141,62 @@ pub fn create_outputs(
 	Ok([(data_script, 0), (recipient_script, amount)])
 }
 
+/// Validates that the outputs of a withdrawal fulfillment transaction are in
+/// the order defined by the protocol: the sBTC data output first, followed by
+/// the recipient payment and optional change outputs.
+pub fn validate_fulfillment_output_order(
+	tx: &Transaction,
+	network: BitcoinNetwork,
+) -> SBTCResult<()> {
+	let mut output_iter = tx.output.iter();
+
+	let data_output = output_iter.next().ok_or(
+		SBTCError::InvalidOutputOrder("Missing the sBTC data output"),
+	)?;
+
+	let mut instructions_iter = data_output.script_pubkey.instructions();
+
+	let Some(Ok(Instruction::Op(OP_RETURN))) = instructions_iter.next() else {
+		return Err(SBTCError::InvalidOutputOrder(
+			"First output is not an OP_RETURN output",
+		));
+	};
+
+	let Some(Ok(Instruction::PushBytes(mut data))) = instructions_iter.next()
+	else {
+		return Err(SBTCError::InvalidOutputOrder(
+			"First output does not contain sBTC data",
+		));
+	};
+
+	let fulfillment_data = ParsedWithdrawalFulfillmentData::codec_deserialize(
+		&mut data,
+	)
+	.map_err(|_| {
+		SBTCError::InvalidOutputOrder(
+			"First output does not contain withdrawal fulfillment data",
+		)
+	})?;
+
+	if magic_bytes(fulfillment_data.network) != magic_bytes(network) {
+		return Err(SBTCError::InvalidOutputOrder(
+			"First output has magic bytes of a different network",
+		));
+	}
+
+	output_iter.next().ok_or(SBTCError::InvalidOutputOrder(
+		"Missing the recipient output",
+	))?;
+
+	if output_iter.any(|output| output.script_pubkey.is_op_return()) {
+		return Err(SBTCError::InvalidOutputOrder(
+			"Only the first output can be an OP_RETURN output",
+		));
+	}
+
+	Ok(())
+}
+
 /// Data output for a withdrawal fulfillment transaction
 pub struct ParsedWithdrawalFulfillmentData {
 	/// The Bitcoin network
@@ -127,51 +208,131 @@ pub struct ParsedWithdrawalFulfillmentData {
 
 impl Codec for ParsedWithdrawalFulfillmentData {
 	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
-		dest.write_all(&magic_bytes(self.network))?;
-		dest.write_all(&[Opcode::WithdrawalFulfillment as u8])?;
-		self.chain_tip.codec_serialize(dest)
+		NetworkPayload {
+			network: self.network,
+			payload: Payload::WithdrawalFulfillment {
+				chain_tip: self.chain_tip,
+			},
+		}
+		.codec_serialize(dest)
 	}
 
 	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
 	where
 		Self: Sized,
 	{
-		let mut magic_bytes_buffer = [0; 2];
-		data.read_exact(&mut magic_bytes_buffer)?;
+		let NetworkPayload { network, payload } =
+			NetworkPayload::codec_deserialize(data)?;
 
-		let network_magic_bytes = [
-			BitcoinNetwork::Bitcoin,
-			BitcoinNetwork::Testnet,
-			BitcoinNetwork::Signet,
-			BitcoinNetwork::Regtest,
-		]
-		.into_iter()
-		.map(|network| (magic_bytes(network), network))
-		.collect::<HashMap<[u8; 2], BitcoinNetwork>>();
-
-		let network = network_magic_bytes
-			.get(&magic_bytes_buffer)
-			.cloned()
-			.ok_or(io::Error::new(
+		match payload {
+			Payload::WithdrawalFulfillment { chain_tip } => {
+				Ok(Self { network, chain_tip })
+			}
+			_ => Err(io::Error::new(
 				io::ErrorKind::InvalidData,
-				format!("Unknown magic bytes: {:?}", magic_bytes_buffer),
-			))?;
+				"Invalid opcode, expected withdrawal fulfillment",
+			)),
+		}
+	}
+}
 
-		let opcode = Opcode::codec_deserialize(data)
-			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+#[cfg(test)]
+mod tests {
+	use bdk::{
+		bitcoin::{PackedLockTime, TxOut},
+		wallet::{get_funded_wallet, AddressIndex},
+	};
+	use stacks_core::uint::Uint256;
 
-		if !matches!(opcode, Opcode::WithdrawalFulfillment) {
-			return Err(io::Error::new(
-				io::ErrorKind::InvalidData,
-				format!(
-					"Invalid opcode, expected withdrawal fulfillment: {:?}",
-					opcode
-				),
-			));
+	use super::*;
+
+	fn fulfillment_tx(network: BitcoinNetwork) -> Transaction {
+		let recipient: BitcoinAddress =
+			"tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms"
+				.parse()
+				.unwrap();
+
+		let outputs = create_outputs(
+			BlockId::new(Uint256::from(1337u64)),
+			network,
+			&recipient,
+			1000,
+		)
+		.unwrap();
+
+		Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: outputs
+				.into_iter()
+				.map(|(script_pubkey, value)| TxOut {
+					value,
+					script_pubkey,
+				})
+				.collect(),
 		}
+	}
+
+	#[test]
+	fn should_accept_correctly_ordered_fulfillment_outputs() {
+		let tx = fulfillment_tx(BitcoinNetwork::Testnet);
 
-		let chain_tip = BlockId::codec_deserialize(data)?;
+		validate_fulfillment_output_order(&tx, BitcoinNetwork::Testnet)
+			.unwrap();
+	}
+
+	#[test]
+	fn should_reject_fulfillment_with_misplaced_op_return() {
+		let mut tx = fulfillment_tx(BitcoinNetwork::Testnet);
+		tx.output.swap(0, 1);
+
+		assert!(matches!(
+			validate_fulfillment_output_order(&tx, BitcoinNetwork::Testnet),
+			Err(SBTCError::InvalidOutputOrder(_))
+		));
+	}
+
+	#[test]
+	fn should_reject_fulfillment_for_another_network() {
+		let tx = fulfillment_tx(BitcoinNetwork::Testnet);
 
-		Ok(Self { network, chain_tip })
+		assert!(matches!(
+			validate_fulfillment_output_order(&tx, BitcoinNetwork::Bitcoin),
+			Err(SBTCError::InvalidOutputOrder(_))
+		));
+	}
+
+	#[test]
+	fn should_spend_manually_selected_utxo() {
+		let (wallet, _, txid) = get_funded_wallet(
+			"wpkh(cVpPVruEDdmutPzisEsYvtST1usBR3ntr8pXSyt6D2YYqXRyPcFW)",
+		);
+		let recipient = wallet.get_address(AddressIndex::New).unwrap().address;
+		let utxo = OutPoint { txid, vout: 0 };
+
+		let psbt = create_psbt(
+			&wallet,
+			BlockId::new(Uint256::from(1337u64)),
+			BitcoinNetwork::Regtest,
+			&recipient,
+			10_000,
+			&InputSelection {
+				utxos: vec![utxo],
+				manually_selected_only: true,
+			},
+		)
+		.unwrap();
+
+		assert!(psbt
+			.unsigned_tx
+			.input
+			.iter()
+			.any(|input| input.previous_output == utxo));
+		validate_fulfillment_output_order(
+			&psbt.unsigned_tx,
+			BitcoinNetwork::Regtest,
+		)
+		.unwrap();
 	}
 }
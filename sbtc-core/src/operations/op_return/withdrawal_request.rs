@@ -17,12 +17,13 @@
 //! 2. Bitcoin address to send the BTC to
 //! 3. Fulfillment fee payment to the sbtc wallet
 //!
-//! The data output should contain data in the following byte format:
+//! The data output should contain a versioned
+//! [`payload`](crate::operations::payload) in the following byte format:
 //!
 //! ```text
-//! 0     2  3                                                                    80
-//! |-----|--|---------------------------------------------------------------------|
-//! magic op                       withdrawal request data
+//! 0     2  3       4                                                            80
+//! |-----|--|-------|-------------------------------------------------------------|
+//! magic op version                               withdrawal request data
 //! ```
 //!
 //! Where withdrawal request data should be in the following format:
@@ -66,7 +67,7 @@
 //!
 //! It is also by convention that we always produce a P2PKH Stacks address from
 //! the recovered public key.
-use std::{collections::HashMap, io, iter};
+use std::{io, iter};
 
 use bdk::{
 	bitcoin::{
@@ -92,7 +93,11 @@ use crate::{
 	operations::{
 		magic_bytes,
 		op_return::utils::{build_op_return_script, reorder_outputs},
-		Opcode,
+		payload::{NetworkPayload, Payload},
+		validation::{
+			validate_op_return_data, validate_output_amount,
+			validate_peg_amount,
+		},
 	},
 	SBTCError, SBTCResult,
 };
@@ -100,12 +105,14 @@ use crate::{
 /// Signature prefix used by convention
 pub const STACKS_SIGNATURE_PREFIX: &[u8] = b"Stacks Signed Message:\n";
 
-/// Tries to parse a Bitcoin transation into a withdrawal request
-pub fn try_parse_withdrawal_request(
+/// Parses a Bitcoin transaction into a withdrawal request. The drawee is the
+/// P2PKH Stacks address of the key recovered from the signature, which the
+/// signature is then verified against.
+pub fn parse(
+	tx: &Transaction,
 	network: BitcoinNetwork,
-	tx: Transaction,
 ) -> SBTCResult<WithdrawalRequestData> {
-	let mut output_iter = tx.output.into_iter();
+	let mut output_iter = tx.output.iter();
 
 	let data_output = output_iter.next().ok_or(SBTCError::NotSBTCOperation)?;
 
@@ -124,6 +131,10 @@ pub fn try_parse_withdrawal_request(
 		WithdrawalRequestDataOutputData::codec_deserialize(&mut data)
 			.map_err(|_| SBTCError::NotSBTCOperation)?;
 
+	if magic_bytes(withdrawal_data.network()) != magic_bytes(network) {
+		return Err(SBTCError::NotSBTCOperation);
+	}
+
 	let recipient_pubkey_output =
 		output_iter.next().ok_or(SBTCError::NotSBTCOperation)?;
 
@@ -138,6 +149,12 @@ pub fn try_parse_withdrawal_request(
 		&recipient_address,
 		&withdrawal_data.signature(),
 	)?;
+	verify_signature(
+		withdrawal_data.amount(),
+		&recipient_address,
+		&withdrawal_data.signature(),
+		&drawee_stacks_public_key,
+	)?;
 	let drawee_stacks_address_version = match network {
 		BitcoinNetwork::Bitcoin => StacksAddressVersion::MainnetSingleSig,
 		_ => StacksAddressVersion::TestnetSingleSig,
@@ -167,6 +184,7 @@ pub fn try_parse_withdrawal_request(
 }
 
 /// Withdrawal request transaction data
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WithdrawalRequestData {
 	/// Where to send the withdrawn BTC
 	pub payee_bitcoin_address: BitcoinAddress,
@@ -221,9 +239,39 @@ pub fn create_psbt<D: BatchDatabase>(
 	fulfillment_amount: u64,
 	network: BitcoinNetwork,
 ) -> SBTCResult<PartiallySignedTransaction> {
-	let outputs = create_outputs(
+	let signature = create_signature(
 		drawee_stacks_private_key,
 		payee_bitcoin_address,
+		amount,
+	);
+
+	create_psbt_with_signature(
+		wallet,
+		signature,
+		payee_bitcoin_address,
+		sbtc_wallet_bitcoin_address,
+		amount,
+		fulfillment_amount,
+		network,
+	)
+}
+
+/// Construct a withdrawal request partially signed transaction from a
+/// signature of the drawee produced elsewhere, such as by a Stacks wallet
+/// signing the message of [`create_withdrawal_request_signing_message`]. The
+/// wallet funding the transaction may be watch-only.
+pub fn create_psbt_with_signature<D: BatchDatabase>(
+	wallet: &Wallet<D>,
+	signature: RecoverableSignature,
+	payee_bitcoin_address: &BitcoinAddress,
+	sbtc_wallet_bitcoin_address: &BitcoinAddress,
+	amount: u64,
+	fulfillment_amount: u64,
+	network: BitcoinNetwork,
+) -> SBTCResult<PartiallySignedTransaction> {
+	let outputs = create_outputs_with_signature(
+		signature,
+		payee_bitcoin_address,
 		sbtc_wallet_bitcoin_address,
 		amount,
 		fulfillment_amount,
@@ -257,30 +305,51 @@ pub fn create_outputs(
 	amount: u64,
 	fulfillment_amount: u64,
 	network: BitcoinNetwork,
+) -> SBTCResult<[(Script, u64); 3]> {
+	let signature = create_signature(
+		drawee_stacks_private_key,
+		payee_bitcoin_address,
+		amount,
+	);
+
+	create_outputs_with_signature(
+		signature,
+		payee_bitcoin_address,
+		sbtc_wallet_bitcoin_address,
+		amount,
+		fulfillment_amount,
+		network,
+	)
+}
+
+/// Generates the outputs for the withdrawal request transaction from a
+/// signature of the drawee produced elsewhere
+pub fn create_outputs_with_signature(
+	signature: RecoverableSignature,
+	payee_bitcoin_address: &BitcoinAddress,
+	sbtc_wallet_bitcoin_address: &BitcoinAddress,
+	amount: u64,
+	fulfillment_amount: u64,
+	network: BitcoinNetwork,
 ) -> SBTCResult<[(Script, u64); 3]> {
 	let recipient_script = payee_bitcoin_address.script_pubkey();
 	let sbtc_wallet_script = sbtc_wallet_bitcoin_address.script_pubkey();
 
-	// Check that we have enough to cover dust
 	let recipient_dust_amount = recipient_script.dust_value().to_sat();
-	let sbtc_wallet_dust_amount = sbtc_wallet_script.dust_value().to_sat();
 
-	if fulfillment_amount < sbtc_wallet_dust_amount {
-		return Err(SBTCError::AmountInsufficient(
-			fulfillment_amount,
-			sbtc_wallet_dust_amount,
-		));
+	validate_peg_amount(amount, &recipient_script, network)?;
+	validate_output_amount(fulfillment_amount, &sbtc_wallet_script)?;
+
+	let data = WithdrawalRequestDataOutputData {
+		network,
+		amount,
+		signature,
 	}
+	.serialize_to_vec();
 
-	let op_return_script = build_op_return_script(
-		&WithdrawalRequestDataOutputData::new(
-			payee_bitcoin_address,
-			drawee_stacks_private_key,
-			amount,
-			network,
-		)
-		.serialize_to_vec(),
-	);
+	validate_op_return_data(&data)?;
+
+	let op_return_script = build_op_return_script(&data);
 
 	let outputs = [
 		(op_return_script, 0),
@@ -341,59 +410,34 @@ impl WithdrawalRequestDataOutputData {
 
 impl Codec for WithdrawalRequestDataOutputData {
 	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
-		dest.write_all(&magic_bytes(self.network))?;
-		dest.write_all(&[Opcode::WithdrawalRequest as u8])?;
-		self.amount.codec_serialize(dest)?;
-		self.signature.codec_serialize(dest)
+		NetworkPayload {
+			network: self.network,
+			payload: Payload::WithdrawalRequest {
+				amount: self.amount,
+				signature: self.signature,
+			},
+		}
+		.codec_serialize(dest)
 	}
 
 	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
 	where
 		Self: Sized,
 	{
-		let mut magic_bytes_buffer = [0; 2];
-		data.read_exact(&mut magic_bytes_buffer)?;
-
-		let network_magic_bytes = [
-			BitcoinNetwork::Bitcoin,
-			BitcoinNetwork::Testnet,
-			BitcoinNetwork::Signet,
-			BitcoinNetwork::Regtest,
-		]
-		.into_iter()
-		.map(|network| (magic_bytes(network), network))
-		.collect::<HashMap<[u8; 2], BitcoinNetwork>>();
-
-		let network = network_magic_bytes
-			.get(&magic_bytes_buffer)
-			.cloned()
-			.ok_or(io::Error::new(
+		let NetworkPayload { network, payload } =
+			NetworkPayload::codec_deserialize(data)?;
+
+		match payload {
+			Payload::WithdrawalRequest { amount, signature } => Ok(Self {
+				network,
+				amount,
+				signature,
+			}),
+			_ => Err(io::Error::new(
 				io::ErrorKind::InvalidData,
-				format!("Unknown magic bytes: {:?}", magic_bytes_buffer),
-			))?;
-
-		let opcode = Opcode::codec_deserialize(data)
-			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-
-		if !matches!(opcode, Opcode::WithdrawalRequest) {
-			return Err(io::Error::new(
-				io::ErrorKind::InvalidData,
-				format!(
-					"Invalid opcode, expected withdrawal request: {:?}",
-					opcode
-				),
-			));
+				"Invalid opcode, expected withdrawal request",
+			)),
 		}
-
-		let amount = u64::codec_deserialize(data)?;
-		let signature = RecoverableSignature::codec_deserialize(data)
-			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-
-		Ok(Self {
-			network,
-			amount,
-			signature,
-		})
 	}
 }
 
@@ -432,6 +476,30 @@ pub fn recover_signature(
 		})
 }
 
+/// Verifies the signature of the withdrawal request against the drawee key.
+/// Unlike recovery, this rejects malleated signatures with a high S value.
+pub fn verify_signature(
+	amount: u64,
+	payee_bitcoin_address: &BitcoinAddress,
+	signature: &RecoverableSignature,
+	drawee_stacks_public_key: &StacksPublicKey,
+) -> SBTCResult<()> {
+	let signing_msg = create_withdrawal_request_signing_message(
+		amount,
+		payee_bitcoin_address,
+	);
+
+	Secp256k1::verification_only()
+		.verify_ecdsa(
+			&signing_msg,
+			&signature.to_standard(),
+			drawee_stacks_public_key,
+		)
+		.map_err(|err| {
+			SBTCError::SECPError("Invalid withdrawal request signature", err)
+		})
+}
+
 /// Creates the SECP signing message for the withdrawal request
 pub fn create_withdrawal_request_signing_message(
 	amount: u64,
@@ -471,8 +539,110 @@ pub fn create_signing_message(data: impl AsRef<[u8]>) -> Message {
 // test that create signing message returns correct hash
 #[cfg(test)]
 mod tests {
+	use bdk::bitcoin::{PackedLockTime, TxOut};
+
 	use super::*;
 
+	fn test_private_key() -> StacksPrivateKey {
+		StacksPrivateKey::from_slice(&[3; 32]).unwrap()
+	}
+
+	fn test_withdrawal_tx(network: BitcoinNetwork) -> Transaction {
+		let payee: BitcoinAddress =
+			"tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms"
+				.parse()
+				.unwrap();
+		let sbtc_wallet: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+
+		let outputs = create_outputs(
+			&test_private_key(),
+			&payee,
+			&sbtc_wallet,
+			1000,
+			5000,
+			network,
+		)
+		.unwrap();
+
+		Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![],
+			output: outputs
+				.into_iter()
+				.map(|(script_pubkey, value)| TxOut {
+					value,
+					script_pubkey,
+				})
+				.collect(),
+		}
+	}
+
+	#[test]
+	fn should_parse_withdrawal_request() {
+		let tx = test_withdrawal_tx(BitcoinNetwork::Testnet);
+
+		let withdrawal = parse(&tx, BitcoinNetwork::Testnet).unwrap();
+
+		let drawee_public_key = StacksPublicKey::from_secret_key(
+			&Secp256k1::new(),
+			&test_private_key(),
+		);
+
+		assert_eq!(withdrawal.amount, 1000);
+		assert_eq!(withdrawal.fulfillment_amount, 5000);
+		assert_eq!(
+			withdrawal.payee_bitcoin_address.to_string(),
+			"tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms"
+		);
+		assert_eq!(
+			withdrawal.drawee_stacks_address,
+			StacksAddress::from_public_key(
+				StacksAddressVersion::TestnetSingleSig,
+				&drawee_public_key,
+			)
+		);
+		assert!(verify_signature(
+			withdrawal.amount,
+			&withdrawal.payee_bitcoin_address,
+			&withdrawal.signature,
+			&drawee_public_key,
+		)
+		.is_ok());
+	}
+
+	#[test]
+	fn should_not_verify_signature_of_another_amount() {
+		let tx = test_withdrawal_tx(BitcoinNetwork::Testnet);
+		let withdrawal = parse(&tx, BitcoinNetwork::Testnet).unwrap();
+
+		let drawee_public_key = StacksPublicKey::from_secret_key(
+			&Secp256k1::new(),
+			&test_private_key(),
+		);
+
+		assert!(verify_signature(
+			withdrawal.amount + 1,
+			&withdrawal.payee_bitcoin_address,
+			&withdrawal.signature,
+			&drawee_public_key,
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn should_not_parse_withdrawal_request_of_another_network() {
+		let tx = test_withdrawal_tx(BitcoinNetwork::Testnet);
+
+		assert!(matches!(
+			parse(&tx, BitcoinNetwork::Bitcoin),
+			Err(SBTCError::NotSBTCOperation)
+		));
+	}
+
 	#[test]
 	fn test_create_signing_message() {
 		let address: BitcoinAddress =
@@ -0,0 +1,224 @@
+//! Versioned binary codec of the sBTC payloads, shared by all the schemes
+//! embedding them in Bitcoin transactions.
+//!
+//! A payload is encoded as:
+//!
+//! ```text
+//! 0     2  3       4                                                             N
+//! |-----|--|-------|-------------------------------------------------------------|
+//! magic op version                          payload data
+//! ```
+//!
+//! The magic bytes are omitted by the schemes carrying them elsewhere in the
+//! transaction, such as commit reveal. Fields are only ever added to the
+//! payload data in a new version, and the decoder keeps reading the previous
+//! versions.
+use std::io;
+
+use bdk::bitcoin::{secp256k1::ecdsa::RecoverableSignature, Network};
+use stacks_core::{codec::Codec, utils::PrincipalData, BlockId};
+
+use crate::operations::{magic_bytes, Opcode};
+
+/// Version of the payload data written by this library
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// Data of an sBTC operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload {
+	/// Deposit
+	Deposit {
+		/// Recipient of the deposit
+		recipient: PrincipalData,
+	},
+	/// Withdrawal request
+	WithdrawalRequest {
+		/// Amount to withdraw
+		amount: u64,
+		/// Signature of the withdrawal request amount and recipient address
+		signature: RecoverableSignature,
+	},
+	/// Withdrawal fulfillment
+	WithdrawalFulfillment {
+		/// The chain tip block ID
+		chain_tip: BlockId,
+	},
+	/// Wallet handoff
+	WalletHandoff,
+}
+
+impl Payload {
+	/// The opcode of the operation
+	pub fn opcode(&self) -> Opcode {
+		match self {
+			Self::Deposit { .. } => Opcode::Deposit,
+			Self::WithdrawalRequest { .. } => Opcode::WithdrawalRequest,
+			Self::WithdrawalFulfillment { .. } => Opcode::WithdrawalFulfillment,
+			Self::WalletHandoff => Opcode::WalletHandoff,
+		}
+	}
+}
+
+impl Codec for Payload {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		self.opcode().codec_serialize(dest)?;
+		dest.write_all(&[PAYLOAD_VERSION])?;
+
+		match self {
+			Self::Deposit { recipient } => recipient.codec_serialize(dest),
+			Self::WithdrawalRequest { amount, signature } => {
+				amount.codec_serialize(dest)?;
+				signature.codec_serialize(dest)
+			}
+			Self::WithdrawalFulfillment { chain_tip } => {
+				chain_tip.codec_serialize(dest)
+			}
+			Self::WalletHandoff => Ok(()),
+		}
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let opcode = Opcode::codec_deserialize(data)?;
+
+		let mut version = [0; 1];
+		data.read_exact(&mut version)?;
+
+		if version[0] != PAYLOAD_VERSION {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("Unsupported payload version: {}", version[0]),
+			));
+		}
+
+		Ok(match opcode {
+			Opcode::Deposit => Self::Deposit {
+				recipient: PrincipalData::codec_deserialize(data)?,
+			},
+			Opcode::WithdrawalRequest => Self::WithdrawalRequest {
+				amount: u64::codec_deserialize(data)?,
+				signature: RecoverableSignature::codec_deserialize(data)
+					.map_err(|err| {
+						io::Error::new(io::ErrorKind::InvalidData, err)
+					})?,
+			},
+			Opcode::WithdrawalFulfillment => Self::WithdrawalFulfillment {
+				chain_tip: BlockId::codec_deserialize(data)?,
+			},
+			Opcode::WalletHandoff => Self::WalletHandoff,
+		})
+	}
+}
+
+/// Payload prefixed with the magic bytes of the network it is meant for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkPayload {
+	/// Bitcoin network of the transaction
+	pub network: Network,
+	/// Data of the operation
+	pub payload: Payload,
+}
+
+impl Codec for NetworkPayload {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		dest.write_all(&magic_bytes(self.network))?;
+		self.payload.codec_serialize(dest)
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let mut magic_bytes_buffer = [0; 2];
+		data.read_exact(&mut magic_bytes_buffer)?;
+
+		// Signet and regtest share their magic bytes, which are read as regtest
+		let network = [
+			Network::Bitcoin,
+			Network::Testnet,
+			Network::Signet,
+			Network::Regtest,
+		]
+		.into_iter()
+		.rev()
+		.find(|network| magic_bytes(*network) == magic_bytes_buffer)
+		.ok_or(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("Unknown magic bytes: {:?}", magic_bytes_buffer),
+		))?;
+
+		let payload = Payload::codec_deserialize(data)?;
+
+		Ok(Self { network, payload })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+	use stacks_core::{address::StacksAddress, uint::Uint256};
+
+	use super::*;
+
+	fn test_payloads() -> Vec<Payload> {
+		let recipient: StacksAddress =
+			"ST3RBZ4TZ3EK22SZRKGFZYBCKD7WQ5B8FFRS57TT6"
+				.try_into()
+				.unwrap();
+		let signature = Secp256k1::new().sign_ecdsa_recoverable(
+			&Message::from_slice(&[7; 32]).unwrap(),
+			&SecretKey::from_slice(&[3; 32]).unwrap(),
+		);
+
+		vec![
+			Payload::Deposit {
+				recipient: recipient.into(),
+			},
+			Payload::WithdrawalRequest {
+				amount: 1000,
+				signature,
+			},
+			Payload::WithdrawalFulfillment {
+				chain_tip: BlockId::new(Uint256::from(1337u64)),
+			},
+			Payload::WalletHandoff,
+		]
+	}
+
+	#[test]
+	fn should_serialize_and_deserialize_payloads() {
+		for payload in test_payloads() {
+			let network_payload = NetworkPayload {
+				network: Network::Testnet,
+				payload,
+			};
+
+			let serialized = network_payload.serialize_to_vec();
+
+			assert_eq!(&serialized[..2], b"T2");
+			assert_eq!(serialized[2], network_payload.payload.opcode() as u8);
+			assert_eq!(serialized[3], PAYLOAD_VERSION);
+			assert_eq!(
+				NetworkPayload::deserialize(&mut serialized.as_slice())
+					.unwrap(),
+				network_payload
+			);
+		}
+	}
+
+	#[test]
+	fn should_not_deserialize_unsupported_version() {
+		let mut serialized = NetworkPayload {
+			network: Network::Testnet,
+			payload: Payload::WalletHandoff,
+		}
+		.serialize_to_vec();
+		serialized[3] = PAYLOAD_VERSION + 1;
+
+		assert!(
+			NetworkPayload::deserialize(&mut serialized.as_slice()).is_err()
+		);
+	}
+}
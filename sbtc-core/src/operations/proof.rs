@@ -0,0 +1,387 @@
+//! SPV proofs that a transaction was mined in a Bitcoin block, in the layout
+//! verified by the Clarity contracts of the protocol
+
+use bdk::bitcoin::{
+	consensus::encode::serialize, Block, BlockHash, BlockHeader, Txid,
+};
+use stacks_core::{
+	clarity::Value,
+	crypto::{sha256::DoubleSha256Hasher, Hashing},
+};
+
+use crate::{SBTCError, SBTCResult};
+
+/// Maximum number of hashes of a merkle proof accepted by the contracts
+pub const MAX_MERKLE_PROOF_DEPTH: usize = 14;
+
+fn double_sha256(data: &[u8]) -> [u8; 32] {
+	DoubleSha256Hasher::hash(data)
+		.as_bytes()
+		.try_into()
+		.expect("Double SHA256 hashes are 32 bytes long")
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+	let mut preimage = [0u8; 64];
+	preimage[..32].copy_from_slice(left);
+	preimage[32..].copy_from_slice(right);
+
+	double_sha256(&preimage)
+}
+
+/// Merkle tree of the txids of a Bitcoin block
+#[derive(Debug, Clone)]
+pub struct BitcoinMerkleTree {
+	data: Vec<Vec<[u8; 32]>>,
+}
+
+impl BitcoinMerkleTree {
+	/// Make a new merkle tree out of the given txids
+	pub fn new(txids: &[Txid]) -> Self {
+		if txids.is_empty() {
+			return Self { data: vec![] };
+		}
+
+		let mut row: Vec<[u8; 32]> = txids
+			.iter()
+			.map(|txid| txid.to_vec().try_into().unwrap())
+			.collect();
+		let mut data = vec![];
+
+		// The txid of a block with a single transaction is its merkle root
+		while row.len() > 1 {
+			// Every row below the root must have an even number of nodes
+			if row.len() % 2 == 1 {
+				row.push(*row.last().unwrap());
+			}
+
+			let next_row = row
+				.chunks(2)
+				.map(|pair| hash_pair(&pair[0], &pair[1]))
+				.collect::<Vec<_>>();
+
+			data.push(row);
+			row = next_row;
+		}
+
+		data.push(row);
+
+		Self { data }
+	}
+
+	/// Get the merkle root, or None if the tree is empty
+	pub fn root(&self) -> Option<[u8; 32]> {
+		self.data.last().map(|root_row| root_row[0])
+	}
+
+	/// Calculate the merkle proof of the transaction at the index. The ith
+	/// bit of the index tells which sibling is used at the ith level of the
+	/// tree: the right one if it is 0, the left one if it is 1.
+	pub fn proof(&self, mut index: usize) -> Option<Vec<[u8; 32]>> {
+		if index >= self.data.first()?.len() {
+			return None;
+		}
+
+		let proof = self.data[..self.data.len() - 1]
+			.iter()
+			.map(|row| {
+				let sibling = row[index ^ 1];
+				index >>= 1;
+
+				sibling
+			})
+			.collect();
+
+		Some(proof)
+	}
+
+	/// Calculate the tree depth, including the leaves
+	pub fn depth(&self) -> usize {
+		self.data.len()
+	}
+}
+
+/// Proof that a transaction was mined in a Bitcoin block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionProof {
+	/// ID of the transaction
+	pub txid: Txid,
+	/// Index of the transaction in the block
+	pub tx_index: u32,
+	/// Height of the block
+	pub block_height: u64,
+	/// Header of the block
+	pub block_header: BlockHeader,
+	/// Hashes linking the txid to the merkle root of the block
+	pub merkle_path: Vec<[u8; 32]>,
+	/// Headers of the blocks built on top of the block, proving its
+	/// confirmations
+	pub header_chain: Vec<BlockHeader>,
+}
+
+impl TransactionProof {
+	/// Create the proof of the transaction at the index of the block. The
+	/// block height is read from the coinbase of the block.
+	pub fn new(block: &Block, tx_index: usize) -> SBTCResult<Self> {
+		let block_height = block.bip34_block_height().map_err(|_| {
+			SBTCError::MalformedData("Block does not commit to its height")
+		})?;
+
+		Self::with_height(block, tx_index, block_height)
+	}
+
+	/// Create the proof of the transaction at the index of the block, at the
+	/// given height
+	pub fn with_height(
+		block: &Block,
+		tx_index: usize,
+		block_height: u64,
+	) -> SBTCResult<Self> {
+		let tx = block
+			.txdata
+			.get(tx_index)
+			.ok_or(SBTCError::MalformedData("Invalid tx index"))?;
+
+		let txids: Vec<Txid> =
+			block.txdata.iter().map(|tx| tx.txid()).collect();
+		let merkle_path = BitcoinMerkleTree::new(&txids)
+			.proof(tx_index)
+			.ok_or(SBTCError::MalformedData("Invalid tx index"))?;
+
+		if merkle_path.len() > MAX_MERKLE_PROOF_DEPTH {
+			return Err(SBTCError::MalformedData(
+				"Merkle proof is deeper than accepted by the contracts",
+			));
+		}
+
+		Ok(Self {
+			txid: tx.txid(),
+			tx_index: tx_index as u32,
+			block_height,
+			block_header: block.header,
+			merkle_path,
+			header_chain: vec![],
+		})
+	}
+
+	/// Attach the headers of the blocks built on top of the block, in
+	/// ascending order of height. Fails if they do not form a chain.
+	pub fn with_header_chain(
+		mut self,
+		header_chain: Vec<BlockHeader>,
+	) -> SBTCResult<Self> {
+		let mut prev_blockhash = self.block_header.block_hash();
+
+		for header in &header_chain {
+			if header.prev_blockhash != prev_blockhash {
+				return Err(SBTCError::MalformedData(
+					"Headers do not form a chain",
+				));
+			}
+
+			prev_blockhash = header.block_hash();
+		}
+
+		self.header_chain = header_chain;
+
+		Ok(self)
+	}
+
+	/// Hash of the block of the transaction
+	pub fn block_hash(&self) -> BlockHash {
+		self.block_header.block_hash()
+	}
+
+	/// Number of confirmations proven by the header chain, counting the
+	/// block of the transaction
+	pub fn confirmations(&self) -> usize {
+		self.header_chain.len() + 1
+	}
+
+	/// Txid in big endian, as displayed by explorers and expected by the
+	/// contracts
+	pub fn txid_bytes(&self) -> [u8; 32] {
+		let mut txid: [u8; 32] = self.txid.to_vec().try_into().unwrap();
+		txid.reverse();
+
+		txid
+	}
+
+	/// Verify the proof the same way as the contracts: the merkle path links
+	/// the txid to the merkle root of the header. The path is empty for the
+	/// only transaction of a block.
+	pub fn verify(&self) -> bool {
+		let mut index = self.tx_index;
+		let mut hash: [u8; 32] = self.txid.to_vec().try_into().unwrap();
+
+		for sibling in &self.merkle_path {
+			hash = if index & 1 == 0 {
+				hash_pair(&hash, sibling)
+			} else {
+				hash_pair(sibling, &hash)
+			};
+			index >>= 1;
+		}
+
+		hash[..] == self.block_header.merkle_root[..]
+	}
+
+	/// Serialize the proof to the tuple consumed by `was-txid-mined` of the
+	/// clarity-bitcoin contract
+	pub fn to_clarity_tuple(&self) -> SBTCResult<Value> {
+		let proof = Value::tuple([
+			("tx-index", Value::UInt(self.tx_index as u128)),
+			("hashes", self.merkle_path_value()),
+		])?;
+
+		Ok(Value::tuple([
+			("height", Value::UInt(self.block_height as u128)),
+			("txid", Value::Buffer(self.txid_bytes().to_vec())),
+			(
+				"header",
+				Value::Buffer(serialize_header(&self.block_header)),
+			),
+			("proof", proof),
+		])?)
+	}
+
+	/// Serialize the merkle path to a list of 32 byte buffers
+	pub fn merkle_path_value(&self) -> Value {
+		Value::List(
+			self.merkle_path
+				.iter()
+				.map(|hash| Value::Buffer(hash.to_vec()))
+				.collect(),
+		)
+	}
+
+	/// Serialize the header chain to a list of 80 byte buffers
+	pub fn header_chain_value(&self) -> Value {
+		Value::List(
+			self.header_chain
+				.iter()
+				.map(|header| Value::Buffer(serialize_header(header)))
+				.collect(),
+		)
+	}
+}
+
+/// Serialize a block header to the 80 bytes hashed into its block hash
+pub fn serialize_header(header: &BlockHeader) -> Vec<u8> {
+	serialize(header)
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{consensus::deserialize, hashes::hex::FromHex};
+
+	use super::*;
+
+	// Testnet block 100,000
+	const SINGLE_TX_BLOCK: &str = "0200000035ab154183570282ce9afc0b494c9fc6a3cfea05aa8c1add2ecc56490000000038ba3d78e4500a5a7570dbe61960398add4410d278b21cd9708e6d9743f374d544fc055227f1001c29c1ea3b0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff3703a08601000427f1001c046a510100522cfabe6d6d0000000000000000000068692066726f6d20706f6f6c7365727665726aac1eeeed88ffffffff0100f2052a010000001976a914912e2b234f941f30b18afbb4fa46171214bf66c888ac00000000";
+
+	// Regtest block 3538, holding an sBTC deposit
+	const REGTEST_BLOCK: &str = "000000205214e3b1be1007826f4537f7d86d8f890104587beae37af2fb17e31195a62325bb8940196d4479391e3460fcc904963da6726ecbb99cb9dfc3705ad9ba748f2182270865ffff7f200000000003020000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff0402d20d00ffffffff029f470000000000001976a914ee9369fb719c0ba43ddf4d94638a970b84775f4788ac0000000000000000266a24aa21a9ed2bec0280b5488f0dd3cca56932fdc3eb7b7fba766f6819a0de1cfeaf74c61ecb0120000000000000000000000000000000000000000000000000000000000000000000000000010000000131bde99ad6d1edb8a0f25b7f8458e605e6c1725217346757e7c9a4c4365ef634030000006b483045022100af3c5c67e972b3c744309e79476d71b8c408ce1e45891b3f4d0a9b8bb76c3a8f0220132e8850d7573747a83ccd0b969f12056b8e960a7e6556f389a87b9be218fc2301210239810ebf35e6f6c26062c99f3e183708d377720617c90a986859ec9c95d00be9fdffffff040000000000000000536a4c5069645b76f4413c41080e57ba4b01a485dc7d2465051bfbd2c97f419ddace3e993f88be7b621278694299a79abc623dd56d071f01245e8648e141bfec88d9ba3b1deef100000dd1000100000ab900014a10270000000000001976a914000000000000000000000000000000000000000088ac10270000000000001976a914000000000000000000000000000000000000000088ac82b0c524010000001976a914ee9369fb719c0ba43ddf4d94638a970b84775f4788ac0000000001000000000101010da73321be48f30562e44ff379ea981e204a4fa4bc859c6cd99418e705c7390000000000feffffff0300000000000000001b6a1969643c051a6d78de7b0625dfbfc16c3a8a5735f6dc3dc3f2cee8030000000000002251205e682db7c014ab76f2b4fdcbbdb76f9b8111468174cdb159df6e88fe9d078ce6ab040000000000001600148ae4a48cb0c3b7874460a6f5287d9dd512a182460247304402206387c555478eb821311ef4d3b125a8b4beb698be624e186ff6234f6cd1deb75702207cf063c9cd57dcd7c34b9477129a3a70403856a46be7b9e8942d79482b246379012103ab37f5b606931d7828855affe75199d952bc6174b4a23861b7ac94132210508cc10d0000";
+
+	fn block(block_hex: &str) -> Block {
+		deserialize(&Vec::<u8>::from_hex(block_hex).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn should_create_proofs_of_every_transaction() {
+		let block = block(REGTEST_BLOCK);
+
+		for tx_index in 0..block.txdata.len() {
+			let proof = TransactionProof::new(&block, tx_index).unwrap();
+
+			assert_eq!(proof.block_height, 3538);
+			assert_eq!(proof.txid, block.txdata[tx_index].txid());
+			assert_eq!(proof.merkle_path.len(), 2);
+			assert!(proof.verify());
+		}
+
+		assert!(TransactionProof::new(&block, block.txdata.len()).is_err());
+	}
+
+	#[test]
+	fn should_reject_tampered_proofs() {
+		let mut proof =
+			TransactionProof::new(&block(REGTEST_BLOCK), 1).unwrap();
+		proof.tx_index = 2;
+
+		assert!(!proof.verify());
+	}
+
+	#[test]
+	fn should_serialize_to_clarity_tuple() {
+		let proof = TransactionProof::new(&block(SINGLE_TX_BLOCK), 0).unwrap();
+
+		assert!(proof.verify());
+
+		let Value::Tuple(tuple) = proof.to_clarity_tuple().unwrap() else {
+			panic!("Proof should serialize to a tuple");
+		};
+
+		assert_eq!(
+			tuple["txid"],
+			Value::Buffer(
+				hex::decode("d574f343976d8e70d91cb278d21044dd8a396019e6db70755a0a50e4783dba38")
+					.unwrap()
+			)
+		);
+		assert_eq!(tuple["height"], Value::UInt(100_000));
+		assert_eq!(
+			tuple["header"],
+			Value::Buffer(
+				hex::decode("0200000035ab154183570282ce9afc0b494c9fc6a3cfea05aa8c1add2ecc56490000000038ba3d78e4500a5a7570dbe61960398add4410d278b21cd9708e6d9743f374d544fc055227f1001c29c1ea3b")
+					.unwrap()
+			)
+		);
+		assert_eq!(
+			tuple["proof"],
+			Value::tuple([
+				("tx-index", Value::UInt(0)),
+				("hashes", Value::List(vec![])),
+			])
+			.unwrap()
+		);
+	}
+
+	#[test]
+	fn should_check_the_header_chain() {
+		let block = block(REGTEST_BLOCK);
+		let proof = TransactionProof::new(&block, 0).unwrap();
+
+		let mut next_header = block.header;
+		next_header.prev_blockhash = block.block_hash();
+
+		let proof = proof.with_header_chain(vec![next_header]).unwrap();
+
+		assert_eq!(proof.confirmations(), 2);
+		assert!(proof.clone().with_header_chain(vec![block.header]).is_err());
+	}
+
+	#[test]
+	fn should_create_empty_merkle_trees() {
+		let merkle_tree = BitcoinMerkleTree::new(&[]);
+
+		assert_eq!(merkle_tree.root(), None);
+		assert_eq!(merkle_tree.proof(0), None);
+		assert_eq!(merkle_tree.depth(), 0);
+	}
+
+	#[test]
+	fn should_use_the_single_txid_as_merkle_root() {
+		let block = block(SINGLE_TX_BLOCK);
+		let txid = block.txdata[0].txid();
+		let merkle_tree = BitcoinMerkleTree::new(&[txid]);
+
+		assert_eq!(
+			merkle_tree.root().unwrap()[..],
+			block.header.merkle_root[..]
+		);
+		assert_eq!(merkle_tree.proof(0), Some(vec![]));
+		assert_eq!(merkle_tree.proof(1), None);
+		assert_eq!(merkle_tree.depth(), 1);
+	}
+}
@@ -0,0 +1,164 @@
+//! Scanning of Bitcoin blocks for sBTC operations, shared by the indexers of
+//! the protocol
+
+use bdk::bitcoin::{
+	Address as BitcoinAddress, Block, BlockHash, Network, Transaction, Txid,
+};
+
+use crate::operations::{
+	op_drop,
+	op_return::{
+		self,
+		deposit::Deposit,
+		wallet_handoff::{self, WalletHandoffData},
+		withdrawal_request::{self, WithdrawalRequestData},
+	},
+};
+
+/// sBTC operation of a transaction
+#[derive(Debug, Clone)]
+pub enum Operation {
+	/// Deposit, embedded in an OP_RETURN output or revealed with OP_DROP
+	Deposit(Deposit),
+	/// Withdrawal request
+	WithdrawalRequest(WithdrawalRequestData),
+	/// Handoff of the sBTC wallet to a new signer set
+	WalletHandoff(WalletHandoffData),
+}
+
+/// sBTC operation found in a block
+#[derive(Debug, Clone)]
+pub struct ParsedOperation {
+	/// ID of the transaction of the operation
+	pub txid: Txid,
+	/// Index of the output paying the sBTC wallet, which is the new one for a
+	/// wallet handoff
+	pub vout: u32,
+	/// Index of the transaction in the block
+	pub tx_index: usize,
+	/// Hash of the block
+	pub block_hash: BlockHash,
+	/// Height of the block, if committed to by its coinbase
+	pub block_height: Option<u64>,
+	/// The operation
+	pub operation: Operation,
+}
+
+/// Walks every transaction of the block and returns the sBTC operations of
+/// the network, in the order of the transactions
+pub fn scan_block(block: &Block, network: Network) -> Vec<ParsedOperation> {
+	let block_hash = block.block_hash();
+	let block_height = block.bip34_block_height().ok();
+
+	block
+		.txdata
+		.iter()
+		.enumerate()
+		.filter_map(|(tx_index, tx)| {
+			let (operation, sbtc_wallet) = parse_operation(tx, network)?;
+
+			let sbtc_wallet_script = sbtc_wallet.script_pubkey();
+			let vout = tx.output.iter().position(|output| {
+				output.script_pubkey == sbtc_wallet_script
+			})?;
+
+			Some(ParsedOperation {
+				txid: tx.txid(),
+				vout: vout as u32,
+				tx_index,
+				block_hash,
+				block_height,
+				operation,
+			})
+		})
+		.collect()
+}
+
+/// Parses the sBTC operation of the transaction, returning it along with the
+/// address of the sBTC wallet it pays
+pub fn parse_operation(
+	tx: &Transaction,
+	network: Network,
+) -> Option<(Operation, BitcoinAddress)> {
+	if op_return::utils::is_sbtc_transaction(tx, network) {
+		if let Ok(deposit) = Deposit::parse(network, tx.clone()) {
+			let sbtc_wallet = deposit.sbtc_wallet_address.clone();
+
+			return Some((Operation::Deposit(deposit), sbtc_wallet));
+		}
+
+		if let Ok(withdrawal) = withdrawal_request::parse(tx, network) {
+			let sbtc_wallet = withdrawal.sbtc_wallet.clone();
+
+			return Some((
+				Operation::WithdrawalRequest(withdrawal),
+				sbtc_wallet,
+			));
+		}
+
+		if let Ok(handoff) = wallet_handoff::parse(tx, network) {
+			let sbtc_wallet = handoff.sbtc_wallet.clone();
+
+			return Some((Operation::WalletHandoff(handoff), sbtc_wallet));
+		}
+
+		return None;
+	}
+
+	// OP_DROP deposits carry their data in the witness of their inputs
+	let deposit = op_drop::deposit::parse_deposit(network, tx.clone()).ok()?;
+	let sbtc_wallet = deposit.sbtc_wallet_address.clone();
+
+	Some((Operation::Deposit(deposit), sbtc_wallet))
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::blockdata::constants::genesis_block;
+	use stacks_core::address::{AddressVersion, StacksAddress};
+
+	use super::*;
+	use crate::operations::op_return::deposit::make_test_deposit_tx;
+
+	#[test]
+	fn should_scan_deposits_of_the_network() {
+		let peg_wallet: BitcoinAddress =
+			"tb1pte5zmd7qzj4hdu45lh9mmdm0nwq3z35pwnxmzkwld6y0a8g83nnq6ts2d4"
+				.parse()
+				.unwrap();
+		let recipient = StacksAddress::new(
+			AddressVersion::TestnetSingleSig,
+			Default::default(),
+		);
+
+		let mut block = genesis_block(Network::Testnet);
+		let deposit_tx = make_test_deposit_tx(
+			&recipient,
+			1000,
+			&peg_wallet.script_pubkey(),
+			Network::Testnet,
+		);
+		block.txdata.push(deposit_tx.clone());
+		block.txdata.push(make_test_deposit_tx(
+			&recipient,
+			1000,
+			&peg_wallet.script_pubkey(),
+			Network::Bitcoin,
+		));
+
+		let operations = scan_block(&block, Network::Testnet);
+
+		assert_eq!(operations.len(), 1);
+
+		let operation = &operations[0];
+
+		assert_eq!(operation.txid, deposit_tx.txid());
+		assert_eq!(operation.vout, 1);
+		assert_eq!(operation.tx_index, 1);
+		assert_eq!(operation.block_hash, block.block_hash());
+		assert!(matches!(
+			&operation.operation,
+			Operation::Deposit(deposit) if deposit.amount == 1000
+		));
+	}
+}
@@ -0,0 +1,342 @@
+//! Signers of the transactions constructed by sBTC operations, so that the
+//! keys do not have to be held by the process constructing them
+
+use bdk::bitcoin::{
+	psbt::PartiallySignedTransaction,
+	secp256k1::{ecdsa::Signature, Message, Secp256k1},
+	util::sighash::SighashCache,
+	EcdsaSig, EcdsaSighashType, PrivateKey, PublicKey, Script,
+};
+#[cfg(feature = "remote-signer")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "remote-signer")]
+use url::Url;
+
+use crate::{SBTCError, SBTCResult};
+
+/// Signer of the inputs spending the P2WPKH outputs of a key
+pub trait TransactionSigner {
+	/// Public key of the signer
+	fn public_key(&self) -> PublicKey;
+
+	/// Signs the signature hash of an input
+	fn sign_sighash(&self, sighash: &Message) -> SBTCResult<Signature>;
+
+	/// Signs the inputs of the PSBT which spend the P2WPKH output of the
+	/// public key. The PSBT is left to be finalized.
+	fn sign_psbt(
+		&self,
+		psbt: &mut PartiallySignedTransaction,
+	) -> SBTCResult<()> {
+		let public_key = self.public_key();
+		let wpubkey_hash = public_key.wpubkey_hash().ok_or(
+			SBTCError::SignerError("Public key is not compressed".to_string()),
+		)?;
+		let script_pubkey = Script::new_v0_p2wpkh(&wpubkey_hash);
+		let script_code = Script::new_p2pkh(&public_key.pubkey_hash());
+
+		let mut sighash_cache = SighashCache::new(&psbt.unsigned_tx);
+
+		for (index, input) in psbt.inputs.iter_mut().enumerate() {
+			let Some(utxo) = input.witness_utxo.as_ref() else {
+				continue;
+			};
+
+			if utxo.script_pubkey != script_pubkey {
+				continue;
+			}
+
+			let sighash = sighash_cache
+				.segwit_signature_hash(
+					index,
+					&script_code,
+					utxo.value,
+					EcdsaSighashType::All,
+				)
+				.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+			let message = Message::from_slice(&sighash[..])
+				.map_err(|err| SBTCError::SECPError("Invalid sighash", err))?;
+
+			input.partial_sigs.insert(
+				public_key,
+				EcdsaSig {
+					sig: self.sign_sighash(&message)?,
+					hash_ty: EcdsaSighashType::All,
+				},
+			);
+		}
+
+		Ok(())
+	}
+}
+
+impl TransactionSigner for PrivateKey {
+	fn public_key(&self) -> PublicKey {
+		self.public_key(&Secp256k1::new())
+	}
+
+	fn sign_sighash(&self, sighash: &Message) -> SBTCResult<Signature> {
+		Ok(Secp256k1::new().sign_ecdsa(sighash, &self.inner))
+	}
+}
+
+#[cfg(feature = "remote-signer")]
+#[derive(Debug, Serialize)]
+struct SignRequest {
+	public_key: String,
+	sighash: String,
+}
+
+#[cfg(feature = "remote-signer")]
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+	signature: String,
+}
+
+/// Signer holding its key in a remote service, such as one backed by an HSM.
+/// Signature hashes are posted to `<url>/sign` as
+/// `{"public_key": "<hex>", "sighash": "<hex>"}`, which answers with the DER
+/// encoded signature as `{"signature": "<hex>"}`.
+#[cfg(feature = "remote-signer")]
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+	client: reqwest::blocking::Client,
+	url: Url,
+	public_key: PublicKey,
+}
+
+#[cfg(feature = "remote-signer")]
+impl RemoteSigner {
+	/// Creates a signer of the key held by the service at the URL
+	pub fn new(url: Url, public_key: PublicKey) -> Self {
+		Self {
+			client: reqwest::blocking::Client::new(),
+			url,
+			public_key,
+		}
+	}
+}
+
+#[cfg(feature = "remote-signer")]
+impl TransactionSigner for RemoteSigner {
+	fn public_key(&self) -> PublicKey {
+		self.public_key
+	}
+
+	fn sign_sighash(&self, sighash: &Message) -> SBTCResult<Signature> {
+		let url = service_url(&self.url, "sign")?;
+
+		let response: SignResponse = self
+			.client
+			.post(url)
+			.json(&SignRequest {
+				public_key: self.public_key.to_string(),
+				sighash: hex::encode(sighash.as_ref()),
+			})
+			.send()
+			.and_then(|response| response.error_for_status())
+			.and_then(|response| response.json())
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+
+		let signature = hex::decode(response.signature)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+		let signature = Signature::from_der(&signature).map_err(|err| {
+			SBTCError::SECPError("Invalid signature from remote signer", err)
+		})?;
+
+		// The service is not trusted to sign with the right key
+		Secp256k1::verification_only()
+			.verify_ecdsa(sighash, &signature, &self.public_key.inner)
+			.map_err(|err| {
+				SBTCError::SECPError(
+					"Invalid signature from remote signer",
+					err,
+				)
+			})?;
+
+		Ok(signature)
+	}
+}
+
+/// URL of the path under the base URL of a service. Unlike [`Url::join`],
+/// the last segment of the base URL is kept whether or not it ends with a
+/// slash.
+#[cfg(feature = "remote-signer")]
+pub(crate) fn service_url(base: &Url, path: &str) -> SBTCResult<Url> {
+	let mut url = base.clone();
+
+	url.path_segments_mut()
+		.map_err(|_| {
+			SBTCError::SignerError(format!("Invalid service URL {}", base))
+		})?
+		.pop_if_empty()
+		.push(path);
+
+	Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+	#[cfg(feature = "remote-signer")]
+	use std::{
+		io::{BufRead, BufReader, Read, Write},
+		net::TcpListener,
+		thread,
+	};
+
+	use bdk::bitcoin::{
+		secp256k1::SecretKey, Network, OutPoint, PackedLockTime, Transaction,
+		TxIn, TxOut,
+	};
+
+	use super::*;
+
+	fn test_private_key(secret: u8) -> PrivateKey {
+		PrivateKey::new(
+			SecretKey::from_slice(&[secret; 32]).unwrap(),
+			Network::Testnet,
+		)
+	}
+
+	fn test_psbt(public_key: &PublicKey) -> PartiallySignedTransaction {
+		let tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: OutPoint::default(),
+				..Default::default()
+			}],
+			output: vec![TxOut {
+				value: 9_000,
+				script_pubkey: Script::new(),
+			}],
+		};
+
+		let mut psbt =
+			PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+		psbt.inputs[0].witness_utxo = Some(TxOut {
+			value: 10_000,
+			script_pubkey: Script::new_v0_p2wpkh(
+				&public_key.wpubkey_hash().unwrap(),
+			),
+		});
+
+		psbt
+	}
+
+	/// Serves a single signature request with the key
+	#[cfg(feature = "remote-signer")]
+	fn serve_remote_signer(private_key: PrivateKey) -> Url {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}/", listener.local_addr().unwrap());
+
+		thread::spawn(move || {
+			let (stream, _) = listener.accept().unwrap();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+			let mut content_length = 0;
+			loop {
+				let mut line = String::new();
+				reader.read_line(&mut line).unwrap();
+
+				if let Some(length) =
+					line.to_ascii_lowercase().strip_prefix("content-length:")
+				{
+					content_length = length.trim().parse().unwrap();
+				}
+				if line == "\r\n" {
+					break;
+				}
+			}
+
+			let mut body = vec![0; content_length];
+			reader.read_exact(&mut body).unwrap();
+
+			let request: serde_json::Value =
+				serde_json::from_slice(&body).unwrap();
+			let sighash =
+				hex::decode(request["sighash"].as_str().unwrap()).unwrap();
+			let signature = private_key
+				.sign_sighash(&Message::from_slice(&sighash).unwrap())
+				.unwrap();
+
+			let body = serde_json::json!({
+				"signature": hex::encode(signature.serialize_der()),
+			})
+			.to_string();
+
+			write!(
+				&stream,
+				"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+				body.len(),
+				body
+			)
+			.unwrap();
+		});
+
+		url.parse().unwrap()
+	}
+
+	#[test]
+	fn should_sign_psbt_inputs_of_the_key() {
+		let private_key = test_private_key(1);
+		let public_key = TransactionSigner::public_key(&private_key);
+		let mut psbt = test_psbt(&public_key);
+
+		test_private_key(2).sign_psbt(&mut psbt).unwrap();
+		assert!(psbt.inputs[0].partial_sigs.is_empty());
+
+		private_key.sign_psbt(&mut psbt).unwrap();
+		assert!(psbt.inputs[0].partial_sigs.contains_key(&public_key));
+	}
+
+	#[cfg(feature = "remote-signer")]
+	#[test]
+	fn should_sign_psbt_with_remote_signer() {
+		let private_key = test_private_key(1);
+		let public_key = TransactionSigner::public_key(&private_key);
+		let mut psbt = test_psbt(&public_key);
+
+		let signer =
+			RemoteSigner::new(serve_remote_signer(private_key), public_key);
+		signer.sign_psbt(&mut psbt).unwrap();
+
+		assert!(psbt.inputs[0].partial_sigs.contains_key(&public_key));
+	}
+
+	#[cfg(feature = "remote-signer")]
+	#[test]
+	fn should_reject_signature_of_another_key() {
+		let public_key = TransactionSigner::public_key(&test_private_key(1));
+		let mut psbt = test_psbt(&public_key);
+
+		let signer = RemoteSigner::new(
+			serve_remote_signer(test_private_key(2)),
+			public_key,
+		);
+
+		assert!(signer.sign_psbt(&mut psbt).is_err());
+	}
+
+	#[cfg(feature = "remote-signer")]
+	#[test]
+	fn should_keep_the_path_of_the_service_url() {
+		for base in
+			["http://signer.internal/hsm", "http://signer.internal/hsm/"]
+		{
+			assert_eq!(
+				service_url(&base.parse().unwrap(), "sign")
+					.unwrap()
+					.as_str(),
+				"http://signer.internal/hsm/sign"
+			);
+		}
+
+		assert_eq!(
+			service_url(&"http://signer.internal".parse().unwrap(), "sign")
+				.unwrap()
+				.as_str(),
+			"http://signer.internal/sign"
+		);
+	}
+}
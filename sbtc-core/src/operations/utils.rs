@@ -1,16 +1,37 @@
 //! Utilities for sBTC transactions
 
+use std::{fmt, str::FromStr};
+
 use bdk::{
-	bitcoin::PrivateKey, blockchain::ElectrumBlockchain,
-	database::MemoryDatabase, electrum_client::Client, template::P2Wpkh,
-	SyncOptions, Wallet,
+	bitcoin::{
+		consensus::encode, psbt::PartiallySignedTransaction,
+		secp256k1::Secp256k1, Address as BitcoinAddress, Network,
+		PackedLockTime, PublicKey, Script, Sequence, Transaction, TxIn, TxOut,
+	},
+	blockchain::{GetHeight, WalletSync},
+	database::MemoryDatabase,
+	miniscript::psbt::PsbtExt,
+	template::P2Wpkh,
+	FeeRate, LocalUtxo, SyncOptions, Wallet,
 };
 
+use stacks_core::utils::PrincipalData;
+
 use crate::{SBTCError, SBTCResult};
 
-/// Initializes the electrum blockchain client
-pub(crate) fn init_blockchain() -> SBTCResult<ElectrumBlockchain> {
-	let client = Client::new("ssl://blockstream.info:993").map_err(|err| {
+/// URL of the public Blockstream Electrum server
+#[cfg(feature = "electrum")]
+pub const BLOCKSTREAM_ELECTRUM_URL: &str = "ssl://blockstream.info:993";
+
+/// Initializes an Electrum blockchain client of the server at the URL, such as
+/// [`BLOCKSTREAM_ELECTRUM_URL`] or a local regtest server
+#[cfg(feature = "electrum")]
+pub fn electrum_blockchain(
+	url: &str,
+) -> SBTCResult<bdk::blockchain::ElectrumBlockchain> {
+	use bdk::{blockchain::ElectrumBlockchain, electrum_client::Client};
+
+	let client = Client::new(url).map_err(|err| {
 		SBTCError::ElectrumError("Could not create Electrum client", err)
 	})?;
 	let blockchain = ElectrumBlockchain::from(client);
@@ -18,23 +39,386 @@ pub(crate) fn init_blockchain() -> SBTCResult<ElectrumBlockchain> {
 	Ok(blockchain)
 }
 
-/// Set up an electrum wallet for sBTC operations
-pub(crate) fn setup_wallet(
-	private_key: PrivateKey,
-) -> SBTCResult<Wallet<MemoryDatabase>> {
-	let blockchain = init_blockchain()?;
-
+/// Set up a watch-only wallet of the P2WPKH outputs of the key for sBTC
+/// operations, synced with the blockchain
+pub fn setup_wallet<B>(
+	public_key: PublicKey,
+	network: Network,
+	blockchain: &B,
+) -> SBTCResult<Wallet<MemoryDatabase>>
+where
+	B: WalletSync + GetHeight,
+{
 	let wallet = Wallet::new(
-		P2Wpkh(private_key),
-		Some(P2Wpkh(private_key)),
-		private_key.network,
+		P2Wpkh(public_key),
+		Some(P2Wpkh(public_key)),
+		network,
 		MemoryDatabase::default(),
 	)
 	.map_err(|err| SBTCError::BDKError("Could not open wallet", err))?;
 
 	wallet
-		.sync(&blockchain, SyncOptions::default())
+		.sync(blockchain, SyncOptions::default())
 		.map_err(|err| SBTCError::BDKError("Could not sync wallet", err))?;
 
 	Ok(wallet)
 }
+
+/// Size of the witness of a P2WPKH input: the item count, a DER signature of
+/// at most 72 bytes and a compressed public key, with their lengths
+const P2WPKH_WITNESS_SIZE: usize = 1 + 1 + 72 + 1 + 33;
+
+/// UTXO set and change address of a caller maintaining its own UTXO index,
+/// funding transactions built without a synced wallet
+#[derive(Debug, Clone)]
+pub struct OfflineFunding {
+	/// UTXOs which may be spent. They are assumed to be P2WPKH outputs when
+	/// estimating the fee.
+	pub utxos: Vec<LocalUtxo>,
+	/// Address receiving the change
+	pub change_address: BitcoinAddress,
+	/// Fee rate of the transaction
+	pub fee_rate: FeeRate,
+}
+
+/// Builds an unsigned transaction paying the outputs, in the given order,
+/// from the UTXOs of the funding. The UTXOs are selected by decreasing value
+/// then outpoint, and the change output is appended last unless it would be
+/// dust, so the same funding and outputs always give the same transaction.
+pub fn build_offline_psbt(
+	funding: &OfflineFunding,
+	outputs: &[(Script, u64)],
+) -> SBTCResult<PartiallySignedTransaction> {
+	let mut utxos: Vec<&LocalUtxo> =
+		funding.utxos.iter().filter(|utxo| !utxo.is_spent).collect();
+	utxos.sort_by(|a, b| {
+		b.txout
+			.value
+			.cmp(&a.txout.value)
+			.then(a.outpoint.cmp(&b.outpoint))
+	});
+
+	let total_output: u64 = outputs.iter().map(|(_, amount)| amount).sum();
+	let change_script = funding.change_address.script_pubkey();
+	let change_dust = change_script.dust_value().to_sat();
+
+	let mut tx = Transaction {
+		version: 2,
+		lock_time: PackedLockTime::ZERO,
+		input: vec![],
+		output: outputs
+			.iter()
+			.map(|(script_pubkey, value)| TxOut {
+				value: *value,
+				script_pubkey: script_pubkey.clone(),
+			})
+			.collect(),
+	};
+
+	let mut total_input = 0;
+	let mut needed = total_output;
+
+	for utxo in &utxos {
+		tx.input.push(TxIn {
+			previous_output: utxo.outpoint,
+			script_sig: Script::new(),
+			sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+			witness: Default::default(),
+		});
+		total_input += utxo.txout.value;
+
+		let fee = estimate_fee(&tx, funding.fee_rate);
+		needed = total_output + fee;
+
+		if total_input < needed {
+			continue;
+		}
+
+		let mut change_output = TxOut {
+			value: 0,
+			script_pubkey: change_script.clone(),
+		};
+		let change_fee = funding
+			.fee_rate
+			.fee_vb(encode::serialize(&change_output).len());
+		let change = (total_input - needed).saturating_sub(change_fee);
+
+		if change >= change_dust {
+			change_output.value = change;
+			tx.output.push(change_output);
+		}
+
+		let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx)
+			.map_err(|_| {
+				SBTCError::MalformedData("Could not create the PSBT")
+			})?;
+
+		for (input, utxo) in psbt.inputs.iter_mut().zip(&utxos) {
+			input.witness_utxo = Some(utxo.txout.clone());
+		}
+
+		return Ok(psbt);
+	}
+
+	Err(SBTCError::InsufficientFunds(total_input, needed))
+}
+
+/// Estimates the weight of an unsigned transaction once its P2WPKH inputs are
+/// signed
+fn estimate_weight(tx: &Transaction) -> usize {
+	// The segwit marker and flag are only serialized with witnesses
+	let witness_weight = 2 + tx.input.len() * P2WPKH_WITNESS_SIZE;
+
+	tx.weight() + witness_weight
+}
+
+/// Estimates the virtual size of an unsigned transaction once its P2WPKH
+/// inputs are signed
+pub fn estimate_vsize(tx: &Transaction) -> usize {
+	(estimate_weight(tx) + 3) / 4
+}
+
+fn estimate_fee(tx: &Transaction, fee_rate: FeeRate) -> u64 {
+	fee_rate.fee_wu(estimate_weight(tx))
+}
+
+/// Finalizes a partially signed transaction whose inputs have all been signed,
+/// for instance by hardware wallets or the cosigners of a multisig, and
+/// extracts the transaction to broadcast
+pub fn finalize_psbt(
+	mut psbt: PartiallySignedTransaction,
+) -> SBTCResult<Transaction> {
+	psbt.finalize_mut(&Secp256k1::verification_only())
+		.map_err(|errors| {
+			SBTCError::PSBTFinalizeError(
+				errors
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>()
+					.join(", "),
+			)
+		})?;
+
+	Ok(psbt.extract_tx())
+}
+
+/// Formats a principal as its address, followed by the contract name for
+/// contract principals
+pub fn principal_to_string(principal: &PrincipalData) -> String {
+	match principal {
+		PrincipalData::Standard(data) => data.1.to_string(),
+		PrincipalData::Contract(data, contract_name) => {
+			format!("{}.{}", data.1, contract_name)
+		}
+	}
+}
+
+/// Computes the effective fee rate of a transaction given the values of the
+/// outputs spent by its inputs, in the same order as the inputs
+pub fn effective_fee_rate(
+	tx: &Transaction,
+	input_values: &[u64],
+) -> SBTCResult<FeeRate> {
+	if input_values.len() != tx.input.len() {
+		return Err(SBTCError::MalformedData(
+			"Number of input values does not match the number of inputs",
+		));
+	}
+
+	let total_input: u64 = input_values.iter().sum();
+	let total_output: u64 = tx.output.iter().map(|output| output.value).sum();
+
+	let fee = total_input.checked_sub(total_output).ok_or(
+		SBTCError::MalformedData("Transaction outputs exceed its inputs"),
+	)?;
+
+	Ok(FeeRate::from_vb(fee, tx.vsize()))
+}
+
+/// Unit suffix accepted when parsing fee rates
+const SAT_PER_VB_UNIT: &str = "sat/vb";
+
+/// Fee rate expressed in satoshis per virtual byte
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SatPerVb(FeeRate);
+
+impl SatPerVb {
+	/// Get the fee rate in satoshis per virtual byte
+	pub fn as_sat_per_vb(&self) -> f32 {
+		self.0.as_sat_per_vb()
+	}
+}
+
+impl FromStr for SatPerVb {
+	type Err = SBTCError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		let lowercase = s.to_ascii_lowercase();
+
+		let value = lowercase
+			.strip_suffix(SAT_PER_VB_UNIT)
+			.unwrap_or(&lowercase)
+			.trim_end();
+
+		let sat_per_vb: f32 = value.parse().map_err(|_| {
+			SBTCError::MalformedData("Fee rate is not a number")
+		})?;
+
+		if !sat_per_vb.is_finite() || sat_per_vb < 0.0 {
+			return Err(SBTCError::MalformedData(
+				"Fee rate should be a non-negative number",
+			));
+		}
+
+		Ok(Self(FeeRate::from_sat_per_vb(sat_per_vb)))
+	}
+}
+
+impl fmt::Display for SatPerVb {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} sat/vB", self.as_sat_per_vb())
+	}
+}
+
+impl From<FeeRate> for SatPerVb {
+	fn from(fee_rate: FeeRate) -> Self {
+		Self(fee_rate)
+	}
+}
+
+impl From<SatPerVb> for FeeRate {
+	fn from(fee_rate: SatPerVb) -> Self {
+		fee_rate.0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::consensus::encode;
+
+	use super::*;
+
+	// Deposit transaction with a single P2WPKH input
+	const TX_HEX: &str = "010000000001019131d69f4616c2a17f3d2519a3dc697136a56846794e677982f565f79295e0370100000000feffffff0300000000000000001b6a1954323c051af0bf935f1ba62167f89c1fff2d9369f972ad0f7e6e0a020000000000225120b85fdda4ae0f69883280360a9b91555a2f23c5b9e34173fabec5d903416c2aaf7b850800000000001600147c969cfcab0d2ad171aa3f201c94b51b0e8eca6602473044022036663b723c79333f9c8b7d5d9db3b6cd301fc6bf82515e62303713eb69b4d18d0220548939af6e1d86fcf8a54da1f6942f25f36ed0488a0d3616c47daa49f59bc7b601210215bd6d522931e602fde924571eb472bc1db953484b29ba6542774ebbf083412329c62500";
+
+	fn test_tx() -> Transaction {
+		encode::deserialize(&hex::decode(TX_HEX).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn should_compute_effective_fee_rate() {
+		let tx = test_tx();
+		let total_output: u64 =
+			tx.output.iter().map(|output| output.value).sum();
+
+		assert_eq!(tx.vsize(), 189);
+
+		// 1890 sats paid for 189 vbytes
+		let fee_rate = effective_fee_rate(&tx, &[total_output + 1890]).unwrap();
+
+		assert_eq!(fee_rate.as_sat_per_vb(), 10.0);
+	}
+
+	#[test]
+	fn should_fail_on_input_values_mismatch() {
+		assert!(effective_fee_rate(&test_tx(), &[]).is_err());
+		assert!(effective_fee_rate(&test_tx(), &[1, 2]).is_err());
+	}
+
+	#[test]
+	fn should_fail_when_outputs_exceed_inputs() {
+		assert!(effective_fee_rate(&test_tx(), &[1000]).is_err());
+	}
+
+	#[test]
+	fn should_parse_sat_per_vb() {
+		for input in ["10", "10sat/vB", "10 sat/vb", " 10 SAT/VB "] {
+			let fee_rate: SatPerVb = input.parse().unwrap();
+
+			assert_eq!(FeeRate::from(fee_rate), FeeRate::from_sat_per_vb(10.0));
+		}
+
+		let fee_rate: SatPerVb = "2.5 sat/vB".parse().unwrap();
+
+		assert_eq!(fee_rate.as_sat_per_vb(), 2.5);
+		assert_eq!(fee_rate.to_string(), "2.5 sat/vB");
+	}
+
+	#[test]
+	fn should_fail_to_parse_invalid_sat_per_vb() {
+		for input in ["-1", "-1 sat/vB", "ten", "sat/vB", "", "NaN"] {
+			assert!(input.parse::<SatPerVb>().is_err());
+		}
+	}
+
+	fn test_funding(values: &[u64]) -> OfflineFunding {
+		let change_address: BitcoinAddress =
+			"tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms"
+				.parse()
+				.unwrap();
+
+		OfflineFunding {
+			utxos: values
+				.iter()
+				.enumerate()
+				.map(|(vout, value)| LocalUtxo {
+					outpoint: bdk::bitcoin::OutPoint::new(
+						bdk::bitcoin::hashes::Hash::all_zeros(),
+						vout as u32,
+					),
+					txout: TxOut {
+						value: *value,
+						script_pubkey: change_address.script_pubkey(),
+					},
+					keychain: bdk::KeychainKind::External,
+					is_spent: false,
+				})
+				.collect(),
+			change_address,
+			fee_rate: FeeRate::from_sat_per_vb(10.0),
+		}
+	}
+
+	#[test]
+	fn should_build_offline_psbt_with_largest_utxos_and_change() {
+		let funding = test_funding(&[5_000, 50_000, 20_000, 30_000]);
+		let recipient = funding.change_address.script_pubkey();
+
+		let psbt = build_offline_psbt(&funding, &[(recipient.clone(), 60_000)])
+			.unwrap();
+		let tx = &psbt.unsigned_tx;
+
+		let vouts: Vec<u32> = tx
+			.input
+			.iter()
+			.map(|input| input.previous_output.vout)
+			.collect();
+		assert_eq!(vouts, vec![1, 3]);
+		assert_eq!(tx.output[0].value, 60_000);
+		assert_eq!(tx.output.len(), 2);
+		assert!(psbt.inputs.iter().all(|input| input.witness_utxo.is_some()));
+
+		let fee =
+			80_000 - tx.output.iter().map(|output| output.value).sum::<u64>();
+		assert!(fee >= estimate_fee(tx, funding.fee_rate));
+
+		assert_eq!(
+			build_offline_psbt(&funding, &[(recipient, 60_000)])
+				.unwrap()
+				.unsigned_tx,
+			*tx
+		);
+	}
+
+	#[test]
+	fn should_not_build_offline_psbt_without_enough_funds() {
+		let funding = test_funding(&[5_000, 10_000]);
+		let recipient = funding.change_address.script_pubkey();
+
+		assert!(matches!(
+			build_offline_psbt(&funding, &[(recipient, 15_000)]),
+			Err(SBTCError::InsufficientFunds(15_000, _))
+		));
+	}
+}
@@ -0,0 +1,155 @@
+//! Validation of the amounts and data of sBTC operations before their
+//! transactions are constructed, so that they are not rejected by the nodes
+//! at broadcast instead
+
+use bdk::bitcoin::{Network, Script};
+use thiserror::Error;
+
+/// Maximum amount of satoshis which can ever exist
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+/// Maximum size of the data pushed in an OP_RETURN output relayed by the
+/// nodes with the default policy
+pub const MAX_OP_RETURN_DATA_SIZE: usize = 80;
+
+/// Validation error of an sBTC operation
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+	/// Amount is below the dust value of its output
+	#[error("Amount {amount} is below the dust value {dust} of its output")]
+	Dust {
+		/// Amount of the output
+		amount: u64,
+		/// Dust value of the output script
+		dust: u64,
+	},
+	/// Amount exceeds the supply of bitcoin
+	#[error("Amount {0} exceeds the 21M BTC supply")]
+	ExceedsSupply(u64),
+	/// Amount is below the minimum peg amount of the network
+	#[error("Amount {amount} is below the minimum peg amount {minimum}")]
+	BelowMinimumPeg {
+		/// Amount of the operation
+		amount: u64,
+		/// Minimum peg amount of the network
+		minimum: u64,
+	},
+	/// OP_RETURN data is larger than relayed by the nodes
+	#[error("OP_RETURN data of {size} bytes exceeds the limit of {max} bytes")]
+	DataTooLarge {
+		/// Size of the data
+		size: usize,
+		/// Maximum size of the data
+		max: usize,
+	},
+}
+
+/// Minimum amount of satoshis pegged in or out by a single operation
+pub fn minimum_peg_amount(network: Network) -> u64 {
+	match network {
+		Network::Bitcoin => 10_000,
+		Network::Testnet | Network::Signet | Network::Regtest => 1_000,
+	}
+}
+
+/// Validates the amount of an output paying the script
+pub fn validate_output_amount(
+	amount: u64,
+	script: &Script,
+) -> Result<(), ValidationError> {
+	if amount > MAX_MONEY {
+		return Err(ValidationError::ExceedsSupply(amount));
+	}
+
+	let dust = script.dust_value().to_sat();
+
+	if amount < dust {
+		return Err(ValidationError::Dust { amount, dust });
+	}
+
+	Ok(())
+}
+
+/// Validates the amount pegged in or out by a deposit or withdrawal request,
+/// paid to the script
+pub fn validate_peg_amount(
+	amount: u64,
+	script: &Script,
+	network: Network,
+) -> Result<(), ValidationError> {
+	validate_output_amount(amount, script)?;
+
+	let minimum = minimum_peg_amount(network);
+
+	if amount < minimum {
+		return Err(ValidationError::BelowMinimumPeg { amount, minimum });
+	}
+
+	Ok(())
+}
+
+/// Validates the data pushed in an OP_RETURN output
+pub fn validate_op_return_data(data: &[u8]) -> Result<(), ValidationError> {
+	if data.len() > MAX_OP_RETURN_DATA_SIZE {
+		return Err(ValidationError::DataTooLarge {
+			size: data.len(),
+			max: MAX_OP_RETURN_DATA_SIZE,
+		});
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::Address as BitcoinAddress;
+
+	use super::*;
+
+	fn test_script() -> Script {
+		"tb1qwe9ddxp6v32uef2v66j00vx6wxax5zat223tms"
+			.parse::<BitcoinAddress>()
+			.unwrap()
+			.script_pubkey()
+	}
+
+	#[test]
+	fn should_validate_peg_amounts() {
+		let script = test_script();
+		let dust = script.dust_value().to_sat();
+
+		validate_peg_amount(10_000, &script, Network::Bitcoin).unwrap();
+
+		assert_eq!(
+			validate_peg_amount(dust - 1, &script, Network::Testnet),
+			Err(ValidationError::Dust {
+				amount: dust - 1,
+				dust
+			})
+		);
+		assert_eq!(
+			validate_peg_amount(9_999, &script, Network::Bitcoin),
+			Err(ValidationError::BelowMinimumPeg {
+				amount: 9_999,
+				minimum: 10_000
+			})
+		);
+		assert_eq!(
+			validate_peg_amount(MAX_MONEY + 1, &script, Network::Bitcoin),
+			Err(ValidationError::ExceedsSupply(MAX_MONEY + 1))
+		);
+	}
+
+	#[test]
+	fn should_validate_op_return_data_size() {
+		validate_op_return_data(&[0; MAX_OP_RETURN_DATA_SIZE]).unwrap();
+
+		assert_eq!(
+			validate_op_return_data(&[0; MAX_OP_RETURN_DATA_SIZE + 1]),
+			Err(ValidationError::DataTooLarge {
+				size: MAX_OP_RETURN_DATA_SIZE + 1,
+				max: MAX_OP_RETURN_DATA_SIZE
+			})
+		);
+	}
+}
@@ -0,0 +1,298 @@
+//! Python bindings of the block scanning, payload parsing and address
+//! utilities, for analytics over raw block data without running romeo.
+//!
+//! Operations are returned as dictionaries of plain values, so lists of them
+//! can be loaded into data frames directly.
+
+use std::str::FromStr;
+
+use bdk::bitcoin::{
+	consensus::deserialize, Address as BitcoinAddress, Block, Network,
+	Transaction,
+};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+use stacks_core::{address::StacksAddress, codec::Codec};
+
+use crate::operations::{
+	payload::{NetworkPayload, Payload},
+	scan::{self, Operation, ParsedOperation},
+	utils::principal_to_string,
+};
+
+/// Scans a raw block for the sBTC operations of the network, such as
+/// `testnet`, in the order of its transactions
+#[pyfunction]
+fn scan_block(
+	py: Python,
+	block: &[u8],
+	network: &str,
+) -> PyResult<Vec<PyObject>> {
+	let block: Block = deserialize(block).map_err(value_error)?;
+
+	scan::scan_block(&block, parse_network(network)?)
+		.iter()
+		.map(|operation| parsed_operation_dict(py, operation))
+		.collect()
+}
+
+/// Parses the sBTC operation of a raw transaction of the network. Returns
+/// `None` if the transaction is not an sBTC operation.
+#[pyfunction]
+fn parse_transaction(
+	py: Python,
+	tx: &[u8],
+	network: &str,
+) -> PyResult<Option<PyObject>> {
+	let tx: Transaction = deserialize(tx).map_err(value_error)?;
+
+	scan::parse_operation(&tx, parse_network(network)?)
+		.map(|(operation, _)| {
+			let dict = PyDict::new(py);
+			dict.set_item("txid", tx.txid().to_string())?;
+			set_operation_items(dict, &operation)?;
+
+			Ok(dict.into())
+		})
+		.transpose()
+}
+
+/// Parses the data of an sBTC OP_RETURN output, starting with the magic bytes
+#[pyfunction]
+fn parse_payload(py: Python, data: &[u8]) -> PyResult<PyObject> {
+	let NetworkPayload { network, payload } =
+		NetworkPayload::codec_deserialize(&mut &data[..])
+			.map_err(value_error)?;
+
+	let dict = PyDict::new(py);
+	dict.set_item("network", network.to_string())?;
+
+	match payload {
+		Payload::Deposit { recipient } => {
+			dict.set_item("operation", "deposit")?;
+			dict.set_item("recipient", principal_to_string(&recipient))?;
+		}
+		Payload::WithdrawalRequest { amount, signature } => {
+			dict.set_item("operation", "withdrawal-request")?;
+			dict.set_item("amount", amount)?;
+			dict.set_item(
+				"signature",
+				hex::encode(signature.serialize_to_vec()),
+			)?;
+		}
+		Payload::WithdrawalFulfillment { chain_tip } => {
+			dict.set_item("operation", "withdrawal-fulfillment")?;
+			dict.set_item(
+				"chain_tip",
+				hex::encode(chain_tip.serialize_to_vec()),
+			)?;
+		}
+		Payload::WalletHandoff => {
+			dict.set_item("operation", "wallet-handoff")?;
+		}
+	}
+
+	Ok(dict.into())
+}
+
+/// Converts a Stacks address into the Bitcoin address of the same hash on
+/// the network
+#[pyfunction]
+fn stacks_to_bitcoin_address(address: &str, network: &str) -> PyResult<String> {
+	let address =
+		StacksAddress::try_from_lenient(address).map_err(value_error)?;
+
+	Ok(address
+		.to_bitcoin_address(parse_network(network)?)
+		.map_err(value_error)?
+		.to_string())
+}
+
+/// Converts a P2PKH or P2SH Bitcoin address into the Stacks address of the
+/// same hash
+#[pyfunction]
+fn bitcoin_to_stacks_address(address: &str) -> PyResult<String> {
+	let address = BitcoinAddress::from_str(address).map_err(value_error)?;
+
+	Ok(StacksAddress::from_bitcoin_address(&address)
+		.map_err(value_error)?
+		.to_string())
+}
+
+/// Whether the string is a valid Stacks address
+#[pyfunction]
+fn is_valid_stacks_address(address: &str) -> bool {
+	StacksAddress::try_from(address).is_ok()
+}
+
+/// Python module of the sBTC primitives
+#[pymodule]
+fn sbtc_core(_py: Python, module: &PyModule) -> PyResult<()> {
+	module.add_function(wrap_pyfunction!(scan_block, module)?)?;
+	module.add_function(wrap_pyfunction!(parse_transaction, module)?)?;
+	module.add_function(wrap_pyfunction!(parse_payload, module)?)?;
+	module
+		.add_function(wrap_pyfunction!(stacks_to_bitcoin_address, module)?)?;
+	module
+		.add_function(wrap_pyfunction!(bitcoin_to_stacks_address, module)?)?;
+	module.add_function(wrap_pyfunction!(is_valid_stacks_address, module)?)?;
+
+	Ok(())
+}
+
+fn parsed_operation_dict(
+	py: Python,
+	parsed: &ParsedOperation,
+) -> PyResult<PyObject> {
+	let dict = PyDict::new(py);
+	dict.set_item("txid", parsed.txid.to_string())?;
+	dict.set_item("vout", parsed.vout)?;
+	dict.set_item("tx_index", parsed.tx_index)?;
+	dict.set_item("block_hash", parsed.block_hash.to_string())?;
+	dict.set_item("block_height", parsed.block_height)?;
+	set_operation_items(dict, &parsed.operation)?;
+
+	Ok(dict.into())
+}
+
+fn set_operation_items(dict: &PyDict, operation: &Operation) -> PyResult<()> {
+	match operation {
+		Operation::Deposit(deposit) => {
+			dict.set_item("operation", "deposit")?;
+			dict.set_item("amount", deposit.amount)?;
+			dict.set_item(
+				"recipient",
+				principal_to_string(&deposit.recipient),
+			)?;
+			dict.set_item(
+				"sbtc_wallet",
+				deposit.sbtc_wallet_address.to_string(),
+			)?;
+		}
+		Operation::WithdrawalRequest(withdrawal) => {
+			dict.set_item("operation", "withdrawal-request")?;
+			dict.set_item("amount", withdrawal.amount)?;
+			dict.set_item(
+				"drawee",
+				withdrawal.drawee_stacks_address.to_string(),
+			)?;
+			dict.set_item(
+				"payee",
+				withdrawal.payee_bitcoin_address.to_string(),
+			)?;
+			dict.set_item("fulfillment_fee", withdrawal.fulfillment_amount)?;
+			dict.set_item("sbtc_wallet", withdrawal.sbtc_wallet.to_string())?;
+		}
+		Operation::WalletHandoff(handoff) => {
+			dict.set_item("operation", "wallet-handoff")?;
+			dict.set_item("amount", handoff.amount)?;
+			dict.set_item("sbtc_wallet", handoff.sbtc_wallet.to_string())?;
+		}
+	}
+
+	Ok(())
+}
+
+fn parse_network(network: &str) -> PyResult<Network> {
+	Network::from_str(network).map_err(value_error)
+}
+
+fn value_error(err: impl ToString) -> PyErr {
+	PyValueError::new_err(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use pyo3::types::PyBytes;
+	use stacks_core::utils::PrincipalData;
+
+	use super::*;
+
+	const ADDRESS: &str = "ST000000000000000000002AMW42H";
+
+	fn with_module(test: impl FnOnce(Python, &PyModule)) {
+		pyo3::prepare_freethreaded_python();
+
+		Python::with_gil(|py| {
+			let module = PyModule::new(py, "sbtc_core").unwrap();
+			sbtc_core(py, module).unwrap();
+
+			test(py, module)
+		})
+	}
+
+	fn item(dict: &PyAny, key: &str) -> String {
+		dict.get_item(key).unwrap().extract().unwrap()
+	}
+
+	#[test]
+	fn should_parse_deposit_payloads() {
+		let data = NetworkPayload {
+			network: Network::Testnet,
+			payload: Payload::Deposit {
+				recipient: PrincipalData::try_from(ADDRESS.to_string())
+					.unwrap(),
+			},
+		}
+		.serialize_to_vec();
+
+		with_module(|py, module| {
+			let payload = module
+				.getattr("parse_payload")
+				.unwrap()
+				.call1((PyBytes::new(py, &data),))
+				.unwrap();
+
+			assert_eq!(item(payload, "network"), "testnet");
+			assert_eq!(item(payload, "operation"), "deposit");
+			assert_eq!(item(payload, "recipient"), ADDRESS);
+		});
+	}
+
+	#[test]
+	fn should_round_trip_addresses() {
+		with_module(|_, module| {
+			let bitcoin_address: String = module
+				.getattr("stacks_to_bitcoin_address")
+				.unwrap()
+				.call1((ADDRESS, "testnet"))
+				.unwrap()
+				.extract()
+				.unwrap();
+			let stacks_address: String = module
+				.getattr("bitcoin_to_stacks_address")
+				.unwrap()
+				.call1((bitcoin_address,))
+				.unwrap()
+				.extract()
+				.unwrap();
+
+			assert_eq!(stacks_address, ADDRESS);
+			assert!(is_valid_stacks_address(ADDRESS));
+			assert!(!is_valid_stacks_address("not an address"));
+		});
+	}
+
+	#[test]
+	fn should_raise_value_errors() {
+		with_module(|py, module| {
+			let calls = [
+				module
+					.getattr("parse_payload")
+					.unwrap()
+					.call1((PyBytes::new(py, b"xx"),)),
+				module
+					.getattr("parse_transaction")
+					.unwrap()
+					.call1((PyBytes::new(py, b""), "testnet")),
+				module
+					.getattr("stacks_to_bitcoin_address")
+					.unwrap()
+					.call1((ADDRESS, "dogecoin")),
+			];
+
+			for call in calls {
+				assert!(call.unwrap_err().is_instance_of::<PyValueError>(py));
+			}
+		});
+	}
+}
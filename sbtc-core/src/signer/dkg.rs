@@ -0,0 +1,671 @@
+//! Distributed key generation of the group key of the signers, so that a set
+//! of signers can bootstrap a shared taproot wallet without any of them ever
+//! knowing its private key.
+//!
+//! A ceremony has two rounds:
+//!
+//! 1. every participant broadcasts commitments to the random polynomials of its
+//!    key shares, with proofs of knowledge of their secrets
+//! 2. once it has the commitments of every key share, every participant sends
+//!    each other participant the evaluations of its polynomials at the IDs of
+//!    the key shares of the recipient
+//!
+//! Every participant checks the shares it receives against the commitments of
+//! their senders. Invalid commitments or shares make it broadcast a complaint
+//! against their senders, which fails the ceremony, to be run again without
+//! them. Otherwise the ceremony ends with the key shares of every participant,
+//! which broadcasts the group key the others must agree on.
+//!
+//! The private shares must only be sent to their recipients, over
+//! authenticated and encrypted channels.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use p256k1::{point::Point, scalar::Scalar};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use wsts::{common::PolyCommitment, compute, errors::DkgError, v1};
+
+use crate::{signer::frost::KeyShares, SBTCError, SBTCResult};
+
+/// Commitments of a participant to the polynomials of its key shares
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DkgPublicShares {
+	/// ID of the ceremony
+	pub dkg_id: u64,
+	/// ID of the participant
+	pub signer_id: u32,
+	/// Polynomial commitments by the IDs of the key shares
+	pub commitments: Vec<(u32, PolyCommitment)>,
+}
+
+/// Private shares of a participant for another participant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DkgPrivateShares {
+	/// ID of the ceremony
+	pub dkg_id: u64,
+	/// ID of the sending participant
+	pub signer_id: u32,
+	/// ID of the receiving participant
+	pub recipient_id: u32,
+	/// Shares for the key shares of the recipient by their IDs, by the IDs of
+	/// the key shares of the sender
+	pub shares: Vec<(u32, Vec<(u32, Scalar)>)>,
+}
+
+/// Complaint of a participant against the participants which sent invalid
+/// commitments or shares
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DkgComplaint {
+	/// ID of the ceremony
+	pub dkg_id: u64,
+	/// ID of the complaining participant
+	pub signer_id: u32,
+	/// IDs of the accused participants
+	pub accused_signer_ids: Vec<u32>,
+}
+
+/// Group key computed by a participant at the end of the ceremony
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DkgEnd {
+	/// ID of the ceremony
+	pub dkg_id: u64,
+	/// ID of the participant
+	pub signer_id: u32,
+	/// Group key of the signers
+	pub group_key: Point,
+}
+
+/// Message exchanged between the participants of a ceremony. Private shares
+/// go to their recipient only, the other messages to every participant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DkgMessage {
+	/// Public shares
+	PublicShares(DkgPublicShares),
+	/// Private shares
+	PrivateShares(DkgPrivateShares),
+	/// Complaint
+	Complaint(DkgComplaint),
+	/// End of the ceremony
+	End(DkgEnd),
+}
+
+impl DkgMessage {
+	/// ID of the ceremony of the message
+	pub fn dkg_id(&self) -> u64 {
+		match self {
+			Self::PublicShares(public_shares) => public_shares.dkg_id,
+			Self::PrivateShares(private_shares) => private_shares.dkg_id,
+			Self::Complaint(complaint) => complaint.dkg_id,
+			Self::End(end) => end.dkg_id,
+		}
+	}
+}
+
+/// State of a participant in a ceremony
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DkgState {
+	/// No ceremony started
+	Idle,
+	/// Waiting for the public shares of the other participants
+	PublicShares,
+	/// Waiting for the private shares of the other participants
+	PrivateShares,
+	/// The key shares are computed
+	Complete,
+	/// A participant complained
+	Failed,
+}
+
+/// Participant of distributed key generation ceremonies
+pub struct DkgParticipant {
+	signer_id: u32,
+	key_ids: Vec<u32>,
+	key_count: u32,
+	threshold: u32,
+	dkg_id: u64,
+	state: DkgState,
+	parties: Vec<v1::Party>,
+	commitments: BTreeMap<u32, PolyCommitment>,
+	signer_key_ids: BTreeMap<u32, Vec<u32>>,
+	shares: HashMap<u32, HashMap<u32, Scalar>>,
+	share_senders: BTreeSet<u32>,
+	accused_signer_ids: BTreeSet<u32>,
+	pending: Vec<DkgMessage>,
+	key_shares: Option<KeyShares>,
+}
+
+impl DkgParticipant {
+	/// Create a participant holding the key shares of the IDs, out of the
+	/// `key_count` key shares of the group of which `threshold` are needed to
+	/// sign
+	pub fn new(
+		signer_id: u32,
+		key_ids: Vec<u32>,
+		key_count: u32,
+		threshold: u32,
+	) -> SBTCResult<Self> {
+		if key_ids.is_empty() || key_ids.iter().any(|id| *id >= key_count) {
+			return Err(SBTCError::SignerError(format!(
+				"Key share IDs must be below {}",
+				key_count
+			)));
+		}
+
+		if threshold == 0 || threshold > key_count {
+			return Err(SBTCError::SignerError(format!(
+				"Threshold must be between 1 and {}",
+				key_count
+			)));
+		}
+
+		Ok(Self {
+			signer_id,
+			key_ids,
+			key_count,
+			threshold,
+			dkg_id: 0,
+			state: DkgState::Idle,
+			parties: vec![],
+			commitments: BTreeMap::new(),
+			signer_key_ids: BTreeMap::new(),
+			shares: HashMap::new(),
+			share_senders: BTreeSet::new(),
+			accused_signer_ids: BTreeSet::new(),
+			pending: vec![],
+			key_shares: None,
+		})
+	}
+
+	/// State of the participant in the current ceremony
+	pub fn state(&self) -> DkgState {
+		self.state
+	}
+
+	/// Key shares computed by the last complete ceremony
+	pub fn key_shares(&self) -> Option<&KeyShares> {
+		self.key_shares.as_ref()
+	}
+
+	/// Participants accused by a complaint in the current ceremony
+	pub fn accused_signer_ids(&self) -> Vec<u32> {
+		self.accused_signer_ids.iter().copied().collect()
+	}
+
+	/// Start a ceremony with fresh polynomials, returning the messages to send
+	pub fn start(&mut self, dkg_id: u64) -> SBTCResult<Vec<DkgMessage>> {
+		self.dkg_id = dkg_id;
+		self.state = DkgState::PublicShares;
+		self.parties = self
+			.key_ids
+			.iter()
+			.map(|id| {
+				v1::Party::new(*id, self.key_count, self.threshold, &mut OsRng)
+			})
+			.collect();
+		self.commitments.clear();
+		self.signer_key_ids.clear();
+		self.shares.clear();
+		self.share_senders.clear();
+		self.accused_signer_ids.clear();
+		self.pending.clear();
+
+		let public_shares = DkgPublicShares {
+			dkg_id,
+			signer_id: self.signer_id,
+			commitments: self
+				.parties
+				.iter()
+				.map(|party| (party.id, party.get_poly_commitment(&mut OsRng)))
+				.collect(),
+		};
+
+		let mut messages = self.receive_public_shares(&public_shares)?;
+		messages.insert(0, DkgMessage::PublicShares(public_shares));
+
+		Ok(messages)
+	}
+
+	/// Process a message of another participant, returning the messages to
+	/// send in response
+	pub fn receive(
+		&mut self,
+		message: &DkgMessage,
+	) -> SBTCResult<Vec<DkgMessage>> {
+		// The rest of a failed ceremony is irrelevant
+		if self.state == DkgState::Failed && message.dkg_id() == self.dkg_id {
+			return Ok(vec![]);
+		}
+
+		match message {
+			DkgMessage::PublicShares(public_shares) => {
+				self.receive_public_shares(public_shares)
+			}
+			DkgMessage::PrivateShares(private_shares) => {
+				self.receive_private_shares(private_shares)
+			}
+			DkgMessage::Complaint(complaint) => {
+				self.receive_complaint(complaint);
+				Ok(vec![])
+			}
+			DkgMessage::End(end) => self.receive_end(end),
+		}
+	}
+
+	fn receive_public_shares(
+		&mut self,
+		public_shares: &DkgPublicShares,
+	) -> SBTCResult<Vec<DkgMessage>> {
+		self.check_round(public_shares.dkg_id, DkgState::PublicShares)?;
+
+		if self.signer_key_ids.contains_key(&public_shares.signer_id) {
+			return Err(SBTCError::SignerError(format!(
+				"Duplicate public shares of signer {}",
+				public_shares.signer_id
+			)));
+		}
+
+		let mut key_ids = vec![];
+
+		for (key_id, commitment) in &public_shares.commitments {
+			if !self.is_valid_commitment(*key_id, commitment) {
+				self.accused_signer_ids.insert(public_shares.signer_id);
+				continue;
+			}
+
+			self.commitments.insert(*key_id, commitment.clone());
+			key_ids.push(*key_id);
+		}
+
+		self.signer_key_ids.insert(public_shares.signer_id, key_ids);
+
+		if !self.accused_signer_ids.is_empty() {
+			return Ok(vec![self.complain()]);
+		}
+
+		if self.commitments.len() < self.key_count as usize {
+			return Ok(vec![]);
+		}
+
+		self.state = DkgState::PrivateShares;
+
+		let mut messages = vec![];
+
+		for (recipient_id, recipient_key_ids) in self.signer_key_ids.clone() {
+			let private_shares = DkgPrivateShares {
+				dkg_id: self.dkg_id,
+				signer_id: self.signer_id,
+				recipient_id,
+				shares: self
+					.parties
+					.iter()
+					.map(|party| {
+						let shares = party.get_shares();
+
+						(
+							party.id,
+							recipient_key_ids
+								.iter()
+								.map(|key_id| (*key_id, shares[key_id]))
+								.collect(),
+						)
+					})
+					.collect(),
+			};
+
+			if recipient_id == self.signer_id {
+				messages.extend(self.receive_private_shares(&private_shares)?);
+			} else {
+				messages.push(DkgMessage::PrivateShares(private_shares));
+			}
+		}
+
+		messages.extend(self.replay_pending()?);
+
+		Ok(messages)
+	}
+
+	fn is_valid_commitment(
+		&self,
+		key_id: u32,
+		commitment: &PolyCommitment,
+	) -> bool {
+		key_id < self.key_count
+			&& !self.commitments.contains_key(&key_id)
+			&& commitment.A.len() == self.threshold as usize
+			&& commitment.id.id == compute::id(key_id)
+			&& commitment.verify()
+	}
+
+	fn receive_private_shares(
+		&mut self,
+		private_shares: &DkgPrivateShares,
+	) -> SBTCResult<Vec<DkgMessage>> {
+		// Signers having all the public shares before this one send their
+		// private shares early
+		if private_shares.dkg_id == self.dkg_id
+			&& self.state == DkgState::PublicShares
+		{
+			self.pending
+				.push(DkgMessage::PrivateShares(private_shares.clone()));
+
+			return Ok(vec![]);
+		}
+
+		self.check_round(private_shares.dkg_id, DkgState::PrivateShares)?;
+
+		let sender_id = private_shares.signer_id;
+
+		if private_shares.recipient_id != self.signer_id {
+			return Err(SBTCError::SignerError(format!(
+				"Private shares of signer {} are for signer {}",
+				sender_id, private_shares.recipient_id
+			)));
+		}
+
+		let Some(sender_key_ids) = self.signer_key_ids.get(&sender_id) else {
+			return Err(SBTCError::SignerError(format!(
+				"Private shares of unknown signer {}",
+				sender_id
+			)));
+		};
+
+		if !self.share_senders.insert(sender_id) {
+			return Err(SBTCError::SignerError(format!(
+				"Duplicate private shares of signer {}",
+				sender_id
+			)));
+		}
+
+		let covers_key_ids = |shares: &[(u32, Vec<(u32, Scalar)>)]| {
+			let senders: BTreeSet<u32> =
+				shares.iter().map(|(key_id, _)| *key_id).collect();
+
+			senders == sender_key_ids.iter().copied().collect()
+				&& shares.iter().all(|(_, shares)| {
+					let recipients: BTreeSet<u32> =
+						shares.iter().map(|(key_id, _)| *key_id).collect();
+
+					recipients == self.key_ids.iter().copied().collect()
+				})
+		};
+
+		if !covers_key_ids(&private_shares.shares) {
+			self.accused_signer_ids.insert(sender_id);
+
+			return Ok(vec![self.complain()]);
+		}
+
+		for (sender_key_id, shares) in &private_shares.shares {
+			for (key_id, share) in shares {
+				self.shares
+					.entry(*key_id)
+					.or_default()
+					.insert(*sender_key_id, *share);
+			}
+		}
+
+		if self.share_senders.len() < self.signer_key_ids.len() {
+			return Ok(vec![]);
+		}
+
+		self.compute_key_shares()
+	}
+
+	fn compute_key_shares(&mut self) -> SBTCResult<Vec<DkgMessage>> {
+		let commitments: Vec<PolyCommitment> =
+			self.commitments.values().cloned().collect();
+
+		for party in self.parties.iter_mut() {
+			let shares = self.shares.remove(&party.id).unwrap_or_default();
+
+			match party
+				.compute_secret(shares.into_iter().collect(), &commitments)
+			{
+				Ok(()) => {}
+				Err(DkgError::BadIds(key_ids))
+				| Err(DkgError::BadShares(key_ids)) => {
+					for key_id in key_ids {
+						let owner = self
+							.signer_key_ids
+							.iter()
+							.find(|(_, key_ids)| key_ids.contains(&key_id))
+							.map(|(signer_id, _)| *signer_id);
+
+						self.accused_signer_ids.extend(owner);
+					}
+				}
+				Err(err) => {
+					return Err(SBTCError::SignerError(err.to_string()))
+				}
+			}
+		}
+
+		if !self.accused_signer_ids.is_empty() {
+			return Ok(vec![self.complain()]);
+		}
+
+		let key_shares =
+			KeyShares::from_parties(self.signer_id, &self.parties)?;
+		let end = DkgEnd {
+			dkg_id: self.dkg_id,
+			signer_id: self.signer_id,
+			group_key: key_shares.group_key,
+		};
+
+		self.state = DkgState::Complete;
+		self.key_shares = Some(key_shares);
+
+		let mut messages = vec![DkgMessage::End(end)];
+		messages.extend(self.replay_pending()?);
+
+		Ok(messages)
+	}
+
+	fn receive_complaint(&mut self, complaint: &DkgComplaint) {
+		if complaint.dkg_id != self.dkg_id {
+			return;
+		}
+
+		self.accused_signer_ids
+			.extend(complaint.accused_signer_ids.iter().copied());
+		self.fail();
+	}
+
+	fn receive_end(&mut self, end: &DkgEnd) -> SBTCResult<Vec<DkgMessage>> {
+		if end.dkg_id != self.dkg_id {
+			return Ok(vec![]);
+		}
+
+		if matches!(
+			self.state,
+			DkgState::PublicShares | DkgState::PrivateShares
+		) {
+			self.pending.push(DkgMessage::End(end.clone()));
+
+			return Ok(vec![]);
+		}
+
+		if self.state != DkgState::Complete {
+			return Ok(vec![]);
+		}
+
+		let agrees = self
+			.key_shares
+			.as_ref()
+			.is_some_and(|key_shares| key_shares.group_key == end.group_key);
+
+		if agrees {
+			return Ok(vec![]);
+		}
+
+		self.accused_signer_ids.insert(end.signer_id);
+
+		Ok(vec![self.complain()])
+	}
+
+	/// Process the messages received before the state they are expected in
+	fn replay_pending(&mut self) -> SBTCResult<Vec<DkgMessage>> {
+		let mut messages = vec![];
+
+		for message in std::mem::take(&mut self.pending) {
+			messages.extend(self.receive(&message)?);
+		}
+
+		Ok(messages)
+	}
+
+	fn check_round(&self, dkg_id: u64, state: DkgState) -> SBTCResult<()> {
+		if dkg_id != self.dkg_id || self.state != state {
+			return Err(SBTCError::SignerError(format!(
+				"Unexpected message of ceremony {} in state {:?}",
+				dkg_id, self.state
+			)));
+		}
+
+		Ok(())
+	}
+
+	fn complain(&mut self) -> DkgMessage {
+		self.fail();
+
+		DkgMessage::Complaint(DkgComplaint {
+			dkg_id: self.dkg_id,
+			signer_id: self.signer_id,
+			accused_signer_ids: self.accused_signer_ids(),
+		})
+	}
+
+	fn fail(&mut self) {
+		// The key shares of a failed ceremony must never be used
+		if self.state == DkgState::Complete {
+			self.key_shares = None;
+		}
+
+		self.state = DkgState::Failed;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::secp256k1::{Message, Secp256k1};
+
+	use super::*;
+	use crate::signer::frost::{
+		Coordinator, KeyShareParticipant, ThresholdCoordinator,
+	};
+
+	fn participants() -> Vec<DkgParticipant> {
+		vec![
+			DkgParticipant::new(0, vec![0, 1], 4, 3).unwrap(),
+			DkgParticipant::new(1, vec![2], 4, 3).unwrap(),
+			DkgParticipant::new(2, vec![3], 4, 3).unwrap(),
+		]
+	}
+
+	/// Delivers the messages until none is left, applying the tamper function
+	/// to every message first
+	fn run_ceremony(
+		participants: &mut [DkgParticipant],
+		tamper: impl Fn(&mut DkgMessage),
+	) {
+		let mut queue: Vec<(u32, DkgMessage)> = vec![];
+
+		for participant in participants.iter_mut() {
+			for message in participant.start(1).unwrap() {
+				queue.push((participant.signer_id, message));
+			}
+		}
+
+		while !queue.is_empty() {
+			let (sender_id, mut message) = queue.remove(0);
+			tamper(&mut message);
+
+			for participant in participants.iter_mut() {
+				let is_recipient = match &message {
+					DkgMessage::PrivateShares(private_shares) => {
+						private_shares.recipient_id == participant.signer_id
+					}
+					_ => participant.signer_id != sender_id,
+				};
+
+				if !is_recipient {
+					continue;
+				}
+
+				for response in participant.receive(&message).unwrap() {
+					queue.push((participant.signer_id, response));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn should_generate_key_shares_signing_for_the_group_key() {
+		let mut participants = participants();
+		run_ceremony(&mut participants, |_| {});
+
+		assert!(participants
+			.iter()
+			.all(|participant| participant.state() == DkgState::Complete));
+
+		let group_key = participants[0].key_shares().unwrap().group_key;
+
+		assert!(participants.iter().all(|participant| {
+			participant.key_shares().unwrap().group_key == group_key
+		}));
+
+		let mut coordinator = ThresholdCoordinator::new(
+			group_key,
+			3,
+			participants
+				.iter()
+				.map(|participant| {
+					KeyShareParticipant::new(
+						participant.key_shares().unwrap().clone(),
+					)
+				})
+				.collect(),
+		);
+
+		let signature = coordinator.sign(&[7; 32]).unwrap();
+
+		Secp256k1::verification_only()
+			.verify_schnorr(
+				&signature,
+				&Message::from_slice(&[7; 32]).unwrap(),
+				&coordinator.output_key().unwrap(),
+			)
+			.unwrap();
+	}
+
+	#[test]
+	fn should_complain_against_invalid_private_shares() {
+		let mut participants = participants();
+		run_ceremony(&mut participants, |message| {
+			if let DkgMessage::PrivateShares(private_shares) = message {
+				if private_shares.signer_id == 1 {
+					private_shares.shares[0].1[0].1 += Scalar::from(1);
+				}
+			}
+		});
+
+		assert!(participants.iter().all(|participant| {
+			participant.state() == DkgState::Failed
+				&& participant.accused_signer_ids() == vec![1]
+				&& participant.key_shares().is_none()
+		}));
+	}
+
+	#[test]
+	fn should_reject_messages_of_another_ceremony() {
+		let mut participants = participants();
+		let messages = participants[1].start(2).unwrap();
+		participants[0].start(1).unwrap();
+
+		assert!(matches!(
+			participants[0].receive(&messages[0]),
+			Err(SBTCError::SignerError(_))
+		));
+	}
+}
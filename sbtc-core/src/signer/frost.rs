@@ -0,0 +1,711 @@
+//! Threshold signing of taproot key path spends with FROST, so that no single
+//! machine holds the private key of the sBTC wallet.
+//!
+//! The participants hold the key shares produced by a WSTS distributed key
+//! generation. A signing round has two steps, driven by the coordinator:
+//!
+//! 1. every participant commits to fresh nonces for its key shares
+//! 2. the participants of a set of nonces covering the threshold of key shares
+//!    return their signature shares
+//!
+//! The coordinator sums the signature shares into a BIP 340 signature for the
+//! taproot output key, which is the group key tweaked without a script tree as
+//! defined by BIP 341, and verifies it before returning it.
+
+use std::collections::HashMap;
+
+use bdk::bitcoin::{
+	hashes::Hash,
+	secp256k1::{self, schnorr, Secp256k1, XOnlyPublicKey},
+	util::{
+		sighash::{Prevouts, SighashCache},
+		taproot::TapTweakHash,
+	},
+	Address as BitcoinAddress, Network, SchnorrSighashType, Transaction, TxOut,
+	Witness,
+};
+use p256k1::{
+	point::{Compressed, Point, G},
+	scalar::Scalar,
+};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "remote-signer")]
+use url::Url;
+use wsts::{
+	common::{Nonce, PublicNonce, SignatureShare},
+	compute, v1,
+};
+
+#[cfg(feature = "remote-signer")]
+use crate::operations::transaction_signer::service_url;
+use crate::{SBTCError, SBTCResult};
+
+/// Request of the coordinator for the nonces of a signing round
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceRequest {
+	/// ID of the signing round
+	pub sign_id: u64,
+	/// The signed message, a taproot sighash
+	pub message: [u8; 32],
+}
+
+/// Nonces of a participant for a signing round
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceResponse {
+	/// ID of the signing round
+	pub sign_id: u64,
+	/// ID of the participant
+	pub signer_id: u32,
+	/// IDs of the key shares of the participant
+	pub key_ids: Vec<u32>,
+	/// Public nonces of the key shares, in the order of their IDs
+	pub nonces: Vec<PublicNonce>,
+}
+
+/// Request of the coordinator for the signature shares of a signing round
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureShareRequest {
+	/// ID of the signing round
+	pub sign_id: u64,
+	/// The signed message, a taproot sighash
+	pub message: [u8; 32],
+	/// Nonces of the participants taking part in the signature
+	pub nonce_responses: Vec<NonceResponse>,
+}
+
+/// Signature shares of a participant for a signing round
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignatureShareResponse {
+	/// ID of the signing round
+	pub sign_id: u64,
+	/// ID of the participant
+	pub signer_id: u32,
+	/// Signature shares of the key shares of the participant
+	pub signature_shares: Vec<SignatureShare>,
+}
+
+/// Message exchanged between the coordinator and the participants of a
+/// signing round
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SigningMessage {
+	/// Nonce request
+	NonceRequest(NonceRequest),
+	/// Nonce response
+	NonceResponse(NonceResponse),
+	/// Signature share request
+	SignatureShareRequest(SignatureShareRequest),
+	/// Signature share response
+	SignatureShareResponse(SignatureShareResponse),
+}
+
+/// Participant of a threshold signing round
+pub trait Participant {
+	/// Commit to fresh nonces for the signing round
+	fn nonces(&mut self, request: &NonceRequest) -> SBTCResult<NonceResponse>;
+
+	/// Sign the message with the nonces committed to for the signing round
+	fn signature_shares(
+		&mut self,
+		request: &SignatureShareRequest,
+	) -> SBTCResult<SignatureShareResponse>;
+}
+
+/// Coordinator of threshold signing rounds
+pub trait Coordinator {
+	/// Run a signing round for the taproot sighash
+	fn sign(&mut self, message: &[u8; 32]) -> SBTCResult<schnorr::Signature>;
+}
+
+/// Key shares of a participant, the output of a distributed key generation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyShares {
+	/// ID of the participant
+	pub signer_id: u32,
+	/// Group key of the signers
+	pub group_key: Point,
+	/// IDs and private keys of the key shares of the participant
+	pub private_keys: Vec<(u32, Scalar)>,
+}
+
+impl KeyShares {
+	/// Key shares of the WSTS parties, after the distributed key generation
+	/// computed their secrets
+	pub fn from_parties(
+		signer_id: u32,
+		parties: &[v1::Party],
+	) -> SBTCResult<Self> {
+		let group_key = parties
+			.first()
+			.ok_or(SBTCError::SignerError(
+				"A participant needs at least one key share".to_string(),
+			))?
+			.group_key;
+
+		if parties.iter().any(|party| party.group_key != group_key) {
+			return Err(SBTCError::SignerError(
+				"Key shares are of different group keys".to_string(),
+			));
+		}
+
+		Ok(Self {
+			signer_id,
+			group_key,
+			private_keys: parties
+				.iter()
+				.map(|party| (party.id, party.save().private_key))
+				.collect(),
+		})
+	}
+}
+
+/// Participant holding key shares of the group key in memory
+pub struct KeyShareParticipant {
+	key_shares: KeyShares,
+	nonces: HashMap<u64, Vec<Nonce>>,
+}
+
+impl KeyShareParticipant {
+	/// Create a participant signing with the key shares
+	pub fn new(key_shares: KeyShares) -> Self {
+		Self {
+			key_shares,
+			nonces: HashMap::new(),
+		}
+	}
+}
+
+impl Participant for KeyShareParticipant {
+	fn nonces(&mut self, request: &NonceRequest) -> SBTCResult<NonceResponse> {
+		let nonces: Vec<Nonce> = self
+			.key_shares
+			.private_keys
+			.iter()
+			.map(|_| Nonce::random(&mut OsRng))
+			.collect();
+
+		let response = NonceResponse {
+			sign_id: request.sign_id,
+			signer_id: self.key_shares.signer_id,
+			key_ids: self
+				.key_shares
+				.private_keys
+				.iter()
+				.map(|(id, _)| *id)
+				.collect(),
+			nonces: nonces.iter().map(PublicNonce::from).collect(),
+		};
+
+		self.nonces.insert(request.sign_id, nonces);
+
+		Ok(response)
+	}
+
+	fn signature_shares(
+		&mut self,
+		request: &SignatureShareRequest,
+	) -> SBTCResult<SignatureShareResponse> {
+		// Nonces are never reused, even if the round fails
+		let nonces = self.nonces.remove(&request.sign_id).ok_or(
+			SBTCError::SignerError(format!(
+				"No nonces for signing round {}",
+				request.sign_id
+			)),
+		)?;
+
+		let round = SigningRound::new(
+			&self.key_shares.group_key,
+			&request.message,
+			&request.nonce_responses,
+		)?;
+
+		let signature_shares = self
+			.key_shares
+			.private_keys
+			.iter()
+			.zip(nonces)
+			.map(|((key_id, private_key), nonce)| SignatureShare {
+				id: *key_id,
+				z_i: round.signature_share(*key_id, private_key, &nonce),
+				key_ids: vec![*key_id],
+			})
+			.collect();
+
+		Ok(SignatureShareResponse {
+			sign_id: request.sign_id,
+			signer_id: self.key_shares.signer_id,
+			signature_shares,
+		})
+	}
+}
+
+/// Participant reached over HTTP, which POSTs the requests as JSON to
+/// `<url>/nonces` and `<url>/signature-shares`
+#[cfg(feature = "remote-signer")]
+#[derive(Debug, Clone)]
+pub struct RemoteParticipant {
+	url: Url,
+	client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "remote-signer")]
+impl RemoteParticipant {
+	/// Create a participant served at the URL
+	pub fn new(url: Url) -> Self {
+		Self::with_client(url, reqwest::blocking::Client::new())
+	}
+
+	/// Create a participant served at the URL, reached through the client,
+	/// such as one configured with a proxy
+	pub fn with_client(url: Url, client: reqwest::blocking::Client) -> Self {
+		Self { url, client }
+	}
+
+	fn post<Req: Serialize, Res: for<'de> Deserialize<'de>>(
+		&self,
+		path: &str,
+		request: &Req,
+	) -> SBTCResult<Res> {
+		let url = service_url(&self.url, path)?;
+
+		self.client
+			.post(url)
+			.json(request)
+			.send()
+			.and_then(|response| response.error_for_status())
+			.and_then(|response| response.json())
+			.map_err(|err| SBTCError::SignerError(err.to_string()))
+	}
+}
+
+#[cfg(feature = "remote-signer")]
+impl Participant for RemoteParticipant {
+	fn nonces(&mut self, request: &NonceRequest) -> SBTCResult<NonceResponse> {
+		self.post("nonces", request)
+	}
+
+	fn signature_shares(
+		&mut self,
+		request: &SignatureShareRequest,
+	) -> SBTCResult<SignatureShareResponse> {
+		self.post("signature-shares", request)
+	}
+}
+
+/// Coordinator running the signing rounds with its participants
+pub struct ThresholdCoordinator<P> {
+	group_key: Point,
+	threshold: u32,
+	participants: Vec<P>,
+	next_sign_id: u64,
+}
+
+impl<P: Participant> ThresholdCoordinator<P> {
+	/// Create a coordinator of the participants, which needs signature shares
+	/// of `threshold` keys to sign for the group key
+	pub fn new(group_key: Point, threshold: u32, participants: Vec<P>) -> Self {
+		Self {
+			group_key,
+			threshold,
+			participants,
+			next_sign_id: 0,
+		}
+	}
+
+	/// Taproot output key of the group key
+	pub fn output_key(&self) -> SBTCResult<XOnlyPublicKey> {
+		output_key(&self.group_key)
+	}
+}
+
+impl<P: Participant> Coordinator for ThresholdCoordinator<P> {
+	fn sign(&mut self, message: &[u8; 32]) -> SBTCResult<schnorr::Signature> {
+		let sign_id = self.next_sign_id;
+		self.next_sign_id += 1;
+
+		let nonce_request = NonceRequest {
+			sign_id,
+			message: *message,
+		};
+
+		// Unreachable participants are left out of the round
+		let mut key_count = 0;
+		let mut signers = vec![];
+		let mut nonce_responses = vec![];
+
+		for (index, participant) in self.participants.iter_mut().enumerate() {
+			if key_count >= self.threshold as usize {
+				break;
+			}
+
+			if let Ok(response) = participant.nonces(&nonce_request) {
+				key_count += response.key_ids.len();
+				signers.push(index);
+				nonce_responses.push(response);
+			}
+		}
+
+		if key_count < self.threshold as usize {
+			return Err(SBTCError::SignerError(format!(
+				"Only {} of the {} keys needed to sign answered",
+				key_count, self.threshold
+			)));
+		}
+
+		let round =
+			SigningRound::new(&self.group_key, message, &nonce_responses)?;
+
+		let share_request = SignatureShareRequest {
+			sign_id,
+			message: *message,
+			nonce_responses,
+		};
+
+		let mut z = Scalar::from(0);
+
+		for index in signers {
+			let response =
+				self.participants[index].signature_shares(&share_request)?;
+
+			for share in response.signature_shares {
+				z += share.z_i;
+			}
+		}
+
+		round.signature(z)
+	}
+}
+
+/// Public values of a signing round, from which the participants compute
+/// their signature shares and the coordinator the signature
+#[allow(non_snake_case)]
+struct SigningRound<'a> {
+	message: &'a [u8; 32],
+	key_ids: Vec<u32>,
+	nonces: Vec<PublicNonce>,
+	R: Point,
+	Q: Point,
+	tweak: Scalar,
+	challenge: Scalar,
+	// BIP 340 signs for the points with an even Y coordinate, so the secrets
+	// of the points with an odd one are negated
+	negate_nonces: bool,
+	negate_key: bool,
+	negate_output_key: bool,
+}
+
+impl<'a> SigningRound<'a> {
+	#[allow(non_snake_case)]
+	fn new(
+		group_key: &Point,
+		message: &'a [u8; 32],
+		nonce_responses: &[NonceResponse],
+	) -> SBTCResult<Self> {
+		let key_ids: Vec<u32> = nonce_responses
+			.iter()
+			.flat_map(|response| response.key_ids.iter().copied())
+			.collect();
+		let nonces: Vec<PublicNonce> = nonce_responses
+			.iter()
+			.flat_map(|response| response.nonces.iter().cloned())
+			.collect();
+
+		if key_ids.len() != nonces.len() {
+			return Err(SBTCError::SignerError(
+				"Every key share needs exactly one nonce".to_string(),
+			));
+		}
+
+		let (_, R) = compute::intermediate(message, &key_ids, &nonces);
+
+		let negate_key = !group_key.has_even_y();
+		let P = if negate_key { -*group_key } else { *group_key };
+		let tweak = tap_tweak(group_key)?;
+		let Q = P + tweak * G;
+
+		Ok(Self {
+			message,
+			challenge: compute::challenge(&Q, &R, message),
+			key_ids,
+			nonces,
+			R,
+			Q,
+			tweak,
+			negate_nonces: !R.has_even_y(),
+			negate_key,
+			negate_output_key: !Q.has_even_y(),
+		})
+	}
+
+	fn signature_share(
+		&self,
+		key_id: u32,
+		private_key: &Scalar,
+		nonce: &Nonce,
+	) -> Scalar {
+		let binding =
+			compute::binding(&compute::id(key_id), &self.nonces, self.message);
+
+		let mut nonce_share = nonce.d + nonce.e * binding;
+		let mut key_share = self.challenge
+			* private_key
+			* compute::lambda(key_id, &self.key_ids);
+
+		if self.negate_nonces {
+			nonce_share = -nonce_share;
+		}
+
+		if self.negate_key != self.negate_output_key {
+			key_share = -key_share;
+		}
+
+		nonce_share + key_share
+	}
+
+	fn signature(&self, z: Scalar) -> SBTCResult<schnorr::Signature> {
+		let mut tweak_share = self.challenge * self.tweak;
+
+		if self.negate_output_key {
+			tweak_share = -tweak_share;
+		}
+
+		let mut bytes = [0u8; 64];
+		bytes[..32].copy_from_slice(&self.R.x().to_bytes());
+		bytes[32..].copy_from_slice(&(z + tweak_share).to_bytes());
+
+		let signature = schnorr::Signature::from_slice(&bytes)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+
+		let message = secp256k1::Message::from_slice(self.message)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+
+		Secp256k1::verification_only()
+			.verify_schnorr(&signature, &message, &x_only(&self.Q)?)
+			.map_err(|_| {
+				SBTCError::SignerError(
+					"Signature shares do not add up to a valid signature"
+						.to_string(),
+				)
+			})?;
+
+		Ok(signature)
+	}
+}
+
+fn x_only(point: &Point) -> SBTCResult<XOnlyPublicKey> {
+	XOnlyPublicKey::from_slice(&point.x().to_bytes())
+		.map_err(|err| SBTCError::SignerError(err.to_string()))
+}
+
+fn tap_tweak(group_key: &Point) -> SBTCResult<Scalar> {
+	let tweak = TapTweakHash::from_key_and_tweak(x_only(group_key)?, None);
+
+	Ok(Scalar::from(tweak.into_inner()))
+}
+
+/// Group key of the public key, as used by the WSTS key shares
+pub fn group_key(public_key: &secp256k1::PublicKey) -> SBTCResult<Point> {
+	let compressed = Compressed::try_from(&public_key.serialize()[..])
+		.map_err(|err| SBTCError::SignerError(format!("{:?}", err)))?;
+
+	Point::try_from(&compressed)
+		.map_err(|err| SBTCError::SignerError(format!("{:?}", err)))
+}
+
+/// Internal key of the taproot output of the group key
+pub fn internal_key(group_key: &Point) -> SBTCResult<XOnlyPublicKey> {
+	x_only(group_key)
+}
+
+/// Taproot output key of the group key, tweaked without a script tree
+pub fn output_key(group_key: &Point) -> SBTCResult<XOnlyPublicKey> {
+	let negate_key = !group_key.has_even_y();
+	let internal_key = if negate_key { -*group_key } else { *group_key };
+
+	x_only(&(internal_key + tap_tweak(group_key)? * G))
+}
+
+/// Taproot address of the group key, the sBTC wallet of the signers
+pub fn wallet_address(
+	group_key: &Point,
+	network: Network,
+) -> SBTCResult<BitcoinAddress> {
+	Ok(BitcoinAddress::p2tr(
+		&Secp256k1::verification_only(),
+		internal_key(group_key)?,
+		None,
+		network,
+	))
+}
+
+/// Sign every input of the transaction spending the taproot outputs of the
+/// group key by their key path. The prevouts are the outputs spent by the
+/// inputs, in the same order.
+pub fn sign_taproot_key_spends(
+	coordinator: &mut impl Coordinator,
+	tx: &mut Transaction,
+	prevouts: &[TxOut],
+) -> SBTCResult<()> {
+	if tx.input.len() != prevouts.len() {
+		return Err(SBTCError::SignerError(
+			"Every input needs the output it spends".to_string(),
+		));
+	}
+
+	let unsigned_tx = tx.clone();
+	let mut sighash_cache = SighashCache::new(&unsigned_tx);
+
+	let witnesses = (0..prevouts.len())
+		.map(|index| {
+			let sighash = sighash_cache
+				.taproot_key_spend_signature_hash(
+					index,
+					&Prevouts::All(prevouts),
+					SchnorrSighashType::Default,
+				)
+				.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+
+			let signature = coordinator.sign(&sighash.into_inner())?;
+
+			Ok(Witness::from_vec(vec![signature.as_ref().to_vec()]))
+		})
+		.collect::<SBTCResult<Vec<_>>>()?;
+
+	for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+		input.witness = witness;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::{OutPoint, PackedLockTime, Script, TxIn};
+
+	use super::*;
+
+	fn key_generation(key_count: u32, threshold: u32) -> Vec<v1::Party> {
+		let mut parties: Vec<v1::Party> = (0..key_count)
+			.map(|id| v1::Party::new(id, key_count, threshold, &mut OsRng))
+			.collect();
+
+		let poly_commitments: Vec<_> = parties
+			.iter()
+			.map(|party| party.get_poly_commitment(&mut OsRng))
+			.collect();
+		let shares: Vec<_> =
+			parties.iter().map(|party| party.get_shares()).collect();
+
+		for party in parties.iter_mut() {
+			let party_shares = shares
+				.iter()
+				.enumerate()
+				.map(|(id, shares)| (id as u32, shares[&party.id]))
+				.collect();
+
+			party
+				.compute_secret(party_shares, &poly_commitments)
+				.unwrap();
+		}
+
+		parties
+	}
+
+	fn coordinator(
+		parties: &[v1::Party],
+		threshold: u32,
+	) -> ThresholdCoordinator<KeyShareParticipant> {
+		// The first signer holds two keys, the others one each
+		let participants = vec![
+			KeyShareParticipant::new(
+				KeyShares::from_parties(0, &parties[..2]).unwrap(),
+			),
+			KeyShareParticipant::new(
+				KeyShares::from_parties(1, &parties[2..3]).unwrap(),
+			),
+			KeyShareParticipant::new(
+				KeyShares::from_parties(2, &parties[3..]).unwrap(),
+			),
+		];
+
+		ThresholdCoordinator::new(parties[0].group_key, threshold, participants)
+	}
+
+	#[test]
+	fn should_sign_for_the_tweaked_group_key() {
+		let parties = key_generation(4, 3);
+		let mut coordinator = coordinator(&parties, 3);
+		let output_key = coordinator.output_key().unwrap();
+
+		// Signing several times covers both parities of the nonces
+		for message in 0..8u8 {
+			let signature = coordinator.sign(&[message; 32]).unwrap();
+
+			Secp256k1::verification_only()
+				.verify_schnorr(
+					&signature,
+					&secp256k1::Message::from_slice(&[message; 32]).unwrap(),
+					&output_key,
+				)
+				.unwrap();
+		}
+	}
+
+	#[test]
+	fn should_sign_taproot_key_spends_of_the_wallet() {
+		let parties = key_generation(4, 3);
+		let mut coordinator = coordinator(&parties, 3);
+		let public_key = secp256k1::PublicKey::from_slice(
+			parties[0].group_key.compress().as_bytes(),
+		)
+		.unwrap();
+
+		assert_eq!(group_key(&public_key).unwrap(), parties[0].group_key);
+
+		let address =
+			wallet_address(&parties[0].group_key, Network::Regtest).unwrap();
+
+		assert_eq!(
+			address.script_pubkey(),
+			Script::new_v1_p2tr_tweaked(
+				bdk::bitcoin::util::schnorr::TweakedPublicKey::dangerous_assume_tweaked(
+					coordinator.output_key().unwrap()
+				)
+			)
+		);
+
+		let prevouts = vec![TxOut {
+			value: 10_000,
+			script_pubkey: address.script_pubkey(),
+		}];
+		let mut tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: OutPoint::null(),
+				..Default::default()
+			}],
+			output: vec![TxOut {
+				value: 9_000,
+				script_pubkey: address.script_pubkey(),
+			}],
+		};
+
+		sign_taproot_key_spends(&mut coordinator, &mut tx, &prevouts).unwrap();
+
+		assert_eq!(tx.input[0].witness.len(), 1);
+		assert_eq!(tx.input[0].witness.to_vec()[0].len(), 64);
+	}
+
+	#[test]
+	fn should_not_sign_below_the_threshold() {
+		let parties = key_generation(4, 4);
+		let mut coordinator = coordinator(&parties, 4);
+		coordinator.participants.pop();
+
+		assert!(matches!(
+			coordinator.sign(&[1; 32]),
+			Err(SBTCError::SignerError(_))
+		));
+	}
+}
@@ -2,6 +2,12 @@
 pub mod config;
 /// sBTC coordinator module
 pub mod coordinator;
+/// sBTC distributed key generation module
+pub mod dkg;
+/// sBTC threshold signing module
+pub mod frost;
+/// sBTC signer networking module
+pub mod network;
 
 use bdk::bitcoin::{
 	Address, Network, PrivateKey, PublicKey, Transaction as BitcoinTransaction,
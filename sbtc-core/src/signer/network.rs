@@ -0,0 +1,827 @@
+//! Networking of the signers, carrying the messages of the distributed key
+//! generation and signing rounds over a TCP mesh of the configured peers.
+//!
+//! Every signer connects to every other one and sends its messages as JSON
+//! lines. The messages are wrapped in envelopes signed by the key of their
+//! sender, and received envelopes are dropped unless:
+//!
+//! - their signature is valid for the public key of the peer they claim to come
+//!   from
+//! - they were not received before
+//! - their sequence number is higher than the one of the last envelope of their
+//!   sender, so that recorded envelopes cannot be replayed
+//!
+//! Messages sent to a single signer, such as the private shares of the
+//! distributed key generation, are encrypted for it with ChaCha20-Poly1305,
+//! under a key agreed by ECDH between the keys of the sender and of the
+//! recipient. Private shares received in the clear are dropped.
+
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	io::{BufRead, BufReader, Read, Write},
+	net::{SocketAddr, TcpListener, TcpStream},
+	sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+	thread,
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bdk::bitcoin::{
+	hashes::{sha256, Hash},
+	secp256k1::{
+		ecdh, schnorr, KeyPair, Message, Parity, Secp256k1, SecretKey,
+		XOnlyPublicKey,
+	},
+};
+use chacha20poly1305::{
+	aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+	ChaCha20Poly1305, Key, Nonce,
+};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	signer::{
+		dkg::{DkgMessage, DkgParticipant, DkgState},
+		frost::{KeyShares, SigningMessage},
+	},
+	SBTCError, SBTCResult,
+};
+
+/// Maximum size of a message on the wire
+pub const MAX_MESSAGE_SIZE: u64 = 1024 * 1024;
+
+/// Number of envelope hashes remembered to drop duplicates
+const SEEN_ENVELOPES_CAPACITY: usize = 4096;
+
+/// Domain of the keys of the sealed messages, hashed with the shared secret
+const SEALED_MESSAGE_DOMAIN: &[u8] = b"sbtc/signer/sealed-message";
+
+/// Message exchanged between signers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SignerMessage {
+	/// Message of a distributed key generation
+	Dkg(DkgMessage),
+	/// Message of a signing round
+	Signing(SigningMessage),
+	/// Message encrypted for its recipient
+	Sealed(SealedMessage),
+}
+
+/// Message encrypted for a single recipient
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedMessage {
+	/// Hex encoded nonce of the encryption
+	pub nonce: String,
+	/// Hex encoded ciphertext of the JSON serialized message
+	pub ciphertext: String,
+}
+
+impl SealedMessage {
+	/// Encrypt the message of the signer for the recipient. The IDs of both
+	/// are authenticated with the message.
+	pub fn seal(
+		key_pair: &KeyPair,
+		signer_id: u32,
+		recipient_public_key: &XOnlyPublicKey,
+		recipient_id: u32,
+		message: &SignerMessage,
+	) -> SBTCResult<Self> {
+		let plaintext = serde_json::to_vec(message)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+		let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+		let ciphertext = cipher(key_pair, recipient_public_key)
+			.encrypt(
+				&nonce,
+				Payload {
+					msg: &plaintext,
+					aad: &associated_data(signer_id, recipient_id),
+				},
+			)
+			.map_err(|_| {
+				SBTCError::SignerError("Could not encrypt message".to_string())
+			})?;
+
+		Ok(Self {
+			nonce: hex::encode(nonce),
+			ciphertext: hex::encode(ciphertext),
+		})
+	}
+
+	/// Decrypt the message the sender sealed for the recipient
+	pub fn open(
+		&self,
+		key_pair: &KeyPair,
+		recipient_id: u32,
+		sender_public_key: &XOnlyPublicKey,
+		signer_id: u32,
+	) -> SBTCResult<SignerMessage> {
+		let nonce = hex::decode(&self.nonce)
+			.ok()
+			.filter(|nonce| nonce.len() == 12)
+			.ok_or(SBTCError::SignerError("Invalid nonce".to_string()))?;
+		let ciphertext = hex::decode(&self.ciphertext)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+
+		let plaintext = cipher(key_pair, sender_public_key)
+			.decrypt(
+				Nonce::from_slice(&nonce),
+				Payload {
+					msg: &ciphertext,
+					aad: &associated_data(signer_id, recipient_id),
+				},
+			)
+			.map_err(|_| {
+				SBTCError::SignerError("Could not decrypt message".to_string())
+			})?;
+
+		serde_json::from_slice(&plaintext)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))
+	}
+}
+
+/// Cipher of the messages between the key pair and the public key. The key is
+/// derived from the x coordinate of the ECDH point, which does not depend on
+/// the parity of the keys.
+fn cipher(key_pair: &KeyPair, public_key: &XOnlyPublicKey) -> ChaCha20Poly1305 {
+	let point = ecdh::shared_secret_point(
+		&public_key.public_key(Parity::Even),
+		&SecretKey::from_keypair(key_pair),
+	);
+	let key =
+		sha256::Hash::hash(&[SEALED_MESSAGE_DOMAIN, &point[..32]].concat());
+
+	ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+}
+
+fn associated_data(signer_id: u32, recipient_id: u32) -> Vec<u8> {
+	[signer_id.to_be_bytes(), recipient_id.to_be_bytes()].concat()
+}
+
+/// Message signed by its sender
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+	/// ID of the sending signer
+	pub signer_id: u32,
+	/// ID of the recipient, if the message is not for every signer
+	pub recipient_id: Option<u32>,
+	/// Sequence number of the message, increasing for every message of the
+	/// sender
+	pub sequence: u64,
+	/// The message
+	pub message: SignerMessage,
+	/// Hex encoded schnorr signature of the sender
+	pub signature: String,
+}
+
+impl Envelope {
+	/// Wrap the message in an envelope signed with the key pair
+	pub fn sign(
+		key_pair: &KeyPair,
+		signer_id: u32,
+		recipient_id: Option<u32>,
+		sequence: u64,
+		message: SignerMessage,
+	) -> SBTCResult<Self> {
+		let digest = Self::digest(signer_id, recipient_id, sequence, &message)?;
+		let signature = Secp256k1::signing_only()
+			.sign_schnorr_no_aux_rand(&digest, key_pair);
+
+		Ok(Self {
+			signer_id,
+			recipient_id,
+			sequence,
+			message,
+			signature: hex::encode(signature.as_ref()),
+		})
+	}
+
+	/// Check that the envelope is signed by the public key
+	pub fn verify(&self, public_key: &XOnlyPublicKey) -> bool {
+		let Ok(digest) = Self::digest(
+			self.signer_id,
+			self.recipient_id,
+			self.sequence,
+			&self.message,
+		) else {
+			return false;
+		};
+
+		hex::decode(&self.signature)
+			.ok()
+			.and_then(|signature| {
+				schnorr::Signature::from_slice(&signature).ok()
+			})
+			.is_some_and(|signature| {
+				Secp256k1::verification_only()
+					.verify_schnorr(&signature, &digest, public_key)
+					.is_ok()
+			})
+	}
+
+	/// Hash identifying the envelope
+	pub fn hash(&self) -> [u8; 32] {
+		sha256::Hash::hash(self.signature.as_bytes()).into_inner()
+	}
+
+	fn digest(
+		signer_id: u32,
+		recipient_id: Option<u32>,
+		sequence: u64,
+		message: &SignerMessage,
+	) -> SBTCResult<Message> {
+		let bytes =
+			serde_json::to_vec(&(signer_id, recipient_id, sequence, message))
+				.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+
+		Message::from_slice(&sha256::Hash::hash(&bytes).into_inner())
+			.map_err(|err| SBTCError::SignerError(err.to_string()))
+	}
+}
+
+/// Filter of duplicate and replayed envelopes
+#[derive(Debug, Default)]
+pub struct ReplayFilter {
+	last_sequences: HashMap<u32, u64>,
+	seen: HashSet<[u8; 32]>,
+	seen_order: VecDeque<[u8; 32]>,
+}
+
+impl ReplayFilter {
+	/// Record the envelope, returning false if it is a duplicate or is older
+	/// than the last envelope of its sender
+	pub fn accept(&mut self, envelope: &Envelope) -> bool {
+		let hash = envelope.hash();
+
+		if self.seen.contains(&hash) {
+			return false;
+		}
+
+		let last_sequence = self.last_sequences.entry(envelope.signer_id);
+		let last_sequence = last_sequence.or_insert(0);
+
+		if envelope.sequence <= *last_sequence {
+			return false;
+		}
+
+		*last_sequence = envelope.sequence;
+
+		if self.seen_order.len() == SEEN_ENVELOPES_CAPACITY {
+			if let Some(oldest) = self.seen_order.pop_front() {
+				self.seen.remove(&oldest);
+			}
+		}
+
+		self.seen.insert(hash);
+		self.seen_order.push_back(hash);
+
+		true
+	}
+}
+
+/// Signer of the mesh
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+	/// ID of the signer
+	pub signer_id: u32,
+	/// Public key the signer signs its envelopes with
+	pub public_key: XOnlyPublicKey,
+	/// Address the signer listens at
+	pub address: SocketAddr,
+}
+
+/// Connection of a signer to the mesh of its peers
+pub struct Network {
+	signer_id: u32,
+	key_pair: KeyPair,
+	peers: Vec<Peer>,
+	connections: HashMap<u32, TcpStream>,
+	sequence: u64,
+	messages: Receiver<(u32, SignerMessage)>,
+}
+
+impl Network {
+	/// Join the mesh, accepting the connections of the peers on the listener
+	pub fn new(
+		listener: TcpListener,
+		signer_id: u32,
+		key_pair: KeyPair,
+		peers: Vec<Peer>,
+	) -> SBTCResult<Self> {
+		let (sender, messages) = mpsc::channel();
+		let public_keys: HashMap<u32, XOnlyPublicKey> = peers
+			.iter()
+			.map(|peer| (peer.signer_id, peer.public_key))
+			.collect();
+
+		thread::spawn(move || {
+			accept_connections(
+				listener,
+				signer_id,
+				key_pair,
+				public_keys,
+				sender,
+			)
+		});
+
+		// Sequence numbers start from the time, so that they keep increasing
+		// across restarts
+		let sequence = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?
+			.as_nanos() as u64;
+
+		Ok(Self {
+			signer_id,
+			key_pair,
+			peers,
+			connections: HashMap::new(),
+			sequence,
+			messages,
+		})
+	}
+
+	/// ID of the signer
+	pub fn signer_id(&self) -> u32 {
+		self.signer_id
+	}
+
+	/// Send the message to every peer
+	pub fn broadcast(&mut self, message: SignerMessage) -> SBTCResult<()> {
+		let envelope = self.envelope(None, message)?;
+
+		for signer_id in self.peer_ids() {
+			if let Err(err) = self.deliver(signer_id, &envelope) {
+				warn!(
+					"Could not send message to signer {}: {}",
+					signer_id, err
+				);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Send the message to the peer only, encrypted for it
+	pub fn send(
+		&mut self,
+		recipient_id: u32,
+		message: SignerMessage,
+	) -> SBTCResult<()> {
+		let sealed_message = SealedMessage::seal(
+			&self.key_pair,
+			self.signer_id,
+			&self.peer(recipient_id)?.public_key,
+			recipient_id,
+			&message,
+		)?;
+		let envelope = self.envelope(
+			Some(recipient_id),
+			SignerMessage::Sealed(sealed_message),
+		)?;
+
+		self.deliver(recipient_id, &envelope)
+	}
+
+	/// Wait for the next message of a peer, up to the timeout
+	pub fn receive(
+		&self,
+		timeout: Duration,
+	) -> SBTCResult<Option<(u32, SignerMessage)>> {
+		match self.messages.recv_timeout(timeout) {
+			Ok(message) => Ok(Some(message)),
+			Err(RecvTimeoutError::Timeout) => Ok(None),
+			Err(RecvTimeoutError::Disconnected) => Err(SBTCError::SignerError(
+				"The listener of the signer stopped".to_string(),
+			)),
+		}
+	}
+
+	/// Run a distributed key generation ceremony with the peers, returning
+	/// the key shares of the signer if it completes before the timeout
+	pub fn run_dkg(
+		&mut self,
+		participant: &mut DkgParticipant,
+		dkg_id: u64,
+		timeout: Duration,
+	) -> SBTCResult<KeyShares> {
+		let deadline = Instant::now() + timeout;
+		let mut end_signer_ids = HashSet::new();
+		let mut outgoing = participant.start(dkg_id)?;
+
+		loop {
+			for message in outgoing.drain(..) {
+				if let DkgMessage::End(_) = &message {
+					end_signer_ids.insert(self.signer_id);
+				}
+
+				match &message {
+					DkgMessage::PrivateShares(private_shares) => self.send(
+						private_shares.recipient_id,
+						SignerMessage::Dkg(message.clone()),
+					)?,
+					_ => self.broadcast(SignerMessage::Dkg(message))?,
+				}
+			}
+
+			match participant.state() {
+				DkgState::Failed => {
+					return Err(SBTCError::SignerError(format!(
+						"Ceremony {} failed, accused signers: {:?}",
+						dkg_id,
+						participant.accused_signer_ids()
+					)))
+				}
+				// Every signer must agree on the group key
+				DkgState::Complete
+					if end_signer_ids.len() == self.peers.len() + 1 =>
+				{
+					return participant.key_shares().cloned().ok_or(
+						SBTCError::SignerError(
+							"Ceremony completed without key shares".to_string(),
+						),
+					);
+				}
+				_ => {}
+			}
+
+			let remaining = deadline.saturating_duration_since(Instant::now());
+
+			if remaining.is_zero() {
+				return Err(SBTCError::SignerError(format!(
+					"Ceremony {} timed out",
+					dkg_id
+				)));
+			}
+
+			let Some((signer_id, SignerMessage::Dkg(message))) =
+				self.receive(remaining)?
+			else {
+				continue;
+			};
+
+			if message.dkg_id() != dkg_id {
+				continue;
+			}
+
+			if let DkgMessage::End(_) = &message {
+				end_signer_ids.insert(signer_id);
+			}
+
+			// Invalid messages of a peer are its problem, not the ceremony's
+			outgoing = match participant.receive(&message) {
+				Ok(outgoing) => outgoing,
+				Err(err) => {
+					warn!("Dropped message of signer {}: {}", signer_id, err);
+					vec![]
+				}
+			};
+		}
+	}
+
+	fn peer(&self, signer_id: u32) -> SBTCResult<&Peer> {
+		self.peers
+			.iter()
+			.find(|peer| peer.signer_id == signer_id)
+			.ok_or(SBTCError::SignerError(format!(
+				"Unknown signer {}",
+				signer_id
+			)))
+	}
+
+	fn peer_ids(&self) -> Vec<u32> {
+		self.peers.iter().map(|peer| peer.signer_id).collect()
+	}
+
+	fn envelope(
+		&mut self,
+		recipient_id: Option<u32>,
+		message: SignerMessage,
+	) -> SBTCResult<Envelope> {
+		self.sequence += 1;
+
+		Envelope::sign(
+			&self.key_pair,
+			self.signer_id,
+			recipient_id,
+			self.sequence,
+			message,
+		)
+	}
+
+	/// Write the envelope to the connection of the peer, connecting again
+	/// once if it broke
+	fn deliver(
+		&mut self,
+		signer_id: u32,
+		envelope: &Envelope,
+	) -> SBTCResult<()> {
+		let mut line = serde_json::to_vec(envelope)
+			.map_err(|err| SBTCError::SignerError(err.to_string()))?;
+		line.push(b'\n');
+
+		for _ in 0..2 {
+			let connection = match self.connections.get_mut(&signer_id) {
+				Some(connection) => connection,
+				None => {
+					let connection =
+						TcpStream::connect(self.peer(signer_id)?.address)
+							.map_err(|err| {
+								SBTCError::SignerError(err.to_string())
+							})?;
+
+					self.connections.entry(signer_id).or_insert(connection)
+				}
+			};
+
+			match connection.write_all(&line) {
+				Ok(()) => return Ok(()),
+				Err(err) => {
+					debug!("Connection to signer {} broke: {}", signer_id, err);
+					self.connections.remove(&signer_id);
+				}
+			}
+		}
+
+		Err(SBTCError::SignerError(format!(
+			"Could not deliver message to signer {}",
+			signer_id
+		)))
+	}
+}
+
+fn accept_connections(
+	listener: TcpListener,
+	signer_id: u32,
+	key_pair: KeyPair,
+	public_keys: HashMap<u32, XOnlyPublicKey>,
+	sender: Sender<(u32, SignerMessage)>,
+) {
+	let (envelopes, received) = mpsc::channel::<Envelope>();
+
+	// Envelopes of every connection go through one filter
+	thread::spawn(move || {
+		let mut filter = ReplayFilter::default();
+
+		for envelope in received {
+			let Some(public_key) = public_keys
+				.get(&envelope.signer_id)
+				.filter(|public_key| envelope.verify(public_key))
+			else {
+				warn!(
+					"Dropped message with an invalid signature of signer {}",
+					envelope.signer_id
+				);
+				continue;
+			};
+			let for_signer = envelope
+				.recipient_id
+				.is_none_or(|recipient_id| recipient_id == signer_id);
+
+			if !for_signer || !filter.accept(&envelope) {
+				continue;
+			}
+
+			let message = match envelope.message {
+				SignerMessage::Sealed(sealed_message) => match sealed_message
+					.open(&key_pair, signer_id, public_key, envelope.signer_id)
+				{
+					Ok(message) => message,
+					Err(err) => {
+						warn!(
+							"Dropped message of signer {}: {}",
+							envelope.signer_id, err
+						);
+						continue;
+					}
+				},
+				SignerMessage::Dkg(DkgMessage::PrivateShares(_)) => {
+					warn!(
+						"Dropped private shares of signer {} sent in the clear",
+						envelope.signer_id
+					);
+					continue;
+				}
+				message => message,
+			};
+
+			if sender.send((envelope.signer_id, message)).is_err() {
+				return;
+			}
+		}
+	});
+
+	for stream in listener.incoming() {
+		let Ok(stream) = stream else {
+			continue;
+		};
+		let envelopes = envelopes.clone();
+
+		thread::spawn(move || read_envelopes(stream, envelopes));
+	}
+}
+
+fn read_envelopes(stream: TcpStream, envelopes: Sender<Envelope>) {
+	let mut reader = BufReader::new(stream);
+
+	loop {
+		let mut line = String::new();
+
+		match (&mut reader).take(MAX_MESSAGE_SIZE).read_line(&mut line) {
+			Ok(0) => return,
+			Ok(_) if !line.ends_with('\n') => {
+				warn!("Closed connection sending an oversized message");
+				return;
+			}
+			Ok(_) => {}
+			Err(_) => return,
+		}
+
+		match serde_json::from_str(&line) {
+			Ok(envelope) => {
+				if envelopes.send(envelope).is_err() {
+					return;
+				}
+			}
+			Err(err) => warn!("Dropped malformed message: {}", err),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use p256k1::scalar::Scalar;
+
+	use super::*;
+	use crate::signer::dkg::{DkgComplaint, DkgPrivateShares};
+
+	fn key_pair(signer_id: u32) -> KeyPair {
+		let secret_key =
+			SecretKey::from_slice(&[signer_id as u8 + 1; 32]).unwrap();
+
+		KeyPair::from_secret_key(&Secp256k1::new(), &secret_key)
+	}
+
+	fn complaint(dkg_id: u64) -> SignerMessage {
+		SignerMessage::Dkg(DkgMessage::Complaint(DkgComplaint {
+			dkg_id,
+			signer_id: 0,
+			accused_signer_ids: vec![1],
+		}))
+	}
+
+	#[test]
+	fn should_authenticate_envelopes_by_the_sender_key() {
+		let envelope =
+			Envelope::sign(&key_pair(0), 0, None, 1, complaint(1)).unwrap();
+
+		assert!(envelope.verify(&key_pair(0).x_only_public_key().0));
+		assert!(!envelope.verify(&key_pair(1).x_only_public_key().0));
+
+		let mut tampered = envelope;
+		tampered.sequence = 2;
+
+		assert!(!tampered.verify(&key_pair(0).x_only_public_key().0));
+	}
+
+	fn public_key(signer_id: u32) -> XOnlyPublicKey {
+		key_pair(signer_id).x_only_public_key().0
+	}
+
+	#[test]
+	fn should_not_reveal_private_shares_in_envelopes() {
+		let share = Scalar::random(&mut OsRng);
+		let message =
+			SignerMessage::Dkg(DkgMessage::PrivateShares(DkgPrivateShares {
+				dkg_id: 1,
+				signer_id: 0,
+				recipient_id: 1,
+				shares: vec![(0, vec![(1, share)])],
+			}));
+		let serialized_share = serde_json::to_string(&share).unwrap();
+
+		let plain_envelope =
+			Envelope::sign(&key_pair(0), 0, Some(1), 1, message.clone())
+				.unwrap();
+
+		assert!(serde_json::to_string(&plain_envelope)
+			.unwrap()
+			.contains(&serialized_share));
+
+		let sealed_message =
+			SealedMessage::seal(&key_pair(0), 0, &public_key(1), 1, &message)
+				.unwrap();
+		let envelope = Envelope::sign(
+			&key_pair(0),
+			0,
+			Some(1),
+			2,
+			SignerMessage::Sealed(sealed_message),
+		)
+		.unwrap();
+		let serialized_envelope = serde_json::to_string(&envelope).unwrap();
+
+		assert!(!serialized_envelope.contains(&serialized_share));
+		assert!(!serialized_envelope.contains(&hex::encode(share.to_bytes())));
+	}
+
+	#[test]
+	fn should_only_open_sealed_messages_for_their_recipient() {
+		let sealed_message = SealedMessage::seal(
+			&key_pair(0),
+			0,
+			&public_key(1),
+			1,
+			&complaint(1),
+		)
+		.unwrap();
+
+		assert!(matches!(
+			sealed_message.open(&key_pair(1), 1, &public_key(0), 0).unwrap(),
+			SignerMessage::Dkg(DkgMessage::Complaint(complaint))
+				if complaint.dkg_id == 1
+		));
+		assert!(sealed_message
+			.open(&key_pair(2), 1, &public_key(0), 0)
+			.is_err());
+		assert!(sealed_message
+			.open(&key_pair(1), 2, &public_key(0), 0)
+			.is_err());
+		assert!(sealed_message
+			.open(&key_pair(1), 1, &public_key(2), 2)
+			.is_err());
+	}
+
+	#[test]
+	fn should_drop_duplicate_and_replayed_envelopes() {
+		let mut filter = ReplayFilter::default();
+		let first =
+			Envelope::sign(&key_pair(0), 0, None, 1, complaint(1)).unwrap();
+		let second =
+			Envelope::sign(&key_pair(0), 0, None, 2, complaint(2)).unwrap();
+
+		assert!(filter.accept(&first));
+		assert!(!filter.accept(&first));
+		assert!(filter.accept(&second));
+		assert!(!filter.accept(
+			&Envelope::sign(&key_pair(0), 0, None, 1, complaint(3)).unwrap()
+		));
+	}
+
+	#[test]
+	fn should_run_distributed_key_generation_over_the_mesh() {
+		let listeners: Vec<TcpListener> = (0..3)
+			.map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+			.collect();
+		let peers: Vec<Peer> = listeners
+			.iter()
+			.enumerate()
+			.map(|(signer_id, listener)| Peer {
+				signer_id: signer_id as u32,
+				public_key: key_pair(signer_id as u32).x_only_public_key().0,
+				address: listener.local_addr().unwrap(),
+			})
+			.collect();
+
+		let handles: Vec<_> = listeners
+			.into_iter()
+			.enumerate()
+			.map(|(signer_id, listener)| {
+				let signer_id = signer_id as u32;
+				let peers = peers
+					.iter()
+					.filter(|peer| peer.signer_id != signer_id)
+					.cloned()
+					.collect();
+
+				thread::spawn(move || {
+					let mut network = Network::new(
+						listener,
+						signer_id,
+						key_pair(signer_id),
+						peers,
+					)
+					.unwrap();
+					let mut participant =
+						DkgParticipant::new(signer_id, vec![signer_id], 3, 2)
+							.unwrap();
+
+					network
+						.run_dkg(&mut participant, 1, Duration::from_secs(30))
+						.unwrap()
+				})
+			})
+			.collect();
+
+		let key_shares: Vec<KeyShares> = handles
+			.into_iter()
+			.map(|handle| handle.join().unwrap())
+			.collect();
+
+		let group_key = key_shares[0].group_key;
+
+		assert!(key_shares
+			.iter()
+			.all(|key_shares| key_shares.group_key == group_key));
+	}
+}
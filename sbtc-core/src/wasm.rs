@@ -0,0 +1,369 @@
+//! JavaScript facade of the construction of sBTC operations, for browser
+//! wallets building deposits and withdrawal requests with the same code that
+//! parses them.
+//!
+//! Binary data is exchanged as hex strings and UTXOs as JSON arrays, so the
+//! facade does not depend on the JavaScript representation of Rust types.
+
+use std::str::FromStr;
+
+use bdk::{
+	bitcoin::{
+		consensus::encode, secp256k1::ecdsa::RecoverableSignature,
+		Address as BitcoinAddress, Network, OutPoint, PublicKey, Script, TxOut,
+		Txid,
+	},
+	FeeRate, KeychainKind, LocalUtxo,
+};
+use serde::Deserialize;
+use stacks_core::{
+	address::{AddressVersion, StacksAddress},
+	codec::Codec,
+	utils::PrincipalData,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::operations::{
+	op_return::{deposit, withdrawal_request},
+	payload::{NetworkPayload, Payload},
+	utils::{build_offline_psbt, OfflineFunding},
+};
+
+/// UTXO of the wallet funding a transaction
+#[derive(Deserialize)]
+struct WasmUtxo {
+	txid: String,
+	vout: u32,
+	value: u64,
+	/// Hex encoded pubkey script of the output
+	script_pubkey: String,
+}
+
+/// Encodes the OP_RETURN payload of a deposit to the recipient, hex encoded
+#[wasm_bindgen(js_name = depositPayload)]
+pub fn deposit_payload(
+	network: &str,
+	recipient: &str,
+) -> Result<String, JsError> {
+	let payload = NetworkPayload {
+		network: parse_network(network)?,
+		payload: Payload::Deposit {
+			recipient: parse_principal(recipient)?,
+		},
+	};
+
+	Ok(hex::encode(payload.serialize_to_vec()))
+}
+
+/// Encodes the OP_RETURN payload of a withdrawal request, hex encoded. The
+/// signature is the 65 byte recoverable signature of the drawee over
+/// [`withdrawal_request_signing_message`].
+#[wasm_bindgen(js_name = withdrawalRequestPayload)]
+pub fn withdrawal_request_payload(
+	network: &str,
+	amount: u64,
+	signature: &str,
+) -> Result<String, JsError> {
+	let payload = NetworkPayload {
+		network: parse_network(network)?,
+		payload: Payload::WithdrawalRequest {
+			amount,
+			signature: parse_signature(signature)?,
+		},
+	};
+
+	Ok(hex::encode(payload.serialize_to_vec()))
+}
+
+/// Hash the drawee signs to request the withdrawal of the amount to the payee,
+/// hex encoded
+#[wasm_bindgen(js_name = withdrawalRequestSigningMessage)]
+pub fn withdrawal_request_signing_message(
+	network: &str,
+	amount: u64,
+	payee_address: &str,
+) -> Result<String, JsError> {
+	let network = parse_network(network)?;
+	let message = withdrawal_request::create_withdrawal_request_signing_message(
+		amount,
+		&parse_address(payee_address, network)?,
+	);
+
+	Ok(hex::encode(message.as_ref()))
+}
+
+/// Derives the single signature Stacks address of a public key
+#[wasm_bindgen(js_name = stacksAddress)]
+pub fn stacks_address(
+	public_key: &str,
+	mainnet: bool,
+) -> Result<String, JsError> {
+	let version = if mainnet {
+		AddressVersion::MainnetSingleSig
+	} else {
+		AddressVersion::TestnetSingleSig
+	};
+
+	Ok(
+		StacksAddress::p2pkh(version, &parse_public_key(public_key)?.inner)
+			.to_string(),
+	)
+}
+
+/// Derives the P2WPKH Bitcoin address of a public key
+#[wasm_bindgen(js_name = bitcoinAddress)]
+pub fn bitcoin_address(
+	public_key: &str,
+	network: &str,
+) -> Result<String, JsError> {
+	Ok(BitcoinAddress::p2wpkh(
+		&parse_public_key(public_key)?,
+		parse_network(network)?,
+	)?
+	.to_string())
+}
+
+/// Builds the unsigned deposit PSBT, hex encoded, spending the UTXOs given as
+/// a JSON array of `{ txid, vout, value, script_pubkey }` objects
+#[wasm_bindgen(js_name = buildDepositPsbt)]
+#[allow(clippy::too_many_arguments)]
+pub fn build_deposit_psbt(
+	network: &str,
+	utxos: &str,
+	change_address: &str,
+	fee_rate: f32,
+	recipient: &str,
+	sbtc_wallet_address: &str,
+	amount: u64,
+) -> Result<String, JsError> {
+	let network = parse_network(network)?;
+	let psbt = deposit::create_offline_psbt(
+		&parse_funding(utxos, change_address, fee_rate, network)?,
+		parse_principal(recipient)?,
+		&parse_address(sbtc_wallet_address, network)?,
+		amount,
+		network,
+	)?;
+
+	Ok(hex::encode(encode::serialize(&psbt)))
+}
+
+/// Builds the unsigned withdrawal request PSBT, hex encoded, spending the
+/// UTXOs given as a JSON array of `{ txid, vout, value, script_pubkey }`
+/// objects
+#[wasm_bindgen(js_name = buildWithdrawalRequestPsbt)]
+#[allow(clippy::too_many_arguments)]
+pub fn build_withdrawal_request_psbt(
+	network: &str,
+	utxos: &str,
+	change_address: &str,
+	fee_rate: f32,
+	signature: &str,
+	payee_address: &str,
+	sbtc_wallet_address: &str,
+	amount: u64,
+	fulfillment_fee: u64,
+) -> Result<String, JsError> {
+	let network = parse_network(network)?;
+	let outputs = withdrawal_request::create_outputs_with_signature(
+		parse_signature(signature)?,
+		&parse_address(payee_address, network)?,
+		&parse_address(sbtc_wallet_address, network)?,
+		amount,
+		fulfillment_fee,
+		network,
+	)?;
+	let psbt = build_offline_psbt(
+		&parse_funding(utxos, change_address, fee_rate, network)?,
+		&outputs,
+	)?;
+
+	Ok(hex::encode(encode::serialize(&psbt)))
+}
+
+fn parse_network(network: &str) -> Result<Network, JsError> {
+	Network::from_str(network)
+		.map_err(|_| JsError::new(&format!("Unknown network: {}", network)))
+}
+
+fn parse_principal(principal: &str) -> Result<PrincipalData, JsError> {
+	Ok(PrincipalData::try_from(principal.to_string())?)
+}
+
+/// Parses the address, which must be one of the network
+fn parse_address(
+	address: &str,
+	network: Network,
+) -> Result<BitcoinAddress, JsError> {
+	let address = BitcoinAddress::from_str(address)?;
+
+	if !address.is_valid_for_network(network) {
+		return Err(JsError::new(&format!(
+			"Address {} is not valid for {}",
+			address, network
+		)));
+	}
+
+	Ok(address)
+}
+
+fn parse_public_key(public_key: &str) -> Result<PublicKey, JsError> {
+	Ok(PublicKey::from_str(public_key)?)
+}
+
+fn parse_signature(signature: &str) -> Result<RecoverableSignature, JsError> {
+	let bytes = hex::decode(signature)?;
+
+	Ok(RecoverableSignature::codec_deserialize(&mut &bytes[..])?)
+}
+
+fn parse_funding(
+	utxos: &str,
+	change_address: &str,
+	fee_rate: f32,
+	network: Network,
+) -> Result<OfflineFunding, JsError> {
+	let utxos: Vec<WasmUtxo> = serde_json::from_str(utxos)?;
+
+	Ok(OfflineFunding {
+		utxos: utxos
+			.into_iter()
+			.map(|utxo| {
+				Ok(LocalUtxo {
+					outpoint: OutPoint::new(
+						Txid::from_str(&utxo.txid)?,
+						utxo.vout,
+					),
+					txout: TxOut {
+						value: utxo.value,
+						script_pubkey: Script::from(hex::decode(
+							utxo.script_pubkey,
+						)?),
+					},
+					keychain: KeychainKind::External,
+					is_spent: false,
+				})
+			})
+			.collect::<Result<_, JsError>>()?,
+		change_address: parse_address(change_address, network)?,
+		fee_rate: FeeRate::from_sat_per_vb(fee_rate),
+	})
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+	use bdk::bitcoin::psbt::PartiallySignedTransaction;
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	use super::*;
+
+	/// Public key of the secret key 1
+	const PUBLIC_KEY: &str =
+		"0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+	/// P2WPKH addresses of the public key, from BIP 173
+	const MAINNET_ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+	const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+	fn ok<T>(res: Result<T, JsError>) -> T {
+		res.map_err(JsValue::from).unwrap()
+	}
+
+	fn utxos(value: u64) -> String {
+		serde_json::json!([{
+			"txid": "0000000000000000000000000000000000000000000000000000000000000001",
+			"vout": 0,
+			"value": value,
+			"script_pubkey": hex::encode(
+				BitcoinAddress::from_str(TESTNET_ADDRESS)
+					.unwrap()
+					.script_pubkey()
+					.as_bytes()
+			),
+		}])
+		.to_string()
+	}
+
+	fn deposit_psbt(
+		change_address: &str,
+		sbtc_wallet_address: &str,
+	) -> Result<String, JsError> {
+		let recipient = ok(stacks_address(PUBLIC_KEY, false));
+
+		build_deposit_psbt(
+			"testnet",
+			&utxos(100_000),
+			change_address,
+			1.0,
+			&recipient,
+			sbtc_wallet_address,
+			10_000,
+		)
+	}
+
+	#[wasm_bindgen_test]
+	fn test_addresses_are_derived_for_the_network() {
+		assert_eq!(ok(bitcoin_address(PUBLIC_KEY, "bitcoin")), MAINNET_ADDRESS);
+		assert_eq!(ok(bitcoin_address(PUBLIC_KEY, "testnet")), TESTNET_ADDRESS);
+		assert!(ok(stacks_address(PUBLIC_KEY, true)).starts_with("SP"));
+		assert!(ok(stacks_address(PUBLIC_KEY, false)).starts_with("ST"));
+		assert!(bitcoin_address("02", "testnet").is_err());
+		assert!(bitcoin_address(PUBLIC_KEY, "dogecoin").is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn test_deposit_payload_round_trips() {
+		let recipient = ok(stacks_address(PUBLIC_KEY, false));
+		let payload =
+			hex::decode(ok(deposit_payload("testnet", &recipient))).unwrap();
+
+		assert_eq!(
+			NetworkPayload::deserialize(&mut &payload[..]).unwrap(),
+			NetworkPayload {
+				network: Network::Testnet,
+				payload: Payload::Deposit {
+					recipient: PrincipalData::try_from(recipient).unwrap(),
+				},
+			}
+		);
+		assert!(deposit_payload("testnet", "not a principal").is_err());
+	}
+
+	#[wasm_bindgen_test]
+	fn test_deposit_psbt_pays_the_sbtc_wallet() {
+		let psbt =
+			hex::decode(ok(deposit_psbt(TESTNET_ADDRESS, TESTNET_ADDRESS)))
+				.unwrap();
+		let psbt: PartiallySignedTransaction =
+			encode::deserialize(&psbt).unwrap();
+		let wallet_script = BitcoinAddress::from_str(TESTNET_ADDRESS)
+			.unwrap()
+			.script_pubkey();
+
+		assert_eq!(psbt.unsigned_tx.output[0].value, 0);
+		assert!(psbt
+			.unsigned_tx
+			.output
+			.iter()
+			.any(|output| output.script_pubkey == wallet_script
+				&& output.value == 10_000));
+	}
+
+	#[wasm_bindgen_test]
+	fn test_addresses_of_another_network_are_rejected() {
+		assert!(deposit_psbt(TESTNET_ADDRESS, MAINNET_ADDRESS).is_err());
+		assert!(deposit_psbt(MAINNET_ADDRESS, TESTNET_ADDRESS).is_err());
+		assert!(withdrawal_request_signing_message(
+			"testnet",
+			10_000,
+			MAINNET_ADDRESS
+		)
+		.is_err());
+		assert!(withdrawal_request_signing_message(
+			"bitcoin",
+			10_000,
+			MAINNET_ADDRESS
+		)
+		.is_ok());
+	}
+}
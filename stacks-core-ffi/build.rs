@@ -0,0 +1,14 @@
+use std::{env, path::PathBuf};
+
+fn main() {
+	let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+
+	// The header only depends on the bindings and the cbindgen config, so it
+	// is not regenerated by the changes of the other files of the package
+	println!("cargo:rerun-if-changed=src");
+	println!("cargo:rerun-if-changed=cbindgen.toml");
+
+	cbindgen::generate(&crate_dir)
+		.expect("Unable to generate the C header")
+		.write_to_file(crate_dir.join("include").join("stacks_core.h"));
+}
@@ -0,0 +1,127 @@
+#ifndef STACKS_CORE_H
+#define STACKS_CORE_H
+
+/* Generated by cbindgen from stacks-core-ffi, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Network of a Stacks address
+typedef enum StacksNetwork {
+  // Mainnet
+  STACKS_NETWORK_MAINNET = 0,
+  // Testnet
+  STACKS_NETWORK_TESTNET = 1,
+} StacksNetwork;
+
+// Result of a call
+typedef enum StacksResultCode {
+  // The call succeeded
+  STACKS_RESULT_CODE_OK = 0,
+  // A required pointer is null
+  STACKS_RESULT_CODE_NULL_POINTER = 1,
+  // A string is not valid UTF-8
+  STACKS_RESULT_CODE_INVALID_UTF8 = 2,
+  // A string contains characters outside of the c32 alphabet
+  STACKS_RESULT_CODE_INVALID_C32 = 3,
+  // The checksum of an address does not match, usually because of a typo
+  STACKS_RESULT_CODE_INVALID_CHECKSUM = 4,
+  // The address version is not a known Stacks address version
+  STACKS_RESULT_CODE_INVALID_VERSION = 5,
+  // The address is malformed or does not encode a 20 byte hash
+  STACKS_RESULT_CODE_INVALID_ADDRESS = 6,
+  // The public key is not a valid compressed or uncompressed key
+  STACKS_RESULT_CODE_INVALID_PUBLIC_KEY = 7,
+  // The network is not one of the [`StacksNetwork`] values
+  STACKS_RESULT_CODE_INVALID_NETWORK = 8,
+} StacksResultCode;
+
+// Bytes allocated by the library, released with [`stacks_bytes_free`]
+typedef struct StacksBytes {
+  // Pointer to the first byte
+  uint8_t *data;
+  // Number of bytes
+  uintptr_t len;
+} StacksBytes;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// C32 encodes the bytes into a string
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes, and `out` to a writable string
+// pointer.
+enum StacksResultCode stacks_c32_encode(const uint8_t *data, uintptr_t len, char **out);
+
+// C32 decodes the string into bytes
+//
+// # Safety
+//
+// `input` must be a NUL terminated string, and `out` must point to writable
+// [`StacksBytes`].
+enum StacksResultCode stacks_c32_decode(const char *input, struct StacksBytes *out);
+
+// Encodes the 20 byte hash into a Stacks address of the version
+//
+// # Safety
+//
+// `hash` must point to 20 readable bytes, and `out` to a writable string
+// pointer.
+enum StacksResultCode stacks_c32_address_encode(uint8_t version, const uint8_t *hash, char **out);
+
+// Decodes a Stacks address into its version and 20 byte hash
+//
+// # Safety
+//
+// `address` must be a NUL terminated string, `version` must point to a
+// writable byte, and `hash` to 20 writable bytes.
+enum StacksResultCode stacks_c32_address_decode(const char *address,
+                                                uint8_t *version,
+                                                uint8_t *hash);
+
+// Derives the single signature Stacks address of a public key of 33 or 65
+// bytes, for the network given as a [`StacksNetwork`] value
+//
+// # Safety
+//
+// `public_key` must point to `len` readable bytes, and `out` to a writable
+// string pointer.
+enum StacksResultCode stacks_address_from_public_key(const uint8_t *public_key,
+                                                     uintptr_t len,
+                                                     uint32_t network,
+                                                     char **out);
+
+// Validates a Stacks address, writing its network to `network` unless it is
+// null
+//
+// # Safety
+//
+// `address` must be a NUL terminated string, and `network` either null or
+// pointing to a writable [`StacksNetwork`].
+enum StacksResultCode stacks_address_validate(const char *address, enum StacksNetwork *network);
+
+// Releases a string returned by the library
+//
+// # Safety
+//
+// `string` must be null or a string returned by the library, not yet
+// released.
+void stacks_string_free(char *string);
+
+// Releases bytes returned by the library
+//
+// # Safety
+//
+// `bytes` must have been returned by the library and not yet released.
+void stacks_bytes_free(struct StacksBytes bytes);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* STACKS_CORE_H */
@@ -0,0 +1,520 @@
+#![forbid(missing_docs)]
+//! C bindings of the c32 and address functions of stacks-core, for wallets
+//! written in other languages, such as Swift and Kotlin, to reuse the
+//! canonical implementation.
+//!
+//! Every function returns a [`StacksResultCode`]. Strings and bytes returned
+//! through out pointers are owned by the caller and must be released with
+//! [`stacks_string_free`] and [`stacks_bytes_free`]. The header is generated
+//! in `include/stacks_core.h` by the build script.
+
+use std::{
+	ffi::{c_char, CStr, CString},
+	ptr, slice,
+};
+
+use stacks_core::{
+	address::{AddressVersion, StacksAddress},
+	c32::{
+		decode, decode_address_checked, encode, encode_address,
+		C32AddressError, C32Error,
+	},
+	crypto::{hash160::HASH160_LENGTH, PublicKey},
+	Network,
+};
+
+/// Result of a call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StacksResultCode {
+	/// The call succeeded
+	Ok = 0,
+	/// A required pointer is null
+	NullPointer = 1,
+	/// A string is not valid UTF-8
+	InvalidUtf8 = 2,
+	/// A string contains characters outside of the c32 alphabet
+	InvalidC32 = 3,
+	/// The checksum of an address does not match, usually because of a typo
+	InvalidChecksum = 4,
+	/// The address version is not a known Stacks address version
+	InvalidVersion = 5,
+	/// The address is malformed or does not encode a 20 byte hash
+	InvalidAddress = 6,
+	/// The public key is not a valid compressed or uncompressed key
+	InvalidPublicKey = 7,
+	/// The network is not one of the [`StacksNetwork`] values
+	InvalidNetwork = 8,
+}
+
+/// Network of a Stacks address
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StacksNetwork {
+	/// Mainnet
+	Mainnet = 0,
+	/// Testnet
+	Testnet = 1,
+}
+
+// Enums are passed in as integers, since C callers can pass any value, which
+// would be undefined behavior as a Rust enum
+impl TryFrom<u32> for StacksNetwork {
+	type Error = StacksResultCode;
+
+	fn try_from(network: u32) -> Result<Self, Self::Error> {
+		match network {
+			0 => Ok(Self::Mainnet),
+			1 => Ok(Self::Testnet),
+			_ => Err(StacksResultCode::InvalidNetwork),
+		}
+	}
+}
+
+/// Bytes allocated by the library, released with [`stacks_bytes_free`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct StacksBytes {
+	/// Pointer to the first byte
+	pub data: *mut u8,
+	/// Number of bytes
+	pub len: usize,
+}
+
+/// C32 encodes the bytes into a string
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `out` to a writable string
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn stacks_c32_encode(
+	data: *const u8,
+	len: usize,
+	out: *mut *mut c_char,
+) -> StacksResultCode {
+	result_code(c32_encode(data, len, out))
+}
+
+/// C32 decodes the string into bytes
+///
+/// # Safety
+///
+/// `input` must be a NUL terminated string, and `out` must point to writable
+/// [`StacksBytes`].
+#[no_mangle]
+pub unsafe extern "C" fn stacks_c32_decode(
+	input: *const c_char,
+	out: *mut StacksBytes,
+) -> StacksResultCode {
+	result_code(c32_decode(input, out))
+}
+
+/// Encodes the 20 byte hash into a Stacks address of the version
+///
+/// # Safety
+///
+/// `hash` must point to 20 readable bytes, and `out` to a writable string
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn stacks_c32_address_encode(
+	version: u8,
+	hash: *const u8,
+	out: *mut *mut c_char,
+) -> StacksResultCode {
+	result_code(c32_address_encode(version, hash, out))
+}
+
+/// Decodes a Stacks address into its version and 20 byte hash
+///
+/// # Safety
+///
+/// `address` must be a NUL terminated string, `version` must point to a
+/// writable byte, and `hash` to 20 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn stacks_c32_address_decode(
+	address: *const c_char,
+	version: *mut u8,
+	hash: *mut u8,
+) -> StacksResultCode {
+	result_code(c32_address_decode(address, version, hash))
+}
+
+/// Derives the single signature Stacks address of a public key of 33 or 65
+/// bytes, for the network given as a [`StacksNetwork`] value
+///
+/// # Safety
+///
+/// `public_key` must point to `len` readable bytes, and `out` to a writable
+/// string pointer.
+#[no_mangle]
+pub unsafe extern "C" fn stacks_address_from_public_key(
+	public_key: *const u8,
+	len: usize,
+	network: u32,
+	out: *mut *mut c_char,
+) -> StacksResultCode {
+	result_code(address_from_public_key(public_key, len, network, out))
+}
+
+/// Validates a Stacks address, writing its network to `network` unless it is
+/// null
+///
+/// # Safety
+///
+/// `address` must be a NUL terminated string, and `network` either null or
+/// pointing to a writable [`StacksNetwork`].
+#[no_mangle]
+pub unsafe extern "C" fn stacks_address_validate(
+	address: *const c_char,
+	network: *mut StacksNetwork,
+) -> StacksResultCode {
+	result_code(address_validate(address, network))
+}
+
+/// Releases a string returned by the library
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by the library, not yet
+/// released.
+#[no_mangle]
+pub unsafe extern "C" fn stacks_string_free(string: *mut c_char) {
+	if !string.is_null() {
+		drop(CString::from_raw(string));
+	}
+}
+
+/// Releases bytes returned by the library
+///
+/// # Safety
+///
+/// `bytes` must have been returned by the library and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn stacks_bytes_free(bytes: StacksBytes) {
+	if !bytes.data.is_null() {
+		drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+			bytes.data, bytes.len,
+		)));
+	}
+}
+
+impl From<C32Error> for StacksResultCode {
+	fn from(err: C32Error) -> Self {
+		match err {
+			C32Error::InvalidChecksum(..) => Self::InvalidChecksum,
+			C32Error::InvalidVersion(_) => Self::InvalidVersion,
+			C32Error::InvalidAddress(_) => Self::InvalidAddress,
+			C32Error::AddressError(err) => err.into(),
+			_ => Self::InvalidC32,
+		}
+	}
+}
+
+impl From<C32AddressError> for StacksResultCode {
+	fn from(err: C32AddressError) -> Self {
+		match err {
+			C32AddressError::InvalidChar(..) => Self::InvalidC32,
+			C32AddressError::InvalidChecksum => Self::InvalidChecksum,
+			C32AddressError::InvalidVersion(_) => Self::InvalidVersion,
+			_ => Self::InvalidAddress,
+		}
+	}
+}
+
+unsafe fn c32_encode(
+	data: *const u8,
+	len: usize,
+	out: *mut *mut c_char,
+) -> Result<(), StacksResultCode> {
+	let data = read_bytes(data, len)?;
+
+	write_string(out, encode(data))
+}
+
+unsafe fn c32_decode(
+	input: *const c_char,
+	out: *mut StacksBytes,
+) -> Result<(), StacksResultCode> {
+	let input = read_str(input)?;
+
+	write_bytes(out, decode(input)?)
+}
+
+unsafe fn c32_address_encode(
+	version: u8,
+	hash: *const u8,
+	out: *mut *mut c_char,
+) -> Result<(), StacksResultCode> {
+	let version = AddressVersion::try_from(version)
+		.map_err(|_| StacksResultCode::InvalidVersion)?;
+	let hash = read_bytes(hash, HASH160_LENGTH)?;
+
+	write_string(out, encode_address(version, hash))
+}
+
+unsafe fn c32_address_decode(
+	address: *const c_char,
+	version: *mut u8,
+	hash: *mut u8,
+) -> Result<(), StacksResultCode> {
+	let address = read_str(address)?;
+
+	if version.is_null() || hash.is_null() {
+		return Err(StacksResultCode::NullPointer);
+	}
+
+	let (_, decoded_version, decoded_hash) =
+		decode_address_checked(address, None)?;
+
+	*version = decoded_version as u8;
+	ptr::copy_nonoverlapping(decoded_hash.as_ptr(), hash, HASH160_LENGTH);
+
+	Ok(())
+}
+
+unsafe fn address_from_public_key(
+	public_key: *const u8,
+	len: usize,
+	network: u32,
+	out: *mut *mut c_char,
+) -> Result<(), StacksResultCode> {
+	let network = StacksNetwork::try_from(network)?;
+	let public_key = PublicKey::from_slice(read_bytes(public_key, len)?)
+		.map_err(|_| StacksResultCode::InvalidPublicKey)?;
+	let version = match network {
+		StacksNetwork::Mainnet => AddressVersion::MainnetSingleSig,
+		StacksNetwork::Testnet => AddressVersion::TestnetSingleSig,
+	};
+
+	write_string(
+		out,
+		StacksAddress::from_public_key(version, &public_key).to_string(),
+	)
+}
+
+unsafe fn address_validate(
+	address: *const c_char,
+	network: *mut StacksNetwork,
+) -> Result<(), StacksResultCode> {
+	let (address_network, _, _) =
+		decode_address_checked(read_str(address)?, None)?;
+
+	if !network.is_null() {
+		*network = match address_network {
+			Network::Mainnet => StacksNetwork::Mainnet,
+			Network::Testnet => StacksNetwork::Testnet,
+		};
+	}
+
+	Ok(())
+}
+
+fn result_code(result: Result<(), StacksResultCode>) -> StacksResultCode {
+	match result {
+		Ok(()) => StacksResultCode::Ok,
+		Err(code) => code,
+	}
+}
+
+unsafe fn read_str<'a>(
+	input: *const c_char,
+) -> Result<&'a str, StacksResultCode> {
+	if input.is_null() {
+		return Err(StacksResultCode::NullPointer);
+	}
+
+	CStr::from_ptr(input)
+		.to_str()
+		.map_err(|_| StacksResultCode::InvalidUtf8)
+}
+
+unsafe fn read_bytes<'a>(
+	data: *const u8,
+	len: usize,
+) -> Result<&'a [u8], StacksResultCode> {
+	match (data.is_null(), len) {
+		(true, 0) => Ok(&[]),
+		(true, _) => Err(StacksResultCode::NullPointer),
+		(false, _) => Ok(slice::from_raw_parts(data, len)),
+	}
+}
+
+unsafe fn write_string(
+	out: *mut *mut c_char,
+	string: String,
+) -> Result<(), StacksResultCode> {
+	if out.is_null() {
+		return Err(StacksResultCode::NullPointer);
+	}
+
+	*out = CString::new(string)
+		.expect("C32 strings should not contain NUL characters")
+		.into_raw();
+
+	Ok(())
+}
+
+unsafe fn write_bytes(
+	out: *mut StacksBytes,
+	bytes: Vec<u8>,
+) -> Result<(), StacksResultCode> {
+	if out.is_null() {
+		return Err(StacksResultCode::NullPointer);
+	}
+
+	let len = bytes.len();
+	let data = Box::into_raw(bytes.into_boxed_slice()).cast::<u8>();
+
+	*out = StacksBytes { data, len };
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ZERO_ADDRESS: &str = "SP000000000000000000002Q6VF78";
+
+	unsafe fn take_string(string: *mut c_char) -> String {
+		let value = CStr::from_ptr(string).to_str().unwrap().to_string();
+		stacks_string_free(string);
+
+		value
+	}
+
+	#[test]
+	fn should_round_trip_c32() {
+		let data = b"hello world";
+		let mut encoded = ptr::null_mut();
+		let mut decoded = StacksBytes {
+			data: ptr::null_mut(),
+			len: 0,
+		};
+
+		unsafe {
+			assert_eq!(
+				stacks_c32_encode(data.as_ptr(), data.len(), &mut encoded),
+				StacksResultCode::Ok
+			);
+			assert_eq!(
+				stacks_c32_decode(encoded, &mut decoded),
+				StacksResultCode::Ok
+			);
+			assert_eq!(slice::from_raw_parts(decoded.data, decoded.len), data);
+
+			stacks_string_free(encoded);
+			stacks_bytes_free(decoded);
+		}
+	}
+
+	#[test]
+	fn should_round_trip_addresses() {
+		let hash = [0; HASH160_LENGTH];
+		let mut address = ptr::null_mut();
+		let mut version = 0;
+		let mut decoded_hash = [1; HASH160_LENGTH];
+
+		unsafe {
+			assert_eq!(
+				stacks_c32_address_encode(
+					AddressVersion::MainnetSingleSig as u8,
+					hash.as_ptr(),
+					&mut address
+				),
+				StacksResultCode::Ok
+			);
+
+			let address = take_string(address);
+			let c_address = CString::new(address.clone()).unwrap();
+
+			assert_eq!(address, ZERO_ADDRESS);
+			assert_eq!(
+				stacks_c32_address_decode(
+					c_address.as_ptr(),
+					&mut version,
+					decoded_hash.as_mut_ptr()
+				),
+				StacksResultCode::Ok
+			);
+		}
+
+		assert_eq!(version, AddressVersion::MainnetSingleSig as u8);
+		assert_eq!(decoded_hash, hash);
+	}
+
+	#[test]
+	fn should_validate_addresses() {
+		let valid = CString::new(ZERO_ADDRESS).unwrap();
+		let typo = CString::new("SP000000000000000000002Q6VF79").unwrap();
+		let mut network = StacksNetwork::Testnet;
+
+		unsafe {
+			assert_eq!(
+				stacks_address_validate(valid.as_ptr(), &mut network),
+				StacksResultCode::Ok
+			);
+			assert_eq!(
+				stacks_address_validate(typo.as_ptr(), ptr::null_mut()),
+				StacksResultCode::InvalidChecksum
+			);
+			assert_eq!(
+				stacks_address_validate(ptr::null(), ptr::null_mut()),
+				StacksResultCode::NullPointer
+			);
+		}
+
+		assert_eq!(network, StacksNetwork::Mainnet);
+	}
+
+	#[test]
+	fn should_reject_invalid_public_keys() {
+		let public_key = [2; 12];
+		let mut address = ptr::null_mut();
+
+		let code = unsafe {
+			stacks_address_from_public_key(
+				public_key.as_ptr(),
+				public_key.len(),
+				StacksNetwork::Mainnet as u32,
+				&mut address,
+			)
+		};
+
+		assert_eq!(code, StacksResultCode::InvalidPublicKey);
+		assert!(address.is_null());
+	}
+
+	#[test]
+	fn should_reject_unknown_networks() {
+		// Public key of the secret key 1
+		let public_key = [
+			0x02, 0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0,
+			0x62, 0x95, 0xce, 0x87, 0x0b, 0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d,
+			0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17, 0x98,
+		];
+		let derive = |network| {
+			let mut address = ptr::null_mut();
+
+			let code = unsafe {
+				stacks_address_from_public_key(
+					public_key.as_ptr(),
+					public_key.len(),
+					network,
+					&mut address,
+				)
+			};
+
+			(code, address)
+		};
+
+		let (code, address) = derive(StacksNetwork::Testnet as u32);
+		assert_eq!(code, StacksResultCode::Ok);
+		assert!(unsafe { take_string(address) }.starts_with("ST"));
+
+		let (code, address) = derive(2);
+		assert_eq!(code, StacksResultCode::InvalidNetwork);
+		assert!(address.is_null());
+	}
+}
@@ -1,28 +1,50 @@
-use std::{
-	fmt,
-	io::{self, Read, Write},
-};
-
-use bdk::bitcoin::blockdata::{
-	opcodes::all::OP_CHECKMULTISIG, script::Builder,
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+#[cfg(feature = "std")]
+use bdk::bitcoin::{
+	blockdata::{opcodes::all::OP_CHECKMULTISIG, script::Builder},
+	hashes::Hash,
+	secp256k1::{Parity, Secp256k1, XOnlyPublicKey},
+	util::{
+		address::{Payload, WitnessVersion},
+		schnorr::{TapTweak, TweakedPublicKey},
+		taproot::TapBranchHash,
+	},
+	Address as BitcoinAddress, Network as BitcoinNetwork, PubkeyHash, Script,
+	ScriptHash,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use strum::{EnumIter, FromRepr};
 
 use crate::{
 	c32::{decode_address, encode_address},
-	codec::Codec,
 	crypto::{
+		combine_public_keys,
 		hash160::{Hash160Hasher, HASH160_LENGTH},
-		sha256::Sha256Hasher,
 		Hashing, PublicKey,
 	},
-	StacksError, StacksResult,
+	Network, StacksError, StacksResult,
 };
+#[cfg(feature = "std")]
+use crate::{codec::Codec, crypto::sha256::Sha256Hasher};
 
 /// Supported stacks address versions
 #[repr(u8)]
-#[derive(FromRepr, EnumIter, PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(
+	FromRepr,
+	EnumIter,
+	PartialEq,
+	Eq,
+	Copy,
+	Clone,
+	Debug,
+	Serialize,
+	Deserialize,
+)]
+#[serde(try_from = "u8", into = "u8")]
 pub enum AddressVersion {
 	/// Mainnet single sig address version
 	MainnetSingleSig = 22,
@@ -43,18 +65,198 @@ impl TryFrom<u8> for AddressVersion {
 	}
 }
 
+impl AddressVersion {
+	/// Get the network the address version belongs to
+	pub fn network(&self) -> Network {
+		match self {
+			Self::MainnetSingleSig | Self::MainnetMultiSig => Network::Mainnet,
+			Self::TestnetSingleSig | Self::TestnetMultiSig => Network::Testnet,
+		}
+	}
+}
+
+impl From<AddressVersion> for u8 {
+	fn from(version: AddressVersion) -> Self {
+		version as u8
+	}
+}
+
+/// How the hash of a Stacks address is computed from public keys
+#[repr(u8)]
+#[derive(FromRepr, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum AddressHashMode {
+	/// Hash160 of a single public key
+	P2PKH = 0x00,
+	/// Hash160 of a multisig redeem script
+	P2SH = 0x01,
+	/// Hash160 of the P2SH wrapped witness program of a single public key
+	P2WPKH = 0x02,
+	/// Hash160 of the P2SH wrapped witness program of a multisig script
+	P2WSH = 0x03,
+	/// Segwit v1 output of an x-only key, tweaked with an optional script
+	/// tree. Stacks spending conditions have no such hash mode, it only
+	/// identifies taproot peg wallets. Their 32 byte output key cannot be
+	/// hashed into a Stacks address, since the Bitcoin form of the hash would
+	/// be a P2SH wrapped v1 output, which anyone can spend.
+	P2TR = 0x04,
+}
+
+/// The public keys and signature threshold an address was created from
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressOrigin {
+	/// Hash mode
+	pub hash_mode: AddressHashMode,
+	/// Number of signatures required to spend
+	pub signature_threshold: usize,
+	/// Ordered public keys. The key of P2TR origins is the internal key with
+	/// an even Y coordinate.
+	pub public_keys: Vec<PublicKey>,
+	/// Script tree merkle root of P2TR origins
+	pub taproot_merkle_root: Option<TapBranchHash>,
+}
+
+#[cfg(feature = "std")]
+impl AddressOrigin {
+	/// Create the origin of a taproot peg wallet from its x-only internal key
+	/// and optional script tree merkle root
+	pub fn p2tr(
+		internal_key: &XOnlyPublicKey,
+		merkle_root: Option<TapBranchHash>,
+	) -> Self {
+		Self {
+			hash_mode: AddressHashMode::P2TR,
+			signature_threshold: 1,
+			public_keys: vec![PublicKey::from_x_only_public_key(
+				*internal_key,
+				Parity::Even,
+			)],
+			taproot_merkle_root: merkle_root,
+		}
+	}
+
+	/// Returns the script whose Hash160 is the address hash, which is `None`
+	/// for P2PKH and P2TR origins
+	pub fn redeem_script(&self) -> Option<Script> {
+		match self.hash_mode {
+			AddressHashMode::P2PKH | AddressHashMode::P2TR => None,
+			AddressHashMode::P2SH => Some(multisig_script(
+				&self.public_keys,
+				self.signature_threshold,
+			)),
+			AddressHashMode::P2WPKH => {
+				Some(p2wpkh_program(self.public_keys.first()?))
+			}
+			AddressHashMode::P2WSH => {
+				Some(p2wsh_program(&self.witness_script()?))
+			}
+		}
+	}
+
+	/// Returns the tweaked output key of P2TR origins
+	pub fn output_key(&self) -> Option<TweakedPublicKey> {
+		match self.hash_mode {
+			AddressHashMode::P2TR => {
+				let (internal_key, _) =
+					self.public_keys.first()?.x_only_public_key();
+				let (output_key, _) = internal_key.tap_tweak(
+					&Secp256k1::verification_only(),
+					self.taproot_merkle_root,
+				);
+
+				Some(output_key)
+			}
+			_ => None,
+		}
+	}
+
+	/// Returns the native segwit v1 output script of P2TR origins, which pays
+	/// the output key itself rather than a hash of it
+	pub fn output_script(&self) -> Option<Script> {
+		Some(Script::new_v1_p2tr_tweaked(self.output_key()?))
+	}
+
+	/// Returns the multisig witness script of P2WSH origins
+	pub fn witness_script(&self) -> Option<Script> {
+		match self.hash_mode {
+			AddressHashMode::P2WSH => Some(multisig_script(
+				&self.public_keys,
+				self.signature_threshold,
+			)),
+			_ => None,
+		}
+	}
+
+	fn hash(&self) -> Hash160Hasher {
+		match self.redeem_script() {
+			Some(script) => Hash160Hasher::new(script.as_bytes()),
+			None => hash_p2pkh(&self.public_keys[0]),
+		}
+	}
+
+	fn validate(&self) -> StacksResult<()> {
+		let key_count = self.public_keys.len();
+
+		let is_valid = match self.hash_mode {
+			AddressHashMode::P2PKH | AddressHashMode::P2WPKH => {
+				key_count == 1
+					&& self.signature_threshold == 1
+					&& self.taproot_merkle_root.is_none()
+			}
+			AddressHashMode::P2SH | AddressHashMode::P2WSH => {
+				self.signature_threshold >= 1
+					&& self.signature_threshold <= key_count
+					&& self.taproot_merkle_root.is_none()
+			}
+			AddressHashMode::P2TR => {
+				return Err(StacksError::InvalidArguments(
+					"P2TR origins have a 32 byte output key, which a Stacks \
+					 address cannot hold",
+				));
+			}
+		};
+
+		if is_valid {
+			Ok(())
+		} else {
+			Err(StacksError::InvalidArguments(
+				"Invalid signature threshold or public key count for the hash mode",
+			))
+		}
+	}
+}
+
 /// A Stacks address
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-#[serde(into = "String")]
+///
+/// Addresses created from public keys remember their [`AddressOrigin`]. The
+/// origin is not serialized and does not take part in equality.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct StacksAddress {
 	version: AddressVersion,
 	hash: Hash160Hasher,
+	#[cfg(feature = "std")]
+	#[serde(skip)]
+	origin: Option<AddressOrigin>,
+}
+
+impl PartialEq for StacksAddress {
+	fn eq(&self, other: &Self) -> bool {
+		self.version == other.version && self.hash == other.hash
+	}
 }
 
+impl Eq for StacksAddress {}
+
 impl StacksAddress {
 	/// Create a new Stacks address from the given version and hash
 	pub fn new(version: AddressVersion, hash: Hash160Hasher) -> Self {
-		Self { version, hash }
+		Self {
+			version,
+			hash,
+			#[cfg(feature = "std")]
+			origin: None,
+		}
 	}
 
 	/// Get the address version
@@ -69,7 +271,68 @@ impl StacksAddress {
 
 	/// Create a new Stacks address with a pay-2-public-key-hash
 	pub fn p2pkh(version: AddressVersion, key: &PublicKey) -> Self {
-		Self::new(version, hash_p2pkh(key))
+		Self {
+			version,
+			hash: hash_p2pkh(key),
+			#[cfg(feature = "std")]
+			origin: Some(AddressOrigin {
+				hash_mode: AddressHashMode::P2PKH,
+				signature_threshold: 1,
+				public_keys: vec![*key],
+				taproot_merkle_root: None,
+			}),
+		}
+	}
+
+	/// Create a P2PKH Stacks address from the aggregate of the public keys,
+	/// such as the group key of the signers. Fails if the aggregate is the
+	/// point at infinity.
+	pub fn from_aggregate_key<'a>(
+		version: AddressVersion,
+		keys: impl IntoIterator<Item = &'a PublicKey>,
+	) -> StacksResult<Self> {
+		Ok(Self::p2pkh(version, &combine_public_keys(keys)?))
+	}
+
+	/// Create a Stacks address from the public key. This is always a P2PKH
+	/// address, by convention.
+	pub fn from_public_key(version: AddressVersion, key: &PublicKey) -> Self {
+		Self::p2pkh(version, key)
+	}
+
+	/// Parse a c32 encoded address, ignoring the case of the input. The
+	/// checksum is still validated.
+	pub fn try_from_lenient(address: &str) -> StacksResult<Self> {
+		Self::try_from(address.to_ascii_uppercase().as_str())
+	}
+}
+
+#[cfg(feature = "std")]
+impl StacksAddress {
+	/// Create a new Stacks address from the public keys it is derived from
+	pub fn from_origin(
+		version: AddressVersion,
+		origin: AddressOrigin,
+	) -> StacksResult<Self> {
+		origin.validate()?;
+
+		Ok(Self::from_valid_origin(version, origin))
+	}
+
+	fn from_valid_origin(
+		version: AddressVersion,
+		origin: AddressOrigin,
+	) -> Self {
+		Self {
+			version,
+			hash: origin.hash(),
+			origin: Some(origin),
+		}
+	}
+
+	/// Get the public keys the address was created from, if known
+	pub fn origin(&self) -> Option<&AddressOrigin> {
+		self.origin.as_ref()
 	}
 
 	/// Create a new Stacks address with a pay-2-script-hash
@@ -78,12 +341,28 @@ impl StacksAddress {
 		keys: impl IntoIterator<Item = &'a PublicKey>,
 		signature_threshold: usize,
 	) -> Self {
-		Self::new(version, hash_p2sh(keys, signature_threshold))
+		Self::from_valid_origin(
+			version,
+			AddressOrigin {
+				hash_mode: AddressHashMode::P2SH,
+				signature_threshold,
+				public_keys: keys.into_iter().copied().collect(),
+				taproot_merkle_root: None,
+			},
+		)
 	}
 
 	/// Create a new Stacks address with a pay-2-witness-public-key-hash
 	pub fn p2wpkh(version: AddressVersion, key: &PublicKey) -> Self {
-		Self::new(version, hash_p2wpkh(key))
+		Self::from_valid_origin(
+			version,
+			AddressOrigin {
+				hash_mode: AddressHashMode::P2WPKH,
+				signature_threshold: 1,
+				public_keys: vec![*key],
+				taproot_merkle_root: None,
+			},
+		)
 	}
 
 	/// Create a new Stacks address with a pay-2-witness-script-hash
@@ -92,16 +371,115 @@ impl StacksAddress {
 		keys: impl IntoIterator<Item = &'a PublicKey>,
 		signature_threshold: usize,
 	) -> Self {
-		Self::new(version, hash_p2wsh(keys, signature_threshold))
+		Self::from_valid_origin(
+			version,
+			AddressOrigin {
+				hash_mode: AddressHashMode::P2WSH,
+				signature_threshold,
+				public_keys: keys.into_iter().copied().collect(),
+				taproot_merkle_root: None,
+			},
+		)
 	}
 
-	/// Create a Stacks address from the public key. This is always a P2PKH
-	/// address, by convention.
-	pub fn from_public_key(version: AddressVersion, key: &PublicKey) -> Self {
-		Self::p2pkh(version, key)
+	/// Convert the address to the Bitcoin address with the same hash. P2PKH
+	/// origins map to P2PKH and P2SH, P2WPKH and P2WSH origins map to P2SH,
+	/// the latter two in their P2SH wrapped form. Fails if the origin does not
+	/// match the address version. Addresses without an origin, such as parsed
+	/// ones, are assumed to follow the Stacks convention of single sig versions
+	/// for P2PKH and multi sig versions for P2SH hashes.
+	pub fn to_bitcoin_address(
+		&self,
+		network: BitcoinNetwork,
+	) -> StacksResult<BitcoinAddress> {
+		let is_mainnet = self.version.network() == Network::Mainnet;
+
+		if is_mainnet != (network == BitcoinNetwork::Bitcoin) {
+			return Err(StacksError::InvalidArguments(
+				"Address version does not match the Bitcoin network",
+			));
+		}
+
+		let is_single_sig = matches!(
+			self.version,
+			AddressVersion::MainnetSingleSig | AddressVersion::TestnetSingleSig
+		);
+
+		if let Some(origin) = &self.origin {
+			let is_p2pkh = match origin.hash_mode {
+				AddressHashMode::P2PKH => true,
+				AddressHashMode::P2SH
+				| AddressHashMode::P2WPKH
+				| AddressHashMode::P2WSH => false,
+				AddressHashMode::P2TR => {
+					return Err(StacksError::InvalidArguments(
+						"P2TR origins have no Bitcoin address with a Hash160",
+					));
+				}
+			};
+
+			if is_p2pkh != is_single_sig {
+				return Err(StacksError::InvalidArguments(
+					"Address hash mode does not match the address version",
+				));
+			}
+		}
+
+		let hash = self.hash.as_ref();
+
+		let payload = if is_single_sig {
+			Payload::PubkeyHash(PubkeyHash::from_slice(hash).unwrap())
+		} else {
+			Payload::ScriptHash(ScriptHash::from_slice(hash).unwrap())
+		};
+
+		Ok(BitcoinAddress { payload, network })
+	}
+
+	/// Create a Stacks address from a P2PKH, P2SH or P2WPKH Bitcoin address.
+	/// P2WPKH addresses map to the Stacks address of the P2SH wrapped form.
+	pub fn from_bitcoin_address(
+		address: &BitcoinAddress,
+	) -> StacksResult<Self> {
+		let is_mainnet = address.network == BitcoinNetwork::Bitcoin;
+
+		let (single_sig, multi_sig) = if is_mainnet {
+			(
+				AddressVersion::MainnetSingleSig,
+				AddressVersion::MainnetMultiSig,
+			)
+		} else {
+			(
+				AddressVersion::TestnetSingleSig,
+				AddressVersion::TestnetMultiSig,
+			)
+		};
+
+		match &address.payload {
+			Payload::PubkeyHash(hash) => Ok(Self::new(
+				single_sig,
+				Hash160Hasher::from(hash.into_inner()),
+			)),
+			Payload::ScriptHash(hash) => {
+				Ok(Self::new(multi_sig, Hash160Hasher::from(hash.into_inner())))
+			}
+			Payload::WitnessProgram {
+				version: WitnessVersion::V0,
+				program,
+			} if program.len() == HASH160_LENGTH => {
+				let script =
+					Script::new_witness_program(WitnessVersion::V0, program);
+
+				Ok(Self::new(multi_sig, Hash160Hasher::new(script.as_bytes())))
+			}
+			_ => Err(StacksError::InvalidArguments(
+				"Unsupported Bitcoin address type",
+			)),
+		}
 	}
 }
 
+#[cfg(feature = "std")]
 impl Codec for StacksAddress {
 	fn codec_serialize<W: Write>(&self, dest: &mut W) -> io::Result<()> {
 		assert_eq!(dest.write(&[self.version() as u8])?, 1);
@@ -112,15 +490,100 @@ impl Codec for StacksAddress {
 		let mut version_buffer = [0; 1];
 		data.read_exact(&mut version_buffer)?;
 
-		let version = AddressVersion::from_repr(version_buffer[0]).unwrap();
+		let version =
+			AddressVersion::from_repr(version_buffer[0]).ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("Invalid address version: {}", version_buffer[0]),
+				)
+			})?;
 
 		let mut hash_buffer = [0; HASH160_LENGTH];
 		data.read_exact(&mut hash_buffer)?;
 
-		let hash = Hash160Hasher::from_bytes(&hash_buffer).unwrap();
+		let hash = Hash160Hasher::from(hash_buffer);
+
+		Ok(Self::new(version, hash))
+	}
+}
+
+/// Version of the binary address table format
+#[cfg(feature = "std")]
+pub const ADDRESS_TABLE_VERSION: u8 = 1;
+
+/// Length of a serialized address: version byte followed by the hash
+#[cfg(feature = "std")]
+const ADDRESS_LENGTH: usize = 1 + HASH160_LENGTH;
+
+/// Length of the address table header: format version and address count
+#[cfg(feature = "std")]
+const ADDRESS_TABLE_HEADER_LENGTH: usize = 5;
+
+/// Serializes addresses into a compact binary table.
+///
+/// The table starts with a header containing the format version (1 byte) and
+/// the number of addresses (4 bytes, big endian), followed by the addresses in
+/// their 21 byte wire format. Fails if there are more addresses than the
+/// header can count.
+#[cfg(feature = "std")]
+pub fn serialize_address_table(
+	addresses: &[StacksAddress],
+) -> StacksResult<Vec<u8>> {
+	let count = u32::try_from(addresses.len()).map_err(|_| {
+		StacksError::InvalidArguments(
+			"Address table cannot hold more than u32::MAX addresses",
+		)
+	})?;
+
+	let mut buffer = Vec::with_capacity(
+		ADDRESS_TABLE_HEADER_LENGTH + addresses.len() * ADDRESS_LENGTH,
+	);
+	buffer.push(ADDRESS_TABLE_VERSION);
+	buffer.extend_from_slice(&count.to_be_bytes());
+
+	for address in addresses {
+		buffer.extend_from_slice(&address.serialize_to_vec());
+	}
+
+	Ok(buffer)
+}
+
+/// Deserializes addresses from a binary table produced by
+/// [`serialize_address_table`]
+#[cfg(feature = "std")]
+pub fn deserialize_address_table(
+	data: &[u8],
+) -> StacksResult<Vec<StacksAddress>> {
+	if data.len() < ADDRESS_TABLE_HEADER_LENGTH {
+		return Err(StacksError::InvalidData(format!(
+			"Address table is too short: {} bytes",
+			data.len()
+		)));
+	}
+
+	let (header, mut body) = data.split_at(ADDRESS_TABLE_HEADER_LENGTH);
 
-		Ok(Self { version, hash })
+	if header[0] != ADDRESS_TABLE_VERSION {
+		return Err(StacksError::InvalidData(format!(
+			"Unsupported address table version: {}",
+			header[0]
+		)));
 	}
+
+	let count = u32::from_be_bytes(header[1..].try_into()?) as usize;
+	let expected_length = count.checked_mul(ADDRESS_LENGTH);
+
+	if expected_length != Some(body.len()) {
+		return Err(StacksError::InvalidData(format!(
+			"Address table holds {} bytes, expected {} addresses",
+			body.len(),
+			count
+		)));
+	}
+
+	(0..count)
+		.map(|_| <StacksAddress as Codec>::deserialize(&mut body))
+		.collect()
 }
 
 impl From<StacksAddress> for String {
@@ -149,6 +612,14 @@ impl TryFrom<&str> for StacksAddress {
 	}
 }
 
+impl TryFrom<String> for StacksAddress {
+	type Error = StacksError;
+
+	fn try_from(address: String) -> Result<Self, Self::Error> {
+		Self::try_from(address.as_str())
+	}
+}
+
 impl fmt::Display for StacksAddress {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		write!(f, "{}", encode_address(self.version, self.hash.as_ref()))
@@ -159,77 +630,45 @@ fn hash_p2pkh(key: &PublicKey) -> Hash160Hasher {
 	Hash160Hasher::new(key.serialize())
 }
 
-fn hash_p2sh<'a>(
-	pub_keys: impl IntoIterator<Item = &'a PublicKey>,
+#[cfg(feature = "std")]
+fn multisig_script(
+	pub_keys: &[PublicKey],
 	signature_threshold: usize,
-) -> Hash160Hasher {
-	let mut builder = Builder::new();
-	let mut key_counter = 0;
-
-	builder = builder.push_int(signature_threshold as i64);
+) -> Script {
+	let mut builder = Builder::new().push_int(signature_threshold as i64);
 
 	for key in pub_keys {
 		builder = builder.push_slice(&key.serialize());
-		key_counter += 1;
 	}
 
-	builder = builder.push_int(key_counter);
-	builder = builder.push_opcode(OP_CHECKMULTISIG);
-
-	let script = builder.into_script();
-	let script_hash = Hash160Hasher::new(script.as_bytes());
-
-	script_hash
+	builder
+		.push_int(pub_keys.len() as i64)
+		.push_opcode(OP_CHECKMULTISIG)
+		.into_script()
 }
 
-fn hash_p2wpkh(key: &PublicKey) -> Hash160Hasher {
-	let key_hash_hasher = Hash160Hasher::new(key.serialize());
-	let key_hash = key_hash_hasher.as_ref();
-	let key_hash_len = key_hash.len();
+#[cfg(feature = "std")]
+fn p2wpkh_program(key: &PublicKey) -> Script {
+	let key_hash = Hash160Hasher::new(key.serialize());
 
-	let mut buff = Vec::with_capacity(key_hash_len + 2);
-	buff.push(0);
-	buff.push(key_hash_len as u8);
-	buff.extend_from_slice(key_hash);
-
-	Hash160Hasher::new(&buff)
+	Script::new_witness_program(WitnessVersion::V0, key_hash.as_ref())
 }
 
-fn hash_p2wsh<'a>(
-	pub_keys: impl IntoIterator<Item = &'a PublicKey>,
-	signature_threshold: usize,
-) -> Hash160Hasher {
-	let mut script = vec![];
-	let mut key_count = 0;
+#[cfg(feature = "std")]
+fn p2wsh_program(script: &Script) -> Script {
+	let script_hash = Sha256Hasher::new(script.as_bytes());
 
-	script.push(signature_threshold as u8 + 80);
-
-	for pub_key in pub_keys {
-		let bytes = pub_key.serialize();
-
-		script.push(bytes.len() as u8);
-		script.extend_from_slice(&bytes);
-		key_count += 1;
-	}
-
-	script.push(key_count + 80);
-	script.push(174);
-
-	let digest = Sha256Hasher::new(&script);
-	let digest_bytes = digest.as_ref();
-
-	let mut buff = vec![];
-	buff.push(0);
-	buff.push(digest_bytes.len() as u8);
-	buff.extend_from_slice(digest_bytes);
-
-	Hash160Hasher::new(&buff)
+	Script::new_witness_program(WitnessVersion::V0, script_hash.as_ref())
 }
 
 #[cfg(test)]
 mod tests {
+	use bdk::bitcoin::hashes::Hash;
+	use rand::{thread_rng, Rng};
+	use strum::IntoEnumIterator;
+
 	use super::*;
-	use crate::crypto::hash160::Hash160Hasher;
+	use crate::crypto::{hash160::Hash160Hasher, PrivateKey};
 
 	/// Sample data computed with these commands on MacOS:
 	///
@@ -267,7 +706,12 @@ mod tests {
 			.try_into()
 			.unwrap();
 
-		assert_eq!(hash_p2sh(&[pk], 1).as_ref(), expected_hash.as_ref());
+		assert_eq!(
+			StacksAddress::p2sh(AddressVersion::MainnetMultiSig, &[pk], 1)
+				.hash()
+				.as_ref(),
+			expected_hash.as_ref()
+		);
 	}
 
 	/// Data obtained from from blockstack_lib throwaway code
@@ -287,7 +731,16 @@ mod tests {
 			.try_into()
 			.unwrap();
 
-		assert_eq!(hash_p2sh(&[pk1, pk2], 2).as_ref(), expected_hash.as_ref());
+		assert_eq!(
+			StacksAddress::p2sh(
+				AddressVersion::MainnetMultiSig,
+				&[pk1, pk2],
+				2
+			)
+			.hash()
+			.as_ref(),
+			expected_hash.as_ref()
+		);
 	}
 
 	/// Data obtained from from blockstack_lib throwaway code
@@ -303,7 +756,12 @@ mod tests {
 			.try_into()
 			.unwrap();
 
-		assert_eq!(hash_p2wsh(&[pk], 1).as_ref(), expected_hash.as_ref());
+		assert_eq!(
+			StacksAddress::p2wsh(AddressVersion::MainnetMultiSig, &[pk], 1)
+				.hash()
+				.as_ref(),
+			expected_hash.as_ref()
+		);
 	}
 
 	/// Data obtained from from blockstack_lib throwaway code
@@ -323,7 +781,16 @@ mod tests {
 			.try_into()
 			.unwrap();
 
-		assert_eq!(hash_p2wsh(&[pk1, pk2], 2).as_ref(), expected_hash.as_ref());
+		assert_eq!(
+			StacksAddress::p2wsh(
+				AddressVersion::MainnetMultiSig,
+				&[pk1, pk2],
+				2
+			)
+			.hash()
+			.as_ref(),
+			expected_hash.as_ref()
+		);
 	}
 
 	/// Data obtained from from blockstack_lib throwaway code
@@ -339,7 +806,12 @@ mod tests {
 			.try_into()
 			.unwrap();
 
-		assert_eq!(hash_p2wpkh(&pk).as_ref(), expected_hash.as_ref());
+		assert_eq!(
+			StacksAddress::p2wpkh(AddressVersion::MainnetMultiSig, &pk)
+				.hash()
+				.as_ref(),
+			expected_hash.as_ref()
+		);
 	}
 
 	/// Data generated with `stx make_keychain`
@@ -356,6 +828,143 @@ mod tests {
 		assert_eq!(addr.to_string(), expected_address);
 	}
 
+	#[test]
+	fn should_keep_multisig_origin() {
+		let keys: Vec<PublicKey> = (1..=3)
+			.map(|secret| {
+				PrivateKey::from_slice(&[secret; 32])
+					.unwrap()
+					.public_key(&Secp256k1::new())
+			})
+			.collect();
+
+		for address in [
+			StacksAddress::p2sh(AddressVersion::MainnetMultiSig, &keys, 2),
+			StacksAddress::p2wsh(AddressVersion::MainnetMultiSig, &keys, 2),
+			StacksAddress::p2wpkh(AddressVersion::MainnetMultiSig, &keys[0]),
+		] {
+			let origin = address.origin().unwrap();
+			let redeem_script = origin.redeem_script().unwrap();
+
+			assert_eq!(
+				Hash160Hasher::new(redeem_script.as_bytes()),
+				*address.hash()
+			);
+			assert_eq!(
+				StacksAddress::from_origin(address.version(), origin.clone())
+					.unwrap(),
+				address
+			);
+		}
+
+		let p2wsh =
+			StacksAddress::p2wsh(AddressVersion::MainnetMultiSig, &keys, 2);
+		let origin = p2wsh.origin().unwrap();
+
+		assert_eq!(origin.signature_threshold, 2);
+		assert_eq!(origin.public_keys, keys);
+		assert_eq!(
+			origin.redeem_script().unwrap(),
+			Script::new_v0_p2wsh(
+				&origin.witness_script().unwrap().wscript_hash()
+			)
+		);
+
+		// Parsed addresses do not know their origin but still compare equal
+		let parsed =
+			StacksAddress::try_from(p2wsh.to_string().as_str()).unwrap();
+
+		assert!(parsed.origin().is_none());
+		assert_eq!(parsed, p2wsh);
+
+		assert!(StacksAddress::from_origin(
+			AddressVersion::MainnetMultiSig,
+			AddressOrigin {
+				hash_mode: AddressHashMode::P2SH,
+				signature_threshold: 4,
+				public_keys: keys,
+				taproot_merkle_root: None,
+			}
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn should_derive_native_p2tr_output() {
+		let pk_hex = "03528351fc1494c66b67e0857fd571e1de37985dd0cae987dbe71c47d2bc7a7712";
+
+		let pk = PublicKey::from_slice(&hex::decode(pk_hex).unwrap()).unwrap();
+		let (internal_key, _) = pk.x_only_public_key();
+		let peg_address = bdk::bitcoin::Address::p2tr(
+			&Secp256k1::verification_only(),
+			internal_key,
+			None,
+			bdk::bitcoin::Network::Testnet,
+		);
+
+		let origin = AddressOrigin::p2tr(&internal_key, None);
+		let script = origin.output_script().unwrap();
+
+		assert_eq!(script, peg_address.script_pubkey());
+		assert!(script.is_v1_p2tr());
+		assert!(!script.is_p2sh());
+		assert_eq!(
+			&script.as_bytes()[2..],
+			origin.output_key().unwrap().serialize()
+		);
+		assert!(origin.redeem_script().is_none());
+
+		let merkle_root = TapBranchHash::from_slice(&[1; 32]).unwrap();
+
+		assert_ne!(
+			AddressOrigin::p2tr(&internal_key, Some(merkle_root))
+				.output_script()
+				.unwrap(),
+			script
+		);
+
+		// The output key does not fit the hash of a Stacks address
+		for version in AddressVersion::iter() {
+			assert!(
+				StacksAddress::from_origin(version, origin.clone()).is_err()
+			);
+		}
+	}
+
+	#[test]
+	fn should_create_address_from_aggregate_key() {
+		let secp = Secp256k1::new();
+		let keys: Vec<PublicKey> = (1..=2)
+			.map(|secret| {
+				PrivateKey::from_slice(&[secret; 32])
+					.unwrap()
+					.public_key(&secp)
+			})
+			.collect();
+
+		assert_eq!(
+			StacksAddress::from_aggregate_key(
+				AddressVersion::MainnetSingleSig,
+				&keys
+			)
+			.unwrap(),
+			StacksAddress::p2pkh(
+				AddressVersion::MainnetSingleSig,
+				&keys[0].combine(&keys[1]).unwrap()
+			)
+		);
+
+		let negated_key = keys[0].negate(&secp);
+
+		assert!(matches!(
+			StacksAddress::from_aggregate_key(
+				AddressVersion::MainnetSingleSig,
+				[&keys[0], &negated_key]
+			),
+			Err(StacksError::InvalidPublicKey(_))
+		));
+	}
+
 	/// Data generated with `stx make_keychain`
 	#[test]
 	fn should_create_correct_address_from_c32_encoded_string() {
@@ -370,4 +979,180 @@ mod tests {
 
 		assert_eq!(addr.hash(), &expected_hash);
 	}
+
+	#[test]
+	fn should_convert_to_and_from_bitcoin_address() {
+		let pk_hex = "02e2ce887c1f1654936fbb7d4036749da5e7b9b64af406e1f3535c8f4336de1c6e";
+		let pk = PublicKey::from_slice(&hex::decode(pk_hex).unwrap()).unwrap();
+		let bitcoin_pk = bdk::bitcoin::PublicKey::new(pk);
+
+		let address =
+			StacksAddress::try_from("SPR4FMGJCD78NF4FRGPM621CW1KHNFEG0HSRDSPK")
+				.unwrap();
+		let bitcoin_address =
+			address.to_bitcoin_address(BitcoinNetwork::Bitcoin).unwrap();
+
+		assert_eq!(
+			bitcoin_address,
+			BitcoinAddress::p2pkh(&bitcoin_pk, BitcoinNetwork::Bitcoin)
+		);
+		assert_eq!(
+			StacksAddress::from_bitcoin_address(&bitcoin_address).unwrap(),
+			address
+		);
+		assert!(address.to_bitcoin_address(BitcoinNetwork::Testnet).is_err());
+
+		let multi_sig =
+			StacksAddress::p2sh(AddressVersion::TestnetMultiSig, [&pk], 1);
+		let bitcoin_address = multi_sig
+			.to_bitcoin_address(BitcoinNetwork::Testnet)
+			.unwrap();
+
+		assert_eq!(
+			StacksAddress::from_bitcoin_address(&bitcoin_address).unwrap(),
+			multi_sig
+		);
+
+		let p2wpkh =
+			BitcoinAddress::p2wpkh(&bitcoin_pk, BitcoinNetwork::Bitcoin)
+				.unwrap();
+
+		assert_eq!(
+			StacksAddress::from_bitcoin_address(&p2wpkh).unwrap(),
+			StacksAddress::p2wpkh(AddressVersion::MainnetMultiSig, &pk)
+		);
+		assert_eq!(
+			StacksAddress::p2wpkh(AddressVersion::MainnetMultiSig, &pk)
+				.to_bitcoin_address(BitcoinNetwork::Bitcoin)
+				.unwrap(),
+			BitcoinAddress::p2shwpkh(&bitcoin_pk, BitcoinNetwork::Bitcoin)
+				.unwrap()
+		);
+	}
+
+	#[test]
+	fn should_not_convert_mismatched_hash_mode_to_bitcoin_address() {
+		let pk_hex = "02e2ce887c1f1654936fbb7d4036749da5e7b9b64af406e1f3535c8f4336de1c6e";
+		let pk = PublicKey::from_slice(&hex::decode(pk_hex).unwrap()).unwrap();
+
+		// A public key hash under a multi sig version is no P2SH hash
+		assert!(StacksAddress::p2pkh(AddressVersion::MainnetMultiSig, &pk)
+			.to_bitcoin_address(BitcoinNetwork::Bitcoin)
+			.is_err());
+
+		// A script hash under a single sig version is no P2PKH hash
+		for address in [
+			StacksAddress::p2sh(AddressVersion::TestnetSingleSig, [&pk], 1),
+			StacksAddress::p2wpkh(AddressVersion::TestnetSingleSig, &pk),
+			StacksAddress::p2wsh(AddressVersion::TestnetSingleSig, [&pk], 1),
+		] {
+			assert!(address
+				.to_bitcoin_address(BitcoinNetwork::Testnet)
+				.is_err());
+		}
+	}
+
+	#[test]
+	fn should_serde_round_trip_address() {
+		let addr = "SPR4FMGJCD78NF4FRGPM621CW1KHNFEG0HSRDSPK";
+		let address = StacksAddress::try_from(addr).unwrap();
+
+		let json = serde_json::to_string(&address).unwrap();
+
+		assert_eq!(json, format!("\"{}\"", addr));
+		assert_eq!(
+			serde_json::from_str::<StacksAddress>(&json).unwrap(),
+			address
+		);
+		assert!(serde_json::from_str::<StacksAddress>("\"SP000\"").is_err());
+	}
+
+	#[test]
+	fn should_serde_round_trip_address_version() {
+		for version in AddressVersion::iter() {
+			let json = serde_json::to_string(&version).unwrap();
+
+			assert_eq!(json, (version as u8).to_string());
+			assert_eq!(
+				serde_json::from_str::<AddressVersion>(&json).unwrap(),
+				version
+			);
+		}
+
+		assert!(serde_json::from_str::<AddressVersion>("0").is_err());
+	}
+
+	#[test]
+	fn should_parse_lowercased_address_leniently() {
+		let addr = "SPR4FMGJCD78NF4FRGPM621CW1KHNFEG0HSRDSPK";
+		let lowercased = addr.to_lowercase();
+
+		assert_eq!(
+			StacksAddress::try_from_lenient(&lowercased).unwrap(),
+			StacksAddress::try_from(addr).unwrap()
+		);
+		assert!(StacksAddress::try_from(lowercased.as_str()).is_err());
+	}
+
+	#[test]
+	fn should_reject_corrupt_address_leniently() {
+		let corrupt = "spr4fmgjcd78nf4frgpm621cw1khnfeg0hsrdspj";
+
+		assert!(StacksAddress::try_from_lenient(corrupt).is_err());
+	}
+
+	#[test]
+	fn should_round_trip_address_table() {
+		let mut rng = thread_rng();
+		let versions: Vec<AddressVersion> = AddressVersion::iter().collect();
+
+		let addresses: Vec<StacksAddress> = (0..1000)
+			.map(|i| {
+				StacksAddress::new(
+					versions[i % versions.len()],
+					Hash160Hasher::from(rng.gen::<[u8; HASH160_LENGTH]>()),
+				)
+			})
+			.collect();
+
+		let table = serialize_address_table(&addresses).unwrap();
+
+		assert_eq!(
+			table.len(),
+			ADDRESS_TABLE_HEADER_LENGTH + addresses.len() * ADDRESS_LENGTH
+		);
+		assert_eq!(deserialize_address_table(&table).unwrap(), addresses);
+	}
+
+	#[test]
+	fn should_fail_to_deserialize_truncated_address_table() {
+		let addresses = vec![
+			StacksAddress::new(
+				AddressVersion::MainnetSingleSig,
+				Hash160Hasher::default(),
+			),
+			StacksAddress::new(
+				AddressVersion::TestnetMultiSig,
+				Hash160Hasher::default(),
+			),
+		];
+
+		let table = serialize_address_table(&addresses).unwrap();
+
+		for length in 0..table.len() {
+			assert!(deserialize_address_table(&table[..length]).is_err());
+		}
+	}
+
+	#[test]
+	fn should_fail_to_deserialize_address_table_with_invalid_version() {
+		let mut table = serialize_address_table(&[StacksAddress::new(
+			AddressVersion::MainnetSingleSig,
+			Hash160Hasher::default(),
+		)])
+		.unwrap();
+		table[ADDRESS_TABLE_HEADER_LENGTH] = 0xff;
+
+		assert!(deserialize_address_table(&table).is_err());
+	}
 }
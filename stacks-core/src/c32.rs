@@ -1,57 +1,52 @@
-use once_cell::sync::Lazy;
+use alloc::{
+	format,
+	string::{FromUtf8Error, String, ToString},
+	vec,
+	vec::Vec,
+};
+use core::{fmt, iter, num::TryFromIntError};
 
 use crate::{
 	address::AddressVersion,
-	crypto::{sha256::DoubleSha256Hasher, Hashing},
+	crypto::{hash160::HASH160_LENGTH, sha256::DoubleSha256Hasher, Hashing},
+	Network,
 };
 
 const C32_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
 
-static C32_BYTE_MAP: Lazy<[Option<u8>; 128]> = Lazy::new(|| {
-	let mut table: [Option<u8>; 128] = [None; 128];
-
-	let alphabet: [char; 32] = C32_ALPHABET
-		.iter()
-		.map(|byte| *byte as char)
-		.collect::<Vec<_>>()
-		.try_into()
-		.unwrap();
-
-	alphabet.iter().enumerate().for_each(|(i, x)| {
-		table[*x as usize] = Some(i as u8);
-	});
-
-	alphabet
-		.iter()
-		.map(|c| c.to_ascii_lowercase())
-		.enumerate()
-		.for_each(|(i, x)| {
-			table[x as usize] = Some(i as u8);
-		});
-
-	[('O', '0'), ('L', '1'), ('I', '1')]
-		.into_iter()
-		.for_each(|special_pair| {
-			let i = alphabet
-				.iter()
-				.enumerate()
-				.find(|(_, a)| **a == special_pair.1)
-				.unwrap()
-				.0;
-
-			table[special_pair.0 as usize] = Some(i as u8);
-			table[special_pair.0.to_ascii_lowercase() as usize] = Some(i as u8);
-		});
+const C32_BYTE_MAP: [Option<u8>; 128] = c32_byte_map();
+
+/// Symbols of the ASCII characters, accepting lowercase characters and the
+/// letters commonly mistaken for the digits 0 and 1
+const fn c32_byte_map() -> [Option<u8>; 128] {
+	let mut table = [None; 128];
+	let mut i = 0;
+
+	while i < C32_ALPHABET.len() {
+		let char = C32_ALPHABET[i];
+
+		table[char as usize] = Some(i as u8);
+		table[char.to_ascii_lowercase() as usize] = Some(i as u8);
+
+		i += 1;
+	}
+
+	table[b'O' as usize] = Some(0);
+	table[b'o' as usize] = Some(0);
+	table[b'L' as usize] = Some(1);
+	table[b'l' as usize] = Some(1);
+	table[b'I' as usize] = Some(1);
+	table[b'i' as usize] = Some(1);
 
 	table
-});
+}
 
 fn encode_overhead(len: usize) -> usize {
-	(len * 8 + 4) / 5
+	(len * 8).div_ceil(5)
 }
 
 fn decode_underhead(len: usize) -> usize {
-	len / (8f64 / 5f64).ceil() as usize
+	len / 8usize.div_ceil(5)
 }
 
 #[derive(thiserror::Error, Clone, Debug, Eq, PartialEq)]
@@ -74,10 +69,47 @@ pub enum C32Error {
 	InvalidVersion(u8),
 	/// Conversion error, from utf8.
 	#[error(transparent)]
-	FromUtf8Error(#[from] std::string::FromUtf8Error),
+	FromUtf8Error(#[from] FromUtf8Error),
 	/// Integer conversion error.
 	#[error(transparent)]
-	IntConversionError(#[from] std::num::TryFromIntError),
+	IntConversionError(#[from] TryFromIntError),
+	/// Streamed data length does not match the declared length.
+	#[error("Invalid C32 stream length - expected {0}, got {1}")]
+	InvalidLength(usize, usize),
+	/// Output writer error.
+	#[error(transparent)]
+	FmtError(#[from] fmt::Error),
+	/// Checked address decoding error.
+	#[error(transparent)]
+	AddressError(#[from] C32AddressError),
+}
+
+#[derive(thiserror::Error, Clone, Debug, Eq, PartialEq)]
+/// Checked address decoding error type
+pub enum C32AddressError {
+	/// Address does not start with `S`.
+	#[error("Stacks addresses start with 'S'")]
+	MissingPrefix,
+	/// Character outside of the C32 alphabet.
+	#[error("Invalid C32 character {0:?} at position {1}")]
+	InvalidChar(char, usize),
+	/// Address does not encode a hash of the expected length.
+	#[error("Invalid address hash length - expected {HASH160_LENGTH} bytes, got {0}")]
+	InvalidLength(usize),
+	/// Checksum does not match, usually because of a typo.
+	#[error("Invalid address checksum, the address may contain a typo")]
+	InvalidChecksum,
+	/// Version is not a known Stacks address version.
+	#[error("Invalid address version: {0}")]
+	InvalidVersion(u8),
+	/// Address belongs to another network.
+	#[error("Address is for {actual}, expected {expected}")]
+	NetworkMismatch {
+		/// Network the address was expected to be for
+		expected: Network,
+		/// Network the address is for
+		actual: Network,
+	},
 }
 /// C32 encode the given data
 pub fn encode(data: impl AsRef<[u8]>) -> String {
@@ -117,9 +149,8 @@ pub fn encode(data: impl AsRef<[u8]>) -> String {
 		}
 	}
 
-	encoded.reverse();
-
-	String::from_utf8(encoded).unwrap()
+	// The alphabet is ASCII, so every byte maps to a single char
+	encoded.into_iter().rev().map(char::from).collect()
 }
 
 /// C32 decode the given data
@@ -173,6 +204,207 @@ pub fn decode(input: impl AsRef<str>) -> Result<Vec<u8>, C32Error> {
 	Ok(decoded)
 }
 
+/// Streaming C32 encoder. Produces the same output as [`encode`] without
+/// buffering the input, which requires the total input length upfront.
+pub struct C32Encoder<W: fmt::Write> {
+	writer: W,
+	len: usize,
+	written: usize,
+	buffer: u32,
+	bits: usize,
+	leading_zero_bytes: usize,
+	seen_nonzero_byte: bool,
+	started: bool,
+}
+
+impl<W: fmt::Write> C32Encoder<W> {
+	/// Create an encoder for `len` bytes of input writing to the given writer
+	pub fn new(writer: W, len: usize) -> Self {
+		Self {
+			writer,
+			len,
+			written: 0,
+			buffer: 0,
+			// Zero bits padding the input to a multiple of 5 bits
+			bits: (5 - len * 8 % 5) % 5,
+			leading_zero_bytes: 0,
+			seen_nonzero_byte: false,
+			started: false,
+		}
+	}
+
+	/// Encode the next chunk of input
+	pub fn write(&mut self, chunk: &[u8]) -> Result<(), C32Error> {
+		if self.written + chunk.len() > self.len {
+			return Err(C32Error::InvalidLength(
+				self.len,
+				self.written + chunk.len(),
+			));
+		}
+
+		self.written += chunk.len();
+
+		for byte in chunk {
+			if !self.seen_nonzero_byte {
+				if *byte == 0 {
+					self.leading_zero_bytes += 1;
+				} else {
+					self.seen_nonzero_byte = true;
+				}
+			}
+
+			self.buffer = (self.buffer << 8) | *byte as u32;
+			self.bits += 8;
+
+			while self.bits >= 5 {
+				self.bits -= 5;
+				self.write_symbol((self.buffer >> self.bits) & 0x1F)?;
+			}
+
+			self.buffer &= (1 << self.bits) - 1;
+		}
+
+		Ok(())
+	}
+
+	/// Finish encoding and return the writer
+	pub fn finish(mut self) -> Result<W, C32Error> {
+		if self.written != self.len {
+			return Err(C32Error::InvalidLength(self.len, self.written));
+		}
+
+		if !self.started {
+			self.write_leading_zeroes()?;
+		}
+
+		Ok(self.writer)
+	}
+
+	fn write_symbol(&mut self, symbol: u32) -> Result<(), C32Error> {
+		if !self.started {
+			if symbol == 0 {
+				return Ok(());
+			}
+
+			self.started = true;
+			self.write_leading_zeroes()?;
+		}
+
+		self.writer
+			.write_char(C32_ALPHABET[symbol as usize] as char)?;
+
+		Ok(())
+	}
+
+	fn write_leading_zeroes(&mut self) -> Result<(), C32Error> {
+		for _ in 0..self.leading_zero_bytes {
+			self.writer.write_char(C32_ALPHABET[0] as char)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Streaming C32 decoder. Produces the same output as [`decode`] without
+/// buffering the input, which requires the total input length upfront.
+pub struct C32Decoder<W: Extend<u8>> {
+	output: W,
+	len: usize,
+	written: usize,
+	buffer: u16,
+	bits: usize,
+	leading_zero_chars: usize,
+	seen_nonzero_char: bool,
+	started: bool,
+}
+
+impl<W: Extend<u8>> C32Decoder<W> {
+	/// Create a decoder for `len` characters of input extending the given
+	/// output
+	pub fn new(output: W, len: usize) -> Self {
+		Self {
+			output,
+			len,
+			written: 0,
+			buffer: 0,
+			// Zero bits padding the input to a multiple of 8 bits
+			bits: (8 - len * 5 % 8) % 8,
+			leading_zero_chars: 0,
+			seen_nonzero_char: false,
+			started: false,
+		}
+	}
+
+	/// Decode the next chunk of input
+	pub fn write(&mut self, chunk: &str) -> Result<(), C32Error> {
+		if self.written + chunk.len() > self.len {
+			return Err(C32Error::InvalidLength(
+				self.len,
+				self.written + chunk.len(),
+			));
+		}
+
+		self.written += chunk.len();
+
+		for byte in chunk.bytes() {
+			let Some(symbol) =
+				C32_BYTE_MAP.get(byte as usize).copied().flatten()
+			else {
+				return Err(C32Error::InvalidChar(byte as char));
+			};
+
+			if !self.seen_nonzero_char {
+				if byte == C32_ALPHABET[0] {
+					self.leading_zero_chars += 1;
+				} else {
+					self.seen_nonzero_char = true;
+				}
+			}
+
+			self.buffer = (self.buffer << 5) | u16::from(symbol);
+			self.bits += 5;
+
+			if self.bits >= 8 {
+				self.bits -= 8;
+				self.write_byte((self.buffer >> self.bits) as u8);
+				self.buffer &= (1 << self.bits) - 1;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Finish decoding and return the output
+	pub fn finish(mut self) -> Result<W, C32Error> {
+		if self.written != self.len {
+			return Err(C32Error::InvalidLength(self.len, self.written));
+		}
+
+		if !self.started {
+			self.write_leading_zeroes();
+		}
+
+		Ok(self.output)
+	}
+
+	fn write_byte(&mut self, byte: u8) {
+		if !self.started {
+			if byte == 0 {
+				return;
+			}
+
+			self.started = true;
+			self.write_leading_zeroes();
+		}
+
+		self.output.extend(iter::once(byte));
+	}
+
+	fn write_leading_zeroes(&mut self) {
+		self.output.extend((0..self.leading_zero_chars).map(|_| 0));
+	}
+}
+
 /// C32 encode the given data with a version check
 pub fn version_check_encode(
 	version: AddressVersion,
@@ -259,13 +491,81 @@ pub fn decode_address(
 	version_check_decode(&address[1..])
 }
 
+/// C32 decode the given address string, reporting exactly what is wrong with
+/// malformed addresses. Returns the detected network along with the version and
+/// hash, and fails if an expected network is given and does not match.
+pub fn decode_address_checked(
+	address: impl AsRef<str>,
+	expected_network: Option<Network>,
+) -> Result<(Network, AddressVersion, Vec<u8>), C32AddressError> {
+	let address = address.as_ref();
+
+	let Some(encoded) = address.strip_prefix('S') else {
+		return Err(C32AddressError::MissingPrefix);
+	};
+
+	let mut values = Vec::with_capacity(encoded.len());
+
+	for (position, char) in address.chars().enumerate().skip(1) {
+		let value = C32_BYTE_MAP.get(char as usize).copied().flatten();
+
+		match value {
+			Some(value) if char.is_ascii() => values.push(value),
+			_ => return Err(C32AddressError::InvalidChar(char, position)),
+		}
+	}
+
+	let Some((version_byte, _)) = values.split_first() else {
+		return Err(C32AddressError::InvalidLength(0));
+	};
+
+	// Every character was validated above, so decoding cannot fail
+	let decoded = decode(&encoded[1..]).expect("C32 characters are valid");
+
+	if decoded.len() != HASH160_LENGTH + 4 {
+		return Err(C32AddressError::InvalidLength(
+			decoded.len().saturating_sub(4),
+		));
+	}
+
+	let (hash, checksum) = decoded.split_at(HASH160_LENGTH);
+
+	let mut buffer_to_check = vec![*version_byte];
+	buffer_to_check.extend_from_slice(hash);
+
+	if DoubleSha256Hasher::new(buffer_to_check).checksum() != checksum {
+		return Err(C32AddressError::InvalidChecksum);
+	}
+
+	let version = AddressVersion::try_from(*version_byte)
+		.map_err(|_| C32AddressError::InvalidVersion(*version_byte))?;
+	let network = version.network();
+
+	match expected_network {
+		Some(expected) if expected != network => {
+			Err(C32AddressError::NetworkMismatch {
+				expected,
+				actual: network,
+			})
+		}
+		_ => Ok((network, version, hash.to_vec())),
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use rand::{thread_rng, Rng, RngCore};
 	use strum::IntoEnumIterator;
 
-	use super::{decode_address, encode, encode_address};
-	use crate::address::AddressVersion;
+	use super::{
+		decode, decode_address, decode_address_checked, encode, encode_address,
+		C32AddressError, C32Decoder, C32Encoder, C32Error,
+	};
+	use crate::{
+		address::AddressVersion,
+		crypto::{sha256::DoubleSha256Hasher, Hashing},
+		Network,
+	};
 
 	#[test]
 	fn test_c32_encode() {
@@ -331,4 +631,128 @@ mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn test_c32_streaming_matches_whole_buffer() {
+		let mut rng = thread_rng();
+
+		for _ in 0..1000 {
+			let len = rng.gen_range(0..64);
+			let zeroes = rng.gen_range(0..=len.min(3));
+			let mut input = vec![0u8; len];
+			rng.fill_bytes(&mut input[zeroes..]);
+
+			let mut encoder = C32Encoder::new(String::new(), len);
+			let mut remaining = input.as_slice();
+
+			while !remaining.is_empty() {
+				let (chunk, rest) =
+					remaining.split_at(rng.gen_range(1..=remaining.len()));
+				encoder.write(chunk).unwrap();
+				remaining = rest;
+			}
+
+			let encoded = encoder.finish().unwrap();
+
+			assert_eq!(encoded, encode(&input));
+
+			let mut decoder = C32Decoder::new(Vec::new(), encoded.len());
+			let mut remaining = encoded.as_str();
+
+			while !remaining.is_empty() {
+				let (chunk, rest) =
+					remaining.split_at(rng.gen_range(1..=remaining.len()));
+				decoder.write(chunk).unwrap();
+				remaining = rest;
+			}
+
+			let decoded = decoder.finish().unwrap();
+
+			assert_eq!(decoded, decode(&encoded).unwrap());
+			assert_eq!(decoded, input);
+		}
+	}
+
+	#[test]
+	fn test_c32_streaming_length_mismatch() {
+		let mut encoder = C32Encoder::new(String::new(), 2);
+
+		assert_eq!(
+			encoder.write(&[1, 2, 3]),
+			Err(C32Error::InvalidLength(2, 3))
+		);
+
+		encoder.write(&[1]).unwrap();
+
+		assert_eq!(encoder.finish(), Err(C32Error::InvalidLength(2, 1)));
+
+		let mut decoder = C32Decoder::new(Vec::new(), 2);
+
+		assert_eq!(decoder.write("U"), Err(C32Error::InvalidChar('U')));
+	}
+
+	#[test]
+	fn should_decode_checked_address() {
+		let hash = [7; 20];
+		let address = encode_address(AddressVersion::TestnetMultiSig, hash);
+
+		assert_eq!(
+			decode_address_checked(&address, Some(Network::Testnet)),
+			Ok((
+				Network::Testnet,
+				AddressVersion::TestnetMultiSig,
+				hash.to_vec()
+			))
+		);
+		assert_eq!(
+			decode_address_checked(&address, Some(Network::Mainnet)),
+			Err(C32AddressError::NetworkMismatch {
+				expected: Network::Mainnet,
+				actual: Network::Testnet,
+			})
+		);
+	}
+
+	#[test]
+	fn should_report_checked_address_errors() {
+		let address = encode_address(AddressVersion::MainnetSingleSig, [7; 20]);
+
+		assert_eq!(
+			decode_address_checked(&address[1..], None),
+			Err(C32AddressError::MissingPrefix)
+		);
+
+		let mut typo = address.clone();
+		typo.replace_range(5..6, "U");
+
+		assert_eq!(
+			decode_address_checked(&typo, None),
+			Err(C32AddressError::InvalidChar('U', 5))
+		);
+
+		let swapped = if &address[5..6] == "Z" { "Y" } else { "Z" };
+		let mut typo = address.clone();
+		typo.replace_range(5..6, swapped);
+
+		assert_eq!(
+			decode_address_checked(&typo, None),
+			Err(C32AddressError::InvalidChecksum)
+		);
+		assert_eq!(
+			decode_address_checked(&address[..address.len() - 2], None),
+			Err(C32AddressError::InvalidLength(19))
+		);
+
+		// A valid checksum over an unknown version byte
+		let mut buffer = vec![10];
+		buffer.extend_from_slice(&[7; 20]);
+		let checksum = DoubleSha256Hasher::new(&buffer).checksum();
+		buffer.extend_from_slice(&checksum);
+		let unknown_version = format!("SA{}", encode(&buffer[1..]));
+
+		assert_eq!(
+			decode_address_checked(unknown_version, None),
+			Err(C32AddressError::InvalidVersion(10))
+		);
+	}
 }
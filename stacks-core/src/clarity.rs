@@ -0,0 +1,487 @@
+//! Clarity values and their consensus serialization.
+use std::{
+	collections::BTreeMap,
+	io::{self, Read},
+};
+
+use strum::FromRepr;
+
+use crate::{codec::Codec, utils::PrincipalData, StacksError, StacksResult};
+
+/// Maximum length of Clarity names, such as tuple keys and function names
+pub const CLARITY_MAX_NAME_LENGTH: usize = 128;
+
+/// Maximum nesting depth of a Clarity value
+pub const MAX_VALUE_DEPTH: usize = 32;
+
+/// Clarity value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+	/// Signed 128-bit integer
+	Int(i128),
+	/// Unsigned 128-bit integer
+	UInt(u128),
+	/// Byte buffer
+	Buffer(Vec<u8>),
+	/// Boolean
+	Bool(bool),
+	/// Standard or contract principal
+	Principal(PrincipalData),
+	/// Successful response
+	ResponseOk(Box<Value>),
+	/// Error response
+	ResponseErr(Box<Value>),
+	/// Optional value
+	Optional(Option<Box<Value>>),
+	/// List of values
+	List(Vec<Value>),
+	/// Tuple of named values
+	Tuple(BTreeMap<String, Value>),
+	/// ASCII string
+	StringAscii(String),
+	/// UTF-8 string
+	StringUtf8(String),
+}
+
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy)]
+enum TypePrefix {
+	Int = 0x00,
+	UInt = 0x01,
+	Buffer = 0x02,
+	BoolTrue = 0x03,
+	BoolFalse = 0x04,
+	PrincipalStandard = 0x05,
+	PrincipalContract = 0x06,
+	ResponseOk = 0x07,
+	ResponseErr = 0x08,
+	OptionalNone = 0x09,
+	OptionalSome = 0x0a,
+	List = 0x0b,
+	Tuple = 0x0c,
+	StringAscii = 0x0d,
+	StringUtf8 = 0x0e,
+}
+
+impl Value {
+	/// Creates a successful response
+	pub fn ok(value: Value) -> Self {
+		Self::ResponseOk(Box::new(value))
+	}
+
+	/// Creates an error response
+	pub fn err(value: Value) -> Self {
+		Self::ResponseErr(Box::new(value))
+	}
+
+	/// Creates an optional value holding the value
+	pub fn some(value: Value) -> Self {
+		Self::Optional(Some(Box::new(value)))
+	}
+
+	/// Creates an empty optional value
+	pub fn none() -> Self {
+		Self::Optional(None)
+	}
+
+	/// Creates a tuple from name and value pairs
+	pub fn tuple<K: Into<String>>(
+		entries: impl IntoIterator<Item = (K, Value)>,
+	) -> StacksResult<Self> {
+		let entries: BTreeMap<String, Value> = entries
+			.into_iter()
+			.map(|(name, value)| (name.into(), value))
+			.collect();
+
+		if entries.keys().any(|name| !is_valid_clarity_name(name)) {
+			return Err(StacksError::InvalidArguments(
+				"Tuple keys should be valid Clarity names",
+			));
+		}
+
+		Ok(Self::Tuple(entries))
+	}
+
+	/// Creates an ASCII string
+	pub fn string_ascii(value: impl Into<String>) -> StacksResult<Self> {
+		let value = value.into();
+
+		if !value.is_ascii() {
+			return Err(StacksError::InvalidArguments(
+				"String should only contain ASCII characters",
+			));
+		}
+
+		Ok(Self::StringAscii(value))
+	}
+
+	/// Serializes the value to a hex string
+	pub fn to_hex(&self) -> String {
+		hex::encode(self.serialize_to_vec())
+	}
+
+	/// Deserializes a value from a hex string
+	pub fn from_hex(data: impl AsRef<str>) -> StacksResult<Self> {
+		let bytes = hex::decode(data.as_ref().trim_start_matches("0x"))?;
+
+		Self::deserialize(&mut &bytes[..])
+	}
+
+	fn type_prefix(&self) -> TypePrefix {
+		match self {
+			Self::Int(_) => TypePrefix::Int,
+			Self::UInt(_) => TypePrefix::UInt,
+			Self::Buffer(_) => TypePrefix::Buffer,
+			Self::Bool(true) => TypePrefix::BoolTrue,
+			Self::Bool(false) => TypePrefix::BoolFalse,
+			Self::Principal(PrincipalData::Standard(_)) => {
+				TypePrefix::PrincipalStandard
+			}
+			Self::Principal(PrincipalData::Contract(_, _)) => {
+				TypePrefix::PrincipalContract
+			}
+			Self::ResponseOk(_) => TypePrefix::ResponseOk,
+			Self::ResponseErr(_) => TypePrefix::ResponseErr,
+			Self::Optional(None) => TypePrefix::OptionalNone,
+			Self::Optional(Some(_)) => TypePrefix::OptionalSome,
+			Self::List(_) => TypePrefix::List,
+			Self::Tuple(_) => TypePrefix::Tuple,
+			Self::StringAscii(_) => TypePrefix::StringAscii,
+			Self::StringUtf8(_) => TypePrefix::StringUtf8,
+		}
+	}
+
+	fn deserialize_with_depth<R: io::Read>(
+		data: &mut R,
+		depth: usize,
+	) -> io::Result<Self> {
+		if depth > MAX_VALUE_DEPTH {
+			return Err(invalid_data("Clarity value is nested too deeply"));
+		}
+
+		let mut type_buffer = [0; 1];
+		data.read_exact(&mut type_buffer)?;
+
+		let type_prefix =
+			TypePrefix::from_repr(type_buffer[0]).ok_or_else(|| {
+				invalid_data(format!(
+					"Invalid Clarity type prefix: {}",
+					type_buffer[0]
+				))
+			})?;
+
+		let value = match type_prefix {
+			TypePrefix::Int => {
+				Self::Int(i128::from_be_bytes(read_array(data)?))
+			}
+			TypePrefix::UInt => {
+				Self::UInt(u128::from_be_bytes(read_array(data)?))
+			}
+			TypePrefix::Buffer => Self::Buffer(read_u32_prefixed(data)?),
+			TypePrefix::BoolTrue => Self::Bool(true),
+			TypePrefix::BoolFalse => Self::Bool(false),
+			TypePrefix::PrincipalStandard | TypePrefix::PrincipalContract => {
+				// The principal codec consumes its own type prefix
+				let mut prefixed = type_buffer.chain(data);
+
+				Self::Principal(PrincipalData::codec_deserialize(
+					&mut prefixed,
+				)?)
+			}
+			TypePrefix::ResponseOk => {
+				Self::ok(Self::deserialize_with_depth(data, depth + 1)?)
+			}
+			TypePrefix::ResponseErr => {
+				Self::err(Self::deserialize_with_depth(data, depth + 1)?)
+			}
+			TypePrefix::OptionalNone => Self::none(),
+			TypePrefix::OptionalSome => {
+				Self::some(Self::deserialize_with_depth(data, depth + 1)?)
+			}
+			TypePrefix::List => {
+				let length = u32::from_be_bytes(read_array(data)?);
+
+				Self::List(
+					(0..length)
+						.map(|_| Self::deserialize_with_depth(data, depth + 1))
+						.collect::<io::Result<_>>()?,
+				)
+			}
+			TypePrefix::Tuple => {
+				let length = u32::from_be_bytes(read_array(data)?);
+				let mut entries = BTreeMap::new();
+
+				for _ in 0..length {
+					let name = read_clarity_name(data)?;
+					let value = Self::deserialize_with_depth(data, depth + 1)?;
+
+					if entries.insert(name, value).is_some() {
+						return Err(invalid_data("Duplicate tuple key"));
+					}
+				}
+
+				Self::Tuple(entries)
+			}
+			TypePrefix::StringAscii => {
+				let value = String::from_utf8(read_u32_prefixed(data)?)
+					.map_err(|err| invalid_data(err.to_string()))?;
+
+				if !value.is_ascii() {
+					return Err(invalid_data(
+						"ASCII string contains non-ASCII characters",
+					));
+				}
+
+				Self::StringAscii(value)
+			}
+			TypePrefix::StringUtf8 => Self::StringUtf8(
+				String::from_utf8(read_u32_prefixed(data)?)
+					.map_err(|err| invalid_data(err.to_string()))?,
+			),
+		};
+
+		Ok(value)
+	}
+}
+
+impl Codec for Value {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		match self {
+			// The principal codec writes its own type prefix
+			Self::Principal(principal) => {
+				return principal.codec_serialize(dest)
+			}
+			_ => dest.write_all(&[self.type_prefix() as u8])?,
+		}
+
+		match self {
+			Self::Int(value) => dest.write_all(&value.to_be_bytes()),
+			Self::UInt(value) => dest.write_all(&value.to_be_bytes()),
+			Self::Buffer(value) => write_u32_prefixed(dest, value),
+			Self::Bool(_) | Self::Optional(None) | Self::Principal(_) => Ok(()),
+			Self::ResponseOk(value)
+			| Self::ResponseErr(value)
+			| Self::Optional(Some(value)) => value.codec_serialize(dest),
+			Self::List(values) => {
+				write_u32_length(dest, values.len())?;
+
+				for value in values {
+					value.codec_serialize(dest)?;
+				}
+
+				Ok(())
+			}
+			Self::Tuple(entries) => {
+				write_u32_length(dest, entries.len())?;
+
+				// Entries are written in lexicographic order of their names
+				for (name, value) in entries {
+					write_clarity_name(dest, name)?;
+					value.codec_serialize(dest)?;
+				}
+
+				Ok(())
+			}
+			Self::StringAscii(value) | Self::StringUtf8(value) => {
+				write_u32_prefixed(dest, value.as_bytes())
+			}
+		}
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		Self::deserialize_with_depth(data, 0)
+	}
+}
+
+impl From<u128> for Value {
+	fn from(value: u128) -> Self {
+		Self::UInt(value)
+	}
+}
+
+impl From<i128> for Value {
+	fn from(value: i128) -> Self {
+		Self::Int(value)
+	}
+}
+
+impl From<bool> for Value {
+	fn from(value: bool) -> Self {
+		Self::Bool(value)
+	}
+}
+
+impl From<Vec<u8>> for Value {
+	fn from(value: Vec<u8>) -> Self {
+		Self::Buffer(value)
+	}
+}
+
+impl From<PrincipalData> for Value {
+	fn from(value: PrincipalData) -> Self {
+		Self::Principal(value)
+	}
+}
+
+fn is_valid_clarity_name(name: &str) -> bool {
+	!name.is_empty() && name.len() <= CLARITY_MAX_NAME_LENGTH && name.is_ascii()
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_array<R: io::Read, const LENGTH: usize>(
+	data: &mut R,
+) -> io::Result<[u8; LENGTH]> {
+	let mut buffer = [0; LENGTH];
+	data.read_exact(&mut buffer)?;
+
+	Ok(buffer)
+}
+
+fn read_u32_prefixed<R: io::Read>(data: &mut R) -> io::Result<Vec<u8>> {
+	let length = u32::from_be_bytes(read_array(data)?) as u64;
+
+	let mut buffer = vec![];
+	data.take(length).read_to_end(&mut buffer)?;
+
+	if buffer.len() as u64 != length {
+		return Err(io::ErrorKind::UnexpectedEof.into());
+	}
+
+	Ok(buffer)
+}
+
+fn write_u32_prefixed<W: io::Write>(
+	dest: &mut W,
+	data: &[u8],
+) -> io::Result<()> {
+	write_u32_length(dest, data.len())?;
+	dest.write_all(data)
+}
+
+pub(crate) fn write_u32_length<W: io::Write>(
+	dest: &mut W,
+	length: usize,
+) -> io::Result<()> {
+	let length = u32::try_from(length)
+		.map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+	dest.write_all(&length.to_be_bytes())
+}
+
+pub(crate) fn write_clarity_name<W: io::Write>(
+	dest: &mut W,
+	name: &str,
+) -> io::Result<()> {
+	if !is_valid_clarity_name(name) {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("Invalid Clarity name: {}", name),
+		));
+	}
+
+	dest.write_all(&[name.len() as u8])?;
+	dest.write_all(name.as_bytes())
+}
+
+pub(crate) fn read_clarity_name<R: io::Read>(
+	data: &mut R,
+) -> io::Result<String> {
+	let [length] = read_array(data)?;
+
+	let mut buffer = vec![0; length as usize];
+	data.read_exact(&mut buffer)?;
+
+	let name = String::from_utf8(buffer)
+		.map_err(|err| invalid_data(err.to_string()))?;
+
+	if !is_valid_clarity_name(&name) {
+		return Err(invalid_data(format!("Invalid Clarity name: {}", name)));
+	}
+
+	Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::address::StacksAddress;
+
+	fn round_trip(value: Value, expected_hex: &str) {
+		assert_eq!(value.to_hex(), expected_hex);
+		assert_eq!(Value::from_hex(expected_hex).unwrap(), value);
+	}
+
+	#[test]
+	fn should_serialize_primitives() {
+		round_trip(Value::Int(-1), "00ffffffffffffffffffffffffffffffff");
+		round_trip(Value::UInt(1), "0100000000000000000000000000000001");
+		round_trip(Value::Buffer(vec![0xde, 0xad]), "0200000002dead");
+		round_trip(Value::Bool(true), "03");
+		round_trip(Value::Bool(false), "04");
+		round_trip(Value::none(), "09");
+		round_trip(Value::string_ascii("hi").unwrap(), "0d000000026869");
+		round_trip(Value::StringUtf8("é".to_string()), "0e00000002c3a9");
+	}
+
+	#[test]
+	fn should_serialize_compound_values() {
+		round_trip(Value::ok(Value::Bool(true)), "0703");
+		round_trip(
+			Value::err(Value::UInt(1)),
+			"080100000000000000000000000000000001",
+		);
+		round_trip(Value::some(Value::Bool(false)), "0a04");
+		round_trip(
+			Value::List(vec![Value::Bool(true), Value::Bool(false)]),
+			"0b000000020304",
+		);
+		// Keys are sorted regardless of insertion order
+		round_trip(
+			Value::tuple([("b", Value::Bool(true)), ("a", Value::Bool(false))])
+				.unwrap(),
+			"0c00000002016104016203",
+		);
+	}
+
+	#[test]
+	fn should_serialize_principals() {
+		let address = StacksAddress::try_from(
+			"SP2JXKMSH007NPYAQHKJPQMAQYAD90NQGTVJVQ02B",
+		)
+		.unwrap();
+		let standard = Value::from(PrincipalData::from(address));
+
+		round_trip(
+			standard.clone(),
+			"0516a5d9d331000f5b79578ce56bd157f29a9056f0d6",
+		);
+
+		let contract = Value::from(
+			PrincipalData::try_from(
+				"SP2JXKMSH007NPYAQHKJPQMAQYAD90NQGTVJVQ02B.asset".to_string(),
+			)
+			.unwrap(),
+		);
+
+		round_trip(
+			contract,
+			"0616a5d9d331000f5b79578ce56bd157f29a9056f0d6056173736574",
+		);
+	}
+
+	#[test]
+	fn should_reject_invalid_values() {
+		assert!(Value::from_hex("0f").is_err());
+		assert!(Value::from_hex("0200000002de").is_err());
+		assert!(Value::from_hex("0d00000001ff").is_err());
+		assert!(Value::from_hex(format!("{}03", "0a".repeat(40))).is_err());
+		assert!(Value::tuple([("", Value::Bool(true))]).is_err());
+		assert!(Value::string_ascii("é").is_err());
+	}
+}
@@ -7,7 +7,8 @@ use crate::{
 	StacksError, StacksResult,
 };
 
-pub(crate) const HASH160_LENGTH: usize = 20;
+/// Length of a Hash160 hash
+pub const HASH160_LENGTH: usize = 20;
 
 #[derive(
 	Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
@@ -64,4 +65,14 @@ mod tests {
 			expected_hash_hex
 		);
 	}
+
+	#[test]
+	fn should_serde_round_trip_as_hex() {
+		let hash = Hash160Hasher::hash(b"Hello world");
+
+		let json = serde_json::to_string(&hash).unwrap();
+
+		assert_eq!(json, "\"f5e95668dadf6fdef8521f7e1aa8a5e650c9f849\"");
+		assert_eq!(serde_json::from_str::<Hash160Hasher>(&json).unwrap(), hash);
+	}
 }
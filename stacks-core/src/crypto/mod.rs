@@ -1,12 +1,25 @@
-pub use bdk::bitcoin::secp256k1;
+use alloc::{string::String, vec, vec::Vec};
+
+pub use secp256k1;
 use serde::{Deserialize, Serialize};
 
 use crate::{StacksError, StacksResult};
 
 /// Module for Hash160 hashing
 pub mod hash160;
+/// Module for recoverable ECDSA signatures
+#[cfg(feature = "std")]
+pub mod recoverable;
+/// Module for Schnorr signatures and taproot keys
+#[cfg(feature = "std")]
+pub mod schnorr;
+/// Module for secret key material
+pub mod secret;
 /// Module for sha256 hashing
 pub mod sha256;
+/// Module for sha512/256 hashing
+pub mod sha512;
+#[cfg(feature = "std")]
 pub mod wif;
 
 const CHECKSUM_LENGTH: usize = 4;
@@ -137,7 +150,70 @@ where
 }
 
 /// Stacks private key
-pub type PrivateKey = bdk::bitcoin::secp256k1::SecretKey;
+pub type PrivateKey = secp256k1::SecretKey;
 
 /// Stacks public key
-pub type PublicKey = bdk::bitcoin::secp256k1::PublicKey;
+pub type PublicKey = secp256k1::PublicKey;
+
+/// Combine the given public keys into an aggregate key. Returns an error if the
+/// aggregate is the point at infinity, which must never be used to derive an
+/// address.
+pub fn combine_public_keys<'a>(
+	keys: impl IntoIterator<Item = &'a PublicKey>,
+) -> StacksResult<PublicKey> {
+	let keys: Vec<&PublicKey> = keys.into_iter().collect();
+
+	if keys.is_empty() {
+		return Err(StacksError::InvalidPublicKey("No public keys to combine"));
+	}
+
+	PublicKey::combine_keys(&keys).map_err(|err| match err {
+		secp256k1::Error::InvalidPublicKeySum => StacksError::InvalidPublicKey(
+			"Aggregate public key is the point at infinity",
+		),
+		err => err.into(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use secp256k1::{Secp256k1, SecretKey};
+
+	use super::*;
+
+	fn public_key(secret: u8) -> PublicKey {
+		let secret_key = SecretKey::from_slice(&[secret; 32]).unwrap();
+
+		PublicKey::from_secret_key(&Secp256k1::new(), &secret_key)
+	}
+
+	#[test]
+	fn should_combine_public_keys() {
+		let key = public_key(1);
+		let other_key = public_key(2);
+
+		assert_eq!(
+			combine_public_keys([&key, &other_key]).unwrap(),
+			key.combine(&other_key).unwrap()
+		);
+	}
+
+	#[test]
+	fn should_reject_infinity_aggregate() {
+		let key = public_key(1);
+		let negated_key = key.negate(&Secp256k1::new());
+
+		assert!(matches!(
+			combine_public_keys([&key, &negated_key]),
+			Err(StacksError::InvalidPublicKey(_))
+		));
+	}
+
+	#[test]
+	fn should_reject_empty_key_set() {
+		assert!(matches!(
+			combine_public_keys([]),
+			Err(StacksError::InvalidPublicKey(_))
+		));
+	}
+}
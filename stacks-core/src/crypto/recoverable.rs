@@ -0,0 +1,186 @@
+//! Recoverable ECDSA signatures in the 65 byte formats used by Stacks.
+//!
+//! Transactions carry signatures in the VRS format, where the recovery ID comes
+//! first. Wallet message signatures and Clarity's `secp256k1-recover?` use the
+//! RSV format, where the recovery ID comes last.
+
+use std::io;
+
+use bdk::bitcoin::secp256k1::{
+	ecdsa::{self, RecoveryId},
+	Message, Secp256k1,
+};
+
+use crate::{
+	address::{AddressVersion, StacksAddress},
+	codec::Codec,
+	crypto::{PrivateKey, PublicKey},
+	StacksError, StacksResult,
+};
+
+/// Length of a serialized recoverable signature
+pub const RECOVERABLE_SIGNATURE_LENGTH: usize = 65;
+
+/// Recoverable ECDSA signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoverableSignature(ecdsa::RecoverableSignature);
+
+impl RecoverableSignature {
+	/// Signs the 32 byte message hash
+	pub fn sign(
+		private_key: &PrivateKey,
+		message_hash: impl AsRef<[u8]>,
+	) -> StacksResult<Self> {
+		let message = Message::from_slice(message_hash.as_ref())?;
+
+		Ok(Self(
+			Secp256k1::new().sign_ecdsa_recoverable(&message, private_key),
+		))
+	}
+
+	/// Parses a signature in the VRS format
+	pub fn from_vrs(
+		bytes: &[u8; RECOVERABLE_SIGNATURE_LENGTH],
+	) -> StacksResult<Self> {
+		Self::from_parts(bytes[0], &bytes[1..])
+	}
+
+	/// Parses a signature in the RSV format
+	pub fn from_rsv(
+		bytes: &[u8; RECOVERABLE_SIGNATURE_LENGTH],
+	) -> StacksResult<Self> {
+		Self::from_parts(bytes[64], &bytes[..64])
+	}
+
+	/// Serializes the signature in the VRS format
+	pub fn to_vrs(&self) -> [u8; RECOVERABLE_SIGNATURE_LENGTH] {
+		let (id, compact) = self.0.serialize_compact();
+
+		let mut vrs = [0; RECOVERABLE_SIGNATURE_LENGTH];
+		vrs[0] = id.to_i32() as u8;
+		vrs[1..].copy_from_slice(&compact);
+
+		vrs
+	}
+
+	/// Serializes the signature in the RSV format
+	pub fn to_rsv(&self) -> [u8; RECOVERABLE_SIGNATURE_LENGTH] {
+		let (id, compact) = self.0.serialize_compact();
+
+		let mut rsv = [0; RECOVERABLE_SIGNATURE_LENGTH];
+		rsv[..64].copy_from_slice(&compact);
+		rsv[64] = id.to_i32() as u8;
+
+		rsv
+	}
+
+	/// Recovers the public key that signed the 32 byte message hash
+	pub fn recover_public_key(
+		&self,
+		message_hash: impl AsRef<[u8]>,
+	) -> StacksResult<PublicKey> {
+		let message = Message::from_slice(message_hash.as_ref())?;
+
+		Ok(Secp256k1::new().recover_ecdsa(&message, &self.0)?)
+	}
+
+	/// Recovers the P2PKH address of the key that signed the 32 byte message
+	/// hash
+	pub fn recover_address(
+		&self,
+		message_hash: impl AsRef<[u8]>,
+		version: AddressVersion,
+	) -> StacksResult<StacksAddress> {
+		Ok(StacksAddress::p2pkh(
+			version,
+			&self.recover_public_key(message_hash)?,
+		))
+	}
+
+	fn from_parts(id: u8, compact: &[u8]) -> StacksResult<Self> {
+		let id = RecoveryId::from_i32(id as i32).map_err(|_| {
+			StacksError::InvalidArguments("Invalid signature recovery ID")
+		})?;
+
+		Ok(Self(ecdsa::RecoverableSignature::from_compact(
+			compact, id,
+		)?))
+	}
+}
+
+impl From<ecdsa::RecoverableSignature> for RecoverableSignature {
+	fn from(signature: ecdsa::RecoverableSignature) -> Self {
+		Self(signature)
+	}
+}
+
+impl From<RecoverableSignature> for ecdsa::RecoverableSignature {
+	fn from(signature: RecoverableSignature) -> Self {
+		signature.0
+	}
+}
+
+impl Codec for RecoverableSignature {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		self.0.codec_serialize(dest)
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		Ok(Self(ecdsa::RecoverableSignature::codec_deserialize(data)?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::crypto::{sha256::Sha256Hasher, Hashing};
+
+	#[test]
+	fn should_recover_signer_address() {
+		let private_key = PrivateKey::from_slice(&[1; 32]).unwrap();
+		let message_hash = Sha256Hasher::new("withdrawal");
+
+		let signature =
+			RecoverableSignature::sign(&private_key, message_hash).unwrap();
+
+		assert_eq!(
+			signature
+				.recover_address(message_hash, AddressVersion::MainnetSingleSig)
+				.unwrap(),
+			StacksAddress::p2pkh(
+				AddressVersion::MainnetSingleSig,
+				&private_key.public_key(&Secp256k1::new())
+			)
+		);
+		assert_ne!(
+			signature
+				.recover_public_key(Sha256Hasher::new("other"))
+				.unwrap(),
+			private_key.public_key(&Secp256k1::new())
+		);
+	}
+
+	#[test]
+	fn should_convert_between_formats() {
+		let private_key = PrivateKey::from_slice(&[1; 32]).unwrap();
+		let signature =
+			RecoverableSignature::sign(&private_key, [2; 32]).unwrap();
+
+		let vrs = signature.to_vrs();
+		let rsv = signature.to_rsv();
+
+		assert_eq!(vrs[0], rsv[64]);
+		assert_eq!(vrs[1..], rsv[..64]);
+		assert_eq!(RecoverableSignature::from_vrs(&vrs).unwrap(), signature);
+		assert_eq!(RecoverableSignature::from_rsv(&rsv).unwrap(), signature);
+		assert_eq!(signature.serialize_to_vec(), vrs);
+
+		let mut invalid = rsv;
+		invalid[64] = 4;
+
+		assert!(RecoverableSignature::from_rsv(&invalid).is_err());
+	}
+}
@@ -0,0 +1,128 @@
+//! BIP340 Schnorr signatures and taproot key tweaking.
+
+use bdk::bitcoin::{
+	secp256k1::{schnorr::Signature, KeyPair, Message, Parity, Secp256k1},
+	util::{schnorr::TapTweak, taproot::TapBranchHash},
+};
+use rand::random;
+
+use crate::{
+	crypto::{PrivateKey, PublicKey},
+	StacksResult,
+};
+
+/// X-only public key used by BIP340 and taproot
+pub type XOnlyPublicKey = bdk::bitcoin::secp256k1::XOnlyPublicKey;
+
+/// BIP340 Schnorr signature
+pub type SchnorrSignature = Signature;
+
+/// Returns the x-only public key of the public key, discarding its parity
+pub fn x_only_public_key(public_key: &PublicKey) -> XOnlyPublicKey {
+	public_key.x_only_public_key().0
+}
+
+/// Tweaks the internal key with the optional script tree merkle root, returning
+/// the taproot output key and its parity
+pub fn tap_tweak_public_key(
+	internal_key: &XOnlyPublicKey,
+	merkle_root: Option<TapBranchHash>,
+) -> (XOnlyPublicKey, Parity) {
+	let (output_key, parity) =
+		internal_key.tap_tweak(&Secp256k1::new(), merkle_root);
+
+	(output_key.to_inner(), parity)
+}
+
+/// Tweaks the private key with the optional script tree merkle root, returning
+/// the key pair that signs for the taproot output key
+pub fn tap_tweak_private_key(
+	private_key: &PrivateKey,
+	merkle_root: Option<TapBranchHash>,
+) -> KeyPair {
+	let secp = Secp256k1::new();
+
+	KeyPair::from_secret_key(&secp, private_key)
+		.tap_tweak(&secp, merkle_root)
+		.to_inner()
+}
+
+/// Signs the 32 byte message hash with BIP340 using fresh auxiliary randomness
+pub fn sign_schnorr(
+	key_pair: &KeyPair,
+	message: impl AsRef<[u8]>,
+) -> StacksResult<SchnorrSignature> {
+	let message = Message::from_slice(message.as_ref())?;
+
+	Ok(Secp256k1::new().sign_schnorr_with_aux_rand(
+		&message,
+		key_pair,
+		&random(),
+	))
+}
+
+/// Verifies a BIP340 signature of the 32 byte message hash
+pub fn verify_schnorr(
+	public_key: &XOnlyPublicKey,
+	message: impl AsRef<[u8]>,
+	signature: &SchnorrSignature,
+) -> StacksResult<()> {
+	let message = Message::from_slice(message.as_ref())?;
+
+	Ok(Secp256k1::new().verify_schnorr(signature, &message, public_key)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use bdk::bitcoin::{hashes::Hash, Address, Network};
+
+	use super::*;
+
+	#[test]
+	fn should_verify_bip340_test_vector() {
+		// Test vector 0 from BIP340
+		let private_key = PrivateKey::from_slice(&[
+			0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+			0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
+		])
+		.unwrap();
+		let public_key =
+			x_only_public_key(&private_key.public_key(&Secp256k1::new()));
+		let signature = SchnorrSignature::from_str("e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0").unwrap();
+
+		assert_eq!(
+			public_key.to_string(),
+			"f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"
+		);
+		assert!(verify_schnorr(&public_key, [0; 32], &signature).is_ok());
+		assert!(verify_schnorr(&public_key, [1; 32], &signature).is_err());
+	}
+
+	#[test]
+	fn should_sign_with_tweaked_key() {
+		let private_key = PrivateKey::from_slice(&[1; 32]).unwrap();
+		let internal_key =
+			x_only_public_key(&private_key.public_key(&Secp256k1::new()));
+		let merkle_root = TapBranchHash::from_slice(&[2; 32]).unwrap();
+
+		let (output_key, _) =
+			tap_tweak_public_key(&internal_key, Some(merkle_root));
+		let address = Address::p2tr(
+			&Secp256k1::new(),
+			internal_key,
+			Some(merkle_root),
+			Network::Bitcoin,
+		);
+
+		assert_eq!(&address.script_pubkey()[2..], &output_key.serialize());
+
+		let key_pair = tap_tweak_private_key(&private_key, Some(merkle_root));
+		let signature = sign_schnorr(&key_pair, [3; 32]).unwrap();
+
+		assert!(verify_schnorr(&output_key, [3; 32], &signature).is_ok());
+		assert!(verify_schnorr(&internal_key, [3; 32], &signature).is_err());
+		assert!(sign_schnorr(&key_pair, [3; 31]).is_err());
+	}
+}
@@ -0,0 +1,148 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use crate::{
+	crypto::{Hex, PrivateKey},
+	StacksError, StacksResult,
+};
+
+/// Length of a secp256k1 private key
+pub const PRIVATE_KEY_LENGTH: usize = 32;
+
+/// Secret key material that is compared in constant time and wiped on drop
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "Hex", into = "Hex")]
+pub struct SecretBytes<const LENGTH: usize>([u8; LENGTH]);
+
+impl<const LENGTH: usize> SecretBytes<LENGTH> {
+	/// Wrap the given secret bytes
+	pub fn new(bytes: [u8; LENGTH]) -> Self {
+		Self(bytes)
+	}
+
+	/// Get the secret bytes
+	pub fn as_bytes(&self) -> &[u8; LENGTH] {
+		&self.0
+	}
+}
+
+impl SecretBytes<PRIVATE_KEY_LENGTH> {
+	/// Get the private key, which fails if the bytes are not a valid
+	/// secp256k1 scalar, such as zero or a value not below the curve order
+	pub fn private_key(&self) -> StacksResult<PrivateKey> {
+		Ok(PrivateKey::from_slice(&self.0)?)
+	}
+}
+
+impl From<PrivateKey> for SecretBytes<PRIVATE_KEY_LENGTH> {
+	fn from(private_key: PrivateKey) -> Self {
+		Self(private_key.secret_bytes())
+	}
+}
+
+impl<const LENGTH: usize> Zeroize for SecretBytes<LENGTH> {
+	fn zeroize(&mut self) {
+		self.0.zeroize();
+	}
+}
+
+impl<const LENGTH: usize> Drop for SecretBytes<LENGTH> {
+	fn drop(&mut self) {
+		self.zeroize();
+	}
+}
+
+impl<const LENGTH: usize> PartialEq for SecretBytes<LENGTH> {
+	fn eq(&self, other: &Self) -> bool {
+		self.0.ct_eq(&other.0).into()
+	}
+}
+
+impl<const LENGTH: usize> Eq for SecretBytes<LENGTH> {}
+
+impl<const LENGTH: usize> fmt::Debug for SecretBytes<LENGTH> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "SecretBytes(<redacted>)")
+	}
+}
+
+impl<const LENGTH: usize> From<SecretBytes<LENGTH>> for Hex {
+	fn from(secret: SecretBytes<LENGTH>) -> Self {
+		Hex(hex::encode(secret.as_bytes()))
+	}
+}
+
+impl<const LENGTH: usize> TryFrom<Hex> for SecretBytes<LENGTH> {
+	type Error = StacksError;
+
+	fn try_from(value: Hex) -> Result<Self, Self::Error> {
+		let mut bytes = hex::decode(&value.0)?;
+
+		let secret = bytes.as_slice().try_into().map(Self);
+		bytes.zeroize();
+
+		Ok(secret?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bdk::bitcoin::secp256k1::Secp256k1;
+	use rand::thread_rng;
+
+	use super::*;
+
+	#[test]
+	fn should_compare_secret_bytes() {
+		let secret = SecretBytes::new([1; 32]);
+
+		assert_eq!(secret, SecretBytes::new([1; 32]));
+		assert_ne!(secret, SecretBytes::new([2; 32]));
+	}
+
+	#[test]
+	fn should_redact_secret_bytes() {
+		let secret = SecretBytes::new([1; 32]);
+
+		assert_eq!(format!("{:?}", secret), "SecretBytes(<redacted>)");
+	}
+
+	#[test]
+	fn should_zeroize_secret_bytes() {
+		let mut secret = SecretBytes::new([1; 32]);
+		secret.zeroize();
+
+		assert_eq!(secret.as_bytes(), &[0; 32]);
+	}
+
+	#[test]
+	fn should_round_trip_private_key() {
+		let private_key =
+			Secp256k1::new().generate_keypair(&mut thread_rng()).0;
+		let secret = SecretBytes::from(private_key);
+
+		assert_eq!(secret.private_key().unwrap(), private_key);
+
+		let json = serde_json::to_string(&secret).unwrap();
+
+		assert_eq!(json, serde_json::to_string(&private_key).unwrap());
+		assert_eq!(
+			serde_json::from_str::<SecretBytes<PRIVATE_KEY_LENGTH>>(&json)
+				.unwrap(),
+			secret
+		);
+	}
+
+	#[test]
+	fn should_reject_invalid_private_keys() {
+		assert!(SecretBytes::new([0; PRIVATE_KEY_LENGTH])
+			.private_key()
+			.is_err());
+		assert!(SecretBytes::new([0xff; PRIVATE_KEY_LENGTH])
+			.private_key()
+			.is_err());
+	}
+}
@@ -1,28 +1,58 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(missing_docs)]
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
 //! # stacks-core library: a library for interacting with the Stacks protocol
+//!
+//! The `std` feature is enabled by default. Without it, the crate builds with
+//! `#![no_std]` and `alloc`, and only exposes the address, c32 and crypto
+//! hashing modules.
 
-use std::{array::TryFromSliceError, io};
+extern crate alloc;
 
+use alloc::string::{String, ToString};
+use core::array::TryFromSliceError;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
 use bdk::bitcoin::Network as BitcoinNetwork;
+#[cfg(feature = "std")]
 use codec::{Codec, CodecError};
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumIter, EnumString, FromRepr};
 use thiserror::Error;
+#[cfg(feature = "std")]
 use uint::Uint256;
 
 /// Module for interacting with stacks addresses
 pub mod address;
 /// Module for c32 encoding and decoding
 pub mod c32;
+/// Module for Clarity values
+#[cfg(feature = "std")]
+pub mod clarity;
+#[cfg(feature = "std")]
 pub mod codec;
+#[cfg(feature = "std")]
 pub mod contract_name;
 /// Module for crypto functions
 pub mod crypto;
+/// Module for SIP-018 structured data signing
+#[cfg(feature = "std")]
+pub mod signing;
+/// Module for deterministic test fixtures
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+/// Module for building and signing Stacks transactions
+#[cfg(feature = "std")]
+pub mod transaction;
 /// Module for creating large integers and performing basic arithmetic
+#[cfg(feature = "std")]
 pub mod uint;
 /// Module for utility functions
+#[cfg(feature = "std")]
 pub mod utils;
+#[cfg(feature = "std")]
 pub mod wallet;
 
 /// Error type for the stacks-core library
@@ -34,6 +64,9 @@ pub enum StacksError {
 	#[error("Could not crackford32 encode or decode: {0}")]
 	/// C32 encoding or decoding error
 	C32Error(#[from] c32::C32Error),
+	#[error("Invalid public key: {0}")]
+	/// Invalid public key
+	InvalidPublicKey(&'static str),
 	#[error("Address version is invalid: {0}")]
 	/// Invalid address version
 	InvalidAddressVersion(u8),
@@ -42,10 +75,11 @@ pub enum StacksError {
 	InvalidSliceLength(#[from] TryFromSliceError),
 	#[error("Could not encode or decode hex: {0}")]
 	/// Hex encoding or decoding error due
-	BadHex(#[from] hex::FromHexError),
+	BadHex(#[cfg_attr(feature = "std", from)] hex::FromHexError),
 	#[error("Could not create Uint from {0} bytes")]
 	/// Invalid Uint bytes
 	InvalidUintBytes(usize),
+	#[cfg(feature = "std")]
 	#[error("Codec error: {0}")]
 	/// Codec error
 	CodecError(#[from] CodecError),
@@ -53,25 +87,47 @@ pub enum StacksError {
 	/// Invalid data
 	InvalidData(String),
 	/// BIP32 Error
+	#[cfg(feature = "std")]
 	#[error("BIP32 error: {0}")]
 	BIP32(#[from] bdk::bitcoin::util::bip32::Error),
 	/// BIP32 Error
+	#[cfg(feature = "std")]
 	#[error("BIP39 error: {0}")]
-	BIP39(#[from] bdk::keys::bip39::Error),
+	BIP39(#[from] bip39::Error),
 	/// SECP Error
 	#[error("SECP error: {0}")]
-	SECP(#[from] bdk::bitcoin::secp256k1::Error),
+	SECP(#[cfg_attr(feature = "std", from)] secp256k1::Error),
 	/// Base58 Error
+	#[cfg(feature = "std")]
 	#[error("Base58 error: {0}")]
 	Base58(#[from] bdk::bitcoin::util::base58::Error),
 }
 
+// The errors of these crates only implement `Error` with std, so they are not
+// sources of the error without it
+#[cfg(not(feature = "std"))]
+impl From<hex::FromHexError> for StacksError {
+	fn from(err: hex::FromHexError) -> Self {
+		Self::BadHex(err)
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl From<secp256k1::Error> for StacksError {
+	fn from(err: secp256k1::Error) -> Self {
+		Self::SECP(err)
+	}
+}
+
 /// Result type for the stacks-core library
 pub type StacksResult<T> = Result<T, StacksError>;
 
 /// A stacks block ID
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BlockId(Uint256);
 
+#[cfg(feature = "std")]
 impl BlockId {
 	/// Creates a new StacksBlockId from a slice of bytes
 	pub fn new(number: Uint256) -> Self {
@@ -79,6 +135,7 @@ impl BlockId {
 	}
 }
 
+#[cfg(feature = "std")]
 impl Codec for BlockId {
 	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
 		self.0.codec_serialize(dest)
@@ -109,7 +166,10 @@ impl Codec for BlockId {
 )]
 #[strum(ascii_case_insensitive)]
 #[strum(serialize_all = "lowercase")]
-#[serde(try_from = "String", into = "String")]
+// strum only implements Display for its parse errors with std, which serde
+// needs of the errors of `try_from`
+#[cfg_attr(feature = "std", serde(try_from = "String", into = "String"))]
+#[cfg_attr(not(feature = "std"), serde(rename_all = "lowercase"))]
 pub enum Network {
 	/// Mainnet
 	Mainnet = 0,
@@ -134,6 +194,7 @@ impl Into<String> for Network {
 }
 
 // For some reason From impl fails to compile
+#[cfg(feature = "std")]
 #[allow(clippy::from_over_into)]
 impl Into<Network> for BitcoinNetwork {
 	fn into(self) -> Network {
@@ -145,6 +206,7 @@ impl Into<Network> for BitcoinNetwork {
 }
 
 // For some reason From impl fails to compile
+#[cfg(feature = "std")]
 #[allow(clippy::from_over_into)]
 impl Into<BitcoinNetwork> for Network {
 	fn into(self) -> BitcoinNetwork {
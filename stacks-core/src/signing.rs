@@ -0,0 +1,226 @@
+//! SIP-018 structured data hashing and signing.
+//!
+//! The signed message hash is computed as
+//! `sha256(SIP018_PREFIX || sha256(domain) || sha256(message))`, where both the
+//! domain and the message are consensus serialized Clarity values.
+
+use std::collections::BTreeMap;
+
+use bdk::bitcoin::secp256k1::{
+	ecdsa::RecoverableSignature, Message, Secp256k1,
+};
+
+use crate::{
+	clarity::Value,
+	codec::Codec,
+	crypto::{
+		recoverable, sha256::Sha256Hasher, Hashing, PrivateKey, PublicKey,
+	},
+	Network, StacksError, StacksResult,
+};
+
+/// Prefix of SIP-018 message hashes
+pub const SIP018_PREFIX: [u8; 6] = *b"SIP018";
+
+/// Chain ID of the Stacks mainnet
+pub const CHAIN_ID_MAINNET: u32 = 0x00000001;
+
+/// Chain ID of the Stacks testnet
+pub const CHAIN_ID_TESTNET: u32 = 0x80000000;
+
+/// Returns the chain ID of the network
+pub fn chain_id(network: Network) -> u32 {
+	match network {
+		Network::Mainnet => CHAIN_ID_MAINNET,
+		Network::Testnet => CHAIN_ID_TESTNET,
+	}
+}
+
+/// SIP-018 signing domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Domain {
+	name: String,
+	version: String,
+	chain_id: u32,
+}
+
+impl Domain {
+	/// Creates a domain from the application name, version and chain ID
+	pub fn new(
+		name: impl Into<String>,
+		version: impl Into<String>,
+		chain_id: u32,
+	) -> StacksResult<Self> {
+		let name = name.into();
+		let version = version.into();
+
+		if !name.is_ascii() || !version.is_ascii() {
+			return Err(StacksError::InvalidArguments(
+				"Domain name and version should be ASCII strings",
+			));
+		}
+
+		Ok(Self {
+			name,
+			version,
+			chain_id,
+		})
+	}
+
+	/// Returns the application name
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Returns the application version
+	pub fn version(&self) -> &str {
+		&self.version
+	}
+
+	/// Returns the chain ID
+	pub fn chain_id(&self) -> u32 {
+		self.chain_id
+	}
+
+	/// Returns the domain as a Clarity tuple of
+	/// `{ chain-id: uint, name: string-ascii, version: string-ascii }`
+	pub fn to_value(&self) -> Value {
+		Value::Tuple(BTreeMap::from([
+			("chain-id".to_string(), Value::UInt(self.chain_id.into())),
+			("name".to_string(), Value::StringAscii(self.name.clone())),
+			(
+				"version".to_string(),
+				Value::StringAscii(self.version.clone()),
+			),
+		]))
+	}
+
+	/// Serializes the domain as a Clarity tuple
+	pub fn serialize_to_vec(&self) -> Vec<u8> {
+		self.to_value().serialize_to_vec()
+	}
+
+	/// Returns the SIP-018 hash of the domain
+	pub fn hash(&self) -> Sha256Hasher {
+		Sha256Hasher::new(self.serialize_to_vec())
+	}
+}
+
+/// Returns the SIP-018 hash of a serialized Clarity value
+pub fn structured_data_hash(message: impl AsRef<[u8]>) -> Sha256Hasher {
+	Sha256Hasher::new(message)
+}
+
+/// Returns the SIP-018 message hash of a serialized Clarity value signed
+/// within the domain
+pub fn message_hash(
+	domain: &Domain,
+	message: impl AsRef<[u8]>,
+) -> Sha256Hasher {
+	let mut buffer = SIP018_PREFIX.to_vec();
+	buffer.extend_from_slice(domain.hash().as_ref());
+	buffer.extend_from_slice(structured_data_hash(message).as_ref());
+
+	Sha256Hasher::new(buffer)
+}
+
+/// Signs a serialized Clarity value within the domain
+pub fn sign_structured_data(
+	private_key: &PrivateKey,
+	domain: &Domain,
+	message: impl AsRef<[u8]>,
+) -> RecoverableSignature {
+	let message = secp_message(domain, message);
+
+	Secp256k1::new().sign_ecdsa_recoverable(&message, private_key)
+}
+
+/// Recovers the public key that signed a serialized Clarity value within the
+/// domain
+pub fn recover_signer(
+	signature: &RecoverableSignature,
+	domain: &Domain,
+	message: impl AsRef<[u8]>,
+) -> StacksResult<PublicKey> {
+	let message = secp_message(domain, message);
+
+	Ok(Secp256k1::new().recover_ecdsa(&message, signature)?)
+}
+
+/// Returns the signature in the RSV format expected by Clarity's
+/// `secp256k1-recover?`
+pub fn signature_to_rsv(signature: &RecoverableSignature) -> [u8; 65] {
+	recoverable::RecoverableSignature::from(*signature).to_rsv()
+}
+
+fn secp_message(domain: &Domain, message: impl AsRef<[u8]>) -> Message {
+	Message::from_slice(message_hash(domain, message).as_ref())
+		.expect("SHA256 hash should be a valid secp message")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Test vectors from SIP-018
+	const HELLO_WORLD_HASH: &str =
+		"5297eef9765c466d945ad1cb2c81b30b9fed6c165575dc9226e9edf78b8cd9e8";
+	const DOMAIN_HASH: &str =
+		"2538b5dc06c5ae2f11549261d7ae174d9f77a55a92b00f330884695497be5065";
+	const MESSAGE_HASH: &str =
+		"1bfdab6d4158313ce34073fbb8d6b0fc32c154d439def12247a0f44bb2225259";
+
+	fn hello_world() -> Vec<u8> {
+		Value::string_ascii("Hello World")
+			.unwrap()
+			.serialize_to_vec()
+	}
+
+	fn test_domain() -> Domain {
+		Domain::new("Test App", "1.0.0", CHAIN_ID_MAINNET).unwrap()
+	}
+
+	#[test]
+	fn should_hash_structured_data() {
+		assert_eq!(
+			structured_data_hash(hello_world()).to_hex(),
+			HELLO_WORLD_HASH
+		);
+		assert_eq!(test_domain().hash().to_hex(), DOMAIN_HASH);
+		assert_eq!(
+			message_hash(&test_domain(), hello_world()).to_hex(),
+			MESSAGE_HASH
+		);
+	}
+
+	#[test]
+	fn should_sign_and_recover_structured_data() {
+		let private_key = PrivateKey::from_slice(&[1; 32]).unwrap();
+		let public_key = private_key.public_key(&Secp256k1::new());
+
+		let signature =
+			sign_structured_data(&private_key, &test_domain(), hello_world());
+
+		assert_eq!(
+			recover_signer(&signature, &test_domain(), hello_world()).unwrap(),
+			public_key
+		);
+
+		let other_domain =
+			Domain::new("Test App", "1.0.0", CHAIN_ID_TESTNET).unwrap();
+
+		assert_ne!(
+			recover_signer(&signature, &other_domain, hello_world()).unwrap(),
+			public_key
+		);
+
+		let rsv = signature_to_rsv(&signature);
+
+		assert_eq!(&rsv[..64], &signature.serialize_compact().1);
+	}
+
+	#[test]
+	fn should_reject_non_ascii_domain() {
+		assert!(Domain::new("Tëst App", "1.0.0", CHAIN_ID_MAINNET).is_err());
+	}
+}
@@ -0,0 +1,102 @@
+//! Deterministic fixtures derived from a fixed mnemonic, so that tests across
+//! crates share the same keys, addresses and transactions.
+
+use bdk::bitcoin::Network as BitcoinNetwork;
+
+use crate::{
+	address::StacksAddress,
+	crypto::{PrivateKey, PublicKey},
+	transaction::{
+		SingleSigSpendingCondition, Transaction, TransactionAuth,
+		TransactionPayload, MEMO_LENGTH,
+	},
+	wallet::{BitcoinCredentials, Credentials, Wallet},
+	Network,
+};
+
+/// BIP39 test mnemonic all fixtures are derived from
+pub const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// Returns the wallet of the test mnemonic
+pub fn test_wallet() -> Wallet {
+	Wallet::new(TEST_MNEMONIC).expect("Test mnemonic is valid")
+}
+
+/// Returns the Stacks credentials at the index
+pub fn test_credentials(network: Network, index: u32) -> Credentials {
+	test_wallet()
+		.credentials(network, index)
+		.expect("Test credentials can be derived")
+}
+
+/// Returns the Bitcoin credentials at the index
+pub fn test_bitcoin_credentials(
+	network: BitcoinNetwork,
+	index: u32,
+) -> BitcoinCredentials {
+	test_wallet()
+		.bitcoin_credentials(network, index)
+		.expect("Test credentials can be derived")
+}
+
+/// Returns the Stacks private key at the index
+pub fn test_private_key(index: u32) -> PrivateKey {
+	test_credentials(Network::Testnet, index)
+		.private_key()
+		.expect("Derived private keys are valid")
+}
+
+/// Returns the Stacks public key at the index
+pub fn test_public_key(index: u32) -> PublicKey {
+	test_credentials(Network::Testnet, index).public_key()
+}
+
+/// Returns the Stacks P2PKH address at the index
+pub fn test_address(network: Network, index: u32) -> StacksAddress {
+	test_credentials(network, index).address()
+}
+
+/// Returns a token transfer of the amount from the key at index 0 to the
+/// address at index 1, signed with a fixed nonce and fee
+pub fn test_token_transfer(network: Network, amount: u64) -> Transaction {
+	let mut tx = Transaction::new(
+		network,
+		TransactionAuth::Standard(SingleSigSpendingCondition::p2pkh(
+			&test_public_key(0),
+			0,
+			180,
+		)),
+		TransactionPayload::TokenTransfer {
+			recipient: test_address(network, 1).into(),
+			amount,
+			memo: [0; MEMO_LENGTH],
+		},
+	);
+
+	tx.sign(&test_private_key(0))
+		.expect("Test transaction can be signed");
+
+	tx
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::codec::Codec;
+
+	#[test]
+	fn should_produce_stable_fixtures() {
+		assert_eq!(
+			test_address(Network::Mainnet, 0).to_string(),
+			"SPC5KHM41H6WHAST7MWWDD807YSPRQKJ69FSH54J"
+		);
+
+		let tx = test_token_transfer(Network::Testnet, 1000);
+
+		// ECDSA signing is deterministic, so the whole transaction is stable
+		assert_eq!(
+			tx.serialize_to_vec(),
+			test_token_transfer(Network::Testnet, 1000).serialize_to_vec()
+		);
+	}
+}
@@ -0,0 +1,815 @@
+//! Stacks transaction construction, signing and wire serialization.
+//!
+//! Only standard single signature authorization is supported for now.
+use std::io::{self, Read};
+
+use bdk::bitcoin::secp256k1::Secp256k1;
+use strum::FromRepr;
+
+use crate::{
+	address::StacksAddress,
+	clarity::{read_clarity_name, write_clarity_name, write_u32_length, Value},
+	codec::Codec,
+	contract_name::ContractName,
+	crypto::{
+		hash160::Hash160Hasher,
+		recoverable::{RecoverableSignature, RECOVERABLE_SIGNATURE_LENGTH},
+		sha512::Sha512_256Hasher,
+		Hashing, PrivateKey, PublicKey,
+	},
+	signing::chain_id,
+	utils::PrincipalData,
+	Network, StacksError, StacksResult,
+};
+
+/// Length of a recoverable signature in the VRS format
+pub const SIGNATURE_LENGTH: usize = RECOVERABLE_SIGNATURE_LENGTH;
+
+/// Length of a token transfer memo
+pub const MEMO_LENGTH: usize = 34;
+
+const AUTH_TYPE_STANDARD: u8 = 0x04;
+
+/// Stacks transaction version
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionVersion {
+	/// Mainnet transaction
+	Mainnet = 0x00,
+	/// Testnet transaction
+	Testnet = 0x80,
+}
+
+impl From<Network> for TransactionVersion {
+	fn from(network: Network) -> Self {
+		match network {
+			Network::Mainnet => Self::Mainnet,
+			Network::Testnet => Self::Testnet,
+		}
+	}
+}
+
+/// Where the transaction can be included
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorMode {
+	/// Only in an anchored block
+	OnChainOnly = 0x01,
+	/// Only in a microblock
+	OffChainOnly = 0x02,
+	/// In either an anchored block or a microblock
+	Any = 0x03,
+}
+
+/// Whether assets not covered by post conditions may be transferred
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostConditionMode {
+	/// Allow transfers not covered by post conditions
+	Allow = 0x01,
+	/// Deny transfers not covered by post conditions
+	Deny = 0x02,
+}
+
+/// Hash mode of a single signature spending condition
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingleSigHashMode {
+	/// Pay-to-public-key-hash
+	P2PKH = 0x00,
+	/// Pay-to-witness-public-key-hash
+	P2WPKH = 0x02,
+}
+
+/// Public key encoding of a spending condition
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicKeyEncoding {
+	/// Compressed public key
+	Compressed = 0x00,
+	/// Uncompressed public key
+	Uncompressed = 0x01,
+}
+
+/// Single signature spending condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingleSigSpendingCondition {
+	/// Hash mode
+	pub hash_mode: SingleSigHashMode,
+	/// Hash of the signer public key
+	pub signer: Hash160Hasher,
+	/// Account nonce
+	pub nonce: u64,
+	/// Transaction fee in micro-STX
+	pub fee: u64,
+	/// Public key encoding
+	pub key_encoding: PublicKeyEncoding,
+	/// Recoverable signature in the VRS format
+	pub signature: [u8; SIGNATURE_LENGTH],
+}
+
+impl SingleSigSpendingCondition {
+	/// Creates an unsigned P2PKH spending condition for the public key
+	pub fn p2pkh(public_key: &PublicKey, nonce: u64, fee: u64) -> Self {
+		Self {
+			hash_mode: SingleSigHashMode::P2PKH,
+			signer: Hash160Hasher::new(public_key.serialize()),
+			nonce,
+			fee,
+			key_encoding: PublicKeyEncoding::Compressed,
+			signature: [0; SIGNATURE_LENGTH],
+		}
+	}
+
+	fn clear(&mut self) {
+		self.nonce = 0;
+		self.fee = 0;
+		self.signature = [0; SIGNATURE_LENGTH];
+	}
+}
+
+impl Codec for SingleSigSpendingCondition {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		dest.write_all(&[self.hash_mode as u8])?;
+		dest.write_all(self.signer.as_ref())?;
+		self.nonce.codec_serialize(dest)?;
+		self.fee.codec_serialize(dest)?;
+		dest.write_all(&[self.key_encoding as u8])?;
+		dest.write_all(&self.signature)
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let hash_mode =
+			read_byte_enum(data, SingleSigHashMode::from_repr, "hash mode")?;
+		let signer = Hash160Hasher::from(read_array(data)?);
+		let nonce = u64::codec_deserialize(data)?;
+		let fee = u64::codec_deserialize(data)?;
+		let key_encoding = read_byte_enum(
+			data,
+			PublicKeyEncoding::from_repr,
+			"public key encoding",
+		)?;
+		let signature = read_array(data)?;
+
+		Ok(Self {
+			hash_mode,
+			signer,
+			nonce,
+			fee,
+			key_encoding,
+			signature,
+		})
+	}
+}
+
+/// Transaction authorization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionAuth {
+	/// Standard authorization, the origin pays the fee
+	Standard(SingleSigSpendingCondition),
+}
+
+impl TransactionAuth {
+	/// Returns the origin spending condition
+	pub fn origin(&self) -> &SingleSigSpendingCondition {
+		match self {
+			Self::Standard(condition) => condition,
+		}
+	}
+
+	fn origin_mut(&mut self) -> &mut SingleSigSpendingCondition {
+		match self {
+			Self::Standard(condition) => condition,
+		}
+	}
+
+	fn auth_type(&self) -> u8 {
+		match self {
+			Self::Standard(_) => AUTH_TYPE_STANDARD,
+		}
+	}
+}
+
+impl Codec for TransactionAuth {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		dest.write_all(&[self.auth_type()])?;
+
+		match self {
+			Self::Standard(condition) => condition.codec_serialize(dest),
+		}
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		match read_u8(data)? {
+			AUTH_TYPE_STANDARD => Ok(Self::Standard(
+				SingleSigSpendingCondition::codec_deserialize(data)?,
+			)),
+			auth_type => Err(invalid_data(format!(
+				"Unsupported authorization type: {}",
+				auth_type
+			))),
+		}
+	}
+}
+
+/// Principal a post condition applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostConditionPrincipal {
+	/// The transaction origin
+	Origin,
+	/// A standard principal
+	Standard(StacksAddress),
+	/// A contract principal
+	Contract(StacksAddress, ContractName),
+}
+
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy)]
+enum PostConditionPrincipalTypeByte {
+	Origin = 0x01,
+	Standard = 0x02,
+	Contract = 0x03,
+}
+
+impl Codec for PostConditionPrincipal {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		match self {
+			Self::Origin => {
+				dest.write_all(&[PostConditionPrincipalTypeByte::Origin as u8])
+			}
+			Self::Standard(address) => {
+				dest.write_all(&[
+					PostConditionPrincipalTypeByte::Standard as u8
+				])?;
+				address.codec_serialize(dest)
+			}
+			Self::Contract(address, contract_name) => {
+				dest.write_all(&[
+					PostConditionPrincipalTypeByte::Contract as u8
+				])?;
+				address.codec_serialize(dest)?;
+				contract_name.codec_serialize(dest)
+			}
+		}
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let principal_type = read_byte_enum(
+			data,
+			PostConditionPrincipalTypeByte::from_repr,
+			"post condition principal type",
+		)?;
+
+		match principal_type {
+			PostConditionPrincipalTypeByte::Origin => Ok(Self::Origin),
+			PostConditionPrincipalTypeByte::Standard => {
+				Ok(Self::Standard(StacksAddress::codec_deserialize(data)?))
+			}
+			PostConditionPrincipalTypeByte::Contract => Ok(Self::Contract(
+				StacksAddress::codec_deserialize(data)?,
+				ContractName::codec_deserialize(data)?,
+			)),
+		}
+	}
+}
+
+/// Condition on the amount of fungible assets sent
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FungibleConditionCode {
+	/// Sent amount equals the given amount
+	SentEq = 0x01,
+	/// Sent amount is greater than the given amount
+	SentGt = 0x02,
+	/// Sent amount is greater than or equal to the given amount
+	SentGe = 0x03,
+	/// Sent amount is less than the given amount
+	SentLt = 0x04,
+	/// Sent amount is less than or equal to the given amount
+	SentLe = 0x05,
+}
+
+/// Fungible token asset defined by a contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+	/// Address of the contract deployer
+	pub address: StacksAddress,
+	/// Name of the contract
+	pub contract_name: ContractName,
+	/// Name of the asset within the contract
+	pub asset_name: String,
+}
+
+impl Codec for AssetInfo {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		self.address.codec_serialize(dest)?;
+		self.contract_name.codec_serialize(dest)?;
+		write_clarity_name(dest, &self.asset_name)
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		Ok(Self {
+			address: StacksAddress::codec_deserialize(data)?,
+			contract_name: ContractName::codec_deserialize(data)?,
+			asset_name: read_clarity_name(data)?,
+		})
+	}
+}
+
+/// Transaction post condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostCondition {
+	/// Condition on the amount of STX sent by the principal
+	Stx(PostConditionPrincipal, FungibleConditionCode, u64),
+	/// Condition on the amount of a fungible token sent by the principal
+	Fungible(
+		PostConditionPrincipal,
+		AssetInfo,
+		FungibleConditionCode,
+		u64,
+	),
+}
+
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy)]
+enum PostConditionTypeByte {
+	Stx = 0x00,
+	Fungible = 0x01,
+}
+
+impl Codec for PostCondition {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		match self {
+			Self::Stx(principal, code, amount) => {
+				dest.write_all(&[PostConditionTypeByte::Stx as u8])?;
+				principal.codec_serialize(dest)?;
+				dest.write_all(&[*code as u8])?;
+				amount.codec_serialize(dest)
+			}
+			Self::Fungible(principal, asset, code, amount) => {
+				dest.write_all(&[PostConditionTypeByte::Fungible as u8])?;
+				principal.codec_serialize(dest)?;
+				asset.codec_serialize(dest)?;
+				dest.write_all(&[*code as u8])?;
+				amount.codec_serialize(dest)
+			}
+		}
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let condition_type = read_byte_enum(
+			data,
+			PostConditionTypeByte::from_repr,
+			"post condition type",
+		)?;
+		let principal = PostConditionPrincipal::codec_deserialize(data)?;
+
+		match condition_type {
+			PostConditionTypeByte::Stx => Ok(Self::Stx(
+				principal,
+				read_condition_code(data)?,
+				u64::codec_deserialize(data)?,
+			)),
+			PostConditionTypeByte::Fungible => Ok(Self::Fungible(
+				principal,
+				AssetInfo::codec_deserialize(data)?,
+				read_condition_code(data)?,
+				u64::codec_deserialize(data)?,
+			)),
+		}
+	}
+}
+
+/// Transaction payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionPayload {
+	/// STX transfer
+	TokenTransfer {
+		/// Recipient of the STX
+		recipient: PrincipalData,
+		/// Amount in micro-STX
+		amount: u64,
+		/// Memo
+		memo: [u8; MEMO_LENGTH],
+	},
+	/// Contract deployment
+	SmartContract {
+		/// Name of the contract
+		contract_name: ContractName,
+		/// Clarity source code of the contract
+		code_body: String,
+	},
+	/// Contract function call
+	ContractCall {
+		/// Address of the contract deployer
+		address: StacksAddress,
+		/// Name of the contract
+		contract_name: ContractName,
+		/// Name of the function
+		function_name: String,
+		/// Function arguments
+		function_args: Vec<Value>,
+	},
+}
+
+#[repr(u8)]
+#[derive(FromRepr, Debug, Clone, Copy)]
+enum PayloadTypeByte {
+	TokenTransfer = 0x00,
+	SmartContract = 0x01,
+	ContractCall = 0x02,
+}
+
+impl Codec for TransactionPayload {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		match self {
+			Self::TokenTransfer {
+				recipient,
+				amount,
+				memo,
+			} => {
+				dest.write_all(&[PayloadTypeByte::TokenTransfer as u8])?;
+				recipient.codec_serialize(dest)?;
+				amount.codec_serialize(dest)?;
+				dest.write_all(memo)
+			}
+			Self::SmartContract {
+				contract_name,
+				code_body,
+			} => {
+				dest.write_all(&[PayloadTypeByte::SmartContract as u8])?;
+				contract_name.codec_serialize(dest)?;
+				write_u32_length(dest, code_body.len())?;
+				dest.write_all(code_body.as_bytes())
+			}
+			Self::ContractCall {
+				address,
+				contract_name,
+				function_name,
+				function_args,
+			} => {
+				dest.write_all(&[PayloadTypeByte::ContractCall as u8])?;
+				address.codec_serialize(dest)?;
+				contract_name.codec_serialize(dest)?;
+				write_clarity_name(dest, function_name)?;
+				write_u32_length(dest, function_args.len())?;
+
+				for arg in function_args {
+					arg.codec_serialize(dest)?;
+				}
+
+				Ok(())
+			}
+		}
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let payload_type =
+			read_byte_enum(data, PayloadTypeByte::from_repr, "payload type")?;
+
+		match payload_type {
+			PayloadTypeByte::TokenTransfer => Ok(Self::TokenTransfer {
+				recipient: PrincipalData::codec_deserialize(data)?,
+				amount: u64::codec_deserialize(data)?,
+				memo: read_array(data)?,
+			}),
+			PayloadTypeByte::SmartContract => {
+				let contract_name = ContractName::codec_deserialize(data)?;
+				let length = u32::from_be_bytes(read_array(data)?);
+
+				let mut code_body = String::new();
+				data.take(length as u64).read_to_string(&mut code_body)?;
+
+				if code_body.len() != length as usize {
+					return Err(io::ErrorKind::UnexpectedEof.into());
+				}
+
+				Ok(Self::SmartContract {
+					contract_name,
+					code_body,
+				})
+			}
+			PayloadTypeByte::ContractCall => {
+				let address = StacksAddress::codec_deserialize(data)?;
+				let contract_name = ContractName::codec_deserialize(data)?;
+				let function_name = read_clarity_name(data)?;
+				let arg_count = u32::from_be_bytes(read_array(data)?);
+				let function_args = (0..arg_count)
+					.map(|_| Value::codec_deserialize(data))
+					.collect::<io::Result<_>>()?;
+
+				Ok(Self::ContractCall {
+					address,
+					contract_name,
+					function_name,
+					function_args,
+				})
+			}
+		}
+	}
+}
+
+/// Stacks transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+	/// Transaction version
+	pub version: TransactionVersion,
+	/// Chain ID
+	pub chain_id: u32,
+	/// Authorization
+	pub auth: TransactionAuth,
+	/// Anchor mode
+	pub anchor_mode: AnchorMode,
+	/// Post condition mode
+	pub post_condition_mode: PostConditionMode,
+	/// Post conditions
+	pub post_conditions: Vec<PostCondition>,
+	/// Payload
+	pub payload: TransactionPayload,
+}
+
+impl Transaction {
+	/// Creates an unsigned transaction for the network that can be included in
+	/// any block and denies transfers not covered by post conditions
+	pub fn new(
+		network: Network,
+		auth: TransactionAuth,
+		payload: TransactionPayload,
+	) -> Self {
+		Self {
+			version: network.into(),
+			chain_id: chain_id(network),
+			auth,
+			anchor_mode: AnchorMode::Any,
+			post_condition_mode: PostConditionMode::Deny,
+			post_conditions: vec![],
+			payload,
+		}
+	}
+
+	/// Returns the transaction ID
+	pub fn txid(&self) -> Sha512_256Hasher {
+		Sha512_256Hasher::new(self.serialize_to_vec())
+	}
+
+	/// Returns the hash signed by the origin
+	pub fn presign_hash(&self) -> Sha512_256Hasher {
+		let mut cleared = self.clone();
+		cleared.auth.origin_mut().clear();
+
+		let origin = self.auth.origin();
+
+		let mut buffer = cleared.txid().as_ref().to_vec();
+		buffer.push(self.auth.auth_type());
+		buffer.extend_from_slice(&origin.fee.to_be_bytes());
+		buffer.extend_from_slice(&origin.nonce.to_be_bytes());
+
+		Sha512_256Hasher::new(buffer)
+	}
+
+	/// Signs the transaction as the origin
+	pub fn sign(&mut self, private_key: &PrivateKey) -> StacksResult<()> {
+		let secp = Secp256k1::new();
+
+		let public_key = private_key.public_key(&secp);
+
+		if Hash160Hasher::new(public_key.serialize())
+			!= self.auth.origin().signer
+		{
+			return Err(StacksError::InvalidArguments(
+				"Private key does not match the origin signer",
+			));
+		}
+
+		let signature =
+			RecoverableSignature::sign(private_key, self.presign_hash())?;
+
+		let origin = self.auth.origin_mut();
+		origin.key_encoding = PublicKeyEncoding::Compressed;
+		origin.signature = signature.to_vrs();
+
+		Ok(())
+	}
+}
+
+impl Codec for Transaction {
+	fn codec_serialize<W: io::Write>(&self, dest: &mut W) -> io::Result<()> {
+		dest.write_all(&[self.version as u8])?;
+		dest.write_all(&self.chain_id.to_be_bytes())?;
+		self.auth.codec_serialize(dest)?;
+		dest.write_all(&[self.anchor_mode as u8])?;
+		dest.write_all(&[self.post_condition_mode as u8])?;
+		write_u32_length(dest, self.post_conditions.len())?;
+
+		for post_condition in &self.post_conditions {
+			post_condition.codec_serialize(dest)?;
+		}
+
+		self.payload.codec_serialize(dest)
+	}
+
+	fn codec_deserialize<R: io::Read>(data: &mut R) -> io::Result<Self>
+	where
+		Self: Sized,
+	{
+		let version = read_byte_enum(
+			data,
+			TransactionVersion::from_repr,
+			"transaction version",
+		)?;
+		let chain_id = u32::from_be_bytes(read_array(data)?);
+		let auth = TransactionAuth::codec_deserialize(data)?;
+		let anchor_mode =
+			read_byte_enum(data, AnchorMode::from_repr, "anchor mode")?;
+		let post_condition_mode = read_byte_enum(
+			data,
+			PostConditionMode::from_repr,
+			"post condition mode",
+		)?;
+
+		let post_condition_count = u32::from_be_bytes(read_array(data)?);
+		let post_conditions = (0..post_condition_count)
+			.map(|_| PostCondition::codec_deserialize(data))
+			.collect::<io::Result<_>>()?;
+
+		let payload = TransactionPayload::codec_deserialize(data)?;
+
+		Ok(Self {
+			version,
+			chain_id,
+			auth,
+			anchor_mode,
+			post_condition_mode,
+			post_conditions,
+			payload,
+		})
+	}
+}
+
+fn invalid_data(message: String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8<R: io::Read>(data: &mut R) -> io::Result<u8> {
+	Ok(read_array::<_, 1>(data)?[0])
+}
+
+fn read_array<R: io::Read, const LENGTH: usize>(
+	data: &mut R,
+) -> io::Result<[u8; LENGTH]> {
+	let mut buffer = [0; LENGTH];
+	data.read_exact(&mut buffer)?;
+
+	Ok(buffer)
+}
+
+fn read_byte_enum<R: io::Read, T>(
+	data: &mut R,
+	from_repr: fn(u8) -> Option<T>,
+	name: &str,
+) -> io::Result<T> {
+	let byte = read_u8(data)?;
+
+	from_repr(byte)
+		.ok_or_else(|| invalid_data(format!("Invalid {}: {}", name, byte)))
+}
+
+fn read_condition_code<R: io::Read>(
+	data: &mut R,
+) -> io::Result<FungibleConditionCode> {
+	read_byte_enum(data, FungibleConditionCode::from_repr, "condition code")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::address::AddressVersion;
+
+	fn private_key() -> PrivateKey {
+		PrivateKey::from_slice(&[1; 32]).unwrap()
+	}
+
+	fn token_transfer() -> Transaction {
+		let public_key = private_key().public_key(&Secp256k1::new());
+		let recipient = StacksAddress::p2pkh(
+			AddressVersion::TestnetSingleSig,
+			&PrivateKey::from_slice(&[2; 32])
+				.unwrap()
+				.public_key(&Secp256k1::new()),
+		);
+
+		let mut tx = Transaction::new(
+			Network::Testnet,
+			TransactionAuth::Standard(SingleSigSpendingCondition::p2pkh(
+				&public_key,
+				3,
+				180,
+			)),
+			TransactionPayload::TokenTransfer {
+				recipient: recipient.clone().into(),
+				amount: 12345,
+				memo: [0; MEMO_LENGTH],
+			},
+		);
+
+		tx.post_conditions.push(PostCondition::Stx(
+			PostConditionPrincipal::Origin,
+			FungibleConditionCode::SentEq,
+			12345,
+		));
+		tx.post_conditions.push(PostCondition::Fungible(
+			PostConditionPrincipal::Standard(recipient.clone()),
+			AssetInfo {
+				address: recipient,
+				contract_name: ContractName::new("asset").unwrap(),
+				asset_name: "sbtc".to_string(),
+			},
+			FungibleConditionCode::SentLe,
+			1,
+		));
+
+		tx
+	}
+
+	#[test]
+	fn should_round_trip_transactions() {
+		let tx = token_transfer();
+
+		let contract_deploy = Transaction::new(
+			Network::Mainnet,
+			tx.auth.clone(),
+			TransactionPayload::SmartContract {
+				contract_name: ContractName::new("asset").unwrap(),
+				code_body: "(define-fungible-token sbtc)".to_string(),
+			},
+		);
+
+		let contract_call = Transaction::new(
+			Network::Mainnet,
+			tx.auth.clone(),
+			TransactionPayload::ContractCall {
+				address: StacksAddress::try_from(
+					"SPR4FMGJCD78NF4FRGPM621CW1KHNFEG0HSRDSPK",
+				)
+				.unwrap(),
+				contract_name: ContractName::new("asset").unwrap(),
+				function_name: "transfer".to_string(),
+				function_args: vec![
+					Value::UInt(100),
+					Value::some(Value::Buffer(vec![1, 2, 3])),
+				],
+			},
+		);
+
+		for tx in [tx, contract_deploy, contract_call] {
+			let bytes = tx.serialize_to_vec();
+
+			assert_eq!(Transaction::deserialize(&mut &bytes[..]).unwrap(), tx);
+		}
+	}
+
+	#[test]
+	fn should_sign_transaction() {
+		let mut tx = token_transfer();
+		let presign_hash = tx.presign_hash();
+
+		tx.sign(&private_key()).unwrap();
+
+		// Signing does not change what is signed
+		assert_eq!(tx.presign_hash(), presign_hash);
+
+		let signature =
+			RecoverableSignature::from_vrs(&tx.auth.origin().signature)
+				.unwrap();
+
+		assert_eq!(
+			signature.recover_public_key(presign_hash).unwrap(),
+			private_key().public_key(&Secp256k1::new())
+		);
+	}
+
+	#[test]
+	fn should_fail_to_sign_with_another_key() {
+		let mut tx = token_transfer();
+
+		assert!(tx.sign(&PrivateKey::from_slice(&[2; 32]).unwrap()).is_err());
+	}
+}
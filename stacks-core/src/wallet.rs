@@ -1,22 +1,28 @@
 //! Exposes tools to create and manage Stacks credentials.
 
-use std::str::FromStr;
-
-use bdk::{
-	bitcoin::{
-		secp256k1::Secp256k1,
-		util::bip32::{DerivationPath, ExtendedPrivKey},
-		Address as BitcoinAddress, AddressType as BitcoinAddressType,
-		Network as BitcoinNetwork,
+use std::{ops::Range, str::FromStr};
+
+use bdk::bitcoin::{
+	secp256k1::Secp256k1,
+	util::bip32::{
+		ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey,
 	},
-	keys::bip39::Mnemonic,
+	Address as BitcoinAddress, AddressType as BitcoinAddressType,
+	Network as BitcoinNetwork,
 };
+pub use bip39::{Language, Mnemonic};
 use rand::random;
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
 use crate::{
 	address::{AddressVersion, StacksAddress},
-	crypto::{wif::WIF, PrivateKey, PublicKey},
+	crypto::{
+		secret::{SecretBytes, PRIVATE_KEY_LENGTH},
+		wif::WIF,
+		PrivateKey, PublicKey,
+	},
 	Network, StacksError, StacksResult,
 };
 
@@ -28,6 +34,12 @@ pub fn stacks_derivation_path(index: u32) -> StacksResult<DerivationPath> {
 	))?)
 }
 
+/// Computes the Stacks derivation path of the external chain, the parent of
+/// all keys returned by [`stacks_derivation_path`]
+pub fn stacks_chain_derivation_path() -> StacksResult<DerivationPath> {
+	Ok(DerivationPath::from_str("m/44'/5757'/0'/0")?)
+}
+
 /// Computes Bitcoin derivation paths
 pub fn bitcoin_derivation_path(
 	network: BitcoinNetwork,
@@ -57,31 +69,98 @@ pub fn bitcoin_derivation_path(
 	Ok(DerivationPath::from_str(&path)?)
 }
 
+/// Length of an encoded extended private key
+pub const EXTENDED_KEY_LENGTH: usize = 78;
+
 /// Derives a key from a master key and a derivation path
 pub fn derive_key(
 	master_key: ExtendedPrivKey,
 	path: DerivationPath,
-) -> ExtendedPrivKey {
-	master_key.derive_priv(&Secp256k1::new(), &path).unwrap()
+) -> StacksResult<ExtendedPrivKey> {
+	Ok(master_key.derive_priv(&Secp256k1::new(), &path)?)
+}
+
+/// Derives the private key at the derivation path, as secret bytes
+fn derive_secret(
+	master_key: ExtendedPrivKey,
+	path: DerivationPath,
+) -> StacksResult<SecretBytes<PRIVATE_KEY_LENGTH>> {
+	Ok(derive_key(master_key, path)?.private_key.into())
+}
+
+/// Deserialize an encoded extended private key, rejecting invalid keys when
+/// loaded rather than when used
+fn deserialize_master_key<'de, D>(
+	deserializer: D,
+) -> Result<SecretBytes<EXTENDED_KEY_LENGTH>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let secret = SecretBytes::deserialize(deserializer)?;
+	ExtendedPrivKey::decode(secret.as_bytes()).map_err(D::Error::custom)?;
+
+	Ok(secret)
+}
+
+/// Maps the indices in parallel when the `parallel` feature is enabled
+fn map_indices<T, F>(indices: Range<u32>, f: F) -> StacksResult<Vec<T>>
+where
+	T: Send,
+	F: Fn(u32) -> StacksResult<T> + Send + Sync,
+{
+	#[cfg(feature = "parallel")]
+	let indices = indices.into_par_iter();
+
+	indices.map(f).collect()
+}
+
+fn single_sig_version(network: Network) -> AddressVersion {
+	match network {
+		Network::Mainnet => AddressVersion::MainnetSingleSig,
+		Network::Testnet => AddressVersion::TestnetSingleSig,
+	}
 }
 
 /// Wallet of credentials
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Wallet {
-	master_key: ExtendedPrivKey,
+	/// Encoded extended private key, wiped on drop
+	#[serde(deserialize_with = "deserialize_master_key")]
+	master_key: SecretBytes<EXTENDED_KEY_LENGTH>,
 	mnemonic: Mnemonic,
 }
 
 impl Wallet {
-	/// Creates a wallet from the network, mnemonic, and optional passphrase
+	/// Creates a wallet from the mnemonic
 	pub fn new(mnemonic: impl AsRef<str>) -> StacksResult<Self> {
-		let mnemonic = Mnemonic::from_str(mnemonic.as_ref())?;
+		Self::new_with_passphrase(mnemonic, "")
+	}
+
+	/// Creates a wallet from the mnemonic and BIP39 passphrase
+	pub fn new_with_passphrase(
+		mnemonic: impl AsRef<str>,
+		passphrase: impl AsRef<str>,
+	) -> StacksResult<Self> {
+		Self::from_mnemonic(
+			Mnemonic::from_str(mnemonic.as_ref())?,
+			passphrase.as_ref(),
+		)
+	}
+
+	fn from_mnemonic(
+		mnemonic: Mnemonic,
+		passphrase: &str,
+	) -> StacksResult<Self> {
+		let seed = SecretBytes::new(mnemonic.to_seed(passphrase));
 
 		// Bitcoin network is irrelevant for extended private keys
-		let master_key = ExtendedPrivKey::new_master(
-			BitcoinNetwork::Bitcoin,
-			&mnemonic.to_seed(""),
-		)?;
+		let master_key = SecretBytes::new(
+			ExtendedPrivKey::new_master(
+				BitcoinNetwork::Bitcoin,
+				seed.as_bytes(),
+			)?
+			.encode(),
+		);
 
 		Ok(Self {
 			master_key,
@@ -89,12 +168,31 @@ impl Wallet {
 		})
 	}
 
-	/// Creates a random wallet
+	/// Creates a random wallet with a 24 word English mnemonic
 	pub fn random() -> StacksResult<Self> {
-		let entropy: [u8; 32] = random();
-		let mnemonic = Mnemonic::from_entropy(&entropy)?;
+		Self::generate(24, Language::English)
+	}
+
+	/// Creates a random wallet with a mnemonic of 12, 15, 18, 21 or 24 words
+	/// in the language
+	pub fn generate(
+		word_count: usize,
+		language: Language,
+	) -> StacksResult<Self> {
+		if !matches!(word_count, 12 | 15 | 18 | 21 | 24) {
+			return Err(StacksError::InvalidArguments(
+				"Mnemonic word count should be 12, 15, 18, 21 or 24",
+			));
+		}
+
+		// Every 3 words encode 4 bytes of entropy
+		let entropy = SecretBytes::new(random::<[u8; 32]>());
+		let mnemonic = Mnemonic::from_entropy_in(
+			language,
+			&entropy.as_bytes()[..word_count / 3 * 4],
+		)?;
 
-		Self::new(mnemonic.to_string())
+		Self::from_mnemonic(mnemonic, "")
 	}
 
 	/// Returns the mnemonic of the wallet
@@ -103,13 +201,47 @@ impl Wallet {
 	}
 
 	/// Returns the master key of the wallet
-	pub fn master_key(&self) -> PrivateKey {
-		self.master_key.private_key
+	pub fn master_key(&self) -> StacksResult<PrivateKey> {
+		Ok(self.extended_master_key()?.private_key)
 	}
 
 	/// Returns the WIF of the wallet
-	pub fn wif(&self, network: Network) -> WIF {
-		WIF::new(network, self.master_key())
+	pub fn wif(&self, network: Network) -> StacksResult<WIF> {
+		Ok(WIF::new(network, self.master_key()?))
+	}
+
+	/// Derives the extended private key at the derivation path, which may mix
+	/// hardened and non-hardened children
+	pub fn derive(
+		&self,
+		path: &DerivationPath,
+	) -> StacksResult<ExtendedPrivKey> {
+		derive_key(self.extended_master_key()?, path.clone())
+	}
+
+	fn extended_master_key(&self) -> StacksResult<ExtendedPrivKey> {
+		Ok(ExtendedPrivKey::decode(self.master_key.as_bytes())?)
+	}
+
+	/// Returns the extended public key at the derivation path, usually an
+	/// account level path such as `m/44'/5757'/0'`
+	pub fn xpub(&self, path: &DerivationPath) -> StacksResult<ExtendedPubKey> {
+		Ok(ExtendedPubKey::from_priv(
+			&Secp256k1::new(),
+			&self.derive(path)?,
+		))
+	}
+
+	/// Returns watch-only credentials that derive the same Stacks addresses as
+	/// [`Wallet::credentials`] without access to the private keys
+	pub fn watch_only_credentials(
+		&self,
+		network: Network,
+	) -> StacksResult<WatchOnlyCredentials> {
+		Ok(WatchOnlyCredentials::new(
+			network,
+			self.xpub(&stacks_chain_derivation_path()?)?,
+		))
 	}
 
 	/// Returns the credentials at the given index
@@ -118,7 +250,33 @@ impl Wallet {
 		network: Network,
 		index: u32,
 	) -> StacksResult<Credentials> {
-		Credentials::new(network, self.master_key, index)
+		Credentials::new(network, self.extended_master_key()?, index)
+	}
+
+	/// Returns the Stacks P2PKH addresses of the credentials at the given
+	/// indices
+	pub fn derive_addresses(
+		&self,
+		network: Network,
+		indices: Range<u32>,
+	) -> StacksResult<Vec<StacksAddress>> {
+		self.watch_only_credentials(network)?.addresses(indices)
+	}
+
+	/// Returns the Bitcoin addresses of the given type of the Bitcoin
+	/// credentials at the given indices
+	pub fn derive_bitcoin_addresses(
+		&self,
+		network: BitcoinNetwork,
+		kind: BitcoinAddressType,
+		indices: Range<u32>,
+	) -> StacksResult<Vec<BitcoinAddress>> {
+		BitcoinCredentials::derive_addresses(
+			network,
+			self.extended_master_key()?,
+			kind,
+			indices,
+		)
 	}
 
 	/// Returns the Bitcoin credentials at the given index
@@ -127,15 +285,25 @@ impl Wallet {
 		network: BitcoinNetwork,
 		index: u32,
 	) -> StacksResult<BitcoinCredentials> {
-		BitcoinCredentials::new(network, self.master_key, index)
+		BitcoinCredentials::new(network, self.extended_master_key()?, index)
 	}
 }
 
 /// Credentials that can be used to sign transactions
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "CredentialsData", into = "CredentialsData")]
 pub struct Credentials {
 	network: Network,
-	private_key: PrivateKey,
+	private_key: SecretBytes<PRIVATE_KEY_LENGTH>,
+	/// Public key of the private key, computed once the key is validated
+	public_key: PublicKey,
+}
+
+/// Serialized form of [`Credentials`]
+#[derive(Clone, Serialize, Deserialize)]
+struct CredentialsData {
+	network: Network,
+	private_key: SecretBytes<PRIVATE_KEY_LENGTH>,
 }
 
 impl Credentials {
@@ -145,14 +313,25 @@ impl Credentials {
 		master_key: ExtendedPrivKey,
 		index: u32,
 	) -> StacksResult<Self> {
-		let private_key =
-			derive_key(master_key, stacks_derivation_path(index)?)
-				.to_priv()
-				.inner;
+		Self::from_secret(
+			network,
+			derive_secret(master_key, stacks_derivation_path(index)?)?,
+		)
+	}
+
+	/// Creates credentials from the network and the bytes of the private key,
+	/// which fails if they are not a valid key
+	fn from_secret(
+		network: Network,
+		private_key: SecretBytes<PRIVATE_KEY_LENGTH>,
+	) -> StacksResult<Self> {
+		let public_key =
+			private_key.private_key()?.public_key(&Secp256k1::new());
 
 		Ok(Self {
 			network,
 			private_key,
+			public_key,
 		})
 	}
 
@@ -162,38 +341,116 @@ impl Credentials {
 	}
 
 	/// Returns the private key
-	pub fn private_key(&self) -> PrivateKey {
-		self.private_key
+	pub fn private_key(&self) -> StacksResult<PrivateKey> {
+		self.private_key.private_key()
 	}
 
 	/// Returns the public key
 	pub fn public_key(&self) -> PublicKey {
-		self.private_key.public_key(&Secp256k1::new())
+		self.public_key
 	}
 
 	/// Returns the Stacks P2PKH address
 	pub fn address(&self) -> StacksAddress {
-		let version = match self.network {
-			Network::Mainnet => AddressVersion::MainnetSingleSig,
-			Network::Testnet => AddressVersion::TestnetSingleSig,
-		};
-
-		StacksAddress::p2pkh(version, &self.public_key())
+		StacksAddress::p2pkh(
+			single_sig_version(self.network),
+			&self.public_key(),
+		)
 	}
 
 	/// Returns the WIF
-	pub fn wif(&self) -> WIF {
-		WIF::new(self.network(), self.private_key())
+	pub fn wif(&self) -> StacksResult<WIF> {
+		Ok(WIF::new(self.network(), self.private_key()?))
+	}
+}
+
+impl TryFrom<CredentialsData> for Credentials {
+	type Error = StacksError;
+
+	fn try_from(data: CredentialsData) -> Result<Self, Self::Error> {
+		Self::from_secret(data.network, data.private_key)
+	}
+}
+
+impl From<Credentials> for CredentialsData {
+	fn from(credentials: Credentials) -> Self {
+		Self {
+			network: credentials.network,
+			private_key: credentials.private_key,
+		}
+	}
+}
+
+/// Credentials that derive Stacks public keys and addresses from an extended
+/// public key, so the master seed can be kept offline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchOnlyCredentials {
+	network: Network,
+	xpub: ExtendedPubKey,
+}
+
+impl WatchOnlyCredentials {
+	/// Creates watch-only credentials from the network and the extended public
+	/// key of the parent of the derived keys
+	pub fn new(network: Network, xpub: ExtendedPubKey) -> Self {
+		Self { network, xpub }
+	}
+
+	/// Returns the Stacks network
+	pub fn network(&self) -> Network {
+		self.network
+	}
+
+	/// Returns the extended public key
+	pub fn xpub(&self) -> ExtendedPubKey {
+		self.xpub
+	}
+
+	/// Returns the public key at the given non-hardened index
+	pub fn public_key(&self, index: u32) -> StacksResult<PublicKey> {
+		let child = ChildNumber::from_normal_idx(index)?;
+
+		Ok(self.xpub.ckd_pub(&Secp256k1::new(), child)?.public_key)
+	}
+
+	/// Returns the Stacks P2PKH address at the given non-hardened index
+	pub fn address(&self, index: u32) -> StacksResult<StacksAddress> {
+		Ok(StacksAddress::p2pkh(
+			single_sig_version(self.network),
+			&self.public_key(index)?,
+		))
+	}
+
+	/// Returns the Stacks P2PKH addresses at the given non-hardened indices
+	pub fn addresses(
+		&self,
+		indices: Range<u32>,
+	) -> StacksResult<Vec<StacksAddress>> {
+		map_indices(indices, |index| self.address(index))
 	}
 }
 
 /// Bitcoin Credentials that can be used to sign transactions
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "BitcoinCredentialsData", into = "BitcoinCredentialsData")]
 pub struct BitcoinCredentials {
 	network: BitcoinNetwork,
-	private_key_p2pkh: PrivateKey,
-	private_key_p2wpkh: PrivateKey,
-	private_key_p2tr: PrivateKey,
+	private_key_p2pkh: SecretBytes<PRIVATE_KEY_LENGTH>,
+	private_key_p2wpkh: SecretBytes<PRIVATE_KEY_LENGTH>,
+	private_key_p2tr: SecretBytes<PRIVATE_KEY_LENGTH>,
+	/// Public keys of the private keys, computed once the keys are validated
+	public_key_p2pkh: PublicKey,
+	public_key_p2wpkh: PublicKey,
+	public_key_p2tr: PublicKey,
+}
+
+/// Serialized form of [`BitcoinCredentials`]
+#[derive(Clone, Serialize, Deserialize)]
+struct BitcoinCredentialsData {
+	network: BitcoinNetwork,
+	private_key_p2pkh: SecretBytes<PRIVATE_KEY_LENGTH>,
+	private_key_p2wpkh: SecretBytes<PRIVATE_KEY_LENGTH>,
+	private_key_p2tr: SecretBytes<PRIVATE_KEY_LENGTH>,
 }
 
 impl BitcoinCredentials {
@@ -203,36 +460,54 @@ impl BitcoinCredentials {
 		master_key: ExtendedPrivKey,
 		index: u32,
 	) -> StacksResult<Self> {
-		let private_key_p2pkh = derive_key(
-			master_key,
-			bitcoin_derivation_path(network, BitcoinAddressType::P2pkh, index)?,
-		)
-		.to_priv()
-		.inner;
-
-		let private_key_p2wpkh = derive_key(
-			master_key,
-			bitcoin_derivation_path(
-				network,
-				BitcoinAddressType::P2wpkh,
-				index,
-			)?,
-		)
-		.to_priv()
-		.inner;
-
-		let private_key_p2tr = derive_key(
-			master_key,
-			bitcoin_derivation_path(network, BitcoinAddressType::P2tr, index)?,
-		)
-		.to_priv()
-		.inner;
+		let derive = |kind| {
+			derive_secret(
+				master_key,
+				bitcoin_derivation_path(network, kind, index)?,
+			)
+		};
 
-		Ok(Self {
+		BitcoinCredentialsData {
 			network,
-			private_key_p2pkh,
-			private_key_p2wpkh,
-			private_key_p2tr,
+			private_key_p2pkh: derive(BitcoinAddressType::P2pkh)?,
+			private_key_p2wpkh: derive(BitcoinAddressType::P2wpkh)?,
+			private_key_p2tr: derive(BitcoinAddressType::P2tr)?,
+		}
+		.try_into()
+	}
+
+	/// Returns the Bitcoin addresses of the given type of the credentials at
+	/// the given indices
+	pub fn derive_addresses(
+		network: BitcoinNetwork,
+		master_key: ExtendedPrivKey,
+		kind: BitcoinAddressType,
+		indices: Range<u32>,
+	) -> StacksResult<Vec<BitcoinAddress>> {
+		let secp = Secp256k1::new();
+
+		map_indices(indices, |index| {
+			let path = bitcoin_derivation_path(network, kind, index)?;
+			let public_key = master_key
+				.derive_priv(&secp, &path)?
+				.to_priv()
+				.public_key(&secp);
+
+			match kind {
+				BitcoinAddressType::P2pkh => {
+					Ok(BitcoinAddress::p2pkh(&public_key, network))
+				}
+				BitcoinAddressType::P2wpkh => {
+					Ok(BitcoinAddress::p2wpkh(&public_key, network)
+						.expect("Derived public keys are compressed"))
+				}
+				_ => Ok(BitcoinAddress::p2tr(
+					&secp,
+					public_key.inner.x_only_public_key().0,
+					None,
+					network,
+				)),
+			}
 		})
 	}
 
@@ -242,33 +517,33 @@ impl BitcoinCredentials {
 	}
 
 	/// Returns the Bitcoin P2PKH private key
-	pub fn private_key_p2pkh(&self) -> PrivateKey {
-		self.private_key_p2pkh
+	pub fn private_key_p2pkh(&self) -> StacksResult<PrivateKey> {
+		self.private_key_p2pkh.private_key()
 	}
 
 	/// Returns the Bitcoin P22PKH private key
-	pub fn private_key_p2wpkh(&self) -> PrivateKey {
-		self.private_key_p2wpkh
+	pub fn private_key_p2wpkh(&self) -> StacksResult<PrivateKey> {
+		self.private_key_p2wpkh.private_key()
 	}
 
 	/// Returns the Bitcoin P2TR private key
-	pub fn private_key_p2tr(&self) -> PrivateKey {
-		self.private_key_p2tr
+	pub fn private_key_p2tr(&self) -> StacksResult<PrivateKey> {
+		self.private_key_p2tr.private_key()
 	}
 
 	/// Returns the Bitcoin P2PKH public key
 	pub fn public_key_p2pkh(&self) -> PublicKey {
-		self.private_key_p2pkh.public_key(&Secp256k1::new())
+		self.public_key_p2pkh
 	}
 
 	/// Returns the Bitcoin P2WPKH public key
 	pub fn public_key_p2wpkh(&self) -> PublicKey {
-		self.private_key_p2wpkh.public_key(&Secp256k1::new())
+		self.public_key_p2wpkh
 	}
 
 	/// Returns the Bitcoin P2TR public key
 	pub fn public_key_p2tr(&self) -> PublicKey {
-		self.private_key_p2tr.public_key(&Secp256k1::new())
+		self.public_key_p2tr
 	}
 
 	/// Returns the Bitcoin P2PKH address
@@ -299,17 +574,256 @@ impl BitcoinCredentials {
 	}
 
 	/// Returns the WIF for P2PKH
-	pub fn wif_p2pkh(&self) -> WIF {
-		WIF::new(self.network().into(), self.private_key_p2pkh())
+	pub fn wif_p2pkh(&self) -> StacksResult<WIF> {
+		Ok(WIF::new(self.network().into(), self.private_key_p2pkh()?))
 	}
 
 	/// Returns the WIF for P2WPKH
-	pub fn wif_p2wpkh(&self) -> WIF {
-		WIF::new(self.network().into(), self.private_key_p2wpkh())
+	pub fn wif_p2wpkh(&self) -> StacksResult<WIF> {
+		Ok(WIF::new(self.network().into(), self.private_key_p2wpkh()?))
 	}
 
 	/// Returns the WIF for P2TR
-	pub fn wif_p2tr(&self) -> WIF {
-		WIF::new(self.network().into(), self.private_key_p2tr())
+	pub fn wif_p2tr(&self) -> StacksResult<WIF> {
+		Ok(WIF::new(self.network().into(), self.private_key_p2tr()?))
+	}
+}
+
+impl TryFrom<BitcoinCredentialsData> for BitcoinCredentials {
+	type Error = StacksError;
+
+	fn try_from(data: BitcoinCredentialsData) -> Result<Self, Self::Error> {
+		let secp = Secp256k1::new();
+
+		Ok(Self {
+			network: data.network,
+			public_key_p2pkh: data
+				.private_key_p2pkh
+				.private_key()?
+				.public_key(&secp),
+			public_key_p2wpkh: data
+				.private_key_p2wpkh
+				.private_key()?
+				.public_key(&secp),
+			public_key_p2tr: data
+				.private_key_p2tr
+				.private_key()?
+				.public_key(&secp),
+			private_key_p2pkh: data.private_key_p2pkh,
+			private_key_p2wpkh: data.private_key_p2wpkh,
+			private_key_p2tr: data.private_key_p2tr,
+		})
+	}
+}
+
+impl From<BitcoinCredentials> for BitcoinCredentialsData {
+	fn from(credentials: BitcoinCredentials) -> Self {
+		Self {
+			network: credentials.network,
+			private_key_p2pkh: credentials.private_key_p2pkh,
+			private_key_p2wpkh: credentials.private_key_p2wpkh,
+			private_key_p2tr: credentials.private_key_p2tr,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+	#[test]
+	fn should_derive_paths_with_hardened_and_normal_children() {
+		let wallet = Wallet::new(MNEMONIC).unwrap();
+
+		assert_eq!(
+			wallet
+				.derive(&stacks_derivation_path(3).unwrap())
+				.unwrap()
+				.private_key,
+			wallet
+				.credentials(Network::Testnet, 3)
+				.unwrap()
+				.private_key()
+				.unwrap()
+		);
+
+		// BIP32 test vector for the account level key of this mnemonic
+		assert_eq!(
+			wallet
+				.xpub(&DerivationPath::from_str("m/44'/0'/0'").unwrap())
+				.unwrap()
+				.to_string(),
+			"xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj"
+		);
+	}
+
+	#[test]
+	fn should_generate_mnemonics() {
+		for word_count in [12, 15, 18, 21, 24] {
+			for language in [Language::English, Language::French] {
+				let wallet = Wallet::generate(word_count, language).unwrap();
+
+				assert_eq!(wallet.mnemonic().word_count(), word_count);
+				assert_eq!(wallet.mnemonic().language(), language);
+			}
+		}
+
+		assert!(Wallet::generate(13, Language::English).is_err());
+	}
+
+	#[test]
+	fn should_derive_address_batches() {
+		let wallet = Wallet::new(MNEMONIC).unwrap();
+
+		let addresses =
+			wallet.derive_addresses(Network::Testnet, 5..8).unwrap();
+
+		assert_eq!(addresses.len(), 3);
+
+		for (address, index) in addresses.iter().zip(5..) {
+			assert_eq!(
+				*address,
+				wallet
+					.credentials(Network::Testnet, index)
+					.unwrap()
+					.address()
+			);
+		}
+
+		let bitcoin_addresses = wallet
+			.derive_bitcoin_addresses(
+				BitcoinNetwork::Testnet,
+				BitcoinAddressType::P2tr,
+				0..2,
+			)
+			.unwrap();
+
+		for (address, index) in bitcoin_addresses.iter().zip(0..) {
+			assert_eq!(
+				*address,
+				wallet
+					.bitcoin_credentials(BitcoinNetwork::Testnet, index)
+					.unwrap()
+					.address_p2tr()
+			);
+		}
+
+		assert!(wallet
+			.derive_bitcoin_addresses(
+				BitcoinNetwork::Testnet,
+				BitcoinAddressType::P2sh,
+				0..1,
+			)
+			.is_err());
+	}
+
+	#[test]
+	fn should_use_passphrase() {
+		// BIP39 reference test vector
+		let wallet = Wallet::new_with_passphrase(MNEMONIC, "TREZOR").unwrap();
+
+		assert_eq!(
+			wallet.derive(&DerivationPath::master()).unwrap().to_string(),
+			"xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+		);
+
+		let without_passphrase = Wallet::new(MNEMONIC).unwrap();
+
+		assert_ne!(
+			wallet.credentials(Network::Mainnet, 0).unwrap().address(),
+			without_passphrase
+				.credentials(Network::Mainnet, 0)
+				.unwrap()
+				.address()
+		);
+		assert_ne!(
+			wallet
+				.bitcoin_credentials(BitcoinNetwork::Bitcoin, 0)
+				.unwrap()
+				.address_p2wpkh(),
+			without_passphrase
+				.bitcoin_credentials(BitcoinNetwork::Bitcoin, 0)
+				.unwrap()
+				.address_p2wpkh()
+		);
+	}
+
+	#[test]
+	fn should_derive_watch_only_addresses() {
+		let wallet = Wallet::new(MNEMONIC).unwrap();
+		let watch_only =
+			wallet.watch_only_credentials(Network::Mainnet).unwrap();
+
+		for index in 0..3 {
+			assert_eq!(
+				watch_only.address(index).unwrap(),
+				wallet
+					.credentials(Network::Mainnet, index)
+					.unwrap()
+					.address()
+			);
+		}
+
+		assert!(watch_only.address(1 << 31).is_err());
+	}
+
+	#[test]
+	fn should_round_trip_credentials() {
+		let wallet = Wallet::new(MNEMONIC).unwrap();
+		let credentials = wallet.credentials(Network::Testnet, 0).unwrap();
+		let bitcoin_credentials = wallet
+			.bitcoin_credentials(BitcoinNetwork::Testnet, 0)
+			.unwrap();
+
+		let json = serde_json::to_string(&credentials).unwrap();
+		let decoded: Credentials = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(decoded.address(), credentials.address());
+		assert_eq!(
+			decoded.private_key().unwrap(),
+			credentials.private_key().unwrap()
+		);
+
+		let json = serde_json::to_string(&bitcoin_credentials).unwrap();
+		let decoded: BitcoinCredentials = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(decoded.address_p2tr(), bitcoin_credentials.address_p2tr());
+
+		let json = serde_json::to_string(&wallet).unwrap();
+		let decoded: Wallet = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(decoded.master_key().unwrap(), wallet.master_key().unwrap());
+	}
+
+	#[test]
+	fn should_reject_zero_private_keys() {
+		let zero = "00".repeat(PRIVATE_KEY_LENGTH);
+		let valid = "01".repeat(PRIVATE_KEY_LENGTH);
+
+		assert!(serde_json::from_str::<Credentials>(&format!(
+			r#"{{"network":"testnet","private_key":"{}"}}"#,
+			zero
+		))
+		.is_err());
+		assert!(serde_json::from_str::<Credentials>(&format!(
+			r#"{{"network":"testnet","private_key":"{}"}}"#,
+			valid
+		))
+		.is_ok());
+
+		assert!(serde_json::from_str::<BitcoinCredentials>(&format!(
+			r#"{{"network":"testnet","private_key_p2pkh":"{}","private_key_p2wpkh":"{}","private_key_p2tr":"{}"}}"#,
+			valid, zero, valid
+		))
+		.is_err());
+
+		assert!(serde_json::from_str::<Wallet>(&format!(
+			r#"{{"master_key":"{}","mnemonic":"{}"}}"#,
+			"00".repeat(EXTENDED_KEY_LENGTH),
+			MNEMONIC
+		))
+		.is_err());
 	}
 }